@@ -213,12 +213,12 @@ class GeekCraftViewer {
             const response = await fetch(`${this.apiUrl}/api/zones`);
             const data = await response.json();
             
-            if (data.success && data.zone_ids && data.zone_ids.length > 0) {
-                this.zoneList = data.zone_ids;
-                this.log(`✓ Found ${data.zone_ids.length} zone(s)`, 'success');
+            if (data.items && data.items.length > 0) {
+                this.zoneList = data.items;
+                this.log(`✓ Found ${data.total} zone(s)`, 'success');
                 
                 // Load the first zone automatically
-                await this.loadZone(data.zone_ids[0]);
+                await this.loadZone(data.items[0]);
             } else {
                 this.log('No zones found. Generate a zone first.', 'info');
             }
@@ -368,6 +368,14 @@ class GeekCraftViewer {
             case 'error':
                 this.log(`Server error: ${message.message}`, 'error');
                 break;
+
+            case 'scriptError': {
+                // A new error raised by your bot (repeats are only counted in /api/gamestate)
+                const error = message.error;
+                const where = error.module ? ` (${error.module}:${error.line}${error.column ? ':' + error.column : ''})` : '';
+                this.log(`Script error at tick ${error.tick}: ${error.message}${where}`, 'error');
+                break;
+            }
             
             default:
                 console.log('Unhandled message:', message);
@@ -462,12 +470,13 @@ class GeekCraftViewer {
         const size = tiles.length;
         let plains = 0, swamps = 0, obstacles = 0;
         
+        // Each row is a string with one surface code per tile
         for (let row of tiles) {
-            for (let tile of row) {
-                switch (tile.surface_type) {
-                    case 'Plain': plains++; break;
-                    case 'Swamp': swamps++; break;
-                    case 'Obstacle': obstacles++; break;
+            for (let code of row) {
+                switch (code) {
+                    case 'P': plains++; break;
+                    case 'S': swamps++; break;
+                    case 'O': obstacles++; break;
                 }
             }
         }
@@ -612,20 +621,21 @@ class GeekCraftViewer {
         const tiles = this.currentZone.tiles;
         
         // Define colors for each terrain type
+        // (rows are strings of surface codes: P Plain, S Swamp, W Water, O Obstacle)
         const terrainColors = {
-            'Plain': '#7cb342',      // Green for plains
-            'Swamp': '#5c6bc0',      // Blue for swamp
-            'Obstacle': '#78909c'    // Gray for obstacles
+            'P': '#7cb342',      // Green for plains
+            'S': '#5c6bc0',      // Blue for swamp
+            'W': '#1e88e5',      // Bright blue for water
+            'O': '#78909c'       // Gray for obstacles
         };
         
         // Render each tile
         for (let row = 0; row < tiles.length; row++) {
             for (let col = 0; col < tiles[row].length; col++) {
-                const tile = tiles[row][col];
-                const surfaceType = tile.surface_type;
+                const surfaceCode = tiles[row][col];
                 
                 // Get color for this terrain type
-                const color = terrainColors[surfaceType] || '#333';
+                const color = terrainColors[surfaceCode] || '#333';
                 
                 // Draw tile
                 this.ctx.fillStyle = color;
@@ -76,16 +76,17 @@ function analyzeZone(zone) {
   let swampCount = 0;
   let obstacleCount = 0;
   
+  // Each row is a string with one surface code per tile
   for (const row of zone.tiles) {
-    for (const tile of row) {
-      switch (tile.surface_type) {
-        case 'Plain':
+    for (const code of row) {
+      switch (code) {
+        case 'P':
           plainCount++;
           break;
-        case 'Swamp':
+        case 'S':
           swampCount++;
           break;
-        case 'Obstacle':
+        case 'O':
           obstacleCount++;
           break;
       }
@@ -111,7 +112,7 @@ function analyzeZone(zone) {
  */
 function visualizeZone(zone) {
   console.log('\n=== Zone Visualization ===');
-  console.log('Legend: . = Plain, ~ = Swamp, # = Obstacle, E = Exit\n');
+  console.log('Legend: . = Plain, ~ = Swamp, = = Water, # = Obstacle, E = Exit\n');
   
   // Create a map of exit positions
   const exitMap = new Map();
@@ -130,15 +131,17 @@ function visualizeZone(zone) {
       if (exitMap.has(`${x},${y}`)) {
         row += 'E';
       } else {
-        const tile = zone.tiles[y][x];
-        switch (tile.surface_type) {
-          case 'Plain':
+        switch (zone.tiles[y][x]) {
+          case 'P':
             row += '.';
             break;
-          case 'Swamp':
+          case 'S':
             row += '~';
             break;
-          case 'Obstacle':
+          case 'W':
+            row += '=';
+            break;
+          case 'O':
             row += '#';
             break;
         }
@@ -187,7 +190,7 @@ async function main() {
     // Step 6: List all zones
     console.log('Step 6: Listing all zones...');
     const listResponse = await listZones();
-    console.log(`Total zones in world: ${listResponse.zone_ids.length}`);
+    console.log(`Total zones in world: ${listResponse.total}`);
     console.log('');
 
     console.log('=== Example completed successfully! ===');
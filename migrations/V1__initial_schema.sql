@@ -0,0 +1,26 @@
+-- Zones, portals and zone assignments, as created before migrations were introduced
+-- (IF NOT EXISTS keeps this a no-op on those databases)
+CREATE TABLE IF NOT EXISTS zones (
+    id TEXT PRIMARY KEY,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    tiles TEXT NOT NULL,
+    exits TEXT NOT NULL,
+    entities TEXT NOT NULL,
+    resources TEXT NOT NULL,
+    owner TEXT,
+    updated_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS portals (
+    id TEXT PRIMARY KEY,
+    from_zone_id TEXT NOT NULL,
+    from_x INTEGER NOT NULL,
+    from_y INTEGER NOT NULL,
+    to_zone_id TEXT NOT NULL,
+    to_x INTEGER NOT NULL,
+    to_y INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS zone_assignments (
+    user_id INTEGER PRIMARY KEY,
+    zone_id TEXT NOT NULL
+);
@@ -0,0 +1,80 @@
+//! Achievements module
+//!
+//! Achievements are unlocked once per user when their [`PlayerStats`] meet the
+//! achievement's condition. Unlocked achievement IDs are stored in the auth database.
+
+use serde::{Deserialize, Serialize};
+
+/// What a player must do to unlock an achievement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AchievementCondition {
+    /// Keep a bot running until this tick of a match
+    ReachTick(u64),
+    /// Eliminate this many enemy bots in a match
+    EliminateEnemies(u32),
+    /// Collect this many resources (of any type) in a match
+    CollectResources(u32),
+    /// Win this many matches in total
+    WinMatches(u32),
+}
+
+impl AchievementCondition {
+    /// Whether the stats meet the condition
+    pub fn is_met(&self, stats: &PlayerStats) -> bool {
+        match *self {
+            AchievementCondition::ReachTick(tick) => stats.highest_tick >= tick,
+            AchievementCondition::EliminateEnemies(count) => stats.enemies_eliminated >= count,
+            AchievementCondition::CollectResources(amount) => stats.resources_collected >= amount,
+            AchievementCondition::WinMatches(count) => stats.matches_won >= count,
+        }
+    }
+}
+
+/// An achievement players can unlock
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Achievement {
+    /// Unique achievement identifier
+    pub id: String,
+    /// Display name
+    pub name: String,
+    /// What the player did to earn it
+    pub description: String,
+    /// Unlock condition
+    pub condition: AchievementCondition,
+}
+
+/// A player's progress, checked against achievement conditions
+///
+/// Fields left at zero are not checked, so callers only fill in what they know.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerStats {
+    /// Last tick the player's bot ran without errors in the current match
+    pub highest_tick: u64,
+    /// Enemy bots eliminated in the current match
+    pub enemies_eliminated: u32,
+    /// Resources collected in the current match
+    pub resources_collected: u32,
+    /// Matches won in total
+    pub matches_won: u32,
+    /// Times the player lost every building and unit in the current match
+    pub defeats: u32,
+}
+
+/// Every achievement in the game
+pub fn all_achievements() -> Vec<Achievement> {
+    let achievement = |id: &str, name: &str, description: &str, condition| Achievement {
+        id: id.to_string(),
+        name: name.to_string(),
+        description: description.to_string(),
+        condition,
+    };
+
+    vec![
+        achievement("first_steps", "First Steps", "Keep a bot running for 10 ticks", AchievementCondition::ReachTick(10)),
+        achievement("marathon", "Marathon", "Keep a bot running for 1000 ticks", AchievementCondition::ReachTick(1000)),
+        achievement("first_blood", "First Blood", "Eliminate an enemy bot", AchievementCondition::EliminateEnemies(1)),
+        achievement("prospector", "Prospector", "Collect 500 resources in a match", AchievementCondition::CollectResources(500)),
+        achievement("first_victory", "First Victory", "Win a match", AchievementCondition::WinMatches(1)),
+        achievement("veteran", "Veteran", "Win 10 matches", AchievementCondition::WinMatches(10)),
+    ]
+}
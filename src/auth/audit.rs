@@ -0,0 +1,403 @@
+//! Audit log module
+//!
+//! A trail of security-relevant actions for server operators: logins (successful or
+//! not), logouts, code submissions and admin actions, with who did them, from where and
+//! whether they succeeded. Entries are write-once: an [`AuditStore`] can append and
+//! query them, never change or delete them.
+//!
+//! - [`InMemoryAuditStore`] is for tests and throwaway servers.
+//! - [`SqliteAuditStore`] keeps entries in an indexed `audit_log` table whose triggers
+//!   refuse updates and deletes; queries use the read connections of a [`SqlitePool`],
+//!   so they do not hold up appends.
+//! - `RedisAuditStore` (feature `redis_backend`) keeps the newest [`REDIS_AUDIT_CAP`]
+//!   entries in a capped list shared by every server instance.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Mutex;
+
+use rusqlite::{params, params_from_iter};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::sqlite_pool::{SqlitePool, DEFAULT_SQLITE_READ_CONNECTIONS};
+
+/// Entries kept by the Redis audit store (older ones are dropped)
+pub const REDIS_AUDIT_CAP: isize = 100_000;
+
+/// Redis list holding the audit log, newest entry first
+pub const REDIS_AUDIT_KEY: &str = "geekcraft:audit";
+
+/// Kind of audited action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// Password login (failed attempts record the username tried)
+    Login,
+    /// End of a session
+    Logout,
+    /// Player code submitted over HTTP or WebSocket
+    CodeSubmission,
+    /// Change made through an `/api/admin/` endpoint
+    Admin,
+}
+
+impl AuditAction {
+    /// Name of the action, as stored and as given to `?action=`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Login => "login",
+            AuditAction::Logout => "logout",
+            AuditAction::CodeSubmission => "code_submission",
+            AuditAction::Admin => "admin",
+        }
+    }
+
+    /// Parse an action name
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "login" => Ok(AuditAction::Login),
+            "logout" => Ok(AuditAction::Logout),
+            "code_submission" => Ok(AuditAction::CodeSubmission),
+            "admin" => Ok(AuditAction::Admin),
+            _ => Err(format!("Unknown audit action {:?}", name)),
+        }
+    }
+}
+
+/// Whether an audited action succeeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    /// The action was carried out
+    Success,
+    /// The action was refused or failed
+    Failure,
+}
+
+impl AuditOutcome {
+    /// Outcome of a result
+    pub fn of(success: bool) -> Self {
+        if success { AuditOutcome::Success } else { AuditOutcome::Failure }
+    }
+
+    /// Name of the outcome, as stored
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditOutcome::Success => "success",
+            AuditOutcome::Failure => "failure",
+        }
+    }
+
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "success" => Ok(AuditOutcome::Success),
+            "failure" => Ok(AuditOutcome::Failure),
+            _ => Err(format!("Unknown audit outcome {:?}", name)),
+        }
+    }
+}
+
+/// One audited action
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// When the action happened (Unix epoch seconds)
+    pub timestamp: i64,
+    /// User who acted (for a failed login, the username tried)
+    pub user: String,
+    /// Client address (`None` if unknown)
+    pub ip: Option<String>,
+    /// What was done
+    pub action: AuditAction,
+    /// Whether it succeeded
+    pub outcome: AuditOutcome,
+    /// Details: the admin endpoint called, or why the action failed (never a password)
+    pub detail: String,
+}
+
+impl AuditEntry {
+    /// Entry for an action happening now
+    pub fn new(user: &str, ip: Option<IpAddr>, action: AuditAction, outcome: AuditOutcome, detail: impl Into<String>) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().timestamp(),
+            user: user.to_string(),
+            ip: ip.map(|ip| ip.to_string()),
+            action,
+            outcome,
+            detail: detail.into(),
+        }
+    }
+
+    /// Whether the entry passes a filter's conditions (pagination aside)
+    pub fn matches(&self, filter: &AuditFilter) -> bool {
+        filter.user.as_ref().is_none_or(|user| &self.user == user)
+            && filter.action.is_none_or(|action| self.action == action)
+            && filter.since.is_none_or(|since| self.timestamp >= since)
+    }
+}
+
+/// Which entries to return, newest first
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditFilter {
+    /// Only entries of this user
+    pub user: Option<String>,
+    /// Only entries of this action
+    pub action: Option<AuditAction>,
+    /// Only entries at or after this time (Unix epoch seconds)
+    pub since: Option<i64>,
+    /// Matching entries to skip
+    pub offset: u32,
+    /// Maximum entries to return
+    pub limit: u32,
+}
+
+/// Write-once storage for the audit log
+pub trait AuditStore: Send + Sync + fmt::Debug {
+    /// Append an entry
+    fn append_audit(&self, entry: &AuditEntry) -> Result<(), String>;
+    /// Entries matching a filter, newest first, and the number of matching entries
+    fn query_audit(&self, filter: &AuditFilter) -> Result<(Vec<AuditEntry>, u32), String>;
+}
+
+/// Page of the entries matching a filter, from entries sorted newest first
+fn page_of(entries: impl Iterator<Item = AuditEntry>, filter: &AuditFilter) -> (Vec<AuditEntry>, u32) {
+    let matching: Vec<AuditEntry> = entries.filter(|entry| entry.matches(filter)).collect();
+    let total = matching.len() as u32;
+    let page = matching.into_iter().skip(filter.offset as usize).take(filter.limit as usize).collect();
+    (page, total)
+}
+
+/// Audit log kept in memory (lost on restart)
+#[derive(Debug, Default)]
+pub struct InMemoryAuditStore {
+    entries: Mutex<Vec<AuditEntry>>,
+}
+
+impl InMemoryAuditStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl AuditStore for InMemoryAuditStore {
+    fn append_audit(&self, entry: &AuditEntry) -> Result<(), String> {
+        self.entries.lock().unwrap().push(entry.clone());
+        Ok(())
+    }
+
+    fn query_audit(&self, filter: &AuditFilter) -> Result<(Vec<AuditEntry>, u32), String> {
+        let entries = self.entries.lock().unwrap();
+        Ok(page_of(entries.iter().rev().cloned(), filter))
+    }
+}
+
+/// Schema of the SQLite audit log (kept out of the world database migrations: the audit
+/// log has a file of its own)
+const SQLITE_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp INTEGER NOT NULL,
+    user TEXT NOT NULL,
+    ip TEXT,
+    action TEXT NOT NULL,
+    outcome TEXT NOT NULL,
+    detail TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS audit_log_user ON audit_log (user, timestamp);
+CREATE INDEX IF NOT EXISTS audit_log_action ON audit_log (action, timestamp);
+CREATE INDEX IF NOT EXISTS audit_log_timestamp ON audit_log (timestamp);
+CREATE TRIGGER IF NOT EXISTS audit_log_no_update BEFORE UPDATE ON audit_log
+BEGIN SELECT RAISE(ABORT, 'audit log entries cannot be changed'); END;
+CREATE TRIGGER IF NOT EXISTS audit_log_no_delete BEFORE DELETE ON audit_log
+BEGIN SELECT RAISE(ABORT, 'audit log entries cannot be deleted'); END;
+";
+
+/// Audit log in a SQLite database file
+pub struct SqliteAuditStore {
+    pool: SqlitePool,
+}
+
+impl fmt::Debug for SqliteAuditStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SqliteAuditStore").finish_non_exhaustive()
+    }
+}
+
+impl SqliteAuditStore {
+    /// Open (or create) a database file with the default number of read connections
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Self::open_with_readers(path, DEFAULT_SQLITE_READ_CONNECTIONS)
+    }
+
+    /// Open (or create) a database file with `readers` read connections (see [`SqlitePool`])
+    pub fn open_with_readers(path: &Path, readers: usize) -> Result<Self, String> {
+        let pool = SqlitePool::open(path, readers)
+            .map_err(|e| format!("Failed to open audit database {}: {}", path.display(), e))?;
+        Self::with_pool(pool)
+    }
+
+    /// Open a private in-memory database
+    pub fn open_in_memory() -> Result<Self, String> {
+        let pool = SqlitePool::open_in_memory()
+            .map_err(|e| format!("Failed to open audit database: {}", e))?;
+        Self::with_pool(pool)
+    }
+
+    fn with_pool(pool: SqlitePool) -> Result<Self, String> {
+        pool.write().execute_batch(SQLITE_SCHEMA)
+            .map_err(|e| format!("Failed to create the audit log table: {}", e))?;
+        Ok(Self { pool })
+    }
+}
+
+impl AuditStore for SqliteAuditStore {
+    fn append_audit(&self, entry: &AuditEntry) -> Result<(), String> {
+        self.pool.write()
+            .execute(
+                "INSERT INTO audit_log (timestamp, user, ip, action, outcome, detail) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![entry.timestamp, entry.user, entry.ip, entry.action.as_str(), entry.outcome.as_str(), entry.detail],
+            )
+            .map(|_| ())
+            .map_err(|e| format!("Audit database error: {}", e))
+    }
+
+    fn query_audit(&self, filter: &AuditFilter) -> Result<(Vec<AuditEntry>, u32), String> {
+        let error = |e: rusqlite::Error| format!("Audit database error: {}", e);
+        let mut conditions = Vec::new();
+        let mut values: Vec<rusqlite::types::Value> = Vec::new();
+        if let Some(user) = &filter.user {
+            values.push(user.clone().into());
+            conditions.push(format!("user = ?{}", values.len()));
+        }
+        if let Some(action) = filter.action {
+            values.push(action.as_str().to_string().into());
+            conditions.push(format!("action = ?{}", values.len()));
+        }
+        if let Some(since) = filter.since {
+            values.push(since.into());
+            conditions.push(format!("timestamp >= ?{}", values.len()));
+        }
+        let clause = if conditions.is_empty() { String::new() } else { format!("WHERE {}", conditions.join(" AND ")) };
+
+        let conn = self.pool.read();
+        let total: u32 = conn
+            .query_row(&format!("SELECT COUNT(*) FROM audit_log {}", clause), params_from_iter(&values), |row| row.get(0))
+            .map_err(error)?;
+
+        let mut statement = conn
+            .prepare(&format!(
+                "SELECT timestamp, user, ip, action, outcome, detail FROM audit_log {} ORDER BY id DESC LIMIT {} OFFSET {}",
+                clause, filter.limit, filter.offset
+            ))
+            .map_err(error)?;
+        let rows = statement
+            .query_map(params_from_iter(&values), |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get::<_, String>(3)?, row.get::<_, String>(4)?, row.get(5)?))
+            })
+            .map_err(error)?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (timestamp, user, ip, action, outcome, detail) = row.map_err(error)?;
+            entries.push(AuditEntry {
+                timestamp,
+                user,
+                ip,
+                action: AuditAction::parse(&action)?,
+                outcome: AuditOutcome::parse(&outcome)?,
+                detail,
+            });
+        }
+        Ok((entries, total))
+    }
+}
+
+/// Audit log in a Redis list shared by every instance, capped at [`REDIS_AUDIT_CAP`] entries
+#[cfg(feature = "redis_backend")]
+#[derive(Debug)]
+pub struct RedisAuditStore {
+    client: redis::Client,
+}
+
+#[cfg(feature = "redis_backend")]
+impl RedisAuditStore {
+    /// Use the Redis server at `url` (connections are made on each call)
+    pub fn open(url: &str) -> Result<Self, String> {
+        let client = redis::Client::open(url).map_err(|e| format!("Invalid Redis URL {}: {}", url, e))?;
+        Ok(Self { client })
+    }
+
+    fn connection(&self) -> Result<redis::Connection, String> {
+        self.client.get_connection_with_timeout(std::time::Duration::from_secs(3))
+            .map_err(|e| format!("Redis error: {}", e))
+    }
+}
+
+#[cfg(feature = "redis_backend")]
+impl AuditStore for RedisAuditStore {
+    fn append_audit(&self, entry: &AuditEntry) -> Result<(), String> {
+        let json = serde_json::to_string(entry).map_err(|e| format!("Failed to serialize audit entry: {}", e))?;
+        redis::pipe()
+            .atomic()
+            .cmd("LPUSH").arg(REDIS_AUDIT_KEY).arg(json).ignore()
+            .cmd("LTRIM").arg(REDIS_AUDIT_KEY).arg(0).arg(REDIS_AUDIT_CAP - 1).ignore()
+            .query::<()>(&mut self.connection()?)
+            .map_err(|e| format!("Redis error: {}", e))
+    }
+
+    fn query_audit(&self, filter: &AuditFilter) -> Result<(Vec<AuditEntry>, u32), String> {
+        let items: Vec<String> = redis::cmd("LRANGE").arg(REDIS_AUDIT_KEY).arg(0).arg(-1)
+            .query(&mut self.connection()?)
+            .map_err(|e| format!("Redis error: {}", e))?;
+        let entries = items.iter().filter_map(|item| match serde_json::from_str::<AuditEntry>(item) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                log::warn!("Skipping unreadable audit entry: {}", e);
+                None
+            }
+        });
+        Ok(page_of(entries, filter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: i64, user: &str, action: AuditAction) -> AuditEntry {
+        AuditEntry { timestamp, ..AuditEntry::new(user, None, action, AuditOutcome::Success, "") }
+    }
+
+    #[test]
+    fn test_stores_filter_and_page_newest_first() {
+        let stores: [Box<dyn AuditStore>; 2] = [Box::new(InMemoryAuditStore::new()), Box::new(SqliteAuditStore::open_in_memory().unwrap())];
+        for store in stores {
+            for (timestamp, user, action) in [(10, "alice", AuditAction::Login), (20, "bob", AuditAction::Login), (30, "alice", AuditAction::CodeSubmission), (40, "alice", AuditAction::Logout)] {
+                store.append_audit(&entry(timestamp, user, action)).unwrap();
+            }
+
+            let all = AuditFilter { limit: 10, ..AuditFilter::default() };
+            let (entries, total) = store.query_audit(&all).unwrap();
+            assert_eq!(total, 4);
+            assert_eq!(entries.iter().map(|entry| entry.timestamp).collect::<Vec<_>>(), vec![40, 30, 20, 10]);
+
+            let alice = AuditFilter { user: Some("alice".to_string()), since: Some(20), limit: 1, offset: 1, ..AuditFilter::default() };
+            assert_eq!(store.query_audit(&alice).unwrap(), (vec![entry(30, "alice", AuditAction::CodeSubmission)], 2));
+
+            let logins = AuditFilter { action: Some(AuditAction::Login), limit: 10, ..AuditFilter::default() };
+            assert_eq!(store.query_audit(&logins).unwrap().1, 2);
+        }
+    }
+
+    #[test]
+    fn test_sqlite_entries_cannot_be_changed() {
+        let store = SqliteAuditStore::open_in_memory().unwrap();
+        store.append_audit(&entry(10, "alice", AuditAction::Login)).unwrap();
+        let conn = store.pool.write();
+        let update = conn.execute("UPDATE audit_log SET user = 'mallory'", []).unwrap_err();
+        assert!(update.to_string().contains("cannot be changed"), "{}", update);
+        let delete = conn.execute("DELETE FROM audit_log", []).unwrap_err();
+        assert!(delete.to_string().contains("cannot be deleted"), "{}", delete);
+    }
+}
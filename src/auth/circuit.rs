@@ -0,0 +1,188 @@
+//! Circuit breaker module
+//!
+//! Stops calling a database that keeps failing. After `threshold` failures in a row
+//! the circuit opens and calls fail at once with [`UNAVAILABLE`] instead of waiting
+//! on a disk that is full or a file that is locked. After `reset_timeout`, one call
+//! is let through (half-open): the circuit closes if it succeeds and opens again if
+//! it fails.
+//!
+//! Errors about the request itself ("not found", "already exists") come from a
+//! database that works, so they count as successes.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Failures in a row that open the circuit by default
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+
+/// Time an open circuit waits before letting a call through by default
+pub const DEFAULT_RESET_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Error of the calls refused while the circuit is open
+pub const UNAVAILABLE: &str = "Database unavailable";
+
+/// State of a [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Calls go through
+    Closed,
+    /// Calls fail at once
+    Open,
+    /// One call goes through to test whether the database is back
+    HalfOpen,
+}
+
+impl fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitState::Closed => write!(f, "closed"),
+            CircuitState::Open => write!(f, "open"),
+            CircuitState::HalfOpen => write!(f, "half-open"),
+        }
+    }
+}
+
+/// Failure counter deciding which database calls are attempted
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    state: CircuitState,
+    failure_count: u32,
+    threshold: u32,
+    reset_timeout: Duration,
+    /// When the circuit last opened, or when the half-open call started
+    since: Option<Instant>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(DEFAULT_FAILURE_THRESHOLD, DEFAULT_RESET_TIMEOUT)
+    }
+}
+
+impl CircuitBreaker {
+    /// Create a closed circuit opening after `threshold` failures in a row (at least 1)
+    pub fn new(threshold: u32, reset_timeout: Duration) -> Self {
+        CircuitBreaker {
+            state: CircuitState::Closed,
+            failure_count: 0,
+            threshold: threshold.max(1),
+            reset_timeout,
+            since: None,
+        }
+    }
+
+    /// Current state
+    pub fn state(&self) -> CircuitState {
+        self.state
+    }
+
+    /// Failures since the last success
+    pub fn failure_count(&self) -> u32 {
+        self.failure_count
+    }
+
+    /// Whether a call may be attempted now
+    ///
+    /// An open circuit lets one call through once `reset_timeout` has passed, and turns
+    /// half-open until its result is recorded. Should that call never report back, another
+    /// one is let through after `reset_timeout`.
+    pub fn allow(&mut self) -> bool {
+        match self.state {
+            CircuitState::Closed => true,
+            CircuitState::Open | CircuitState::HalfOpen => {
+                let waited = self.since.is_none_or(|since| since.elapsed() >= self.reset_timeout);
+                if waited {
+                    self.state = CircuitState::HalfOpen;
+                    self.since = Some(Instant::now());
+                }
+                waited
+            }
+        }
+    }
+
+    /// Record a call that reached the database
+    pub fn record_success(&mut self) {
+        self.state = CircuitState::Closed;
+        self.failure_count = 0;
+        self.since = None;
+    }
+
+    /// Record a failed call, opening the circuit at the threshold or after a half-open call
+    pub fn record_failure(&mut self) {
+        self.failure_count = self.failure_count.saturating_add(1);
+        if self.state == CircuitState::HalfOpen || self.failure_count >= self.threshold {
+            self.state = CircuitState::Open;
+            self.since = Some(Instant::now());
+        }
+    }
+
+    /// Record the result of a call (see [`is_request_error`])
+    pub fn record<T>(&mut self, result: &Result<T, String>) {
+        match result {
+            Err(e) if !is_request_error(e) => self.record_failure(),
+            _ => self.record_success(),
+        }
+    }
+}
+
+/// Whether an error is about the request rather than the database
+pub fn is_request_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    error.contains("not found") || error.contains("already")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circuit_opens_at_threshold_and_recovers() {
+        let mut circuit = CircuitBreaker::new(3, Duration::from_millis(20));
+        for _ in 0..2 {
+            assert!(circuit.allow());
+            circuit.record(&Err::<(), _>("disk I/O error".to_string()));
+        }
+        assert_eq!(circuit.state(), CircuitState::Closed);
+        circuit.record(&Err::<(), _>("database is locked".to_string()));
+        assert_eq!(circuit.state(), CircuitState::Open);
+        assert!(!circuit.allow());
+
+        // One trial call after the timeout; a failure opens the circuit again
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(circuit.allow());
+        assert_eq!(circuit.state(), CircuitState::HalfOpen);
+        assert!(!circuit.allow(), "only one call is let through while half-open");
+        circuit.record_failure();
+        assert_eq!(circuit.state(), CircuitState::Open);
+        assert!(!circuit.allow());
+
+        // A successful trial closes it
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(circuit.allow());
+        circuit.record(&Ok(()));
+        assert_eq!(circuit.state(), CircuitState::Closed);
+        assert_eq!(circuit.failure_count(), 0);
+        assert!(circuit.allow());
+    }
+
+    #[test]
+    fn test_request_errors_and_successes_keep_the_circuit_closed() {
+        let mut circuit = CircuitBreaker::new(2, Duration::from_secs(60));
+        for _ in 0..5 {
+            circuit.record(&Err::<(), _>("Username already exists".to_string()));
+            circuit.record(&Err::<(), _>("User not found".to_string()));
+        }
+        assert_eq!(circuit.state(), CircuitState::Closed);
+
+        // Failures must come in a row
+        circuit.record_failure();
+        circuit.record(&Ok(()));
+        circuit.record_failure();
+        assert_eq!(circuit.state(), CircuitState::Closed);
+        circuit.record_failure();
+        assert_eq!(circuit.state(), CircuitState::Open);
+    }
+}
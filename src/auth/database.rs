@@ -10,9 +10,12 @@
 //! 
 //! Users can easily switch between backends by changing configuration.
 
-use super::models::{User, Session};
-use std::collections::HashMap;
+use super::circuit::{CircuitBreaker, CircuitState, UNAVAILABLE};
+use super::models::{User, Session, MatchRecord, Team, Alliance, SharedLibrary, Friendship, FollowRequest, Invite, DEFAULT_RATING};
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
 
 /// Helper function to get current Unix timestamp safely
 fn get_unix_timestamp() -> i64 {
@@ -38,6 +41,31 @@ pub enum DatabaseBackend {
     MongoDB(String), // Connection string: "mongodb://localhost:27017"
 }
 
+/// Which users [`AuthDatabaseTrait::list_users`] returns
+#[derive(Debug, Clone, Default)]
+pub struct UserFilter {
+    /// Only users whose username starts with this, ignoring case
+    pub username_prefix: Option<String>,
+}
+
+impl UserFilter {
+    /// Whether a user passes the filter
+    pub fn matches(&self, user: &User) -> bool {
+        self.username_prefix.as_deref().is_none_or(|prefix| {
+            user.username.get(..prefix.len()).is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+        })
+    }
+}
+
+/// Why an existing invite code cannot be used at `now`
+fn unusable_invite_error(invite: &Invite, now: i64) -> String {
+    if invite.expires_at.is_some_and(|expires_at| expires_at <= now) {
+        "Invite code has already expired".to_string()
+    } else {
+        "Invite code has already been used".to_string()
+    }
+}
+
 /// Authentication database trait
 /// Implement this trait to add support for new database backends
 pub trait AuthDatabaseTrait: Send + Sync {
@@ -45,19 +73,100 @@ pub trait AuthDatabaseTrait: Send + Sync {
     fn create_user(&self, username: &str, password_hash: &str) -> Result<User, String>;
     /// Get a user by username
     fn get_user_by_username(&self, username: &str) -> Result<Option<User>, String>;
+    /// Create a user signing in with an identity provider (no password)
+    fn create_oauth_user(&self, username: &str, provider: &str, oauth_id: &str) -> Result<User, String>;
+    /// Get the user signing in with an identity provider account
+    fn get_user_by_oauth(&self, provider: &str, oauth_id: &str) -> Result<Option<User>, String>;
+    /// Set a user's email, unverified, with a pending verification token
+    fn set_email_verification(&self, user_id: i64, email: &str, token: &str, expires_at: i64) -> Result<(), String>;
+    /// Get the user with a pending email verification token
+    fn get_user_by_email_token(&self, token: &str) -> Result<Option<User>, String>;
+    /// Mark a user's email as verified, consuming the verification token
+    fn mark_email_verified(&self, user_id: i64) -> Result<(), String>;
     /// Create a new session for a user
     fn create_session(&self, token: &str, user_id: i64, expires_at: i64) -> Result<(), String>;
     /// Get a session by token
     fn get_session(&self, token: &str) -> Result<Option<Session>, String>;
+    /// Move the expiration of a session (sliding expiration)
+    fn touch_session(&self, token: &str, expires_at: i64) -> Result<(), String>;
     /// Delete a session by token
     fn delete_session(&self, token: &str) -> Result<(), String>;
     /// Delete all expired sessions
     fn delete_expired_sessions(&self) -> Result<(), String>;
+    /// Store a match result and set both players' ratings to the ones in the record
+    fn record_match(&self, record: &MatchRecord) -> Result<(), String>;
+    /// Get the most recent matches of a player (newest first)
+    fn get_match_history(&self, username: &str, limit: usize) -> Result<Vec<MatchRecord>, String>;
+    /// Get a user by ID
+    fn get_user_by_id(&self, user_id: i64) -> Result<Option<User>, String>;
+    /// Create or replace a team
+    fn save_team(&self, team: &Team) -> Result<(), String>;
+    /// Get a team by ID
+    fn get_team(&self, team_id: &Uuid) -> Result<Option<Team>, String>;
+    /// Get the team a user belongs to (if any)
+    fn get_team_of_user(&self, user_id: i64) -> Result<Option<Team>, String>;
+    /// Delete a team
+    fn delete_team(&self, team_id: &Uuid) -> Result<(), String>;
+    /// Create or replace an alliance
+    fn save_alliance(&self, alliance: &Alliance) -> Result<(), String>;
+    /// Get an alliance by ID
+    fn get_alliance(&self, alliance_id: &Uuid) -> Result<Option<Alliance>, String>;
+    /// Get the alliance a user is a member of (if any)
+    fn get_alliance_of_user(&self, user_id: i64) -> Result<Option<Alliance>, String>;
+    /// Delete an alliance
+    fn delete_alliance(&self, alliance_id: &Uuid) -> Result<(), String>;
+    /// Create or replace a version of a shared library
+    fn save_library(&self, library: &SharedLibrary) -> Result<(), String>;
+    /// Get a library version by ID
+    fn get_library(&self, library_id: &Uuid) -> Result<Option<SharedLibrary>, String>;
+    /// Get every version of every library
+    fn list_libraries(&self) -> Result<Vec<SharedLibrary>, String>;
+    /// Get the IDs of the achievements a user has unlocked
+    fn get_unlocked_achievements(&self, user_id: i64) -> Result<HashSet<String>, String>;
+    /// Mark achievements as unlocked for a user (already unlocked ones are ignored)
+    fn unlock_achievements(&self, user_id: i64, achievement_ids: &[String]) -> Result<(), String>;
+    /// Store a friendship (ignored if the users are already friends)
+    fn add_friend(&self, friendship: &Friendship) -> Result<(), String>;
+    /// Delete the friendship between two users (in either direction)
+    fn remove_friend(&self, user_id: i64, friend_id: i64) -> Result<(), String>;
+    /// Get the friends of a user
+    fn list_friends(&self, user_id: i64) -> Result<Vec<User>, String>;
+    /// Store a pending friend request (ignored if it already exists)
+    fn create_follow_request(&self, request: &FollowRequest) -> Result<(), String>;
+    /// Delete a pending friend request; returns whether it existed
+    fn delete_follow_request(&self, from_id: i64, to_id: i64) -> Result<bool, String>;
+    /// Get the pending friend requests sent to a user
+    fn list_follow_requests(&self, to_id: i64) -> Result<Vec<FollowRequest>, String>;
+    /// Whether a user has a session that has not expired
+    fn has_active_session(&self, user_id: i64) -> Result<bool, String>;
+    /// Get up to `limit` of the users matching `filter`, sorted by ID and skipping the first
+    /// `offset`, and the number of matching users
+    fn list_users(&self, offset: u32, limit: u32, filter: &UserFilter) -> Result<(Vec<User>, u32), String>;
+    /// Store a new invite code (fails if the code exists)
+    fn create_invite(&self, invite: &Invite) -> Result<(), String>;
+    /// Get every invite code, oldest first
+    fn list_invites(&self) -> Result<Vec<Invite>, String>;
+    /// Delete an invite code; returns whether it existed
+    fn revoke_invite(&self, code: &str) -> Result<bool, String>;
+    /// Take one use of an invite code that has not expired at `now`, returning the invite
+    /// with its remaining uses
+    ///
+    /// Must be atomic: when several registrations race for the last use, only one gets it.
+    fn consume_invite(&self, code: &str, now: i64) -> Result<Invite, String>;
+    /// Give back a use taken by a registration that then failed (never beyond `max_uses`;
+    /// ignored if the code was revoked)
+    fn release_invite(&self, code: &str) -> Result<(), String>;
+    /// Check that the database answers queries
+    fn ping(&self) -> Result<(), String>;
 }
 
 /// Main authentication database wrapper
+///
+/// Every call goes through a [`CircuitBreaker`]: while the backend keeps failing, calls
+/// fail at once with [`UNAVAILABLE`] (see [`crate::auth::circuit`]).
 pub struct AuthDatabase {
     backend: Box<dyn AuthDatabaseTrait>,
+    circuit: Mutex<CircuitBreaker>,
 }
 
 impl AuthDatabase {
@@ -71,37 +180,249 @@ impl AuthDatabase {
             }
         };
         
-        Ok(AuthDatabase { backend: db })
+        Ok(AuthDatabase { backend: db, circuit: Mutex::new(CircuitBreaker::default()) })
+    }
+
+    /// Replace the circuit breaker (closed, with the given threshold and reset timeout)
+    pub fn with_circuit_breaker(mut self, threshold: u32, reset_timeout: Duration) -> Self {
+        self.circuit = Mutex::new(CircuitBreaker::new(threshold, reset_timeout));
+        self
+    }
+
+    /// State of the circuit breaker, for health checks
+    pub fn circuit_status(&self) -> CircuitState {
+        self.circuit.lock().unwrap().state()
+    }
+
+    /// Call the backend unless the circuit is open, and record the result
+    fn call<T>(&self, query: impl FnOnce(&dyn AuthDatabaseTrait) -> Result<T, String>) -> Result<T, String> {
+        if !self.circuit.lock().unwrap().allow() {
+            return Err(UNAVAILABLE.to_string());
+        }
+        let result = query(self.backend.as_ref());
+
+        let mut circuit = self.circuit.lock().unwrap();
+        let before = circuit.state();
+        circuit.record(&result);
+        match (before, circuit.state(), &result) {
+            (CircuitState::Closed, CircuitState::Open, Err(e)) => {
+                log::error!("❌ Database circuit opened after {} failures: {}", circuit.failure_count(), e);
+            }
+            (CircuitState::HalfOpen, CircuitState::Open, Err(e)) => log::warn!("Database still failing, circuit opened again: {}", e),
+            (CircuitState::HalfOpen, CircuitState::Closed, _) => log::info!("✓ Database circuit closed"),
+            _ => {}
+        }
+        result
     }
     
     /// Create a new user with the given username and password hash
     pub fn create_user(&self, username: &str, password_hash: &str) -> Result<User, String> {
-        self.backend.create_user(username, password_hash)
+        self.call(|backend| backend.create_user(username, password_hash))
     }
     
     /// Get a user by username
     pub fn get_user_by_username(&self, username: &str) -> Result<Option<User>, String> {
-        self.backend.get_user_by_username(username)
+        self.call(|backend| backend.get_user_by_username(username))
+    }
+
+    /// Create a user signing in with an identity provider (no password)
+    pub fn create_oauth_user(&self, username: &str, provider: &str, oauth_id: &str) -> Result<User, String> {
+        self.call(|backend| backend.create_oauth_user(username, provider, oauth_id))
+    }
+
+    /// Get the user signing in with an identity provider account
+    pub fn get_user_by_oauth(&self, provider: &str, oauth_id: &str) -> Result<Option<User>, String> {
+        self.call(|backend| backend.get_user_by_oauth(provider, oauth_id))
+    }
+
+    /// Set a user's email, unverified, with a pending verification token
+    pub fn set_email_verification(&self, user_id: i64, email: &str, token: &str, expires_at: i64) -> Result<(), String> {
+        self.call(|backend| backend.set_email_verification(user_id, email, token, expires_at))
+    }
+
+    /// Get the user with a pending email verification token
+    pub fn get_user_by_email_token(&self, token: &str) -> Result<Option<User>, String> {
+        self.call(|backend| backend.get_user_by_email_token(token))
+    }
+
+    /// Mark a user's email as verified, consuming the verification token
+    pub fn mark_email_verified(&self, user_id: i64) -> Result<(), String> {
+        self.call(|backend| backend.mark_email_verified(user_id))
     }
     
     /// Create a new session for a user
     pub fn create_session(&self, token: &str, user_id: i64, expires_at: i64) -> Result<(), String> {
-        self.backend.create_session(token, user_id, expires_at)
+        self.call(|backend| backend.create_session(token, user_id, expires_at))
     }
     
     /// Get a session by token
     pub fn get_session(&self, token: &str) -> Result<Option<Session>, String> {
-        self.backend.get_session(token)
+        self.call(|backend| backend.get_session(token))
+    }
+    
+    /// Move the expiration of a session (sliding expiration)
+    pub fn touch_session(&self, token: &str, expires_at: i64) -> Result<(), String> {
+        self.call(|backend| backend.touch_session(token, expires_at))
     }
     
     /// Delete a session by token
     pub fn delete_session(&self, token: &str) -> Result<(), String> {
-        self.backend.delete_session(token)
+        self.call(|backend| backend.delete_session(token))
     }
     
     /// Delete all expired sessions
     pub fn delete_expired_sessions(&self) -> Result<(), String> {
-        self.backend.delete_expired_sessions()
+        self.call(|backend| backend.delete_expired_sessions())
+    }
+    
+    /// Store a match result and update both players' ratings
+    pub fn record_match(&self, record: &MatchRecord) -> Result<(), String> {
+        self.call(|backend| backend.record_match(record))
+    }
+    
+    /// Get the most recent matches of a player (newest first)
+    pub fn get_match_history(&self, username: &str, limit: usize) -> Result<Vec<MatchRecord>, String> {
+        self.call(|backend| backend.get_match_history(username, limit))
+    }
+    
+    /// Get a user by ID
+    pub fn get_user_by_id(&self, user_id: i64) -> Result<Option<User>, String> {
+        self.call(|backend| backend.get_user_by_id(user_id))
+    }
+    
+    /// Create or replace a team
+    pub fn save_team(&self, team: &Team) -> Result<(), String> {
+        self.call(|backend| backend.save_team(team))
+    }
+    
+    /// Get a team by ID
+    pub fn get_team(&self, team_id: &Uuid) -> Result<Option<Team>, String> {
+        self.call(|backend| backend.get_team(team_id))
+    }
+    
+    /// Get the team a user belongs to (if any)
+    pub fn get_team_of_user(&self, user_id: i64) -> Result<Option<Team>, String> {
+        self.call(|backend| backend.get_team_of_user(user_id))
+    }
+    
+    /// Delete a team
+    pub fn delete_team(&self, team_id: &Uuid) -> Result<(), String> {
+        self.call(|backend| backend.delete_team(team_id))
+    }
+    
+    /// Create or replace an alliance
+    pub fn save_alliance(&self, alliance: &Alliance) -> Result<(), String> {
+        self.call(|backend| backend.save_alliance(alliance))
+    }
+    
+    /// Get an alliance by ID
+    pub fn get_alliance(&self, alliance_id: &Uuid) -> Result<Option<Alliance>, String> {
+        self.call(|backend| backend.get_alliance(alliance_id))
+    }
+    
+    /// Get the alliance a user is a member of (if any)
+    pub fn get_alliance_of_user(&self, user_id: i64) -> Result<Option<Alliance>, String> {
+        self.call(|backend| backend.get_alliance_of_user(user_id))
+    }
+    
+    /// Delete an alliance
+    pub fn delete_alliance(&self, alliance_id: &Uuid) -> Result<(), String> {
+        self.call(|backend| backend.delete_alliance(alliance_id))
+    }
+    
+    /// Create or replace a version of a shared library
+    pub fn save_library(&self, library: &SharedLibrary) -> Result<(), String> {
+        self.call(|backend| backend.save_library(library))
+    }
+    
+    /// Get a library version by ID
+    pub fn get_library(&self, library_id: &Uuid) -> Result<Option<SharedLibrary>, String> {
+        self.call(|backend| backend.get_library(library_id))
+    }
+    
+    /// Get every version of every library
+    pub fn list_libraries(&self) -> Result<Vec<SharedLibrary>, String> {
+        self.call(|backend| backend.list_libraries())
+    }
+    
+    /// Get the IDs of the achievements a user has unlocked
+    pub fn get_unlocked_achievements(&self, user_id: i64) -> Result<HashSet<String>, String> {
+        self.call(|backend| backend.get_unlocked_achievements(user_id))
+    }
+    
+    /// Mark achievements as unlocked for a user
+    pub fn unlock_achievements(&self, user_id: i64, achievement_ids: &[String]) -> Result<(), String> {
+        self.call(|backend| backend.unlock_achievements(user_id, achievement_ids))
+    }
+    
+    /// Store a friendship
+    pub fn add_friend(&self, friendship: &Friendship) -> Result<(), String> {
+        self.call(|backend| backend.add_friend(friendship))
+    }
+    
+    /// Delete the friendship between two users
+    pub fn remove_friend(&self, user_id: i64, friend_id: i64) -> Result<(), String> {
+        self.call(|backend| backend.remove_friend(user_id, friend_id))
+    }
+    
+    /// Get the friends of a user
+    pub fn list_friends(&self, user_id: i64) -> Result<Vec<User>, String> {
+        self.call(|backend| backend.list_friends(user_id))
+    }
+    
+    /// Store a pending friend request
+    pub fn create_follow_request(&self, request: &FollowRequest) -> Result<(), String> {
+        self.call(|backend| backend.create_follow_request(request))
+    }
+    
+    /// Delete a pending friend request; returns whether it existed
+    pub fn delete_follow_request(&self, from_id: i64, to_id: i64) -> Result<bool, String> {
+        self.call(|backend| backend.delete_follow_request(from_id, to_id))
+    }
+    
+    /// Get the pending friend requests sent to a user
+    pub fn list_follow_requests(&self, to_id: i64) -> Result<Vec<FollowRequest>, String> {
+        self.call(|backend| backend.list_follow_requests(to_id))
+    }
+    
+    /// Whether a user has a session that has not expired
+    pub fn has_active_session(&self, user_id: i64) -> Result<bool, String> {
+        self.call(|backend| backend.has_active_session(user_id))
+    }
+    
+    /// Get one page of the users matching `filter`, sorted by ID, and the number of matching users
+    pub fn list_users(&self, offset: u32, limit: u32, filter: &UserFilter) -> Result<(Vec<User>, u32), String> {
+        self.call(|backend| backend.list_users(offset, limit, filter))
+    }
+    
+    /// Store a new invite code (fails if the code exists)
+    pub fn create_invite(&self, invite: &Invite) -> Result<(), String> {
+        self.call(|backend| backend.create_invite(invite))
+    }
+    
+    /// Get every invite code, oldest first
+    pub fn list_invites(&self) -> Result<Vec<Invite>, String> {
+        self.call(|backend| backend.list_invites())
+    }
+    
+    /// Delete an invite code; returns whether it existed
+    pub fn revoke_invite(&self, code: &str) -> Result<bool, String> {
+        self.call(|backend| backend.revoke_invite(code))
+    }
+    
+    /// Atomically take one use of an invite code that has not expired at `now`
+    pub fn consume_invite(&self, code: &str, now: i64) -> Result<Invite, String> {
+        self.call(|backend| backend.consume_invite(code, now))
+    }
+    
+    /// Give back a use of an invite code taken by a failed registration
+    pub fn release_invite(&self, code: &str) -> Result<(), String> {
+        self.call(|backend| backend.release_invite(code))
+    }
+    
+    /// Check that the database answers queries
+    pub fn ping(&self) -> Result<(), String> {
+        self.call(|backend| backend.ping())
     }
 }
 
@@ -113,6 +434,14 @@ struct InMemoryBackend {
     users: Arc<Mutex<HashMap<String, User>>>,
     users_by_id: Arc<Mutex<HashMap<i64, User>>>,
     sessions: Arc<Mutex<HashMap<String, Session>>>,
+    matches: Arc<Mutex<Vec<MatchRecord>>>,
+    teams: Arc<Mutex<HashMap<Uuid, Team>>>,
+    alliances: Arc<Mutex<HashMap<Uuid, Alliance>>>,
+    libraries: Arc<Mutex<HashMap<Uuid, SharedLibrary>>>,
+    unlocked_achievements: Arc<Mutex<HashMap<i64, HashSet<String>>>>,
+    friendships: Arc<Mutex<Vec<Friendship>>>,
+    follow_requests: Arc<Mutex<Vec<FollowRequest>>>,
+    invites: Arc<Mutex<HashMap<String, Invite>>>,
     next_user_id: Arc<Mutex<i64>>,
 }
 
@@ -122,13 +451,21 @@ impl InMemoryBackend {
             users: Arc::new(Mutex::new(HashMap::new())),
             users_by_id: Arc::new(Mutex::new(HashMap::new())),
             sessions: Arc::new(Mutex::new(HashMap::new())),
+            matches: Arc::new(Mutex::new(Vec::new())),
+            teams: Arc::new(Mutex::new(HashMap::new())),
+            alliances: Arc::new(Mutex::new(HashMap::new())),
+            libraries: Arc::new(Mutex::new(HashMap::new())),
+            unlocked_achievements: Arc::new(Mutex::new(HashMap::new())),
+            friendships: Arc::new(Mutex::new(Vec::new())),
+            follow_requests: Arc::new(Mutex::new(Vec::new())),
+            invites: Arc::new(Mutex::new(HashMap::new())),
             next_user_id: Arc::new(Mutex::new(1)),
         }
     }
 }
 
-impl AuthDatabaseTrait for InMemoryBackend {
-    fn create_user(&self, username: &str, password_hash: &str) -> Result<User, String> {
+impl InMemoryBackend {
+    fn insert_user(&self, username: &str, password_hash: &str, oauth_provider: &str, oauth_id: &str) -> Result<User, String> {
         let mut users = self.users.lock().unwrap();
         
         if users.contains_key(username) {
@@ -146,6 +483,13 @@ impl AuthDatabaseTrait for InMemoryBackend {
             username: username.to_string(),
             password_hash: password_hash.to_string(),
             created_at: now,
+            rating: DEFAULT_RATING,
+            oauth_provider: oauth_provider.to_string(),
+            oauth_id: oauth_id.to_string(),
+            email: None,
+            email_verified: false,
+            email_verification_token: None,
+            email_verification_expires_at: None,
         };
         
         users.insert(username.to_string(), user.clone());
@@ -154,11 +498,65 @@ impl AuthDatabaseTrait for InMemoryBackend {
         Ok(user)
     }
     
+    /// Change a user in both indexes
+    fn update_user(&self, user_id: i64, change: impl FnOnce(&mut User)) -> Result<(), String> {
+        let mut users = self.users.lock().unwrap();
+        let mut users_by_id = self.users_by_id.lock().unwrap();
+        let user = users_by_id.get_mut(&user_id).ok_or("User not found")?;
+        change(user);
+        users.insert(user.username.clone(), user.clone());
+        Ok(())
+    }
+}
+
+impl AuthDatabaseTrait for InMemoryBackend {
+    fn create_user(&self, username: &str, password_hash: &str) -> Result<User, String> {
+        self.insert_user(username, password_hash, "", "")
+    }
+    
     fn get_user_by_username(&self, username: &str) -> Result<Option<User>, String> {
         let users = self.users.lock().unwrap();
         Ok(users.get(username).cloned())
     }
     
+    fn create_oauth_user(&self, username: &str, provider: &str, oauth_id: &str) -> Result<User, String> {
+        if self.get_user_by_oauth(provider, oauth_id)?.is_some() {
+            return Err(format!("This {} account is already registered", provider));
+        }
+        self.insert_user(username, "", provider, oauth_id)
+    }
+    
+    fn get_user_by_oauth(&self, provider: &str, oauth_id: &str) -> Result<Option<User>, String> {
+        let users = self.users.lock().unwrap();
+        Ok(users.values()
+            .find(|user| user.oauth_provider == provider && user.oauth_id == oauth_id)
+            .cloned())
+    }
+    
+    fn set_email_verification(&self, user_id: i64, email: &str, token: &str, expires_at: i64) -> Result<(), String> {
+        self.update_user(user_id, |user| {
+            user.email = Some(email.to_string());
+            user.email_verified = false;
+            user.email_verification_token = Some(token.to_string());
+            user.email_verification_expires_at = Some(expires_at);
+        })
+    }
+    
+    fn get_user_by_email_token(&self, token: &str) -> Result<Option<User>, String> {
+        let users = self.users.lock().unwrap();
+        Ok(users.values()
+            .find(|user| user.email_verification_token.as_deref() == Some(token))
+            .cloned())
+    }
+    
+    fn mark_email_verified(&self, user_id: i64) -> Result<(), String> {
+        self.update_user(user_id, |user| {
+            user.email_verified = true;
+            user.email_verification_token = None;
+            user.email_verification_expires_at = None;
+        })
+    }
+    
     fn create_session(&self, token: &str, user_id: i64, expires_at: i64) -> Result<(), String> {
         let users_by_id = self.users_by_id.lock().unwrap();
         let user = users_by_id.get(&user_id)
@@ -200,6 +598,14 @@ impl AuthDatabaseTrait for InMemoryBackend {
         }
     }
     
+    fn touch_session(&self, token: &str, expires_at: i64) -> Result<(), String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.get_mut(token)
+            .ok_or("Session not found")?;
+        session.expires_at = expires_at;
+        Ok(())
+    }
+    
     fn delete_session(&self, token: &str) -> Result<(), String> {
         let mut sessions = self.sessions.lock().unwrap();
         sessions.remove(token);
@@ -214,6 +620,211 @@ impl AuthDatabaseTrait for InMemoryBackend {
         
         Ok(())
     }
+    
+    fn record_match(&self, record: &MatchRecord) -> Result<(), String> {
+        let mut users = self.users.lock().unwrap();
+        let mut users_by_id = self.users_by_id.lock().unwrap();
+        
+        for (username, rating) in [(&record.player_a, record.rating_a), (&record.player_b, record.rating_b)] {
+            let user = users.get_mut(username)
+                .ok_or_else(|| format!("User {} not found", username))?;
+            user.rating = rating;
+            users_by_id.insert(user.id, user.clone());
+        }
+        
+        self.matches.lock().unwrap().push(record.clone());
+        Ok(())
+    }
+    
+    fn get_match_history(&self, username: &str, limit: usize) -> Result<Vec<MatchRecord>, String> {
+        let matches = self.matches.lock().unwrap();
+        Ok(matches.iter()
+            .rev()
+            .filter(|m| m.player_a == username || m.player_b == username)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+    
+    fn get_user_by_id(&self, user_id: i64) -> Result<Option<User>, String> {
+        let users_by_id = self.users_by_id.lock().unwrap();
+        Ok(users_by_id.get(&user_id).cloned())
+    }
+    
+    fn save_team(&self, team: &Team) -> Result<(), String> {
+        self.teams.lock().unwrap().insert(team.id, team.clone());
+        Ok(())
+    }
+    
+    fn get_team(&self, team_id: &Uuid) -> Result<Option<Team>, String> {
+        Ok(self.teams.lock().unwrap().get(team_id).cloned())
+    }
+    
+    fn get_team_of_user(&self, user_id: i64) -> Result<Option<Team>, String> {
+        let teams = self.teams.lock().unwrap();
+        Ok(teams.values().find(|team| team.members.contains(&user_id)).cloned())
+    }
+    
+    fn delete_team(&self, team_id: &Uuid) -> Result<(), String> {
+        self.teams.lock().unwrap().remove(team_id);
+        Ok(())
+    }
+    
+    fn save_alliance(&self, alliance: &Alliance) -> Result<(), String> {
+        self.alliances.lock().unwrap().insert(alliance.id, alliance.clone());
+        Ok(())
+    }
+    
+    fn get_alliance(&self, alliance_id: &Uuid) -> Result<Option<Alliance>, String> {
+        Ok(self.alliances.lock().unwrap().get(alliance_id).cloned())
+    }
+    
+    fn get_alliance_of_user(&self, user_id: i64) -> Result<Option<Alliance>, String> {
+        let alliances = self.alliances.lock().unwrap();
+        Ok(alliances.values().find(|alliance| alliance.members.contains(&user_id)).cloned())
+    }
+    
+    fn delete_alliance(&self, alliance_id: &Uuid) -> Result<(), String> {
+        self.alliances.lock().unwrap().remove(alliance_id);
+        Ok(())
+    }
+    
+    fn save_library(&self, library: &SharedLibrary) -> Result<(), String> {
+        self.libraries.lock().unwrap().insert(library.id, library.clone());
+        Ok(())
+    }
+    
+    fn get_library(&self, library_id: &Uuid) -> Result<Option<SharedLibrary>, String> {
+        Ok(self.libraries.lock().unwrap().get(library_id).cloned())
+    }
+    
+    fn list_libraries(&self) -> Result<Vec<SharedLibrary>, String> {
+        Ok(self.libraries.lock().unwrap().values().cloned().collect())
+    }
+    
+    fn get_unlocked_achievements(&self, user_id: i64) -> Result<HashSet<String>, String> {
+        let unlocked = self.unlocked_achievements.lock().unwrap();
+        Ok(unlocked.get(&user_id).cloned().unwrap_or_default())
+    }
+    
+    fn unlock_achievements(&self, user_id: i64, achievement_ids: &[String]) -> Result<(), String> {
+        let mut unlocked = self.unlocked_achievements.lock().unwrap();
+        unlocked.entry(user_id).or_default().extend(achievement_ids.iter().cloned());
+        Ok(())
+    }
+    
+    fn add_friend(&self, friendship: &Friendship) -> Result<(), String> {
+        let mut friendships = self.friendships.lock().unwrap();
+        let (a, b) = (friendship.user_id, friendship.friend_id);
+        if !friendships.iter().any(|f| (f.user_id, f.friend_id) == (a, b) || (f.user_id, f.friend_id) == (b, a)) {
+            friendships.push(friendship.clone());
+        }
+        Ok(())
+    }
+    
+    fn remove_friend(&self, user_id: i64, friend_id: i64) -> Result<(), String> {
+        let mut friendships = self.friendships.lock().unwrap();
+        friendships.retain(|f| (f.user_id, f.friend_id) != (user_id, friend_id) && (f.user_id, f.friend_id) != (friend_id, user_id));
+        Ok(())
+    }
+    
+    fn list_friends(&self, user_id: i64) -> Result<Vec<User>, String> {
+        let friendships = self.friendships.lock().unwrap();
+        let users_by_id = self.users_by_id.lock().unwrap();
+        Ok(friendships.iter()
+            .filter_map(|f| match (f.user_id, f.friend_id) {
+                (a, b) if a == user_id => Some(b),
+                (a, b) if b == user_id => Some(a),
+                _ => None,
+            })
+            .filter_map(|friend_id| users_by_id.get(&friend_id).cloned())
+            .collect())
+    }
+    
+    fn create_follow_request(&self, request: &FollowRequest) -> Result<(), String> {
+        let mut requests = self.follow_requests.lock().unwrap();
+        if !requests.contains(request) {
+            requests.push(*request);
+        }
+        Ok(())
+    }
+    
+    fn delete_follow_request(&self, from_id: i64, to_id: i64) -> Result<bool, String> {
+        let mut requests = self.follow_requests.lock().unwrap();
+        let before = requests.len();
+        requests.retain(|r| (r.from_id, r.to_id) != (from_id, to_id));
+        Ok(requests.len() < before)
+    }
+    
+    fn list_follow_requests(&self, to_id: i64) -> Result<Vec<FollowRequest>, String> {
+        let requests = self.follow_requests.lock().unwrap();
+        Ok(requests.iter().filter(|r| r.to_id == to_id).copied().collect())
+    }
+    
+    fn has_active_session(&self, user_id: i64) -> Result<bool, String> {
+        let now = get_unix_timestamp();
+        let sessions = self.sessions.lock().unwrap();
+        Ok(sessions.values().any(|session| session.user_id == user_id && session.expires_at >= now))
+    }
+    
+    fn list_users(&self, offset: u32, limit: u32, filter: &UserFilter) -> Result<(Vec<User>, u32), String> {
+        let users = self.users_by_id.lock().unwrap();
+        let mut matching: Vec<&User> = users.values().filter(|user| filter.matches(user)).collect();
+        matching.sort_by_key(|user| user.id);
+        let page = matching.iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .map(|user| (*user).clone())
+            .collect();
+        Ok((page, matching.len() as u32))
+    }
+    
+    fn create_invite(&self, invite: &Invite) -> Result<(), String> {
+        let mut invites = self.invites.lock().unwrap();
+        if invites.contains_key(&invite.code) {
+            return Err("Invite code already exists".to_string());
+        }
+        invites.insert(invite.code.clone(), invite.clone());
+        Ok(())
+    }
+    
+    fn list_invites(&self) -> Result<Vec<Invite>, String> {
+        let invites = self.invites.lock().unwrap();
+        let mut list: Vec<Invite> = invites.values().cloned().collect();
+        list.sort_by(|a, b| (a.created_at, &a.code).cmp(&(b.created_at, &b.code)));
+        Ok(list)
+    }
+    
+    fn revoke_invite(&self, code: &str) -> Result<bool, String> {
+        Ok(self.invites.lock().unwrap().remove(code).is_some())
+    }
+    
+    fn consume_invite(&self, code: &str, now: i64) -> Result<Invite, String> {
+        let mut invites = self.invites.lock().unwrap();
+        let invite = invites.get_mut(code).ok_or("Invite code not found")?;
+        if invite.uses_left == 0 || invite.expires_at.is_some_and(|expires_at| expires_at <= now) {
+            return Err(unusable_invite_error(invite, now));
+        }
+        invite.uses_left -= 1;
+        Ok(invite.clone())
+    }
+    
+    fn release_invite(&self, code: &str) -> Result<(), String> {
+        if let Some(invite) = self.invites.lock().unwrap().get_mut(code) {
+            invite.uses_left = (invite.uses_left + 1).min(invite.max_uses);
+        }
+        Ok(())
+    }
+    
+    fn ping(&self) -> Result<(), String> {
+        // A panic while holding a lock leaves the store unusable
+        for poisoned in [self.users.is_poisoned(), self.users_by_id.is_poisoned(), self.sessions.is_poisoned()] {
+            if poisoned {
+                return Err("In-memory database is poisoned".to_string());
+            }
+        }
+        Ok(())
+    }
 }
 
 // ============================================================================
@@ -223,16 +834,27 @@ impl AuthDatabaseTrait for InMemoryBackend {
 use mongodb::{
     Client, 
     bson::{doc, Document, to_document, from_document},
-    options::{ClientOptions, IndexOptions},
+    options::{ClientOptions, FindOptions, IndexOptions},
     IndexModel,
 };
-use std::time::Duration;
 
 struct MongoBackend {
     client: Client,
     db_name: String,
 }
 
+/// Escape the regex metacharacters of a literal, for `$regex` queries
+fn escape_regex(literal: &str) -> String {
+    let mut escaped = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        if "\\.^$|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
 // Note: This implementation creates a new runtime for each operation to maintain
 // compatibility with the synchronous AuthDatabaseTrait. For production use with
 // high throughput, consider refactoring the trait to be async or using a shared
@@ -304,6 +926,37 @@ impl MongoBackend {
                 .await
                 .map_err(|e| format!("Failed to create username index: {}", e))?;
             
+            // Each identity provider account belongs to at most one user
+            let oauth_index = IndexModel::builder()
+                .keys(doc! { "oauth_provider": 1, "oauth_id": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .unique(true)
+                        .partial_filter_expression(doc! { "oauth_provider": { "$gt": "" } })
+                        .build()
+                )
+                .build();
+            
+            users_collection
+                .create_index(oauth_index, None)
+                .await
+                .map_err(|e| format!("Failed to create OAuth index: {}", e))?;
+            
+            // Invite codes are looked up and consumed by code
+            let invite_index = IndexModel::builder()
+                .keys(doc! { "code": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .unique(true)
+                        .build()
+                )
+                .build();
+            
+            db.collection::<Document>("invites")
+                .create_index(invite_index, None)
+                .await
+                .map_err(|e| format!("Failed to create invite code index: {}", e))?;
+            
             Ok::<(Client, String), String>((client, db_name))
         })?;
         
@@ -313,10 +966,29 @@ impl MongoBackend {
     fn get_database(&self) -> mongodb::Database {
         self.client.database(&self.db_name)
     }
-}
-
-impl AuthDatabaseTrait for MongoBackend {
-    fn create_user(&self, username: &str, password_hash: &str) -> Result<User, String> {
+    
+    /// Apply an update to a user
+    fn update_user(&self, user_id: i64, update: Document) -> Result<(), String> {
+        let db = self.get_database();
+        let users_collection = db.collection::<Document>("users");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let result = users_collection
+                .update_one(doc! { "id": user_id }, update, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            if result.matched_count == 0 {
+                return Err("User not found".to_string());
+            }
+            Ok(())
+        })
+    }
+    
+    fn insert_user(&self, username: &str, password_hash: &str, oauth_provider: &str, oauth_id: &str) -> Result<User, String> {
         let db = self.get_database();
         let users_collection = db.collection::<Document>("users");
         
@@ -359,6 +1031,13 @@ impl AuthDatabaseTrait for MongoBackend {
                 username: username.to_string(),
                 password_hash: password_hash.to_string(),
                 created_at: now,
+                rating: DEFAULT_RATING,
+                oauth_provider: oauth_provider.to_string(),
+                oauth_id: oauth_id.to_string(),
+                email: None,
+                email_verified: false,
+                email_verification_token: None,
+                email_verification_expires_at: None,
             };
             
             // Insert user document
@@ -374,7 +1053,53 @@ impl AuthDatabaseTrait for MongoBackend {
         })
     }
     
-    fn get_user_by_username(&self, username: &str) -> Result<Option<User>, String> {
+    /// Find the first team matching a filter
+    fn find_team(&self, filter: Document) -> Result<Option<Team>, String> {
+        let db = self.get_database();
+        let teams_collection = db.collection::<Document>("teams");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let team_doc = teams_collection
+                .find_one(filter, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            team_doc
+                .map(|doc| from_document(doc).map_err(|e| format!("Failed to deserialize team: {}", e)))
+                .transpose()
+        })
+    }
+    
+    /// Find the first alliance matching a filter
+    fn find_alliance(&self, filter: Document) -> Result<Option<Alliance>, String> {
+        let db = self.get_database();
+        let alliances_collection = db.collection::<Document>("alliances");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let alliance_doc = alliances_collection
+                .find_one(filter, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            alliance_doc
+                .map(|doc| from_document(doc).map_err(|e| format!("Failed to deserialize alliance: {}", e)))
+                .transpose()
+        })
+    }
+}
+
+impl AuthDatabaseTrait for MongoBackend {
+    fn create_user(&self, username: &str, password_hash: &str) -> Result<User, String> {
+        self.insert_user(username, password_hash, "", "")
+    }
+    
+    fn get_user_by_username(&self, username: &str) -> Result<Option<User>, String> {
         let db = self.get_database();
         let users_collection = db.collection::<Document>("users");
         
@@ -398,6 +1123,67 @@ impl AuthDatabaseTrait for MongoBackend {
         })
     }
     
+    fn create_oauth_user(&self, username: &str, provider: &str, oauth_id: &str) -> Result<User, String> {
+        if self.get_user_by_oauth(provider, oauth_id)?.is_some() {
+            return Err(format!("This {} account is already registered", provider));
+        }
+        self.insert_user(username, "", provider, oauth_id)
+    }
+    
+    fn get_user_by_oauth(&self, provider: &str, oauth_id: &str) -> Result<Option<User>, String> {
+        let db = self.get_database();
+        let users_collection = db.collection::<Document>("users");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let user_doc = users_collection
+                .find_one(doc! { "oauth_provider": provider, "oauth_id": oauth_id }, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            user_doc
+                .map(|doc| from_document(doc).map_err(|e| format!("Failed to deserialize user: {}", e)))
+                .transpose()
+        })
+    }
+    
+    fn set_email_verification(&self, user_id: i64, email: &str, token: &str, expires_at: i64) -> Result<(), String> {
+        self.update_user(user_id, doc! { "$set": {
+            "email": email,
+            "email_verified": false,
+            "email_verification_token": token,
+            "email_verification_expires_at": expires_at,
+        } })
+    }
+    
+    fn get_user_by_email_token(&self, token: &str) -> Result<Option<User>, String> {
+        let db = self.get_database();
+        let users_collection = db.collection::<Document>("users");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let user_doc = users_collection
+                .find_one(doc! { "email_verification_token": token }, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            user_doc
+                .map(|doc| from_document(doc).map_err(|e| format!("Failed to deserialize user: {}", e)))
+                .transpose()
+        })
+    }
+    
+    fn mark_email_verified(&self, user_id: i64) -> Result<(), String> {
+        self.update_user(user_id, doc! {
+            "$set": { "email_verified": true },
+            "$unset": { "email_verification_token": "", "email_verification_expires_at": "" },
+        })
+    }
+    
     fn create_session(&self, token: &str, user_id: i64, expires_at: i64) -> Result<(), String> {
         let db = self.get_database();
         let users_collection = db.collection::<Document>("users");
@@ -501,6 +1287,31 @@ impl AuthDatabaseTrait for MongoBackend {
         })
     }
     
+    fn touch_session(&self, token: &str, expires_at: i64) -> Result<(), String> {
+        let db = self.get_database();
+        let sessions_collection = db.collection::<Document>("sessions");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            // The TTL index follows the new expiration
+            let result = sessions_collection
+                .update_one(
+                    doc! { "token": token },
+                    doc! { "$set": { "expires_at": bson::DateTime::from_millis(expires_at * 1000) } },
+                    None
+                )
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            if result.matched_count == 0 {
+                return Err("Session not found".to_string());
+            }
+            Ok(())
+        })
+    }
+    
     fn delete_session(&self, token: &str) -> Result<(), String> {
         let db = self.get_database();
         let sessions_collection = db.collection::<Document>("sessions");
@@ -537,4 +1348,683 @@ impl AuthDatabaseTrait for MongoBackend {
             Ok(())
         })
     }
+    
+    fn record_match(&self, record: &MatchRecord) -> Result<(), String> {
+        let db = self.get_database();
+        let users_collection = db.collection::<Document>("users");
+        let matches_collection = db.collection::<Document>("matches");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            for (username, rating) in [(&record.player_a, record.rating_a), (&record.player_b, record.rating_b)] {
+                let result = users_collection
+                    .update_one(doc! { "username": username }, doc! { "$set": { "rating": rating } }, None)
+                    .await
+                    .map_err(|e| format!("MongoDB error: {}", e))?;
+                
+                if result.matched_count == 0 {
+                    return Err(format!("User {} not found", username));
+                }
+            }
+            
+            let match_doc = to_document(record)
+                .map_err(|e| format!("Failed to serialize match: {}", e))?;
+            
+            matches_collection
+                .insert_one(match_doc, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            Ok(())
+        })
+    }
+    
+    fn get_match_history(&self, username: &str, limit: usize) -> Result<Vec<MatchRecord>, String> {
+        let db = self.get_database();
+        let matches_collection = db.collection::<Document>("matches");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let options = mongodb::options::FindOptions::builder()
+                .sort(doc! { "played_at": -1 })
+                .limit(limit as i64)
+                .build();
+            
+            let mut cursor = matches_collection
+                .find(doc! { "$or": [{ "player_a": username }, { "player_b": username }] }, options)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            let mut records = Vec::new();
+            while cursor.advance().await.map_err(|e| format!("MongoDB error: {}", e))? {
+                let doc = cursor.deserialize_current()
+                    .map_err(|e| format!("MongoDB error: {}", e))?;
+                let record: MatchRecord = from_document(doc)
+                    .map_err(|e| format!("Failed to deserialize match: {}", e))?;
+                records.push(record);
+            }
+            
+            Ok(records)
+        })
+    }
+    
+    fn get_user_by_id(&self, user_id: i64) -> Result<Option<User>, String> {
+        let db = self.get_database();
+        let users_collection = db.collection::<Document>("users");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let user_doc = users_collection
+                .find_one(doc! { "id": user_id }, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            user_doc
+                .map(|doc| from_document(doc).map_err(|e| format!("Failed to deserialize user: {}", e)))
+                .transpose()
+        })
+    }
+    
+    fn save_team(&self, team: &Team) -> Result<(), String> {
+        let db = self.get_database();
+        let teams_collection = db.collection::<Document>("teams");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            // Team IDs are stored as strings so they can be queried directly
+            let mut team_doc = to_document(team)
+                .map_err(|e| format!("Failed to serialize team: {}", e))?;
+            team_doc.insert("id", team.id.to_string());
+            
+            teams_collection
+                .replace_one(
+                    doc! { "id": team.id.to_string() },
+                    team_doc,
+                    mongodb::options::ReplaceOptions::builder().upsert(true).build()
+                )
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            Ok(())
+        })
+    }
+    
+    fn get_team(&self, team_id: &Uuid) -> Result<Option<Team>, String> {
+        self.find_team(doc! { "id": team_id.to_string() })
+    }
+    
+    fn get_team_of_user(&self, user_id: i64) -> Result<Option<Team>, String> {
+        self.find_team(doc! { "members": user_id })
+    }
+    
+    fn delete_team(&self, team_id: &Uuid) -> Result<(), String> {
+        let db = self.get_database();
+        let teams_collection = db.collection::<Document>("teams");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            teams_collection
+                .delete_one(doc! { "id": team_id.to_string() }, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            Ok(())
+        })
+    }
+    
+    fn save_alliance(&self, alliance: &Alliance) -> Result<(), String> {
+        let db = self.get_database();
+        let alliances_collection = db.collection::<Document>("alliances");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            // Alliance IDs are stored as strings so they can be queried directly
+            let mut alliance_doc = to_document(alliance)
+                .map_err(|e| format!("Failed to serialize alliance: {}", e))?;
+            alliance_doc.insert("id", alliance.id.to_string());
+            
+            alliances_collection
+                .replace_one(
+                    doc! { "id": alliance.id.to_string() },
+                    alliance_doc,
+                    mongodb::options::ReplaceOptions::builder().upsert(true).build()
+                )
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            Ok(())
+        })
+    }
+    
+    fn get_alliance(&self, alliance_id: &Uuid) -> Result<Option<Alliance>, String> {
+        self.find_alliance(doc! { "id": alliance_id.to_string() })
+    }
+    
+    fn get_alliance_of_user(&self, user_id: i64) -> Result<Option<Alliance>, String> {
+        self.find_alliance(doc! { "members": user_id })
+    }
+    
+    fn save_library(&self, library: &SharedLibrary) -> Result<(), String> {
+        let db = self.get_database();
+        let libraries_collection = db.collection::<Document>("libraries");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            // Library IDs are stored as strings so they can be queried directly
+            let mut library_doc = to_document(library)
+                .map_err(|e| format!("Failed to serialize library: {}", e))?;
+            library_doc.insert("id", library.id.to_string());
+            
+            libraries_collection
+                .replace_one(
+                    doc! { "id": library.id.to_string() },
+                    library_doc,
+                    mongodb::options::ReplaceOptions::builder().upsert(true).build()
+                )
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            Ok(())
+        })
+    }
+    
+    fn get_library(&self, library_id: &Uuid) -> Result<Option<SharedLibrary>, String> {
+        let db = self.get_database();
+        let libraries_collection = db.collection::<Document>("libraries");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let library_doc = libraries_collection
+                .find_one(doc! { "id": library_id.to_string() }, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            library_doc
+                .map(|doc| from_document(doc).map_err(|e| format!("Failed to deserialize library: {}", e)))
+                .transpose()
+        })
+    }
+    
+    fn list_libraries(&self) -> Result<Vec<SharedLibrary>, String> {
+        let db = self.get_database();
+        let libraries_collection = db.collection::<Document>("libraries");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let mut cursor = libraries_collection
+                .find(doc! {}, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            let mut libraries = Vec::new();
+            while cursor.advance().await.map_err(|e| format!("MongoDB error: {}", e))? {
+                let doc = cursor.deserialize_current()
+                    .map_err(|e| format!("MongoDB error: {}", e))?;
+                libraries.push(from_document(doc).map_err(|e| format!("Failed to deserialize library: {}", e))?);
+            }
+            
+            Ok(libraries)
+        })
+    }
+    
+    fn delete_alliance(&self, alliance_id: &Uuid) -> Result<(), String> {
+        let db = self.get_database();
+        let alliances_collection = db.collection::<Document>("alliances");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            alliances_collection
+                .delete_one(doc! { "id": alliance_id.to_string() }, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            Ok(())
+        })
+    }
+    
+    fn get_unlocked_achievements(&self, user_id: i64) -> Result<HashSet<String>, String> {
+        let db = self.get_database();
+        let users_collection = db.collection::<Document>("users");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let user_doc = users_collection
+                .find_one(doc! { "id": user_id }, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            // Unlocked achievement IDs are kept in an array on the user document
+            Ok(user_doc
+                .and_then(|doc| doc.get_array("unlocked_achievements").ok().cloned())
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|id| id.as_str().map(str::to_string))
+                .collect())
+        })
+    }
+    
+    fn unlock_achievements(&self, user_id: i64, achievement_ids: &[String]) -> Result<(), String> {
+        let db = self.get_database();
+        let users_collection = db.collection::<Document>("users");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            users_collection
+                .update_one(
+                    doc! { "id": user_id },
+                    doc! { "$addToSet": { "unlocked_achievements": { "$each": achievement_ids } } },
+                    None
+                )
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            Ok(())
+        })
+    }
+    
+    fn add_friend(&self, friendship: &Friendship) -> Result<(), String> {
+        let db = self.get_database();
+        let friendships_collection = db.collection::<Document>("friendships");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let (a, b) = (friendship.user_id, friendship.friend_id);
+            let existing = friendships_collection
+                .find_one(doc! { "$or": [{ "user_id": a, "friend_id": b }, { "user_id": b, "friend_id": a }] }, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            if existing.is_some() {
+                return Ok(());
+            }
+            
+            let friendship_doc = to_document(friendship)
+                .map_err(|e| format!("Failed to serialize friendship: {}", e))?;
+            friendships_collection
+                .insert_one(friendship_doc, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            Ok(())
+        })
+    }
+    
+    fn remove_friend(&self, user_id: i64, friend_id: i64) -> Result<(), String> {
+        let db = self.get_database();
+        let friendships_collection = db.collection::<Document>("friendships");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            friendships_collection
+                .delete_many(
+                    doc! { "$or": [{ "user_id": user_id, "friend_id": friend_id }, { "user_id": friend_id, "friend_id": user_id }] },
+                    None
+                )
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            Ok(())
+        })
+    }
+    
+    fn list_friends(&self, user_id: i64) -> Result<Vec<User>, String> {
+        let db = self.get_database();
+        let friendships_collection = db.collection::<Document>("friendships");
+        let users_collection = db.collection::<Document>("users");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let mut cursor = friendships_collection
+                .find(doc! { "$or": [{ "user_id": user_id }, { "friend_id": user_id }] }, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            let mut friend_ids = Vec::new();
+            while cursor.advance().await.map_err(|e| format!("MongoDB error: {}", e))? {
+                let doc = cursor.deserialize_current()
+                    .map_err(|e| format!("MongoDB error: {}", e))?;
+                let friendship: Friendship = from_document(doc)
+                    .map_err(|e| format!("Failed to deserialize friendship: {}", e))?;
+                friend_ids.push(if friendship.user_id == user_id { friendship.friend_id } else { friendship.user_id });
+            }
+            
+            let mut cursor = users_collection
+                .find(doc! { "id": { "$in": friend_ids } }, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            let mut friends = Vec::new();
+            while cursor.advance().await.map_err(|e| format!("MongoDB error: {}", e))? {
+                let doc = cursor.deserialize_current()
+                    .map_err(|e| format!("MongoDB error: {}", e))?;
+                friends.push(from_document(doc).map_err(|e| format!("Failed to deserialize user: {}", e))?);
+            }
+            
+            Ok(friends)
+        })
+    }
+    
+    fn create_follow_request(&self, request: &FollowRequest) -> Result<(), String> {
+        let db = self.get_database();
+        let requests_collection = db.collection::<Document>("follow_requests");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let request_doc = to_document(request)
+                .map_err(|e| format!("Failed to serialize friend request: {}", e))?;
+            requests_collection
+                .replace_one(
+                    doc! { "from_id": request.from_id, "to_id": request.to_id },
+                    request_doc,
+                    mongodb::options::ReplaceOptions::builder().upsert(true).build()
+                )
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            Ok(())
+        })
+    }
+    
+    fn delete_follow_request(&self, from_id: i64, to_id: i64) -> Result<bool, String> {
+        let db = self.get_database();
+        let requests_collection = db.collection::<Document>("follow_requests");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let result = requests_collection
+                .delete_one(doc! { "from_id": from_id, "to_id": to_id }, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            Ok(result.deleted_count > 0)
+        })
+    }
+    
+    fn list_follow_requests(&self, to_id: i64) -> Result<Vec<FollowRequest>, String> {
+        let db = self.get_database();
+        let requests_collection = db.collection::<Document>("follow_requests");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let mut cursor = requests_collection
+                .find(doc! { "to_id": to_id }, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            let mut requests = Vec::new();
+            while cursor.advance().await.map_err(|e| format!("MongoDB error: {}", e))? {
+                let doc = cursor.deserialize_current()
+                    .map_err(|e| format!("MongoDB error: {}", e))?;
+                requests.push(from_document(doc).map_err(|e| format!("Failed to deserialize friend request: {}", e))?);
+            }
+            
+            Ok(requests)
+        })
+    }
+    
+    fn has_active_session(&self, user_id: i64) -> Result<bool, String> {
+        let db = self.get_database();
+        let sessions_collection = db.collection::<Document>("sessions");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let now = bson::DateTime::from_millis(get_unix_timestamp() * 1000);
+            let session_doc = sessions_collection
+                .find_one(doc! { "user_id": user_id, "expires_at": { "$gte": now } }, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            Ok(session_doc.is_some())
+        })
+    }
+    
+    fn list_users(&self, offset: u32, limit: u32, filter: &UserFilter) -> Result<(Vec<User>, u32), String> {
+        let db = self.get_database();
+        let users_collection = db.collection::<Document>("users");
+        let query = match &filter.username_prefix {
+            Some(prefix) => doc! { "username": { "$regex": format!("^{}", escape_regex(prefix)), "$options": "i" } },
+            None => doc! {},
+        };
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let total = users_collection
+                .count_documents(query.clone(), None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            if limit == 0 {
+                return Ok((Vec::new(), total as u32));
+            }
+            
+            let options = FindOptions::builder()
+                .sort(doc! { "id": 1 })
+                .skip(offset as u64)
+                .limit(limit as i64)
+                .build();
+            let mut cursor = users_collection
+                .find(query, options)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            let mut users = Vec::new();
+            while cursor.advance().await.map_err(|e| format!("MongoDB error: {}", e))? {
+                let doc = cursor.deserialize_current()
+                    .map_err(|e| format!("MongoDB error: {}", e))?;
+                users.push(from_document(doc).map_err(|e| format!("Failed to deserialize user: {}", e))?);
+            }
+            
+            Ok((users, total as u32))
+        })
+    }
+    
+    fn create_invite(&self, invite: &Invite) -> Result<(), String> {
+        let db = self.get_database();
+        let invites_collection = db.collection::<Document>("invites");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let existing = invites_collection
+                .find_one(doc! { "code": &invite.code }, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            if existing.is_some() {
+                return Err("Invite code already exists".to_string());
+            }
+            
+            let invite_doc = to_document(invite)
+                .map_err(|e| format!("Failed to serialize invite: {}", e))?;
+            invites_collection
+                .insert_one(invite_doc, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            Ok(())
+        })
+    }
+    
+    fn list_invites(&self) -> Result<Vec<Invite>, String> {
+        let db = self.get_database();
+        let invites_collection = db.collection::<Document>("invites");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let options = FindOptions::builder()
+                .sort(doc! { "created_at": 1, "code": 1 })
+                .build();
+            let mut cursor = invites_collection
+                .find(doc! {}, options)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            let mut invites = Vec::new();
+            while cursor.advance().await.map_err(|e| format!("MongoDB error: {}", e))? {
+                let doc = cursor.deserialize_current()
+                    .map_err(|e| format!("MongoDB error: {}", e))?;
+                invites.push(from_document(doc).map_err(|e| format!("Failed to deserialize invite: {}", e))?);
+            }
+            
+            Ok(invites)
+        })
+    }
+    
+    fn revoke_invite(&self, code: &str) -> Result<bool, String> {
+        let db = self.get_database();
+        let invites_collection = db.collection::<Document>("invites");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            let result = invites_collection
+                .delete_one(doc! { "code": code }, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            
+            Ok(result.deleted_count > 0)
+        })
+    }
+    
+    fn consume_invite(&self, code: &str, now: i64) -> Result<Invite, String> {
+        let db = self.get_database();
+        let invites_collection = db.collection::<Document>("invites");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            // The filter and the decrement are applied as one operation, so concurrent
+            // registrations cannot take the same last use
+            let usable = doc! {
+                "code": code,
+                "uses_left": { "$gt": 0 },
+                "$or": [{ "expires_at": null }, { "expires_at": { "$gt": now } }],
+            };
+            let consumed = invites_collection
+                .find_one_and_update(
+                    usable,
+                    doc! { "$inc": { "uses_left": -1 } },
+                    mongodb::options::FindOneAndUpdateOptions::builder()
+                        .return_document(mongodb::options::ReturnDocument::After)
+                        .build()
+                )
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            if let Some(doc) = consumed {
+                return from_document(doc).map_err(|e| format!("Failed to deserialize invite: {}", e));
+            }
+            
+            let existing = invites_collection
+                .find_one(doc! { "code": code }, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?
+                .ok_or("Invite code not found")?;
+            let invite: Invite = from_document(existing)
+                .map_err(|e| format!("Failed to deserialize invite: {}", e))?;
+            Err(unusable_invite_error(&invite, now))
+        })
+    }
+    
+    fn release_invite(&self, code: &str) -> Result<(), String> {
+        let db = self.get_database();
+        let invites_collection = db.collection::<Document>("invites");
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            invites_collection
+                .update_one(
+                    doc! { "code": code, "$expr": { "$lt": ["$uses_left", "$max_uses"] } },
+                    doc! { "$inc": { "uses_left": 1 } },
+                    None,
+                )
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            Ok(())
+        })
+    }
+    
+    fn ping(&self) -> Result<(), String> {
+        let db = self.get_database();
+        
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+        
+        rt.block_on(async {
+            db.run_command(doc! { "ping": 1 }, None)
+                .await
+                .map_err(|e| format!("MongoDB error: {}", e))?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failing_backend_opens_the_circuit() {
+        let db = AuthDatabase::new(DatabaseBackend::InMemory).unwrap()
+            .with_circuit_breaker(2, Duration::from_millis(20));
+        let disk_full = || db.call(|_| Err::<(), _>("disk full".to_string()));
+        assert_eq!(disk_full(), Err("disk full".to_string()));
+        assert_eq!(db.circuit_status(), CircuitState::Closed);
+        assert_eq!(disk_full(), Err("disk full".to_string()));
+        assert_eq!(db.circuit_status(), CircuitState::Open);
+
+        // Calls fail fast without reaching the backend
+        assert_eq!(db.create_user("alice", "hash").unwrap_err(), UNAVAILABLE);
+        assert_eq!(db.get_user_by_username("alice").unwrap_err(), UNAVAILABLE);
+
+        // The first call after the timeout closes the circuit again
+        std::thread::sleep(Duration::from_millis(25));
+        db.ping().unwrap();
+        assert_eq!(db.circuit_status(), CircuitState::Closed);
+        assert!(db.create_user("alice", "hash").is_ok());
+        assert!(db.create_user("alice", "hash").is_err());
+        assert_eq!(db.circuit_status(), CircuitState::Closed);
+    }
 }
@@ -0,0 +1,115 @@
+//! Outgoing email
+//!
+//! The server emails players to verify their address (see
+//! [`AuthService::send_verification_email`]). Emails go through an [`EmailSender`]:
+//! [`SmtpEmailSender`] relays them to an SMTP server, [`MockEmailSender`] keeps them in
+//! memory for tests, and [`NoEmailSender`] refuses them when SMTP is not configured.
+//!
+//! [`AuthService::send_verification_email`]: crate::auth::AuthService::send_verification_email
+
+use std::sync::Mutex;
+
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+
+use crate::config::ServerConfig;
+
+/// Time a player has to follow an email verification link, in seconds
+pub const EMAIL_VERIFICATION_TTL_SECS: i64 = 24 * 3600;
+
+/// An email sent by the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    /// Recipient address
+    pub to: String,
+    /// Subject line
+    pub subject: String,
+    /// Plain text body
+    pub body: String,
+}
+
+/// Delivers the server's emails
+///
+/// Sending blocks until the email is handed over; call it off the async runtime.
+pub trait EmailSender: Send + Sync {
+    /// Send an email
+    fn send(&self, email: &Email) -> Result<(), String>;
+}
+
+/// Sender used when no SMTP server is configured: every email fails
+#[derive(Debug, Default)]
+pub struct NoEmailSender;
+
+impl EmailSender for NoEmailSender {
+    fn send(&self, _email: &Email) -> Result<(), String> {
+        Err("Email is not configured on this server".to_string())
+    }
+}
+
+/// Sender relaying emails to an SMTP server (STARTTLS)
+pub struct SmtpEmailSender {
+    transport: SmtpTransport,
+    from: Mailbox,
+}
+
+impl SmtpEmailSender {
+    /// Sender for the configured SMTP server, if `smtp_host` and `email_from` are set
+    ///
+    /// The password of `smtp_username` is read from `GEEKCRAFT_SMTP_PASSWORD`, keeping it
+    /// out of [`ServerConfig`] so the admin configuration endpoint never shows it.
+    pub fn from_config(config: &ServerConfig) -> Result<Option<Self>, String> {
+        let (Some(host), Some(from)) = (&config.smtp_host, &config.email_from) else {
+            return Ok(None);
+        };
+        let from: Mailbox = from.parse().map_err(|e| format!("Invalid email_from {}: {}", from, e))?;
+        let mut transport = SmtpTransport::starttls_relay(host)
+            .map_err(|e| format!("Invalid SMTP server {}: {}", host, e))?
+            .port(config.smtp_port);
+        if let Some(username) = &config.smtp_username {
+            let password = std::env::var("GEEKCRAFT_SMTP_PASSWORD").unwrap_or_default();
+            transport = transport.credentials(Credentials::new(username.clone(), password));
+        }
+        Ok(Some(Self { transport: transport.build(), from }))
+    }
+}
+
+impl EmailSender for SmtpEmailSender {
+    fn send(&self, email: &Email) -> Result<(), String> {
+        let to: Mailbox = email.to.parse().map_err(|e| format!("Invalid address {}: {}", email.to, e))?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(email.subject.clone())
+            .body(email.body.clone())
+            .map_err(|e| format!("Could not build the email: {}", e))?;
+        self.transport.send(&message)
+            .map(|_| ())
+            .map_err(|e| format!("Could not send the email: {}", e))
+    }
+}
+
+/// Sender keeping emails in memory, for tests
+#[derive(Debug, Default)]
+pub struct MockEmailSender {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl MockEmailSender {
+    /// No email sent yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Emails sent so far, oldest first
+    pub fn sent(&self) -> Vec<Email> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+impl EmailSender for MockEmailSender {
+    fn send(&self, email: &Email) -> Result<(), String> {
+        self.sent.lock().unwrap().push(email.clone());
+        Ok(())
+    }
+}
@@ -6,7 +6,14 @@
 pub mod models;
 pub mod service;
 pub mod database;
+pub mod achievements;
+pub mod oauth;
+pub mod email;
+pub mod circuit;
+pub mod audit;
 
-pub use models::{User, Session};
+pub use models::{User, Session, MatchOutcome, MatchRecord, Team, Alliance, Friendship, FollowRequest, Invite, RegistrationMode};
 pub use service::AuthService;
-pub use database::{AuthDatabase, DatabaseBackend};
+pub use database::{AuthDatabase, DatabaseBackend, UserFilter};
+pub use circuit::{CircuitBreaker, CircuitState};
+pub use achievements::{Achievement, AchievementCondition, PlayerStats};
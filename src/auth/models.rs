@@ -1,6 +1,17 @@
 //! Data models for authentication
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::game::zone::ResourceType;
+
+/// Rating of a new player
+pub const DEFAULT_RATING: i32 = 1200;
+
+fn default_rating() -> i32 {
+    DEFAULT_RATING
+}
 
 /// User account
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,11 +20,130 @@ pub struct User {
     pub id: i64,
     /// Username
     pub username: String,
-    /// Hashed password (not serialized in responses)
+    /// Hashed password (not serialized in responses); empty for accounts signing in with OAuth
     #[serde(skip_serializing)]
     pub password_hash: String,
     /// Account creation timestamp (Unix epoch)
     pub created_at: i64,
+    /// ELO rating
+    #[serde(default = "default_rating")]
+    pub rating: i32,
+    /// Identity provider the account signs in with (`github`, `discord`), empty for password accounts
+    #[serde(default)]
+    pub oauth_provider: String,
+    /// ID of the account at its identity provider
+    #[serde(default)]
+    pub oauth_id: String,
+    /// Email address the account can be recovered with
+    #[serde(default)]
+    pub email: Option<String>,
+    /// Whether the player proved they own `email`
+    #[serde(default)]
+    pub email_verified: bool,
+    /// Pending email verification token (not serialized in responses)
+    #[serde(default, skip_serializing)]
+    pub email_verification_token: Option<String>,
+    /// When the pending verification token expires (Unix epoch)
+    #[serde(default, skip_serializing)]
+    pub email_verification_expires_at: Option<i64>,
+}
+
+/// Result of a match between two players
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchOutcome {
+    /// Player A won
+    PlayerAWins,
+    /// Player B won
+    PlayerBWins,
+    /// Neither player won
+    Draw,
+}
+
+/// A recorded match with the players' ratings after it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchRecord {
+    /// Username of player A
+    pub player_a: String,
+    /// Username of player B
+    pub player_b: String,
+    /// Match result
+    pub outcome: MatchOutcome,
+    /// Rating of player A after the match
+    pub rating_a: i32,
+    /// Rating of player B after the match
+    pub rating_b: i32,
+    /// When the match was recorded (Unix epoch)
+    pub played_at: i64,
+}
+
+/// A team of players sharing a resource pool and visibility
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Team {
+    /// Unique team identifier
+    pub id: Uuid,
+    /// Team name
+    pub name: String,
+    /// User IDs of the members (the first one created the team)
+    pub members: Vec<i64>,
+    /// Resources shared by the members (as of the last sync with the world)
+    #[serde(default)]
+    pub resource_pool: HashMap<ResourceType, u32>,
+}
+
+/// An alliance of players sharing visibility, who cannot damage each other
+///
+/// Unlike a [`Team`], allies keep their own resources, and players join by accepting an invitation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Alliance {
+    /// Unique alliance identifier
+    pub id: Uuid,
+    /// Alliance name
+    pub name: String,
+    /// User IDs of the members (the first one created the alliance)
+    pub members: Vec<i64>,
+    /// User IDs of the players invited but not yet members
+    #[serde(default)]
+    pub invited: Vec<i64>,
+}
+
+/// A version of a script library shared by a player
+///
+/// Once approved by an admin, the latest approved version of each library can be
+/// imported by every bot as `@community/<name>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedLibrary {
+    /// Unique identifier of this version
+    pub id: Uuid,
+    /// Library name (imported as `@community/<name>`)
+    pub name: String,
+    /// User ID of the author (the only player who can publish new versions)
+    pub author_id: i64,
+    /// JavaScript source
+    pub code: String,
+    /// Version number (starting at 1)
+    pub version: u32,
+    /// Whether an admin approved this version
+    pub approved: bool,
+}
+
+/// A friendship between two users (symmetric; stored once)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Friendship {
+    /// User who sent the friend request
+    pub user_id: i64,
+    /// User who accepted it
+    pub friend_id: i64,
+    /// When the request was accepted (Unix epoch)
+    pub created_at: i64,
+}
+
+/// A pending friend request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FollowRequest {
+    /// User who sent the request
+    pub from_id: i64,
+    /// User who can accept it
+    pub to_id: i64,
 }
 
 /// Active session
@@ -31,17 +161,54 @@ pub struct Session {
     pub expires_at: i64,
 }
 
+/// Who can create an account
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegistrationMode {
+    /// Anyone can register
+    #[default]
+    Open,
+    /// Registering needs an invite code created by an admin
+    Invite,
+    /// Nobody can register; existing accounts can still log in
+    Closed,
+}
+
+/// A code letting players register while registration is by invite
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Invite {
+    /// The code, given as `invite_code` when registering
+    pub code: String,
+    /// Username of the admin who created it
+    pub created_by: String,
+    /// Creation timestamp (Unix epoch)
+    pub created_at: i64,
+    /// Number of registrations it allows in total
+    pub max_uses: u32,
+    /// Number of registrations it still allows
+    pub uses_left: u32,
+    /// When it stops being accepted (Unix epoch), if ever
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+}
+
 /// Registration request
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct RegisterRequest {
     /// Desired username
     pub username: String,
     /// User password (will be hashed)
     pub password: String,
+    /// Email address to verify and recover the account with
+    #[serde(default)]
+    pub email: Option<String>,
+    /// Invite code, needed while registration is by invite
+    #[serde(default)]
+    pub invite_code: Option<String>,
 }
 
 /// Login request
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct LoginRequest {
     /// Username
     pub username: String,
@@ -50,7 +217,7 @@ pub struct LoginRequest {
 }
 
 /// Authentication response
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct AuthResponse {
     /// Whether the operation succeeded
     pub success: bool,
@@ -0,0 +1,232 @@
+//! OAuth2 sign-in
+//!
+//! Players can sign in with a GitHub or Discord account through the authorization code
+//! flow: `POST /api/auth/oauth/:provider/start` redirects to the provider, which sends
+//! the player back to `GET /api/auth/oauth/:provider/callback` with a code. The server
+//! exchanges the code for an access token, fetches the account's ID and name, and logs
+//! the matching user in (see [`AuthService::oauth_login`]).
+//!
+//! Each flow carries a random `state` that the callback must return before
+//! [`STATE_TTL_SECS`]; it can only be used once.
+//!
+//! [`AuthService::oauth_login`]: crate::auth::AuthService::oauth_login
+
+use std::collections::HashMap;
+
+use dashmap::DashMap;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::config::ServerConfig;
+
+/// Time a player has to complete a flow after starting it, in seconds
+pub const STATE_TTL_SECS: i64 = 600;
+
+/// An OAuth2 identity provider
+#[derive(Clone)]
+pub struct OAuthProvider {
+    /// Provider name, as in the route paths and [`User::oauth_provider`](crate::auth::User::oauth_provider)
+    pub name: &'static str,
+    /// Client ID of the server's OAuth application
+    pub client_id: String,
+    /// Client secret of the server's OAuth application
+    pub client_secret: String,
+    /// Page the player is sent to to approve the sign-in
+    pub authorize_url: String,
+    /// Endpoint exchanging a code for an access token
+    pub token_url: String,
+    /// Endpoint returning the signed-in account
+    pub user_url: String,
+    /// Scope requested
+    pub scope: &'static str,
+    /// Field of the account holding its name
+    pub login_field: &'static str,
+}
+
+impl OAuthProvider {
+    /// GitHub OAuth app
+    pub fn github(client_id: String, client_secret: String) -> Self {
+        Self {
+            name: "github",
+            client_id,
+            client_secret,
+            authorize_url: "https://github.com/login/oauth/authorize".to_string(),
+            token_url: "https://github.com/login/oauth/access_token".to_string(),
+            user_url: "https://api.github.com/user".to_string(),
+            scope: "read:user",
+            login_field: "login",
+        }
+    }
+
+    /// Discord application
+    pub fn discord(client_id: String, client_secret: String) -> Self {
+        Self {
+            name: "discord",
+            client_id,
+            client_secret,
+            authorize_url: "https://discord.com/oauth2/authorize".to_string(),
+            token_url: "https://discord.com/api/oauth2/token".to_string(),
+            user_url: "https://discord.com/api/users/@me".to_string(),
+            scope: "identify",
+            login_field: "username",
+        }
+    }
+
+    /// Use other endpoints (e.g. GitHub Enterprise, or a mock in tests)
+    pub fn with_endpoints(mut self, authorize_url: &str, token_url: &str, user_url: &str) -> Self {
+        self.authorize_url = authorize_url.to_string();
+        self.token_url = token_url.to_string();
+        self.user_url = user_url.to_string();
+        self
+    }
+
+    /// URL of the provider's approval page for a flow
+    pub fn authorization_url(&self, state: &str, redirect_uri: &str) -> Result<String, String> {
+        reqwest::Url::parse_with_params(&self.authorize_url, [
+            ("response_type", "code"),
+            ("client_id", self.client_id.as_str()),
+            ("redirect_uri", redirect_uri),
+            ("scope", self.scope),
+            ("state", state),
+        ])
+        .map(String::from)
+        .map_err(|e| format!("Invalid {} authorize URL: {}", self.name, e))
+    }
+
+    /// Exchange an authorization code for an access token
+    pub async fn exchange_code(&self, http: &reqwest::Client, code: &str, redirect_uri: &str) -> Result<String, String> {
+        #[derive(Deserialize)]
+        struct TokenResponse {
+            access_token: Option<String>,
+            error: Option<String>,
+            error_description: Option<String>,
+        }
+
+        let response: TokenResponse = http.post(&self.token_url)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.as_str()),
+                ("code", code),
+                ("redirect_uri", redirect_uri),
+            ])
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| format!("{} token request failed: {}", self.name, e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid {} token response: {}", self.name, e))?;
+
+        match (response.access_token, response.error) {
+            (Some(token), None) => Ok(token),
+            (_, error) => Err(format!(
+                "{} refused the code: {}",
+                self.name,
+                response.error_description.or(error).unwrap_or_else(|| "no access token".to_string())
+            )),
+        }
+    }
+
+    /// ID and name of the account an access token belongs to
+    pub async fn fetch_account(&self, http: &reqwest::Client, access_token: &str) -> Result<(String, String), String> {
+        let account: serde_json::Value = http.get(&self.user_url)
+            .bearer_auth(access_token)
+            .header(reqwest::header::ACCEPT, "application/json")
+            // GitHub rejects requests without a user agent
+            .header(reqwest::header::USER_AGENT, concat!("GeekCraft/", env!("CARGO_PKG_VERSION")))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| format!("{} account request failed: {}", self.name, e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid {} account response: {}", self.name, e))?;
+
+        // GitHub IDs are numbers, Discord IDs strings
+        let id = match &account["id"] {
+            serde_json::Value::Number(id) => id.to_string(),
+            serde_json::Value::String(id) if !id.is_empty() => id.clone(),
+            _ => return Err(format!("{} account has no ID", self.name)),
+        };
+        let login = account[self.login_field].as_str()
+            .ok_or_else(|| format!("{} account has no {}", self.name, self.login_field))?;
+        Ok((id, login.to_string()))
+    }
+}
+
+/// Configured providers and the flows under way
+#[derive(Default)]
+pub struct OAuth {
+    providers: HashMap<&'static str, OAuthProvider>,
+    /// Pending flows (state -> provider name and expiry)
+    states: DashMap<String, (&'static str, i64)>,
+    http: reqwest::Client,
+}
+
+impl OAuth {
+    /// No providers configured
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Providers enabled by the configuration
+    ///
+    /// A provider is enabled when its client ID is configured and its client secret is
+    /// set in the environment (`GEEKCRAFT_GITHUB_CLIENT_SECRET`,
+    /// `GEEKCRAFT_DISCORD_CLIENT_SECRET`). Secrets are kept out of [`ServerConfig`] so the
+    /// admin configuration endpoint never shows them.
+    pub fn from_config(config: &ServerConfig) -> Self {
+        let providers = [
+            (&config.github_client_id, "GEEKCRAFT_GITHUB_CLIENT_SECRET", OAuthProvider::github as fn(String, String) -> OAuthProvider),
+            (&config.discord_client_id, "GEEKCRAFT_DISCORD_CLIENT_SECRET", OAuthProvider::discord),
+        ];
+        let mut oauth = Self::new();
+        for (client_id, secret_var, provider) in providers {
+            let Some(client_id) = client_id.clone() else { continue };
+            match std::env::var(secret_var).ok().filter(|secret| !secret.is_empty()) {
+                Some(secret) => oauth = oauth.with_provider(provider(client_id, secret)),
+                None => log::warn!("{} is not set, OAuth sign-in with client {} is disabled", secret_var, client_id),
+            }
+        }
+        oauth
+    }
+
+    /// Enable a provider (replacing any with the same name)
+    pub fn with_provider(mut self, provider: OAuthProvider) -> Self {
+        self.providers.insert(provider.name, provider);
+        self
+    }
+
+    /// A configured provider, by name
+    pub fn provider(&self, name: &str) -> Option<&OAuthProvider> {
+        self.providers.get(name)
+    }
+
+    /// Names of the configured providers
+    pub fn provider_names(&self) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = self.providers.keys().copied().collect();
+        names.sort();
+        names
+    }
+
+    /// HTTP client used to reach the providers
+    pub fn http(&self) -> &reqwest::Client {
+        &self.http
+    }
+
+    /// Start a flow with a provider, returning its `state`
+    pub fn start(&self, provider: &OAuthProvider, now: i64) -> String {
+        self.states.retain(|_, (_, expires_at)| *expires_at > now);
+        let state = Uuid::new_v4().simple().to_string();
+        self.states.insert(state.clone(), (provider.name, now + STATE_TTL_SECS));
+        state
+    }
+
+    /// Complete a flow: whether `state` was issued for this provider and has not expired (it is consumed)
+    pub fn finish(&self, provider: &OAuthProvider, state: &str, now: i64) -> bool {
+        self.states.remove(state)
+            .is_some_and(|(_, (name, expires_at))| name == provider.name && expires_at > now)
+    }
+}
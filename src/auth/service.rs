@@ -1,26 +1,131 @@
 //! Authentication service
 
-use super::database::AuthDatabase;
-use super::models::{Session, AuthResponse};
+use super::achievements::{all_achievements, Achievement, PlayerStats};
+use super::audit::{AuditStore, InMemoryAuditStore};
+use super::circuit::CircuitState;
+use super::database::{AuthDatabase, UserFilter};
+use super::email::{Email, EmailSender, EMAIL_VERIFICATION_TTL_SECS};
+use super::models::{Alliance, Session, AuthResponse, FollowRequest, Friendship, Invite, MatchOutcome, MatchRecord, RegistrationMode, SharedLibrary, Team, User};
+use crate::scripting::bundle::MAX_MODULE_SIZE;
+use crate::utils::retry::{retry_with_backoff, DEFAULT_BASE_DELAY, DEFAULT_MAX_ATTEMPTS};
 use uuid::Uuid;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-/// Session duration in seconds (24 hours)
-const SESSION_DURATION: i64 = 86400;
+/// ELO K-factor (maximum rating change per match)
+const ELO_K_FACTOR: f64 = 32.0;
+
+/// Maximum number of players in a team
+pub const MAX_TEAM_SIZE: usize = 4;
+
+/// Shortest time between two extensions of the same session, in seconds
+pub const SESSION_TOUCH_INTERVAL_SECS: i64 = 300;
+
+/// Start of the message refusing to create an account for an identity provider
+/// account while registration is not open
+pub const OAUTH_SIGNUP_REFUSED: &str = "Registration is not open on this server";
 
 /// Authentication service
 pub struct AuthService {
     db: Arc<AuthDatabase>,
+    max_alliance_size: usize,
+    session_duration_secs: i64,
+    session_sliding_percent: u32,
+    session_max_lifetime_secs: i64,
+    retry_attempts: u32,
+    retry_base_delay: Duration,
+    audit: Arc<dyn AuditStore>,
+    /// Held while a match result reads and updates the players' ratings
+    match_lock: Mutex<()>,
 }
 
 impl AuthService {
     /// Create a new authentication service
     pub fn new(db: Arc<AuthDatabase>) -> Self {
-        AuthService { db }
+        AuthService {
+            db,
+            max_alliance_size: crate::config::MAX_ALLIANCE_SIZE,
+            session_duration_secs: crate::config::SESSION_DURATION_SECS,
+            session_sliding_percent: 0,
+            session_max_lifetime_secs: crate::config::SESSION_MAX_LIFETIME_SECS,
+            retry_attempts: DEFAULT_MAX_ATTEMPTS,
+            retry_base_delay: DEFAULT_BASE_DELAY,
+            audit: Arc::new(InMemoryAuditStore::new()),
+            match_lock: Mutex::new(()),
+        }
     }
     
+    /// Set the maximum number of players in an alliance
+    pub fn with_max_alliance_size(mut self, max_alliance_size: usize) -> Self {
+        self.max_alliance_size = max_alliance_size.max(1);
+        self
+    }
+
+    /// Set the lifetime of login sessions, in seconds
+    pub fn with_session_duration(mut self, session_duration_secs: i64) -> Self {
+        self.session_duration_secs = session_duration_secs.max(1);
+        self
+    }
+
+    /// Extend sessions used after `percent` of their lifetime by a full session duration
+    /// (0 keeps fixed expiration)
+    pub fn with_sliding_expiration(mut self, percent: u32) -> Self {
+        self.session_sliding_percent = percent.min(100);
+        self
+    }
+
+    /// Set the age after which sessions expire however they are extended, in seconds
+    pub fn with_max_session_lifetime(mut self, max_lifetime_secs: i64) -> Self {
+        self.session_max_lifetime_secs = max_lifetime_secs.max(1);
+        self
+    }
+    
+    /// Set how often user and session writes and session lookups are attempted when the
+    /// database fails transiently, and the wait before the first retry
+    pub fn with_retry(mut self, max_attempts: u32, base_delay: Duration) -> Self {
+        self.retry_attempts = max_attempts.max(1);
+        self.retry_base_delay = base_delay;
+        self
+    }
+
+    /// Keep the audit log in the given store (in memory by default)
+    pub fn with_audit_store(mut self, audit: Arc<dyn AuditStore>) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// Store of the audit log
+    pub fn audit_store(&self) -> Arc<dyn AuditStore> {
+        self.audit.clone()
+    }
+
+    /// Run a database call, retrying transient failures
+    fn retry<T>(&self, operation: impl FnMut() -> Result<T, String>) -> Result<T, String> {
+        retry_with_backoff(operation, self.retry_attempts, self.retry_base_delay)
+    }
+
     /// Register a new user
-    pub fn register(&self, username: &str, password: &str) -> AuthResponse {
+    ///
+    /// In [`RegistrationMode::Invite`], one use of `invite_code` is taken (and given back if
+    /// the account cannot be created); in [`RegistrationMode::Closed`] registration always fails.
+    pub fn register(&self, username: &str, password: &str, mode: RegistrationMode, invite_code: Option<&str>) -> AuthResponse {
+        let failure = |message: String| AuthResponse {
+            success: false,
+            message,
+            token: None,
+            username: None,
+        };
+        let invite_code = match (mode, invite_code.map(str::trim).filter(|code| !code.is_empty())) {
+            (RegistrationMode::Closed, _) => {
+                return failure("Registration is closed on this server; existing players can still log in".to_string());
+            }
+            (RegistrationMode::Invite, None) => {
+                return failure("Registration on this server requires an invite code".to_string());
+            }
+            (RegistrationMode::Invite, Some(code)) => Some(code),
+            (RegistrationMode::Open, _) => None,
+        };
+
         // Validate username
         if username.trim().is_empty() || username.len() < 3 || username.len() > 32 {
             return AuthResponse {
@@ -51,21 +156,37 @@ impl AuthService {
             };
         }
         
+        if let Some(code) = invite_code {
+            // Taken names would waste a use of the code
+            match self.db.get_user_by_username(username) {
+                Ok(None) => {}
+                Ok(Some(_)) => return failure("Username already exists".to_string()),
+                Err(e) => return failure(e),
+            }
+            if let Err(e) = self.db.consume_invite(code, chrono::Utc::now().timestamp()) {
+                return failure(e);
+            }
+        }
+        let release_invite = || {
+            if let Some(code) = invite_code {
+                if let Err(e) = self.db.release_invite(code) {
+                    log::warn!("Could not give back a use of invite code {}: {}", code, e);
+                }
+            }
+        };
+        
         // Hash password
         let password_hash = match bcrypt::hash(password, bcrypt::DEFAULT_COST) {
             Ok(hash) => hash,
             Err(e) => {
                 log::error!("Failed to hash password: {}", e);
-                return AuthResponse {
-                    success: false,
-                    message: "Internal error".to_string(),
-                    token: None,
-                    username: None,
-                };
+                release_invite();
+                return failure("Internal error".to_string());
             }
         };
         
-        // Create user
+        // Create user (not retried: a failure reported after the row was written would
+        // then register the name twice or fail as taken)
         match self.db.create_user(username, &password_hash) {
             Ok(_) => AuthResponse {
                 success: true,
@@ -73,13 +194,42 @@ impl AuthService {
                 token: None,
                 username: Some(username.to_string()),
             },
-            Err(e) => AuthResponse {
-                success: false,
-                message: e,
-                token: None,
-                username: None,
-            },
+            Err(e) => {
+                release_invite();
+                failure(e)
+            }
+        }
+    }
+
+    /// Create an invite code allowing `max_uses` registrations until `expires_at` (Unix epoch), if set
+    pub fn create_invite(&self, created_by: &str, max_uses: u32, expires_at: Option<i64>) -> Result<Invite, String> {
+        if max_uses == 0 {
+            return Err("An invite code must allow at least one use".to_string());
+        }
+        let now = chrono::Utc::now().timestamp();
+        if expires_at.is_some_and(|expires_at| expires_at <= now) {
+            return Err("Invite expiry must be in the future".to_string());
         }
+        let invite = Invite {
+            code: Uuid::new_v4().simple().to_string(),
+            created_by: created_by.to_string(),
+            created_at: now,
+            max_uses,
+            uses_left: max_uses,
+            expires_at,
+        };
+        self.retry(|| self.db.create_invite(&invite))?;
+        Ok(invite)
+    }
+
+    /// Every invite code, oldest first (including used up and expired ones)
+    pub fn list_invites(&self) -> Result<Vec<Invite>, String> {
+        self.db.list_invites()
+    }
+
+    /// Delete an invite code; returns whether it existed
+    pub fn revoke_invite(&self, code: &str) -> Result<bool, String> {
+        self.retry(|| self.db.revoke_invite(code))
     }
     
     /// Login a user
@@ -106,35 +256,19 @@ impl AuthService {
             }
         };
         
+        // Accounts created through an identity provider have no password
+        if user.password_hash.is_empty() {
+            return AuthResponse {
+                success: false,
+                message: format!("This account signs in with {}", user.oauth_provider),
+                token: None,
+                username: None,
+            };
+        }
+        
         // Verify password
         match bcrypt::verify(password, &user.password_hash) {
-            Ok(true) => {
-                // Create session token
-                let token = Uuid::new_v4().to_string();
-                let now = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .expect("System clock is before Unix epoch")
-                    .as_secs() as i64;
-                let expires_at = now + SESSION_DURATION;
-                
-                // Store session
-                if let Err(e) = self.db.create_session(&token, user.id, expires_at) {
-                    log::error!("Failed to create session: {}", e);
-                    return AuthResponse {
-                        success: false,
-                        message: "Internal error".to_string(),
-                        token: None,
-                        username: None,
-                    };
-                }
-                
-                AuthResponse {
-                    success: true,
-                    message: "Login successful".to_string(),
-                    token: Some(token),
-                    username: Some(user.username),
-                }
-            }
+            Ok(true) => self.start_session(user),
             Ok(false) => AuthResponse {
                 success: false,
                 message: "Invalid username or password".to_string(),
@@ -153,6 +287,99 @@ impl AuthService {
         }
     }
     
+    /// Log in with an identity provider account, creating its user on first login
+    ///
+    /// `login` is the account's name at the provider; a new user gets it as username,
+    /// made valid and unique if needed. Users are only created while `mode` is
+    /// [`RegistrationMode::Open`]: there is no invite code to check on this path.
+    pub fn oauth_login(&self, provider: &str, oauth_id: &str, login: &str, mode: RegistrationMode) -> AuthResponse {
+        let existing = match self.db.get_user_by_oauth(provider, oauth_id) {
+            Ok(existing) => existing,
+            Err(e) => {
+                log::error!("Database error: {}", e);
+                return AuthResponse {
+                    success: false,
+                    message: "Internal error".to_string(),
+                    token: None,
+                    username: None,
+                };
+            }
+        };
+        let user = match existing {
+            Some(user) => user,
+            None if mode != RegistrationMode::Open => {
+                return AuthResponse {
+                    success: false,
+                    message: format!("{}: new players cannot sign up with {}", OAUTH_SIGNUP_REFUSED, provider),
+                    token: None,
+                    username: None,
+                };
+            }
+            None => match self.create_oauth_user(provider, oauth_id, login) {
+                Ok(user) => {
+                    log::info!("User {} registered with {}", user.username, provider);
+                    user
+                }
+                Err(e) => {
+                    return AuthResponse {
+                        success: false,
+                        message: e,
+                        token: None,
+                        username: None,
+                    };
+                }
+            },
+        };
+        self.start_session(user)
+    }
+
+    /// Create the user of an identity provider account, under the first free username derived from `login`
+    fn create_oauth_user(&self, provider: &str, oauth_id: &str, login: &str) -> Result<User, String> {
+        let mut base: String = login.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+            .take(28)
+            .collect();
+        if base.len() < 3 {
+            base = format!("{}_{}", provider, base);
+        }
+
+        for suffix in 1..=100 {
+            let username = if suffix == 1 { base.clone() } else { format!("{}-{}", base, suffix) };
+            if self.db.get_user_by_username(&username)?.is_none() {
+                return self.db.create_oauth_user(&username, provider, oauth_id);
+            }
+        }
+        Err(format!("No free username for {}", login))
+    }
+
+    /// Open a session for a user who just proved their identity
+    fn start_session(&self, user: User) -> AuthResponse {
+        let token = Uuid::new_v4().to_string();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("System clock is before Unix epoch")
+            .as_secs() as i64;
+        let expires_at = now + self.session_duration_secs;
+        
+        // Store session
+        if let Err(e) = self.retry(|| self.db.create_session(&token, user.id, expires_at)) {
+            log::error!("Failed to create session: {}", e);
+            return AuthResponse {
+                success: false,
+                message: "Internal error".to_string(),
+                token: None,
+                username: None,
+            };
+        }
+        
+        AuthResponse {
+            success: true,
+            message: "Login successful".to_string(),
+            token: Some(token),
+            username: Some(user.username),
+        }
+    }
+    
     /// Logout a user
     pub fn logout(&self, token: &str) -> AuthResponse {
         match self.db.delete_session(token) {
@@ -174,15 +401,101 @@ impl AuthService {
         }
     }
     
-    /// Validate a session token
+    /// Validate a session token, extending the session if sliding expiration is enabled
     pub fn validate_token(&self, token: &str) -> Option<Session> {
-        match self.db.get_session(token) {
-            Ok(session) => session,
+        self.validate_token_at(token, chrono::Utc::now().timestamp())
+    }
+
+    /// Validate a session token at a given time (Unix epoch)
+    ///
+    /// Sessions older than the maximum lifetime are deleted. With sliding expiration, a
+    /// session used after the configured share of its lifetime gets a new expiration one
+    /// session duration away (capped by the maximum lifetime). Since extensions always
+    /// start a full duration, the last one is `expires_at - duration`, which limits them
+    /// to one per [`SESSION_TOUCH_INTERVAL_SECS`] without storing anything more.
+    pub fn validate_token_at(&self, token: &str, now: i64) -> Option<Session> {
+        let mut session = match self.retry(|| self.db.get_session(token)) {
+            Ok(Some(session)) if session.expires_at >= now => session,
+            Ok(_) => return None,
             Err(e) => {
                 log::error!("Failed to validate token: {}", e);
-                None
+                return None;
             }
+        };
+
+        let deadline = session.created_at + self.session_max_lifetime_secs;
+        if now >= deadline {
+            log::debug!("Session of {} reached its maximum lifetime", session.username);
+            if let Err(e) = self.db.delete_session(token) {
+                log::error!("Failed to delete session: {}", e);
+            }
+            return None;
+        }
+        if self.session_sliding_percent == 0 {
+            return Some(session);
+        }
+
+        let renewed_at = session.expires_at - self.session_duration_secs;
+        let threshold = (self.session_duration_secs * self.session_sliding_percent as i64 / 100)
+            .max(SESSION_TOUCH_INTERVAL_SECS);
+        let expires_at = (now + self.session_duration_secs).min(deadline);
+        if now - renewed_at >= threshold && expires_at > session.expires_at {
+            match self.db.touch_session(token, expires_at) {
+                Ok(()) => session.expires_at = expires_at,
+                Err(e) => log::error!("Failed to extend session: {}", e),
+            }
+        }
+        Some(session)
+    }
+    
+    /// Set a user's email and send them a link to verify it
+    ///
+    /// The address stays unverified until the link is followed. The link carries a new
+    /// random token valid for [`EMAIL_VERIFICATION_TTL_SECS`], replacing any pending one;
+    /// `verify_url` is the address of `GET /api/auth/verify-email`.
+    pub fn send_verification_email(&self, sender: &dyn EmailSender, user_id: i64, email: &str, verify_url: &str) -> Result<(), String> {
+        let email = email.trim();
+        email.parse::<lettre::Address>()
+            .map_err(|_| format!("{} is not an email address", email))?;
+        let user = self.db.get_user_by_id(user_id)?
+            .ok_or_else(|| format!("User {} not found", user_id))?;
+
+        let token = Uuid::new_v4().simple().to_string();
+        let expires_at = chrono::Utc::now().timestamp() + EMAIL_VERIFICATION_TTL_SECS;
+        self.db.set_email_verification(user_id, email, &token, expires_at)?;
+
+        sender.send(&Email {
+            to: email.to_string(),
+            subject: "Verify your GeekCraft email address".to_string(),
+            body: format!(
+                "Hello {},\n\nOpen this link within {} hours to verify your email address:\n{}?token={}\n\nIf you did not ask for it, you can ignore this email.\n",
+                user.username, EMAIL_VERIFICATION_TTL_SECS / 3600, verify_url, token
+            ),
+        })?;
+        log::info!("Verification email sent to {}", user.username);
+        Ok(())
+    }
+
+    /// Verify the email of the user a verification token was sent to
+    pub fn verify_email(&self, token: &str) -> Result<User, String> {
+        self.verify_email_at(token, chrono::Utc::now().timestamp())
+    }
+
+    /// Verify the email of the user a verification token was sent to, at a given time (Unix epoch)
+    ///
+    /// The token is consumed; an expired token is refused and kept until a new one replaces it.
+    pub fn verify_email_at(&self, token: &str, now: i64) -> Result<User, String> {
+        let mut user = self.db.get_user_by_email_token(token)?
+            .ok_or("Unknown verification link")?;
+        if user.email_verification_expires_at.is_none_or(|expires_at| now > expires_at) {
+            return Err("This verification link has expired, ask for a new one".to_string());
         }
+        self.db.mark_email_verified(user.id)?;
+        user.email_verified = true;
+        user.email_verification_token = None;
+        user.email_verification_expires_at = None;
+        log::info!("{} verified their email address", user.username);
+        Ok(user)
     }
     
     /// Cleanup expired sessions
@@ -191,4 +504,461 @@ impl AuthService {
             log::error!("Failed to cleanup expired sessions: {}", e);
         }
     }
+    
+    /// Record a match between two players and update their ELO ratings
+    ///
+    /// Results are recorded one at a time, so concurrent results for the same player each
+    /// start from the rating the previous one left.
+    pub fn record_match_result(&self, player_a: &str, player_b: &str, outcome: MatchOutcome) -> Result<MatchRecord, String> {
+        if player_a == player_b {
+            return Err("A player cannot play against themselves".to_string());
+        }
+        
+        let _recording = self.match_lock.lock().unwrap();
+        let user_a = self.db.get_user_by_username(player_a)?
+            .ok_or_else(|| format!("User {} not found", player_a))?;
+        let user_b = self.db.get_user_by_username(player_b)?
+            .ok_or_else(|| format!("User {} not found", player_b))?;
+        
+        let score_a = match outcome {
+            MatchOutcome::PlayerAWins => 1.0,
+            MatchOutcome::PlayerBWins => 0.0,
+            MatchOutcome::Draw => 0.5,
+        };
+        let (rating_a, rating_b) = elo_update(user_a.rating, user_b.rating, score_a);
+        
+        let record = MatchRecord {
+            player_a: player_a.to_string(),
+            player_b: player_b.to_string(),
+            outcome,
+            rating_a,
+            rating_b,
+            played_at: chrono::Utc::now().timestamp(),
+        };
+        self.db.record_match(&record)?;
+        
+        log::info!("Match recorded: {} ({}) vs {} ({}): {:?}", player_a, rating_a, player_b, rating_b, outcome);
+        Ok(record)
+    }
+    
+    /// Get a user by ID
+    pub fn get_user(&self, user_id: i64) -> Result<Option<User>, String> {
+        self.db.get_user_by_id(user_id)
+    }
+    
+    /// Check that the user database answers queries
+    pub fn ping(&self) -> Result<(), String> {
+        self.db.ping()
+    }
+    
+    /// State of the user database's circuit breaker
+    pub fn circuit_status(&self) -> CircuitState {
+        self.db.circuit_status()
+    }
+    
+    /// Get one page of the users matching `filter`, sorted by ID, with whether they have an
+    /// active session, and the number of matching users
+    pub fn list_users(&self, offset: u32, limit: u32, filter: &UserFilter) -> Result<(Vec<(User, bool)>, u32), String> {
+        let (users, total) = self.db.list_users(offset, limit, filter)?;
+        let users = users.into_iter()
+            .map(|user| {
+                let online = self.db.has_active_session(user.id)?;
+                Ok((user, online))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok((users, total))
+    }
+    
+    /// Unlock the achievements whose conditions `stats` meet; returns the newly unlocked ones
+    pub fn check_and_unlock_achievements(&self, user_id: i64, stats: &PlayerStats) -> Vec<Achievement> {
+        let met: Vec<Achievement> = all_achievements()
+            .into_iter()
+            .filter(|achievement| achievement.condition.is_met(stats))
+            .collect();
+        if met.is_empty() {
+            return met;
+        }
+        
+        let unlocked = match self.db.get_unlocked_achievements(user_id) {
+            Ok(unlocked) => unlocked,
+            Err(e) => {
+                log::error!("Failed to load achievements of user {}: {}", user_id, e);
+                return Vec::new();
+            }
+        };
+        let new: Vec<Achievement> = met.into_iter()
+            .filter(|achievement| !unlocked.contains(&achievement.id))
+            .collect();
+        if new.is_empty() {
+            return new;
+        }
+        
+        let ids: Vec<String> = new.iter().map(|achievement| achievement.id.clone()).collect();
+        if let Err(e) = self.db.unlock_achievements(user_id, &ids) {
+            log::error!("Failed to unlock achievements {:?} for user {}: {}", ids, user_id, e);
+            return Vec::new();
+        }
+        log::info!("User {} unlocked achievements {:?}", user_id, ids);
+        new
+    }
+    
+    /// Achievements a user has unlocked and those still locked
+    pub fn achievements_of(&self, user_id: i64) -> Result<(Vec<Achievement>, Vec<Achievement>), String> {
+        let unlocked = self.db.get_unlocked_achievements(user_id)?;
+        Ok(all_achievements()
+            .into_iter()
+            .partition(|achievement| unlocked.contains(&achievement.id)))
+    }
+    
+    /// Number of matches a player has won
+    pub fn matches_won(&self, username: &str) -> Result<u32, String> {
+        let history = self.db.get_match_history(username, u32::MAX as usize)?;
+        Ok(history.iter()
+            .filter(|record| match record.outcome {
+                MatchOutcome::PlayerAWins => record.player_a == username,
+                MatchOutcome::PlayerBWins => record.player_b == username,
+                MatchOutcome::Draw => false,
+            })
+            .count() as u32)
+    }
+    
+    /// Get a user by username
+    pub fn get_user_by_username(&self, username: &str) -> Result<Option<User>, String> {
+        self.db.get_user_by_username(username)
+    }
+    
+    /// Get the team a user belongs to (if any)
+    pub fn get_team_of_user(&self, user_id: i64) -> Result<Option<Team>, String> {
+        self.db.get_team_of_user(user_id)
+    }
+    
+    /// Get a team by ID
+    pub fn get_team(&self, team_id: &Uuid) -> Result<Option<Team>, String> {
+        self.db.get_team(team_id)
+    }
+    
+    /// Store a team (e.g. after syncing its resource pool)
+    pub fn save_team(&self, team: &Team) -> Result<(), String> {
+        self.db.save_team(team)
+    }
+    
+    /// Create a team with `user_id` as its only member
+    pub fn create_team(&self, user_id: i64, name: &str) -> Result<Team, String> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > 32 {
+            return Err("Team name must be 1 to 32 characters long".to_string());
+        }
+        if self.db.get_team_of_user(user_id)?.is_some() {
+            return Err("You are already in a team".to_string());
+        }
+        
+        let team = Team {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            members: vec![user_id],
+            resource_pool: Default::default(),
+        };
+        self.db.save_team(&team)?;
+        Ok(team)
+    }
+    
+    /// Add `user_id` to a team; only members may invite
+    pub fn invite_to_team(&self, team_id: &Uuid, inviter_id: i64, user_id: i64) -> Result<Team, String> {
+        let mut team = self.db.get_team(team_id)?
+            .ok_or_else(|| format!("Team {} not found", team_id))?;
+        if !team.members.contains(&inviter_id) {
+            return Err("Only team members can invite players".to_string());
+        }
+        if self.db.get_user_by_id(user_id)?.is_none() {
+            return Err(format!("User {} not found", user_id));
+        }
+        if self.db.get_team_of_user(user_id)?.is_some() {
+            return Err(format!("User {} is already in a team", user_id));
+        }
+        if team.members.len() >= MAX_TEAM_SIZE {
+            return Err(format!("Team {} is full ({} players)", team.name, MAX_TEAM_SIZE));
+        }
+        
+        team.members.push(user_id);
+        self.db.save_team(&team)?;
+        Ok(team)
+    }
+    
+    /// Remove `user_id` from a team; returns the remaining team (`None` if it was disbanded)
+    pub fn leave_team(&self, team_id: &Uuid, user_id: i64) -> Result<Option<Team>, String> {
+        let mut team = self.db.get_team(team_id)?
+            .ok_or_else(|| format!("Team {} not found", team_id))?;
+        if !team.members.contains(&user_id) {
+            return Err("You are not a member of this team".to_string());
+        }
+        
+        team.members.retain(|member| *member != user_id);
+        if team.members.is_empty() {
+            self.db.delete_team(team_id)?;
+            return Ok(None);
+        }
+        self.db.save_team(&team)?;
+        Ok(Some(team))
+    }
+    
+    /// Get the alliance a user is a member of (if any)
+    pub fn get_alliance_of_user(&self, user_id: i64) -> Result<Option<Alliance>, String> {
+        self.db.get_alliance_of_user(user_id)
+    }
+    
+    /// Create an alliance with `user_id` as its only member
+    pub fn create_alliance(&self, user_id: i64, name: &str) -> Result<Alliance, String> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > 32 {
+            return Err("Alliance name must be 1 to 32 characters long".to_string());
+        }
+        if self.db.get_alliance_of_user(user_id)?.is_some() {
+            return Err("You are already in an alliance".to_string());
+        }
+        
+        let alliance = Alliance {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            members: vec![user_id],
+            invited: Vec::new(),
+        };
+        self.db.save_alliance(&alliance)?;
+        Ok(alliance)
+    }
+    
+    /// Invite a player to the inviter's alliance; they join once they accept
+    pub fn invite_to_alliance(&self, inviter_id: i64, username: &str) -> Result<(Alliance, User), String> {
+        let mut alliance = self.db.get_alliance_of_user(inviter_id)?
+            .ok_or_else(|| "You are not in an alliance".to_string())?;
+        let user = self.db.get_user_by_username(username)?
+            .ok_or_else(|| format!("User {} not found", username))?;
+        if alliance.members.contains(&user.id) {
+            return Err(format!("{} is already in your alliance", user.username));
+        }
+        if alliance.invited.contains(&user.id) {
+            return Err(format!("{} is already invited", user.username));
+        }
+        if alliance.members.len() >= self.max_alliance_size {
+            return Err(format!("Alliance {} is full ({} players)", alliance.name, self.max_alliance_size));
+        }
+        
+        alliance.invited.push(user.id);
+        self.db.save_alliance(&alliance)?;
+        Ok((alliance, user))
+    }
+    
+    /// Join an alliance `user_id` was invited to
+    pub fn accept_alliance(&self, user_id: i64, alliance_id: &Uuid) -> Result<Alliance, String> {
+        let mut alliance = self.db.get_alliance(alliance_id)?
+            .filter(|alliance| alliance.invited.contains(&user_id))
+            .ok_or_else(|| format!("No pending invitation to alliance {}", alliance_id))?;
+        if self.db.get_alliance_of_user(user_id)?.is_some() {
+            return Err("You are already in an alliance; leave it first".to_string());
+        }
+        if alliance.members.len() >= self.max_alliance_size {
+            return Err(format!("Alliance {} is full ({} players)", alliance.name, self.max_alliance_size));
+        }
+        
+        alliance.invited.retain(|invited| *invited != user_id);
+        alliance.members.push(user_id);
+        self.db.save_alliance(&alliance)?;
+        Ok(alliance)
+    }
+    
+    /// Leave the user's alliance; returns it with the remaining members (empty if it was disbanded)
+    pub fn leave_alliance(&self, user_id: i64) -> Result<Alliance, String> {
+        let mut alliance = self.db.get_alliance_of_user(user_id)?
+            .ok_or_else(|| "You are not in an alliance".to_string())?;
+        
+        alliance.members.retain(|member| *member != user_id);
+        if alliance.members.is_empty() {
+            self.db.delete_alliance(&alliance.id)?;
+        } else {
+            self.db.save_alliance(&alliance)?;
+        }
+        Ok(alliance)
+    }
+    
+    /// Publish a library, or a new version of one of the author's libraries
+    ///
+    /// New versions are not approved: bots keep importing the latest approved version
+    /// until an admin approves the new one.
+    pub fn publish_library(&self, author_id: i64, name: &str, code: &str) -> Result<SharedLibrary, String> {
+        let valid_name = name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if name.is_empty() || name.len() > 64 || !valid_name {
+            return Err("Library name must be 1 to 64 lowercase letters, digits or hyphens".to_string());
+        }
+        if code.len() > MAX_MODULE_SIZE {
+            return Err(format!("Library too large: {} bytes (max: {} bytes)", code.len(), MAX_MODULE_SIZE));
+        }
+        
+        let versions: Vec<SharedLibrary> = self.db.list_libraries()?.into_iter()
+            .filter(|library| library.name == name)
+            .collect();
+        if versions.iter().any(|library| library.author_id != author_id) {
+            return Err(format!("Library {} belongs to another player", name));
+        }
+        
+        let library = SharedLibrary {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            author_id,
+            code: code.to_string(),
+            version: versions.iter().map(|library| library.version).max().unwrap_or(0) + 1,
+            approved: false,
+        };
+        self.db.save_library(&library)?;
+        Ok(library)
+    }
+    
+    /// Approve a library version
+    pub fn approve_library(&self, library_id: &Uuid) -> Result<SharedLibrary, String> {
+        let mut library = self.db.get_library(library_id)?
+            .ok_or_else(|| format!("Library {} not found", library_id))?;
+        library.approved = true;
+        self.db.save_library(&library)?;
+        Ok(library)
+    }
+    
+    /// The latest approved version of every library, sorted by name
+    pub fn approved_libraries(&self) -> Result<Vec<SharedLibrary>, String> {
+        let mut libraries: Vec<SharedLibrary> = Vec::new();
+        for library in self.db.list_libraries()?.into_iter().filter(|library| library.approved) {
+            match libraries.iter_mut().find(|latest| latest.name == library.name) {
+                Some(latest) if latest.version < library.version => *latest = library,
+                Some(_) => {}
+                None => libraries.push(library),
+            }
+        }
+        libraries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(libraries)
+    }
+    
+    fn other_user(&self, user_id: i64, username: &str) -> Result<User, String> {
+        let user = self.db.get_user_by_username(username)?
+            .ok_or_else(|| format!("User {} not found", username))?;
+        if user.id == user_id {
+            return Err("You cannot be your own friend".to_string());
+        }
+        Ok(user)
+    }
+    
+    fn are_friends(&self, user_id: i64, friend_id: i64) -> Result<bool, String> {
+        Ok(self.db.list_friends(user_id)?.iter().any(|friend| friend.id == friend_id))
+    }
+    
+    /// Send a friend request to `username`; they become friends once it is accepted
+    pub fn request_friend(&self, user_id: i64, username: &str) -> Result<User, String> {
+        let target = self.other_user(user_id, username)?;
+        if self.are_friends(user_id, target.id)? {
+            return Err(format!("You are already friends with {}", target.username));
+        }
+        if self.db.list_follow_requests(user_id)?.iter().any(|request| request.from_id == target.id) {
+            return Err(format!("{} already sent you a friend request; accept it instead", target.username));
+        }
+        if self.db.list_follow_requests(target.id)?.iter().any(|request| request.from_id == user_id) {
+            return Err(format!("You already sent a friend request to {}", target.username));
+        }
+        
+        self.db.create_follow_request(&FollowRequest { from_id: user_id, to_id: target.id })?;
+        Ok(target)
+    }
+    
+    /// Accept the pending friend request `username` sent to `user_id`
+    pub fn accept_friend(&self, user_id: i64, username: &str) -> Result<User, String> {
+        let requester = self.other_user(user_id, username)?;
+        if !self.db.delete_follow_request(requester.id, user_id)? {
+            return Err(format!("No pending friend request from {}", requester.username));
+        }
+        
+        self.db.add_friend(&Friendship {
+            user_id: requester.id,
+            friend_id: user_id,
+            created_at: chrono::Utc::now().timestamp(),
+        })?;
+        Ok(requester)
+    }
+    
+    /// Remove `username` from the friends of `user_id`, or cancel a request sent to them
+    pub fn remove_friend(&self, user_id: i64, username: &str) -> Result<User, String> {
+        let other = self.other_user(user_id, username)?;
+        let cancelled = self.db.delete_follow_request(user_id, other.id)?;
+        if !self.are_friends(user_id, other.id)? {
+            if cancelled {
+                return Ok(other);
+            }
+            return Err(format!("You are not friends with {}", other.username));
+        }
+        
+        self.db.remove_friend(user_id, other.id)?;
+        Ok(other)
+    }
+    
+    /// Friends of a user, each with whether they have an active session
+    pub fn friends_with_status(&self, user_id: i64) -> Result<Vec<(User, bool)>, String> {
+        let mut friends = Vec::new();
+        for friend in self.db.list_friends(user_id)? {
+            let online = self.db.has_active_session(friend.id)?;
+            friends.push((friend, online));
+        }
+        friends.sort_by(|(a, _), (b, _)| a.username.cmp(&b.username));
+        Ok(friends)
+    }
+    
+    /// Users with a pending friend request to `user_id`
+    pub fn incoming_friend_requests(&self, user_id: i64) -> Result<Vec<User>, String> {
+        let mut requesters = Vec::new();
+        for request in self.db.list_follow_requests(user_id)? {
+            if let Some(user) = self.db.get_user_by_id(request.from_id)? {
+                requesters.push(user);
+            }
+        }
+        Ok(requesters)
+    }
+}
+
+/// New ratings of two players after a match (`score_a`: 1 win, 0.5 draw, 0 loss for A)
+fn elo_update(rating_a: i32, rating_b: i32, score_a: f64) -> (i32, i32) {
+    let expected_a = 1.0 / (1.0 + 10f64.powf(f64::from(rating_b - rating_a) / 400.0));
+    let delta = (ELO_K_FACTOR * (score_a - expected_a)).round() as i32;
+    (rating_a + delta, rating_b - delta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::database::DatabaseBackend;
+
+    #[test]
+    fn test_elo_update() {
+        // Equal ratings: winner gains half the K-factor
+        assert_eq!(elo_update(1200, 1200, 1.0), (1216, 1184));
+        assert_eq!(elo_update(1200, 1200, 0.5), (1200, 1200));
+        // Upsets move ratings more than expected results
+        let (underdog, _) = elo_update(1000, 1400, 1.0);
+        let (favorite, _) = elo_update(1400, 1000, 1.0);
+        assert!(underdog - 1000 > favorite - 1400);
+    }
+
+    #[test]
+    fn test_concurrent_match_results_are_not_lost() {
+        let db = Arc::new(AuthDatabase::new(DatabaseBackend::InMemory).unwrap());
+        let service = Arc::new(AuthService::new(db.clone()));
+        db.create_user("alice", "hash").unwrap();
+        db.create_user("bob", "hash").unwrap();
+
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let service = service.clone();
+                std::thread::spawn(move || service.record_match_result("alice", "bob", MatchOutcome::PlayerAWins).unwrap())
+            })
+            .collect();
+        let mut ratings: Vec<i32> = threads.into_iter().map(|thread| thread.join().unwrap().rating_a).collect();
+
+        // Each win started from the rating the previous one left
+        ratings.sort();
+        ratings.dedup();
+        assert_eq!(ratings.len(), 8);
+        assert_eq!(db.get_user_by_username("alice").unwrap().unwrap().rating, ratings[7]);
+    }
 }
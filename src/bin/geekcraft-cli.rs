@@ -0,0 +1,349 @@
+//! GeekCraft - Command Line Client
+//!
+//! Talks to a GeekCraft server through `geekcraft::client`. `login` stores the session
+//! token (and the server URL) in `~/.geekcraft/config.toml` for later commands. Every
+//! command prints a human-readable summary, or the server's response with `--json`;
+//! failures print the server's error message and exit with status 1.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use base64::Engine as _;
+use clap::{Parser, Subcommand};
+use serde::{Deserialize, Serialize};
+
+use geekcraft::client::Client;
+use geekcraft::config::{DEFAULT_HOST, DEFAULT_PORT};
+use geekcraft::game::zone::{SurfaceType, Zone};
+use geekcraft::network::campaign_routes::StartRunRequest;
+use geekcraft::network::server::CodeSubmission;
+
+#[derive(Parser)]
+#[command(name = "geekcraft-cli", version, about = "Command line client for a GeekCraft server")]
+struct Cli {
+    /// Server URL (default: the one used at login, or http://127.0.0.1:3030)
+    #[arg(long, global = true)]
+    server: Option<String>,
+    /// Print the server's JSON responses
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create an account
+    Register {
+        username: String,
+        #[arg(long, short)]
+        password: String,
+    },
+    /// Log in and save the session token
+    Login {
+        username: String,
+        #[arg(long, short)]
+        password: String,
+    },
+    /// Submit a bot (language from the extension: .js, .ts, .lua or .wasm)
+    Submit {
+        file: PathBuf,
+    },
+    /// Manage the submitted code
+    Code {
+        #[command(subcommand)]
+        command: CodeCommand,
+    },
+    /// Show the game state
+    Gamestate,
+    /// Inspect zones
+    Zone {
+        #[command(subcommand)]
+        command: ZoneCommand,
+    },
+    /// Manage campaign runs
+    Campaign {
+        #[command(subcommand)]
+        command: CampaignCommand,
+    },
+    /// Server administration (requires an admin account)
+    Admin {
+        #[command(subcommand)]
+        command: AdminCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum CodeCommand {
+    /// Print the submitted entry module, or write it to a file
+    Pull {
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ZoneCommand {
+    /// Draw a zone's tiles (P plain, S swamp, W water, # obstacle)
+    Show {
+        zone_id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CampaignCommand {
+    /// Start a run
+    Start {
+        run_id: String,
+        /// Map template to start on
+        #[arg(long)]
+        map_template: Option<String>,
+        /// NPC opponent to add (harvester, raider or turtle); repeat for more
+        #[arg(long = "npc")]
+        npc_opponents: Vec<String>,
+        /// Scenario to play (sets the map template and NPC opponents)
+        #[arg(long, conflicts_with_all = ["map_template", "npc_opponents"])]
+        scenario: Option<String>,
+    },
+    /// Stop a run
+    Stop {
+        run_id: String,
+    },
+    /// Save a run
+    Save {
+        run_id: String,
+    },
+    /// Load a saved run
+    Load {
+        run_id: String,
+    },
+    /// List saved runs
+    List,
+}
+
+#[derive(Subcommand)]
+enum AdminCommand {
+    /// Manage user accounts
+    Users {
+        #[command(subcommand)]
+        command: UsersCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum UsersCommand {
+    /// List every account
+    List,
+}
+
+/// Contents of `~/.geekcraft/config.toml`
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CliConfig {
+    server: Option<String>,
+    username: Option<String>,
+    token: Option<String>,
+}
+
+impl CliConfig {
+    fn path() -> Result<PathBuf, String> {
+        let home = std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .ok_or_else(|| "Cannot find the home directory".to_string())?;
+        Ok(Path::new(&home).join(".geekcraft").join("config.toml"))
+    }
+
+    fn load() -> Result<Self, String> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        toml::from_str(&text)
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let path = Self::path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let text = toml::to_string(self)
+            .map_err(|e| format!("Failed to serialize config: {}", e))?;
+        fs::write(&path, text)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Print a response as JSON, or the human-readable text
+fn output<T: Serialize>(json: bool, response: &T, human: impl FnOnce() -> String) -> Result<(), String> {
+    if json {
+        let text = serde_json::to_string_pretty(response)
+            .map_err(|e| format!("Failed to serialize response: {}", e))?;
+        println!("{}", text);
+    } else {
+        println!("{}", human());
+    }
+    Ok(())
+}
+
+async fn run(cli: Cli) -> Result<(), String> {
+    let mut config = CliConfig::load()?;
+    let server = cli.server.clone()
+        .or_else(|| config.server.clone())
+        .unwrap_or_else(|| format!("http://{}:{}", DEFAULT_HOST, DEFAULT_PORT));
+    let mut client = Client::new(server.clone());
+    if let Some(token) = &config.token {
+        client = client.with_token(token.clone());
+    }
+    let json = cli.json;
+
+    match cli.command {
+        Command::Register { username, password } => {
+            let response = client.register(&username, &password).await.map_err(|e| e.to_string())?;
+            output(json, &response, || response.message.clone())
+        }
+        Command::Login { username, password } => {
+            let response = client.login(&username, &password).await.map_err(|e| e.to_string())?;
+            config.server = Some(server);
+            config.username = Some(username.clone());
+            config.token = response.token.clone();
+            config.save()?;
+            output(json, &response, || format!("Logged in as {}", username))
+        }
+        Command::Submit { file } => {
+            let submission = read_submission(&file)?;
+            let response = client.submit_code(&submission).await.map_err(|e| e.to_string())?;
+            output(json, &response, || response.message.clone())
+        }
+        Command::Code { command: CodeCommand::Pull { output: path } } => {
+            let response = client.get_code().await.map_err(|e| e.to_string())?;
+            let code = response.code.clone().ok_or_else(|| "No code submitted".to_string())?;
+            match path {
+                Some(path) => {
+                    fs::write(&path, &code)
+                        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                    output(json, &response, || format!("Wrote {} bytes to {}", code.len(), path.display()))
+                }
+                None => output(json, &response, || code.clone()),
+            }
+        }
+        Command::Gamestate => {
+            let state = client.get_gamestate().await.map_err(|e| e.to_string())?;
+            output(json, &state, || format!(
+                "Tick: {}\nScript tick: {}\nDay phase: {:?}\nRun state: {:?}\nPlayers: {}",
+                state.tick, state.script_tick, state.day_phase, state.run_state, state.players.join(", ")
+            ))
+        }
+        Command::Zone { command: ZoneCommand::Show { zone_id } } => {
+            let zone = client.get_zone(&zone_id).await.map_err(|e| e.to_string())?;
+            output(json, &zone, || render_zone(&zone))
+        }
+        Command::Campaign { command } => run_campaign(&client, json, command).await,
+        Command::Admin { command: AdminCommand::Users { command: UsersCommand::List } } => {
+            let response = client.list_users().await.map_err(|e| e.to_string())?;
+            output(json, &response, || {
+                let mut lines = vec![format!("{:>6}  {:<24} {:>6}  STATUS", "ID", "USERNAME", "RATING")];
+                for user in &response.users {
+                    let status = match (user.online, user.admin) {
+                        (true, true) => "online, admin",
+                        (true, false) => "online",
+                        (false, true) => "admin",
+                        (false, false) => "",
+                    };
+                    lines.push(format!("{:>6}  {:<24} {:>6}  {}", user.id, user.username, user.rating, status));
+                }
+                lines.join("\n")
+            })
+        }
+    }
+}
+
+async fn run_campaign(client: &Client, json: bool, command: CampaignCommand) -> Result<(), String> {
+    match command {
+        CampaignCommand::Start { run_id, map_template, npc_opponents, scenario } => {
+            let request = StartRunRequest { run_id, allow_spectators: None, map_template, npc_opponents, scenario };
+            let response = client.start_campaign(&request).await.map_err(|e| e.to_string())?;
+            output(json, &response, || response.message.clone())
+        }
+        CampaignCommand::Stop { run_id } => {
+            let response = client.stop_campaign(&run_id).await.map_err(|e| e.to_string())?;
+            output(json, &response, || response.message.clone())
+        }
+        CampaignCommand::Save { run_id } => {
+            let response = client.save_campaign(&run_id).await.map_err(|e| e.to_string())?;
+            output(json, &response, || response.message.clone())
+        }
+        CampaignCommand::Load { run_id } => {
+            let response = client.load_campaign(&run_id).await.map_err(|e| e.to_string())?;
+            output(json, &response, || response.message.clone())
+        }
+        CampaignCommand::List => {
+            let response = client.list_campaign_saves().await.map_err(|e| e.to_string())?;
+            output(json, &response, || {
+                if response.saves.is_empty() {
+                    "No saved runs".to_string()
+                } else {
+                    response.saves.join("\n")
+                }
+            })
+        }
+    }
+}
+
+/// Build a submission from a bot file, picking the language from its extension
+fn read_submission(file: &Path) -> Result<CodeSubmission, String> {
+    let extension = file.extension().and_then(|ext| ext.to_str()).unwrap_or("js");
+    let language = match extension {
+        "js" | "mjs" | "cjs" => "javascript",
+        "ts" => "typescript",
+        "lua" => "lua",
+        "wasm" => "wasm",
+        other => return Err(format!("Unknown bot file extension .{} (expected .js, .ts, .lua or .wasm)", other)),
+    };
+
+    let code = if language == "wasm" {
+        let bytes = fs::read(file)
+            .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    } else {
+        fs::read_to_string(file)
+            .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?
+    };
+
+    Ok(CodeSubmission {
+        code: Some(code),
+        modules: None,
+        language: Some(language.to_string()),
+        api_version: None,
+    })
+}
+
+/// One line per row of tiles
+fn render_zone(zone: &Zone) -> String {
+    zone.tiles.iter()
+        .map(|row| row.iter()
+            .map(|tile| match tile.surface_type {
+                SurfaceType::Plain => 'P',
+                SurfaceType::Swamp => 'S',
+                SurfaceType::Water => 'W',
+                SurfaceType::Obstacle => '#',
+            })
+            .collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
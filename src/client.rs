@@ -0,0 +1,331 @@
+//! GeekCraft client
+//!
+//! Typed async client for the REST and WebSocket API, sharing its request and response
+//! types with the server. Requests go to the versioned `/api/v1` prefix with the session
+//! token as a Bearer header; after `login` the client remembers the credentials and logs
+//! in again once when a request is rejected with `401` (e.g. an expired session).
+//!
+//! Failed requests map onto the server's `{success: false, message}` envelope as
+//! [`ClientError::Api`], whether the server answered with an error status or with
+//! `success: false`.
+
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use futures_util::{SinkExt, Stream, StreamExt};
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use crate::auth::models::{AuthResponse, LoginRequest, RegisterRequest};
+use crate::config::API_VERSION;
+use crate::game::zone::Zone;
+use crate::network::admin_routes::UserListResponse;
+use crate::network::campaign_routes::{
+    ListSavesResponse, LoadRunRequest, LoadRunResponse, SaveRunRequest, SaveRunResponse, StartRunRequest,
+    StartRunResponse, StopRunRequest, StopRunResponse,
+};
+use crate::network::server::{CodeSubmission, CodeSubmissionResponse, GameStateResponse, PlayerCodeResponse};
+use crate::network::zone_routes::GetZoneResponse;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Error returned by [`Client`] calls
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request could not be sent or its response could not be read
+    Http(reqwest::Error),
+    /// The server rejected the request
+    Api {
+        /// HTTP status of the response (`200` for `success: false` bodies)
+        status: u16,
+        /// Error message from the response envelope
+        message: String,
+    },
+    /// WebSocket connection or protocol error
+    WebSocket(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Http(err) => write!(f, "HTTP error: {}", err),
+            ClientError::Api { status, message } => write!(f, "{} ({})", message, status),
+            ClientError::WebSocket(message) => write!(f, "WebSocket error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(err: reqwest::Error) -> Self {
+        ClientError::Http(err)
+    }
+}
+
+impl ClientError {
+    fn api(status: StatusCode, message: impl Into<String>) -> Self {
+        ClientError::Api { status: status.as_u16(), message: message.into() }
+    }
+}
+
+/// Session token and the credentials used to renew it
+#[derive(Default)]
+struct Credentials {
+    token: Option<String>,
+    login: Option<(String, String)>,
+}
+
+/// Async client for a GeekCraft server
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    credentials: Mutex<Credentials>,
+}
+
+impl Client {
+    /// Create a client for the server at `base_url` (e.g. `http://127.0.0.1:3030`)
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Client {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            credentials: Mutex::new(Credentials::default()),
+        }
+    }
+
+    /// Use an existing session token (it is not renewed when it expires)
+    pub fn with_token(self, token: impl Into<String>) -> Self {
+        self.credentials.lock().unwrap().token = Some(token.into());
+        self
+    }
+
+    /// Current session token, if logged in
+    pub fn token(&self) -> Option<String> {
+        self.credentials.lock().unwrap().token.clone()
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/api/{}{}", self.base_url, API_VERSION, path)
+    }
+
+    /// Register a new account (does not log in)
+    pub async fn register(&self, username: &str, password: &str) -> Result<AuthResponse, ClientError> {
+        let request = RegisterRequest { username: username.to_string(), password: password.to_string(), email: None, invite_code: None };
+        self.send(Method::POST, "/auth/register", Some(&request), false).await
+    }
+
+    /// Log in and keep the session token (and credentials, to renew it) for later calls
+    pub async fn login(&self, username: &str, password: &str) -> Result<AuthResponse, ClientError> {
+        let response = self.login_request(username, password).await?;
+        let mut credentials = self.credentials.lock().unwrap();
+        credentials.token = response.token.clone();
+        credentials.login = Some((username.to_string(), password.to_string()));
+        Ok(response)
+    }
+
+    async fn login_request(&self, username: &str, password: &str) -> Result<AuthResponse, ClientError> {
+        let request = LoginRequest { username: username.to_string(), password: password.to_string() };
+        Self::parse(self.request(Method::POST, "/auth/login", Some(&request), false).send().await?).await
+    }
+
+    /// Log in again with the stored credentials; false if there are none
+    async fn refresh_token(&self) -> Result<bool, ClientError> {
+        let login = self.credentials.lock().unwrap().login.clone();
+        let Some((username, password)) = login else {
+            return Ok(false);
+        };
+        let response = self.login_request(&username, &password).await?;
+        self.credentials.lock().unwrap().token = response.token;
+        Ok(true)
+    }
+
+    /// Submit the player's code
+    pub async fn submit_code(&self, submission: &CodeSubmission) -> Result<CodeSubmissionResponse, ClientError> {
+        self.send(Method::POST, "/submit", Some(submission), true).await
+    }
+
+    /// Get the player's submitted code
+    pub async fn get_code(&self) -> Result<PlayerCodeResponse, ClientError> {
+        self.send::<(), _>(Method::GET, "/code", None, true).await
+    }
+
+    /// Get the current game state
+    pub async fn get_gamestate(&self) -> Result<GameStateResponse, ClientError> {
+        self.send::<(), _>(Method::GET, "/gamestate", None, true).await
+    }
+
+    /// Get a zone by ID
+    pub async fn get_zone(&self, zone_id: &str) -> Result<Zone, ClientError> {
+        let response: GetZoneResponse = self.send::<(), _>(Method::GET, &format!("/zone/{}", zone_id), None, true).await?;
+        response.zone.ok_or_else(|| ClientError::api(StatusCode::NOT_FOUND, response.message))
+    }
+
+    /// Start a campaign run
+    pub async fn start_campaign(&self, request: &StartRunRequest) -> Result<StartRunResponse, ClientError> {
+        self.send(Method::POST, "/campaign/start", Some(request), true).await
+    }
+
+    /// Stop a campaign run
+    pub async fn stop_campaign(&self, run_id: &str) -> Result<StopRunResponse, ClientError> {
+        let request = StopRunRequest { run_id: run_id.to_string() };
+        self.send(Method::POST, "/campaign/stop", Some(&request), true).await
+    }
+
+    /// Save a campaign run
+    pub async fn save_campaign(&self, run_id: &str) -> Result<SaveRunResponse, ClientError> {
+        let request = SaveRunRequest { run_id: run_id.to_string() };
+        self.send(Method::POST, "/campaign/save", Some(&request), true).await
+    }
+
+    /// Load a saved campaign run
+    pub async fn load_campaign(&self, run_id: &str) -> Result<LoadRunResponse, ClientError> {
+        let request = LoadRunRequest { run_id: run_id.to_string() };
+        self.send(Method::POST, "/campaign/load", Some(&request), true).await
+    }
+
+    /// List saved campaign runs
+    pub async fn list_campaign_saves(&self) -> Result<ListSavesResponse, ClientError> {
+        self.send::<(), _>(Method::GET, "/campaign/saves", None, true).await
+    }
+
+    /// List every user account (admin only)
+    pub async fn list_users(&self) -> Result<UserListResponse, ClientError> {
+        self.send::<(), _>(Method::GET, "/admin/users", None, true).await
+    }
+
+    fn request<B: Serialize>(&self, method: Method, path: &str, body: Option<&B>, authenticated: bool) -> RequestBuilder {
+        let mut request = self.http.request(method, self.url(path));
+        if authenticated {
+            if let Some(token) = self.token() {
+                request = request.bearer_auth(token);
+            }
+        }
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        request
+    }
+
+    /// Send a request, renewing the token and retrying once if it is rejected with `401`
+    async fn send<B: Serialize, T: DeserializeOwned>(&self, method: Method, path: &str, body: Option<&B>, authenticated: bool) -> Result<T, ClientError> {
+        let mut response = self.request(method.clone(), path, body, authenticated).send().await?;
+        if response.status() == StatusCode::UNAUTHORIZED && authenticated && self.refresh_token().await? {
+            response = self.request(method, path, body, authenticated).send().await?;
+        }
+        Self::parse(response).await
+    }
+
+    async fn parse<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, ClientError> {
+        let status = response.status();
+        let bytes = response.bytes().await?;
+        let value: Option<serde_json::Value> = serde_json::from_slice(&bytes).ok();
+
+        let failed = !status.is_success()
+            || value.as_ref().and_then(|value| value.get("success")).and_then(|v| v.as_bool()) == Some(false);
+        if failed {
+            let message = value.as_ref()
+                .and_then(|value| value.get("message").or_else(|| value.get("error")))
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+                .unwrap_or_else(|| status.canonical_reason().unwrap_or("Request failed").to_string());
+            return Err(ClientError::api(status, message));
+        }
+
+        let value = value.ok_or_else(|| ClientError::api(status, "Response is not JSON"))?;
+        serde_json::from_value(value)
+            .map_err(|err| ClientError::api(status, format!("Unexpected response: {}", err)))
+    }
+
+    /// Subscribe to game state updates over the WebSocket
+    ///
+    /// The stream polls the state every `interval` and yields it whenever the tick has
+    /// changed; it ends when the connection closes.
+    pub async fn subscribe_gamestate(&self, interval: Duration) -> Result<impl Stream<Item = Result<GameStateResponse, ClientError>>, ClientError> {
+        let ws = match self.connect_ws().await {
+            Err(ClientError::Api { status: 401, .. }) if self.refresh_token().await? => self.connect_ws().await?,
+            result => result?,
+        };
+        Ok(futures_util::stream::unfold((ws, None::<u64>), move |(mut ws, last_tick)| async move {
+            loop {
+                if last_tick.is_some() {
+                    tokio::time::sleep(interval).await;
+                }
+                let state = match poll_gamestate(&mut ws).await {
+                    Ok(Some(state)) => state,
+                    Ok(None) => return None,
+                    Err(err) => return Some((Err(err), (ws, last_tick))),
+                };
+                if last_tick != Some(state.tick) {
+                    let tick = state.tick;
+                    return Some((Ok(state), (ws, Some(tick))));
+                }
+            }
+        }))
+    }
+
+    /// Open an authenticated WebSocket connection
+    async fn connect_ws(&self) -> Result<WsStream, ClientError> {
+        let url = format!("{}/ws", self.base_url.replacen("http", "ws", 1));
+        let (mut ws, _) = connect_async(url).await
+            .map_err(|err| ClientError::WebSocket(err.to_string()))?;
+
+        let token = self.token().ok_or_else(|| ClientError::api(StatusCode::UNAUTHORIZED, "Not logged in"))?;
+        send_ws(&mut ws, serde_json::json!({"type": "auth", "token": token})).await?;
+        loop {
+            let message = next_ws(&mut ws).await?
+                .ok_or_else(|| ClientError::WebSocket("Connection closed".to_string()))?;
+            let reason = message["message"].as_str().unwrap_or("Authentication failed");
+            match message["type"].as_str() {
+                Some("authResponse") if message["success"] == true => return Ok(ws),
+                Some("authResponse") => return Err(ClientError::api(StatusCode::UNAUTHORIZED, reason)),
+                Some("error") => return Err(ClientError::WebSocket(reason.to_string())),
+                _ => {}
+            }
+        }
+    }
+}
+
+async fn send_ws(ws: &mut WsStream, command: serde_json::Value) -> Result<(), ClientError> {
+    ws.send(Message::Text(command.to_string())).await
+        .map_err(|err| ClientError::WebSocket(err.to_string()))
+}
+
+/// Request the game state and wait for it, or `None` once the connection is closed
+async fn poll_gamestate(ws: &mut WsStream) -> Result<Option<GameStateResponse>, ClientError> {
+    send_ws(ws, serde_json::json!({"type": "getGameState"})).await?;
+    while let Some(message) = next_ws(ws).await? {
+        match message["type"].as_str() {
+            Some("gameStateResponse") => {
+                return serde_json::from_value(message)
+                    .map(Some)
+                    .map_err(|err| ClientError::WebSocket(format!("Unexpected game state: {}", err)));
+            }
+            Some("error") => {
+                let reason = message["message"].as_str().unwrap_or("Unknown error");
+                return Err(ClientError::WebSocket(reason.to_string()));
+            }
+            _ => {}
+        }
+    }
+    Ok(None)
+}
+
+/// Next JSON text message, or `None` once the connection is closed
+async fn next_ws(ws: &mut WsStream) -> Result<Option<serde_json::Value>, ClientError> {
+    while let Some(message) = ws.next().await {
+        match message.map_err(|err| ClientError::WebSocket(err.to_string()))? {
+            Message::Text(text) => {
+                if let Ok(value) = serde_json::from_str(&text) {
+                    return Ok(Some(value));
+                }
+            }
+            Message::Close(_) => return Ok(None),
+            _ => {}
+        }
+    }
+    Ok(None)
+}
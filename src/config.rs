@@ -0,0 +1,825 @@
+//! Server configuration
+//!
+//! The constants below are the defaults of every tunable. A [`ServerConfig`] holds the
+//! values the server actually runs with: those of a TOML file (see
+//! [`ServerConfig::from_file`]), falling back to `GEEKCRAFT_*` environment variables,
+//! then to the defaults. Admins can reload it while the server runs, except for the
+//! [`RESTART_REQUIRED_FIELDS`].
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::auth::models::RegistrationMode;
+use crate::game::world::{RespawnMode, WorldConfig};
+use crate::game::zone::ResourceType;
+use crate::network::ip_filter::parse_ranges;
+use crate::scripting::js_runtime::ScriptLimits;
+
+/// Current REST API version, used as the `/api/<version>/` URL prefix
+pub const API_VERSION: &str = "v1";
+
+/// Default server port
+pub const DEFAULT_PORT: u16 = 3030;
+
+/// Default server address
+pub const DEFAULT_HOST: &str = "127.0.0.1";
+
+/// Default address the server listens on (all interfaces)
+pub const LISTEN_HOST: &str = "0.0.0.0";
+
+/// Default configuration file, read at startup if it exists (overridden by `GEEKCRAFT_CONFIG`)
+pub const CONFIG_PATH: &str = "./geekcraft.toml";
+
+/// Default lifetime of a login session, in seconds (24 hours)
+pub const SESSION_DURATION_SECS: i64 = 86400;
+
+/// Default longest time a session can be kept alive by sliding expiration, in seconds (7 days)
+pub const SESSION_MAX_LIFETIME_SECS: i64 = 7 * 86400;
+
+/// Number of simulation ticks per second
+pub const TICKS_PER_SECOND: u32 = 60;
+
+/// Simulation ticks between two script executions
+pub const SCRIPT_TICK_INTERVAL: u64 = 30;
+
+/// Maximum timeout for script execution (ms)
+pub const SCRIPT_TIMEOUT_MS: u64 = 100;
+
+/// Maximum memory for a script (MB)
+pub const SCRIPT_MAX_MEMORY_MB: usize = 128;
+
+/// Whether scripts run in strict mode (no `eval`, no host-revealing globals)
+pub const SCRIPT_STRICT_MODE: bool = true;
+
+/// Whether the intrinsics are frozen before bot code runs
+pub const SCRIPT_FREEZE_INTRINSICS: bool = true;
+
+/// Script execution timeout for dry-run validation in milliseconds
+pub const SCRIPT_VALIDATE_TIMEOUT_MS: u64 = 50;
+
+/// Frames per second sent to WebSocket spectators
+pub const SPECTATOR_FRAME_RATE: u32 = 10;
+
+/// Maximum concurrent WebSocket connections per authenticated user
+pub const MAX_WS_PER_USER: u32 = 3;
+
+/// Ticks played in each tournament match
+pub const TOURNAMENT_MAX_TICKS: u64 = 1000;
+
+/// Seconds between full keyframes sent to zone state subscribers
+pub const STATE_KEYFRAME_INTERVAL_SECS: u64 = 10;
+
+/// Seconds without a game loop heartbeat after which the server is not ready
+pub const READY_MAX_TICK_AGE_SECS: u64 = 10;
+
+/// Serialized zone responses kept in memory by default
+pub const ZONE_CACHE_SIZE: usize = 100;
+
+/// WebAssembly fuel granted per millisecond of script timeout
+pub const WASM_FUEL_PER_MS: u64 = 100_000;
+
+/// Default world width, in zones
+pub const WORLD_WIDTH: u32 = 100;
+
+/// Default world height, in zones
+pub const WORLD_HEIGHT: u32 = 100;
+
+/// Default maximum number of zones in the world
+pub const MAX_ZONES: usize = 1000;
+
+/// Minerals awarded for capturing a zone by default
+pub const ZONE_CAPTURE_REWARD_MINERALS: u32 = 100;
+
+/// Gas awarded for capturing a zone by default
+pub const ZONE_CAPTURE_REWARD_GAS: u32 = 50;
+
+/// Default directory holding map templates
+pub const MAPS_DIR: &str = "./maps";
+
+/// Maximum number of players in an alliance by default
+pub const MAX_ALLIANCE_SIZE: usize = 8;
+
+/// Ticks a defeated player waits before respawning by default
+pub const RESPAWN_COOLDOWN_TICKS: u64 = 100;
+
+/// Simulation ticks between two rounds of market order matching
+pub const MARKET_MATCH_INTERVAL_TICKS: u64 = 60;
+
+/// Simulation ticks a market order stays open before expiring
+pub const MARKET_ORDER_TTL_TICKS: u64 = 36_000;
+
+/// Default port of the SMTP server emails are relayed to (submission with STARTTLS)
+pub const SMTP_PORT: u16 = 587;
+
+/// Default SQLite file holding the zones and portals of the world
+pub const WORLD_DB_PATH: &str = "./geekcraft_world.db";
+
+/// Default SQLite file holding the audit log
+pub const AUDIT_DB_PATH: &str = "./geekcraft_audit.db";
+
+/// Seconds clients are asked to wait (`Retry-After`) before retrying during maintenance
+pub const MAINTENANCE_RETRY_AFTER_SECS: u64 = 300;
+
+/// Fields of [`ServerConfig`] that only take effect when the server starts
+///
+/// A reload keeps their current values (see [`ServerConfig::reloaded`]).
+pub const RESTART_REQUIRED_FIELDS: &[&str] = &[
+    "host",
+    "port",
+    "tls_cert_path",
+    "tls_key_path",
+    "control_socket",
+    "admin_users",
+    "session_duration_secs",
+    "session_sliding_percent",
+    "session_max_lifetime_secs",
+    "github_client_id",
+    "discord_client_id",
+    "smtp_host",
+    "smtp_port",
+    "smtp_username",
+    "email_from",
+    "zone_cache_size",
+    "sqlite_read_connections",
+    "script_timeout_ms",
+    "script_max_memory_mb",
+    "script_strict_mode",
+    "script_freeze_intrinsics",
+    "max_alliance_size",
+    "world_width",
+    "world_height",
+    "max_zones",
+    "maps_dir",
+    "world_seed",
+];
+
+/// A gameplay feature that can be turned off per server (see [`FeatureFlags`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FeatureFlag {
+    /// Weather events scheduled by admins
+    Weather,
+    /// Portals linking zones
+    Portals,
+    /// Bot tournaments
+    Tournament,
+    /// Players only see what is within the visibility radius of their entities
+    FogOfWar,
+}
+
+impl FeatureFlag {
+    /// Name of the feature, as used in error responses
+    pub fn name(self) -> &'static str {
+        match self {
+            FeatureFlag::Weather => "weather",
+            FeatureFlag::Portals => "portals",
+            FeatureFlag::Tournament => "tournament",
+            FeatureFlag::FogOfWar => "fog_of_war",
+        }
+    }
+}
+
+/// Gameplay features enabled on this server, all enabled by default
+///
+/// The `[features]` table of the configuration file; each flag can also be set with the
+/// environment variable named in its documentation. Disabled features answer their
+/// endpoints with `501 Not Implemented`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureFlags {
+    /// Weather scheduling, `POST /api/admin/world/weather` (`GEEKCRAFT_FEATURE_WEATHER`)
+    pub weather_enabled: bool,
+    /// Portal creation and removal, `/api/admin/world/portals` (`GEEKCRAFT_FEATURE_PORTALS`)
+    pub portals_enabled: bool,
+    /// Tournaments and their replays, `/api/admin/tournament` and `/api/tournament` (`GEEKCRAFT_FEATURE_TOURNAMENT`)
+    pub tournament_enabled: bool,
+    /// Fog of war; off, players see every enemy entity, resource and event in the zones
+    /// where they have entities (`GEEKCRAFT_FEATURE_FOG_OF_WAR`)
+    pub fog_of_war_enabled: bool,
+}
+
+impl Default for FeatureFlags {
+    fn default() -> Self {
+        Self {
+            weather_enabled: true,
+            portals_enabled: true,
+            tournament_enabled: true,
+            fog_of_war_enabled: true,
+        }
+    }
+}
+
+impl FeatureFlags {
+    /// Whether a feature is enabled
+    pub fn is_enabled(&self, flag: FeatureFlag) -> bool {
+        match flag {
+            FeatureFlag::Weather => self.weather_enabled,
+            FeatureFlag::Portals => self.portals_enabled,
+            FeatureFlag::Tournament => self.tournament_enabled,
+            FeatureFlag::FogOfWar => self.fog_of_war_enabled,
+        }
+    }
+}
+
+/// Configuration shared by the game loop and the server, replaced on reload
+pub type SharedConfig = Arc<RwLock<ServerConfig>>;
+
+/// Problem found while loading or validating a [`ServerConfig`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The configuration file could not be read
+    Io {
+        /// Path of the file
+        path: String,
+        /// Underlying error
+        message: String,
+    },
+    /// The configuration file is not valid TOML, or has unknown or mistyped fields
+    Parse {
+        /// Path of the file
+        path: String,
+        /// Underlying error
+        message: String,
+    },
+    /// A value the server cannot run with
+    Invalid {
+        /// Name of the field
+        field: &'static str,
+        /// What is wrong with it
+        message: String,
+    },
+    /// A suspicious value the server still runs with
+    Warning {
+        /// Name of the field
+        field: &'static str,
+        /// Why it is suspicious
+        message: String,
+    },
+}
+
+impl ConfigError {
+    /// Whether the server must not start with this configuration
+    pub fn is_fatal(&self) -> bool {
+        !matches!(self, ConfigError::Warning { .. })
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, message } => write!(f, "Failed to read {}: {}", path, message),
+            ConfigError::Parse { path, message } => write!(f, "Invalid configuration file {}: {}", path, message),
+            ConfigError::Invalid { field, message } => write!(f, "Invalid {}: {}", field, message),
+            ConfigError::Warning { field, message } => write!(f, "{}: {}", field, message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Every server tunable
+///
+/// Field names are the keys of the TOML file; each field can also be set with the
+/// environment variable named in its documentation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address the server listens on (`GEEKCRAFT_HOST`)
+    pub host: String,
+    /// Port the server listens on (`GEEKCRAFT_PORT`)
+    pub port: u16,
+    /// PEM certificate chain to serve HTTPS and `wss://` with (`GEEKCRAFT_TLS_CERT_PATH`)
+    pub tls_cert_path: Option<PathBuf>,
+    /// PEM private key of the certificate (`GEEKCRAFT_TLS_KEY_PATH`)
+    pub tls_key_path: Option<PathBuf>,
+    /// Unix socket accepting local admin commands, if set (`GEEKCRAFT_CONTROL_SOCKET`)
+    pub control_socket: Option<PathBuf>,
+    /// Usernames allowed to use admin endpoints (`GEEKCRAFT_ADMIN_USERS`, comma-separated)
+    pub admin_users: Vec<String>,
+    /// Whether only admins are served, other users getting `503 Service Unavailable`
+    /// (`GEEKCRAFT_MAINTENANCE_MODE`); written to the configuration file by the maintenance endpoints
+    pub maintenance_mode: bool,
+    /// Who can create an account: `open` (anyone), `invite` (with an invite code created by an
+    /// admin) or `closed` (`GEEKCRAFT_REGISTRATION_MODE`)
+    pub registration_mode: RegistrationMode,
+    /// If not empty, the only client addresses served, as CIDR ranges (`GEEKCRAFT_IP_ALLOWLIST`, comma-separated)
+    pub ip_allowlist: Vec<String>,
+    /// Client addresses refused, as CIDR ranges (`GEEKCRAFT_IP_BLOCKLIST`, comma-separated)
+    pub ip_blocklist: Vec<String>,
+    /// Whether HTTP responses over 1 KB are gzip or brotli compressed for clients accepting it (`GEEKCRAFT_ENABLE_COMPRESSION`)
+    pub enable_compression: bool,
+    /// Lifetime of a login session, in seconds (`GEEKCRAFT_SESSION_DURATION_SECS`)
+    pub session_duration_secs: i64,
+    /// Percentage of its lifetime after which a used session is extended by a full
+    /// `session_duration_secs`; 0 keeps fixed expiration (`GEEKCRAFT_SESSION_SLIDING_PERCENT`)
+    pub session_sliding_percent: u32,
+    /// Age after which a session expires even if it is still used, in seconds (`GEEKCRAFT_SESSION_MAX_LIFETIME_SECS`)
+    pub session_max_lifetime_secs: i64,
+    /// Client ID of the GitHub OAuth app players can sign in with (`GEEKCRAFT_GITHUB_CLIENT_ID`);
+    /// its secret is only read from `GEEKCRAFT_GITHUB_CLIENT_SECRET`
+    pub github_client_id: Option<String>,
+    /// Client ID of the Discord application players can sign in with (`GEEKCRAFT_DISCORD_CLIENT_ID`);
+    /// its secret is only read from `GEEKCRAFT_DISCORD_CLIENT_SECRET`
+    pub discord_client_id: Option<String>,
+    /// URL players reach the server at, e.g. `https://geekcraft.example.com`, used to build
+    /// OAuth redirect URIs and email links; defaults to the request's `Host` (`GEEKCRAFT_PUBLIC_URL`)
+    pub public_url: Option<String>,
+    /// SMTP server emails are relayed to; email is disabled without it (`GEEKCRAFT_SMTP_HOST`)
+    pub smtp_host: Option<String>,
+    /// Port of the SMTP server, which must support STARTTLS (`GEEKCRAFT_SMTP_PORT`)
+    pub smtp_port: u16,
+    /// User to log in to the SMTP server as (`GEEKCRAFT_SMTP_USERNAME`); the password is
+    /// only read from `GEEKCRAFT_SMTP_PASSWORD`
+    pub smtp_username: Option<String>,
+    /// Sender of the server's emails, e.g. `GeekCraft <noreply@example.com>` (`GEEKCRAFT_EMAIL_FROM`)
+    pub email_from: Option<String>,
+    /// Maximum concurrent WebSocket connections per user (`GEEKCRAFT_MAX_WS_PER_USER`)
+    pub max_ws_per_user: u32,
+    /// Frames per second sent to spectators (`GEEKCRAFT_SPECTATOR_FPS`)
+    pub spectator_frame_rate: u32,
+    /// Seconds between full keyframes on zone spectator streams (`GEEKCRAFT_KEYFRAME_INTERVAL_SECS`)
+    pub keyframe_interval_secs: u64,
+    /// Simulation ticks per second (`GEEKCRAFT_TICKS_PER_SECOND`)
+    pub ticks_per_second: u32,
+    /// Seconds without a game loop heartbeat after which `/api/health/ready` fails (`GEEKCRAFT_READY_MAX_TICK_AGE_SECS`)
+    pub ready_max_tick_age_secs: u64,
+    /// Zones whose serialized `GET /api/zone/:zone_id` response is kept in memory (`GEEKCRAFT_ZONE_CACHE_SIZE`)
+    pub zone_cache_size: usize,
+    /// Read-only connections of the SQLite world and audit databases, 0 to read through
+    /// the write connection (`GEEKCRAFT_SQLITE_READ_CONNECTIONS`)
+    pub sqlite_read_connections: usize,
+    /// Ticks played in each tournament match (`GEEKCRAFT_TOURNAMENT_MAX_TICKS`)
+    pub tournament_max_ticks: u64,
+    /// Maximum run time of one script execution, in milliseconds (`GEEKCRAFT_SCRIPT_TIMEOUT_MS`)
+    pub script_timeout_ms: u64,
+    /// Maximum memory of one script, in MB (`GEEKCRAFT_SCRIPT_MAX_MEMORY_MB`)
+    pub script_max_memory_mb: usize,
+    /// Run scripts in strict mode: no `eval` or `Function` constructor, and only the
+    /// standard globals (`GEEKCRAFT_SCRIPT_STRICT_MODE`)
+    pub script_strict_mode: bool,
+    /// Freeze the standard objects and their prototypes before bot code runs (`GEEKCRAFT_SCRIPT_FREEZE_INTRINSICS`)
+    pub script_freeze_intrinsics: bool,
+    /// Maximum number of scripts running at once, defaulting to one per CPU core (`GEEKCRAFT_MAX_CONCURRENT_SCRIPTS`)
+    pub max_concurrent_scripts: usize,
+    /// How long a script waits for an execution slot before it is put off to the next tick,
+    /// in milliseconds (`GEEKCRAFT_SCRIPT_QUEUE_TIMEOUT_MS`)
+    pub script_queue_timeout_ms: u64,
+    /// Maximum pending plus unread bot messages per player (`GEEKCRAFT_INBOX_LIMIT`)
+    pub inbox_limit: usize,
+    /// Whether bots can only message their allies (`GEEKCRAFT_MESSAGES_ALLIES_ONLY`)
+    pub messages_allies_only: bool,
+    /// Number of suspicious patterns that gets a code submission refused (`GEEKCRAFT_MAX_SUSPICIOUS_PATTERNS`)
+    pub max_suspicious_patterns: u32,
+    /// Oldest bot API version accepted; bots bound to an older one must be resubmitted (`GEEKCRAFT_MIN_API_VERSION`)
+    pub min_api_version: u32,
+    /// Maximum number of players in an alliance (`GEEKCRAFT_MAX_ALLIANCE_SIZE`)
+    pub max_alliance_size: usize,
+    /// Width of the world, in zones (`GEEKCRAFT_WORLD_WIDTH`)
+    pub world_width: u32,
+    /// Height of the world, in zones (`GEEKCRAFT_WORLD_HEIGHT`)
+    pub world_height: u32,
+    /// Maximum number of zones (`GEEKCRAFT_MAX_ZONES`)
+    pub max_zones: usize,
+    /// Directory holding map templates (`GEEKCRAFT_MAPS_DIR`)
+    pub maps_dir: PathBuf,
+    /// Where defeated players respawn: `original_zone` or `new_zone` (`GEEKCRAFT_RESPAWN_MODE`)
+    pub respawn_mode: RespawnMode,
+    /// Ticks between a player's defeat and their respawn (`GEEKCRAFT_RESPAWN_COOLDOWN_TICKS`)
+    pub respawn_cooldown_ticks: u64,
+    /// Simulation ticks between two script executions (`GEEKCRAFT_SCRIPT_TICK_INTERVAL`)
+    pub script_tick_interval: u64,
+    /// Simulation ticks between two rounds of market matching (`GEEKCRAFT_MARKET_MATCH_INTERVAL_TICKS`)
+    pub market_match_interval_ticks: u64,
+    /// Simulation ticks a market order stays open (`GEEKCRAFT_MARKET_ORDER_TTL_TICKS`)
+    pub market_order_ttl_ticks: u64,
+    /// Past simulation ticks players can look at through `/api/history`, 0 to keep no
+    /// history (`GEEKCRAFT_HISTORY_RETENTION_TICKS`)
+    pub history_retention_ticks: u64,
+    /// Simulation ticks between two keyframes of the history (`GEEKCRAFT_HISTORY_KEYFRAME_INTERVAL`)
+    pub history_keyframe_interval: u64,
+    /// Minerals awarded for capturing a zone (`GEEKCRAFT_ZONE_CAPTURE_REWARD_MINERALS`)
+    pub zone_capture_reward_minerals: u32,
+    /// Gas awarded for capturing a zone (`GEEKCRAFT_ZONE_CAPTURE_REWARD_GAS`)
+    pub zone_capture_reward_gas: u32,
+    /// Seed of the world's random number generator; set, the simulation is deterministic
+    /// and `/api/gamestate` reports a hash of the world's state (`GEEKCRAFT_WORLD_SEED`)
+    pub world_seed: Option<u64>,
+    /// Gameplay features enabled on this server (`GEEKCRAFT_FEATURE_*`)
+    pub features: FeatureFlags,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: LISTEN_HOST.to_string(),
+            port: DEFAULT_PORT,
+            tls_cert_path: None,
+            tls_key_path: None,
+            control_socket: None,
+            admin_users: Vec::new(),
+            maintenance_mode: false,
+            registration_mode: RegistrationMode::Open,
+            ip_allowlist: Vec::new(),
+            ip_blocklist: Vec::new(),
+            enable_compression: true,
+            session_duration_secs: SESSION_DURATION_SECS,
+            session_sliding_percent: 0,
+            session_max_lifetime_secs: SESSION_MAX_LIFETIME_SECS,
+            github_client_id: None,
+            discord_client_id: None,
+            public_url: None,
+            smtp_host: None,
+            smtp_port: SMTP_PORT,
+            smtp_username: None,
+            email_from: None,
+            max_ws_per_user: MAX_WS_PER_USER,
+            spectator_frame_rate: SPECTATOR_FRAME_RATE,
+            keyframe_interval_secs: STATE_KEYFRAME_INTERVAL_SECS,
+            ticks_per_second: TICKS_PER_SECOND,
+            ready_max_tick_age_secs: READY_MAX_TICK_AGE_SECS,
+            zone_cache_size: ZONE_CACHE_SIZE,
+            sqlite_read_connections: crate::utils::sqlite_pool::DEFAULT_SQLITE_READ_CONNECTIONS,
+            tournament_max_ticks: TOURNAMENT_MAX_TICKS,
+            script_timeout_ms: SCRIPT_TIMEOUT_MS,
+            script_max_memory_mb: SCRIPT_MAX_MEMORY_MB,
+            script_strict_mode: SCRIPT_STRICT_MODE,
+            script_freeze_intrinsics: SCRIPT_FREEZE_INTRINSICS,
+            max_concurrent_scripts: crate::scripting::slots::default_max_concurrent_scripts(),
+            script_queue_timeout_ms: SCRIPT_TIMEOUT_MS,
+            inbox_limit: crate::scripting::messaging::MAX_INBOX_MESSAGES,
+            messages_allies_only: false,
+            max_suspicious_patterns: crate::scripting::sanitizer::DEFAULT_MAX_SUSPICIOUS_PATTERNS,
+            min_api_version: crate::scripting::api_version::OLDEST_API_VERSION,
+            max_alliance_size: MAX_ALLIANCE_SIZE,
+            world_width: WORLD_WIDTH,
+            world_height: WORLD_HEIGHT,
+            max_zones: MAX_ZONES,
+            maps_dir: PathBuf::from(MAPS_DIR),
+            respawn_mode: RespawnMode::default(),
+            respawn_cooldown_ticks: RESPAWN_COOLDOWN_TICKS,
+            script_tick_interval: SCRIPT_TICK_INTERVAL,
+            market_match_interval_ticks: MARKET_MATCH_INTERVAL_TICKS,
+            market_order_ttl_ticks: MARKET_ORDER_TTL_TICKS,
+            zone_capture_reward_minerals: ZONE_CAPTURE_REWARD_MINERALS,
+            history_retention_ticks: crate::game::history::DEFAULT_HISTORY_RETENTION_TICKS,
+            history_keyframe_interval: crate::game::history::DEFAULT_HISTORY_KEYFRAME_INTERVAL,
+            zone_capture_reward_gas: ZONE_CAPTURE_REWARD_GAS,
+            world_seed: None,
+            features: FeatureFlags::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Defaults overridden by the `GEEKCRAFT_*` environment variables
+    ///
+    /// Missing or invalid values (including zero for counts and durations) fall back to
+    /// the defaults.
+    pub fn from_env() -> Self {
+        fn var(name: &str) -> Option<String> {
+            std::env::var(name).ok()
+        }
+        fn parsed<T: std::str::FromStr>(name: &str) -> Option<T> {
+            var(name).and_then(|v| v.parse().ok())
+        }
+        fn list(name: &str) -> Option<Vec<String>> {
+            var(name).map(|items| items.split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect())
+        }
+        fn positive<T: std::str::FromStr + PartialOrd + Default>(name: &str) -> Option<T> {
+            parsed(name).filter(|value: &T| *value > T::default())
+        }
+        fn flag(name: &str) -> Option<bool> {
+            var(name).map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        }
+
+        let defaults = Self::default();
+        Self {
+            host: var("GEEKCRAFT_HOST").filter(|host| !host.is_empty()).unwrap_or(defaults.host),
+            port: positive("GEEKCRAFT_PORT").unwrap_or(defaults.port),
+            tls_cert_path: var("GEEKCRAFT_TLS_CERT_PATH").filter(|path| !path.is_empty()).map(PathBuf::from),
+            tls_key_path: var("GEEKCRAFT_TLS_KEY_PATH").filter(|path| !path.is_empty()).map(PathBuf::from),
+            control_socket: var("GEEKCRAFT_CONTROL_SOCKET").filter(|path| !path.is_empty()).map(PathBuf::from),
+            admin_users: list("GEEKCRAFT_ADMIN_USERS").unwrap_or(defaults.admin_users),
+            maintenance_mode: var("GEEKCRAFT_MAINTENANCE_MODE")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(defaults.maintenance_mode),
+            registration_mode: match var("GEEKCRAFT_REGISTRATION_MODE").as_deref() {
+                Some("open") => RegistrationMode::Open,
+                Some("invite") => RegistrationMode::Invite,
+                Some("closed") => RegistrationMode::Closed,
+                _ => defaults.registration_mode,
+            },
+            ip_allowlist: list("GEEKCRAFT_IP_ALLOWLIST").unwrap_or(defaults.ip_allowlist),
+            ip_blocklist: list("GEEKCRAFT_IP_BLOCKLIST").unwrap_or(defaults.ip_blocklist),
+            enable_compression: var("GEEKCRAFT_ENABLE_COMPRESSION")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(defaults.enable_compression),
+            session_duration_secs: positive("GEEKCRAFT_SESSION_DURATION_SECS").unwrap_or(defaults.session_duration_secs),
+            session_sliding_percent: parsed("GEEKCRAFT_SESSION_SLIDING_PERCENT").unwrap_or(defaults.session_sliding_percent),
+            session_max_lifetime_secs: positive("GEEKCRAFT_SESSION_MAX_LIFETIME_SECS").unwrap_or(defaults.session_max_lifetime_secs),
+            github_client_id: var("GEEKCRAFT_GITHUB_CLIENT_ID").filter(|id| !id.is_empty()),
+            discord_client_id: var("GEEKCRAFT_DISCORD_CLIENT_ID").filter(|id| !id.is_empty()),
+            public_url: var("GEEKCRAFT_PUBLIC_URL").filter(|url| !url.is_empty()),
+            smtp_host: var("GEEKCRAFT_SMTP_HOST").filter(|host| !host.is_empty()),
+            smtp_port: positive("GEEKCRAFT_SMTP_PORT").unwrap_or(defaults.smtp_port),
+            smtp_username: var("GEEKCRAFT_SMTP_USERNAME").filter(|user| !user.is_empty()),
+            email_from: var("GEEKCRAFT_EMAIL_FROM").filter(|from| !from.is_empty()),
+            max_ws_per_user: positive("GEEKCRAFT_MAX_WS_PER_USER").unwrap_or(defaults.max_ws_per_user),
+            spectator_frame_rate: positive("GEEKCRAFT_SPECTATOR_FPS").unwrap_or(defaults.spectator_frame_rate),
+            keyframe_interval_secs: positive("GEEKCRAFT_KEYFRAME_INTERVAL_SECS").unwrap_or(defaults.keyframe_interval_secs),
+            ticks_per_second: positive("GEEKCRAFT_TICKS_PER_SECOND").unwrap_or(defaults.ticks_per_second),
+            ready_max_tick_age_secs: positive("GEEKCRAFT_READY_MAX_TICK_AGE_SECS").unwrap_or(defaults.ready_max_tick_age_secs),
+            zone_cache_size: positive("GEEKCRAFT_ZONE_CACHE_SIZE").unwrap_or(defaults.zone_cache_size),
+            sqlite_read_connections: parsed("GEEKCRAFT_SQLITE_READ_CONNECTIONS").unwrap_or(defaults.sqlite_read_connections),
+            tournament_max_ticks: positive("GEEKCRAFT_TOURNAMENT_MAX_TICKS").unwrap_or(defaults.tournament_max_ticks),
+            script_timeout_ms: positive("GEEKCRAFT_SCRIPT_TIMEOUT_MS").unwrap_or(defaults.script_timeout_ms),
+            script_max_memory_mb: positive("GEEKCRAFT_SCRIPT_MAX_MEMORY_MB").unwrap_or(defaults.script_max_memory_mb),
+            script_strict_mode: flag("GEEKCRAFT_SCRIPT_STRICT_MODE").unwrap_or(defaults.script_strict_mode),
+            script_freeze_intrinsics: flag("GEEKCRAFT_SCRIPT_FREEZE_INTRINSICS").unwrap_or(defaults.script_freeze_intrinsics),
+            max_concurrent_scripts: positive("GEEKCRAFT_MAX_CONCURRENT_SCRIPTS").unwrap_or(defaults.max_concurrent_scripts),
+            script_queue_timeout_ms: positive("GEEKCRAFT_SCRIPT_QUEUE_TIMEOUT_MS").unwrap_or(defaults.script_queue_timeout_ms),
+            inbox_limit: positive("GEEKCRAFT_INBOX_LIMIT").unwrap_or(defaults.inbox_limit),
+            messages_allies_only: var("GEEKCRAFT_MESSAGES_ALLIES_ONLY")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(defaults.messages_allies_only),
+            max_suspicious_patterns: positive("GEEKCRAFT_MAX_SUSPICIOUS_PATTERNS").unwrap_or(defaults.max_suspicious_patterns),
+            min_api_version: positive("GEEKCRAFT_MIN_API_VERSION").unwrap_or(defaults.min_api_version),
+            max_alliance_size: positive("GEEKCRAFT_MAX_ALLIANCE_SIZE").unwrap_or(defaults.max_alliance_size),
+            world_width: positive("GEEKCRAFT_WORLD_WIDTH").unwrap_or(defaults.world_width),
+            world_height: positive("GEEKCRAFT_WORLD_HEIGHT").unwrap_or(defaults.world_height),
+            max_zones: positive("GEEKCRAFT_MAX_ZONES").unwrap_or(defaults.max_zones),
+            maps_dir: var("GEEKCRAFT_MAPS_DIR").map(PathBuf::from).unwrap_or(defaults.maps_dir),
+            respawn_mode: match var("GEEKCRAFT_RESPAWN_MODE").as_deref() {
+                Some("new_zone") => RespawnMode::NewZone,
+                Some("original_zone") => RespawnMode::OriginalZone,
+                _ => defaults.respawn_mode,
+            },
+            respawn_cooldown_ticks: parsed("GEEKCRAFT_RESPAWN_COOLDOWN_TICKS").unwrap_or(defaults.respawn_cooldown_ticks),
+            script_tick_interval: positive("GEEKCRAFT_SCRIPT_TICK_INTERVAL").unwrap_or(defaults.script_tick_interval),
+            market_match_interval_ticks: positive("GEEKCRAFT_MARKET_MATCH_INTERVAL_TICKS").unwrap_or(defaults.market_match_interval_ticks),
+            market_order_ttl_ticks: positive("GEEKCRAFT_MARKET_ORDER_TTL_TICKS").unwrap_or(defaults.market_order_ttl_ticks),
+            zone_capture_reward_minerals: parsed("GEEKCRAFT_ZONE_CAPTURE_REWARD_MINERALS").unwrap_or(defaults.zone_capture_reward_minerals),
+            history_retention_ticks: parsed("GEEKCRAFT_HISTORY_RETENTION_TICKS").unwrap_or(defaults.history_retention_ticks),
+            history_keyframe_interval: positive("GEEKCRAFT_HISTORY_KEYFRAME_INTERVAL").unwrap_or(defaults.history_keyframe_interval),
+            zone_capture_reward_gas: parsed("GEEKCRAFT_ZONE_CAPTURE_REWARD_GAS").unwrap_or(defaults.zone_capture_reward_gas),
+            world_seed: parsed("GEEKCRAFT_WORLD_SEED"),
+            features: FeatureFlags {
+                weather_enabled: flag("GEEKCRAFT_FEATURE_WEATHER").unwrap_or(defaults.features.weather_enabled),
+                portals_enabled: flag("GEEKCRAFT_FEATURE_PORTALS").unwrap_or(defaults.features.portals_enabled),
+                tournament_enabled: flag("GEEKCRAFT_FEATURE_TOURNAMENT").unwrap_or(defaults.features.tournament_enabled),
+                fog_of_war_enabled: flag("GEEKCRAFT_FEATURE_FOG_OF_WAR").unwrap_or(defaults.features.fog_of_war_enabled),
+            },
+        }
+    }
+
+    /// Read a TOML file; fields it leaves out come from [`ServerConfig::from_env`]
+    ///
+    /// Unknown fields are rejected so that typos don't go unnoticed. The values are not
+    /// checked: see [`ServerConfig::validate`].
+    pub fn from_file(path: &str) -> Result<ServerConfig, ConfigError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| ConfigError::Io { path: path.to_string(), message: e.to_string() })?;
+        Self::from_toml(&text)
+            .map_err(|message| ConfigError::Parse { path: path.to_string(), message })
+    }
+
+    /// Parse TOML text over the environment and defaults (see [`ServerConfig::from_file`])
+    pub fn from_toml(text: &str) -> Result<ServerConfig, String> {
+        let overrides: toml::Table = toml::from_str(text).map_err(|e| e.to_string())?;
+        let mut merged = toml::Table::try_from(Self::from_env()).map_err(|e| e.to_string())?;
+        for (field, value) in overrides {
+            // Tables like `[features]` override the flags they set, not the whole table
+            match (merged.get_mut(&field), value) {
+                (Some(toml::Value::Table(current)), toml::Value::Table(table)) => current.extend(table),
+                (_, value) => {
+                    merged.insert(field, value);
+                }
+            }
+        }
+        merged.try_into().map_err(|e: toml::de::Error| e.to_string())
+    }
+
+    /// This configuration updated with `new`, keeping the [`RESTART_REQUIRED_FIELDS`]
+    ///
+    /// Returns the updated configuration and the restart-only fields whose new value
+    /// was ignored.
+    pub fn reloaded(&self, new: ServerConfig) -> Result<(ServerConfig, Vec<&'static str>), String> {
+        let current = toml::Table::try_from(self).map_err(|e| e.to_string())?;
+        let mut merged = toml::Table::try_from(new).map_err(|e| e.to_string())?;
+        let mut ignored = Vec::new();
+        for field in RESTART_REQUIRED_FIELDS {
+            if merged.get(*field) != current.get(*field) {
+                ignored.push(*field);
+                match current.get(*field) {
+                    Some(value) => merged.insert(field.to_string(), value.clone()),
+                    None => merged.remove(*field),
+                };
+            }
+        }
+        let config = merged.try_into().map_err(|e: toml::de::Error| e.to_string())?;
+        Ok((config, ignored))
+    }
+
+    /// This configuration with the fields of a JSON object replaced
+    ///
+    /// Unknown fields are rejected, and so are [`RESTART_REQUIRED_FIELDS`] given a
+    /// different value. The values are not checked: see [`ServerConfig::validate`].
+    pub fn patched(&self, patch: serde_json::Value) -> Result<ServerConfig, String> {
+        let serde_json::Value::Object(patch) = patch else {
+            return Err("Configuration patch must be a JSON object".to_string());
+        };
+        let mut merged = match serde_json::to_value(self).map_err(|e| e.to_string())? {
+            serde_json::Value::Object(fields) => fields,
+            _ => return Err("Configuration is not a JSON object".to_string()),
+        };
+        for (field, value) in patch {
+            if RESTART_REQUIRED_FIELDS.contains(&field.as_str()) && merged.get(&field) != Some(&value) {
+                return Err(format!("{} cannot be changed while the server runs, restart it instead", field));
+            }
+            match (merged.get_mut(&field), value) {
+                (Some(serde_json::Value::Object(current)), serde_json::Value::Object(object)) => current.extend(object),
+                (_, value) => {
+                    merged.insert(field, value);
+                }
+            }
+        }
+        serde_json::from_value(serde_json::Value::Object(merged)).map_err(|e| e.to_string())
+    }
+
+    /// Set one field of a TOML configuration file, keeping its other fields and comments
+    ///
+    /// The file is created if it does not exist. Only the file changes: apply the new
+    /// value to the running server separately.
+    pub fn write_field(path: &str, field: &str, value: impl Into<toml_edit::Value>) -> Result<(), String> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path, e)),
+        };
+        let mut document: toml_edit::DocumentMut = text.parse()
+            .map_err(|e: toml_edit::TomlError| format!("Invalid configuration file {}: {}", path, e))?;
+        document[field] = toml_edit::value(value);
+        std::fs::write(path, document.to_string()).map_err(|e| format!("Failed to write {}: {}", path, e))
+    }
+
+    /// Problems with the configuration; the server should not start if any is fatal
+    pub fn validate(&self) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+        let mut invalid = |field: &'static str, message: &str| {
+            errors.push(ConfigError::Invalid { field, message: message.to_string() });
+        };
+
+        if self.host.trim().is_empty() {
+            invalid("host", "must not be empty");
+        }
+        if self.port == 0 {
+            invalid("port", "must not be 0");
+        }
+        if self.session_duration_secs <= 0 {
+            invalid("session_duration_secs", "must be positive");
+        }
+        if self.session_sliding_percent > 100 {
+            invalid("session_sliding_percent", "must be at most 100");
+        }
+        if self.session_max_lifetime_secs < self.session_duration_secs {
+            invalid("session_max_lifetime_secs", "must be at least session_duration_secs");
+        }
+        let positive_fields = [
+            ("max_ws_per_user", self.max_ws_per_user as u64),
+            ("spectator_frame_rate", self.spectator_frame_rate as u64),
+            ("keyframe_interval_secs", self.keyframe_interval_secs),
+            ("smtp_port", self.smtp_port as u64),
+            ("ticks_per_second", self.ticks_per_second as u64),
+            ("ready_max_tick_age_secs", self.ready_max_tick_age_secs),
+            ("zone_cache_size", self.zone_cache_size as u64),
+            ("tournament_max_ticks", self.tournament_max_ticks),
+            ("script_timeout_ms", self.script_timeout_ms),
+            ("script_max_memory_mb", self.script_max_memory_mb as u64),
+            ("max_concurrent_scripts", self.max_concurrent_scripts as u64),
+            ("script_queue_timeout_ms", self.script_queue_timeout_ms),
+            ("inbox_limit", self.inbox_limit as u64),
+            ("max_suspicious_patterns", self.max_suspicious_patterns as u64),
+            ("max_alliance_size", self.max_alliance_size as u64),
+            ("world_width", self.world_width as u64),
+            ("world_height", self.world_height as u64),
+            ("max_zones", self.max_zones as u64),
+            ("script_tick_interval", self.script_tick_interval),
+            ("market_match_interval_ticks", self.market_match_interval_ticks),
+            ("market_order_ttl_ticks", self.market_order_ttl_ticks),
+            ("history_keyframe_interval", self.history_keyframe_interval),
+        ];
+        for (field, value) in positive_fields {
+            if value == 0 {
+                invalid(field, "must be greater than 0");
+            }
+        }
+        if let Err(e) = crate::scripting::api_version::check_api_version(self.min_api_version) {
+            invalid("min_api_version", &e);
+        }
+        if self.ticks_per_second > 1000 {
+            invalid("ticks_per_second", "must be at most 1000");
+        }
+        if let Some(url) = &self.public_url {
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                invalid("public_url", "must start with http:// or https://");
+            }
+        }
+        if let Some(from) = &self.email_from {
+            if let Err(e) = from.parse::<lettre::message::Mailbox>() {
+                invalid("email_from", &format!("is not an email address: {}", e));
+            }
+        }
+        for (field, ranges) in [("ip_allowlist", &self.ip_allowlist), ("ip_blocklist", &self.ip_blocklist)] {
+            if let Err(e) = parse_ranges(ranges) {
+                invalid(field, &e);
+            }
+        }
+
+        if (1..1024).contains(&self.port) {
+            errors.push(ConfigError::Warning {
+                field: "port",
+                message: format!("port {} is privileged and needs root (or CAP_NET_BIND_SERVICE) to bind", self.port),
+            });
+        }
+        if self.ticks_per_second > 0 && self.script_tick_interval > 0 {
+            let script_tick_ms = self.script_tick_interval * 1000 / self.ticks_per_second as u64;
+            if self.script_timeout_ms > script_tick_ms {
+                errors.push(ConfigError::Warning {
+                    field: "script_timeout_ms",
+                    message: format!("{}ms is longer than the {}ms between two script ticks", self.script_timeout_ms, script_tick_ms),
+                });
+            }
+        }
+        if self.tls_cert_path.is_some() != self.tls_key_path.is_some() {
+            errors.push(ConfigError::Warning {
+                field: if self.tls_cert_path.is_some() { "tls_key_path" } else { "tls_cert_path" },
+                message: "TLS needs both a certificate and a key, serving plain HTTP".to_string(),
+            });
+        }
+        if self.smtp_host.is_some() != self.email_from.is_some() {
+            errors.push(ConfigError::Warning {
+                field: if self.smtp_host.is_some() { "email_from" } else { "smtp_host" },
+                message: "email needs both an SMTP server and a sender address, emails are disabled".to_string(),
+            });
+        }
+        if !self.maps_dir.is_dir() {
+            errors.push(ConfigError::Warning {
+                field: "maps_dir",
+                message: format!("{} is not a directory, map templates are unavailable", self.maps_dir.display()),
+            });
+        }
+        errors
+    }
+
+    /// Time between two simulation ticks
+    pub fn tick_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.ticks_per_second.max(1)
+    }
+
+    /// Certificate and key paths, if both are set (the server then serves HTTPS)
+    pub fn tls_paths(&self) -> Option<(String, String)> {
+        let cert = self.tls_cert_path.as_ref()?;
+        let key = self.tls_key_path.as_ref()?;
+        Some((cert.display().to_string(), key.display().to_string()))
+    }
+
+    /// Limits applied to each script execution
+    pub fn script_limits(&self) -> ScriptLimits {
+        ScriptLimits {
+            timeout: Duration::from_millis(self.script_timeout_ms),
+            max_memory_bytes: self.script_max_memory_mb * 1024 * 1024,
+            strict: self.script_strict_mode,
+            freeze_intrinsics: self.script_freeze_intrinsics,
+        }
+    }
+
+    /// World settings (zone generation uses its defaults)
+    pub fn world_config(&self) -> WorldConfig {
+        WorldConfig {
+            width: self.world_width,
+            height: self.world_height,
+            max_zones: self.max_zones,
+            zone_capture_reward_resources: [
+                (ResourceType::Minerals, self.zone_capture_reward_minerals),
+                (ResourceType::Gas, self.zone_capture_reward_gas),
+            ].into(),
+            maps_dir: self.maps_dir.clone(),
+            respawn_mode: self.respawn_mode,
+            respawn_cooldown_ticks: self.respawn_cooldown_ticks,
+            script_tick_interval: self.script_tick_interval,
+            market_match_interval_ticks: self.market_match_interval_ticks,
+            market_order_ttl_ticks: self.market_order_ttl_ticks,
+            seed: self.world_seed,
+            fog_of_war: self.features.fog_of_war_enabled,
+            history_retention_ticks: self.history_retention_ticks,
+            history_keyframe_interval: self.history_keyframe_interval,
+            ..WorldConfig::default()
+        }
+    }
+}
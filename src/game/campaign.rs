@@ -1,16 +1,27 @@
 //! Campaign module
 //! 
-//! Manages campaign runs, save/load functionality, and game state persistence.
+//! Manages campaign runs, save/load functionality, and game state persistence. Runs can
+//! be started against built-in NPC opponents (see [`crate::game::npc`]), or on a
+//! scenario whose objectives decide when the run is won or lost (see
+//! [`crate::game::scenario`]).
+//!
+//! Runs are versioned for optimistic locking: every change gives a run a new `version`.
+//! [`CampaignManager::update_run`] only applies a change to the version the caller read,
+//! and [`CampaignManager::save_run`] refuses to overwrite a save file someone else wrote
+//! since this manager last read or wrote it; both fail with
+//! [`CampaignError::VersionConflict`] instead of losing the other change.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use crate::game::world::World;
-use crate::scripting::sandbox::ScriptEngine;
+use crate::game::npc::Npc;
+use crate::game::scenario::{ObjectiveEvaluator, ObjectiveProgress, Scenario};
+use crate::game::world::{World, WorldConfig};
 
 /// Validate run_id to prevent path traversal attacks
 fn validate_run_id(run_id: &str) -> Result<(), String> {
@@ -36,36 +47,165 @@ fn validate_run_id(run_id: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Failure of a campaign operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CampaignError {
+    /// No run with this ID is in memory
+    NotFound {
+        /// ID of the run
+        run_id: String,
+    },
+    /// The run changed since the version the caller read (or its save file since this
+    /// manager last read or wrote it)
+    VersionConflict {
+        /// ID of the run
+        run_id: String,
+        /// Version the caller expected
+        expected: u64,
+        /// Version found
+        found: u64,
+    },
+    /// Invalid run ID, or failure to read or write the save file
+    Other(String),
+}
+
+impl fmt::Display for CampaignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CampaignError::NotFound { run_id } => write!(f, "Run {} not found", run_id),
+            CampaignError::VersionConflict { run_id, expected, found } => write!(
+                f,
+                "Run {} was changed concurrently (version {} expected, {} found); reload it and try again",
+                run_id, expected, found
+            ),
+            CampaignError::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for CampaignError {}
+
+impl From<String> for CampaignError {
+    fn from(message: String) -> Self {
+        CampaignError::Other(message)
+    }
+}
+
 /// Represents a single campaign run instance
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CampaignRun {
+    /// Unique run identifier
     pub run_id: String,
+    /// Current tick of the run
     pub tick: u64,
+    /// Whether the run is currently ticking
     pub running: bool,
+    /// Run creation timestamp (Unix epoch)
     pub created_at: i64,
+    /// Incremented on every change of the run (tick, start, stop, update)
+    #[serde(default)]
+    pub version: u64,
+    /// Whether spectators may watch this run
+    #[serde(default = "default_allow_spectators")]
+    pub allow_spectators: bool,
+    /// Map template the run was started on (if any)
+    #[serde(default)]
+    pub map_template: Option<String>,
+    /// Behaviors of the NPC opponents of the run (see [`crate::game::npc::NPC_BEHAVIORS`])
+    #[serde(default)]
+    pub npc_opponents: Vec<String>,
+    /// Techs researched by each player of the run's world, as of the last save
+    #[serde(default)]
+    pub researched_techs: BTreeMap<String, BTreeSet<String>>,
+    /// Scenario the run is played on (if any)
+    #[serde(default)]
+    pub scenario: Option<Scenario>,
+    /// Whether the run is under way, won, or lost
+    #[serde(default)]
+    pub status: RunStatus,
+    /// Progress towards each objective of the scenario
+    #[serde(default)]
+    pub objectives: Vec<ObjectiveProgress>,
+}
+
+fn default_allow_spectators() -> bool {
+    true
+}
+
+/// Outcome of a run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    /// Still being played (runs without a scenario stay so)
+    #[default]
+    InProgress,
+    /// Every mandatory objective of the scenario is met
+    Completed,
+    /// A mandatory objective of the scenario failed
+    Failed,
+}
+
+/// Options chosen by the creator when starting a run
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// Whether spectators may watch the run (default: true)
+    pub allow_spectators: bool,
+    /// Map template to start the run on (see [`World::add_zone_from_template`])
+    pub map_template: Option<String>,
+    /// Behaviors of NPC opponents to spawn, each with its own zone and units
+    pub npc_opponents: Vec<String>,
+    /// Scenario to play (see [`CampaignManager::scenario`]); it sets the map template
+    /// and NPC opponents
+    pub scenario: Option<Scenario>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            allow_spectators: true,
+            map_template: None,
+            npc_opponents: Vec::new(),
+            scenario: None,
+        }
+    }
 }
 
 impl CampaignRun {
+    /// Create a new (stopped) run
     pub fn new(run_id: String) -> Self {
         Self {
             run_id,
             tick: 0,
             running: false,
             created_at: chrono::Utc::now().timestamp(),
+            version: 0,
+            allow_spectators: true,
+            map_template: None,
+            npc_opponents: Vec::new(),
+            researched_techs: BTreeMap::new(),
+            scenario: None,
+            status: RunStatus::InProgress,
+            objectives: Vec::new(),
         }
     }
 
+    /// Start the run
     pub fn start(&mut self) {
         self.running = true;
+        self.version += 1;
     }
 
+    /// Stop the run
     pub fn stop(&mut self) {
         self.running = false;
+        self.version += 1;
     }
 
+    /// Advance the run by one tick (only while running)
     pub fn tick(&mut self) {
         if self.running {
             self.tick += 1;
+            self.version += 1;
         }
     }
 }
@@ -76,33 +216,49 @@ pub struct InMemoryRunStore {
 }
 
 impl InMemoryRunStore {
+    /// Create an empty run store
     pub fn new() -> Self {
         Self {
             runs: HashMap::new(),
         }
     }
 
+    /// Create and store a new run
     pub fn create_run(&mut self, run_id: String) -> CampaignRun {
         let run = CampaignRun::new(run_id.clone());
         self.runs.insert(run_id, run.clone());
         run
     }
 
+    /// Get a run by ID
     pub fn get_run(&self, run_id: &str) -> Option<&CampaignRun> {
         self.runs.get(run_id)
     }
 
+    /// Get a mutable reference to a run by ID
     pub fn get_run_mut(&mut self, run_id: &str) -> Option<&mut CampaignRun> {
         self.runs.get_mut(run_id)
     }
 
+    /// Remove a run from the store
     pub fn remove_run(&mut self, run_id: &str) -> Option<CampaignRun> {
         self.runs.remove(run_id)
     }
 
+    /// Insert (or replace) a run
     pub fn insert_run(&mut self, run_id: String, run: CampaignRun) {
         self.runs.insert(run_id, run);
     }
+
+    /// IDs of the runs that are running, sorted
+    pub fn running_run_ids(&self) -> Vec<String> {
+        let mut run_ids: Vec<String> = self.runs.values()
+            .filter(|run| run.running)
+            .map(|run| run.run_id.clone())
+            .collect();
+        run_ids.sort();
+        run_ids
+    }
 }
 
 impl Default for InMemoryRunStore {
@@ -111,18 +267,33 @@ impl Default for InMemoryRunStore {
     }
 }
 
+/// Directory campaign runs are saved to: `GEEKCRAFT_SAVE_DIR` (default `./saves`)
+pub fn save_dir_from_env() -> PathBuf {
+    PathBuf::from(std::env::var("GEEKCRAFT_SAVE_DIR").unwrap_or_else(|_| "./saves".to_string()))
+}
+
 /// Campaign manager handles campaign operations including persistence
 pub struct CampaignManager {
     store: InMemoryRunStore,
     save_dir: PathBuf,
+    /// Configuration of the worlds of runs started on a map template
+    world_config: WorldConfig,
+    /// Worlds of runs started on a map template, against NPCs or on a given world (run_id -> world)
+    worlds: HashMap<String, World>,
+    /// NPC opponents of each run (run_id -> NPCs)
+    npcs: HashMap<String, Vec<Npc>>,
+    /// Objective evaluators of runs played on a scenario (run_id -> evaluator)
+    evaluators: HashMap<String, ObjectiveEvaluator>,
+    /// Version of each run in its save file when this manager last read or wrote it
+    saved_versions: HashMap<String, u64>,
 }
 
 impl CampaignManager {
+    /// Create a campaign manager using `GEEKCRAFT_SAVE_DIR` (default `./saves`)
+    ///
+    /// Map templates are read from the directory given by [`WorldConfig::from_env`].
     pub fn new() -> Self {
-        let save_dir = std::env::var("GEEKCRAFT_SAVE_DIR")
-            .unwrap_or_else(|_| "./saves".to_string());
-        
-        let save_path = PathBuf::from(save_dir);
+        let save_path = save_dir_from_env();
         
         // Create save directory if it doesn't exist
         if !save_path.exists() {
@@ -136,27 +307,125 @@ impl CampaignManager {
         Self {
             store: InMemoryRunStore::new(),
             save_dir: save_path,
+            world_config: WorldConfig::from_env(),
+            worlds: HashMap::new(),
+            npcs: HashMap::new(),
+            evaluators: HashMap::new(),
+            saved_versions: HashMap::new(),
         }
     }
 
-    pub fn start_run(&mut self, run_id: String) -> Result<CampaignRun, String> {
+    /// Scenario `name`, from the maps directory or built in (see [`Scenario::find`])
+    pub fn scenario(&self, name: &str) -> Result<Scenario, String> {
+        Scenario::find(&self.world_config.maps_dir, name)
+    }
+
+    /// Create and start a new run, optionally on a map template
+    pub fn start_run(&mut self, run_id: String, map_template: Option<String>) -> Result<CampaignRun, String> {
+        self.start_run_with_options(run_id, RunOptions {
+            map_template,
+            ..RunOptions::default()
+        })
+    }
+
+    /// Create and start a new run with creator-supplied options
+    pub fn start_run_with_options(&mut self, run_id: String, mut options: RunOptions) -> Result<CampaignRun, String> {
         validate_run_id(&run_id)?;
         
         if self.store.get_run(&run_id).is_some() {
             return Err(format!("Run {} already exists", run_id));
         }
 
+        if let Some(scenario) = &options.scenario {
+            if options.map_template.is_some() || !options.npc_opponents.is_empty() {
+                return Err(format!("Scenario {} sets the map template and NPC opponents of the run", scenario.name));
+            }
+            scenario.validate()?;
+            options.map_template = scenario.map_template.clone();
+            options.npc_opponents = scenario.npc_opponents.clone();
+        }
+        self.build_world(&run_id, options.map_template.as_deref(), &options.npc_opponents, options.scenario.as_ref())?;
+        let objectives = match (&options.scenario, self.worlds.get(&run_id)) {
+            (Some(scenario), Some(world)) => scenario.initial_progress(world),
+            _ => Vec::new(),
+        };
+
         self.store.create_run(run_id.clone());
         let run = self.store.get_run_mut(&run_id)
             .ok_or_else(|| "Failed to retrieve created run".to_string())?;
+        run.allow_spectators = options.allow_spectators;
+        run.map_template = options.map_template;
+        run.npc_opponents = options.npc_opponents;
+        run.scenario = options.scenario;
+        run.objectives = objectives;
+        if let (Some(scenario), Some(world)) = (&run.scenario, self.worlds.get_mut(&run_id)) {
+            world.set_objectives(&scenario.player, run.objectives.clone());
+        }
         run.start();
         Ok(run.clone())
     }
 
+    /// Create and start a new run on a world built by the caller (e.g. a lobby's match world)
+    ///
+    /// The options may not set a map template, NPC opponents or a scenario, which build
+    /// a world of their own.
+    pub fn start_run_with_world(&mut self, run_id: String, options: RunOptions, world: World) -> Result<CampaignRun, String> {
+        if options.map_template.is_some() || !options.npc_opponents.is_empty() || options.scenario.is_some() {
+            return Err(format!("Run {} is played on the given world, without a map template, NPC opponents or scenario", run_id));
+        }
+        let run = self.start_run_with_options(run_id.clone(), options)?;
+        self.worlds.insert(run_id.clone(), world);
+        self.npcs.insert(run_id, Vec::new());
+        Ok(run)
+    }
+
+    /// World of a run started on a map template, against NPCs or on a given world
+    pub fn world(&self, run_id: &str) -> Option<&World> {
+        self.worlds.get(run_id)
+    }
+
+    /// Mutable world of a run started on a map template, against NPCs or on a given world
+    pub fn world_mut(&mut self, run_id: &str) -> Option<&mut World> {
+        self.worlds.get_mut(run_id)
+    }
+
+    /// NPC opponents of a run
+    pub fn npcs(&self, run_id: &str) -> &[Npc] {
+        self.npcs.get(run_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Build a fresh world for a run: the zone of a map template, then one zone per NPC,
+    /// then the start of the scenario's player
+    ///
+    /// Runs with none of them have no world.
+    fn build_world(&mut self, run_id: &str, map_template: Option<&str>, npc_opponents: &[String], scenario: Option<&Scenario>) -> Result<(), String> {
+        if map_template.is_none() && npc_opponents.is_empty() && scenario.is_none() {
+            return Ok(());
+        }
+        let mut world = World::with_config(self.world_config.clone());
+        if let Some(name) = map_template {
+            world.add_zone_from_template(name)?;
+        }
+        let npcs = npc_opponents.iter()
+            .enumerate()
+            .map(|(index, behavior)| Npc::spawn(&mut world, behavior, index + 1))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(scenario) = scenario {
+            scenario.set_up(&mut world)?;
+            self.evaluators.insert(run_id.to_string(), ObjectiveEvaluator::new(scenario.clone()));
+        }
+
+        self.worlds.insert(run_id.to_string(), world);
+        self.npcs.insert(run_id.to_string(), npcs);
+        Ok(())
+    }
+
+    /// Get a snapshot of a run's state
     pub fn get_run_state(&self, run_id: &str) -> Option<CampaignRun> {
         self.store.get_run(run_id).cloned()
     }
 
+    /// Stop a running run
     pub fn stop_run(&mut self, run_id: &str) -> Result<(), String> {
         let run = self.store.get_run_mut(run_id)
             .ok_or_else(|| format!("Run {} not found", run_id))?;
@@ -165,7 +434,12 @@ impl CampaignManager {
         Ok(())
     }
 
-    pub fn tick_run(&mut self, run_id: &str, _world: &mut World, _script_engine: &mut ScriptEngine) -> Result<(), String> {
+    /// Advance a running run by one tick
+    ///
+    /// The run's world (if any) advances by one simulation tick; on script ticks its NPCs
+    /// issue their commands first, like scripts in the main game loop. The objectives of
+    /// the run's scenario are then evaluated: once the run is completed or failed, it stops.
+    pub fn tick_run(&mut self, run_id: &str) -> Result<(), String> {
         let run = self.store.get_run_mut(run_id)
             .ok_or_else(|| format!("Run {} not found", run_id))?;
         
@@ -174,31 +448,115 @@ impl CampaignManager {
         }
 
         run.tick();
-        
-        // TODO: Actual game simulation logic would go here
-        // For now, this is a minimal implementation
+
+        if let Some(world) = self.worlds.get_mut(run_id) {
+            if world.is_script_tick() {
+                for npc in self.npcs.get_mut(run_id).into_iter().flatten() {
+                    for error in npc.take_turn(world) {
+                        log::debug!("NPC {} of run {}: {}", npc.player_id(), run_id, error);
+                    }
+                }
+            }
+            world.advance_tick();
+
+            if let Some(evaluator) = self.evaluators.get_mut(run_id) {
+                run.status = evaluator.evaluate(world, run.tick, &mut run.objectives);
+                world.set_objectives(&evaluator.scenario().player, run.objectives.clone());
+                if run.status != RunStatus::InProgress {
+                    log::info!("Run {} is {:?} at tick {}", run_id, run.status, run.tick);
+                    run.stop();
+                }
+            }
+        }
         
         Ok(())
     }
 
-    pub fn save_run(&self, run_id: &str) -> Result<(), String> {
+    /// Save a run to disk as JSON, with the techs researched in its world
+    ///
+    /// The file is replaced atomically. Fails with [`CampaignError::VersionConflict`] if
+    /// the file was written by someone else since this manager last read or wrote it; a
+    /// run this manager never loaded nor saved replaces any older save with its ID.
+    pub fn save_run(&mut self, run_id: &str) -> Result<(), CampaignError> {
         validate_run_id(run_id)?;
         
-        let run = self.store.get_run(run_id)
-            .ok_or_else(|| format!("Run {} not found", run_id))?;
+        let mut run = self.store.get_run(run_id)
+            .ok_or_else(|| CampaignError::NotFound { run_id: run_id.to_string() })?
+            .clone();
+        if let Some(world) = self.worlds.get(run_id) {
+            run.researched_techs = world.researched_techs();
+        }
 
         let file_path = self.save_dir.join(format!("{}.json", run_id));
+        if let Some(&expected) = self.saved_versions.get(run_id) {
+            match Self::stored_version(&file_path)? {
+                Some(found) if found != expected => {
+                    return Err(CampaignError::VersionConflict { run_id: run_id.to_string(), expected, found });
+                }
+                _ => {}
+            }
+        }
         
-        let json = serde_json::to_string_pretty(run)
+        let json = serde_json::to_string_pretty(&run)
             .map_err(|e| format!("Failed to serialize run: {}", e))?;
         
-        fs::write(&file_path, json)
+        let temp_path = self.save_dir.join(format!("{}.json.tmp", run_id));
+        fs::write(&temp_path, json)
+            .and_then(|()| fs::rename(&temp_path, &file_path))
             .map_err(|e| format!("Failed to write save file: {}", e))?;
+        self.saved_versions.insert(run_id.to_string(), run.version);
 
-        log::info!("Saved run {} to {:?}", run_id, file_path);
+        log::info!("Saved run {} (version {}) to {:?}", run_id, run.version, file_path);
         Ok(())
     }
 
+    /// Save every running run, returning the outcome for each
+    pub fn save_running_runs(&mut self) -> Vec<(String, Result<(), CampaignError>)> {
+        self.store.running_run_ids().into_iter()
+            .map(|run_id| {
+                let result = self.save_run(&run_id);
+                (run_id, result)
+            })
+            .collect()
+    }
+
+    /// Version held by a save file (`None` if there is no file)
+    fn stored_version(file_path: &Path) -> Result<Option<u64>, String> {
+        if !file_path.exists() {
+            return Ok(None);
+        }
+        let json = fs::read_to_string(file_path)
+            .map_err(|e| format!("Failed to read save file: {}", e))?;
+        let run: CampaignRun = serde_json::from_str(&json)
+            .map_err(|e| format!("Failed to deserialize run: {}", e))?;
+        Ok(Some(run.version))
+    }
+
+    /// Change a run and save it, if it is still at `expected_version`
+    ///
+    /// `update` must not change the run's ID. The run gets the next version, and is saved
+    /// like [`CampaignManager::save_run`] does; if the save fails, the change is undone.
+    /// Fails with [`CampaignError::VersionConflict`] if the run changed since the caller
+    /// read it.
+    pub fn update_run(&mut self, run_id: &str, expected_version: u64, update: impl FnOnce(&mut CampaignRun)) -> Result<CampaignRun, CampaignError> {
+        let run = self.store.get_run_mut(run_id)
+            .ok_or_else(|| CampaignError::NotFound { run_id: run_id.to_string() })?;
+        if run.version != expected_version {
+            return Err(CampaignError::VersionConflict { run_id: run_id.to_string(), expected: expected_version, found: run.version });
+        }
+
+        let previous = run.clone();
+        update(run);
+        run.version = expected_version + 1;
+        let updated = run.clone();
+        if let Err(e) = self.save_run(run_id) {
+            self.store.insert_run(run_id.to_string(), previous);
+            return Err(e);
+        }
+        Ok(updated)
+    }
+
+    /// Load a run from disk into the store
     pub fn load_run(&mut self, run_id: &str) -> Result<CampaignRun, String> {
         validate_run_id(run_id)?;
         
@@ -213,6 +571,19 @@ impl CampaignManager {
         
         let run: CampaignRun = serde_json::from_str(&json)
             .map_err(|e| format!("Failed to deserialize run: {}", e))?;
+        self.saved_versions.insert(run_id.to_string(), run.version);
+
+        // Runs on a map template, against NPCs, or on a scenario start again from the
+        // beginning, keeping the techs researched and the objectives met so far
+        self.build_world(run_id, run.map_template.as_deref(), &run.npc_opponents, run.scenario.as_ref())?;
+        if let Some(world) = self.worlds.get_mut(run_id) {
+            for (player_id, researched) in &run.researched_techs {
+                world.set_researched_techs(player_id, researched.clone());
+            }
+            if let Some(scenario) = &run.scenario {
+                world.set_objectives(&scenario.player, run.objectives.clone());
+            }
+        }
 
         self.store.insert_run(run_id.to_string(), run.clone());
         
@@ -220,6 +591,7 @@ impl CampaignManager {
         Ok(run)
     }
 
+    /// List the IDs of all saved runs
     pub fn list_all_saves(&self) -> Result<Vec<String>, String> {
         if !self.save_dir.exists() {
             return Ok(Vec::new());
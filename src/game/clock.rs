@@ -0,0 +1,142 @@
+//! World clock module
+//!
+//! Day/night cycle. Each day is split into four equal phases (Dawn, Day, Dusk, Night).
+//! At night entities see half as far and swamps are 50% more costly to cross.
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::zone::SurfaceType;
+
+/// Default length of a full day in ticks
+pub const DEFAULT_DAY_LENGTH_TICKS: u64 = 100;
+
+/// Visibility radius of entities (in tiles) outside the night
+pub const BASE_VISIBILITY_RADIUS: u32 = 10;
+
+/// Phase of the day
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DayPhase {
+    /// First quarter of the day
+    Dawn,
+    /// Second quarter of the day
+    Day,
+    /// Third quarter of the day
+    Dusk,
+    /// Last quarter of the day
+    Night,
+}
+
+/// Tracks the time of day
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorldClock {
+    /// Ticks elapsed since the world started
+    pub current_tick: u64,
+    /// Length of a full day in ticks
+    pub day_length_ticks: u64,
+    /// Current phase
+    pub phase: DayPhase,
+}
+
+impl WorldClock {
+    /// Create a clock at tick 0 with the given day length (at least 4 ticks)
+    pub fn new(day_length_ticks: u64) -> Self {
+        let day_length_ticks = day_length_ticks.max(4);
+        Self {
+            current_tick: 0,
+            day_length_ticks,
+            phase: Self::phase_at(0, day_length_ticks),
+        }
+    }
+
+    /// Phase at a tick for a given day length
+    pub fn phase_at(tick: u64, day_length_ticks: u64) -> DayPhase {
+        let quarter = day_length_ticks.max(4) / 4;
+        match (tick % day_length_ticks.max(4)) / quarter {
+            0 => DayPhase::Dawn,
+            1 => DayPhase::Day,
+            2 => DayPhase::Dusk,
+            _ => DayPhase::Night,
+        }
+    }
+
+    /// Advance the clock by one tick
+    pub fn advance(&mut self) {
+        self.current_tick += 1;
+        self.phase = Self::phase_at(self.current_tick, self.day_length_ticks);
+    }
+
+    /// Visibility radius of entities in the current phase
+    pub fn visibility_radius(&self) -> u32 {
+        match self.phase {
+            DayPhase::Night => BASE_VISIBILITY_RADIUS / 2,
+            _ => BASE_VISIBILITY_RADIUS,
+        }
+    }
+
+    /// Cost of entering a tile in the current phase (`None` if not walkable)
+    pub fn movement_cost(&self, surface: SurfaceType) -> Option<u32> {
+        let cost = surface.movement_cost()?;
+        match (self.phase, surface) {
+            (DayPhase::Night, SurfaceType::Swamp) => Some(cost + cost / 2),
+            _ => Some(cost),
+        }
+    }
+}
+
+impl Default for WorldClock {
+    fn default() -> Self {
+        Self::new(DEFAULT_DAY_LENGTH_TICKS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_transitions_at_quarter_boundaries() {
+        let mut clock = WorldClock::default();
+        let mut transitions = Vec::new();
+        let mut phase = clock.phase;
+
+        for _ in 0..200 {
+            clock.advance();
+            if clock.phase != phase {
+                transitions.push((clock.current_tick, clock.phase));
+                phase = clock.phase;
+            }
+        }
+
+        assert_eq!(transitions, vec![
+            (25, DayPhase::Day),
+            (50, DayPhase::Dusk),
+            (75, DayPhase::Night),
+            (100, DayPhase::Dawn),
+            (125, DayPhase::Day),
+            (150, DayPhase::Dusk),
+            (175, DayPhase::Night),
+            (200, DayPhase::Dawn),
+        ]);
+    }
+
+    #[test]
+    fn test_night_halves_visibility_and_slows_swamps() {
+        let mut clock = WorldClock::new(8);
+        assert_eq!(clock.phase, DayPhase::Dawn);
+        assert_eq!(clock.visibility_radius(), BASE_VISIBILITY_RADIUS);
+
+        while clock.phase != DayPhase::Night {
+            clock.advance();
+        }
+        assert_eq!(clock.current_tick, 6);
+        assert_eq!(clock.visibility_radius(), BASE_VISIBILITY_RADIUS / 2);
+        assert_eq!(clock.movement_cost(SurfaceType::Swamp), Some(6));
+        assert_eq!(clock.movement_cost(SurfaceType::Plain), Some(2));
+        assert_eq!(clock.movement_cost(SurfaceType::Obstacle), None);
+
+        clock.advance();
+        clock.advance();
+        assert_eq!(clock.phase, DayPhase::Dawn);
+        assert_eq!(clock.movement_cost(SurfaceType::Swamp), Some(4));
+    }
+}
@@ -0,0 +1,221 @@
+//! Append-only log of game actions
+//!
+//! The world appends an entry to its [`ActionLog`] before each entity move, harvest and
+//! zone capture, and marks it applied once the change is made. Applied entries are
+//! written to the log file, one JSON object per line, and never rewritten. Replaying the
+//! applied entries on a [`Checkpoint`] of the world rebuilds its state at the end of the
+//! log. (The recent events shown to players are [`crate::game::events::EventLog`].)
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::world::World;
+use super::zone::ResourceType;
+
+/// A change of world state, with what is needed to make it again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameAction {
+    /// An entity moved to a tile (through a portal if one is there)
+    EntityMoved {
+        /// Zone the entity moved from
+        zone_id: String,
+        /// Entity ID within the zone
+        entity_id: u32,
+        /// Column of the tile entered
+        x: usize,
+        /// Row of the tile entered
+        y: usize,
+    },
+    /// A player took `amount` from the deposit at (`x`, `y`)
+    ResourceHarvested {
+        /// Harvesting player
+        player_id: String,
+        /// Zone of the deposit
+        zone_id: String,
+        /// Column of the deposit
+        x: usize,
+        /// Row of the deposit
+        y: usize,
+        /// Resource collected
+        resource: ResourceType,
+        /// Amount collected
+        amount: u32,
+    },
+    /// A player captured a zone and was paid its rewards
+    ZoneCaptured {
+        /// Captured zone
+        zone_id: String,
+        /// New owner
+        player_id: String,
+        /// Resources paid to the new owner
+        rewards: Vec<(ResourceType, u32)>,
+    },
+}
+
+/// One action of the log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Position in the log, starting at 0
+    pub entry_id: u64,
+    /// Simulation tick the action happened on
+    pub tick: u64,
+    /// What happened
+    pub action: GameAction,
+    /// Whether the action was carried out (an action that failed stays unapplied)
+    pub applied: bool,
+}
+
+/// World state at a point of an [`ActionLog`], to replay the following entries on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// First entry not reflected in `world`
+    pub next_entry_id: u64,
+    /// World state before that entry
+    pub world: World,
+}
+
+impl Checkpoint {
+    /// Write the checkpoint to a JSON file, replacing it atomically
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string(self)
+            .map_err(|e| format!("Failed to serialize checkpoint: {}", e))?;
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, json)
+            .and_then(|()| fs::rename(&temp_path, path))
+            .map_err(|e| format!("Failed to write checkpoint: {}", e))
+    }
+
+    /// Read a checkpoint written by [`Checkpoint::save`]
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read checkpoint: {}", e))?;
+        serde_json::from_str(&json)
+            .map_err(|e| format!("Failed to deserialize checkpoint: {}", e))
+    }
+}
+
+/// Append-only log of the actions of a world
+///
+/// A clone keeps the entries but not the file: only the original writes to it.
+#[derive(Debug, Default)]
+pub struct ActionLog {
+    entries: Vec<LogEntry>,
+    /// Where applied entries are appended (none for an in-memory log)
+    file: Option<(PathBuf, BufWriter<File>)>,
+}
+
+impl Clone for ActionLog {
+    fn clone(&self) -> Self {
+        Self { entries: self.entries.clone(), file: None }
+    }
+}
+
+impl ActionLog {
+    /// Create an empty in-memory log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a log file, creating it if needed, and load the entries already in it
+    ///
+    /// A last line cut short (a write interrupted by a crash) is dropped from the file.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut entries = Vec::new();
+        if path.exists() {
+            let contents = fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read action log: {}", e))?;
+            let complete = contents.ends_with('\n');
+            let lines: Vec<&str> = contents.lines().filter(|line| !line.trim().is_empty()).collect();
+            for (index, line) in lines.iter().enumerate() {
+                match serde_json::from_str::<LogEntry>(line) {
+                    Ok(entry) => entries.push(entry),
+                    Err(e) if index + 1 == lines.len() && !complete => {
+                        log::warn!("Dropping the incomplete last entry of action log {:?}: {}", path, e);
+                        let keep = contents.rfind('\n').map_or(0, |end| end + 1);
+                        OpenOptions::new().write(true).open(path)
+                            .and_then(|file| file.set_len(keep as u64))
+                            .map_err(|e| format!("Failed to truncate action log: {}", e))?;
+                    }
+                    Err(e) => return Err(format!("Invalid action log entry on line {}: {}", index + 1, e)),
+                }
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)
+            .map_err(|e| format!("Failed to open action log: {}", e))?;
+        Ok(Self { entries, file: Some((path.to_path_buf(), BufWriter::new(file))) })
+    }
+
+    /// All entries, in order
+    pub fn entries(&self) -> &[LogEntry] {
+        &self.entries
+    }
+
+    /// ID the next appended entry gets
+    pub fn next_entry_id(&self) -> u64 {
+        self.entries.last().map_or(0, |entry| entry.entry_id + 1)
+    }
+
+    /// File the log is written to (`None` for an in-memory log)
+    pub fn path(&self) -> Option<&Path> {
+        self.file.as_ref().map(|(path, _)| path.as_path())
+    }
+
+    /// Record an action about to be carried out, returning its entry ID
+    pub fn append(&mut self, tick: u64, action: GameAction) -> u64 {
+        let entry_id = self.next_entry_id();
+        self.entries.push(LogEntry { entry_id, tick, action, applied: false });
+        entry_id
+    }
+
+    /// Mark an entry applied and append it to the log file
+    pub fn commit(&mut self, entry_id: u64) -> Result<(), String> {
+        let entry = self.entries.iter_mut().rev()
+            .find(|entry| entry.entry_id == entry_id)
+            .ok_or_else(|| format!("Action log entry {} not found", entry_id))?;
+        if entry.applied {
+            return Ok(());
+        }
+        entry.applied = true;
+
+        if let Some((_, writer)) = &mut self.file {
+            let line = serde_json::to_string(entry)
+                .map_err(|e| format!("Failed to serialize action log entry: {}", e))?;
+            writeln!(writer, "{}", line).map_err(|e| format!("Failed to write action log: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Write buffered entries to the log file
+    pub fn flush(&mut self) -> Result<(), String> {
+        match &mut self.file {
+            Some((_, writer)) => writer.flush().map_err(|e| format!("Failed to write action log: {}", e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Snapshot of a world whose actions up to now are all in this log
+    pub fn checkpoint(&self, world: &World) -> Checkpoint {
+        let mut world = world.clone();
+        world.take_action_log();
+        Checkpoint { next_entry_id: self.next_entry_id(), world }
+    }
+
+    /// Rebuild a world by replaying the applied entries that follow a checkpoint
+    ///
+    /// The rebuilt world has no action log of its own. Its tick is that of the last
+    /// replayed entry, if later than the checkpoint's.
+    pub fn replay_from(&self, checkpoint: &Checkpoint) -> Result<World, String> {
+        let mut world = checkpoint.world.clone();
+        world.take_action_log();
+        for entry in self.entries.iter().filter(|entry| entry.applied && entry.entry_id >= checkpoint.next_entry_id) {
+            world.apply_action(entry.tick, &entry.action)
+                .map_err(|e| format!("Failed to replay action log entry {}: {}", entry.entry_id, e))?;
+        }
+        Ok(world)
+    }
+}
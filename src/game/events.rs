@@ -0,0 +1,204 @@
+//! Game events module
+//!
+//! What happened in the simulation (units created, moved, and destroyed, resources
+//! collected, buildings completed, techs researched, players defeated), kept per zone in bounded ring
+//! buffers so players can catch up on recent events. Each event is tagged with its tick
+//! and the players involved; other players only see it if one of their entities in the
+//! zone is within visibility range of where it happened.
+
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::zone::ResourceType;
+
+/// Events kept per zone (the oldest are evicted first)
+pub const MAX_EVENTS_PER_ZONE: usize = 10_000;
+
+/// What happened
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum GameEventKind {
+    /// A unit appeared
+    UnitCreated {
+        /// Entity ID within the zone
+        unit_id: u32,
+        /// Unit kind (`worker`, `soldier`, ...)
+        kind: String,
+        /// X coordinate
+        x: usize,
+        /// Y coordinate
+        y: usize,
+    },
+    /// A unit stepped onto a new tile
+    UnitMoved {
+        /// Entity ID within the zone
+        unit_id: u32,
+        /// X coordinate of the new tile
+        x: usize,
+        /// Y coordinate of the new tile
+        y: usize,
+    },
+    /// A unit or building ran out of hit points
+    UnitDestroyed {
+        /// Entity ID within the zone
+        unit_id: u32,
+        /// Entity kind
+        kind: String,
+        /// X coordinate
+        x: usize,
+        /// Y coordinate
+        y: usize,
+    },
+    /// A unit harvested a resource deposit
+    ResourceCollected {
+        /// Entity ID of the harvester
+        unit_id: u32,
+        /// Resource collected
+        resource: ResourceType,
+        /// Amount collected
+        amount: u32,
+        /// X coordinate of the deposit
+        x: usize,
+        /// Y coordinate of the deposit
+        y: usize,
+    },
+    /// A building was completed
+    BuildingCompleted {
+        /// Entity ID within the zone
+        unit_id: u32,
+        /// Building kind (`base`, `turret`, ...)
+        kind: String,
+        /// X coordinate
+        x: usize,
+        /// Y coordinate
+        y: usize,
+    },
+    /// A tech was researched at a base
+    ResearchCompleted {
+        /// Researched tech (see [`crate::game::tech::TECH_TREE`])
+        tech: String,
+        /// X coordinate of the base
+        x: usize,
+        /// Y coordinate of the base
+        y: usize,
+    },
+    /// A research was cancelled because its base was destroyed
+    ResearchCancelled {
+        /// Tech that was being researched
+        tech: String,
+        /// X coordinate of the base
+        x: usize,
+        /// Y coordinate of the base
+        y: usize,
+    },
+    /// A player lost every building and unit
+    PlayerDefeated,
+}
+
+impl GameEventKind {
+    /// Where the event happened (`None` for events without a location)
+    pub fn position(&self) -> Option<(usize, usize)> {
+        match *self {
+            GameEventKind::UnitCreated { x, y, .. }
+            | GameEventKind::UnitMoved { x, y, .. }
+            | GameEventKind::UnitDestroyed { x, y, .. }
+            | GameEventKind::ResourceCollected { x, y, .. }
+            | GameEventKind::BuildingCompleted { x, y, .. }
+            | GameEventKind::ResearchCompleted { x, y, .. }
+            | GameEventKind::ResearchCancelled { x, y, .. } => Some((x, y)),
+            GameEventKind::PlayerDefeated => None,
+        }
+    }
+}
+
+/// An event recorded in a zone
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameEvent {
+    /// Sequence number, increasing across the world
+    pub id: u64,
+    /// Simulation tick the event happened at
+    pub tick: u64,
+    /// Zone the event happened in
+    pub zone_id: String,
+    /// Players involved (owners of the units, defeated player, ...)
+    pub players: Vec<String>,
+    /// What happened
+    #[serde(flatten)]
+    pub kind: GameEventKind,
+}
+
+/// Recent events of every zone
+#[derive(Debug, Clone, Default)]
+pub struct EventLog {
+    zones: HashMap<String, VecDeque<GameEvent>>,
+    next_id: u64,
+}
+
+impl EventLog {
+    /// Create an empty log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an event, evicting the zone's oldest event if its buffer is full
+    pub fn record(&mut self, tick: u64, zone_id: &str, players: Vec<String>, kind: GameEventKind) {
+        let events = self.zones.entry(zone_id.to_string()).or_default();
+        if events.len() >= MAX_EVENTS_PER_ZONE {
+            events.pop_front();
+        }
+        events.push_back(GameEvent {
+            id: self.next_id,
+            tick,
+            zone_id: zone_id.to_string(),
+            players,
+            kind,
+        });
+        self.next_id += 1;
+    }
+
+    /// Events of a zone, oldest first
+    pub fn zone_events(&self, zone_id: &str) -> impl Iterator<Item = &GameEvent> {
+        self.zones.get(zone_id).into_iter().flatten()
+    }
+
+    /// Events after `since_tick` in every zone that pass `filter`, oldest first, at most `limit`
+    pub fn query(&self, since_tick: u64, limit: usize, mut filter: impl FnMut(&GameEvent) -> bool) -> Vec<GameEvent> {
+        let mut events: Vec<&GameEvent> = self.zones.values()
+            .flat_map(|events| {
+                // Events of a zone are in tick order
+                let start = events.partition_point(|event| event.tick <= since_tick);
+                events.range(start..)
+            })
+            .filter(|event| filter(event))
+            .collect();
+        events.sort_by_key(|event| event.id);
+        events.into_iter().take(limit).cloned().collect()
+    }
+
+    /// Forget the events of a zone
+    pub fn remove_zone(&mut self, zone_id: &str) {
+        self.zones.remove(zone_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oldest_events_are_evicted_per_zone() {
+        let mut log = EventLog::new();
+        for tick in 0..MAX_EVENTS_PER_ZONE as u64 + 5 {
+            log.record(tick, "busy", vec!["alice".to_string()], GameEventKind::UnitMoved { unit_id: 1, x: 0, y: 0 });
+        }
+        log.record(0, "quiet", vec!["bob".to_string()], GameEventKind::PlayerDefeated);
+
+        assert_eq!(log.zone_events("busy").count(), MAX_EVENTS_PER_ZONE);
+        assert_eq!(log.zone_events("busy").next().unwrap().tick, 5);
+        assert_eq!(log.zone_events("quiet").count(), 1);
+
+        let recent = log.query(MAX_EVENTS_PER_ZONE as u64, 10, |_| true);
+        assert_eq!(recent.iter().map(|event| event.tick).collect::<Vec<_>>(), vec![10_001, 10_002, 10_003, 10_004]);
+    }
+}
@@ -0,0 +1,264 @@
+//! Game loop module
+//!
+//! Drives the shared world. The simulation ticks `ticks_per_second` times per second
+//! ([`TICKS_PER_SECOND`] by default: movement, weather, defeats), but players' scripts
+//! only run on script ticks, every `script_tick_interval` simulation ticks. The commands a script issues are buffered in
+//! the world and carried out over the simulation ticks that follow, so a multi-tile move
+//! keeps going until the next script tick. The world is recorded in its replay history
+//! at every script tick, before the scripts run. Admins can pause the loop and step it
+//! one tick at a time through a [`SimControl`]. The tick rate is read from the shared
+//! configuration before every tick, so a reloaded rate applies right away. The loop
+//! records a heartbeat in its [`SimControl`] on every turn, paused or not, so readiness
+//! checks can tell a wedged loop from an idle one. After every tick the world's events
+//! are drained and sent to the statistics read model (see [`crate::game::stats`]).
+//! Changes to the world are made under its write lock; zone reads do not wait for them
+//! (see [`crate::game::zone_map`]).
+//!
+//! [`TICKS_PER_SECOND`]: crate::config::TICKS_PER_SECOND
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicI64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::{field, Instrument};
+
+use crate::config::SharedConfig;
+use crate::game::stats::StatsFeed;
+use crate::game::world::World;
+use crate::scripting::handle::ScriptEngineHandle;
+
+/// Run one simulation tick, executing the scripts of every player with code first if it is a script tick
+///
+/// Returns the number of scripts executed.
+pub async fn run_simulation_tick(world: &RwLock<World>, script_engine: &ScriptEngineHandle) -> usize {
+    let start = Instant::now();
+    let (tick, script_tick) = {
+        let world = world.read().await;
+        (world.get_tick(), world.is_script_tick().then(|| world.get_script_tick()))
+    };
+    let span = tracing::info_span!("tick", tick, script_tick, scripts = field::Empty, duration_ms = field::Empty);
+
+    let executed = simulate_tick(world, script_engine, script_tick).instrument(span.clone()).await;
+
+    let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+    span.record("scripts", executed);
+    span.record("duration_ms", duration_ms);
+    span.in_scope(|| tracing::debug!(scripts = executed, duration_ms, "tick completed"));
+    executed
+}
+
+/// Body of [`run_simulation_tick`], run inside its `tick` span
+async fn simulate_tick(world: &RwLock<World>, script_engine: &ScriptEngineHandle, script_tick: Option<u64>) -> usize {
+    let mut executed = 0;
+    if let Some(script_tick) = script_tick {
+        if let Err(err) = world.write().await.record_snapshot() {
+            log::warn!("Could not record the world at script tick {}: {}", script_tick, err);
+        }
+
+        let players = script_engine.read().await.list_players();
+        let snapshots: BTreeMap<String, serde_json::Value> = {
+            let world = world.read().await;
+            players.into_iter()
+                .map(|player| {
+                    let snapshot = world.player_snapshot(&player);
+                    (player, snapshot)
+                })
+                .collect()
+        };
+
+        let results = script_engine.run_tick(script_tick, &snapshots).await;
+        executed = results.len();
+
+        let mut world = world.write().await;
+        for (player, result) in results {
+            if let Some(error) = result.error {
+                log::debug!("Script of {} failed at script tick {}: {}", player, script_tick, error);
+                continue;
+            }
+            for error in world.apply_commands(&player, &result.commands) {
+                log::debug!("Command of {} rejected at script tick {}: {}", player, script_tick, error);
+            }
+        }
+    }
+
+    world.write().await.advance_tick();
+    executed
+}
+
+/// Whether the game loop ticks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u8)]
+pub enum RunState {
+    /// Ticking at the configured rate
+    Running = 0,
+    /// Frozen: the world doesn't change and no script runs
+    Paused = 1,
+    /// Running a requested number of ticks, then pausing
+    StepOnce = 2,
+}
+
+impl RunState {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => RunState::Paused,
+            2 => RunState::StepOnce,
+            _ => RunState::Running,
+        }
+    }
+}
+
+/// Shared pause / resume / step control of the game loop
+///
+/// The run state is an atomic so it can be read without locking. Changes and the loop's
+/// check before each tick go through the lock on the remaining steps, so a step runs
+/// exactly the requested number of ticks even when requests race.
+#[derive(Debug, Clone, Default)]
+pub struct SimControl {
+    state: Arc<AtomicU8>,
+    steps: Arc<Mutex<u64>>,
+    /// Unix time of the loop's latest heartbeat, in milliseconds (0 before the first)
+    heartbeat: Arc<AtomicI64>,
+}
+
+impl SimControl {
+    /// Control of a running loop
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current run state
+    pub fn state(&self) -> RunState {
+        RunState::from_u8(self.state.load(Ordering::Acquire))
+    }
+
+    /// Stop ticking (cancels any remaining steps)
+    pub fn pause(&self) {
+        let mut steps = self.steps.lock().unwrap_or_else(|e| e.into_inner());
+        *steps = 0;
+        self.state.store(RunState::Paused as u8, Ordering::Release);
+    }
+
+    /// Tick at the configured rate again (cancels any remaining steps)
+    pub fn resume(&self) {
+        let mut steps = self.steps.lock().unwrap_or_else(|e| e.into_inner());
+        *steps = 0;
+        self.state.store(RunState::Running as u8, Ordering::Release);
+    }
+
+    /// Run `ticks` more ticks, then pause; the loop must be paused or already stepping
+    ///
+    /// Returns the number of ticks left to run.
+    pub fn step(&self, ticks: u64) -> Result<u64, String> {
+        if ticks == 0 {
+            return Err("ticks must be at least 1".to_string());
+        }
+        let mut steps = self.steps.lock().unwrap_or_else(|e| e.into_inner());
+        if self.state() == RunState::Running {
+            return Err("Simulation is running, pause it first".to_string());
+        }
+        *steps = steps.saturating_add(ticks);
+        self.state.store(RunState::StepOnce as u8, Ordering::Release);
+        Ok(*steps)
+    }
+
+    /// Whether the loop should run its next tick (takes one step when stepping)
+    pub fn take_tick(&self) -> bool {
+        let mut steps = self.steps.lock().unwrap_or_else(|e| e.into_inner());
+        match self.state() {
+            RunState::Running => true,
+            RunState::Paused => false,
+            RunState::StepOnce => {
+                if *steps == 0 {
+                    return false;
+                }
+                *steps -= 1;
+                if *steps == 0 {
+                    self.state.store(RunState::Paused as u8, Ordering::Release);
+                }
+                true
+            }
+        }
+    }
+
+    /// Record that the loop is alive at a Unix time in milliseconds
+    pub fn heartbeat(&self, now_ms: i64) {
+        self.heartbeat.store(now_ms, Ordering::Release);
+    }
+
+    /// Unix time of the loop's latest heartbeat in milliseconds, if it has run
+    pub fn last_heartbeat(&self) -> Option<i64> {
+        Some(self.heartbeat.load(Ordering::Acquire)).filter(|&at| at > 0)
+    }
+}
+
+/// Tick the world forever, one tick every [`ServerConfig::tick_interval`] of `config`
+///
+/// Ticks that fall behind are skipped rather than run in a burst. Ticks are skipped while
+/// `control` is paused.
+///
+/// [`ServerConfig::tick_interval`]: crate::config::ServerConfig::tick_interval
+pub async fn run_game_loop(world: Arc<RwLock<World>>, script_engine: ScriptEngineHandle, config: SharedConfig, control: SimControl, stats: StatsFeed) {
+    let mut tick_interval = config.read().unwrap().tick_interval();
+    let mut interval = tokio::time::interval(tick_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+        control.heartbeat(chrono::Utc::now().timestamp_millis());
+        let configured = config.read().unwrap().tick_interval();
+        if configured != tick_interval {
+            log::info!("Tick interval changed from {:?} to {:?}", tick_interval, configured);
+            tick_interval = configured;
+            interval = tokio::time::interval_at(tokio::time::Instant::now() + tick_interval, tick_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        }
+        if !control.take_tick() {
+            continue;
+        }
+        run_simulation_tick(&world, &script_engine).await;
+        let batch = world.write().await.drain_stats();
+        for event in &batch.events {
+            log::debug!("World event: {:?}", event);
+        }
+        // Nobody reads the statistics once the updater is gone
+        let _ = stats.send(batch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_racing_steps_run_exactly_the_requested_ticks() {
+        let control = SimControl::new();
+        control.pause();
+
+        let requests: Vec<_> = (0..4)
+            .map(|_| {
+                let control = control.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..25 {
+                        control.step(2).unwrap();
+                    }
+                })
+            })
+            .collect();
+        let mut ticks = 0;
+        while requests.iter().any(|request| !request.is_finished()) || control.state() == RunState::StepOnce {
+            if control.take_tick() {
+                ticks += 1;
+            }
+        }
+        for request in requests {
+            request.join().unwrap();
+        }
+
+        assert_eq!(ticks, 200);
+        assert_eq!(control.state(), RunState::Paused);
+        assert!(!control.take_tick());
+    }
+}
@@ -0,0 +1,225 @@
+//! Game history module
+//!
+//! Lets players look at the world as it was at a past simulation tick, e.g. to find out
+//! why their bot misbehaved 500 ticks ago. The world keeps keyframes, serialized copies of
+//! itself taken every `keyframe_interval` ticks (see [`WorldSnapshot`]), and the actions
+//! applied since: moves, harvests and zone captures, as recorded in an
+//! [`ActionLog`](crate::game::event_log::ActionLog). The state at a tick is rebuilt from
+//! the nearest earlier keyframe by replaying the actions up to that tick, so the keyframe
+//! interval bounds the cost of a query. Changes that are not actions (combat damage,
+//! weather, construction, research, ...) show as of that keyframe, and actions on what
+//! such changes created are skipped. Like a [`WorldSnapshot`], a rebuilt world has no
+//! in-flight moves or event log.
+//!
+//! History is kept for at least `retention_ticks` ticks and pruned beyond that. The last
+//! few rebuilt ticks are cached.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::game::event_log::{GameAction, LogEntry};
+use crate::game::replay::WorldSnapshot;
+use crate::game::world::World;
+
+/// Default number of past ticks kept in the history
+pub const DEFAULT_HISTORY_RETENTION_TICKS: u64 = 10_000;
+
+/// Default number of ticks between two keyframes
+pub const DEFAULT_HISTORY_KEYFRAME_INTERVAL: u64 = 100;
+
+/// Rebuilt ticks kept for repeated queries
+const REBUILT_TICKS_CACHED: usize = 4;
+
+/// Why a tick cannot be rebuilt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HistoryError {
+    /// The tick is older than the history (`earliest` is the oldest tick kept, if any)
+    Expired {
+        /// Oldest tick that can be rebuilt
+        earliest: Option<u64>,
+    },
+    /// The tick has not happened yet
+    Future {
+        /// Current tick
+        latest: u64,
+    },
+    /// The recorded history could not be replayed
+    Failed(String),
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryError::Expired { earliest: Some(earliest) } => write!(f, "History before tick {} has been pruned", earliest),
+            HistoryError::Expired { earliest: None } => write!(f, "No history has been recorded yet"),
+            HistoryError::Future { latest } => write!(f, "The current tick is {}", latest),
+            HistoryError::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for HistoryError {}
+
+/// The world at a tick, and the first action not reflected in it
+#[derive(Debug)]
+struct Keyframe {
+    tick: u64,
+    next_action: u64,
+    snapshot: WorldSnapshot,
+}
+
+/// Keyframes and actions of the recent ticks of a world (see the [module documentation](self))
+///
+/// A cloned history keeps its limits but starts empty.
+#[derive(Debug, Default)]
+pub struct GameHistory {
+    retention_ticks: u64,
+    keyframe_interval: u64,
+    keyframes: VecDeque<Keyframe>,
+    /// Applied actions, oldest first
+    actions: VecDeque<LogEntry>,
+    next_action: u64,
+    /// Action being carried out, recorded once it is applied
+    staged: Option<(u64, GameAction)>,
+    rebuilt: Mutex<VecDeque<(u64, Arc<World>)>>,
+}
+
+impl Clone for GameHistory {
+    fn clone(&self) -> Self {
+        Self::new(self.retention_ticks, self.keyframe_interval)
+    }
+}
+
+impl GameHistory {
+    /// Create an empty history (a `retention_ticks` of 0 records nothing)
+    pub fn new(retention_ticks: u64, keyframe_interval: u64) -> Self {
+        Self { retention_ticks, keyframe_interval: keyframe_interval.max(1), ..Self::default() }
+    }
+
+    /// Change the limits; they apply from the next keyframe (a `retention_ticks` of 0 drops the history)
+    pub fn set_limits(&mut self, retention_ticks: u64, keyframe_interval: u64) {
+        if retention_ticks == 0 {
+            *self = Self::new(0, keyframe_interval);
+        }
+        self.retention_ticks = retention_ticks;
+        self.keyframe_interval = keyframe_interval.max(1);
+    }
+
+    /// Whether the history records anything
+    pub fn is_enabled(&self) -> bool {
+        self.retention_ticks > 0
+    }
+
+    /// Remember an action about to be carried out at `tick`
+    pub fn stage(&mut self, tick: u64, action: &GameAction) {
+        if self.is_enabled() {
+            self.staged = Some((tick, action.clone()));
+        }
+    }
+
+    /// Record the staged action as applied
+    pub fn commit(&mut self) {
+        if let Some((tick, action)) = self.staged.take() {
+            self.actions.push_back(LogEntry { entry_id: self.next_action, tick, action, applied: true });
+            self.next_action += 1;
+        }
+    }
+
+    /// Whether a keyframe should be taken at the end of `tick`
+    pub fn keyframe_due(&self, tick: u64) -> bool {
+        self.is_enabled() && (self.keyframes.is_empty() || tick.is_multiple_of(self.keyframe_interval))
+    }
+
+    /// Add a keyframe of the world at the end of `tick`, pruning what falls out of the retention window
+    pub fn record_keyframe(&mut self, tick: u64, snapshot: WorldSnapshot) {
+        self.keyframes.push_back(Keyframe { tick, next_action: self.next_action, snapshot });
+
+        // Keep the keyframe the oldest retained tick is rebuilt from
+        let cutoff = tick.saturating_sub(self.retention_ticks);
+        while self.keyframes.get(1).is_some_and(|next| next.tick <= cutoff) {
+            self.keyframes.pop_front();
+        }
+        let first_action = self.keyframes.front().map_or(self.next_action, |keyframe| keyframe.next_action);
+        while self.actions.front().is_some_and(|entry| entry.entry_id < first_action) {
+            self.actions.pop_front();
+        }
+        let earliest = self.earliest_tick().unwrap_or(tick);
+        self.rebuilt.lock().retain(|(rebuilt, _)| *rebuilt >= earliest);
+    }
+
+    /// Oldest tick that can be rebuilt, if any
+    pub fn earliest_tick(&self) -> Option<u64> {
+        self.keyframes.front().map(|keyframe| keyframe.tick)
+    }
+
+    /// Rebuild the world as it was at the end of `tick`, `now` being the current tick
+    ///
+    /// The rebuilt world is detached: it has no store, action log or history. Only a
+    /// keyframe that cannot be restored fails the rebuild.
+    pub fn rebuild(&self, tick: u64, now: u64) -> Result<Arc<World>, HistoryError> {
+        if tick > now {
+            return Err(HistoryError::Future { latest: now });
+        }
+        let keyframe = self.keyframes.iter().rev()
+            .find(|keyframe| keyframe.tick <= tick)
+            .ok_or(HistoryError::Expired { earliest: self.earliest_tick() })?;
+        if let Some((_, world)) = self.rebuilt.lock().iter().find(|(rebuilt, _)| *rebuilt == tick) {
+            return Ok(world.clone());
+        }
+
+        let mut world = keyframe.snapshot.restore().map_err(HistoryError::Failed)?;
+        let actions = self.actions.iter()
+            .skip_while(|entry| entry.entry_id < keyframe.next_action)
+            .take_while(|entry| entry.tick <= tick);
+        for entry in actions {
+            world.skip_to_tick(entry.tick);
+            // An entity created since the keyframe is not there to move
+            if let Err(e) = world.apply_action(entry.tick, &entry.action) {
+                log::debug!("Skipping action {} rebuilding tick {}: {}", entry.entry_id, tick, e);
+            }
+        }
+        world.skip_to_tick(tick);
+
+        let world = Arc::new(world);
+        let mut rebuilt = self.rebuilt.lock();
+        if rebuilt.len() >= REBUILT_TICKS_CACHED {
+            rebuilt.pop_front();
+        }
+        rebuilt.push_back((tick, world.clone()));
+        Ok(world)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_is_pruned_to_the_retention_window() {
+        let mut world = World::new();
+        let mut history = GameHistory::new(250, 100);
+        for tick in 0..=1000 {
+            world.skip_to_tick(tick);
+            if history.keyframe_due(tick) {
+                history.record_keyframe(tick, WorldSnapshot::capture(&world).unwrap());
+            }
+        }
+
+        // Tick 750 is rebuilt from the keyframe of tick 700
+        assert_eq!(history.earliest_tick(), Some(700));
+        assert_eq!(history.rebuild(699, 1000).unwrap_err(), HistoryError::Expired { earliest: Some(700) });
+        assert_eq!(history.rebuild(1001, 1000).unwrap_err(), HistoryError::Future { latest: 1000 });
+        let rebuilt = history.rebuild(750, 1000).unwrap();
+        assert_eq!(rebuilt.get_tick(), 750);
+        assert!(Arc::ptr_eq(&rebuilt, &history.rebuild(750, 1000).unwrap()));
+
+        let mut disabled = GameHistory::new(0, 100);
+        assert!(!disabled.keyframe_due(0));
+        disabled.stage(0, &GameAction::ZoneCaptured { zone_id: "z".to_string(), player_id: "p".to_string(), rewards: Vec::new() });
+        disabled.commit();
+        assert!(disabled.actions.is_empty());
+    }
+}
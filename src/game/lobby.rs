@@ -0,0 +1,327 @@
+//! Lobby module
+//!
+//! Lets players gather in a lobby before a multiplayer match. When the owner starts the
+//! lobby, an isolated world is created for its players and handed to a campaign run.
+//! Once the run stops the lobby is finished, and it is removed
+//! [`FINISHED_LOBBY_RETENTION_SECS`] later.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::game::world::World;
+
+/// Minimum number of players needed to start a match
+pub const MIN_LOBBY_PLAYERS: usize = 2;
+
+/// Maximum number of players a lobby may allow
+pub const MAX_LOBBY_PLAYERS: u8 = 8;
+
+/// Maximum length of a lobby name
+const MAX_LOBBY_NAME_LENGTH: usize = 64;
+
+/// How long a finished lobby stays listed, in seconds
+pub const FINISHED_LOBBY_RETENTION_SECS: i64 = 3600;
+
+/// Lobby lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LobbyStatus {
+    /// Open for players to join
+    Waiting,
+    /// Match is being set up
+    Starting,
+    /// Match is running
+    InProgress,
+    /// The match's run stopped
+    Finished,
+}
+
+/// Match settings chosen by the lobby owner
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchConfig {
+    /// Whether spectators may watch the match (default: true)
+    #[serde(default = "default_allow_spectators")]
+    pub allow_spectators: bool,
+}
+
+fn default_allow_spectators() -> bool {
+    true
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        Self {
+            allow_spectators: true,
+        }
+    }
+}
+
+/// A group of players waiting for (or playing) a match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lobby {
+    /// Unique lobby identifier
+    pub id: Uuid,
+    /// Display name
+    pub name: String,
+    /// User ID of the owner (the only player allowed to start the match)
+    pub owner_id: i64,
+    /// Maximum number of players
+    pub max_players: u8,
+    /// User IDs of the players, in join order
+    pub players: Vec<i64>,
+    /// Usernames of the players (user ID -> username)
+    pub player_names: HashMap<i64, String>,
+    /// Current status
+    pub status: LobbyStatus,
+    /// Match settings
+    pub config: MatchConfig,
+    /// Campaign run of the match (once started)
+    pub run_id: Option<String>,
+    /// When the match's run stopped (Unix epoch)
+    #[serde(default)]
+    pub finished_at: Option<i64>,
+}
+
+impl Lobby {
+    /// Whether the lobby has no free seat
+    pub fn is_full(&self) -> bool {
+        self.players.len() >= usize::from(self.max_players)
+    }
+}
+
+/// Manages lobbies
+pub struct LobbyManager {
+    lobbies: HashMap<Uuid, Lobby>,
+}
+
+impl LobbyManager {
+    /// Create an empty lobby manager
+    pub fn new() -> Self {
+        Self {
+            lobbies: HashMap::new(),
+        }
+    }
+
+    /// Create a lobby owned (and joined) by the given user
+    ///
+    /// Lobbies finished more than [`FINISHED_LOBBY_RETENTION_SECS`] ago are removed first.
+    pub fn create(&mut self, name: &str, owner_id: i64, owner_name: &str, max_players: u8, config: MatchConfig) -> Result<Lobby, String> {
+        self.remove_finished(chrono::Utc::now().timestamp());
+
+        let name = name.trim();
+        if name.is_empty() || name.len() > MAX_LOBBY_NAME_LENGTH {
+            return Err(format!("Lobby name must be between 1 and {} characters", MAX_LOBBY_NAME_LENGTH));
+        }
+
+        if !(MIN_LOBBY_PLAYERS as u8..=MAX_LOBBY_PLAYERS).contains(&max_players) {
+            return Err(format!("Max players must be between {} and {}", MIN_LOBBY_PLAYERS, MAX_LOBBY_PLAYERS));
+        }
+
+        let lobby = Lobby {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            owner_id,
+            max_players,
+            players: vec![owner_id],
+            player_names: HashMap::from([(owner_id, owner_name.to_string())]),
+            status: LobbyStatus::Waiting,
+            config,
+            run_id: None,
+            finished_at: None,
+        };
+        self.lobbies.insert(lobby.id, lobby.clone());
+        Ok(lobby)
+    }
+
+    /// Get a lobby by ID
+    pub fn get(&self, lobby_id: &Uuid) -> Option<&Lobby> {
+        self.lobbies.get(lobby_id)
+    }
+
+    /// List all lobbies, by name
+    pub fn list(&self) -> Vec<Lobby> {
+        let mut lobbies: Vec<Lobby> = self.lobbies.values().cloned().collect();
+        lobbies.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+        lobbies
+    }
+
+    /// Join a waiting lobby
+    pub fn join(&mut self, lobby_id: &Uuid, user_id: i64, username: &str) -> Result<Lobby, String> {
+        let lobby = self.waiting_lobby_mut(lobby_id)?;
+
+        if lobby.players.contains(&user_id) {
+            return Err("Already in this lobby".to_string());
+        }
+        if lobby.is_full() {
+            return Err(format!("Lobby {} is full", lobby.name));
+        }
+
+        lobby.players.push(user_id);
+        lobby.player_names.insert(user_id, username.to_string());
+        Ok(lobby.clone())
+    }
+
+    /// Leave a waiting lobby
+    ///
+    /// Ownership passes to the next player; the lobby is removed when the last player
+    /// leaves (`None` is returned).
+    pub fn leave(&mut self, lobby_id: &Uuid, user_id: i64) -> Result<Option<Lobby>, String> {
+        let lobby = self.waiting_lobby_mut(lobby_id)?;
+
+        if !lobby.players.contains(&user_id) {
+            return Err("Not in this lobby".to_string());
+        }
+
+        lobby.players.retain(|id| *id != user_id);
+        lobby.player_names.remove(&user_id);
+
+        match lobby.players.first() {
+            Some(next_owner) => {
+                if lobby.owner_id == user_id {
+                    lobby.owner_id = *next_owner;
+                }
+                Ok(Some(lobby.clone()))
+            }
+            None => {
+                self.lobbies.remove(lobby_id);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Start the match (owner only, at least `MIN_LOBBY_PLAYERS` players)
+    ///
+    /// Moves the lobby to `Starting` and returns it with an isolated world where each
+    /// player has a base and a worker in their own zone, for the campaign run to play
+    /// on. Call `mark_in_progress` once the run is running.
+    pub fn start(&mut self, lobby_id: &Uuid, user_id: i64) -> Result<(Lobby, World), String> {
+        let lobby = self.waiting_lobby_mut(lobby_id)?;
+
+        if lobby.owner_id != user_id {
+            return Err("Only the lobby owner can start the match".to_string());
+        }
+        if lobby.players.len() < MIN_LOBBY_PLAYERS {
+            return Err(format!("At least {} players are required to start", MIN_LOBBY_PLAYERS));
+        }
+
+        let run_id = format!("lobby_{}", lobby.id.simple());
+        let mut world = World::new();
+        for player_id in &lobby.players {
+            world.spawn_player(&lobby.player_names[player_id])?;
+        }
+
+        lobby.status = LobbyStatus::Starting;
+        lobby.run_id = Some(run_id);
+        Ok((lobby.clone(), world))
+    }
+
+    /// Mark a starting lobby as in progress
+    pub fn mark_in_progress(&mut self, lobby_id: &Uuid) -> Option<Lobby> {
+        let lobby = self.lobbies.get_mut(lobby_id)?;
+        lobby.status = LobbyStatus::InProgress;
+        Some(lobby.clone())
+    }
+
+    /// Return a starting lobby to waiting (when its run could not be started)
+    pub fn cancel_start(&mut self, lobby_id: &Uuid) {
+        if let Some(lobby) = self.lobbies.get_mut(lobby_id) {
+            lobby.run_id = None;
+            lobby.status = LobbyStatus::Waiting;
+        }
+    }
+
+    /// Mark the lobby whose match is played in a run as finished, once the run stopped
+    pub fn finish_run(&mut self, run_id: &str) -> Option<Lobby> {
+        let lobby = self.lobbies.values_mut()
+            .find(|lobby| lobby.run_id.as_deref() == Some(run_id) && lobby.status != LobbyStatus::Finished)?;
+        lobby.status = LobbyStatus::Finished;
+        lobby.finished_at = Some(chrono::Utc::now().timestamp());
+        Some(lobby.clone())
+    }
+
+    /// Remove the lobbies finished more than [`FINISHED_LOBBY_RETENTION_SECS`] before `now`
+    pub fn remove_finished(&mut self, now: i64) {
+        self.lobbies.retain(|_, lobby| {
+            lobby.finished_at.is_none_or(|finished_at| now - finished_at <= FINISHED_LOBBY_RETENTION_SECS)
+        });
+    }
+
+    fn waiting_lobby_mut(&mut self, lobby_id: &Uuid) -> Result<&mut Lobby, String> {
+        let lobby = self.lobbies.get_mut(lobby_id)
+            .ok_or_else(|| format!("Lobby {} not found", lobby_id))?;
+
+        if lobby.status != LobbyStatus::Waiting {
+            return Err(format!("Lobby {} has already started", lobby.name));
+        }
+        Ok(lobby)
+    }
+}
+
+impl Default for LobbyManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cannot_start_alone() {
+        let mut manager = LobbyManager::new();
+        let lobby = manager.create("solo", 1, "alice", 4, MatchConfig::default()).unwrap();
+
+        let err = manager.start(&lobby.id, 1).unwrap_err();
+        assert!(err.contains("At least 2 players"));
+        assert_eq!(manager.get(&lobby.id).unwrap().status, LobbyStatus::Waiting);
+    }
+
+    #[test]
+    fn test_start_creates_isolated_world() {
+        let mut manager = LobbyManager::new();
+        let lobby = manager.create("duel", 1, "alice", 2, MatchConfig::default()).unwrap();
+        manager.join(&lobby.id, 2, "bob").unwrap();
+        assert!(manager.join(&lobby.id, 3, "carol").unwrap_err().contains("full"));
+
+        assert!(manager.start(&lobby.id, 2).unwrap_err().contains("owner"));
+        let (started, world) = manager.start(&lobby.id, 1).unwrap();
+        assert_eq!(started.status, LobbyStatus::Starting);
+
+        assert_eq!(world.get_zone_ids(), vec!["player_alice_zone".to_string(), "player_bob_zone".to_string()]);
+        assert!(!world.is_defeated("bob"));
+        assert!(manager.join(&lobby.id, 3, "carol").is_err());
+    }
+
+    #[test]
+    fn test_finished_lobbies_are_removed_after_retention() {
+        let mut manager = LobbyManager::new();
+        let lobby = manager.create("duel", 1, "alice", 2, MatchConfig::default()).unwrap();
+        manager.join(&lobby.id, 2, "bob").unwrap();
+        let (started, _) = manager.start(&lobby.id, 1).unwrap();
+        let run_id = started.run_id.unwrap();
+        manager.mark_in_progress(&lobby.id);
+
+        let finished = manager.finish_run(&run_id).unwrap();
+        assert_eq!(finished.status, LobbyStatus::Finished);
+        assert!(manager.finish_run(&run_id).is_none());
+
+        let finished_at = finished.finished_at.unwrap();
+        manager.remove_finished(finished_at + FINISHED_LOBBY_RETENTION_SECS);
+        assert!(manager.get(&lobby.id).is_some());
+        manager.remove_finished(finished_at + FINISHED_LOBBY_RETENTION_SECS + 1);
+        assert!(manager.get(&lobby.id).is_none());
+    }
+
+    #[test]
+    fn test_owner_leaving_transfers_ownership() {
+        let mut manager = LobbyManager::new();
+        let lobby = manager.create("handoff", 1, "alice", 3, MatchConfig::default()).unwrap();
+        manager.join(&lobby.id, 2, "bob").unwrap();
+
+        let lobby_after = manager.leave(&lobby.id, 1).unwrap().unwrap();
+        assert_eq!(lobby_after.owner_id, 2);
+        assert!(manager.leave(&lobby.id, 2).unwrap().is_none());
+        assert!(manager.get(&lobby.id).is_none());
+    }
+}
@@ -0,0 +1,194 @@
+//! Market module
+//!
+//! Order book for trading resources between players for credits. Orders are not
+//! escrowed when placed: each fill checks that the seller still holds the resource and
+//! the buyer the credits, and an order whose owner cannot cover its fill is cancelled.
+//! Buy orders are matched highest price first against sell orders lowest price first
+//! (oldest first at equal prices), at the sell order's price; orders can be partially
+//! filled by several counterparties.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::game::zone::ResourceType;
+
+/// Side of an order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderSide {
+    /// Buy the resource for credits
+    Buy,
+    /// Sell the resource for credits
+    Sell,
+}
+
+/// An open order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MarketOrder {
+    /// Unique order identifier
+    pub id: Uuid,
+    /// Player who placed the order
+    pub player_id: String,
+    /// Buy or sell
+    pub side: OrderSide,
+    /// Resource traded (never credits)
+    pub resource: ResourceType,
+    /// Amount ordered
+    pub amount: u32,
+    /// Amount not filled yet
+    pub remaining: u32,
+    /// Credits per unit
+    pub price: u32,
+    /// Tick the order was placed at
+    pub created_tick: u64,
+    /// Tick the order expires at
+    pub expires_tick: u64,
+}
+
+/// A fill between a buy and a sell order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trade {
+    /// Buy order filled
+    pub buy_order_id: Uuid,
+    /// Sell order filled
+    pub sell_order_id: Uuid,
+    /// Buying player
+    pub buyer: String,
+    /// Selling player
+    pub seller: String,
+    /// Resource traded
+    pub resource: ResourceType,
+    /// Amount traded
+    pub amount: u32,
+    /// Credits paid per unit
+    pub price: u32,
+}
+
+/// Balances trades are settled against
+pub trait Ledger {
+    /// Amount of a resource a player holds
+    fn balance(&self, player_id: &str, resource: ResourceType) -> u32;
+    /// Take an amount of a resource from a player (fails if they hold less)
+    fn withdraw(&mut self, player_id: &str, resource: ResourceType, amount: u32) -> Result<(), String>;
+    /// Give an amount of a resource to a player
+    fn deposit(&mut self, player_id: &str, resource: ResourceType, amount: u32);
+}
+
+/// Open orders of the world
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Market {
+    orders: Vec<MarketOrder>,
+}
+
+impl Market {
+    /// Create an empty market
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an order to the book
+    pub fn place(&mut self, order: MarketOrder) {
+        self.orders.push(order);
+    }
+
+    /// Open orders, oldest first
+    pub fn orders(&self) -> &[MarketOrder] {
+        &self.orders
+    }
+
+    /// Cancel an order; only its owner may
+    pub fn cancel(&mut self, player_id: &str, order_id: Uuid) -> Result<MarketOrder, String> {
+        let index = self.orders.iter().position(|order| order.id == order_id)
+            .ok_or_else(|| format!("Order {} not found", order_id))?;
+        if self.orders[index].player_id != player_id {
+            return Err("Only the owner of an order can cancel it".to_string());
+        }
+        Ok(self.orders.remove(index))
+    }
+
+    /// Remove the orders expiring at or before `tick`
+    pub fn expire(&mut self, tick: u64) -> Vec<MarketOrder> {
+        let (expired, open) = std::mem::take(&mut self.orders)
+            .into_iter()
+            .partition(|order| order.expires_tick <= tick);
+        self.orders = open;
+        expired
+    }
+
+    /// Match compatible orders, settling each fill against the ledger
+    ///
+    /// Returns the trades made and the orders cancelled because their owner could not
+    /// cover a fill. Filled orders leave the book.
+    pub fn match_orders(&mut self, ledger: &mut impl Ledger) -> (Vec<Trade>, Vec<MarketOrder>) {
+        let mut trades = Vec::new();
+        let mut cancelled = Vec::new();
+
+        let mut buys: Vec<usize> = self.indices(OrderSide::Buy);
+        buys.sort_by_key(|&i| (std::cmp::Reverse(self.orders[i].price), self.orders[i].created_tick));
+        let mut sells: Vec<usize> = self.indices(OrderSide::Sell);
+        sells.sort_by_key(|&i| (self.orders[i].price, self.orders[i].created_tick));
+        let mut dropped = vec![false; self.orders.len()];
+
+        for &b in &buys {
+            for &s in &sells {
+                let (buy, sell) = (&self.orders[b], &self.orders[s]);
+                if dropped[b] || buy.remaining == 0 {
+                    break;
+                }
+                if dropped[s] || sell.remaining == 0 || sell.resource != buy.resource || sell.player_id == buy.player_id {
+                    continue;
+                }
+                if sell.price > buy.price {
+                    break;
+                }
+
+                let amount = buy.remaining.min(sell.remaining);
+                let Some(cost) = amount.checked_mul(sell.price) else {
+                    continue;
+                };
+                if ledger.balance(&sell.player_id, sell.resource) < amount {
+                    dropped[s] = true;
+                    continue;
+                }
+                if ledger.balance(&buy.player_id, ResourceType::Credits) < cost {
+                    dropped[b] = true;
+                    break;
+                }
+
+                let trade = Trade {
+                    buy_order_id: buy.id,
+                    sell_order_id: sell.id,
+                    buyer: buy.player_id.clone(),
+                    seller: sell.player_id.clone(),
+                    resource: sell.resource,
+                    amount,
+                    price: sell.price,
+                };
+                // Balances were checked above, so both withdrawals succeed
+                ledger.withdraw(&trade.seller, trade.resource, amount).expect("seller balance checked");
+                ledger.withdraw(&trade.buyer, ResourceType::Credits, cost).expect("buyer balance checked");
+                ledger.deposit(&trade.buyer, trade.resource, amount);
+                ledger.deposit(&trade.seller, ResourceType::Credits, cost);
+
+                self.orders[b].remaining -= amount;
+                self.orders[s].remaining -= amount;
+                trades.push(trade);
+            }
+        }
+
+        let mut index = 0;
+        self.orders.retain(|order| {
+            let keep = order.remaining > 0 && !dropped[index];
+            if dropped[index] {
+                cancelled.push(order.clone());
+            }
+            index += 1;
+            keep
+        });
+        (trades, cancelled)
+    }
+
+    fn indices(&self, side: OrderSide) -> Vec<usize> {
+        (0..self.orders.len()).filter(|&i| self.orders[i].side == side).collect()
+    }
+}
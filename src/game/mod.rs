@@ -1,7 +1,26 @@
 //! Game module
 //! 
-//! Contains world management, campaign system, and zone generation.
+//! Contains world management, campaign system, NPC opponents, and zone generation.
 
 pub mod world;
+pub mod zone_map;
+pub mod clock;
+pub mod weather;
+pub mod pathfinding;
 pub mod campaign;
-pub mod zone;
\ No newline at end of file
+pub mod zone;
+pub mod lobby;
+pub mod tournament;
+pub mod store;
+pub mod game_loop;
+pub mod events;
+pub mod replay;
+pub mod history;
+pub mod market;
+pub mod npc;
+pub mod tech;
+pub mod rng;
+pub mod scenario;
+pub mod stats;
+pub mod event_log;
+pub mod snapshot_cache;
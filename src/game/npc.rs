@@ -0,0 +1,302 @@
+//! NPC module
+//!
+//! Built-in opponents for players who have nobody to fight. An NPC is a player driven by
+//! a Rust [`BotController`] instead of a script: it decides from the same snapshot a script
+//! gets (see [`World::player_snapshot`]), so it only sees what fog of war lets it see, and
+//! its commands go through [`World::apply_commands`], paying the same unit costs. Behaviors
+//! only depend on the snapshot, so NPCs play the same way in worlds built from the same seed.
+
+use serde::Deserialize;
+
+use crate::game::world::{unit_cost, World};
+use crate::game::zone::{Mobility, ResourceDeposit};
+use crate::scripting::commands::BotCommand;
+
+/// Names of the built-in behaviors (see [`controller`])
+pub const NPC_BEHAVIORS: &[&str] = &["harvester", "raider", "turtle"];
+
+/// Minerals in the deposit placed next to a new NPC's worker
+pub const NPC_DEPOSIT_AMOUNT: u32 = 1000;
+
+/// Most workers an NPC keeps harvesting
+const MAX_WORKERS: usize = 3;
+
+/// Most soldiers a turtle defender keeps
+const MAX_DEFENDERS: usize = 4;
+
+/// Distance from its base within which a turtle defender engages enemies
+const DEFENSE_RADIUS: usize = 4;
+
+/// Decides the commands of an NPC player
+pub trait BotController: Send + Sync {
+    /// Behavior name (one of [`NPC_BEHAVIORS`])
+    fn behavior(&self) -> &'static str;
+
+    /// Commands for one script tick, from the player's snapshot
+    fn decide(&mut self, snapshot: &serde_json::Value) -> Vec<BotCommand>;
+}
+
+/// Create the controller of a built-in behavior
+pub fn controller(behavior: &str) -> Result<Box<dyn BotController>, String> {
+    match behavior {
+        "harvester" => Ok(Box::new(IdleHarvester)),
+        "raider" => Ok(Box::new(Raider)),
+        "turtle" => Ok(Box::new(TurtleDefender)),
+        other => Err(format!("Unknown NPC behavior {} (expected one of: {})", other, NPC_BEHAVIORS.join(", "))),
+    }
+}
+
+/// An NPC player of a world
+pub struct Npc {
+    player_id: String,
+    controller: Box<dyn BotController>,
+}
+
+impl Npc {
+    /// Add an NPC to a world as player `npc_<behavior>_<index>`
+    ///
+    /// The NPC starts like a respawned player, with a base and a worker in its own zone,
+    /// plus a mineral deposit next to the worker to fund its units.
+    pub fn spawn(world: &mut World, behavior: &str, index: usize) -> Result<Self, String> {
+        let controller = controller(behavior)?;
+        let player_id = format!("npc_{}_{}", behavior, index);
+        let zone_id = world.spawn_player(&player_id)?;
+
+        world.with_zone_write(&zone_id, |zone| {
+            let worker = zone.entities.iter()
+                .find(|entity| entity.owner.as_deref() == Some(player_id.as_str()) && entity.kind == "worker")
+                .map(|worker| (worker.x, worker.y))
+                .expect("spawned worker exists");
+            let deposit = neighbours(worker)
+                .find(|&(x, y)| {
+                    zone.get_tile(x, y).is_some_and(|tile| tile.surface_type.movement_cost_for(Mobility::GROUND).is_some())
+                        && !zone.entities.iter().any(|entity| (entity.x, entity.y) == (x, y))
+                })
+                .unwrap_or(worker);
+            zone.resources.push(ResourceDeposit { x: deposit.0, y: deposit.1, amount: NPC_DEPOSIT_AMOUNT });
+        }).expect("spawned zone exists");
+        world.persist_zone(&zone_id);
+
+        Ok(Self { player_id, controller })
+    }
+
+    /// Player ID of the NPC
+    pub fn player_id(&self) -> &str {
+        &self.player_id
+    }
+
+    /// Behavior name of the NPC
+    pub fn behavior(&self) -> &'static str {
+        self.controller.behavior()
+    }
+
+    /// Decide and issue the NPC's commands for the current script tick
+    ///
+    /// Returns an error message per rejected command.
+    pub fn take_turn(&mut self, world: &mut World) -> Vec<String> {
+        let commands = self.controller.decide(&world.player_snapshot(&self.player_id));
+        world.apply_commands(&self.player_id, &commands)
+    }
+}
+
+/// Harvests with its workers and produces more workers; never attacks
+pub struct IdleHarvester;
+
+impl BotController for IdleHarvester {
+    fn behavior(&self) -> &'static str {
+        "harvester"
+    }
+
+    fn decide(&mut self, snapshot: &serde_json::Value) -> Vec<BotCommand> {
+        let view = View::parse(snapshot);
+        let mut commands = view.harvest();
+        commands.extend(view.produce("worker", view.count("worker") < MAX_WORKERS));
+        commands
+    }
+}
+
+/// Spends everything on soldiers that hunt down every enemy in sight
+pub struct Raider;
+
+impl BotController for Raider {
+    fn behavior(&self) -> &'static str {
+        "raider"
+    }
+
+    fn decide(&mut self, snapshot: &serde_json::Value) -> Vec<BotCommand> {
+        let view = View::parse(snapshot);
+        let mut commands = view.harvest();
+        if view.count("worker") == 0 {
+            commands.extend(view.produce("worker", true));
+        } else {
+            commands.extend(view.produce("soldier", true));
+        }
+        for soldier in view.units_of("soldier") {
+            if let Some(enemy) = view.nearest_enemy(soldier, |_| true) {
+                commands.push(engage(soldier, enemy));
+            }
+        }
+        commands
+    }
+}
+
+/// Keeps a few soldiers next to its base and only fights enemies that come close
+pub struct TurtleDefender;
+
+impl BotController for TurtleDefender {
+    fn behavior(&self) -> &'static str {
+        "turtle"
+    }
+
+    fn decide(&mut self, snapshot: &serde_json::Value) -> Vec<BotCommand> {
+        let view = View::parse(snapshot);
+        let mut commands = view.harvest();
+        commands.extend(view.produce("soldier", view.count("worker") > 0 && view.count("soldier") < MAX_DEFENDERS));
+        let Some(base) = view.base() else {
+            return commands;
+        };
+        for soldier in view.units_of("soldier") {
+            let intruder = view.nearest_enemy(soldier, |enemy| distance(enemy.position, base.position) <= DEFENSE_RADIUS);
+            match intruder {
+                Some(enemy) => commands.push(engage(soldier, enemy)),
+                None if distance(soldier.position, base.position) > 2 && soldier.action.is_none() => {
+                    commands.push(move_to(soldier, base.position));
+                }
+                None => {}
+            }
+        }
+        commands
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+struct Position {
+    x: usize,
+    y: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct Entity {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    zone_id: String,
+    position: Position,
+    #[serde(default)]
+    action: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Resource {
+    zone_id: String,
+    position: Position,
+}
+
+/// The parts of a snapshot the behaviors use, sorted by ID for stable decisions
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct View {
+    units: Vec<Entity>,
+    structures: Vec<Entity>,
+    enemy_units: Vec<Entity>,
+    enemy_structures: Vec<Entity>,
+    resources: Vec<Resource>,
+    stockpile: std::collections::HashMap<String, u32>,
+}
+
+impl View {
+    fn parse(snapshot: &serde_json::Value) -> Self {
+        let mut view: View = serde_json::from_value(snapshot.clone()).unwrap_or_default();
+        for entities in [&mut view.units, &mut view.structures, &mut view.enemy_units, &mut view.enemy_structures] {
+            entities.sort_by(|a, b| a.id.cmp(&b.id));
+        }
+        view
+    }
+
+    fn units_of<'a>(&'a self, kind: &'a str) -> impl Iterator<Item = &'a Entity> + 'a {
+        self.units.iter().filter(move |unit| unit.kind == kind)
+    }
+
+    fn count(&self, kind: &str) -> usize {
+        self.units_of(kind).count()
+    }
+
+    fn base(&self) -> Option<&Entity> {
+        self.structures.iter().find(|structure| structure.kind == "base")
+    }
+
+    /// Produce a unit at the base if `wanted` and the stockpile can pay for it
+    fn produce(&self, kind: &str, wanted: bool) -> Option<BotCommand> {
+        let minerals = self.stockpile.get("minerals").copied().unwrap_or(0);
+        let affordable = unit_cost(kind).is_ok_and(|cost| minerals >= cost);
+        let base = self.base()?;
+        (wanted && affordable).then(|| BotCommand {
+            action: "produceUnit".to_string(),
+            actor: Some(base.id.clone()),
+            params: serde_json::json!({"unitType": kind}),
+            player_id: None,
+        })
+    }
+
+    /// Workers harvest a deposit in reach, or walk to the nearest one in their zone
+    fn harvest(&self) -> Vec<BotCommand> {
+        let mut commands = Vec::new();
+        for worker in self.units_of("worker") {
+            let nearest = self.resources.iter()
+                .filter(|resource| resource.zone_id == worker.zone_id)
+                .min_by_key(|resource| (distance(resource.position, worker.position), resource.position.y, resource.position.x));
+            match nearest {
+                Some(resource) if distance(resource.position, worker.position) <= 1 => commands.push(BotCommand {
+                    action: "harvest".to_string(),
+                    actor: Some(worker.id.clone()),
+                    params: serde_json::json!({}),
+                    player_id: None,
+                }),
+                Some(resource) if worker.action.is_none() => commands.push(move_to(worker, resource.position)),
+                _ => {}
+            }
+        }
+        commands
+    }
+
+    /// Nearest visible enemy in the unit's zone accepted by `filter` (units before structures)
+    fn nearest_enemy<'a>(&'a self, unit: &Entity, filter: impl Fn(&Entity) -> bool) -> Option<&'a Entity> {
+        let nearest = |enemies: &'a [Entity]| enemies.iter()
+            .filter(|enemy| enemy.zone_id == unit.zone_id && filter(enemy))
+            .min_by_key(|enemy| (distance(enemy.position, unit.position), enemy.id.as_str()));
+        nearest(&self.enemy_units).or_else(|| nearest(&self.enemy_structures))
+    }
+}
+
+/// Attack an enemy in reach, or walk towards it
+fn engage(unit: &Entity, enemy: &Entity) -> BotCommand {
+    if distance(unit.position, enemy.position) <= 1 {
+        return BotCommand {
+            action: "attack".to_string(),
+            actor: Some(unit.id.clone()),
+            params: serde_json::json!({"target": enemy.id}),
+            player_id: None,
+        };
+    }
+    move_to(unit, enemy.position)
+}
+
+fn move_to(unit: &Entity, position: Position) -> BotCommand {
+    BotCommand {
+        action: "moveTo".to_string(),
+        actor: Some(unit.id.clone()),
+        params: serde_json::json!({"position": {"x": position.x, "y": position.y}}),
+        player_id: None,
+    }
+}
+
+/// Chebyshev distance (attacks and harvests reach the 8 neighbouring tiles)
+fn distance(a: Position, b: Position) -> usize {
+    a.x.abs_diff(b.x).max(a.y.abs_diff(b.y))
+}
+
+fn neighbours((x, y): (usize, usize)) -> impl Iterator<Item = (usize, usize)> {
+    [(0, -1), (1, 0), (0, 1), (-1, 0), (1, -1), (1, 1), (-1, 1), (-1, -1)]
+        .into_iter()
+        .filter_map(move |(dx, dy)| Some((x.checked_add_signed(dx)?, y.checked_add_signed(dy)?)))
+}
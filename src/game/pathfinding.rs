@@ -0,0 +1,140 @@
+//! Pathfinding module
+//!
+//! Cheapest paths between tiles of a zone. Moves go to the four neighbouring tiles and
+//! cost the movement cost of the tile entered, which depends on the unit's [`Mobility`]:
+//! ground units cannot enter Water, swimmers can, and flyers cross everything but obstacles.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::game::zone::{Mobility, Zone};
+
+/// Cost of entering a tile (`None` if outside the zone or impassable for the unit)
+fn entry_cost(zone: &Zone, (x, y): (usize, usize), mobility: Mobility) -> Option<u32> {
+    zone.get_tile(x, y)?.surface_type.movement_cost_for(mobility)
+}
+
+/// Tiles next to a tile (diagonals excluded; may be outside the zone)
+fn neighbours((x, y): (usize, usize)) -> [(usize, usize); 4] {
+    [
+        (x.wrapping_sub(1), y),
+        (x + 1, y),
+        (x, y.wrapping_sub(1)),
+        (x, y + 1),
+    ]
+}
+
+/// Find the cheapest path from `from` to `to` for a unit
+///
+/// Returns the tiles of the path (both ends included) and its total cost, or `None`
+/// if either end cannot be entered by the unit or no path exists.
+pub fn find_path(zone: &Zone, from: (usize, usize), to: (usize, usize), mobility: Mobility) -> Option<(Vec<(usize, usize)>, u32)> {
+    entry_cost(zone, from, mobility)?;
+    entry_cost(zone, to, mobility)?;
+
+    let mut costs: HashMap<(usize, usize), u32> = HashMap::from([(from, 0)]);
+    let mut previous: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+    let mut queue = BinaryHeap::from([Reverse((0, from))]);
+
+    while let Some(Reverse((cost, tile))) = queue.pop() {
+        if tile == to {
+            let mut path = vec![to];
+            while let Some(&step) = previous.get(path.last().expect("path is never empty")) {
+                path.push(step);
+            }
+            path.reverse();
+            return Some((path, cost));
+        }
+        if costs.get(&tile).is_some_and(|&best| cost > best) {
+            continue;
+        }
+
+        for next in neighbours(tile) {
+            let Some(step_cost) = entry_cost(zone, next, mobility) else {
+                continue;
+            };
+            let next_cost = cost + step_cost;
+            if costs.get(&next).is_none_or(|&best| next_cost < best) {
+                costs.insert(next, next_cost);
+                previous.insert(next, tile);
+                queue.push(Reverse((next_cost, next)));
+            }
+        }
+    }
+    None
+}
+
+/// All tiles a unit can reach from a tile (empty if it cannot stand on it)
+pub fn reachable_tiles(zone: &Zone, from: (usize, usize), mobility: Mobility) -> HashSet<(usize, usize)> {
+    let mut reached = HashSet::new();
+    if entry_cost(zone, from, mobility).is_none() {
+        return reached;
+    }
+
+    let mut stack = vec![from];
+    reached.insert(from);
+    while let Some(tile) = stack.pop() {
+        for next in neighbours(tile) {
+            if entry_cost(zone, next, mobility).is_some() && reached.insert(next) {
+                stack.push(next);
+            }
+        }
+    }
+    reached
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::zone::{Exit, ExitDirection, SurfaceType, WATER_SWIM_COST};
+
+    /// Plain zone split in two by a vertical river at x = 5
+    fn river_zone() -> Zone {
+        let mut zone = Zone::generate("river".to_string(), 1);
+        for tile in zone.tiles.iter_mut().flatten() {
+            tile.surface_type = if tile.x == 5 { SurfaceType::Water } else { SurfaceType::Plain };
+        }
+        zone
+    }
+
+    #[test]
+    fn test_swimmer_crosses_water_and_ground_unit_cannot() {
+        let zone = river_zone();
+        let swimmer = Mobility { can_swim: true, can_fly: false };
+
+        let (path, cost) = find_path(&zone, (2, 2), (8, 2), swimmer).unwrap();
+        assert_eq!(path.first(), Some(&(2, 2)));
+        assert_eq!(path.last(), Some(&(8, 2)));
+        assert!(path.contains(&(5, 2)));
+        assert_eq!(cost, 5 * 2 + WATER_SWIM_COST);
+
+        assert!(find_path(&zone, (2, 2), (8, 2), Mobility::GROUND).is_none());
+        assert!(find_path(&zone, (2, 2), (5, 2), Mobility::GROUND).is_none());
+        assert_eq!(find_path(&zone, (2, 2), (4, 9), Mobility::GROUND).unwrap().1, 2 * 9);
+    }
+
+    #[test]
+    fn test_flyer_crosses_water_but_not_obstacles() {
+        let mut zone = river_zone();
+        let flyer = Mobility { can_swim: false, can_fly: true };
+        assert_eq!(find_path(&zone, (2, 2), (8, 2), flyer).unwrap().1, 6 * 2);
+
+        for row in zone.tiles.iter_mut() {
+            row[5].surface_type = SurfaceType::Obstacle;
+        }
+        assert!(find_path(&zone, (2, 2), (8, 2), flyer).is_none());
+    }
+
+    #[test]
+    fn test_connectivity_treats_water_as_impassable() {
+        let mut zone = river_zone();
+        zone.exits = vec![
+            Exit { x: 0, y: 3, direction: ExitDirection::West },
+            Exit { x: 2, y: 0, direction: ExitDirection::North },
+        ];
+        assert!(zone.validate_connectivity().is_ok());
+
+        zone.exits.push(Exit { x: 29, y: 3, direction: ExitDirection::East });
+        assert!(zone.validate_connectivity().unwrap_err().contains("(29, 3)"));
+    }
+}
@@ -0,0 +1,103 @@
+//! Replay history module
+//!
+//! Serialized copies of the world taken at recent script ticks, kept in a bounded ring
+//! buffer. A snapshot can be restored into a detached [`World`] to rebuild what a player's
+//! script saw at that tick, e.g. to dry-run new code against it without touching the
+//! live world. Like [`World::save`], a snapshot holds the persistent state (zones,
+//! stockpiles, teams, ...) but not in-flight moves or the event log.
+
+use std::collections::VecDeque;
+
+use crate::game::world::World;
+
+/// Snapshots kept in the history (the oldest are evicted first)
+pub const MAX_REPLAY_SNAPSHOTS: usize = 20;
+
+/// The serialized world at a script tick
+#[derive(Debug, Clone)]
+pub struct WorldSnapshot {
+    tick: u64,
+    state: String,
+}
+
+impl WorldSnapshot {
+    /// Serialize the world as of its current script tick
+    pub fn capture(world: &World) -> Result<Self, String> {
+        let state = serde_json::to_string(world)
+            .map_err(|e| format!("Failed to serialize world: {}", e))?;
+        Ok(WorldSnapshot { tick: world.get_script_tick(), state })
+    }
+
+    /// Script tick the snapshot was taken at
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Rebuild a detached world from the snapshot
+    pub fn restore(&self) -> Result<World, String> {
+        serde_json::from_str(&self.state)
+            .map_err(|e| format!("Failed to deserialize world: {}", e))
+    }
+
+    /// The snapshot a player's script got at this tick (see [`World::player_snapshot`])
+    pub fn player_view(&self, player_id: &str) -> Result<serde_json::Value, String> {
+        Ok(self.restore()?.player_snapshot(player_id))
+    }
+}
+
+/// Snapshots of the most recent script ticks
+#[derive(Debug, Clone, Default)]
+pub struct ReplayHistory {
+    snapshots: VecDeque<WorldSnapshot>,
+}
+
+impl ReplayHistory {
+    /// Create an empty history
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a snapshot, replacing one of the same tick and evicting the oldest if full
+    pub fn record(&mut self, snapshot: WorldSnapshot) {
+        self.snapshots.retain(|existing| existing.tick != snapshot.tick);
+        if self.snapshots.len() >= MAX_REPLAY_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// Snapshot taken at a script tick, if still kept
+    pub fn at(&self, tick: u64) -> Option<&WorldSnapshot> {
+        self.snapshots.iter().find(|snapshot| snapshot.tick == tick)
+    }
+
+    /// Script ticks kept, oldest first
+    pub fn ticks(&self) -> Vec<u64> {
+        self.snapshots.iter().map(WorldSnapshot::tick).collect()
+    }
+
+    /// Oldest and newest script ticks kept, if any
+    pub fn range(&self) -> Option<(u64, u64)> {
+        Some((self.snapshots.front()?.tick, self.snapshots.back()?.tick))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oldest_snapshots_are_evicted() {
+        let world = World::new();
+        let mut history = ReplayHistory::new();
+        for tick in 0..MAX_REPLAY_SNAPSHOTS as u64 + 3 {
+            let snapshot = WorldSnapshot::capture(&world).unwrap();
+            history.record(WorldSnapshot { tick, ..snapshot });
+        }
+
+        assert_eq!(history.range(), Some((3, MAX_REPLAY_SNAPSHOTS as u64 + 2)));
+        assert!(history.at(2).is_none());
+        let restored = history.at(3).unwrap().restore().unwrap();
+        assert_eq!(restored.get_tick(), world.get_tick());
+    }
+}
@@ -0,0 +1,45 @@
+//! World random number generator
+//!
+//! Every random decision of the simulation (for now, which entities a storm strikes)
+//! is drawn from the single [`WorldRng`] owned by the [`World`](crate::game::world::World),
+//! in tick order. A world created with a seed therefore plays out the same way each
+//! time it is given the same commands; without one, the generator is seeded from the
+//! system's entropy.
+
+/// SplitMix64 generator: small, fast, and identical on every platform
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorldRng {
+    state: u64,
+}
+
+impl WorldRng {
+    /// Generator producing the same sequence for the same seed
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Generator seeded from the system's entropy
+    pub fn from_entropy() -> Self {
+        Self::new(uuid::Uuid::new_v4().as_u64_pair().0)
+    }
+
+    /// Next 64 random bits
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Next random number in `[0, 1)`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Default for WorldRng {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
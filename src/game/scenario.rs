@@ -0,0 +1,364 @@
+//! Scenario module
+//!
+//! Goals for campaign runs. A [`Scenario`] sets the map and NPC opponents of a run and
+//! the objectives its player must meet: harvest an amount of a resource, destroy the
+//! structures of a kind held by an enemy, or survive a number of ticks. Scenarios are
+//! built in (see [`BUILTIN_SCENARIOS`]) or read from `<name>.json` / `<name>.toml` files
+//! in the `scenarios` directory of the maps directory (`GEEKCRAFT_MAPS_DIR`), which take
+//! precedence.
+//!
+//! An [`ObjectiveEvaluator`] checks the objectives against the run's world after every
+//! tick. An objective with a `deadline_ticks` fails if it is not met by then, and a
+//! survival objective fails as soon as the player is defeated; met or failed, an
+//! objective stays so. The run is completed once every mandatory objective is, and failed
+//! as soon as one of them fails; optional objectives are only there for the player.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::campaign::RunStatus;
+use crate::game::events::GameEventKind;
+use crate::game::world::World;
+use crate::game::zone::ResourceType;
+
+/// Subdirectory of the maps directory holding scenario files
+pub const SCENARIOS_DIR: &str = "scenarios";
+
+/// Names of the scenarios available without a file (see [`Scenario::builtin`])
+pub const BUILTIN_SCENARIOS: &[&str] = &["crossroads_harvest", "crossroads_conquest"];
+
+/// What a player must do to meet an objective
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Goal {
+    /// Collect `amount` of a resource from deposits (spending it doesn't count against it)
+    Harvest {
+        /// Resource to collect
+        resource: ResourceType,
+        /// Amount to collect
+        amount: u32,
+    },
+    /// Leave no entity of `kind` owned by `owner`, or by any other player without one
+    /// (neutral entities don't count)
+    Destroy {
+        /// Entity kind, e.g. `base`
+        kind: String,
+        /// Player whose entities must be destroyed
+        #[serde(default)]
+        owner: Option<String>,
+    },
+    /// Still be in play after `ticks` ticks of the run
+    Survive {
+        /// Ticks to survive
+        ticks: u64,
+    },
+}
+
+/// An objective of a scenario
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Objective {
+    /// Identifier, unique within the scenario
+    pub id: String,
+    /// Text shown to players, e.g. "Harvest 500 minerals"
+    pub description: String,
+    /// What to do
+    #[serde(flatten)]
+    pub goal: Goal,
+    /// Whether the run fails if the objective does (default: true)
+    #[serde(default = "default_mandatory")]
+    pub mandatory: bool,
+    /// Run tick by which the objective must be met (none: no time limit)
+    #[serde(default)]
+    pub deadline_ticks: Option<u64>,
+}
+
+fn default_mandatory() -> bool {
+    true
+}
+
+fn default_player() -> String {
+    "player1".to_string()
+}
+
+/// A campaign scenario: where it is played and what the player must do
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scenario {
+    /// Scenario name (defaults to the file name without its extension)
+    #[serde(default)]
+    pub name: String,
+    /// Short description shown to players
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Map template the run is played on (a procedural zone per player if absent)
+    #[serde(default)]
+    pub map_template: Option<String>,
+    /// Behaviors of the NPC opponents of the run
+    #[serde(default)]
+    pub npc_opponents: Vec<String>,
+    /// Player the objectives are for (default: `player1`); they get a base and a worker
+    /// in their own zone if the map gives them nothing
+    #[serde(default = "default_player")]
+    pub player: String,
+    /// Resources the player starts with
+    #[serde(default)]
+    pub starting_resources: HashMap<ResourceType, u32>,
+    /// Objectives, in the order they are shown
+    pub objectives: Vec<Objective>,
+}
+
+/// Whether an objective is met
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ObjectiveStatus {
+    /// Neither met nor failed yet
+    #[default]
+    InProgress,
+    /// Met
+    Completed,
+    /// Missed its deadline, or the player was defeated before surviving long enough
+    Failed,
+}
+
+/// Progress of a run towards one of its scenario's objectives
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObjectiveProgress {
+    /// ID of the objective
+    pub id: String,
+    /// Text of the objective
+    pub description: String,
+    /// Whether the run fails if the objective does
+    pub mandatory: bool,
+    /// Whether the objective is met
+    pub status: ObjectiveStatus,
+    /// Resources collected, entities destroyed, or ticks survived so far
+    pub progress: u64,
+    /// Value of `progress` that meets the objective
+    pub target: u64,
+}
+
+impl Scenario {
+    /// Find scenario `name`: a file in `<maps_dir>/scenarios`, else a built-in one
+    pub fn find(maps_dir: &Path, name: &str) -> Result<Self, String> {
+        let dir = maps_dir.join(SCENARIOS_DIR);
+        match Self::load_named(&dir, name) {
+            Ok(scenario) => Ok(scenario),
+            Err(e) if !e.contains("not found") => Err(e),
+            Err(_) => Self::builtin(name).ok_or_else(|| format!(
+                "Scenario {} not found in {} nor among the built-in scenarios ({})",
+                name, dir.display(), BUILTIN_SCENARIOS.join(", ")
+            )),
+        }
+    }
+
+    /// Load scenario `name` from a directory (`<name>.json`, else `<name>.toml`)
+    pub fn load_named(dir: &Path, name: &str) -> Result<Self, String> {
+        if name.is_empty() || name.len() > 64 || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(format!("Scenario name {:?} must be 1 to 64 alphanumeric characters, underscores, or hyphens", name));
+        }
+        let path = ["json", "toml"]
+            .iter()
+            .map(|extension| dir.join(format!("{}.{}", name, extension)))
+            .find(|path| path.is_file())
+            .ok_or_else(|| format!("Scenario {} not found in {}", name, dir.display()))?;
+
+        let file = path.display().to_string();
+        let source = fs::read_to_string(&path)
+            .map_err(|e| format!("{}: failed to read scenario: {}", file, e))?;
+        let parsed: Result<Self, String> = if path.extension().is_some_and(|extension| extension == "json") {
+            serde_json::from_str(&source).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&source).map_err(|e| e.to_string())
+        };
+        let mut scenario = parsed.map_err(|e| format!("{}: malformed scenario: {}", file, e.trim_end()))?;
+        if scenario.name.is_empty() {
+            scenario.name = name.to_string();
+        }
+        scenario.validate().map_err(|e| format!("{}: {}", file, e))?;
+        Ok(scenario)
+    }
+
+    /// Built-in scenario `name` (see [`BUILTIN_SCENARIOS`]), played on the `crossroads` map
+    pub fn builtin(name: &str) -> Option<Self> {
+        let objective = |id: &str, description: &str, goal: Goal, mandatory: bool, deadline_ticks: Option<u64>| Objective {
+            id: id.to_string(),
+            description: description.to_string(),
+            goal,
+            mandatory,
+            deadline_ticks,
+        };
+        let (description, objectives) = match name {
+            "crossroads_harvest" => ("Build up an economy from a lone base", vec![
+                objective("harvest", "Harvest 500 minerals", Goal::Harvest { resource: ResourceType::Minerals, amount: 500 }, true, None),
+                objective("survive", "Survive 2000 ticks", Goal::Survive { ticks: 2000 }, true, None),
+            ]),
+            "crossroads_conquest" => ("Destroy the base across the pond", vec![
+                objective("destroy_base", "Destroy the enemy base", Goal::Destroy { kind: "base".to_string(), owner: Some("player2".to_string()) }, true, Some(36_000)),
+                objective("harvest", "Harvest 1000 minerals", Goal::Harvest { resource: ResourceType::Minerals, amount: 1000 }, false, None),
+            ]),
+            _ => return None,
+        };
+        Some(Self {
+            name: name.to_string(),
+            description: Some(description.to_string()),
+            map_template: Some("crossroads".to_string()),
+            npc_opponents: Vec::new(),
+            player: default_player(),
+            starting_resources: [(ResourceType::Minerals, 100)].into(),
+            objectives,
+        })
+    }
+
+    /// Check that the scenario has a mandatory objective and that its objectives can be met
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.objectives.iter().any(|objective| objective.mandatory) {
+            return Err("a scenario needs at least one mandatory objective".to_string());
+        }
+        let mut ids = HashSet::new();
+        for objective in &self.objectives {
+            if !ids.insert(objective.id.as_str()) {
+                return Err(format!("objective ID {:?} is used twice", objective.id));
+            }
+            match &objective.goal {
+                Goal::Harvest { amount: 0, .. } | Goal::Survive { ticks: 0 } => {
+                    return Err(format!("objective {} has nothing to do", objective.id));
+                }
+                Goal::Harvest { resource: ResourceType::Credits, .. } => {
+                    return Err(format!("objective {}: credits are not found in deposits", objective.id));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Give the scenario's player their start in a run's world: a base and a worker if
+    /// the map gave them no entity, and the starting resources
+    pub fn set_up(&self, world: &mut World) -> Result<(), String> {
+        if world.zones_of_player(&self.player).is_empty() {
+            world.spawn_player(&self.player)?;
+        }
+        for (&resource, &amount) in &self.starting_resources {
+            world.deposit_resources(&self.player, resource, amount);
+        }
+        Ok(())
+    }
+
+    /// Progress of a run starting in `world`, nothing met yet
+    pub fn initial_progress(&self, world: &World) -> Vec<ObjectiveProgress> {
+        self.objectives.iter()
+            .map(|objective| ObjectiveProgress {
+                id: objective.id.clone(),
+                description: objective.description.clone(),
+                mandatory: objective.mandatory,
+                status: ObjectiveStatus::InProgress,
+                progress: 0,
+                target: match &objective.goal {
+                    Goal::Harvest { amount, .. } => *amount as u64,
+                    Goal::Destroy { kind, owner } => self.destroy_targets(world, kind, owner.as_deref()),
+                    Goal::Survive { ticks } => *ticks,
+                },
+            })
+            .collect()
+    }
+
+    /// Entities a [`Goal::Destroy`] objective still has to destroy
+    fn destroy_targets(&self, world: &World, kind: &str, owner: Option<&str>) -> u64 {
+        world.get_zone_ids().iter()
+            .filter_map(|zone_id| world.with_zone_read(zone_id, |zone| {
+                zone.entities.iter()
+                    .filter(|entity| entity.kind == kind)
+                    .filter(|entity| match (owner, entity.owner.as_deref()) {
+                        (Some(owner), Some(entity_owner)) => entity_owner == owner,
+                        (None, Some(entity_owner)) => entity_owner != self.player,
+                        (_, None) => false,
+                    })
+                    .count() as u64
+            }))
+            .sum()
+    }
+}
+
+/// Checks the objectives of a run's scenario against its world, tick after tick
+#[derive(Debug, Clone)]
+pub struct ObjectiveEvaluator {
+    scenario: Scenario,
+    /// Last world tick whose events were counted
+    since_tick: u64,
+}
+
+impl ObjectiveEvaluator {
+    /// Evaluator for a run whose world starts at tick 0
+    pub fn new(scenario: Scenario) -> Self {
+        Self { scenario, since_tick: 0 }
+    }
+
+    /// Scenario evaluated
+    pub fn scenario(&self) -> &Scenario {
+        &self.scenario
+    }
+
+    /// Update the progress of the objectives after a tick, returning where the run stands
+    ///
+    /// `run_tick` is the run's tick, which deadlines and survival are measured in; the
+    /// resources collected since the previous call are added to the harvest objectives.
+    pub fn evaluate(&mut self, world: &World, run_tick: u64, progress: &mut [ObjectiveProgress]) -> RunStatus {
+        let player = self.scenario.player.as_str();
+        let mut harvested: HashMap<ResourceType, u64> = HashMap::new();
+        let events = world.event_log().query(self.since_tick, usize::MAX, |event| {
+            matches!(event.kind, GameEventKind::ResourceCollected { .. }) && event.players.iter().any(|p| p == player)
+        });
+        for event in events {
+            if let GameEventKind::ResourceCollected { resource, amount, .. } = event.kind {
+                *harvested.entry(resource).or_default() += amount as u64;
+            }
+        }
+        self.since_tick = world.get_tick();
+
+        for (objective, progress) in self.scenario.objectives.iter().zip(progress.iter_mut()) {
+            if progress.status != ObjectiveStatus::InProgress {
+                continue;
+            }
+            let met = match &objective.goal {
+                Goal::Harvest { resource, .. } => {
+                    progress.progress = (progress.progress + harvested.get(resource).copied().unwrap_or(0)).min(progress.target);
+                    progress.progress >= progress.target
+                }
+                Goal::Destroy { kind, owner } => {
+                    let remaining = self.scenario.destroy_targets(world, kind, owner.as_deref());
+                    progress.target = progress.target.max(remaining);
+                    progress.progress = progress.target - remaining;
+                    remaining == 0
+                }
+                Goal::Survive { ticks } => {
+                    if world.is_defeated(player) {
+                        progress.status = ObjectiveStatus::Failed;
+                        continue;
+                    }
+                    progress.progress = run_tick.min(*ticks);
+                    run_tick >= *ticks
+                }
+            };
+            if met {
+                progress.status = ObjectiveStatus::Completed;
+            } else if objective.deadline_ticks.is_some_and(|deadline| run_tick >= deadline) {
+                progress.status = ObjectiveStatus::Failed;
+            }
+        }
+        outcome(progress)
+    }
+}
+
+/// Where a run stands given the progress of its objectives
+pub fn outcome(progress: &[ObjectiveProgress]) -> RunStatus {
+    let mut mandatory = progress.iter().filter(|objective| objective.mandatory);
+    if mandatory.clone().any(|objective| objective.status == ObjectiveStatus::Failed) {
+        RunStatus::Failed
+    } else if mandatory.all(|objective| objective.status == ObjectiveStatus::Completed) {
+        RunStatus::Completed
+    } else {
+        RunStatus::InProgress
+    }
+}
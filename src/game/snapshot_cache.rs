@@ -0,0 +1,238 @@
+//! Snapshot cache
+//!
+//! [`World::player_snapshot`](crate::game::world::World::player_snapshot) is built for every
+//! player on every script tick, but most of what goes into it does not depend on the
+//! player and rarely changes: terrain never moves and idle entities stay put. The cache
+//! keeps, for each zone, the JSON fragments snapshots are assembled from:
+//!
+//! - the zone's obstacles, built when first asked for and again only after
+//!   [`Zone::terrain_version`] changes (the world only changes tiles through
+//!   [`World::with_zone_write`](crate::game::world::World::with_zone_write));
+//! - its entities and resource deposits, built again only when [`Zone::version`] changes
+//!   (every move, attack, harvest, construction, capture or storm gives the zone a new
+//!   version).
+//!
+//! A snapshot then only filters the fragments by owner and visibility and clones those it
+//! keeps. While no zone changes, the cache is not even compared with the zones.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::game::zone::{SurfaceType, Zone};
+use crate::game::zone_map::ZoneMap;
+
+/// An entity, as listed in snapshots
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityFragment {
+    /// Zone of the entity
+    pub zone_id: String,
+    /// Entity ID within its zone
+    pub entity_id: u32,
+    /// Player owning the entity (none for neutral entities)
+    pub owner: Option<String>,
+    /// Column of the entity
+    pub x: usize,
+    /// Row of the entity
+    pub y: usize,
+    /// Whether the entity is a structure rather than a unit
+    pub structure: bool,
+    /// As listed in `units`, `structures` and the enemy lists (with a `null` action)
+    pub listed: serde_json::Value,
+    /// As listed in `allied_units`
+    pub allied: serde_json::Value,
+}
+
+/// A resource deposit, as listed in snapshots
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceFragment {
+    /// Column of the deposit
+    pub x: usize,
+    /// Row of the deposit
+    pub y: usize,
+    /// As listed in `resources`
+    pub listed: serde_json::Value,
+}
+
+/// The entities and resource deposits of a zone at one [`Zone::version`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoneContents {
+    /// Zone ID
+    pub zone_id: String,
+    /// Version of the zone the fragments were built from
+    pub version: u64,
+    /// Entities, in zone order
+    pub entities: Vec<EntityFragment>,
+    /// Resource deposits, in zone order
+    pub resources: Vec<ResourceFragment>,
+}
+
+impl ZoneContents {
+    fn build(zone: &Zone) -> Self {
+        let entities = zone.entities.iter()
+            .map(|entity| EntityFragment {
+                zone_id: zone.id.clone(),
+                entity_id: entity.id,
+                owner: entity.owner.clone(),
+                x: entity.x,
+                y: entity.y,
+                structure: entity.is_structure(),
+                listed: serde_json::json!({
+                    "id": format!("{}:{}", zone.id, entity.id),
+                    "type": entity.kind,
+                    "owner": entity.owner,
+                    "zone_id": zone.id,
+                    "position": {"x": entity.x, "y": entity.y},
+                    "hits": entity.hits,
+                    "action": null,
+                }),
+                allied: serde_json::json!({
+                    "zone_id": zone.id,
+                    "id": entity.id,
+                    "kind": entity.kind,
+                    "owner": entity.owner,
+                    "x": entity.x,
+                    "y": entity.y,
+                    "hits": entity.hits,
+                }),
+            })
+            .collect();
+        let resources = zone.resources.iter()
+            .map(|deposit| ResourceFragment {
+                x: deposit.x,
+                y: deposit.y,
+                listed: serde_json::json!({
+                    "id": format!("{}:{}:{}", zone.id, deposit.x, deposit.y),
+                    "zone_id": zone.id,
+                    "position": {"x": deposit.x, "y": deposit.y},
+                    "amount": deposit.amount,
+                }),
+            })
+            .collect();
+        Self {
+            zone_id: zone.id.clone(),
+            version: zone.version,
+            entities,
+            resources,
+        }
+    }
+}
+
+/// The obstacles of a zone at one [`Zone::terrain_version`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoneTerrain {
+    /// Version of the zone's tiles the fragment was built from
+    pub terrain_version: u64,
+    /// Width in tiles
+    pub width: usize,
+    /// Height in tiles
+    pub height: usize,
+    /// Obstacle tiles, as listed in `obstacles`
+    pub obstacles: serde_json::Value,
+}
+
+impl ZoneTerrain {
+    fn build(zone: &Zone) -> Self {
+        let obstacles = zone.tiles.iter()
+            .flatten()
+            .filter(|tile| tile.surface_type == SurfaceType::Obstacle)
+            .map(|tile| serde_json::json!({"x": tile.x, "y": tile.y}))
+            .collect();
+        Self {
+            terrain_version: zone.terrain_version,
+            width: zone.width,
+            height: zone.height,
+            obstacles,
+        }
+    }
+}
+
+/// Number of fragments built since the cache was created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheBuilds {
+    /// Obstacle lists built
+    pub terrain: u64,
+    /// Entity and deposit lists built (one per zone)
+    pub contents: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    /// Last version given out and number of zones when `contents` was checked
+    checked: Option<(u64, usize)>,
+    contents: Arc<BTreeMap<String, Arc<ZoneContents>>>,
+    terrain: BTreeMap<String, Arc<ZoneTerrain>>,
+    builds: CacheBuilds,
+}
+
+/// Fragments of player snapshots, by zone (see the [module documentation](self))
+///
+/// A cloned cache starts empty.
+#[derive(Debug, Default)]
+pub struct SnapshotCache {
+    state: Mutex<CacheState>,
+}
+
+impl Clone for SnapshotCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl SnapshotCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The contents of every zone, by zone ID
+    ///
+    /// `zone_version` is the last [`Zone::version`] the world gave out: while it and the
+    /// number of zones stay the same, no zone changed and the zones are not looked at.
+    pub fn contents(&self, zones: &ZoneMap, zone_version: u64) -> Arc<BTreeMap<String, Arc<ZoneContents>>> {
+        let mut state = self.state.lock();
+        let stamp = (zone_version, zones.len());
+        if state.checked == Some(stamp) {
+            return state.contents.clone();
+        }
+
+        let mut contents = BTreeMap::new();
+        for zone in zones.values() {
+            let cached = state.contents.get(&zone.id).filter(|cached| cached.version == zone.version).cloned();
+            let fragment = cached.unwrap_or_else(|| {
+                state.builds.contents += 1;
+                Arc::new(ZoneContents::build(&zone))
+            });
+            contents.insert(zone.id.clone(), fragment);
+        }
+        state.terrain.retain(|zone_id, _| contents.contains_key(zone_id));
+        state.contents = Arc::new(contents);
+        state.checked = Some(stamp);
+        state.contents.clone()
+    }
+
+    /// The obstacles of a zone (`None` if the zone does not exist)
+    pub fn terrain(&self, zones: &ZoneMap, zone_id: &str) -> Option<Arc<ZoneTerrain>> {
+        let zone = zones.get(zone_id)?;
+        let mut state = self.state.lock();
+        if let Some(terrain) = state.terrain.get(zone_id).filter(|terrain| terrain.terrain_version == zone.terrain_version) {
+            return Some(terrain.clone());
+        }
+        state.builds.terrain += 1;
+        let terrain = Arc::new(ZoneTerrain::build(&zone));
+        state.terrain.insert(zone_id.to_string(), terrain.clone());
+        Some(terrain)
+    }
+
+    /// Number of fragments built so far
+    pub fn builds(&self) -> CacheBuilds {
+        self.state.lock().builds
+    }
+
+    /// Drop every fragment; they are built again when next needed
+    pub fn clear(&self) {
+        let builds = self.builds();
+        *self.state.lock() = CacheState { builds, ..CacheState::default() };
+    }
+}
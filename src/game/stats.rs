@@ -0,0 +1,181 @@
+//! Statistics read model module
+//!
+//! Player and zone statistics kept apart from the [`World`](crate::game::world::World),
+//! so that reading them never waits on the game loop. After every tick the loop drains
+//! the world's events into a [`StatsBatch`] (see
+//! [`World::drain_stats`](crate::game::world::World::drain_stats)) and sends it to a
+//! background task, which folds it into the [`StatsReadModel`]. The model is only ever
+//! written by that task; the statistics endpoints only read it.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::game::world::WorldEvent;
+
+/// Read model shared between the updating task and the endpoints
+pub type SharedStats = Arc<RwLock<StatsReadModel>>;
+
+/// Sending half of the channel feeding a [`StatsReadModel`]
+pub type StatsFeed = mpsc::UnboundedSender<StatsBatch>;
+
+/// A zone's owner and entity count at the end of a tick
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZoneCensus {
+    /// Zone identifier
+    pub zone_id: String,
+    /// Player owning the zone (if any)
+    pub owner: Option<String>,
+    /// Units and buildings in the zone
+    pub entity_count: usize,
+}
+
+/// What happened in the world since the previous batch
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatsBatch {
+    /// Simulation tick the batch was taken at
+    pub tick: u64,
+    /// Events since the previous batch
+    pub events: Vec<WorldEvent>,
+    /// Every zone at `tick`
+    pub zones: Vec<ZoneCensus>,
+}
+
+/// A player's statistics
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerStat {
+    /// Enemy units and buildings the player's units destroyed
+    pub units_killed: u64,
+    /// Units and buildings of the player destroyed by attacks
+    pub units_lost: u64,
+    /// Resources the player's units harvested
+    pub resources_collected: u64,
+    /// Ticks at the end of which the player was in play (not defeated)
+    pub ticks_survived: u64,
+    /// Times the player was defeated
+    pub defeats: u32,
+    /// Whether the player is defeated and waiting to respawn
+    pub defeated: bool,
+}
+
+/// A change of a zone's owner
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnershipChange {
+    /// Tick of the first batch showing the new owner
+    pub tick: u64,
+    /// New owner (`None` when the zone became unowned)
+    pub owner: Option<String>,
+}
+
+/// A zone's statistics
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZoneStat {
+    /// Current owner
+    pub owner: Option<String>,
+    /// Owners of the zone, oldest first
+    pub ownership_history: Vec<OwnershipChange>,
+    /// Units and buildings in the zone
+    pub entity_count: usize,
+}
+
+/// Player and zone statistics built from [`StatsBatch`]es
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatsReadModel {
+    /// Statistics of every player seen in the world
+    pub player_stats: HashMap<String, PlayerStat>,
+    /// Statistics of every zone of the world
+    pub zone_stats: HashMap<String, ZoneStat>,
+    /// Tick of the latest batch applied
+    pub tick: u64,
+}
+
+impl StatsReadModel {
+    /// Create an empty read model
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Statistics of a player
+    pub fn player(&self, player_id: &str) -> Option<&PlayerStat> {
+        self.player_stats.get(player_id)
+    }
+
+    /// Statistics of a zone
+    pub fn zone(&self, zone_id: &str) -> Option<&ZoneStat> {
+        self.zone_stats.get(zone_id)
+    }
+
+    /// Fold a batch into the statistics
+    ///
+    /// Players are seen once they own a zone or take part in an event. Every player
+    /// seen and not defeated at the end of the batch survived the ticks since the
+    /// previous batch.
+    pub fn apply(&mut self, batch: &StatsBatch) {
+        for event in &batch.events {
+            match event {
+                WorldEvent::UnitDestroyed { owner, attacker, .. } => {
+                    self.player_mut(attacker).units_killed += 1;
+                    if let Some(owner) = owner {
+                        self.player_mut(owner).units_lost += 1;
+                    }
+                }
+                WorldEvent::ResourceCollected { player_id, amount, .. } => {
+                    self.player_mut(player_id).resources_collected += u64::from(*amount);
+                }
+                WorldEvent::PlayerDefeated { player_id, .. } => {
+                    let stat = self.player_mut(player_id);
+                    stat.defeats += 1;
+                    stat.defeated = true;
+                }
+                WorldEvent::PlayerRespawned { player_id, .. } => {
+                    self.player_mut(player_id).defeated = false;
+                }
+                WorldEvent::TradeExecuted { trade, .. } => {
+                    self.player_mut(&trade.buyer);
+                    self.player_mut(&trade.seller);
+                }
+            }
+        }
+
+        let mut present = BTreeSet::new();
+        for census in &batch.zones {
+            present.insert(census.zone_id.as_str());
+            if let Some(owner) = &census.owner {
+                self.player_mut(owner);
+            }
+            let stat = self.zone_stats.entry(census.zone_id.clone()).or_default();
+            stat.entity_count = census.entity_count;
+            if stat.ownership_history.is_empty() || stat.owner != census.owner {
+                stat.owner = census.owner.clone();
+                stat.ownership_history.push(OwnershipChange { tick: batch.tick, owner: census.owner.clone() });
+            }
+        }
+        self.zone_stats.retain(|zone_id, _| present.contains(zone_id.as_str()));
+
+        let elapsed = batch.tick.saturating_sub(self.tick);
+        for stat in self.player_stats.values_mut().filter(|stat| !stat.defeated) {
+            stat.ticks_survived += elapsed;
+        }
+        self.tick = batch.tick;
+    }
+
+    fn player_mut(&mut self, player_id: &str) -> &mut PlayerStat {
+        self.player_stats.entry(player_id.to_string()).or_default()
+    }
+}
+
+/// Start the task applying the batches sent to the returned feed to `model`
+///
+/// The task stops once every sender is dropped.
+pub fn spawn_stats_updater(model: SharedStats) -> StatsFeed {
+    let (feed, mut batches) = mpsc::unbounded_channel::<StatsBatch>();
+    tokio::spawn(async move {
+        while let Some(batch) = batches.recv().await {
+            model.write().apply(&batch);
+        }
+    });
+    feed
+}
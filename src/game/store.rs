@@ -0,0 +1,391 @@
+//! World store module
+//!
+//! Persistence for zones and the portals linking them, so a world survives restarts.
+//! [`SqliteWorldStore`] keeps them in a SQLite file (`zones` table with the compact tile
+//! rows, `portals` table, and the zone assigned to each user in `zone_assignments`),
+//! reading through a [`SqlitePool`] so loads do not wait for saves;
+//! [`InMemoryWorldStore`] is for tests and throwaway servers.
+//! Rows that cannot be decoded are skipped with a warning when loading.
+//!
+//! The SQLite schema is built by numbered migrations in `migrations/`
+//! (`V<n>__<name>.sql`, embedded at compile time). Opening a database runs those it has
+//! not applied yet, recording them in `refinery_schema_history`; a schema change is a new
+//! migration file, never an edit to an applied one.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Mutex;
+
+use refinery::{Migration, Runner};
+use rusqlite::{params, Connection};
+use uuid::Uuid;
+
+use crate::game::world::Portal;
+use crate::game::zone::{CompactZone, Zone};
+use crate::utils::sqlite_pool::{SqlitePool, DEFAULT_SQLITE_READ_CONNECTIONS};
+
+/// Storage for the zones and portals of a world
+pub trait WorldStore: Send + Sync + fmt::Debug {
+    /// Create or replace a zone
+    fn save_zone(&self, zone: &Zone) -> Result<(), String>;
+    /// Load every stored zone (undecodable zones are skipped)
+    fn load_all_zones(&self) -> Result<Vec<Zone>, String>;
+    /// Delete a zone
+    fn delete_zone(&self, zone_id: &str) -> Result<(), String>;
+    /// Create or replace a portal
+    fn save_portal(&self, portal: &Portal) -> Result<(), String>;
+    /// Load every stored portal (undecodable portals are skipped)
+    fn load_all_portals(&self) -> Result<Vec<Portal>, String>;
+    /// Delete a portal
+    fn delete_portal(&self, portal_id: &Uuid) -> Result<(), String>;
+    /// Record the zone assigned to a user
+    fn save_zone_assignment(&self, user_id: i64, zone_id: &str) -> Result<(), String>;
+    /// Load the zone assigned to each user
+    fn load_zone_assignments(&self) -> Result<HashMap<i64, String>, String>;
+}
+
+/// World store kept in memory (lost on restart)
+#[derive(Debug, Default)]
+pub struct InMemoryWorldStore {
+    zones: Mutex<HashMap<String, CompactZone>>,
+    portals: Mutex<HashMap<Uuid, Portal>>,
+    zone_assignments: Mutex<HashMap<i64, String>>,
+}
+
+impl InMemoryWorldStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl WorldStore for InMemoryWorldStore {
+    fn save_zone(&self, zone: &Zone) -> Result<(), String> {
+        self.zones.lock().unwrap().insert(zone.id.clone(), zone.to_compact());
+        Ok(())
+    }
+
+    fn load_all_zones(&self) -> Result<Vec<Zone>, String> {
+        let zones = self.zones.lock().unwrap();
+        Ok(zones.values()
+            .filter_map(|compact| match Zone::from_compact(compact.clone()) {
+                Ok(zone) => Some(zone),
+                Err(e) => {
+                    log::warn!("Skipping stored zone {}: {}", compact.id, e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    fn delete_zone(&self, zone_id: &str) -> Result<(), String> {
+        self.zones.lock().unwrap().remove(zone_id);
+        Ok(())
+    }
+
+    fn save_portal(&self, portal: &Portal) -> Result<(), String> {
+        self.portals.lock().unwrap().insert(portal.id, portal.clone());
+        Ok(())
+    }
+
+    fn load_all_portals(&self) -> Result<Vec<Portal>, String> {
+        Ok(self.portals.lock().unwrap().values().cloned().collect())
+    }
+
+    fn delete_portal(&self, portal_id: &Uuid) -> Result<(), String> {
+        self.portals.lock().unwrap().remove(portal_id);
+        Ok(())
+    }
+
+    fn save_zone_assignment(&self, user_id: i64, zone_id: &str) -> Result<(), String> {
+        self.zone_assignments.lock().unwrap().insert(user_id, zone_id.to_string());
+        Ok(())
+    }
+
+    fn load_zone_assignments(&self) -> Result<HashMap<i64, String>, String> {
+        Ok(self.zone_assignments.lock().unwrap().clone())
+    }
+}
+
+mod embedded {
+    refinery::embed_migrations!("migrations");
+}
+
+/// Migrations of the world database schema, oldest first
+pub fn migrations() -> Vec<Migration> {
+    embedded::migrations::runner().get_migrations().clone()
+}
+
+/// Apply the migrations a database has not applied yet, returning its schema version
+///
+/// Fails without changing anything if the database was migrated past the last of
+/// `migrations` (by a newer server), or if an applied migration was edited since.
+pub fn migrate(conn: &mut Connection, migrations: &[Migration]) -> Result<i32, String> {
+    let latest = migrations.iter().map(Migration::version).max().unwrap_or(0);
+    let current = schema_version(conn)?;
+    if current > latest {
+        return Err(format!(
+            "World database schema version {} is newer than this server supports ({})",
+            current, latest
+        ));
+    }
+
+    let report = Runner::new(migrations).run(conn).map_err(|e| format!("World database migration failed: {}", e))?;
+    for migration in report.applied_migrations() {
+        log::info!("Applied world database migration {}", migration);
+    }
+    schema_version(conn)
+}
+
+/// Version of the last migration applied to a database (0 if none)
+pub fn schema_version(conn: &mut Connection) -> Result<i32, String> {
+    let error = |e: rusqlite::Error| format!("Failed to read the world database schema version: {}", e);
+    let has_history: bool = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'refinery_schema_history')",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(error)?;
+    if !has_history {
+        return Ok(0);
+    }
+    conn.query_row("SELECT COALESCE(MAX(version), 0) FROM refinery_schema_history", [], |row| row.get(0))
+        .map_err(error)
+}
+
+/// World store in a SQLite database file
+pub struct SqliteWorldStore {
+    pool: SqlitePool,
+}
+
+impl fmt::Debug for SqliteWorldStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SqliteWorldStore").finish_non_exhaustive()
+    }
+}
+
+impl SqliteWorldStore {
+    /// Open (or create) a database file with the default number of read connections
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Self::open_with_readers(path, DEFAULT_SQLITE_READ_CONNECTIONS)
+    }
+
+    /// Open (or create) a database file with `readers` read connections (see [`SqlitePool`])
+    pub fn open_with_readers(path: &Path, readers: usize) -> Result<Self, String> {
+        let pool = SqlitePool::open(path, readers)
+            .map_err(|e| format!("Failed to open world database {}: {}", path.display(), e))?;
+        Self::with_pool(pool)
+    }
+
+    /// Open a private in-memory database
+    pub fn open_in_memory() -> Result<Self, String> {
+        let pool = SqlitePool::open_in_memory()
+            .map_err(|e| format!("Failed to open world database: {}", e))?;
+        Self::with_pool(pool)
+    }
+
+    fn with_pool(pool: SqlitePool) -> Result<Self, String> {
+        migrate(&mut pool.write(), &migrations())?;
+        Ok(Self { pool })
+    }
+
+    /// Run a statement, mapping errors to a message
+    fn execute(&self, sql: &str, params: impl rusqlite::Params) -> Result<(), String> {
+        self.pool.write()
+            .execute(sql, params)
+            .map(|_| ())
+            .map_err(|e| format!("World database error: {}", e))
+    }
+}
+
+/// Columns of a `zones` row, before decoding
+type ZoneRow = (String, i64, i64, String, String, String, String, Option<String>);
+
+/// Decode a `zones` row
+fn zone_from_row((id, width, height, tiles, exits, entities, resources, owner): ZoneRow) -> Result<Zone, String> {
+    fn json<T: serde::de::DeserializeOwned>(column: &str, value: &str) -> Result<T, String> {
+        serde_json::from_str(value).map_err(|e| format!("invalid {} column: {}", column, e))
+    }
+    let size = |column: &str, value: i64| {
+        usize::try_from(value).map_err(|_| format!("invalid {} column: {}", column, value))
+    };
+
+    Zone::from_compact(CompactZone {
+        width: size("width", width)?,
+        height: size("height", height)?,
+        // Empty rows never occur (zones are at least MIN_ZONE_SIZE tiles high)
+        tiles: tiles.lines().map(str::to_string).collect(),
+        exits: json("exits", &exits)?,
+        entities: json("entities", &entities)?,
+        resources: json("resources", &resources)?,
+        owner,
+        id,
+    })
+}
+
+impl WorldStore for SqliteWorldStore {
+    fn save_zone(&self, zone: &Zone) -> Result<(), String> {
+        let compact = zone.to_compact();
+        let json = |value: serde_json::Result<String>| value.map_err(|e| format!("Failed to serialize zone {}: {}", zone.id, e));
+
+        self.execute(
+            "INSERT OR REPLACE INTO zones (id, width, height, tiles, exits, entities, resources, owner, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                compact.id,
+                compact.width as i64,
+                compact.height as i64,
+                compact.tiles.join("\n"),
+                json(serde_json::to_string(&compact.exits))?,
+                json(serde_json::to_string(&compact.entities))?,
+                json(serde_json::to_string(&compact.resources))?,
+                compact.owner,
+                chrono::Utc::now().timestamp(),
+            ],
+        )
+    }
+
+    fn load_all_zones(&self) -> Result<Vec<Zone>, String> {
+        let conn = self.pool.read();
+        let mut statement = conn
+            .prepare("SELECT id, width, height, tiles, exits, entities, resources, owner FROM zones ORDER BY id")
+            .map_err(|e| format!("World database error: {}", e))?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?, row.get(7)?))
+            })
+            .map_err(|e| format!("World database error: {}", e))?;
+
+        let mut zones = Vec::new();
+        for row in rows {
+            let decoded = row
+                .map_err(|e| format!("unreadable row: {}", e))
+                .and_then(|row: ZoneRow| {
+                    let id = row.0.clone();
+                    zone_from_row(row).map_err(|e| format!("zone {}: {}", id, e))
+                });
+            match decoded {
+                Ok(zone) => zones.push(zone),
+                Err(e) => log::warn!("Skipping stored zone ({})", e),
+            }
+        }
+        Ok(zones)
+    }
+
+    fn delete_zone(&self, zone_id: &str) -> Result<(), String> {
+        self.execute("DELETE FROM zones WHERE id = ?1", params![zone_id])
+    }
+
+    fn save_portal(&self, portal: &Portal) -> Result<(), String> {
+        self.execute(
+            "INSERT OR REPLACE INTO portals (id, from_zone_id, from_x, from_y, to_zone_id, to_x, to_y)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                portal.id.to_string(),
+                portal.from_zone_id,
+                portal.from_x as i64,
+                portal.from_y as i64,
+                portal.to_zone_id,
+                portal.to_x as i64,
+                portal.to_y as i64,
+            ],
+        )
+    }
+
+    fn load_all_portals(&self) -> Result<Vec<Portal>, String> {
+        let conn = self.pool.read();
+        let mut statement = conn
+            .prepare("SELECT id, from_zone_id, from_x, from_y, to_zone_id, to_x, to_y FROM portals ORDER BY id")
+            .map_err(|e| format!("World database error: {}", e))?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, i64>(5)?,
+                    row.get::<_, i64>(6)?,
+                ))
+            })
+            .map_err(|e| format!("World database error: {}", e))?;
+
+        let mut portals = Vec::new();
+        for row in rows {
+            let decoded = row
+                .map_err(|e| format!("unreadable row: {}", e))
+                .and_then(|(id, from_zone_id, from_x, from_y, to_zone_id, to_x, to_y)| {
+                    let coordinate = |value: i64| usize::try_from(value).map_err(|_| format!("portal {}: invalid coordinate {}", id, value));
+                    Ok(Portal {
+                        id: Uuid::parse_str(&id).map_err(|e| format!("portal {}: {}", id, e))?,
+                        from_x: coordinate(from_x)?,
+                        from_y: coordinate(from_y)?,
+                        to_x: coordinate(to_x)?,
+                        to_y: coordinate(to_y)?,
+                        from_zone_id,
+                        to_zone_id,
+                    })
+                });
+            match decoded {
+                Ok(portal) => portals.push(portal),
+                Err(e) => log::warn!("Skipping stored portal ({})", e),
+            }
+        }
+        Ok(portals)
+    }
+
+    fn delete_portal(&self, portal_id: &Uuid) -> Result<(), String> {
+        self.execute("DELETE FROM portals WHERE id = ?1", params![portal_id.to_string()])
+    }
+
+    fn save_zone_assignment(&self, user_id: i64, zone_id: &str) -> Result<(), String> {
+        self.execute(
+            "INSERT OR REPLACE INTO zone_assignments (user_id, zone_id) VALUES (?1, ?2)",
+            params![user_id, zone_id],
+        )
+    }
+
+    fn load_zone_assignments(&self) -> Result<HashMap<i64, String>, String> {
+        let conn = self.pool.read();
+        let mut statement = conn
+            .prepare("SELECT user_id, zone_id FROM zone_assignments")
+            .map_err(|e| format!("World database error: {}", e))?;
+        let rows = statement
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
+            .map_err(|e| format!("World database error: {}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("World database error: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corrupt_rows_are_skipped() {
+        let store = SqliteWorldStore::open_in_memory().unwrap();
+        let zone = Zone::generate("player_ok_zone".to_string(), 7);
+        store.save_zone(&zone).unwrap();
+
+        store.execute(
+            "INSERT INTO zones (id, width, height, tiles, exits, entities, resources, owner, updated_at)
+             VALUES ('player_bad_zone', 2, 2, 'PX\nPP', '[]', '[]', '[]', NULL, 0),
+                    ('player_worse_zone', 30, 30, '', 'not json', '[]', '[]', NULL, 0)",
+            [],
+        ).unwrap();
+        store.execute(
+            "INSERT INTO portals VALUES ('not-a-uuid', 'player_ok_zone', 0, 0, 'player_ok_zone', 1, 1)",
+            [],
+        ).unwrap();
+
+        assert_eq!(store.load_all_zones().unwrap(), vec![zone]);
+        assert!(store.load_all_portals().unwrap().is_empty());
+
+        store.delete_zone("player_ok_zone").unwrap();
+        assert!(store.load_all_zones().unwrap().is_empty());
+    }
+}
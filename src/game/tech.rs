@@ -0,0 +1,173 @@
+//! Tech module
+//!
+//! The tech tree: upgrades a player researches at a base, over several ticks and for a
+//! mineral cost, once their prerequisites are researched. Researched techs modify unit
+//! stats through [`bonus`], which the world applies while simulating harvests, attacks
+//! and moves.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+/// A unit stat that techs improve
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stat {
+    /// Extra resources collected by one `harvest` command
+    HarvestAmount,
+    /// Damage taken from each attack removed
+    Armor,
+    /// Extra tiles walked per simulation tick
+    MoveSpeed,
+}
+
+/// A stat bonus granted to one kind of unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Modifier {
+    /// Improved stat
+    pub stat: Stat,
+    /// Unit kind it applies to (`worker`, `soldier`, ...)
+    pub unit: &'static str,
+    /// Amount added to the stat
+    pub bonus: u32,
+}
+
+/// An entry of the tech tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Tech {
+    /// Identifier used by the `research` command
+    pub id: &'static str,
+    /// Display name
+    pub name: &'static str,
+    /// Minerals paid when the research starts
+    pub cost: u32,
+    /// Simulation ticks the research takes
+    pub research_ticks: u64,
+    /// Techs to research first
+    pub prerequisites: &'static [&'static str],
+    /// Bonuses granted once researched
+    pub modifiers: &'static [Modifier],
+}
+
+/// Every tech
+pub const TECH_TREE: &[Tech] = &[
+    Tech {
+        id: "improved_harvesting",
+        name: "Improved Harvesting",
+        cost: 150,
+        research_ticks: 60,
+        prerequisites: &[],
+        modifiers: &[Modifier { stat: Stat::HarvestAmount, unit: "worker", bonus: 5 }],
+    },
+    Tech {
+        id: "fast_workers",
+        name: "Fast Workers",
+        cost: 150,
+        research_ticks: 90,
+        prerequisites: &["improved_harvesting"],
+        modifiers: &[Modifier { stat: Stat::MoveSpeed, unit: "worker", bonus: 1 }],
+    },
+    Tech {
+        id: "soldier_armor_1",
+        name: "Soldier Armor I",
+        cost: 100,
+        research_ticks: 60,
+        prerequisites: &[],
+        modifiers: &[Modifier { stat: Stat::Armor, unit: "soldier", bonus: 3 }],
+    },
+    Tech {
+        id: "soldier_armor_2",
+        name: "Soldier Armor II",
+        cost: 200,
+        research_ticks: 120,
+        prerequisites: &["soldier_armor_1"],
+        modifiers: &[Modifier { stat: Stat::Armor, unit: "soldier", bonus: 3 }],
+    },
+];
+
+/// A tech of the tree, by ID
+pub fn tech(id: &str) -> Result<&'static Tech, String> {
+    TECH_TREE.iter()
+        .find(|tech| tech.id == id)
+        .ok_or_else(|| format!("Unknown tech {:?}", id))
+}
+
+/// Total bonus to a stat of a unit kind from a set of researched techs
+pub fn bonus(researched: &BTreeSet<String>, stat: Stat, unit: &str) -> u32 {
+    TECH_TREE.iter()
+        .filter(|tech| researched.contains(tech.id))
+        .flat_map(|tech| tech.modifiers)
+        .filter(|modifier| modifier.stat == stat && modifier.unit == unit)
+        .map(|modifier| modifier.bonus)
+        .sum()
+}
+
+/// A research under way at a base
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Research {
+    /// Tech being researched
+    pub tech: String,
+    /// Zone of the base
+    pub zone_id: String,
+    /// Entity ID of the base; the research is cancelled if it is destroyed
+    pub base_id: u32,
+    /// Position of the base
+    pub x: usize,
+    /// Position of the base
+    pub y: usize,
+    /// Simulation tick the research completes at
+    pub completes_at: u64,
+}
+
+/// Techs of one player
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerTech {
+    /// Researched techs
+    pub researched: BTreeSet<String>,
+    /// Research under way (one at a time)
+    pub research: Option<Research>,
+}
+
+/// A player's techs as shown to their scripts and in `/api/gamestate`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TechStatus {
+    /// Researched techs
+    pub researched: Vec<String>,
+    /// Research under way
+    pub research: Option<Research>,
+    /// Techs that can be researched next
+    pub available: Vec<String>,
+}
+
+impl PlayerTech {
+    /// Check that a tech can be researched now (funds are checked by the world)
+    pub fn can_research(&self, tech: &Tech) -> Result<(), String> {
+        if self.researched.contains(tech.id) {
+            return Err(format!("{} is already researched", tech.id));
+        }
+        if let Some(research) = &self.research {
+            return Err(format!("Already researching {}", research.tech));
+        }
+        let missing: Vec<&str> = tech.prerequisites.iter()
+            .copied()
+            .filter(|prerequisite| !self.researched.contains(*prerequisite))
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!("{} requires {}", tech.id, missing.join(", ")));
+        }
+        Ok(())
+    }
+
+    /// Researched, under way, and available techs
+    pub fn status(&self) -> TechStatus {
+        TechStatus {
+            researched: self.researched.iter().cloned().collect(),
+            research: self.research.clone(),
+            available: TECH_TREE.iter()
+                .filter(|tech| !self.researched.contains(tech.id))
+                .filter(|tech| tech.prerequisites.iter().all(|prerequisite| self.researched.contains(*prerequisite)))
+                .map(|tech| tech.id.to_string())
+                .collect(),
+        }
+    }
+}
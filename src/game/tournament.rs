@@ -0,0 +1,719 @@
+//! Tournament module
+//!
+//! Plays bots against each other in isolated worlds, round after round, in one of the
+//! [`TournamentFormat`]s. Each player starts with a base and a worker in their own zone,
+//! the two zones linked so units can reach each other, and both bots' commands are
+//! applied to the match world every tick. A player who loses every unit and building is
+//! eliminated and loses. Otherwise a bot survives while its script runs without errors
+//! or timeouts; the bot that survives longer wins, and when both last the whole match,
+//! the one with the higher score (see [`match_score`]) wins. A bot that fails forfeits
+//! that match only and plays its next one with a fresh runtime.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::auth::achievements::PlayerStats;
+use crate::auth::models::MatchOutcome;
+use crate::game::replay::{ReplayHistory, WorldSnapshot, MAX_REPLAY_SNAPSHOTS};
+use crate::game::world::{World, WorldConfig};
+use crate::scripting::bundle::ScriptBundle;
+use crate::scripting::js_runtime::ScriptLimits;
+use crate::scripting::runtime::create_runtime;
+
+/// Tournament lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TournamentStatus {
+    /// Matches are being played
+    Running,
+    /// All matches finished
+    Completed,
+}
+
+/// Points for a win in the standings
+pub const WIN_POINTS: u32 = 3;
+
+/// Points for a draw in the standings
+pub const DRAW_POINTS: u32 = 1;
+
+/// Longest match a tournament can be created with, in ticks
+pub const MAX_MATCH_TICKS: u64 = 100_000;
+
+/// Score of each building a player owns at the end of a match
+pub const BUILDING_POINTS: u64 = 100;
+
+/// How players are paired
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TournamentFormat {
+    /// A single round pairing players in order
+    #[default]
+    Pairs,
+    /// Every player meets every other player once
+    RoundRobin,
+    /// Winners meet in the next round until one is left; on a draw the first player of
+    /// the pairing (the better seed) advances
+    SingleElimination,
+}
+
+/// Settings shared by every match of a tournament
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchSettings {
+    /// Ticks after which a match ends
+    pub max_ticks: u64,
+    /// Seed of the match worlds (`None` for a different map each match)
+    pub seed: Option<u64>,
+}
+
+/// Result of one tournament match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TournamentMatch {
+    /// First player
+    pub player_a: String,
+    /// Second player
+    pub player_b: String,
+    /// Winner (`None` for a draw)
+    pub winner: Option<String>,
+    /// Ticks player A's bot ran without errors
+    pub survived_a: u64,
+    /// Ticks player B's bot ran without errors
+    pub survived_b: u64,
+    /// Score of player A at the end of the match (see [`match_score`])
+    pub score_a: u64,
+    /// Score of player B at the end of the match (see [`match_score`])
+    pub score_b: u64,
+    /// Ticks played
+    pub ticks: u64,
+    /// Error that eliminated player A (if any)
+    pub error_a: Option<String>,
+    /// Error that eliminated player B (if any)
+    pub error_b: Option<String>,
+    /// Whether the result was recorded and ratings updated
+    pub recorded: bool,
+    /// Round the match was played in (from 1)
+    #[serde(default)]
+    pub round: u32,
+}
+
+impl TournamentMatch {
+    /// A match that could not be played (no winner, not recorded)
+    pub fn aborted(player_a: &str, player_b: &str, reason: &str) -> Self {
+        TournamentMatch {
+            player_a: player_a.to_string(),
+            player_b: player_b.to_string(),
+            winner: None,
+            survived_a: 0,
+            survived_b: 0,
+            score_a: 0,
+            score_b: 0,
+            ticks: 0,
+            error_a: Some(reason.to_string()),
+            error_b: Some(reason.to_string()),
+            recorded: false,
+            round: 0,
+        }
+    }
+
+    /// Match outcome from player A's point of view
+    pub fn outcome(&self) -> MatchOutcome {
+        match &self.winner {
+            Some(winner) if *winner == self.player_a => MatchOutcome::PlayerAWins,
+            Some(_) => MatchOutcome::PlayerBWins,
+            None => MatchOutcome::Draw,
+        }
+    }
+
+    /// Player who goes on in a single elimination bracket
+    pub fn advancing(&self) -> &str {
+        self.winner.as_deref().unwrap_or(&self.player_a)
+    }
+}
+
+/// A player's record in a tournament
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Standing {
+    /// Player
+    pub player: String,
+    /// Matches played
+    pub played: u32,
+    /// Matches won
+    pub wins: u32,
+    /// Matches drawn
+    pub draws: u32,
+    /// Matches lost
+    pub losses: u32,
+    /// Matches in which the player's bot failed
+    pub forfeits: u32,
+    /// [`WIN_POINTS`] per win and [`DRAW_POINTS`] per draw
+    pub points: u32,
+}
+
+/// A tournament and its match results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TournamentRun {
+    /// Unique tournament identifier
+    pub id: String,
+    /// Current status
+    pub status: TournamentStatus,
+    /// How players are paired
+    #[serde(default)]
+    pub format: TournamentFormat,
+    /// Participating players
+    pub players: Vec<String>,
+    /// Current round (0 before the first one starts)
+    #[serde(default)]
+    pub round: u32,
+    /// Pairings of the current round still to be reported
+    pub pending: Vec<(String, String)>,
+    /// Player left without an opponent in the current round
+    pub bye: Option<String>,
+    /// Players still in the running (single elimination)
+    #[serde(default)]
+    pub remaining: Vec<String>,
+    /// Finished matches
+    pub matches: Vec<TournamentMatch>,
+    /// Matches the tournament will have played once completed
+    #[serde(default)]
+    pub expected_matches: usize,
+    /// Ticks per match
+    pub max_ticks: u64,
+    /// Seed of the match worlds
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Start timestamp (Unix epoch)
+    pub started_at: i64,
+    /// Completion timestamp (Unix epoch)
+    pub finished_at: Option<i64>,
+}
+
+impl TournamentRun {
+    /// Settings of the tournament's matches
+    pub fn settings(&self) -> MatchSettings {
+        MatchSettings { max_ticks: self.max_ticks, seed: self.seed }
+    }
+
+    /// Every player's record, best first (by points, then wins, then fewest forfeits)
+    pub fn standings(&self) -> Vec<Standing> {
+        let mut standings: HashMap<&str, Standing> = self.players.iter()
+            .map(|player| (player.as_str(), Standing {
+                player: player.clone(),
+                played: 0,
+                wins: 0,
+                draws: 0,
+                losses: 0,
+                forfeits: 0,
+                points: 0,
+            }))
+            .collect();
+
+        for result in &self.matches {
+            for (player, error) in [(&result.player_a, &result.error_a), (&result.player_b, &result.error_b)] {
+                let Some(standing) = standings.get_mut(player.as_str()) else {
+                    continue;
+                };
+                standing.played += 1;
+                standing.forfeits += u32::from(error.is_some());
+                match &result.winner {
+                    Some(winner) if winner == player => {
+                        standing.wins += 1;
+                        standing.points += WIN_POINTS;
+                    }
+                    Some(_) => standing.losses += 1,
+                    None => {
+                        standing.draws += 1;
+                        standing.points += DRAW_POINTS;
+                    }
+                }
+            }
+        }
+
+        let mut standings: Vec<Standing> = standings.into_values().collect();
+        standings.sort_by(|a, b| b.points.cmp(&a.points)
+            .then(b.wins.cmp(&a.wins))
+            .then(a.forfeits.cmp(&b.forfeits))
+            .then(a.player.cmp(&b.player)));
+        standings
+    }
+
+    /// Players going on to the next round of a single elimination bracket, the one
+    /// who had a bye first so that the bye moves down the bracket
+    fn advancing(&self) -> Vec<String> {
+        let (pairs, _) = pair_players(&self.remaining);
+        let winners = pairs.iter().map(|(a, b)| {
+            self.matches.iter()
+                .find(|result| result.round == self.round && result.player_a == *a && result.player_b == *b)
+                .map_or(a.as_str(), TournamentMatch::advancing)
+                .to_string()
+        });
+        self.bye.iter().cloned().chain(winners).collect()
+    }
+}
+
+/// Pairings of a round, and the player sitting it out with an odd count
+pub type Round = (Vec<(String, String)>, Option<String>);
+
+/// Pair players in order; with an odd count the last player gets a bye
+pub fn pair_players(players: &[String]) -> Round {
+    let pairs = players.chunks_exact(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect();
+    let bye = players.chunks_exact(2).remainder().first().cloned();
+    (pairs, bye)
+}
+
+/// Round robin schedule (circle method)
+pub fn round_robin_rounds(players: &[String]) -> Vec<Round> {
+    let mut slots: Vec<Option<&String>> = players.iter().map(Some).collect();
+    if slots.len() % 2 == 1 {
+        slots.push(None);
+    }
+    let count = slots.len();
+
+    let mut rounds = Vec::new();
+    for _ in 1..count {
+        let mut pairs = Vec::new();
+        let mut bye = None;
+        for i in 0..count / 2 {
+            match (slots[i], slots[count - 1 - i]) {
+                (Some(a), Some(b)) => pairs.push((a.clone(), b.clone())),
+                (Some(player), None) | (None, Some(player)) => bye = Some(player.clone()),
+                (None, None) => {}
+            }
+        }
+        rounds.push((pairs, bye));
+        // The first slot stays put while the others turn
+        slots[1..].rotate_right(1);
+    }
+    rounds
+}
+
+/// Play one match between two bots in an isolated world (blocking)
+pub fn run_match(player_a: (&str, &ScriptBundle), player_b: (&str, &ScriptBundle), max_ticks: u64) -> TournamentMatch {
+    run_match_with_progress(player_a, player_b, MatchSettings { max_ticks, seed: None }, |_, _| {}).0
+}
+
+/// Play one match, calling `on_tick` with each player's stats after every tick
+///
+/// The stats hold the ticks the player's bot survived, whether the other player was
+/// eliminated or their bot failed, the resources in the player's stockpile, and the
+/// player's defeats (`matches_won` is left at 0). The replay holds the world at the
+/// start, at the end and at evenly spaced ticks in between.
+pub fn run_match_with_progress(
+    player_a: (&str, &ScriptBundle),
+    player_b: (&str, &ScriptBundle),
+    settings: MatchSettings,
+    on_tick: impl FnMut(&str, &PlayerStats),
+) -> (TournamentMatch, ReplayHistory) {
+    let world = match_world([player_a.0, player_b.0], settings.seed);
+    play_match(world, player_a, player_b, settings.max_ticks, on_tick)
+}
+
+/// World of a match: a base and a worker for each player in their own zone, the two
+/// zones linked through their exits
+fn match_world(players: [&str; 2], seed: Option<u64>) -> World {
+    // Matches run both bots on every simulation tick
+    let mut world = World::with_config(WorldConfig {
+        script_tick_interval: 1,
+        seed,
+        ..WorldConfig::default()
+    });
+    let config = world.config().default_zone_config.clone();
+    let linked = players.iter()
+        .map(|player| World::generate_grid_zone(&World::player_zone_id(player), &config, true))
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(|zones| world.add_zone_grid(zones, true));
+    if let Err(e) = linked {
+        log::warn!("Match zones of {} and {} are not linked: {}", players[0], players[1], e);
+    }
+    for player in players {
+        world.spawn_player(player).expect("A new world has room for two zones");
+    }
+    world
+}
+
+/// Play a match in `world`, where both players already have their units
+fn play_match(
+    mut world: World,
+    player_a: (&str, &ScriptBundle),
+    player_b: (&str, &ScriptBundle),
+    max_ticks: u64,
+    mut on_tick: impl FnMut(&str, &PlayerStats),
+) -> (TournamentMatch, ReplayHistory) {
+    // Room for the first and last snapshots besides the evenly spaced ones
+    let replay_interval = max_ticks.div_ceil(MAX_REPLAY_SNAPSHOTS as u64 - 2).max(1);
+    let mut replay = ReplayHistory::new();
+    let mut capture = |world: &World| match WorldSnapshot::capture(world) {
+        Ok(snapshot) => replay.record(snapshot),
+        Err(e) => log::warn!("Could not capture tournament replay: {}", e),
+    };
+    capture(&world);
+
+    let players = [player_a, player_b];
+    let runtimes = players.map(|(_, bundle)| create_runtime(bundle.language(), ScriptLimits::default()));
+    let mut survived = [0u64; 2];
+    let mut errors: [Option<String>; 2] = [None, None];
+    let mut eliminated = [false; 2];
+    // Commands are applied in player ID order, whichever player is A
+    let mut order = [0, 1];
+    order.sort_by_key(|&i| players[i].0);
+
+    let mut ticks = 0;
+    while ticks < max_ticks {
+        // Both bots see the world as it was before either one acts
+        let mut commands = [Vec::new(), Vec::new()];
+        for (i, (name, bundle)) in players.iter().enumerate() {
+            if errors[i].is_some() {
+                continue;
+            }
+
+            let result = runtimes[i].execute_tick(bundle, &world.player_snapshot(name));
+            match result.error {
+                Some(error) => errors[i] = Some(error),
+                None => {
+                    survived[i] += 1;
+                    commands[i] = result.commands;
+                }
+            }
+        }
+        for i in order {
+            world.apply_commands(players[i].0, &commands[i]);
+        }
+
+        world.advance_tick();
+        ticks += 1;
+        eliminated = players.map(|(name, _)| world.is_defeated(name));
+
+        for (i, (name, _)) in players.iter().enumerate() {
+            on_tick(name, &PlayerStats {
+                highest_tick: survived[i],
+                enemies_eliminated: u32::from(eliminated[1 - i] || errors[1 - i].is_some()),
+                resources_collected: world.stockpile(name).values().sum(),
+                matches_won: 0,
+                defeats: world.player_record(name).map_or(0, |record| record.defeats),
+            });
+        }
+
+        // Once a player or their bot is out the result cannot change
+        let over = ticks == max_ticks || eliminated.contains(&true) || errors.iter().any(Option::is_some);
+        if over || ticks.is_multiple_of(replay_interval) {
+            capture(&world);
+        }
+        if over {
+            break;
+        }
+    }
+
+    let scores = players.map(|(name, _)| match_score(&world, name));
+    let winner = match (eliminated, survived[0].cmp(&survived[1]), scores[0].cmp(&scores[1])) {
+        ([false, true], _, _) => Some(player_a.0),
+        ([true, false], _, _) => Some(player_b.0),
+        ([true, true], _, _) => None,
+        (_, std::cmp::Ordering::Greater, _) => Some(player_a.0),
+        (_, std::cmp::Ordering::Less, _) => Some(player_b.0),
+        (_, _, std::cmp::Ordering::Greater) => Some(player_a.0),
+        (_, _, std::cmp::Ordering::Less) => Some(player_b.0),
+        _ => None,
+    };
+
+    let [error_a, error_b] = errors;
+    let result = TournamentMatch {
+        player_a: player_a.0.to_string(),
+        player_b: player_b.0.to_string(),
+        winner: winner.map(str::to_string),
+        survived_a: survived[0],
+        survived_b: survived[1],
+        score_a: scores[0],
+        score_b: scores[1],
+        ticks,
+        error_a,
+        error_b,
+        recorded: false,
+        round: 0,
+    };
+    (result, replay)
+}
+
+/// Score of a player in a match world: the resources in their stockpile, plus
+/// [`BUILDING_POINTS`] per building they own
+pub fn match_score(world: &World, player_id: &str) -> u64 {
+    let buildings: usize = world.get_zone_ids().iter()
+        .filter_map(|zone_id| world.with_zone_read(zone_id, |zone| {
+            zone.entities.iter()
+                .filter(|entity| entity.is_structure() && entity.owner.as_deref() == Some(player_id))
+                .count()
+        }))
+        .sum();
+    let resources: u64 = world.stockpile(player_id).values().map(|&amount| u64::from(amount)).sum();
+    resources + BUILDING_POINTS * buildings as u64
+}
+
+/// Tracks tournaments
+pub struct TournamentManager {
+    runs: HashMap<String, TournamentRun>,
+    /// Replays by tournament and index in its matches
+    replays: HashMap<(String, usize), ReplayHistory>,
+    max_ticks: u64,
+}
+
+impl TournamentManager {
+    /// Create a manager whose matches last `max_ticks` ticks
+    pub fn new(max_ticks: u64) -> Self {
+        Self {
+            runs: HashMap::new(),
+            replays: HashMap::new(),
+            max_ticks,
+        }
+    }
+
+    /// Ticks per match for new tournaments
+    pub fn max_ticks(&self) -> u64 {
+        self.max_ticks
+    }
+
+    /// Set the ticks per match for new tournaments
+    pub fn set_max_ticks(&mut self, max_ticks: u64) {
+        self.max_ticks = max_ticks;
+    }
+
+    /// Create a running tournament pairing the given players once, with the default
+    /// match length
+    pub fn create(&mut self, players: Vec<String>) -> Result<TournamentRun, String> {
+        let settings = MatchSettings { max_ticks: self.max_ticks, seed: None };
+        self.create_with(players, TournamentFormat::Pairs, settings)
+    }
+
+    /// Create a running tournament; its rounds are started with [`Self::next_round`]
+    pub fn create_with(&mut self, players: Vec<String>, format: TournamentFormat, settings: MatchSettings) -> Result<TournamentRun, String> {
+        if players.len() < 2 {
+            return Err("At least 2 players with submitted code are required".to_string());
+        }
+        if let Some(player) = players.iter().enumerate().find_map(|(i, player)| players[..i].contains(player).then_some(player)) {
+            return Err(format!("Player {} is listed twice", player));
+        }
+        if settings.max_ticks == 0 || settings.max_ticks > MAX_MATCH_TICKS {
+            return Err(format!("Matches must last between 1 and {} ticks", MAX_MATCH_TICKS));
+        }
+
+        let count = players.len();
+        let expected_matches = match format {
+            TournamentFormat::Pairs => count / 2,
+            TournamentFormat::RoundRobin => count * (count - 1) / 2,
+            TournamentFormat::SingleElimination => count - 1,
+        };
+        let run = TournamentRun {
+            id: uuid::Uuid::new_v4().to_string(),
+            status: TournamentStatus::Running,
+            format,
+            remaining: if format == TournamentFormat::SingleElimination { players.clone() } else { Vec::new() },
+            players,
+            round: 0,
+            pending: Vec::new(),
+            bye: None,
+            matches: Vec::new(),
+            expected_matches,
+            max_ticks: settings.max_ticks,
+            seed: settings.seed,
+            started_at: chrono::Utc::now().timestamp(),
+            finished_at: None,
+        };
+        self.runs.insert(run.id.clone(), run.clone());
+        Ok(run)
+    }
+
+    /// Get a tournament by ID
+    pub fn get(&self, tournament_id: &str) -> Option<&TournamentRun> {
+        self.runs.get(tournament_id)
+    }
+
+    /// Start the next round of a tournament and return its pairings
+    ///
+    /// Called once every match of the current round is reported. Returns `None`, and
+    /// completes the tournament, when there is no round left.
+    pub fn next_round(&mut self, tournament_id: &str) -> Option<Vec<(String, String)>> {
+        let run = self.runs.get_mut(tournament_id)?;
+        if run.status == TournamentStatus::Completed {
+            return None;
+        }
+
+        let next = match run.format {
+            TournamentFormat::Pairs => (run.round == 0).then(|| pair_players(&run.players)),
+            TournamentFormat::RoundRobin => round_robin_rounds(&run.players).into_iter().nth(run.round as usize),
+            TournamentFormat::SingleElimination => {
+                if run.round > 0 {
+                    run.remaining = run.advancing();
+                }
+                (run.remaining.len() > 1).then(|| pair_players(&run.remaining))
+            }
+        };
+
+        match next {
+            Some((pairs, bye)) => {
+                run.round += 1;
+                run.pending = pairs.clone();
+                run.bye = bye;
+                Some(pairs)
+            }
+            None => {
+                run.status = TournamentStatus::Completed;
+                run.pending.clear();
+                run.bye = None;
+                run.finished_at = Some(chrono::Utc::now().timestamp());
+                None
+            }
+        }
+    }
+
+    /// Store a finished match of the current round and its replay
+    pub fn report_match(&mut self, tournament_id: &str, mut result: TournamentMatch, replay: ReplayHistory) {
+        let Some(run) = self.runs.get_mut(tournament_id) else {
+            return;
+        };
+
+        run.pending.retain(|(a, b)| !(*a == result.player_a && *b == result.player_b));
+        result.round = run.round;
+        run.matches.push(result);
+        if replay.range().is_some() {
+            self.replays.insert((tournament_id.to_string(), run.matches.len() - 1), replay);
+        }
+    }
+
+    /// Replay of a tournament match, by its index in the tournament's matches
+    pub fn replay(&self, tournament_id: &str, match_index: usize) -> Option<&ReplayHistory> {
+        self.replays.get(&(tournament_id.to_string(), match_index))
+    }
+}
+
+impl Default for TournamentManager {
+    fn default() -> Self {
+        Self::new(crate::config::TOURNAMENT_MAX_TICKS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::world::ATTACK_DAMAGE;
+    use crate::game::zone::DEFAULT_ENTITY_HITS;
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_pair_players_with_bye() {
+        let (pairs, bye) = pair_players(&names(&["a", "b", "c"]));
+        assert_eq!(pairs, vec![("a".to_string(), "b".to_string())]);
+        assert_eq!(bye.as_deref(), Some("c"));
+    }
+
+    #[test]
+    fn test_round_robin_meets_everyone_once() {
+        let players = names(&["a", "b", "c", "d", "e"]);
+        let rounds = round_robin_rounds(&players);
+        assert_eq!(rounds.len(), 5);
+
+        let mut met = std::collections::HashSet::new();
+        for (pairs, bye) in &rounds {
+            assert_eq!(pairs.len(), 2);
+            let mut seated: Vec<&String> = pairs.iter().flat_map(|(a, b)| [a, b]).chain(bye).collect();
+            seated.sort();
+            assert_eq!(seated, players.iter().collect::<Vec<_>>());
+            for (a, b) in pairs {
+                assert!(met.insert((a.min(b).clone(), a.max(b).clone())), "{} and {} met twice", a, b);
+            }
+        }
+        assert_eq!(met.len(), 10);
+    }
+
+    #[test]
+    fn test_single_elimination_bracket() {
+        let mut manager = TournamentManager::new(10);
+        let settings = MatchSettings { max_ticks: 10, seed: None };
+        let run = manager.create_with(names(&["a", "b", "c"]), TournamentFormat::SingleElimination, settings).unwrap();
+        assert_eq!(run.expected_matches, 2);
+        let result = |a: &str, b: &str, winner: Option<&str>| TournamentMatch {
+            winner: winner.map(str::to_string),
+            ..TournamentMatch::aborted(a, b, "")
+        };
+
+        assert_eq!(manager.next_round(&run.id), Some(vec![("a".to_string(), "b".to_string())]));
+        manager.report_match(&run.id, result("a", "b", Some("b")), ReplayHistory::new());
+
+        // The bye plays the winner; a draw sends the first player of the pairing on
+        assert_eq!(manager.next_round(&run.id), Some(vec![("c".to_string(), "b".to_string())]));
+        manager.report_match(&run.id, result("c", "b", None), ReplayHistory::new());
+        assert_eq!(manager.next_round(&run.id), None);
+
+        let run = manager.get(&run.id).unwrap();
+        assert_eq!(run.status, TournamentStatus::Completed);
+        assert_eq!(run.remaining, names(&["c"]));
+        assert_eq!(run.matches.iter().map(|m| m.round).collect::<Vec<_>>(), vec![1, 2]);
+        let standings = run.standings();
+        assert_eq!(standings[0].player, "b");
+        assert_eq!((standings[0].wins, standings[0].draws, standings[0].points), (1, 1, WIN_POINTS + DRAW_POINTS));
+    }
+
+    #[test]
+    fn test_crashing_bot_loses() {
+        let steady = ScriptBundle::single("game.buildStructure('turret', {x: 1, y: 1});".to_string()).unwrap();
+        let crashing = ScriptBundle::single("if (game.tick >= 2) { throw new Error('oops'); }".to_string()).unwrap();
+
+        let result = run_match(("steady", &steady), ("crashing", &crashing), 10);
+        assert_eq!(result.winner.as_deref(), Some("steady"));
+        assert_eq!(result.survived_b, 2);
+        assert!(result.error_b.as_deref().unwrap_or_default().contains("oops"));
+        assert_eq!(result.outcome(), MatchOutcome::PlayerAWins);
+    }
+
+    #[test]
+    fn test_issuing_commands_does_not_win_a_match() {
+        let busy = ScriptBundle::single("for (const unit of game.getMyUnits()) { unit.moveTo({x: 0, y: 0}); }".to_string()).unwrap();
+        let idle = ScriptBundle::single("// idle".to_string()).unwrap();
+
+        let result = run_match(("busy", &busy), ("idle", &idle), 5);
+        assert_eq!(result.winner, None);
+        // Both still own the base they started with
+        assert_eq!((result.score_a, result.score_b), (BUILDING_POINTS, BUILDING_POINTS));
+    }
+
+    #[test]
+    fn test_bot_destroying_the_other_player_wins() {
+        let hunter = ScriptBundle::single("\
+            const target = game.getEnemyUnits()[0];\n\
+            if (target) { for (const unit of game.getMyUnits()) { unit.attack(target); } }".to_string()).unwrap();
+        let prey = ScriptBundle::single("// idle".to_string()).unwrap();
+
+        // The prey's only unit stands next to the hunter's worker
+        let world = match_world(["hunter", "prey"], Some(1));
+        assert!(!world.portals().is_empty(), "The players' zones are linked");
+        let mut prey_worker = world.with_zone_read(&World::player_zone_id("prey"), |zone| {
+            zone.entities.iter().find(|entity| entity.kind == "worker").unwrap().clone()
+        }).unwrap();
+        world.with_zone_write(&World::player_zone_id("prey"), |zone| zone.entities.clear());
+        world.with_zone_write(&World::player_zone_id("hunter"), |zone| {
+            let worker = zone.entities.iter().find(|entity| entity.kind == "worker").unwrap();
+            let (x, y) = [(1, 0), (0, 1), (1, 1), (-1, 0), (0, -1), (-1, -1), (1, -1), (-1, 1)].into_iter()
+                .map(|(dx, dy): (isize, isize)| (worker.x.wrapping_add_signed(dx), worker.y.wrapping_add_signed(dy)))
+                .find(|&(x, y)| zone.get_tile(x, y).is_some() && !zone.entities.iter().any(|entity| (entity.x, entity.y) == (x, y)))
+                .unwrap();
+            prey_worker.id = zone.entities.iter().map(|entity| entity.id).max().unwrap() + 1;
+            (prey_worker.x, prey_worker.y) = (x, y);
+            zone.entities.push(prey_worker);
+        });
+
+        let (result, _) = play_match(world, ("hunter", &hunter), ("prey", &prey), 100, |_, _| {});
+        assert_eq!(result.winner.as_deref(), Some("hunter"));
+        // One attack a tick brings the worker down
+        assert_eq!(result.ticks, u64::from(DEFAULT_ENTITY_HITS / ATTACK_DAMAGE));
+        assert_eq!((&result.error_a, &result.error_b), (&None, &None));
+        assert_eq!(result.outcome(), MatchOutcome::PlayerAWins);
+    }
+
+    #[test]
+    fn test_identical_bots_draw() {
+        let bot = ScriptBundle::single("// idle".to_string()).unwrap();
+        let result = run_match(("a", &bot), ("b", &bot), 5);
+        assert_eq!(result.winner, None);
+        assert_eq!(result.ticks, 5);
+        assert_eq!(result.outcome(), MatchOutcome::Draw);
+    }
+}
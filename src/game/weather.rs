@@ -0,0 +1,118 @@
+//! Weather module
+//!
+//! Temporary weather events affecting a single zone. Rain makes swamps costlier to
+//! cross, fog shortens how far entities see, and storms damage entities standing on
+//! outdoor tiles (tiles with no obstacle next to them to shelter behind).
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::rng::WorldRng;
+use crate::game::zone::{SurfaceType, Zone};
+
+/// Damage dealt by a storm to an entity it strikes
+pub const STORM_DAMAGE: u32 = 10;
+
+/// Kind of weather event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WeatherEventType {
+    /// Swamp movement costs increase by `magnitude` (0.5 = +50%)
+    Rain,
+    /// Visibility radius decreases by `magnitude` (0.5 = halved, capped at 1.0)
+    Fog,
+    /// Each tick, entities on outdoor tiles are struck with probability `magnitude`
+    Storm,
+}
+
+/// A weather event scheduled on a zone
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeatherEvent {
+    /// Kind of event
+    pub event_type: WeatherEventType,
+    /// Zone the event applies to
+    pub affected_zone_id: String,
+    /// First tick of the event
+    pub start_tick: u64,
+    /// Number of ticks the event lasts
+    pub duration_ticks: u64,
+    /// Strength of the event (see [`WeatherEventType`])
+    pub magnitude: f32,
+}
+
+impl WeatherEvent {
+    /// First tick after the event
+    pub fn end_tick(&self) -> u64 {
+        self.start_tick.saturating_add(self.duration_ticks)
+    }
+
+    /// Whether the event is in effect at a tick
+    pub fn is_active(&self, tick: u64) -> bool {
+        self.start_tick <= tick && tick < self.end_tick()
+    }
+
+    /// Check the event before scheduling it
+    pub fn validate(&self) -> Result<(), String> {
+        if self.duration_ticks == 0 {
+            return Err("Weather events must last at least one tick".to_string());
+        }
+        if !self.magnitude.is_finite() || self.magnitude < 0.0 {
+            return Err(format!("Invalid weather magnitude: {}", self.magnitude));
+        }
+        Ok(())
+    }
+
+    /// Movement cost of a surface under this event
+    pub fn apply_movement_cost(&self, surface: SurfaceType, cost: u32) -> u32 {
+        match (self.event_type, surface) {
+            (WeatherEventType::Rain, SurfaceType::Swamp) => {
+                cost + (cost as f32 * self.magnitude).round() as u32
+            }
+            _ => cost,
+        }
+    }
+
+    /// Visibility radius under this event (never below 1 tile)
+    pub fn apply_visibility(&self, radius: u32) -> u32 {
+        match self.event_type {
+            WeatherEventType::Fog => {
+                let factor = 1.0 - self.magnitude.min(1.0);
+                ((radius as f32 * factor).round() as u32).max(1)
+            }
+            _ => radius,
+        }
+    }
+
+    /// Strike entities on outdoor tiles of the zone; destroyed entities are removed
+    ///
+    /// One roll is drawn from the world's generator per entity, in the zone's entity
+    /// order, so worlds with the same seed deal the same damage.
+    pub fn apply_storm(&self, zone: &mut Zone, rng: &mut WorldRng) {
+        if self.event_type != WeatherEventType::Storm {
+            return;
+        }
+
+        let chance = self.magnitude.min(1.0) as f64;
+        let outdoor: Vec<bool> = zone.entities.iter()
+            .map(|entity| is_outdoor(zone, entity.x, entity.y))
+            .collect();
+
+        for (entity, outdoor) in zone.entities.iter_mut().zip(outdoor) {
+            if rng.next_f64() < chance && outdoor {
+                entity.hits = entity.hits.saturating_sub(STORM_DAMAGE);
+            }
+        }
+        zone.entities.retain(|entity| entity.hits > 0);
+    }
+}
+
+/// Whether a tile has no obstacle next to it (diagonals excluded)
+fn is_outdoor(zone: &Zone, x: usize, y: usize) -> bool {
+    let neighbours = [
+        (x.wrapping_sub(1), y),
+        (x + 1, y),
+        (x, y.wrapping_sub(1)),
+        (x, y + 1),
+    ];
+    !neighbours.iter().any(|&(nx, ny)| {
+        zone.get_tile(nx, ny).is_some_and(|tile| tile.surface_type == SurfaceType::Obstacle)
+    })
+}
@@ -1,62 +1,2563 @@
 //! World module
 //! 
-//! Manages the game world state, including zones and tick counter.
+//! Manages the game world state, including zones, portals, weather, and tick counter.
 
-use std::collections::HashMap;
-use crate::game::zone::Zone;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::game::clock::{DayPhase, WorldClock};
+use crate::game::event_log::{ActionLog, GameAction};
+use crate::game::events::{EventLog, GameEvent, GameEventKind};
+use crate::game::history::{GameHistory, HistoryError};
+use crate::game::market::{Ledger, Market, MarketOrder, OrderSide, Trade};
+use crate::game::pathfinding::find_path;
+use crate::game::replay::{ReplayHistory, WorldSnapshot};
+use crate::game::rng::WorldRng;
+use crate::game::scenario::ObjectiveProgress;
+use crate::game::snapshot_cache::{CacheBuilds, EntityFragment, SnapshotCache};
+use crate::game::stats::{StatsBatch, ZoneCensus};
+use crate::game::store::WorldStore;
+use crate::game::tech::{self, PlayerTech, Research, Stat, TechStatus};
+use crate::game::weather::WeatherEvent;
+use crate::game::zone::edit::{RejectedEdit, ZoneEdit};
+use crate::game::zone::export::ZoneExport;
+use crate::game::zone::template::{self, MapTemplate};
+use crate::game::zone::{EntityRef, ExitDirection, Mobility, ResourceType, SurfaceType, Zone, ZoneGenConfig, DEFAULT_ENTITY_HITS, ZONE_SIZE};
+use crate::game::zone_map::{ZoneMap, ZoneMut, ZoneReader};
+use crate::scripting::commands::BotCommand;
+
+/// A one-way link from a tile of one zone to a tile of another (possibly non-adjacent) zone
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Portal {
+    /// Unique portal identifier
+    pub id: Uuid,
+    /// Zone containing the portal tile
+    pub from_zone_id: String,
+    /// X coordinate of the portal tile
+    pub from_x: usize,
+    /// Y coordinate of the portal tile
+    pub from_y: usize,
+    /// Destination zone
+    pub to_zone_id: String,
+    /// X coordinate of the destination tile
+    pub to_x: usize,
+    /// Y coordinate of the destination tile
+    pub to_y: usize,
+}
+
+/// World dimensions and limits
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorldConfig {
+    /// Width of the world, in zones
+    pub width: u32,
+    /// Height of the world, in zones
+    pub height: u32,
+    /// Maximum number of zones
+    pub max_zones: usize,
+    /// Configuration used to generate player zones
+    pub default_zone_config: ZoneGenConfig,
+    /// Resources awarded to a player when they capture a zone
+    #[serde(default = "default_capture_reward")]
+    pub zone_capture_reward_resources: HashMap<ResourceType, u32>,
+    /// Directory holding map templates (not exposed to clients)
+    #[serde(skip, default = "template::default_maps_dir")]
+    pub maps_dir: PathBuf,
+    /// Where defeated players respawn
+    #[serde(default)]
+    pub respawn_mode: RespawnMode,
+    /// Ticks between a player's defeat and their respawn
+    #[serde(default = "default_respawn_cooldown")]
+    pub respawn_cooldown_ticks: u64,
+    /// Simulation ticks between two script executions
+    #[serde(default = "default_script_tick_interval")]
+    pub script_tick_interval: u64,
+    /// Simulation ticks between two rounds of market order matching
+    #[serde(default = "default_market_match_interval")]
+    pub market_match_interval_ticks: u64,
+    /// Simulation ticks a market order stays open before expiring
+    #[serde(default = "default_market_order_ttl")]
+    pub market_order_ttl_ticks: u64,
+    /// Seed of the world's random number generator; set, the world is deterministic
+    /// (not exposed to clients)
+    #[serde(skip)]
+    pub seed: Option<u64>,
+    /// Whether players only see what is within the visibility radius of their entities;
+    /// off, they see their entities' whole zones
+    #[serde(default = "default_fog_of_war")]
+    pub fog_of_war: bool,
+    /// Past simulation ticks kept in the world's history (0 keeps none)
+    #[serde(default = "default_history_retention")]
+    pub history_retention_ticks: u64,
+    /// Simulation ticks between two keyframes of the history
+    #[serde(default = "default_history_keyframe_interval")]
+    pub history_keyframe_interval: u64,
+}
+
+fn default_history_retention() -> u64 {
+    crate::game::history::DEFAULT_HISTORY_RETENTION_TICKS
+}
+
+fn default_history_keyframe_interval() -> u64 {
+    crate::game::history::DEFAULT_HISTORY_KEYFRAME_INTERVAL
+}
+
+fn default_fog_of_war() -> bool {
+    true
+}
+
+fn default_respawn_cooldown() -> u64 {
+    crate::config::RESPAWN_COOLDOWN_TICKS
+}
+
+fn default_script_tick_interval() -> u64 {
+    crate::config::SCRIPT_TICK_INTERVAL
+}
+
+fn default_market_match_interval() -> u64 {
+    crate::config::MARKET_MATCH_INTERVAL_TICKS
+}
+
+fn default_market_order_ttl() -> u64 {
+    crate::config::MARKET_ORDER_TTL_TICKS
+}
+
+/// Where a defeated player gets their new base
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RespawnMode {
+    /// Back in their home zone, after clearing enemy structures around the new base
+    #[default]
+    OriginalZone,
+    /// In a newly generated zone
+    NewZone,
+}
+
+/// Radius (in tiles) around a respawned base cleared of enemy structures
+pub const RESPAWN_CLEAR_RADIUS: usize = 3;
+
+/// Damage dealt by one `attack` command
+pub const ATTACK_DAMAGE: u32 = 10;
+
+/// Resources collected by one `harvest` command
+pub const HARVEST_AMOUNT: u32 = 10;
+
+/// Most events passed to a script in its snapshot
+pub const MAX_SCRIPT_EVENTS: usize = 100;
+
+/// Seeds tried for a zone of a connected grid before giving up (see [`World::generate_grid_zone`])
+pub const GRID_ZONE_ATTEMPTS: u64 = 64;
+
+/// Minerals needed to produce each kind of unit with `produceUnit`
+pub const UNIT_COSTS: &[(&str, u32)] = &[("worker", 50), ("soldier", 100)];
+
+/// Minerals needed to produce a unit of the given kind (see [`UNIT_COSTS`])
+pub fn unit_cost(kind: &str) -> Result<u32, String> {
+    UNIT_COSTS.iter()
+        .find(|(name, _)| *name == kind)
+        .map(|(_, cost)| *cost)
+        .ok_or_else(|| format!("Unknown unit type {:?}", kind))
+}
+
+/// FNV-1a hasher behind [`World::state_hash`], whose output never changes between
+/// Rust releases (unlike the standard library's hashers)
+struct StateHasher(u64);
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for StateHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    // Integers are written little-endian and lengths as 64 bits, whatever the platform
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Last [`Zone::version`] given out by a world
+///
+/// Atomic, so zones can be changed one at a time without `&mut` access to the world
+/// (see [`World::with_zone_write`]).
+#[derive(Debug, Default)]
+struct ZoneVersions(AtomicU64);
+
+impl ZoneVersions {
+    fn last(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Clone for ZoneVersions {
+    fn clone(&self) -> Self {
+        Self(AtomicU64::new(self.last()))
+    }
+}
+
+/// Give a changed zone the next version of the world's counter
+fn new_version(zone_version: &ZoneVersions, zone: &mut Zone) {
+    zone.version = zone_version.0.fetch_add(1, Ordering::Relaxed) + 1;
+}
+
+/// Bonus of a player's researched techs to a stat of one of their units
+fn tech_bonus(techs: &HashMap<String, PlayerTech>, player_id: &str, stat: Stat, kind: &str) -> u32 {
+    techs.get(player_id).map_or(0, |techs| tech::bonus(&techs.researched, stat, kind))
+}
+
+/// Something that happened during a tick, for the server to relay to clients
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum WorldEvent {
+    /// A player lost their last building and unit
+    PlayerDefeated {
+        /// Defeated player
+        player_id: String,
+        /// Tick of the defeat
+        tick: u64,
+        /// Tick at which the player will respawn
+        respawn_tick: u64,
+    },
+    /// A defeated player got a new base and worker
+    PlayerRespawned {
+        /// Respawned player
+        player_id: String,
+        /// Zone of the new base
+        zone_id: String,
+        /// Tick of the respawn
+        tick: u64,
+    },
+    /// An attack destroyed a unit or building
+    UnitDestroyed {
+        /// Zone of the destroyed entity
+        zone_id: String,
+        /// Entity ID within the zone
+        unit_id: u32,
+        /// Entity kind
+        kind: String,
+        /// Owner of the destroyed entity (if any)
+        owner: Option<String>,
+        /// Player whose unit dealt the last blow
+        attacker: String,
+        /// Tick of the destruction
+        tick: u64,
+    },
+    /// A player's unit harvested a resource deposit
+    ResourceCollected {
+        /// Harvesting player
+        player_id: String,
+        /// Zone of the deposit
+        zone_id: String,
+        /// Resource collected
+        resource: ResourceType,
+        /// Amount collected
+        amount: u32,
+        /// Tick of the harvest
+        tick: u64,
+    },
+    /// Two market orders were (partially) filled against each other
+    TradeExecuted {
+        /// The fill
+        #[serde(flatten)]
+        trade: Trade,
+        /// Tick of the trade
+        tick: u64,
+    },
+}
+
+/// Defeat tracking for a player who has owned entities
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerRecord {
+    /// Zone the player respawns in (with [`RespawnMode::OriginalZone`])
+    pub home_zone: Option<String>,
+    /// Tick of the current defeat (`None` while the player is in play)
+    pub defeated_at: Option<u64>,
+    /// Number of times the player was defeated
+    pub defeats: u32,
+}
+
+/// A zone as shown on a player's map
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZoneSummary {
+    /// Zone identifier
+    pub zone_id: String,
+    /// Position on the world grid (if placed)
+    pub position: Option<(u32, u32)>,
+    /// Player owning the zone (if any)
+    pub owner: Option<String>,
+    /// Number of units in the zone (only for zones of the player and their allies)
+    pub units: Option<usize>,
+    /// Number of structures in the zone (only for zones of the player and their allies)
+    pub structures: Option<usize>,
+}
+
+fn default_capture_reward() -> HashMap<ResourceType, u32> {
+    HashMap::from([
+        (ResourceType::Minerals, crate::config::ZONE_CAPTURE_REWARD_MINERALS),
+        (ResourceType::Gas, crate::config::ZONE_CAPTURE_REWARD_GAS),
+    ])
+}
+
+/// Reason a zone could not be captured
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaptureError {
+    /// The zone does not exist
+    ZoneNotFound(String),
+    /// The player has no entity in the zone
+    InsufficientPresence,
+    /// Another player has more entities in the zone
+    Contested,
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::ZoneNotFound(zone_id) => write!(f, "Zone {} not found", zone_id),
+            CaptureError::InsufficientPresence => write!(f, "Player has no entity in the zone"),
+            CaptureError::Contested => write!(f, "Another player has more entities in the zone"),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+/// Reason a batch of zone edits was not applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditZoneError {
+    /// The zone does not exist
+    ZoneNotFound(String),
+    /// Edits refused, with the reason (none of the batch was applied)
+    Rejected(Vec<RejectedEdit>),
+}
+
+impl fmt::Display for EditZoneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditZoneError::ZoneNotFound(zone_id) => write!(f, "Zone {} not found", zone_id),
+            EditZoneError::Rejected(rejected) => write!(f, "{} edits rejected", rejected.len()),
+        }
+    }
+}
+
+impl std::error::Error for EditZoneError {}
+
+impl WorldConfig {
+    /// Read `GEEKCRAFT_WORLD_WIDTH`, `GEEKCRAFT_WORLD_HEIGHT`, `GEEKCRAFT_MAX_ZONES`, `GEEKCRAFT_MAPS_DIR`,
+    /// `GEEKCRAFT_RESPAWN_MODE` (`original_zone` or `new_zone`), `GEEKCRAFT_RESPAWN_COOLDOWN_TICKS`,
+    /// `GEEKCRAFT_SCRIPT_TICK_INTERVAL`, `GEEKCRAFT_MARKET_MATCH_INTERVAL_TICKS`,
+    /// `GEEKCRAFT_MARKET_ORDER_TTL_TICKS`, and `GEEKCRAFT_ZONE_CAPTURE_REWARD_MINERALS`/`_GAS`
+    ///
+    /// Missing or invalid values fall back to the defaults (see [`ServerConfig::from_env`]).
+    ///
+    /// [`ServerConfig::from_env`]: crate::config::ServerConfig::from_env
+    pub fn from_env() -> Self {
+        crate::config::ServerConfig::from_env().world_config()
+    }
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            width: crate::config::WORLD_WIDTH,
+            height: crate::config::WORLD_HEIGHT,
+            max_zones: crate::config::MAX_ZONES,
+            default_zone_config: ZoneGenConfig::default(),
+            zone_capture_reward_resources: default_capture_reward(),
+            maps_dir: template::default_maps_dir(),
+            respawn_mode: RespawnMode::default(),
+            respawn_cooldown_ticks: crate::config::RESPAWN_COOLDOWN_TICKS,
+            script_tick_interval: crate::config::SCRIPT_TICK_INTERVAL,
+            market_match_interval_ticks: crate::config::MARKET_MATCH_INTERVAL_TICKS,
+            market_order_ttl_ticks: crate::config::MARKET_ORDER_TTL_TICKS,
+            seed: None,
+            fog_of_war: true,
+            history_retention_ticks: default_history_retention(),
+            history_keyframe_interval: default_history_keyframe_interval(),
+        }
+    }
+}
+
+/// A multi-tile move being walked, one tile per simulation tick
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PendingMove {
+    zone_id: String,
+    entity_id: u32,
+    /// Tiles still to enter, in order
+    path: VecDeque<(usize, usize)>,
+}
 
 /// Game world containing zones and game state
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct World {
+    /// Simulation tick
     tick: u64,
-    /// Map of zone_id to Zone for multi-zone world support
-    zones: HashMap<String, Zone>,
+    /// Script tick (scripts run every `script_tick_interval` simulation ticks)
+    #[serde(default)]
+    script_tick: u64,
+    /// Dimensions and limits
+    #[serde(default)]
+    config: WorldConfig,
+    /// Zones by ID, each behind its own lock (see [`crate::game::zone_map`])
+    zones: ZoneMap,
+    /// Last [`Zone::version`] given out; versions are never reused, even by a new zone
+    /// with the ID of a removed one
+    #[serde(skip)]
+    zone_version: ZoneVersions,
+    /// Position of placed zones on the world grid
+    #[serde(default)]
+    zone_positions: HashMap<String, (u32, u32)>,
+    /// Portals between zones
+    #[serde(default)]
+    portals: Vec<Portal>,
+    /// Day/night cycle
+    #[serde(default)]
+    world_clock: WorldClock,
+    /// Scheduled and active weather events
+    #[serde(default)]
+    weather: Vec<WeatherEvent>,
+    /// Resources held by each player outside a team
+    #[serde(default)]
+    stockpiles: HashMap<String, HashMap<ResourceType, u32>>,
+    /// Members of each team (player IDs)
+    #[serde(default)]
+    teams: HashMap<Uuid, Vec<String>>,
+    /// Resources shared by the members of each team
+    #[serde(default)]
+    team_pools: HashMap<Uuid, HashMap<ResourceType, u32>>,
+    /// Members of each alliance (player IDs)
+    #[serde(default)]
+    alliances: HashMap<Uuid, Vec<String>>,
+    /// Alliance memberships that drop a member, applied at the end of the current tick
+    #[serde(default)]
+    pending_alliances: HashMap<Uuid, Vec<String>>,
+    /// Open market orders
+    #[serde(default)]
+    market: Market,
+    /// Zone assigned to each user (by user ID)
+    #[serde(default)]
+    zone_assignments: HashMap<i64, String>,
+    /// Zones each zone requires a player to have cleared before it is unlocked for them
+    #[serde(default)]
+    zone_unlock_requirements: HashMap<String, Vec<String>>,
+    /// Zones each player has cleared (see [`World::mark_zone_cleared`])
+    #[serde(default)]
+    cleared_zones: HashMap<String, HashSet<String>>,
+    /// Defeat tracking of every player who has owned entities
+    #[serde(default)]
+    players: HashMap<String, PlayerRecord>,
+    /// Researched techs and research under way of each player
+    #[serde(default)]
+    techs: HashMap<String, PlayerTech>,
+    /// Events of past ticks not yet taken by [`World::drain_events`]
+    #[serde(skip)]
+    events: Vec<WorldEvent>,
+    /// Moves issued by scripts that are still being walked
+    #[serde(skip)]
+    pending_moves: Vec<PendingMove>,
+    /// One-tick actions (`attack`, `harvest`) issued by scripts, resolved on the next tick
+    #[serde(skip)]
+    pending_actions: Vec<(String, BotCommand)>,
+    /// Commands of each player rejected on their last script tick, shown in their next snapshot
+    #[serde(skip)]
+    command_errors: HashMap<String, Vec<String>>,
+    /// Commands of each player refused by [`World::authorize`], since the world was loaded
+    #[serde(skip)]
+    command_violations: HashMap<String, u64>,
+    /// Scenario objectives of each player of a campaign run, shown in their snapshot
+    #[serde(skip)]
+    objectives: HashMap<String, Vec<ObjectiveProgress>>,
+    /// Recent events of every zone
+    #[serde(skip)]
+    event_log: EventLog,
+    /// Snapshots of recent script ticks
+    #[serde(skip)]
+    replay: ReplayHistory,
+    /// Keyframes and actions of recent simulation ticks
+    #[serde(skip)]
+    history: GameHistory,
+    /// Where zones and portals are written through to (none for a transient world)
+    #[serde(skip)]
+    store: Option<Arc<dyn WorldStore>>,
+    /// Moves, harvests and captures, recorded as they happen (none if not recording)
+    #[serde(skip)]
+    action_log: Option<ActionLog>,
+    /// Source of every random decision of the simulation
+    #[serde(skip)]
+    rng: WorldRng,
+    /// Fragments player snapshots are assembled from
+    #[serde(skip)]
+    snapshot_cache: SnapshotCache,
+}
+
+/// Players counted together for zone capture: a team, or a player without one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Side<'a> {
+    Team(Uuid),
+    Player(&'a str),
 }
 
 impl World {
-    /// Create a new game world
+    /// Create a new game world with the default configuration
     pub fn new() -> Self {
+        Self::with_config(WorldConfig::default())
+    }
+
+    /// Create a new game world with the default configuration and a seeded generator
+    ///
+    /// Two worlds created with the same seed and given the same commands go through the
+    /// same states (see [`World::state_hash`]).
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_config(WorldConfig { seed: Some(seed), ..WorldConfig::default() })
+    }
+
+    /// Create a new game world
+    ///
+    /// Its generator is seeded with `config.seed`, or from the system's entropy without one.
+    pub fn with_config(config: WorldConfig) -> Self {
+        let rng = config.seed.map_or_else(WorldRng::from_entropy, WorldRng::new);
+        let history = GameHistory::new(config.history_retention_ticks, config.history_keyframe_interval);
         World {
             tick: 0,
-            zones: HashMap::new(),
+            script_tick: 0,
+            config,
+            zones: ZoneMap::new(),
+            zone_version: ZoneVersions::default(),
+            zone_positions: HashMap::new(),
+            portals: Vec::new(),
+            world_clock: WorldClock::default(),
+            weather: Vec::new(),
+            stockpiles: HashMap::new(),
+            teams: HashMap::new(),
+            team_pools: HashMap::new(),
+            alliances: HashMap::new(),
+            pending_alliances: HashMap::new(),
+            market: Market::new(),
+            zone_assignments: HashMap::new(),
+            zone_unlock_requirements: HashMap::new(),
+            cleared_zones: HashMap::new(),
+            players: HashMap::new(),
+            techs: HashMap::new(),
+            events: Vec::new(),
+            pending_moves: Vec::new(),
+            pending_actions: Vec::new(),
+            command_errors: HashMap::new(),
+            command_violations: HashMap::new(),
+            objectives: HashMap::new(),
+            event_log: EventLog::new(),
+            replay: ReplayHistory::new(),
+            history,
+            store: None,
+            action_log: None,
+            rng,
+            snapshot_cache: SnapshotCache::new(),
+        }
+    }
+
+    /// Create a world backed by a store, loading the zones and portals it holds
+    ///
+    /// Zones and portals added afterwards are written through to the store. Stored
+    /// portals whose zones are missing are dropped with a warning.
+    pub fn open(config: WorldConfig, store: Arc<dyn WorldStore>) -> Result<Self, String> {
+        let mut world = Self::with_config(config);
+        for zone in store.load_all_zones()? {
+            world.zones.insert(zone);
+        }
+        for portal in store.load_all_portals()? {
+            if world.zones.contains_key(&portal.from_zone_id) && world.zones.contains_key(&portal.to_zone_id) {
+                world.portals.push(portal);
+            } else {
+                log::warn!("Skipping stored portal {}: zone {} or {} is missing", portal.id, portal.from_zone_id, portal.to_zone_id);
+            }
+        }
+        world.zone_assignments = store.load_zone_assignments()?;
+        if world.zones.len() > world.config.max_zones {
+            log::warn!("Store holds {} zones, more than the {} allowed; no new zones can be added", world.zones.len(), world.config.max_zones);
         }
+
+        world.store = Some(store);
+        Ok(world)
+    }
+
+    /// Store the world writes through to (if any)
+    pub fn store(&self) -> Option<&Arc<dyn WorldStore>> {
+        self.store.as_ref()
+    }
+
+    /// Record moves, harvests and zone captures in an action log from now on
+    pub fn set_action_log(&mut self, log: ActionLog) {
+        self.action_log = Some(log);
+    }
+
+    /// Action log the world records to (if any)
+    pub fn action_log(&self) -> Option<&ActionLog> {
+        self.action_log.as_ref()
     }
 
-    /// Get the current game tick
+    /// Stop recording, returning the action log
+    pub fn take_action_log(&mut self) -> Option<ActionLog> {
+        self.action_log.take()
+    }
+
+    /// Write the buffered entries of the action log (if any) to its file
+    pub fn flush_action_log(&mut self) -> Result<(), String> {
+        self.action_log.as_mut().map_or(Ok(()), ActionLog::flush)
+    }
+
+    /// Append an action about to be carried out to the action log (if any)
+    fn log_action(&mut self, action: GameAction) -> Option<u64> {
+        let tick = self.tick;
+        self.history.stage(tick, &action);
+        self.action_log.as_mut().map(|log| log.append(tick, action))
+    }
+
+    /// Mark a logged action applied, writing it to the log file
+    ///
+    /// Failures are logged: the in-memory world stays authoritative.
+    fn commit_action(&mut self, entry_id: Option<u64>) {
+        self.history.commit();
+        if let (Some(log), Some(entry_id)) = (&mut self.action_log, entry_id) {
+            if let Err(e) = log.commit(entry_id) {
+                log::warn!("Failed to record action {}: {}", entry_id, e);
+            }
+        }
+    }
+
+    /// Carry out a logged action again (see [`ActionLog::replay_from`])
+    ///
+    /// The world's tick moves forward to `tick` if it is behind.
+    pub fn apply_action(&mut self, tick: u64, action: &GameAction) -> Result<(), String> {
+        self.tick = self.tick.max(tick);
+        match action {
+            GameAction::EntityMoved { zone_id, entity_id, x, y } => {
+                self.move_entity(zone_id, *entity_id, *x, *y).map(|_| ())
+            }
+            GameAction::ResourceHarvested { player_id, zone_id, x, y, resource, amount } => {
+                let mut zone = self.contents_mut(zone_id)
+                    .ok_or_else(|| format!("Zone {} not found", zone_id))?;
+                let index = zone.resources.iter()
+                    .position(|deposit| deposit.x == *x && deposit.y == *y && deposit.amount >= *amount)
+                    .ok_or_else(|| format!("No deposit of {} at ({}, {}) in zone {}", amount, x, y, zone_id))?;
+                zone.resources[index].amount -= amount;
+                if zone.resources[index].amount == 0 {
+                    zone.resources.remove(index);
+                }
+                drop(zone);
+                self.deposit_resources(player_id, *resource, *amount);
+                Ok(())
+            }
+            GameAction::ZoneCaptured { zone_id, player_id, rewards } => {
+                self.contents_mut(zone_id)
+                    .ok_or_else(|| format!("Zone {} not found", zone_id))?
+                    .owner = Some(player_id.clone());
+                for &(resource, amount) in rewards {
+                    self.deposit_resources(player_id, resource, amount);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Write a zone through to the store (after changing it with [`World::with_zone_write`])
+    ///
+    /// Failures are logged: the in-memory world stays authoritative.
+    pub fn persist_zone(&self, zone_id: &str) {
+        if let (Some(store), Some(zone)) = (&self.store, self.zones.get(zone_id)) {
+            if let Err(e) = store.save_zone(&zone) {
+                log::warn!("Failed to persist zone {}: {}", zone_id, e);
+            }
+        }
+    }
+
+    /// Write every zone through to the store; returns the number of zones written
+    pub fn persist_all(&self) -> Result<usize, String> {
+        let Some(store) = &self.store else {
+            return Ok(0);
+        };
+        for zone in self.zones.values() {
+            store.save_zone(&zone).map_err(|e| format!("Failed to persist zone {}: {}", zone.id, e))?;
+        }
+        Ok(self.zones.len())
+    }
+
+    /// Dimensions and limits of the world
+    pub fn config(&self) -> &WorldConfig {
+        &self.config
+    }
+
+    /// Replace the world settings, effective from the next tick
+    ///
+    /// Zones already generated keep their size and resources.
+    pub fn set_config(&mut self, config: WorldConfig) {
+        self.history.set_limits(config.history_retention_ticks, config.history_keyframe_interval);
+        self.config = config;
+    }
+
+    /// Get the current simulation tick
     pub fn get_tick(&self) -> u64 {
         self.tick
     }
 
+    /// Get the current script tick (the tick reported to scripts)
+    pub fn get_script_tick(&self) -> u64 {
+        self.script_tick
+    }
+
+    /// Whether the world was created with a seed (see [`World::with_seed`])
+    pub fn is_deterministic(&self) -> bool {
+        self.config.seed.is_some()
+    }
+
+    /// Stable 64-bit hash of the tick, the entities and resource deposits of every zone,
+    /// and the players' and teams' resources
+    ///
+    /// Maps are hashed in key order, so two worlds in the same state have the same hash
+    /// whatever their maps' iteration order, on any platform.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = StateHasher::default();
+        self.tick.hash(&mut hasher);
+
+        for zone in self.zones.values() {
+            zone.id.hash(&mut hasher);
+            let mut entities: Vec<&EntityRef> = zone.entities.iter().collect();
+            entities.sort_by_key(|entity| entity.id);
+            for entity in entities {
+                (entity.id, &entity.kind, &entity.owner, entity.x as u64, entity.y as u64, entity.hits).hash(&mut hasher);
+            }
+            for deposit in &zone.resources {
+                (deposit.x as u64, deposit.y as u64, deposit.amount).hash(&mut hasher);
+            }
+        }
+
+        let stockpiles = self.stockpiles.iter().map(|(player_id, stockpile)| (player_id.to_string(), stockpile));
+        let pools = self.team_pools.iter().map(|(team_id, pool)| (team_id.to_string(), pool));
+        let mut holders: Vec<(String, &HashMap<ResourceType, u32>)> = stockpiles.chain(pools).collect();
+        holders.sort_by(|a, b| a.0.cmp(&b.0));
+        for (holder, resources) in holders {
+            holder.hash(&mut hasher);
+            let mut resources: Vec<(u8, u32)> = resources.iter().map(|(&resource, &amount)| (resource as u8, amount)).collect();
+            resources.sort();
+            resources.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Whether scripts run on the current simulation tick
+    pub fn is_script_tick(&self) -> bool {
+        self.tick.is_multiple_of(self.config.script_tick_interval.max(1))
+    }
+
+    /// Advance the world by one simulation tick
+    ///
+    /// Pending moves advance by one tile; market orders are matched every
+    /// `market_match_interval_ticks` and the script tick advances every
+    /// `script_tick_interval` simulation ticks. Players leaving an alliance stop being
+    /// allies once the tick is over.
+    pub fn advance_tick(&mut self) {
+        self.tick += 1;
+        self.tick_actions();
+        self.tick_research();
+        self.tick_moves();
+        self.world_clock.advance();
+        self.tick_weather();
+        self.tick_defeats();
+        if self.tick.is_multiple_of(self.config.market_match_interval_ticks.max(1)) {
+            self.tick_market();
+        }
+        if self.is_script_tick() {
+            self.script_tick += 1;
+        }
+        self.tick_alliances();
+        if let Some(Err(e)) = self.action_log.as_mut().map(ActionLog::flush) {
+            log::warn!("Failed to write the action log: {}", e);
+        }
+        if self.history.keyframe_due(self.tick) {
+            match WorldSnapshot::capture(self) {
+                Ok(snapshot) => self.history.record_keyframe(self.tick, snapshot),
+                Err(e) => log::warn!("Could not record the world at tick {} in its history: {}", self.tick, e),
+            }
+        }
+    }
+
+    /// Move the clock forward to `tick` without simulating anything, as when replaying history
+    pub(crate) fn skip_to_tick(&mut self, tick: u64) {
+        while self.tick < tick {
+            self.tick += 1;
+            self.world_clock.advance();
+            if self.is_script_tick() {
+                self.script_tick += 1;
+            }
+        }
+    }
+
+    /// The world as it was at the end of a past simulation tick (see [`GameHistory`])
+    pub fn state_at(&self, tick: u64) -> Result<Arc<World>, HistoryError> {
+        self.history.rebuild(tick, self.tick)
+    }
+
+    /// Keyframes and actions of recent simulation ticks
+    pub fn history(&self) -> &GameHistory {
+        &self.history
+    }
+
+    /// Buffer the commands a player's script issued, to be carried out over the next simulation ticks
+    ///
+    /// `moveTo` (actor `"<zone_id>:<entity_id>"`, `{"position": {"x", "y"}}`) walks the
+    /// entity along the cheapest path, one tile per tick; `stop` cancels its move.
+    /// `attack` (`{"target": "<zone_id>:<entity_id>"}`) and `harvest` are resolved on the
+    /// next tick; attacks on allies are rejected as friendly fire. `produceUnit` (actor a
+    /// structure, `{"unitType": "worker"}`) places the unit next to the structure on the next
+    /// tick if the player can pay its [`UNIT_COSTS`]. `research` (actor a base, `{"tech": "<id>"}`)
+    /// starts researching a tech of the [`tech::TECH_TREE`] on the next tick, if its
+    /// prerequisites are researched and the player can pay for it. Other actions are not
+    /// simulated yet and are ignored.
+    ///
+    /// Every command is first authorized (see [`World::authorize`]); refused commands are
+    /// dropped and counted in [`World::command_violations`]. Returns an error message per
+    /// rejected command; the player's next snapshot lists them as `command_errors`.
+    pub fn apply_commands(&mut self, player_id: &str, commands: &[BotCommand]) -> Vec<String> {
+        let mut errors = Vec::new();
+        for command in commands {
+            let result = self.authorize(player_id, command).inspect_err(|_| self.record_violation(player_id));
+            let result = result.and_then(|()| match command.action.as_str() {
+                "moveTo" => self.queue_move(player_id, command),
+                "stop" => self.own_entity(player_id, command.actor.as_deref()).map(|(zone_id, entity_id)| {
+                    self.pending_moves.retain(|pending| (pending.zone_id.as_str(), pending.entity_id) != (zone_id.as_str(), entity_id));
+                }),
+                "attack" | "harvest" => self.own_entity(player_id, command.actor.as_deref())
+                    .map(|_| self.pending_actions.push((player_id.to_string(), command.clone()))),
+                "produceUnit" => self.own_structure(player_id, command.actor.as_deref())
+                    .and_then(|_| unit_cost(command.params["unitType"].as_str().unwrap_or_default()))
+                    .map(|_| self.pending_actions.push((player_id.to_string(), command.clone()))),
+                "research" => self.check_research(player_id, command)
+                    .map(|_| self.pending_actions.push((player_id.to_string(), command.clone()))),
+                _ => Ok(()),
+            });
+            if let Err(e) = result {
+                errors.push(format!("{} failed: {}", command.action, e));
+            }
+        }
+        self.command_errors.insert(player_id.to_string(), errors.clone());
+        errors
+    }
+
+    /// Authorization stage of the command pipeline: whether a player may issue a command
+    ///
+    /// The command must not have been issued by another player, its actor (if any) must be
+    /// an entity of the player, and the target of an `attack` must be an existing entity of
+    /// a player who is neither the attacker nor an ally. Checked against the world as it is
+    /// when commands are applied, and again when the one-tick actions are resolved, since
+    /// entities can be destroyed and alliances formed in between.
+    pub fn authorize(&self, player_id: &str, command: &BotCommand) -> Result<(), String> {
+        if let Some(issuer) = command.player_id.as_deref().filter(|issuer| *issuer != player_id) {
+            return Err(format!("Command issued by {} cannot be applied for {}", issuer, player_id));
+        }
+        if let Some(actor) = command.actor.as_deref() {
+            match self.entity_owner(actor) {
+                None => return Err(format!("Entity {} not found", actor)),
+                Some(owner) if owner.as_deref() != Some(player_id) => return Err(format!("Entity {} is not yours", actor)),
+                Some(_) => {}
+            }
+        }
+        if command.action != "attack" {
+            return Ok(());
+        }
+        let Some(target) = command.params["target"].as_str() else {
+            return Ok(());
+        };
+        match self.entity_owner(target) {
+            None => Err(format!("Target {} not found", target)),
+            Some(Some(owner)) if owner == player_id => Err(format!("Target {} is your own", target)),
+            Some(Some(ally)) if self.are_allied(player_id, &ally) => Err(format!(
+                "Friendly fire: target {} belongs to your ally {}", target, ally
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Number of commands of a player refused by [`World::authorize`]
+    pub fn command_violations(&self, player_id: &str) -> u64 {
+        self.command_violations.get(player_id).copied().unwrap_or(0)
+    }
+
+    fn record_violation(&mut self, player_id: &str) {
+        *self.command_violations.entry(player_id.to_string()).or_default() += 1;
+    }
+
+    /// Owner of the entity a `"<zone_id>:<entity_id>"` reference points to (`None` if there is no such entity)
+    fn entity_owner(&self, reference: &str) -> Option<Option<String>> {
+        let (zone_id, entity_id) = reference.rsplit_once(':')?;
+        let entity_id = entity_id.parse::<u32>().ok()?;
+        let zone = self.zones.get(zone_id)?;
+        let owner = zone.entities.iter().find(|entity| entity.id == entity_id)?.owner.clone();
+        Some(owner)
+    }
+
+    /// Zone and ID of a unit the player owns, from a command actor
+    fn own_entity(&self, player_id: &str, actor: Option<&str>) -> Result<(String, u32), String> {
+        self.owned(player_id, actor, false)
+    }
+
+    /// Zone and ID of a structure the player owns, from a command actor
+    fn own_structure(&self, player_id: &str, actor: Option<&str>) -> Result<(String, u32), String> {
+        self.owned(player_id, actor, true)
+    }
+
+    fn owned(&self, player_id: &str, actor: Option<&str>, structure: bool) -> Result<(String, u32), String> {
+        let (what, label) = if structure { ("structure", "Structure") } else { ("unit", "Unit") };
+        let actor = actor.ok_or_else(|| format!("No {} given", what))?;
+        let (zone_id, entity_id) = actor.rsplit_once(':')
+            .and_then(|(zone_id, id)| Some((zone_id, id.parse::<u32>().ok()?)))
+            .ok_or_else(|| format!("Unknown {} {}", what, actor))?;
+        let zone = self.zones.get(zone_id)
+            .ok_or_else(|| format!("{} {} not found", label, actor))?;
+        let entity = zone.entities.iter().find(|entity| entity.id == entity_id)
+            .ok_or_else(|| format!("{} {} not found", label, actor))?;
+        if entity.owner.as_deref() != Some(player_id) || entity.is_structure() != structure {
+            return Err(format!("{} {} is not one of your {}s", label, actor, what));
+        }
+        Ok((zone_id.to_string(), entity_id))
+    }
+
+    /// Replace the pending move of a unit with a path to the command's position
+    fn queue_move(&mut self, player_id: &str, command: &BotCommand) -> Result<(), String> {
+        let (zone_id, entity_id) = self.own_entity(player_id, command.actor.as_deref())?;
+        let coordinate = |axis: &str| command.params["position"][axis].as_u64().map(|v| v as usize);
+        let (Some(x), Some(y)) = (coordinate("x"), coordinate("y")) else {
+            return Err("Missing position".to_string());
+        };
+
+        let zone = self.zones.get(&zone_id).expect("entity checked above");
+        let entity = zone.entities.iter().find(|entity| entity.id == entity_id).expect("entity checked above");
+        let (path, _) = find_path(&zone, (entity.x, entity.y), (x, y), entity.mobility())
+            .ok_or_else(|| format!("No path to ({}, {})", x, y))?;
+        drop(zone);
+
+        self.pending_moves.retain(|pending| (pending.zone_id.as_str(), pending.entity_id) != (zone_id.as_str(), entity_id));
+        self.pending_moves.push(PendingMove {
+            zone_id,
+            entity_id,
+            path: path.into_iter().skip(1).collect(),
+        });
+        Ok(())
+    }
+
+    /// Resolve the `attack`, `harvest`, `produceUnit` and `research` commands issued on the last script tick
+    ///
+    /// Commands are authorized again first: those no longer allowed (e.g. their actor was
+    /// destroyed) are counted as violations and added to the player's `command_errors`.
+    fn tick_actions(&mut self) {
+        for (player_id, command) in std::mem::take(&mut self.pending_actions) {
+            if let Err(e) = self.authorize(&player_id, &command) {
+                self.record_violation(&player_id);
+                self.command_errors.entry(player_id).or_default().push(format!("{} failed: {}", command.action, e));
+                continue;
+            }
+            let result = match command.action.as_str() {
+                "attack" => self.attack(&player_id, &command),
+                "produceUnit" => self.produce_unit(&player_id, &command),
+                "research" => self.start_research(&player_id, &command),
+                _ => self.harvest(&player_id, &command),
+            };
+            if let Err(e) = result {
+                log::debug!("{} of {} failed: {}", command.action, player_id, e);
+            }
+        }
+    }
+
+    /// Damage an enemy entity next to the attacker, destroying it at 0 hit points
+    ///
+    /// Armor techs of the defender reduce the [`ATTACK_DAMAGE`], down to 1.
+    fn attack(&mut self, player_id: &str, command: &BotCommand) -> Result<(), String> {
+        let (zone_id, attacker_id) = self.own_entity(player_id, command.actor.as_deref())?;
+        let target = command.params["target"].as_str().ok_or_else(|| "Missing target".to_string())?;
+        let target_id = target.strip_prefix(&format!("{}:", zone_id))
+            .and_then(|id| id.parse::<u32>().ok())
+            .ok_or_else(|| format!("Target {} is not in zone {}", target, zone_id))?;
+
+        let mut zone = self.zones.get_mut(&zone_id).expect("attacker's zone exists");
+        new_version(&self.zone_version, &mut zone);
+        let attacker = zone.entities.iter().find(|entity| entity.id == attacker_id).expect("attacker exists");
+        let (ax, ay) = (attacker.x, attacker.y);
+        let index = zone.entities.iter().position(|entity| entity.id == target_id)
+            .ok_or_else(|| format!("Target {} not found", target))?;
+        let defender = &mut zone.entities[index];
+        if defender.owner.as_deref() == Some(player_id) {
+            return Err(format!("Target {} is your own", target));
+        }
+        if defender.x.abs_diff(ax) > 1 || defender.y.abs_diff(ay) > 1 {
+            return Err(format!("Target {} is out of range", target));
+        }
+
+        let armor = defender.owner.as_deref()
+            .map_or(0, |owner| tech_bonus(&self.techs, owner, Stat::Armor, &defender.kind));
+        defender.hits = defender.hits.saturating_sub(ATTACK_DAMAGE.saturating_sub(armor).max(1));
+        if defender.hits > 0 {
+            return Ok(());
+        }
+        let destroyed = zone.entities.remove(index);
+        drop(zone);
+        self.pending_moves.retain(|pending| (pending.zone_id.as_str(), pending.entity_id) != (zone_id.as_str(), target_id));
+        let players = destroyed.owner.iter().cloned().chain([player_id.to_string()]).collect();
+        self.record_event(&zone_id, players, GameEventKind::UnitDestroyed {
+            unit_id: destroyed.id,
+            kind: destroyed.kind.clone(),
+            x: destroyed.x,
+            y: destroyed.y,
+        });
+        self.events.push(WorldEvent::UnitDestroyed {
+            zone_id,
+            unit_id: destroyed.id,
+            kind: destroyed.kind,
+            owner: destroyed.owner,
+            attacker: player_id.to_string(),
+            tick: self.tick,
+        });
+        Ok(())
+    }
+
+    /// Collect from a resource deposit on or next to the unit into the player's stockpile
+    ///
+    /// Harvesting techs add to the [`HARVEST_AMOUNT`].
+    fn harvest(&mut self, player_id: &str, command: &BotCommand) -> Result<(), String> {
+        let (zone_id, unit_id) = self.own_entity(player_id, command.actor.as_deref())?;
+        let mut zone = self.zones.get_mut(&zone_id).expect("harvester's zone exists");
+        new_version(&self.zone_version, &mut zone);
+        let unit = zone.entities.iter().find(|entity| entity.id == unit_id).expect("harvester exists");
+        let (ux, uy) = (unit.x, unit.y);
+        let harvest_amount = HARVEST_AMOUNT + tech_bonus(&self.techs, player_id, Stat::HarvestAmount, &unit.kind);
+        let index = zone.resources.iter()
+            .position(|deposit| deposit.amount > 0 && deposit.x.abs_diff(ux) <= 1 && deposit.y.abs_diff(uy) <= 1)
+            .ok_or_else(|| "No resource deposit in reach".to_string())?;
+
+        let deposit = &zone.resources[index];
+        let (amount, x, y) = (deposit.amount.min(harvest_amount), deposit.x, deposit.y);
+        let tick = self.tick;
+        let action = GameAction::ResourceHarvested {
+            player_id: player_id.to_string(),
+            zone_id: zone_id.clone(),
+            x,
+            y,
+            resource: ResourceType::Minerals,
+            amount,
+        };
+        self.history.stage(tick, &action);
+        let entry = self.action_log.as_mut().map(|log| log.append(tick, action));
+        let deposit = &mut zone.resources[index];
+        deposit.amount -= amount;
+        if deposit.amount == 0 {
+            zone.resources.remove(index);
+        }
+        drop(zone);
+
+        self.deposit_resources(player_id, ResourceType::Minerals, amount);
+        self.commit_action(entry);
+        self.record_event(&zone_id, vec![player_id.to_string()], GameEventKind::ResourceCollected {
+            unit_id,
+            resource: ResourceType::Minerals,
+            amount,
+            x,
+            y,
+        });
+        self.events.push(WorldEvent::ResourceCollected {
+            player_id: player_id.to_string(),
+            zone_id,
+            resource: ResourceType::Minerals,
+            amount,
+            tick: self.tick,
+        });
+        Ok(())
+    }
+
+    /// Pay for a unit and place it on a free walkable tile next to the producing structure
+    fn produce_unit(&mut self, player_id: &str, command: &BotCommand) -> Result<(), String> {
+        let (zone_id, structure_id) = self.own_structure(player_id, command.actor.as_deref())?;
+        let kind = command.params["unitType"].as_str().unwrap_or_default().to_string();
+        let cost = unit_cost(&kind)?;
+        let zone = self.zones.get(&zone_id).expect("structure exists");
+        let structure = zone.entities.iter().find(|entity| entity.id == structure_id).expect("structure exists");
+        let (sx, sy) = (structure.x, structure.y);
+        let (x, y) = [(0, -1), (1, 0), (0, 1), (-1, 0), (1, -1), (1, 1), (-1, 1), (-1, -1)]
+            .into_iter()
+            .filter_map(|(dx, dy)| Some((sx.checked_add_signed(dx)?, sy.checked_add_signed(dy)?)))
+            .find(|&(x, y)| {
+                zone.get_tile(x, y).is_some_and(|tile| tile.surface_type.movement_cost_for(Mobility::GROUND).is_some())
+                    && !zone.entities.iter().any(|entity| entity.x == x && entity.y == y)
+            })
+            .ok_or_else(|| format!("No free tile next to {}:{}", zone_id, structure_id))?;
+        drop(zone);
+        self.withdraw_resources(player_id, ResourceType::Minerals, cost)?;
+
+        let mut zone = self.contents_mut(&zone_id).expect("structure's zone exists");
+        let unit_id = zone.entities.iter().map(|entity| entity.id).max().map_or(1, |id| id + 1);
+        zone.entities.push(EntityRef {
+            id: unit_id,
+            kind: kind.clone(),
+            owner: Some(player_id.to_string()),
+            x,
+            y,
+            hits: DEFAULT_ENTITY_HITS,
+            can_swim: false,
+            can_fly: false,
+        });
+        drop(zone);
+        self.persist_zone(&zone_id);
+        self.record_event(&zone_id, vec![player_id.to_string()], GameEventKind::UnitCreated { unit_id, kind, x, y });
+        Ok(())
+    }
+
+    /// Move every unit with a pending move one tile further (more with speed techs)
+    ///
+    /// Moves that are blocked, whose unit is gone, or that take a portal end there.
+    fn tick_moves(&mut self) {
+        let mut moves = std::mem::take(&mut self.pending_moves);
+        moves.retain_mut(|pending| {
+            let steps = self.zones.get(&pending.zone_id)
+                .and_then(|zone| {
+                    let entity = zone.entities.iter().find(|entity| entity.id == pending.entity_id)?;
+                    Some(tech_bonus(&self.techs, entity.owner.as_deref()?, Stat::MoveSpeed, &entity.kind))
+                })
+                .unwrap_or(0) + 1;
+            for _ in 0..steps {
+                let Some((x, y)) = pending.path.pop_front() else {
+                    return false;
+                };
+                match self.move_entity(&pending.zone_id, pending.entity_id, x, y) {
+                    Ok((zone_id, unit_id, x, y)) => {
+                        let owner = self.zones.get(&zone_id).and_then(|zone| {
+                            zone.entities.iter().find(|entity| entity.id == unit_id).and_then(|entity| entity.owner.clone())
+                        });
+                        self.record_event(&zone_id, owner.into_iter().collect(), GameEventKind::UnitMoved { unit_id, x, y });
+                        if zone_id != pending.zone_id || pending.path.is_empty() {
+                            return false;
+                        }
+                    }
+                    Err(e) => {
+                        log::debug!("Move of {}:{} stopped: {}", pending.zone_id, pending.entity_id, e);
+                        return false;
+                    }
+                }
+            }
+            true
+        });
+        self.pending_moves = moves;
+    }
+
+    /// Check a `research` command: its actor is one of the player's bases, and the tech
+    /// can be researched and paid for
+    fn check_research(&self, player_id: &str, command: &BotCommand) -> Result<(String, u32, &'static tech::Tech), String> {
+        let (zone_id, base_id) = self.own_structure(player_id, command.actor.as_deref())?;
+        let zone = self.zones.get(&zone_id).expect("structure exists");
+        let base = zone.entities.iter().find(|entity| entity.id == base_id).expect("structure exists");
+        if base.kind != "base" {
+            return Err(format!("Only a base can research, not a {}", base.kind));
+        }
+        drop(zone);
+        let tech = tech::tech(command.params["tech"].as_str().unwrap_or_default())?;
+        self.techs.get(player_id).cloned().unwrap_or_default().can_research(tech)?;
+        let minerals = self.balance(player_id, ResourceType::Minerals);
+        if minerals < tech.cost {
+            return Err(format!("{} costs {} minerals, you have {}", tech.id, tech.cost, minerals));
+        }
+        Ok((zone_id, base_id, tech))
+    }
+
+    /// Pay for a tech and start researching it at the command's base
+    fn start_research(&mut self, player_id: &str, command: &BotCommand) -> Result<(), String> {
+        let (zone_id, base_id, tech) = self.check_research(player_id, command)?;
+        self.withdraw_resources(player_id, ResourceType::Minerals, tech.cost)?;
+        let (x, y) = self.zones.get(&zone_id)
+            .and_then(|zone| zone.entities.iter().find(|entity| entity.id == base_id).map(|base| (base.x, base.y)))
+            .expect("base exists");
+        let research = Research {
+            tech: tech.id.to_string(),
+            zone_id,
+            base_id,
+            x,
+            y,
+            completes_at: self.tick + tech.research_ticks,
+        };
+        self.techs.entry(player_id.to_string()).or_default().research = Some(research);
+        Ok(())
+    }
+
+    /// Complete the research that are due, and cancel (without refund) those whose base is gone
+    fn tick_research(&mut self) {
+        let mut finished = Vec::new();
+        for (player_id, techs) in &mut self.techs {
+            let Some(research) = &techs.research else {
+                continue;
+            };
+            let base_alive = self.zones.get(&research.zone_id).is_some_and(|zone| {
+                zone.entities.iter()
+                    .find(|entity| entity.id == research.base_id)
+                    .is_some_and(|base| base.owner.as_deref() == Some(player_id.as_str()) && base.kind == "base")
+            });
+            if !base_alive {
+                let research = techs.research.take().expect("research checked above");
+                finished.push((player_id.clone(), research, false));
+            } else if self.tick >= research.completes_at {
+                let research = techs.research.take().expect("research checked above");
+                techs.researched.insert(research.tech.clone());
+                finished.push((player_id.clone(), research, true));
+            }
+        }
+        finished.sort_by(|a, b| a.0.cmp(&b.0));
+        for (player_id, research, completed) in finished {
+            let Research { tech, zone_id, x, y, .. } = research;
+            let kind = if completed {
+                GameEventKind::ResearchCompleted { tech, x, y }
+            } else {
+                log::debug!("Research of {} by {} cancelled: its base is gone", tech, player_id);
+                GameEventKind::ResearchCancelled { tech, x, y }
+            };
+            self.record_event(&zone_id, vec![player_id], kind);
+        }
+    }
+
+    /// Researched techs and research under way of a player
+    pub fn tech_status(&self, player_id: &str) -> TechStatus {
+        self.techs.get(player_id).cloned().unwrap_or_default().status()
+    }
+
+    /// Scenario objectives of a player and their progress (none outside campaign runs)
+    pub fn objectives(&self, player_id: &str) -> &[ObjectiveProgress] {
+        self.objectives.get(player_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Show a player the progress of their scenario objectives (see [`crate::game::scenario`])
+    pub fn set_objectives(&mut self, player_id: &str, objectives: Vec<ObjectiveProgress>) {
+        self.objectives.insert(player_id.to_string(), objectives);
+    }
+
+    /// Researched techs of every player who has any
+    pub fn researched_techs(&self) -> BTreeMap<String, BTreeSet<String>> {
+        self.techs.iter()
+            .filter(|(_, techs)| !techs.researched.is_empty())
+            .map(|(player_id, techs)| (player_id.clone(), techs.researched.clone()))
+            .collect()
+    }
+
+    /// Mark techs as researched for a player (e.g. when restoring a saved campaign)
+    pub fn set_researched_techs(&mut self, player_id: &str, researched: BTreeSet<String>) {
+        self.techs.entry(player_id.to_string()).or_default().researched = researched;
+    }
+
+    /// Record an event at the current tick
+    pub fn record_event(&mut self, zone_id: &str, players: Vec<String>, kind: GameEventKind) {
+        self.event_log.record(self.tick, zone_id, players, kind);
+    }
+
+    /// Recent events of every zone
+    pub fn event_log(&self) -> &EventLog {
+        &self.event_log
+    }
+
+    /// Events after `since_tick` a player can see, oldest first, at most `limit`
+    ///
+    /// A player sees the events involving them, and events within the visibility radius
+    /// (see [`World::visibility_radius`]) of one of their or their allies' entities in the same zone.
+    pub fn events_visible_to(&self, player_id: &str, since_tick: u64, limit: usize) -> Vec<GameEvent> {
+        self.events_in_sight(player_id, &self.observers(player_id), since_tick, limit)
+    }
+
+    /// [`World::events_visible_to`], with the player's observers already known
+    fn events_in_sight(&self, player_id: &str, observers: &HashMap<String, Vec<(usize, usize)>>, since_tick: u64, limit: usize) -> Vec<GameEvent> {
+        self.event_log.query(since_tick, limit, |event| {
+            if event.players.iter().any(|player| player == player_id) {
+                return true;
+            }
+            event.kind.position().is_some_and(|(x, y)| self.in_sight(observers, &event.zone_id, x, y))
+        })
+    }
+
+    /// A zone as a player sees it: its terrain, their own, teammates' and allies' entities,
+    /// and the other entities and resource deposits in sight (see [`World::events_visible_to`])
+    pub fn zone_view(&self, player_id: &str, zone_id: &str) -> Option<Zone> {
+        let mut zone = self.zones.get(zone_id).map(|zone| Zone::clone(&zone))?;
+        let observers = self.observers(player_id);
+        let team = self.team_of(player_id);
+        let allies = self.allies_of(player_id);
+        let friendly = |owner: &str| {
+            owner == player_id
+                || allies.iter().any(|ally| *ally == owner)
+                || team.is_some_and(|team| self.team_of(owner) == Some(team))
+        };
+        zone.entities.retain(|entity| {
+            entity.owner.as_deref().is_some_and(friendly) || self.in_sight(&observers, zone_id, entity.x, entity.y)
+        });
+        zone.resources.retain(|deposit| self.in_sight(&observers, zone_id, deposit.x, deposit.y));
+        Some(zone)
+    }
+
+    /// Positions of the entities of a player and their allies, by zone
+    fn observers(&self, player_id: &str) -> HashMap<String, Vec<(usize, usize)>> {
+        let allies = self.allies_of(player_id);
+        let is_observer = |owner: &str| owner == player_id || allies.iter().any(|ally| *ally == owner);
+        let mut observers: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+        for zone in self.snapshot_cache.contents(&self.zones, self.zone_version.last()).values() {
+            for entity in zone.entities.iter().filter(|entity| entity.owner.as_deref().is_some_and(is_observer)) {
+                observers.entry(zone.zone_id.clone()).or_default().push((entity.x, entity.y));
+            }
+        }
+        observers
+    }
+
+    /// Whether a tile is within the visibility radius of one of the observers in its zone
+    /// (anywhere in the zone without fog of war)
+    fn in_sight(&self, observers: &HashMap<String, Vec<(usize, usize)>>, zone_id: &str, x: usize, y: usize) -> bool {
+        let Some(positions) = observers.get(zone_id) else {
+            return false;
+        };
+        if !self.config.fog_of_war {
+            return true;
+        }
+        let radius = self.visibility_radius(zone_id) as usize;
+        positions.iter().any(|&(ox, oy)| ox.abs_diff(x).pow(2) + oy.abs_diff(y).pow(2) <= radius * radius)
+    }
+
+    /// Whether an entity still has tiles to walk
+    pub fn is_moving(&self, zone_id: &str, entity_id: u32) -> bool {
+        self.pending_moves.iter().any(|pending| pending.zone_id == zone_id && pending.entity_id == entity_id)
+    }
+
+    /// Add a snapshot of the world at the current script tick to the replay history
+    pub fn record_snapshot(&mut self) -> Result<(), String> {
+        let snapshot = WorldSnapshot::capture(self)?;
+        self.replay.record(snapshot);
+        Ok(())
+    }
+
+    /// Replay history of recent script ticks
+    pub fn replay(&self) -> &ReplayHistory {
+        &self.replay
+    }
+
+    /// Take the events of the ticks since the last call
+    pub fn drain_events(&mut self) -> Vec<WorldEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Take the events of the ticks since the last call along with the owner and entity
+    /// count of every zone, for the [`StatsReadModel`](crate::game::stats::StatsReadModel)
+    pub fn drain_stats(&mut self) -> StatsBatch {
+        let zones = self.zones.values().iter()
+            .map(|zone| ZoneCensus {
+                zone_id: zone.id.clone(),
+                owner: zone.owner.clone(),
+                entity_count: zone.entities.len(),
+            })
+            .collect();
+        StatsBatch { tick: self.tick, events: self.drain_events(), zones }
+    }
+
+    /// Defeat tracking of a player (`None` if they never owned an entity)
+    pub fn player_record(&self, player_id: &str) -> Option<&PlayerRecord> {
+        self.players.get(player_id)
+    }
+
+    /// Whether a player is defeated and waiting to respawn
+    pub fn is_defeated(&self, player_id: &str) -> bool {
+        self.players.get(player_id).is_some_and(|record| record.defeated_at.is_some())
+    }
+
+    /// Detect players who lost every building and unit, and respawn those whose cooldown is over
+    ///
+    /// Zones and players are gone through in ID order, so the first zone a player is seen
+    /// in (their home zone if they have none) and the order of the events don't depend on
+    /// the maps' iteration order.
+    fn tick_defeats(&mut self) {
+        let mut owned: BTreeMap<String, String> = BTreeMap::new();
+        for zone in self.zones.values() {
+            for owner in zone.entities.iter().filter_map(|entity| entity.owner.as_deref()) {
+                if !owned.contains_key(owner) {
+                    owned.insert(owner.to_string(), zone.id.clone());
+                }
+            }
+        }
+
+        for (player_id, zone_id) in &owned {
+            let own_zone = format!("player_{}_zone", player_id);
+            let record = self.players.entry(player_id.clone()).or_default();
+            if record.home_zone.is_none() {
+                let home = if self.zones.contains_key(&own_zone) { own_zone } else { zone_id.clone() };
+                record.home_zone = Some(home);
+            }
+        }
+
+        let tick = self.tick;
+        let cooldown = self.config.respawn_cooldown_ticks;
+        let mut respawns = Vec::new();
+        let mut defeats = Vec::new();
+        let mut players: Vec<(&String, &mut PlayerRecord)> = self.players.iter_mut().collect();
+        players.sort_by(|a, b| a.0.cmp(b.0));
+        for (player_id, record) in players {
+            match record.defeated_at {
+                None if !owned.contains_key(player_id.as_str()) => {
+                    record.defeated_at = Some(tick);
+                    record.defeats += 1;
+                    log::info!("{} was defeated at tick {}", player_id, tick);
+                    self.events.push(WorldEvent::PlayerDefeated {
+                        player_id: player_id.clone(),
+                        tick,
+                        respawn_tick: tick + cooldown,
+                    });
+                    if let Some(home_zone) = &record.home_zone {
+                        defeats.push((home_zone.clone(), player_id.clone()));
+                    }
+                }
+                Some(defeated_at) if tick >= defeated_at + cooldown => respawns.push(player_id.clone()),
+                _ => {}
+            }
+        }
+
+        for (zone_id, player_id) in defeats {
+            self.record_event(&zone_id, vec![player_id], GameEventKind::PlayerDefeated);
+        }
+
+        respawns.sort();
+        for player_id in respawns {
+            match self.respawn(&player_id) {
+                Ok(zone_id) => {
+                    log::info!("{} respawned in zone {} at tick {}", player_id, zone_id, tick);
+                    self.events.push(WorldEvent::PlayerRespawned { player_id, zone_id, tick });
+                }
+                // Retried on the next tick
+                Err(e) => log::warn!("Could not respawn {}: {}", player_id, e),
+            }
+        }
+    }
+
+    /// Give a defeated player a new base and worker, per the world's respawn mode
+    fn respawn(&mut self, player_id: &str) -> Result<String, String> {
+        let home = self.players.get(player_id)
+            .and_then(|record| record.home_zone.clone())
+            .filter(|zone_id| self.zones.contains_key(zone_id));
+
+        let zone_id = match (self.config.respawn_mode, home) {
+            (RespawnMode::OriginalZone, Some(zone_id)) => zone_id,
+            (RespawnMode::OriginalZone, None) => self.generate_player_zone(player_id)?,
+            (RespawnMode::NewZone, _) => {
+                let defeats = self.players.get(player_id).map_or(0, |record| record.defeats);
+                let zone_id = format!("player_{}_respawn_{}_zone", player_id, defeats);
+                self.check_capacity(&zone_id)?;
+                let seed = Self::hash_string(&zone_id);
+                let zone = Zone::generate_with_config(zone_id.clone(), seed, &self.config.default_zone_config)?;
+                self.add_zone(zone);
+                zone_id
+            }
+        };
+
+        self.place_base_and_worker(&zone_id, player_id)?;
+        let record = self.players.entry(player_id.to_string()).or_default();
+        record.defeated_at = None;
+        record.home_zone = Some(zone_id.clone());
+        Ok(zone_id)
+    }
+
+    /// Give a player a base and a worker in their own zone, generating it if needed
+    ///
+    /// This is the same start a respawned player gets. Returns the zone ID.
+    pub fn spawn_player(&mut self, player_id: &str) -> Result<String, String> {
+        let zone_id = format!("player_{}_zone", player_id);
+        if !self.zones.contains_key(&zone_id) {
+            self.generate_player_zone(player_id)?;
+        }
+        self.place_base_and_worker(&zone_id, player_id)?;
+        self.players.entry(player_id.to_string()).or_default().home_zone = Some(zone_id.clone());
+        Ok(zone_id)
+    }
+
+    /// Place a player's starter worker on the next free spawn point of a zone
+    ///
+    /// Returns the spawn point, which is then used (see [`Zone::get_spawn_point`]). Fails
+    /// if the zone does not exist or has no free spawn point left.
+    pub fn place_player_start(&mut self, player_id: &str, zone_id: &str) -> Result<(usize, usize), String> {
+        let mut zone = self.contents_mut(zone_id).ok_or_else(|| format!("Zone {} not found", zone_id))?;
+        let (x, y) = zone.get_spawn_point()
+            .ok_or_else(|| format!("Zone {} has no free spawn point left", zone_id))?;
+        let unit_id = zone.entities.iter().map(|entity| entity.id).max().map_or(1, |id| id + 1);
+        zone.entities.push(EntityRef {
+            id: unit_id,
+            kind: "worker".to_string(),
+            owner: Some(player_id.to_string()),
+            x,
+            y,
+            hits: DEFAULT_ENTITY_HITS,
+            can_swim: false,
+            can_fly: false,
+        });
+        drop(zone);
+        self.persist_zone(zone_id);
+        self.record_event(zone_id, vec![player_id.to_string()], GameEventKind::UnitCreated {
+            unit_id,
+            kind: "worker".to_string(),
+            x,
+            y,
+        });
+        Ok((x, y))
+    }
+
+    /// Place a new base and worker of a player near the center of a zone
+    fn place_base_and_worker(&mut self, zone_id: &str, player_id: &str) -> Result<(), String> {
+        let mut zone = self.contents_mut(zone_id).expect("spawn zone exists");
+        let (base, worker) = Self::spawn_tiles(&zone)
+            .ok_or_else(|| format!("Zone {} has no room for a base", zone_id))?;
+
+        zone.entities.retain(|entity| {
+            let enemy = entity.owner.as_deref() != Some(player_id) && entity.is_structure();
+            let near = entity.x.abs_diff(base.0) <= RESPAWN_CLEAR_RADIUS && entity.y.abs_diff(base.1) <= RESPAWN_CLEAR_RADIUS;
+            !(enemy && near)
+        });
+        let next_id = zone.entities.iter().map(|entity| entity.id).max().map_or(1, |id| id + 1);
+        for (id, kind, (x, y)) in [(next_id, "base", base), (next_id + 1, "worker", worker)] {
+            zone.entities.push(EntityRef {
+                id,
+                kind: kind.to_string(),
+                owner: Some(player_id.to_string()),
+                x,
+                y,
+                hits: DEFAULT_ENTITY_HITS,
+                can_swim: false,
+                can_fly: false,
+            });
+        }
+        drop(zone);
+        self.persist_zone(zone_id);
+        self.record_event(zone_id, vec![player_id.to_string()], GameEventKind::BuildingCompleted {
+            unit_id: next_id,
+            kind: "base".to_string(),
+            x: base.0,
+            y: base.1,
+        });
+        self.record_event(zone_id, vec![player_id.to_string()], GameEventKind::UnitCreated {
+            unit_id: next_id + 1,
+            kind: "worker".to_string(),
+            x: worker.0,
+            y: worker.1,
+        });
+        Ok(())
+    }
+
+    /// Base and worker tiles for a respawn: the walkable tile closest to the zone's center
+    /// with a walkable neighbour
+    ///
+    /// Tiles holding units are avoided; enemy structures near the base are cleared afterwards.
+    fn spawn_tiles(zone: &Zone) -> Option<((usize, usize), (usize, usize))> {
+        let walkable = |x: usize, y: usize| {
+            zone.get_tile(x, y).is_some_and(|tile| tile.surface_type.movement_cost_for(Mobility::GROUND).is_some())
+                && !zone.entities.iter().any(|entity| entity.x == x && entity.y == y && !entity.is_structure())
+        };
+        let (cx, cy) = (zone.width / 2, zone.height / 2);
+
+        let mut candidates: Vec<(usize, usize)> = (0..zone.height)
+            .flat_map(|y| (0..zone.width).map(move |x| (x, y)))
+            .collect();
+        candidates.sort_by_key(|&(x, y)| (x.abs_diff(cx) + y.abs_diff(cy), y, x));
+        candidates.into_iter()
+            .filter(|&(x, y)| walkable(x, y))
+            .find_map(|(x, y)| {
+                [(x + 1, y), (x.wrapping_sub(1), y), (x, y + 1), (x, y.wrapping_sub(1))]
+                    .into_iter()
+                    .find(|&(nx, ny)| walkable(nx, ny))
+                    .map(|worker| ((x, y), worker))
+            })
+    }
+
+    /// Apply active weather events for the current tick and drop finished ones
+    pub fn tick_weather(&mut self) {
+        let tick = self.tick;
+        self.weather.retain(|event| tick < event.end_tick());
+
+        let mut destroyed = Vec::new();
+        for event in self.weather.iter().filter(|event| event.is_active(tick)) {
+            if let Some(mut zone) = self.zones.get_mut(&event.affected_zone_id) {
+                let before = zone.entities.clone();
+                event.apply_storm(&mut zone, &mut self.rng);
+                if zone.entities != before {
+                    new_version(&self.zone_version, &mut zone);
+                }
+                destroyed.extend(before.into_iter()
+                    .filter(|entity| !zone.entities.iter().any(|survivor| survivor.id == entity.id))
+                    .map(|entity| (zone.id.clone(), entity)));
+            }
+        }
+
+        for (zone_id, entity) in destroyed {
+            self.record_event(&zone_id, entity.owner.into_iter().collect(), GameEventKind::UnitDestroyed {
+                unit_id: entity.id,
+                kind: entity.kind,
+                x: entity.x,
+                y: entity.y,
+            });
+        }
+    }
+
+    /// Schedule a weather event on an existing zone
+    pub fn schedule_weather(&mut self, event: WeatherEvent) -> Result<(), String> {
+        event.validate()?;
+        if !self.zones.contains_key(&event.affected_zone_id) {
+            return Err(format!("Zone {} not found", event.affected_zone_id));
+        }
+        if event.end_tick() <= self.tick {
+            return Err(format!("Weather event ends at tick {}, which has already passed", event.end_tick()));
+        }
+
+        self.weather.push(event);
+        Ok(())
+    }
+
+    /// Scheduled and active weather events
+    pub fn weather_events(&self) -> &[WeatherEvent] {
+        &self.weather
+    }
+
+    /// Weather events in effect in a zone at the current tick
+    pub fn active_weather<'a>(&'a self, zone_id: &'a str) -> impl Iterator<Item = &'a WeatherEvent> + 'a {
+        let tick = self.tick;
+        self.weather.iter()
+            .filter(move |event| event.affected_zone_id == zone_id && event.is_active(tick))
+    }
+
+    /// Visibility radius of entities in a zone (time of day and weather)
+    pub fn visibility_radius(&self, zone_id: &str) -> u32 {
+        self.active_weather(zone_id)
+            .fold(self.world_clock.visibility_radius(), |radius, event| event.apply_visibility(radius))
+    }
+
+    /// Cost of entering a tile in a zone (time of day and weather; `None` if not walkable)
+    pub fn movement_cost(&self, zone_id: &str, surface: SurfaceType) -> Option<u32> {
+        let cost = self.world_clock.movement_cost(surface)?;
+        Some(self.active_weather(zone_id)
+            .fold(cost, |cost, event| event.apply_movement_cost(surface, cost)))
+    }
+
+    /// Day/night cycle
+    pub fn world_clock(&self) -> &WorldClock {
+        &self.world_clock
+    }
+
+    /// Replace the day/night cycle (e.g. to change the day length)
+    pub fn set_world_clock(&mut self, clock: WorldClock) {
+        self.world_clock = clock;
+    }
+
+    /// Current phase of the day
+    pub fn day_phase(&self) -> DayPhase {
+        self.world_clock.phase
+    }
+
     /// Add a zone to the world
-    pub fn add_zone(&mut self, zone: Zone) {
-        self.zones.insert(zone.id.clone(), zone);
+    pub fn add_zone(&mut self, mut zone: Zone) {
+        let zone_id = zone.id.clone();
+        new_version(&self.zone_version, &mut zone);
+        zone.terrain_version = zone.version;
+        self.zones.insert(zone);
+        self.persist_zone(&zone_id);
+    }
+
+    /// Apply a batch of edits to a zone, all or none (see [`Zone::apply_edits`])
+    ///
+    /// The edited zone gets a new [`Zone::version`] and [`Zone::terrain_version`], so
+    /// cached responses and snapshots of it are rebuilt, and is written through to the store.
+    pub fn edit_zone(&mut self, zone_id: &str, edits: &[ZoneEdit], force: bool) -> Result<(), EditZoneError> {
+        let mut edited = self.zones.get(zone_id)
+            .map(|zone| Zone::clone(&zone))
+            .ok_or_else(|| EditZoneError::ZoneNotFound(zone_id.to_string()))?;
+        edited.apply_edits(edits, force).map_err(EditZoneError::Rejected)?;
+
+        let mut zone = self.contents_mut(zone_id).expect("zone checked above");
+        zone.terrain_version = zone.version;
+        edited.version = zone.version;
+        edited.terrain_version = zone.terrain_version;
+        *zone = edited;
+        drop(zone);
+        self.persist_zone(zone_id);
+        Ok(())
+    }
+
+    /// Remove a zone, along with its position and the portals leading to or from it
+    pub fn remove_zone(&mut self, zone_id: &str) -> Option<Zone> {
+        let zone = self.zones.remove(zone_id)?;
+        self.zone_positions.remove(zone_id);
+        self.event_log.remove_zone(zone_id);
+
+        let (removed, kept) = std::mem::take(&mut self.portals)
+            .into_iter()
+            .partition(|portal| portal.from_zone_id == zone_id || portal.to_zone_id == zone_id);
+        self.portals = kept;
+
+        if let Some(store) = &self.store {
+            let deleted = removed.iter()
+                .try_for_each(|portal: &Portal| store.delete_portal(&portal.id))
+                .and_then(|_| store.delete_zone(zone_id));
+            if let Err(e) = deleted {
+                log::warn!("Failed to delete zone {} from the store: {}", zone_id, e);
+            }
+        }
+        Some(zone)
     }
 
     /// Get a zone by ID
+    ///
+    /// This is a copy of the zone, made once per change of the zone: use
+    /// [`World::with_zone_read`] to read a zone in place.
+    #[deprecated(note = "copies the whole zone; use `World::with_zone_read`")]
     pub fn get_zone(&self, zone_id: &str) -> Option<&Zone> {
-        self.zones.get(zone_id)
+        self.zones.snapshot(zone_id)
     }
 
-    /// Get a mutable reference to a zone by ID
+    /// Get a zone by ID to change it
+    ///
+    /// The zone gets a new [`Zone::version`] and [`Zone::terrain_version`], whether or not
+    /// it is then changed. Changes reach [`World::zone_reader`] readers the next time the
+    /// world is used: use [`World::with_zone_write`] to change a zone in place.
+    #[deprecated(note = "checks the whole zone out of the zone map; use `World::with_zone_write`")]
     pub fn get_zone_mut(&mut self, zone_id: &str) -> Option<&mut Zone> {
-        self.zones.get_mut(zone_id)
+        let zone = self.zones.checkout(zone_id)?;
+        new_version(&self.zone_version, zone);
+        zone.terrain_version = zone.version;
+        Some(zone)
     }
 
-    /// Get all zone IDs
+    /// Lock a zone to change its entities, deposits or owner, but not its tiles
+    ///
+    /// The zone gets a new [`Zone::version`] and keeps its [`Zone::terrain_version`].
+    fn contents_mut(&mut self, zone_id: &str) -> Option<ZoneMut<'_>> {
+        let mut zone = self.zones.get_mut(zone_id)?;
+        new_version(&self.zone_version, &mut zone);
+        Some(zone)
+    }
+
+    /// Run `read` on a zone, locking only that zone (`None` if the zone does not exist)
+    pub fn with_zone_read<R>(&self, zone_id: &str, read: impl FnOnce(&Zone) -> R) -> Option<R> {
+        self.zones.get(zone_id).map(|zone| read(&zone))
+    }
+
+    /// Run `write` on a zone, locking only that zone (`None` if the zone does not exist)
+    ///
+    /// The zone gets a new [`Zone::version`] and [`Zone::terrain_version`], whether or not
+    /// it is then changed. Other zones can be read and written meanwhile.
+    pub fn with_zone_write<R>(&self, zone_id: &str, write: impl FnOnce(&mut Zone) -> R) -> Option<R> {
+        let mut zone = self.zones.write(zone_id)?;
+        new_version(&self.zone_version, &mut zone);
+        zone.terrain_version = zone.version;
+        Some(write(&mut zone))
+    }
+
+    /// Read access to the zones for other tasks, which never waits for the world lock
+    ///
+    /// Readers only wait for the zone they read, while the world changes that zone.
+    pub fn zone_reader(&self) -> ZoneReader {
+        self.zones.reader()
+    }
+
+    /// Get all zone IDs, sorted
     pub fn get_zone_ids(&self) -> Vec<String> {
-        self.zones.keys().cloned().collect()
+        self.zones.ids()
     }
 
-    /// Generate and add a new zone for a player
-    pub fn generate_player_zone(&mut self, player_id: &str) -> String {
-        let zone_id = format!("player_{}_zone", player_id);
+    /// ID of the zone generated for a player (`player_<id>_zone`)
+    pub fn player_zone_id(player_id: &str) -> String {
+        format!("player_{}_zone", player_id)
+    }
+
+    /// Generate and add a new zone for a player with the world's default zone configuration
+    pub fn generate_player_zone(&mut self, player_id: &str) -> Result<String, String> {
+        let config = self.config.default_zone_config.clone();
+        self.generate_player_zone_with_config(player_id, &config)
+    }
+
+    /// Generate and add a new zone for a player from a generation configuration
+    ///
+    /// Fails when the world already holds `max_zones` zones (regenerating an existing
+    /// player's zone is always allowed).
+    pub fn generate_player_zone_with_config(&mut self, player_id: &str, config: &ZoneGenConfig) -> Result<String, String> {
+        let zone_id = Self::player_zone_id(player_id);
+        self.check_capacity(&zone_id)?;
         
         // Use player_id hash as seed for deterministic generation
         let seed = Self::hash_string(&zone_id);
         
-        let zone = Zone::generate(zone_id.clone(), seed);
+        let zone = Zone::generate_with_config(zone_id.clone(), seed, config)?;
         self.add_zone(zone);
         
-        zone_id
+        Ok(zone_id)
+    }
+
+    /// Zone of a user, generating it (and recording the assignment) if it does not exist
+    ///
+    /// The assignment is kept in the world store, so a user whose zone was deleted gets
+    /// the same zone ID back.
+    pub fn ensure_player_zone(&mut self, user_id: i64, player_id: &str) -> Result<String, String> {
+        if let Some(zone_id) = self.zone_assignments.get(&user_id).filter(|zone_id| self.zones.contains_key(zone_id)) {
+            return Ok(zone_id.clone());
+        }
+
+        let zone_id = self.generate_player_zone(player_id)?;
+        if let Some(store) = &self.store {
+            store.save_zone_assignment(user_id, &zone_id)?;
+        }
+        self.zone_assignments.insert(user_id, zone_id.clone());
+        Ok(zone_id)
+    }
+
+    /// Zone assigned to a user (the zone may have been deleted since)
+    pub fn assigned_zone(&self, user_id: i64) -> Option<&str> {
+        self.zone_assignments.get(&user_id).map(String::as_str)
+    }
+
+    /// Load the map template `name` from the maps directory and add it as zone `map_<name>`
+    ///
+    /// Subject to the same `max_zones` limit as generated zones.
+    pub fn add_zone_from_template(&mut self, name: &str) -> Result<String, String> {
+        let template = MapTemplate::load_named(&self.config.maps_dir, name)?;
+        let zone_id = format!("map_{}", name);
+        self.check_capacity(&zone_id)?;
+
+        let zone = template.to_zone(zone_id.clone())?;
+        self.add_zone(zone);
+        Ok(zone_id)
+    }
+
+    /// Install an exported zone as a new zone `imported_<id>`, never replacing one
+    ///
+    /// Subject to the same `max_zones` limit as generated zones.
+    pub fn import_zone(&mut self, export: &ZoneExport) -> Result<String, String> {
+        let zone_id = loop {
+            let zone_id = format!("imported_{}", &Uuid::new_v4().simple().to_string()[..12]);
+            if !self.zones.contains_key(&zone_id) {
+                break zone_id;
+            }
+        };
+        self.check_capacity(&zone_id)?;
+
+        let zone = Zone::import(export, zone_id.clone())?;
+        self.add_zone(zone);
+        Ok(zone_id)
+    }
+
+    /// Fail if adding `zone_id` would exceed `max_zones` (replacing a zone is always allowed)
+    fn check_capacity(&self, zone_id: &str) -> Result<(), String> {
+        if !self.zones.contains_key(zone_id) && self.zones.len() >= self.config.max_zones {
+            return Err(format!("World is full ({} zones)", self.config.max_zones));
+        }
+        Ok(())
+    }
+
+    /// Place a zone on the world grid; the position must be inside the world and free
+    pub fn set_zone_position(&mut self, zone_id: &str, x: u32, y: u32) -> Result<(), String> {
+        if !self.zones.contains_key(zone_id) {
+            return Err(format!("Zone {} not found", zone_id));
+        }
+        if x >= self.config.width || y >= self.config.height {
+            return Err(format!(
+                "Position ({}, {}) is outside the {}x{} world",
+                x, y, self.config.width, self.config.height
+            ));
+        }
+        if let Some((other, _)) = self.zone_positions.iter().find(|(id, pos)| **pos == (x, y) && id.as_str() != zone_id) {
+            return Err(format!("Position ({}, {}) is taken by zone {}", x, y, other));
+        }
+
+        self.zone_positions.insert(zone_id.to_string(), (x, y));
+        Ok(())
+    }
+
+    /// Position of a zone on the world grid (if placed)
+    pub fn zone_position(&self, zone_id: &str) -> Option<(u32, u32)> {
+        self.zone_positions.get(zone_id).copied()
+    }
+
+    /// Add a portal; both endpoints must be tiles of existing zones walkable by ground units
+    pub fn add_portal(&mut self, portal: Portal) -> Result<(), String> {
+        self.check_walkable(&portal.from_zone_id, portal.from_x, portal.from_y, Mobility::GROUND)?;
+        self.check_walkable(&portal.to_zone_id, portal.to_x, portal.to_y, Mobility::GROUND)?;
+
+        if self.portal_at(&portal.from_zone_id, portal.from_x, portal.from_y).is_some() {
+            return Err(format!("A portal already starts at ({}, {}) in zone {}", portal.from_x, portal.from_y, portal.from_zone_id));
+        }
+        if self.portals.iter().any(|p| p.id == portal.id) {
+            return Err(format!("Portal {} already exists", portal.id));
+        }
+
+        if let Some(store) = &self.store {
+            store.save_portal(&portal)?;
+        }
+        self.portals.push(portal);
+        Ok(())
+    }
+
+    /// Remove a portal by ID
+    pub fn remove_portal(&mut self, id: Uuid) -> Option<Portal> {
+        let index = self.portals.iter().position(|portal| portal.id == id)?;
+        if let Some(store) = &self.store {
+            if let Err(e) = store.delete_portal(&id) {
+                log::warn!("Failed to delete portal {} from the store: {}", id, e);
+            }
+        }
+        Some(self.portals.remove(index))
+    }
+
+    /// All portals
+    pub fn portals(&self) -> &[Portal] {
+        &self.portals
+    }
+
+    /// Portal starting at a tile (if any)
+    pub fn portal_at(&self, zone_id: &str, x: usize, y: usize) -> Option<&Portal> {
+        self.portals.iter().find(|portal| portal.from_zone_id == zone_id && portal.from_x == x && portal.from_y == y)
+    }
+
+    /// Link an exit of one zone with the exit on the facing side of another, by a portal each way
+    ///
+    /// `direction` is the side of `from_zone_id` the link leaves from: connecting `North`
+    /// joins its North exit with the South exit of `to_zone_id`. Both exits must be
+    /// walkable by ground units. Returns the portal there and the portal back.
+    pub fn connect_zones(&mut self, from_zone_id: &str, direction: ExitDirection, to_zone_id: &str) -> Result<(Portal, Portal), String> {
+        let exit = |zone_id: &str, direction: ExitDirection| {
+            let zone = self.zones.get(zone_id)
+                .ok_or_else(|| format!("Zone {} not found", zone_id))?;
+            zone.exits.iter()
+                .find(|exit| exit.direction == direction)
+                .map(|exit| (exit.x, exit.y))
+                .ok_or_else(|| format!("Zone {} has no {:?} exit", zone_id, direction))
+        };
+        let (from_x, from_y) = exit(from_zone_id, direction)?;
+        let (to_x, to_y) = exit(to_zone_id, direction.opposite())?;
+
+        let there = Portal {
+            id: Uuid::new_v4(),
+            from_zone_id: from_zone_id.to_string(),
+            from_x,
+            from_y,
+            to_zone_id: to_zone_id.to_string(),
+            to_x,
+            to_y,
+        };
+        let back = Portal {
+            id: Uuid::new_v4(),
+            from_zone_id: to_zone_id.to_string(),
+            from_x: to_x,
+            from_y: to_y,
+            to_zone_id: from_zone_id.to_string(),
+            to_x: from_x,
+            to_y: from_y,
+        };
+        self.add_portal(there.clone())?;
+        if let Err(e) = self.add_portal(back.clone()) {
+            self.remove_portal(there.id);
+            return Err(e);
+        }
+        Ok((there, back))
+    }
+
+    /// IDs for `count` bulk-generated zones (`generated_0001`, ...), numbered after the existing ones
+    pub fn next_generated_zone_ids(&self, count: usize) -> Vec<String> {
+        let last = self.zones.ids().iter()
+            .filter_map(|zone_id| zone_id.strip_prefix("generated_")?.parse::<usize>().ok())
+            .max()
+            .unwrap_or(0);
+        (last + 1..=last + count).map(|n| format!("generated_{:04}", n)).collect()
+    }
+
+    /// Generate a zone for [`World::add_zone_grid`]; CPU-bound, and independent of any world
+    ///
+    /// The seed comes from the zone ID. A zone meant to be connected gets an exit on each
+    /// side, all walkable and reachable from each other: up to [`GRID_ZONE_ATTEMPTS`] seeds
+    /// are tried in turn until one gives such a zone.
+    pub fn generate_grid_zone(zone_id: &str, config: &ZoneGenConfig, connected: bool) -> Result<Zone, String> {
+        if !connected {
+            return Zone::generate_with_config(zone_id.to_string(), Self::hash_string(zone_id), config);
+        }
+
+        let config = ZoneGenConfig { min_exits: 4, max_exits: 4, ..config.clone() };
+        for attempt in 0..GRID_ZONE_ATTEMPTS {
+            let seed = Self::hash_string(zone_id).wrapping_add(attempt);
+            let zone = Zone::generate_with_config(zone_id.to_string(), seed, &config)?;
+            let exits_walkable = zone.exits.iter().all(|exit| {
+                zone.get_tile(exit.x, exit.y)
+                    .is_some_and(|tile| tile.surface_type.movement_cost_for(Mobility::GROUND).is_some())
+            });
+            if exits_walkable && zone.validate_connectivity().is_ok() {
+                return Ok(zone);
+            }
+        }
+        Err(format!("No seed out of {} gave zone {} connected exits on every side", GRID_ZONE_ATTEMPTS, zone_id))
+    }
+
+    /// Add new zones, and with `connect_exits` link them in a grid
+    ///
+    /// The zones fill rows of `ceil(sqrt(n))` zones, in order. Each zone is connected to
+    /// the next one in its row (its East exit to their West exit) and to the zone at the
+    /// same place in the next row (its North exit to their South exit); see
+    /// [`World::connect_zones`]. None of the zones may exist yet, and together they must
+    /// fit in `max_zones`. Returns the portals created.
+    pub fn add_zone_grid(&mut self, zones: Vec<Zone>, connect_exits: bool) -> Result<Vec<Portal>, String> {
+        if let Some(zone) = zones.iter().find(|zone| self.zones.contains_key(&zone.id)) {
+            return Err(format!("Zone {} already exists", zone.id));
+        }
+        if self.zones.len() + zones.len() > self.config.max_zones {
+            return Err(format!(
+                "World is full ({} zones): {} more do not fit next to the {} there",
+                self.config.max_zones, zones.len(), self.zones.len()
+            ));
+        }
+
+        let zone_ids: Vec<String> = zones.iter().map(|zone| zone.id.clone()).collect();
+        for zone in zones {
+            self.add_zone(zone);
+        }
+        if !connect_exits {
+            return Ok(Vec::new());
+        }
+
+        let columns = (1..).find(|columns| columns * columns >= zone_ids.len()).unwrap_or(1);
+        let mut portals = Vec::new();
+        for (index, zone_id) in zone_ids.iter().enumerate() {
+            let east = Some(index + 1).filter(|next| next % columns != 0);
+            let north = Some(index + columns);
+            for (direction, neighbour) in [(ExitDirection::East, east), (ExitDirection::North, north)] {
+                if let Some(neighbour) = neighbour.and_then(|neighbour| zone_ids.get(neighbour)) {
+                    let (there, back) = self.connect_zones(zone_id, direction, neighbour)?;
+                    portals.extend([there, back]);
+                }
+            }
+        }
+        Ok(portals)
+    }
+
+    /// Give a zone to a player
+    ///
+    /// Entities of teammates count together: the player's side needs at least one entity
+    /// in the zone, and no other side may have more. The new owner is awarded the
+    /// configured capture reward; capturing a zone the player's side already owns
+    /// changes nothing.
+    pub fn capture_zone(&mut self, zone_id: &str, player_id: &str) -> Result<(), CaptureError> {
+        let zone = self.zones.get(zone_id)
+            .ok_or_else(|| CaptureError::ZoneNotFound(zone_id.to_string()))?;
+
+        let side = self.side_of(player_id);
+        let mut presence: HashMap<Side, usize> = HashMap::new();
+        for owner in zone.entities.iter().filter_map(|entity| entity.owner.as_deref()) {
+            *presence.entry(self.side_of(owner)).or_default() += 1;
+        }
+        let own = presence.get(&side).copied().unwrap_or(0);
+        if own == 0 {
+            return Err(CaptureError::InsufficientPresence);
+        }
+        if presence.values().any(|&count| count > own) {
+            return Err(CaptureError::Contested);
+        }
+        if zone.owner.as_deref().is_some_and(|owner| self.side_of(owner) == side) {
+            return Ok(());
+        }
+        drop(zone);
+
+        let rewards = self.config.zone_capture_reward_resources.clone();
+        let entry = self.log_action(GameAction::ZoneCaptured {
+            zone_id: zone_id.to_string(),
+            player_id: player_id.to_string(),
+            rewards: rewards.iter().map(|(&resource, &amount)| (resource, amount)).collect(),
+        });
+        self.contents_mut(zone_id).expect("zone checked above").owner = Some(player_id.to_string());
+        self.persist_zone(zone_id);
+        for (resource, amount) in rewards {
+            self.deposit_resources(player_id, resource, amount);
+        }
+        self.commit_action(entry);
+        Ok(())
+    }
+
+    /// Require a player to have cleared every zone of `prerequisites` before `zone_id`
+    /// is unlocked for them; no prerequisites unlock the zone for everyone
+    pub fn set_zone_unlock_requirements(&mut self, zone_id: &str, prerequisites: Vec<String>) {
+        if prerequisites.is_empty() {
+            self.zone_unlock_requirements.remove(zone_id);
+        } else {
+            self.zone_unlock_requirements.insert(zone_id.to_string(), prerequisites);
+        }
+    }
+
+    /// Zones a player must have cleared before `zone_id` is unlocked for them
+    pub fn zone_unlock_requirements(&self, zone_id: &str) -> &[String] {
+        self.zone_unlock_requirements.get(zone_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether a player has cleared every zone `zone_id` requires (zones without
+    /// requirements are always unlocked; the zone does not need to exist)
+    pub fn is_zone_unlocked(&self, player_id: &str, zone_id: &str) -> bool {
+        self.zone_unlock_requirements(zone_id).iter()
+            .all(|prerequisite| self.is_zone_cleared(player_id, prerequisite))
+    }
+
+    /// Record that a player cleared a zone: every enemy in it eliminated and its
+    /// objective completed
+    ///
+    /// Zones requiring it may then unlock for the player (see [`World::is_zone_unlocked`]).
+    pub fn mark_zone_cleared(&mut self, player_id: &str, zone_id: &str) {
+        self.cleared_zones.entry(player_id.to_string()).or_default().insert(zone_id.to_string());
+    }
+
+    /// Whether a player has cleared a zone
+    pub fn is_zone_cleared(&self, player_id: &str, zone_id: &str) -> bool {
+        self.cleared_zones.get(player_id).is_some_and(|zones| zones.contains(zone_id))
+    }
+
+    /// Owner of a zone (`None` if the zone does not exist or is not owned)
+    pub fn zone_owner(&self, zone_id: &str) -> Option<String> {
+        self.with_zone_read(zone_id, |zone| zone.owner.clone()).flatten()
+    }
+
+    /// Resources available to a player (their team's pool if they are in a team)
+    pub fn stockpile(&self, player_id: &str) -> HashMap<ResourceType, u32> {
+        let stockpile = match self.team_of(player_id) {
+            Some(team_id) => self.team_pools.get(&team_id),
+            None => self.stockpiles.get(player_id),
+        };
+        stockpile.cloned().unwrap_or_default()
+    }
+
+    /// Add collected resources to a player's stockpile, or to their team's pool
+    pub fn deposit_resources(&mut self, player_id: &str, resource: ResourceType, amount: u32) {
+        let stockpile = match self.team_of(player_id) {
+            Some(team_id) => self.team_pools.entry(team_id).or_default(),
+            None => self.stockpiles.entry(player_id.to_string()).or_default(),
+        };
+        let total = stockpile.entry(resource).or_default();
+        *total = total.saturating_add(amount);
+    }
+
+    /// Take resources from a player's stockpile, or from their team's pool
+    ///
+    /// Fails without taking anything if they hold less than `amount`.
+    pub fn withdraw_resources(&mut self, player_id: &str, resource: ResourceType, amount: u32) -> Result<(), String> {
+        let stockpile = match self.team_of(player_id) {
+            Some(team_id) => self.team_pools.entry(team_id).or_default(),
+            None => self.stockpiles.entry(player_id.to_string()).or_default(),
+        };
+        let total = stockpile.entry(resource).or_default();
+        if *total < amount {
+            return Err(format!("Not enough {:?}: {} held, {} needed", resource, total, amount));
+        }
+        *total -= amount;
+        Ok(())
+    }
+
+    /// Set the members of a team (an empty list disbands it and drops its pool)
+    ///
+    /// Players joining the team add their own stockpile to the team's pool.
+    pub fn set_team_members(&mut self, team_id: Uuid, members: Vec<String>) {
+        if members.is_empty() {
+            self.teams.remove(&team_id);
+            self.team_pools.remove(&team_id);
+            return;
+        }
+
+        for member in &members {
+            if let Some(previous) = self.team_of(member).filter(|previous| *previous != team_id) {
+                if let Some(others) = self.teams.get_mut(&previous) {
+                    others.retain(|other| other != member);
+                }
+            }
+            if let Some(stockpile) = self.stockpiles.remove(member) {
+                let pool = self.team_pools.entry(team_id).or_default();
+                for (resource, amount) in stockpile {
+                    let total = pool.entry(resource).or_default();
+                    *total = total.saturating_add(amount);
+                }
+            }
+        }
+        self.teams.insert(team_id, members);
+    }
+
+    /// Team of a player (if any)
+    pub fn team_of(&self, player_id: &str) -> Option<Uuid> {
+        self.teams.iter()
+            .find(|(_, members)| members.iter().any(|member| member == player_id))
+            .map(|(&team_id, _)| team_id)
+    }
+
+    /// Members of a team (empty if the team is unknown)
+    pub fn team_members(&self, team_id: &Uuid) -> &[String] {
+        self.teams.get(team_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Resources shared by the members of a team
+    pub fn team_pool(&self, team_id: &Uuid) -> HashMap<ResourceType, u32> {
+        self.team_pools.get(team_id).cloned().unwrap_or_default()
+    }
+
+    /// Set the members of an alliance (an empty list disbands it)
+    ///
+    /// Players joining become allies at once. A change dropping a member (leaving, or the
+    /// alliance disbanding) is applied at the end of the current tick, so no tick sees
+    /// them as allies for part of it.
+    pub fn set_alliance_members(&mut self, alliance_id: Uuid, members: Vec<String>) {
+        let current = self.alliances.get(&alliance_id).map_or(&[][..], Vec::as_slice);
+        if current.iter().all(|member| members.contains(member)) && !self.pending_alliances.contains_key(&alliance_id) {
+            self.alliances.insert(alliance_id, members);
+        } else {
+            self.pending_alliances.insert(alliance_id, members);
+        }
+    }
+
+    /// Apply the alliance changes deferred to the end of the tick
+    fn tick_alliances(&mut self) {
+        for (alliance_id, members) in std::mem::take(&mut self.pending_alliances) {
+            if members.is_empty() {
+                self.alliances.remove(&alliance_id);
+            } else {
+                self.alliances.insert(alliance_id, members);
+            }
+        }
+    }
+
+    /// Alliance of a player (if any)
+    pub fn alliance_of(&self, player_id: &str) -> Option<Uuid> {
+        self.alliances.iter()
+            .find(|(_, members)| members.iter().any(|member| member == player_id))
+            .map(|(&alliance_id, _)| alliance_id)
+    }
+
+    /// The other members of a player's alliance
+    pub fn allies_of(&self, player_id: &str) -> Vec<&String> {
+        self.alliance_of(player_id)
+            .map(|alliance_id| self.alliances[&alliance_id].iter().filter(|member| *member != player_id).collect())
+            .unwrap_or_default()
+    }
+
+    /// Whether two different players are in the same alliance
+    pub fn are_allied(&self, player_id: &str, other_id: &str) -> bool {
+        player_id != other_id && self.alliances.values()
+            .any(|members| members.iter().any(|m| m == player_id) && members.iter().any(|m| m == other_id))
+    }
+
+    /// Every zone as seen by a player, sorted by ID
+    ///
+    /// Unit and structure counts are only given for zones owned by the player or an ally.
+    pub fn map_for(&self, player_id: &str) -> Vec<ZoneSummary> {
+        self.zones.values().iter()
+            .map(|zone| {
+                let visible = zone.owner.as_deref()
+                    .is_some_and(|owner| owner == player_id || self.are_allied(player_id, owner));
+                let structures = zone.entities.iter().filter(|entity| entity.is_structure()).count();
+                ZoneSummary {
+                    zone_id: zone.id.clone(),
+                    position: self.zone_position(&zone.id),
+                    owner: zone.owner.clone(),
+                    units: visible.then(|| zone.entities.len() - structures),
+                    structures: visible.then_some(structures),
+                }
+            })
+            .collect()
+    }
+
+    /// Place a market order buying or selling `amount` of a resource at `price` credits per unit
+    ///
+    /// Nothing is escrowed, but the player must hold the resource (to sell) or the
+    /// credits (to buy) when placing the order. It expires after `market_order_ttl_ticks`.
+    pub fn place_order(&mut self, player_id: &str, side: OrderSide, resource: ResourceType, amount: u32, price: u32) -> Result<MarketOrder, String> {
+        if resource == ResourceType::Credits {
+            return Err("Credits cannot be traded for credits".to_string());
+        }
+        if amount == 0 || price == 0 {
+            return Err("Amount and price must be positive".to_string());
+        }
+        let (needed, needed_amount) = match side {
+            OrderSide::Sell => (resource, amount),
+            OrderSide::Buy => (ResourceType::Credits, amount.checked_mul(price).ok_or("Order total is too large")?),
+        };
+        let held = self.balance(player_id, needed);
+        if held < needed_amount {
+            return Err(format!("Not enough {:?}: {} held, {} needed", needed, held, needed_amount));
+        }
+
+        let order = MarketOrder {
+            id: Uuid::new_v4(),
+            player_id: player_id.to_string(),
+            side,
+            resource,
+            amount,
+            remaining: amount,
+            price,
+            created_tick: self.tick,
+            expires_tick: self.tick.saturating_add(self.config.market_order_ttl_ticks),
+        };
+        self.market.place(order.clone());
+        Ok(order)
+    }
+
+    /// Cancel one of a player's market orders
+    pub fn cancel_order(&mut self, player_id: &str, order_id: Uuid) -> Result<MarketOrder, String> {
+        self.market.cancel(player_id, order_id)
+    }
+
+    /// Open market orders, oldest first
+    pub fn market_orders(&self) -> &[MarketOrder] {
+        self.market.orders()
+    }
+
+    /// Expire old market orders and match the others, emitting a [`WorldEvent::TradeExecuted`] per fill
+    fn tick_market(&mut self) {
+        let mut market = std::mem::take(&mut self.market);
+        for order in market.expire(self.tick) {
+            log::debug!("Market order {} of {} expired", order.id, order.player_id);
+        }
+        let (trades, cancelled) = market.match_orders(self);
+        self.market = market;
+
+        for order in cancelled {
+            log::debug!("Market order {} of {} cancelled: not enough resources to fill it", order.id, order.player_id);
+        }
+        for trade in trades {
+            self.events.push(WorldEvent::TradeExecuted { trade, tick: self.tick });
+        }
+    }
+
+    /// Players owning at least one entity in a zone (sorted, without duplicates)
+    pub fn players_in_zone(&self, zone_id: &str) -> Vec<String> {
+        let Some(zone) = self.zones.get(zone_id) else {
+            return Vec::new();
+        };
+        let mut players: Vec<String> = zone.entities.iter()
+            .filter_map(|entity| entity.owner.clone())
+            .collect();
+        players.sort();
+        players.dedup();
+        players
+    }
+
+    /// Zones where a player owns at least one entity (sorted)
+    pub fn zones_of_player(&self, player_id: &str) -> Vec<String> {
+        self.zones.values().iter()
+            .filter(|zone| zone.entities.iter().any(|entity| entity.owner.as_deref() == Some(player_id)))
+            .map(|zone| zone.id.clone())
+            .collect()
+    }
+
+    /// Number of zones owned by members of a team
+    pub fn team_zone_count(&self, team_id: &Uuid) -> usize {
+        let members = self.team_members(team_id);
+        self.zones.values().iter()
+            .filter(|zone| zone.owner.as_ref().is_some_and(|owner| members.contains(owner)))
+            .count()
+    }
+
+    fn side_of<'a>(&self, player_id: &'a str) -> Side<'a> {
+        match self.team_of(player_id) {
+            Some(team_id) => Side::Team(team_id),
+            None => Side::Player(player_id),
+        }
+    }
+
+    /// Move an entity to a tile of its zone it can enter (see [`Mobility`])
+    ///
+    /// Stepping onto a portal tile moves the entity to the portal's destination tile
+    /// (it may get a new ID if its ID is taken in the destination zone). Returns the
+    /// entity's resulting zone ID, ID, and position.
+    pub fn move_entity(&mut self, zone_id: &str, entity_id: u32, x: usize, y: usize) -> Result<(String, u32, usize, usize), String> {
+        let zone = self.zones.get(zone_id)
+            .ok_or_else(|| format!("Zone {} not found", zone_id))?;
+        let index = zone.entities.iter().position(|entity| entity.id == entity_id)
+            .ok_or_else(|| format!("Entity {} not found in zone {}", entity_id, zone_id))?;
+        let mobility = zone.entities[index].mobility();
+        drop(zone);
+        self.check_walkable(zone_id, x, y, mobility)?;
+        let entry = self.log_action(GameAction::EntityMoved { zone_id: zone_id.to_string(), entity_id, x, y });
+
+        let Some(portal) = self.portal_at(zone_id, x, y).cloned() else {
+            let mut zone = self.contents_mut(zone_id).expect("zone checked above");
+            let entity = &mut zone.entities[index];
+            entity.x = x;
+            entity.y = y;
+            drop(zone);
+            self.commit_action(entry);
+            return Ok((zone_id.to_string(), entity_id, x, y));
+        };
+
+        let destination = self.zones.get(&portal.to_zone_id)
+            .ok_or_else(|| format!("Portal destination zone {} not found", portal.to_zone_id))?;
+        let new_id = if destination.entities.iter().any(|entity| entity.id == entity_id) {
+            destination.entities.iter().map(|entity| entity.id).max().unwrap_or(0) + 1
+        } else {
+            entity_id
+        };
+        drop(destination);
+
+        let mut entity = self.contents_mut(zone_id).expect("zone checked above").entities.remove(index);
+        entity.id = new_id;
+        entity.x = portal.to_x;
+        entity.y = portal.to_y;
+        self.contents_mut(&portal.to_zone_id).expect("zone checked above").entities.push(entity);
+        self.commit_action(entry);
+
+        Ok((portal.to_zone_id, new_id, portal.to_x, portal.to_y))
+    }
+
+    /// Fail unless the tile exists and a unit with the given capabilities can enter it
+    fn check_walkable(&self, zone_id: &str, x: usize, y: usize, mobility: Mobility) -> Result<(), String> {
+        let zone = self.zones.get(zone_id)
+            .ok_or_else(|| format!("Zone {} not found", zone_id))?;
+        let tile = zone.get_tile(x, y)
+            .ok_or_else(|| format!("Tile ({}, {}) is outside zone {}", x, y, zone_id))?;
+
+        match tile.surface_type {
+            SurfaceType::Obstacle => Err(format!("Tile ({}, {}) in zone {} is an obstacle", x, y, zone_id)),
+            surface if surface.movement_cost_for(mobility).is_none() => {
+                Err(format!("Tile ({}, {}) in zone {} is {:?} and cannot be entered", x, y, zone_id, surface))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Save the world (tick, zones, portals) to a JSON file
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize world: {}", e))?;
+
+        fs::write(path, json)
+            .map_err(|e| format!("Failed to write world file: {}", e))
+    }
+
+    /// Load a world saved with [`World::save`]
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read world file: {}", e))?;
+
+        serde_json::from_str(&json)
+            .map_err(|e| format!("Failed to deserialize world: {}", e))
+    }
+
+    /// Number of snapshot fragments built so far (see [`crate::game::snapshot_cache`])
+    pub fn snapshot_cache_builds(&self) -> CacheBuilds {
+        self.snapshot_cache.builds()
+    }
+
+    /// Drop the cached snapshot fragments; the next snapshots build them again
+    pub fn clear_snapshot_cache(&self) {
+        self.snapshot_cache.clear();
+    }
+
+    /// Build the JSON snapshot of a player's view passed to their script
+    ///
+    /// Contains the script tick, phase of the day, visibility radius (including fog), map size, the obstacles of the player's zone (if generated), and the player's stockpile.
+    /// `units` and `structures` list the player's own entities in every zone (unit IDs are `"<zone_id>:<entity_id>"`).
+    /// Team and alliance members share visibility: `team` lists the teammates, `allies` the
+    /// other members of the player's alliance, and `allied_units` the entities of both in every zone.
+    /// `events` holds the events the player can see from the simulation ticks since the previous script tick.
+    /// `enemy_units` and `enemy_structures` (entities of other players, but not teammates or
+    /// allies) and `resources` (deposits, IDs `"<zone_id>:<x>:<y>"`) only list what is within the visibility
+    /// radius of the player's or their allies' entities, like events.
+    ///
+    /// Terrain, entity and deposit lists come from the world's [`SnapshotCache`], built again
+    /// only for the zones that changed.
+    pub fn player_snapshot(&self, player_id: &str) -> serde_json::Value {
+        let zone_id = format!("player_{}_zone", player_id);
+        let terrain = self.snapshot_cache.terrain(&self.zones, &zone_id);
+        let (width, height) = terrain.as_ref().map_or((ZONE_SIZE, ZONE_SIZE), |terrain| (terrain.width, terrain.height));
+        let obstacles = terrain.map_or_else(|| serde_json::json!([]), |terrain| terrain.obstacles.clone());
+
+        let team_id = self.team_of(player_id);
+        let teammates: Vec<&String> = team_id
+            .map(|team_id| self.team_members(&team_id).iter().filter(|member| *member != player_id).collect())
+            .unwrap_or_default();
+        let allies = self.allies_of(player_id);
+        let contents = self.snapshot_cache.contents(&self.zones, self.zone_version.last());
+        let entities = || contents.values().flat_map(|zone| zone.entities.iter());
+        let allied_units: Vec<serde_json::Value> = entities()
+            .filter(|entity| entity.owner.as_ref().is_some_and(|owner| teammates.contains(&owner) || allies.contains(&owner)))
+            .map(|entity| entity.allied.clone())
+            .collect();
+
+        let listed = |entity: &EntityFragment| {
+            let mut listed = entity.listed.clone();
+            if self.is_moving(&entity.zone_id, entity.entity_id) {
+                listed["action"] = serde_json::json!("moving");
+            }
+            listed
+        };
+        let (structures, units): (Vec<_>, Vec<_>) = entities()
+            .filter(|entity| entity.owner.as_deref() == Some(player_id))
+            .partition(|entity| entity.structure);
+        let units: Vec<serde_json::Value> = units.into_iter().map(listed).collect();
+        let structures: Vec<serde_json::Value> = structures.into_iter().map(listed).collect();
+
+        // Nothing is in sight in zones without observers
+        let observers = self.observers(player_id);
+        let observed = || contents.values().filter(|zone| observers.contains_key(&zone.zone_id));
+        let (enemy_structures, enemy_units): (Vec<_>, Vec<_>) = observed()
+            .flat_map(|zone| zone.entities.iter())
+            .filter(|entity| entity.owner.as_ref().is_some_and(|owner| {
+                owner != player_id && !teammates.contains(&owner) && !allies.contains(&owner)
+            }))
+            .filter(|entity| self.in_sight(&observers, &entity.zone_id, entity.x, entity.y))
+            .partition(|entity| entity.structure);
+        let enemy_units: Vec<serde_json::Value> = enemy_units.into_iter().map(listed).collect();
+        let enemy_structures: Vec<serde_json::Value> = enemy_structures.into_iter().map(listed).collect();
+        let resources: Vec<serde_json::Value> = observed()
+            .flat_map(|zone| zone.resources.iter().map(move |deposit| (zone, deposit)))
+            .filter(|(zone, deposit)| self.in_sight(&observers, &zone.zone_id, deposit.x, deposit.y))
+            .map(|(_, deposit)| deposit.listed.clone())
+            .collect();
+
+        let mut snapshot = serde_json::json!({
+            "tick": self.script_tick,
+            "day_phase": self.world_clock.phase,
+            "visibility_radius": self.visibility_radius(&zone_id),
+            "player_id": player_id,
+            "zone_id": zone_id,
+            "map_size": {"width": width, "height": height},
+            "stockpile": self.stockpile(player_id),
+            "defeated": self.is_defeated(player_id),
+            "respawn_tick": self.players.get(player_id)
+                .and_then(|record| record.defeated_at)
+                .map(|tick| tick + self.config.respawn_cooldown_ticks),
+            "defeats": self.players.get(player_id).map_or(0, |record| record.defeats),
+            "tech": self.tech_status(player_id),
+            "command_errors": self.command_errors.get(player_id).cloned().unwrap_or_default(),
+            "objectives": self.objectives(player_id),
+            "team": team_id.map(|team_id| serde_json::json!({"id": team_id, "teammates": teammates})),
+            "allies": allies,
+            "events": self.events_in_sight(player_id, &observers, self.tick.saturating_sub(self.config.script_tick_interval), MAX_SCRIPT_EVENTS),
+        });
+        // Moved in rather than through `json!`, which would copy them once more
+        for (key, list) in [
+            ("obstacles", obstacles),
+            ("allied_units", allied_units.into()),
+            ("units", units.into()),
+            ("enemy_units", enemy_units.into()),
+            ("enemy_structures", enemy_structures.into()),
+            ("resources", resources.into()),
+            ("structures", structures.into()),
+        ] {
+            snapshot[key] = list;
+        }
+        snapshot
     }
 
     /// Simple string hash function for seed generation
@@ -73,4 +2574,18 @@ impl Default for World {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+impl Ledger for World {
+    fn balance(&self, player_id: &str, resource: ResourceType) -> u32 {
+        self.stockpile(player_id).get(&resource).copied().unwrap_or(0)
+    }
+
+    fn withdraw(&mut self, player_id: &str, resource: ResourceType, amount: u32) -> Result<(), String> {
+        self.withdraw_resources(player_id, resource, amount)
+    }
+
+    fn deposit(&mut self, player_id: &str, resource: ResourceType, amount: u32) {
+        self.deposit_resources(player_id, resource, amount);
+    }
+}
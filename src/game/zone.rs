@@ -1,14 +1,57 @@
 //! Zone generation module for procedural landscape generation
 //! 
 //! Each player starts in their own 30x30 tile zone with procedurally generated terrain.
-//! Zones feature three surface types (Plain, Swamp, Obstacle) and 2-4 exits for future interconnection.
+//! Zones feature four surface types (Plain, Swamp, Water, Obstacle) and 2-4 exits for future interconnection.
+//! Size, terrain distribution, and exit count can be changed with a [`ZoneGenConfig`].
+//! Terrain is drawn from smooth fractal noise, so swamps, lakes, and obstacle ridges form
+//! contiguous regions; [`TerrainStyle::Legacy`] reproduces zones generated before that.
+//!
+//! Zones serialize in a compact form ([`CompactZone`]): each row of tiles is a string with
+//! one character per tile (`P` Plain, `S` Swamp, `W` Water, `O` Obstacle). The older
+//! format with one `{x, y, surface_type}` object per tile is still accepted when loading.
+//! Hand-authored zones are loaded from map files by the [`template`] module, and zones
+//! are shared between servers as [`export`] files. Admins tweak existing zones with the
+//! [`edit`] module.
 
-use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+pub mod edit;
+pub mod export;
+pub mod noise;
+pub mod template;
 
-/// Size of each zone in tiles
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{HashMap, HashSet};
+
+use crate::game::clock::DayPhase;
+use crate::game::pathfinding;
+
+/// Default width and height of a zone in tiles
 pub const ZONE_SIZE: usize = 30;
 
+/// Smallest allowed zone width or height
+pub const MIN_ZONE_SIZE: usize = 8;
+
+/// Largest allowed zone width or height
+pub const MAX_ZONE_SIZE: usize = 256;
+
+/// Largest allowed number of exits
+pub const MAX_ZONE_EXITS: usize = 8;
+
+/// Largest number of spawn points designated in a zone
+pub const MAX_SPAWN_POINTS: usize = 4;
+
+/// Smallest distance, in tiles, from a spawn point to the edge of its zone
+pub const SPAWN_POINT_MARGIN: usize = 2;
+
+/// Total amount of resource deposits from which a zone no longer counts as scarce
+/// (see [`Zone::rate_difficulty`])
+pub const RICH_ZONE_RESOURCES: u32 = 2000;
+
+/// Hit points of a newly placed entity
+pub const DEFAULT_ENTITY_HITS: u32 = 100;
+
+/// Entity kinds that are buildings rather than units
+pub const STRUCTURE_KINDS: &[&str] = &["base", "turret", "factory", "tower"];
+
 /// Surface types that can appear in a zone
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SurfaceType {
@@ -16,12 +59,71 @@ pub enum SurfaceType {
     Plain,
     /// Swamp surface - walkable, slower movement
     Swamp,
+    /// Water - only swimming and flying units can cross it
+    Water,
     /// Obstacle - not walkable, blocks movement
     Obstacle,
 }
 
+/// Cost of entering a Water tile for a swimming unit
+pub const WATER_SWIM_COST: u32 = 3;
+
+/// Movement capabilities of a unit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Mobility {
+    /// Can cross Water tiles
+    pub can_swim: bool,
+    /// Can cross every tile but obstacles, at the Plain cost
+    pub can_fly: bool,
+}
+
+impl Mobility {
+    /// A unit that can only walk
+    pub const GROUND: Mobility = Mobility { can_swim: false, can_fly: false };
+}
+
+impl SurfaceType {
+    /// Character representing this surface in compact tile rows
+    pub fn code(&self) -> char {
+        match self {
+            SurfaceType::Plain => 'P',
+            SurfaceType::Swamp => 'S',
+            SurfaceType::Water => 'W',
+            SurfaceType::Obstacle => 'O',
+        }
+    }
+
+    /// Surface represented by a compact tile row character
+    pub fn from_code(code: char) -> Option<SurfaceType> {
+        match code {
+            'P' => Some(SurfaceType::Plain),
+            'S' => Some(SurfaceType::Swamp),
+            'W' => Some(SurfaceType::Water),
+            'O' => Some(SurfaceType::Obstacle),
+            _ => None,
+        }
+    }
+
+    /// Base cost of entering a tile of this surface for a ground unit (`None` if not walkable)
+    pub fn movement_cost(&self) -> Option<u32> {
+        self.movement_cost_for(Mobility::GROUND)
+    }
+
+    /// Base cost of entering a tile of this surface for a unit (`None` if it cannot enter)
+    pub fn movement_cost_for(&self, mobility: Mobility) -> Option<u32> {
+        match self {
+            SurfaceType::Obstacle => None,
+            _ if mobility.can_fly => Some(2),
+            SurfaceType::Plain => Some(2),
+            SurfaceType::Swamp => Some(4),
+            SurfaceType::Water if mobility.can_swim => Some(WATER_SWIM_COST),
+            SurfaceType::Water => None,
+        }
+    }
+}
+
 /// Represents a single tile in a zone
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Tile {
     /// X coordinate within the zone (0-29)
     pub x: usize,
@@ -32,7 +134,7 @@ pub struct Tile {
 }
 
 /// Represents an exit point from a zone
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Exit {
     /// X coordinate of the exit
     pub x: usize,
@@ -45,39 +147,421 @@ pub struct Exit {
 /// Cardinal directions for exits
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ExitDirection {
+    /// Top edge (y = 0)
     North,
+    /// Bottom edge
     South,
+    /// Right edge
     East,
+    /// Left edge (x = 0)
     West,
 }
 
+impl ExitDirection {
+    /// Edge facing this one
+    pub fn opposite(self) -> Self {
+        match self {
+            ExitDirection::North => ExitDirection::South,
+            ExitDirection::South => ExitDirection::North,
+            ExitDirection::East => ExitDirection::West,
+            ExitDirection::West => ExitDirection::East,
+        }
+    }
+}
+
+/// An entity (unit or structure) placed in a zone
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntityRef {
+    /// Entity identifier, unique within the zone
+    pub id: u32,
+    /// Entity kind (`worker`, `soldier`, `base`, ...)
+    pub kind: String,
+    /// Owning player (if any)
+    pub owner: Option<String>,
+    /// X coordinate within the zone
+    pub x: usize,
+    /// Y coordinate within the zone
+    pub y: usize,
+    /// Remaining hit points
+    #[serde(default = "default_entity_hits")]
+    pub hits: u32,
+    /// Can cross Water tiles
+    #[serde(default)]
+    pub can_swim: bool,
+    /// Can cross every tile but obstacles
+    #[serde(default)]
+    pub can_fly: bool,
+}
+
+impl EntityRef {
+    /// Movement capabilities of the entity
+    pub fn mobility(&self) -> Mobility {
+        Mobility {
+            can_swim: self.can_swim,
+            can_fly: self.can_fly,
+        }
+    }
+
+    /// Whether the entity is a building (see [`STRUCTURE_KINDS`])
+    pub fn is_structure(&self) -> bool {
+        STRUCTURE_KINDS.contains(&self.kind.as_str())
+    }
+}
+
+fn default_entity_hits() -> u32 {
+    DEFAULT_ENTITY_HITS
+}
+
+/// Kind of resource held in a player's stockpile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceType {
+    /// Minerals
+    Minerals,
+    /// Gas
+    Gas,
+    /// Credits, the currency of the market (never found in deposits)
+    Credits,
+}
+
+/// A harvestable resource deposit in a zone
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceDeposit {
+    /// X coordinate within the zone
+    pub x: usize,
+    /// Y coordinate within the zone
+    pub y: usize,
+    /// Remaining amount
+    pub amount: u32,
+}
+
+/// Terrain generation algorithm
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TerrainStyle {
+    /// Independent value per tile (zones generated before smooth terrain)
+    Legacy,
+    /// Fractal value noise: contiguous swamps, lakes, and obstacle ridges
+    #[default]
+    Smooth,
+}
+
+/// Parameters for procedural zone generation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ZoneGenConfig {
+    /// Width in tiles
+    pub width: usize,
+    /// Height in tiles
+    pub height: usize,
+    /// Share of Plain tiles
+    pub plain_ratio: f64,
+    /// Share of Swamp tiles
+    pub swamp_ratio: f64,
+    /// Share of Water tiles
+    #[serde(default)]
+    pub water_ratio: f64,
+    /// Share of Obstacle tiles
+    pub obstacle_ratio: f64,
+    /// Minimum number of exits
+    pub min_exits: usize,
+    /// Maximum number of exits
+    pub max_exits: usize,
+    /// Terrain generation algorithm
+    #[serde(default)]
+    pub terrain_style: TerrainStyle,
+    /// Noise lattice cells per tile for the first octave (smaller = larger regions)
+    #[serde(default = "default_noise_frequency")]
+    pub noise_frequency: f64,
+    /// Number of noise octaves
+    #[serde(default = "default_noise_octaves")]
+    pub noise_octaves: u32,
+}
+
+fn default_noise_frequency() -> f64 {
+    0.08
+}
+
+fn default_noise_octaves() -> u32 {
+    4
+}
+
+impl ZoneGenConfig {
+    /// Default configuration with the legacy terrain algorithm
+    pub fn legacy() -> Self {
+        Self {
+            terrain_style: TerrainStyle::Legacy,
+            ..Self::default()
+        }
+    }
+
+    /// Check sizes, ratios, and exit counts
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, size) in [("width", self.width), ("height", self.height)] {
+            if !(MIN_ZONE_SIZE..=MAX_ZONE_SIZE).contains(&size) {
+                return Err(format!("Zone {} must be between {} and {}, got {}", name, MIN_ZONE_SIZE, MAX_ZONE_SIZE, size));
+            }
+        }
+
+        let ratios = [self.plain_ratio, self.swamp_ratio, self.water_ratio, self.obstacle_ratio];
+        if ratios.iter().any(|ratio| !ratio.is_finite() || *ratio < 0.0) {
+            return Err("Terrain ratios must be non-negative numbers".to_string());
+        }
+        let total: f64 = ratios.iter().sum();
+        if (total - 1.0).abs() > 1e-6 {
+            return Err(format!("Terrain ratios must sum to 1, got {}", total));
+        }
+
+        if !(self.noise_frequency > 0.0 && self.noise_frequency <= 1.0) {
+            return Err(format!("Noise frequency must be in (0, 1], got {}", self.noise_frequency));
+        }
+        if !(1..=8).contains(&self.noise_octaves) {
+            return Err(format!("Noise octaves must be between 1 and 8, got {}", self.noise_octaves));
+        }
+
+        if self.min_exits == 0 || self.min_exits > self.max_exits || self.max_exits > MAX_ZONE_EXITS {
+            return Err(format!(
+                "Exit counts must satisfy 1 <= min_exits <= max_exits <= {}, got {}..={}",
+                MAX_ZONE_EXITS, self.min_exits, self.max_exits
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for ZoneGenConfig {
+    /// 30x30 smooth tiles, ~60% Plain, ~25% Swamp, ~15% Obstacle, no Water, 2-4 exits
+    fn default() -> Self {
+        Self {
+            width: ZONE_SIZE,
+            height: ZONE_SIZE,
+            plain_ratio: 0.60,
+            swamp_ratio: 0.25,
+            water_ratio: 0.0,
+            obstacle_ratio: 0.15,
+            min_exits: 2,
+            max_exits: 4,
+            terrain_style: TerrainStyle::Smooth,
+            noise_frequency: default_noise_frequency(),
+            noise_octaves: default_noise_octaves(),
+        }
+    }
+}
+
 /// Represents a procedurally generated zone
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Serialized as a [`CompactZone`].
+#[derive(Debug, Clone)]
 pub struct Zone {
     /// Unique identifier for this zone
     pub id: String,
-    /// 2D grid of tiles (30x30)
+    /// Width in tiles
+    pub width: usize,
+    /// Height in tiles
+    pub height: usize,
+    /// 2D grid of tiles (`height` rows of `width` tiles)
     pub tiles: Vec<Vec<Tile>>,
-    /// List of exits (2-4 per zone)
+    /// List of exits (2-4 per zone by default)
+    pub exits: Vec<Exit>,
+    /// Entities in the zone
+    pub entities: Vec<EntityRef>,
+    /// Resource deposits in the zone
+    pub resources: Vec<ResourceDeposit>,
+    /// Player who captured the zone (if any)
+    pub owner: Option<String>,
+    /// Changes whenever the world changes the zone (not serialized; see
+    /// [`World::with_zone_write`](crate::game::world::World::with_zone_write))
+    pub version: u64,
+    /// The [`Zone::version`] at which the tiles last may have changed (not serialized);
+    /// moves, combat, harvesting, construction and captures leave it unchanged
+    pub terrain_version: u64,
+    /// Unused starting positions for players, near the exits (not serialized; designated
+    /// again from the terrain when a zone is loaded, see [`Zone::get_spawn_point`])
+    pub spawn_points: Vec<(usize, usize)>,
+    /// How hard the zone is, from 0.0 to 1.0 (not serialized; rated again from the
+    /// terrain and resources when a zone is loaded, see [`Zone::rate_difficulty`])
+    pub difficulty: f32,
+}
+
+/// Zones are equal if their contents are; [`Zone::version`], [`Zone::terrain_version`],
+/// [`Zone::spawn_points`] and [`Zone::difficulty`] are ignored
+impl PartialEq for Zone {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+            && self.width == other.width
+            && self.height == other.height
+            && self.tiles == other.tiles
+            && self.exits == other.exits
+            && self.entities == other.entities
+            && self.resources == other.resources
+            && self.owner == other.owner
+    }
+}
+
+impl Eq for Zone {}
+
+/// Serialized form of a [`Zone`], with one string of surface codes per row of tiles
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactZone {
+    /// Unique identifier for this zone
+    pub id: String,
+    /// Width in tiles
+    pub width: usize,
+    /// Height in tiles
+    pub height: usize,
+    /// Rows of tiles, one [`SurfaceType::code`] per tile
+    pub tiles: Vec<String>,
+    /// List of exits
     pub exits: Vec<Exit>,
+    /// Entities in the zone
+    #[serde(default)]
+    pub entities: Vec<EntityRef>,
+    /// Resource deposits in the zone
+    #[serde(default)]
+    pub resources: Vec<ResourceDeposit>,
+    /// Player who captured the zone (if any)
+    #[serde(default)]
+    pub owner: Option<String>,
+}
+
+/// Tile rows in either serialized format
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TileRows {
+    Compact(Vec<String>),
+    Verbose(Vec<Vec<Tile>>),
+}
+
+/// Any serialized zone: compact, or verbose as saved before the compact format
+#[derive(Deserialize)]
+struct SerializedZone {
+    id: String,
+    width: Option<usize>,
+    height: Option<usize>,
+    tiles: TileRows,
+    exits: Vec<Exit>,
+    #[serde(default)]
+    entities: Vec<EntityRef>,
+    #[serde(default)]
+    resources: Vec<ResourceDeposit>,
+    #[serde(default)]
+    owner: Option<String>,
+}
+
+impl Serialize for Zone {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_compact().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Zone {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let zone = SerializedZone::deserialize(deserializer)?;
+        let compact = match zone.tiles {
+            TileRows::Compact(rows) => CompactZone {
+                id: zone.id,
+                width: zone.width.unwrap_or_else(|| rows.first().map_or(0, |row| row.chars().count())),
+                height: zone.height.unwrap_or(rows.len()),
+                tiles: rows,
+                exits: zone.exits,
+                entities: zone.entities,
+                resources: zone.resources,
+                owner: zone.owner,
+            },
+            TileRows::Verbose(rows) => CompactZone {
+                id: zone.id,
+                width: zone.width.unwrap_or_else(|| rows.first().map_or(0, Vec::len)),
+                height: zone.height.unwrap_or(rows.len()),
+                tiles: rows.iter()
+                    .map(|row| row.iter().map(|tile| tile.surface_type.code()).collect())
+                    .collect(),
+                exits: zone.exits,
+                entities: zone.entities,
+                resources: zone.resources,
+                owner: zone.owner,
+            },
+        };
+        Zone::from_compact(compact).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A tile whose surface changed between two zone states
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TileChange {
+    /// X coordinate of the tile
+    pub x: usize,
+    /// Y coordinate of the tile
+    pub y: usize,
+    /// Surface before the change
+    pub old: SurfaceType,
+    /// Surface after the change
+    pub new: SurfaceType,
+}
+
+/// A resource deposit whose amount changed (0 when it appeared or was depleted)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceChange {
+    /// X coordinate of the deposit
+    pub x: usize,
+    /// Y coordinate of the deposit
+    pub y: usize,
+    /// Amount before the change
+    pub old_amount: u32,
+    /// Amount after the change
+    pub new_amount: u32,
+}
+
+/// Changes between two states of the same zone
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZoneDiff {
+    /// Tiles whose surface changed
+    pub changed_tiles: Vec<TileChange>,
+    /// Entities that appeared or changed (moved, new owner)
+    pub added_entities: Vec<EntityRef>,
+    /// IDs of entities that are gone
+    pub removed_entities: Vec<u32>,
+    /// Resource deposits whose amount changed
+    pub changed_resources: Vec<ResourceChange>,
+}
+
+impl ZoneDiff {
+    /// Whether the two zone states were identical
+    pub fn is_empty(&self) -> bool {
+        self.changed_tiles.is_empty()
+            && self.added_entities.is_empty()
+            && self.removed_entities.is_empty()
+            && self.changed_resources.is_empty()
+    }
 }
 
 impl Zone {
-    /// Generate a new zone with procedural landscape
+    /// Generate a new zone with procedural landscape and the default configuration
     /// 
     /// # Arguments
     /// * `zone_id` - Unique identifier for the zone
     /// * `seed` - Seed for random generation (use zone_id hash for deterministic generation)
     pub fn generate(zone_id: String, seed: u64) -> Self {
+        Self::generate_with_config(zone_id, seed, &ZoneGenConfig::default())
+            .expect("Default zone configuration is valid")
+    }
+
+    /// Generate a new zone with procedural landscape from a configuration
+    pub fn generate_with_config(zone_id: String, seed: u64, config: &ZoneGenConfig) -> Result<Self, String> {
+        config.validate()?;
         let mut rng = SimpleRng::new(seed);
         
         // Generate tiles with procedural algorithm
-        let mut tiles = Vec::with_capacity(ZONE_SIZE);
+        let smooth = match config.terrain_style {
+            TerrainStyle::Legacy => None,
+            TerrainStyle::Smooth => Some(Self::smooth_surfaces(seed, config)),
+        };
+        let mut tiles = Vec::with_capacity(config.height);
         
-        for y in 0..ZONE_SIZE {
-            let mut row = Vec::with_capacity(ZONE_SIZE);
-            for x in 0..ZONE_SIZE {
-                let surface_type = Self::generate_surface_type(x, y, &mut rng);
+        for y in 0..config.height {
+            let mut row = Vec::with_capacity(config.width);
+            for x in 0..config.width {
+                let surface_type = match &smooth {
+                    Some(surfaces) => surfaces[y * config.width + x],
+                    None => Self::generate_surface_type(x, y, &mut rng, config),
+                };
                 row.push(Tile {
                     x,
                     y,
@@ -87,50 +571,89 @@ impl Zone {
             tiles.push(row);
         }
         
-        // Generate 2-4 exits
-        let num_exits = 2 + (rng.next() % 3) as usize; // 2, 3, or 4 exits
-        let exits = Self::generate_exits(num_exits, &mut rng);
+        // Generate min_exits..=max_exits exits (2-4 by default)
+        let exit_range = (config.max_exits - config.min_exits + 1) as u64;
+        let num_exits = config.min_exits + (rng.next() % exit_range) as usize;
+        let exits = Self::generate_exits(num_exits, &mut rng, config.width, config.height);
         
-        Zone {
+        let mut zone = Zone {
             id: zone_id,
+            width: config.width,
+            height: config.height,
             tiles,
             exits,
-        }
+            entities: Vec::new(),
+            resources: Vec::new(),
+            owner: None,
+            version: 0,
+            terrain_version: 0,
+            spawn_points: Vec::new(),
+            difficulty: 0.0,
+        };
+        zone.designate_spawn_points();
+        zone.difficulty = zone.rate_difficulty();
+        Ok(zone)
     }
     
-    /// Generate surface type for a tile using procedural algorithm
-    fn generate_surface_type(x: usize, y: usize, rng: &mut SimpleRng) -> SurfaceType {
-        // Use Perlin-like noise approximation for natural-looking terrain
-        let noise_value = Self::noise(x, y, rng.seed);
+    /// Generate surface type for a tile with the legacy per-tile algorithm
+    fn generate_surface_type(x: usize, y: usize, rng: &mut SimpleRng, config: &ZoneGenConfig) -> SurfaceType {
+        let noise_value = noise::hash_noise(x, y, rng.seed);
         
-        // Distribution: ~60% Plain, ~25% Swamp, ~15% Obstacle
-        if noise_value < 0.60 {
+        // Distribution follows the configured ratios
+        if noise_value < config.plain_ratio {
             SurfaceType::Plain
-        } else if noise_value < 0.85 {
+        } else if noise_value < config.plain_ratio + config.swamp_ratio {
             SurfaceType::Swamp
+        } else if noise_value < config.plain_ratio + config.swamp_ratio + config.water_ratio {
+            SurfaceType::Water
         } else {
             SurfaceType::Obstacle
         }
     }
     
-    /// Simple 2D noise function for terrain generation
-    fn noise(x: usize, y: usize, seed: u64) -> f64 {
-        // Combine position with seed for deterministic pseudo-random values
-        let hash = (x as u64).wrapping_mul(374761393)
-            .wrapping_add((y as u64).wrapping_mul(668265263))
-            .wrapping_add(seed);
-        
-        // Mix the bits
-        let hash = hash ^ (hash >> 13);
-        let hash = hash.wrapping_mul(1274126177);
-        let hash = hash ^ (hash >> 16);
-        
-        // Convert to 0.0-1.0 range
-        (hash % 10000) as f64 / 10000.0
+    /// Surface types of all tiles (row-major) from two smooth noise fields
+    ///
+    /// The highest `obstacle_ratio` share of an elevation field become obstacle ridges.
+    /// The remaining tiles are ranked by a moisture field: the wettest become water, the
+    /// next ones swamp (so swamps surround lakes), and the rest plain.
+    fn smooth_surfaces(seed: u64, config: &ZoneGenConfig) -> Vec<SurfaceType> {
+        let count = config.width * config.height;
+        let field = |field_seed: u64| -> Vec<f64> {
+            (0..count)
+                .map(|i| {
+                    let (x, y) = ((i % config.width) as f64, (i / config.width) as f64);
+                    noise::fractal_noise(x, y, field_seed, config.noise_octaves, config.noise_frequency)
+                })
+                .collect()
+        };
+        let elevation = field(seed);
+        let moisture = field(seed ^ 0x5bd1e9955bd1e995);
+        let share = |ratio: f64, available: usize| ((ratio * count as f64).round() as usize).min(available);
+
+        let mut surfaces = vec![SurfaceType::Plain; count];
+        let mut by_elevation: Vec<usize> = (0..count).collect();
+        by_elevation.sort_by(|&a, &b| elevation[b].total_cmp(&elevation[a]).then(a.cmp(&b)));
+        let (ridges, lowlands) = by_elevation.split_at(share(config.obstacle_ratio, count));
+        for &i in ridges {
+            surfaces[i] = SurfaceType::Obstacle;
+        }
+
+        let mut by_moisture = lowlands.to_vec();
+        by_moisture.sort_by(|&a, &b| moisture[b].total_cmp(&moisture[a]).then(a.cmp(&b)));
+        let water = share(config.water_ratio, by_moisture.len());
+        let swamp = share(config.swamp_ratio, by_moisture.len() - water);
+        for (rank, &i) in by_moisture.iter().enumerate() {
+            if rank < water {
+                surfaces[i] = SurfaceType::Water;
+            } else if rank < water + swamp {
+                surfaces[i] = SurfaceType::Swamp;
+            }
+        }
+        surfaces
     }
     
     /// Generate exits for the zone
-    fn generate_exits(num_exits: usize, rng: &mut SimpleRng) -> Vec<Exit> {
+    fn generate_exits(num_exits: usize, rng: &mut SimpleRng, width: usize, height: usize) -> Vec<Exit> {
         let mut exits = Vec::with_capacity(num_exits);
         let mut used_directions = HashSet::new();
         
@@ -157,10 +680,10 @@ impl Zone {
             
             // Place exit on the edge based on direction
             let (x, y) = match direction {
-                ExitDirection::North => (rng.next() as usize % ZONE_SIZE, 0),
-                ExitDirection::South => (rng.next() as usize % ZONE_SIZE, ZONE_SIZE - 1),
-                ExitDirection::East => (ZONE_SIZE - 1, rng.next() as usize % ZONE_SIZE),
-                ExitDirection::West => (0, rng.next() as usize % ZONE_SIZE),
+                ExitDirection::North => (rng.next() as usize % width, 0),
+                ExitDirection::South => (rng.next() as usize % width, height - 1),
+                ExitDirection::East => (width - 1, rng.next() as usize % height),
+                ExitDirection::West => (0, rng.next() as usize % height),
             };
             
             exits.push(Exit { x, y, direction });
@@ -169,15 +692,260 @@ impl Zone {
         exits
     }
     
+    /// Convert to the compact serialized form
+    pub fn to_compact(&self) -> CompactZone {
+        CompactZone {
+            id: self.id.clone(),
+            width: self.width,
+            height: self.height,
+            tiles: self.tiles.iter()
+                .map(|row| row.iter().map(|tile| tile.surface_type.code()).collect())
+                .collect(),
+            exits: self.exits.clone(),
+            entities: self.entities.clone(),
+            resources: self.resources.clone(),
+            owner: self.owner.clone(),
+        }
+    }
+
+    /// Build a zone from its compact serialized form
+    pub fn from_compact(compact: CompactZone) -> Result<Self, String> {
+        if compact.tiles.len() != compact.height {
+            return Err(format!("Zone {} has {} tile rows, expected {}", compact.id, compact.tiles.len(), compact.height));
+        }
+
+        let mut tiles = Vec::with_capacity(compact.height);
+        for (y, row) in compact.tiles.iter().enumerate() {
+            let row: Vec<Tile> = row.chars()
+                .enumerate()
+                .map(|(x, code)| {
+                    SurfaceType::from_code(code)
+                        .map(|surface_type| Tile { x, y, surface_type })
+                        .ok_or_else(|| format!("Invalid tile code {:?} at ({}, {}) in zone {}", code, x, y, compact.id))
+                })
+                .collect::<Result<_, _>>()?;
+            if row.len() != compact.width {
+                return Err(format!("Row {} of zone {} has {} tiles, expected {}", y, compact.id, row.len(), compact.width));
+            }
+            tiles.push(row);
+        }
+
+        let mut zone = Zone {
+            id: compact.id,
+            width: compact.width,
+            height: compact.height,
+            tiles,
+            exits: compact.exits,
+            entities: compact.entities,
+            resources: compact.resources,
+            owner: compact.owner,
+            version: 0,
+            terrain_version: 0,
+            spawn_points: Vec::new(),
+            difficulty: 0.0,
+        };
+        zone.designate_spawn_points();
+        zone.difficulty = zone.rate_difficulty();
+        Ok(zone)
+    }
+
+    /// Designate up to [`MAX_SPAWN_POINTS`] spawn points, one near each exit (or near the
+    /// center of a zone without exits)
+    ///
+    /// Spawn points are tiles of the zone's largest area ground units can walk around
+    /// (so never Water or Obstacle), at least [`SPAWN_POINT_MARGIN`] tiles from the edges
+    /// and not next to one another. Each is the closest such tile to its exit.
+    fn designate_spawn_points(&mut self) {
+        let mut area = HashSet::new();
+        let mut seen = HashSet::new();
+        for tile in self.tiles.iter().flatten() {
+            if seen.contains(&(tile.x, tile.y)) {
+                continue;
+            }
+            let reachable = pathfinding::reachable_tiles(self, (tile.x, tile.y), Mobility::GROUND);
+            seen.extend(reachable.iter().copied());
+            if reachable.len() > area.len() {
+                area = reachable;
+            }
+        }
+
+        let inner = |&&(x, y): &&(usize, usize)| {
+            x >= SPAWN_POINT_MARGIN && y >= SPAWN_POINT_MARGIN
+                && x + SPAWN_POINT_MARGIN < self.width && y + SPAWN_POINT_MARGIN < self.height
+        };
+        let targets: Vec<(usize, usize)> = if self.exits.is_empty() {
+            vec![(self.width / 2, self.height / 2)]
+        } else {
+            self.exits.iter().take(MAX_SPAWN_POINTS).map(|exit| (exit.x, exit.y)).collect()
+        };
+        let mut spawn_points: Vec<(usize, usize)> = Vec::with_capacity(targets.len());
+        for (tx, ty) in targets {
+            let closest = area.iter()
+                .filter(inner)
+                .filter(|&&(x, y)| spawn_points.iter().all(|&(sx, sy)| x.abs_diff(sx) > 1 || y.abs_diff(sy) > 1))
+                .min_by_key(|&&(x, y)| (x.abs_diff(tx) + y.abs_diff(ty), y, x));
+            if let Some(&point) = closest {
+                spawn_points.push(point);
+            }
+        }
+        self.spawn_points = spawn_points;
+    }
+
+    /// Rate how hard the zone is, from 0.0 to 1.0
+    ///
+    /// The average of its obstacle density (the share of Obstacle tiles) and its resource
+    /// scarcity (1.0 without deposits, 0.0 from [`RICH_ZONE_RESOURCES`] in total).
+    pub fn rate_difficulty(&self) -> f32 {
+        let tiles = (self.width * self.height).max(1);
+        let obstacle_density = self.count_surface_type(SurfaceType::Obstacle) as f32 / tiles as f32;
+        let resources: u64 = self.resources.iter().map(|deposit| deposit.amount as u64).sum();
+        let scarcity = 1.0 - (resources as f32 / RICH_ZONE_RESOURCES as f32).min(1.0);
+        (obstacle_density + scarcity) / 2.0
+    }
+
+    /// Take the first spawn point no entity stands on, marking it used
+    ///
+    /// Returns `None` once every spawn point is used or occupied. Spawn points are not
+    /// saved: a reloaded zone designates them again, skipping those still occupied by the
+    /// entity placed there.
+    pub fn get_spawn_point(&mut self) -> Option<(usize, usize)> {
+        let index = self.spawn_points.iter()
+            .position(|&(x, y)| !self.entities.iter().any(|entity| entity.x == x && entity.y == y))?;
+        Some(self.spawn_points.remove(index))
+    }
+
     /// Get a tile at specific coordinates
     pub fn get_tile(&self, x: usize, y: usize) -> Option<&Tile> {
-        if x < ZONE_SIZE && y < ZONE_SIZE {
-            Some(&self.tiles[y][x])
+        if x < self.width && y < self.height {
+            self.tiles.get(y).and_then(|row| row.get(x))
         } else {
             None
         }
     }
+
+    /// Up to `limit` tiles in row-major order, starting at `(start_x, start_y)`
+    ///
+    /// Returns the tiles and the position of the next tile, if any are left.
+    pub fn get_tiles_paginated(&self, start_x: usize, start_y: usize, limit: usize) -> (Vec<&Tile>, Option<(usize, usize)>) {
+        if start_x >= self.width || start_y >= self.height {
+            return (Vec::new(), None);
+        }
+
+        let start = start_y * self.width + start_x;
+        let end = (start + limit).min(self.width * self.height);
+        let tiles = (start..end)
+            .filter_map(|i| self.get_tile(i % self.width, i / self.width))
+            .collect();
+        let next = (end < self.width * self.height).then(|| (end % self.width, end / self.width));
+        (tiles, next)
+    }
     
+    /// Compute the changes needed to go from this zone state to `other`
+    ///
+    /// An entity that moved is reported in `added_entities` with its new position.
+    pub fn diff(&self, other: &Zone) -> ZoneDiff {
+        let changed_tiles = self.tiles.iter()
+            .flatten()
+            .zip(other.tiles.iter().flatten())
+            .filter(|(old, new)| old.surface_type != new.surface_type)
+            .map(|(old, new)| TileChange {
+                x: new.x,
+                y: new.y,
+                old: old.surface_type,
+                new: new.surface_type,
+            })
+            .collect();
+
+        let old_entities: HashMap<u32, &EntityRef> = self.entities.iter().map(|e| (e.id, e)).collect();
+        let new_ids: HashSet<u32> = other.entities.iter().map(|e| e.id).collect();
+        let added_entities = other.entities.iter()
+            .filter(|entity| old_entities.get(&entity.id) != Some(entity))
+            .cloned()
+            .collect();
+        let removed_entities = self.entities.iter()
+            .map(|entity| entity.id)
+            .filter(|id| !new_ids.contains(id))
+            .collect();
+
+        let old_amounts: HashMap<(usize, usize), u32> = self.resources.iter().map(|r| ((r.x, r.y), r.amount)).collect();
+        let new_amounts: HashMap<(usize, usize), u32> = other.resources.iter().map(|r| ((r.x, r.y), r.amount)).collect();
+        let mut changed_resources: Vec<ResourceChange> = old_amounts.keys()
+            .chain(new_amounts.keys())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .filter_map(|&(x, y)| {
+                let old_amount = old_amounts.get(&(x, y)).copied().unwrap_or(0);
+                let new_amount = new_amounts.get(&(x, y)).copied().unwrap_or(0);
+                (old_amount != new_amount).then_some(ResourceChange { x, y, old_amount, new_amount })
+            })
+            .collect();
+        changed_resources.sort_by_key(|change| (change.y, change.x));
+
+        ZoneDiff {
+            changed_tiles,
+            added_entities,
+            removed_entities,
+            changed_resources,
+        }
+    }
+
+    /// Render the zone's tiles as an SVG image, shaded for the phase of the day
+    pub fn to_svg(&self, phase: DayPhase) -> String {
+        const TILE_PX: usize = 10;
+        let (width, height) = (self.width * TILE_PX, self.height * TILE_PX);
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">"
+        );
+        for tile in self.tiles.iter().flatten() {
+            let color = match tile.surface_type {
+                SurfaceType::Plain => "#8bc34a",
+                SurfaceType::Swamp => "#556b2f",
+                SurfaceType::Water => "#1e88e5",
+                SurfaceType::Obstacle => "#5d4037",
+            };
+            svg.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{TILE_PX}\" height=\"{TILE_PX}\" fill=\"{}\"/>",
+                tile.x * TILE_PX,
+                tile.y * TILE_PX,
+                color
+            ));
+        }
+
+        // Tint the whole map according to the light level
+        let shade = match phase {
+            DayPhase::Dawn => Some(("#ff9800", 0.15)),
+            DayPhase::Day => None,
+            DayPhase::Dusk => Some(("#673ab7", 0.25)),
+            DayPhase::Night => Some(("#0d1b3e", 0.55)),
+        };
+        if let Some((color, opacity)) = shade {
+            svg.push_str(&format!(
+                "<rect class=\"shade\" width=\"{width}\" height=\"{height}\" fill=\"{color}\" fill-opacity=\"{opacity}\"/>"
+            ));
+        }
+
+        svg.push_str("</svg>");
+        svg
+    }
+
+    /// Check that every exit can be reached from every other exit by ground units
+    ///
+    /// Water and Obstacle tiles are treated as impassable.
+    pub fn validate_connectivity(&self) -> Result<(), String> {
+        let Some(first) = self.exits.first() else {
+            return Ok(());
+        };
+        let reachable = pathfinding::reachable_tiles(self, (first.x, first.y), Mobility::GROUND);
+        match self.exits.iter().find(|exit| !reachable.contains(&(exit.x, exit.y))) {
+            Some(exit) => Err(format!(
+                "Exit ({}, {}) of zone {} cannot be reached from exit ({}, {}) by ground units",
+                exit.x, exit.y, self.id, first.x, first.y
+            )),
+            None => Ok(()),
+        }
+    }
+
     /// Count tiles by surface type
     pub fn count_surface_type(&self, surface_type: SurfaceType) -> usize {
         self.tiles
@@ -290,4 +1058,285 @@ mod tests {
         assert!(swamps > 0, "Should have some swamps");
         assert!(obstacles > 0, "Should have some obstacles");
     }
+
+    #[test]
+    fn test_diff_unchanged_zone_is_empty() {
+        let zone = Zone::generate("zone1".to_string(), 12345);
+        let diff = zone.diff(&zone.clone());
+        assert!(diff.is_empty());
+        assert_eq!(diff, ZoneDiff::default());
+    }
+
+    #[test]
+    fn test_diff_single_tile_change() {
+        let before = Zone::generate("zone1".to_string(), 12345);
+        let mut after = before.clone();
+        let old = after.tiles[4][7].surface_type;
+        let new = if old == SurfaceType::Obstacle { SurfaceType::Plain } else { SurfaceType::Obstacle };
+        after.tiles[4][7].surface_type = new;
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.changed_tiles, vec![TileChange { x: 7, y: 4, old, new }]);
+        assert!(diff.added_entities.is_empty() && diff.removed_entities.is_empty());
+        assert!(diff.changed_resources.is_empty());
+    }
+
+    #[test]
+    fn test_tiles_paginated_wraps_rows() {
+        let zone = Zone::generate("zone1".to_string(), 12345);
+        let (tiles, next) = zone.get_tiles_paginated(ZONE_SIZE - 2, 0, 5);
+        let positions: Vec<(usize, usize)> = tiles.iter().map(|tile| (tile.x, tile.y)).collect();
+        assert_eq!(positions, vec![(ZONE_SIZE - 2, 0), (ZONE_SIZE - 1, 0), (0, 1), (1, 1), (2, 1)]);
+        assert_eq!(next, Some((3, 1)));
+
+        let (tiles, next) = zone.get_tiles_paginated(ZONE_SIZE - 1, ZONE_SIZE - 1, 100);
+        assert_eq!(tiles.len(), 1);
+        assert_eq!(next, None);
+        assert!(zone.get_tiles_paginated(ZONE_SIZE, 0, 100).0.is_empty());
+    }
+
+    #[test]
+    fn test_diff_entities_and_resources() {
+        let mut before = Zone::generate("zone1".to_string(), 12345);
+        let worker = EntityRef { id: 1, kind: "worker".to_string(), owner: Some("alice".to_string()), x: 2, y: 2, hits: DEFAULT_ENTITY_HITS, can_swim: false, can_fly: false };
+        before.entities = vec![worker.clone(), EntityRef { id: 2, ..worker.clone() }];
+        before.resources = vec![ResourceDeposit { x: 5, y: 5, amount: 100 }];
+
+        let mut after = before.clone();
+        after.entities = vec![EntityRef { x: 3, ..worker }];
+        after.resources[0].amount = 90;
+
+        let diff = before.diff(&after);
+        assert!(diff.changed_tiles.is_empty());
+        assert_eq!(diff.added_entities.len(), 1);
+        assert_eq!(diff.added_entities[0].x, 3);
+        assert_eq!(diff.removed_entities, vec![2]);
+        assert_eq!(diff.changed_resources, vec![ResourceChange { x: 5, y: 5, old_amount: 100, new_amount: 90 }]);
+    }
+
+    #[test]
+    fn test_svg_shaded_by_phase() {
+        let zone = Zone::generate("zone1".to_string(), 12345);
+        let day = zone.to_svg(DayPhase::Day);
+        let night = zone.to_svg(DayPhase::Night);
+
+        assert!(day.starts_with("<svg") && day.ends_with("</svg>"));
+        assert_eq!(day.matches("<rect").count(), ZONE_SIZE * ZONE_SIZE);
+        assert!(!day.contains("class=\"shade\""));
+        assert!(night.contains("class=\"shade\""));
+        assert_ne!(night, zone.to_svg(DayPhase::Dusk));
+    }
+
+    #[test]
+    fn test_non_square_zone() {
+        let config = ZoneGenConfig { width: 40, height: 12, ..ZoneGenConfig::default() };
+        let zone = Zone::generate_with_config("wide".to_string(), 7, &config).unwrap();
+
+        assert_eq!((zone.width, zone.height), (40, 12));
+        assert_eq!(zone.tiles.len(), 12);
+        assert!(zone.tiles.iter().all(|row| row.len() == 40));
+        assert!(zone.get_tile(39, 11).is_some());
+        assert!(zone.get_tile(40, 0).is_none());
+        assert!(zone.get_tile(0, 12).is_none());
+        assert!(zone.exits.iter().all(|exit| exit.x < 40 && exit.y < 12));
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let invalid = [
+            ZoneGenConfig { width: 7, ..ZoneGenConfig::default() },
+            ZoneGenConfig { height: 257, ..ZoneGenConfig::default() },
+            ZoneGenConfig { plain_ratio: 0.7, ..ZoneGenConfig::default() },
+            ZoneGenConfig { plain_ratio: 1.2, swamp_ratio: -0.35, ..ZoneGenConfig::default() },
+            ZoneGenConfig { min_exits: 5, max_exits: 4, ..ZoneGenConfig::default() },
+        ];
+        for config in invalid {
+            assert!(Zone::generate_with_config("bad".to_string(), 1, &config).is_err(), "{:?}", config);
+        }
+        assert!(ZoneGenConfig::default().validate().is_ok());
+
+        let err = ZoneGenConfig { obstacle_ratio: 0.5, ..ZoneGenConfig::default() }.validate().unwrap_err();
+        assert!(err.contains("sum to 1"), "{}", err);
+
+        let rocky = ZoneGenConfig { plain_ratio: 0.0, swamp_ratio: 0.0, obstacle_ratio: 1.0, ..ZoneGenConfig::default() };
+        let zone = Zone::generate_with_config("rocky".to_string(), 3, &rocky).unwrap();
+        assert_eq!(zone.count_surface_type(SurfaceType::Obstacle), ZONE_SIZE * ZONE_SIZE);
+    }
+
+    #[test]
+    fn test_legacy_generation_unchanged() {
+        // FNV-1a of the serialized tiles and exits, recorded before zones became configurable
+        let legacy = ZoneGenConfig::legacy();
+        let expected = [
+            (0, 0xb8c1946d630571c8u64),
+            (12345, 0x34c718f6af0205ef),
+            (987654321, 0x90b5bf15a1614836),
+            (u64::MAX, 0x7af61452b0efe795),
+        ];
+        for (seed, fingerprint) in expected {
+            let zone = Zone::generate_with_config("z".to_string(), seed, &legacy).unwrap();
+            let bytes = serde_json::to_vec(&(&zone.tiles, &zone.exits)).unwrap();
+            let hash = bytes.iter().fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3));
+            assert_eq!(hash, fingerprint, "seed {}", seed);
+            assert_eq!(zone.exits, Zone::generate("z".to_string(), seed).exits);
+        }
+    }
+
+    #[test]
+    fn test_water_ratio_generates_water() {
+        let config = ZoneGenConfig { plain_ratio: 0.5, swamp_ratio: 0.15, water_ratio: 0.35, obstacle_ratio: 0.0, ..ZoneGenConfig::default() };
+        let zone = Zone::generate_with_config("lake".to_string(), 42, &config).unwrap();
+        assert!(zone.count_surface_type(SurfaceType::Water) > 0);
+        assert_eq!(zone.count_surface_type(SurfaceType::Obstacle), 0);
+        assert_eq!(Zone::generate("lake".to_string(), 42).count_surface_type(SurfaceType::Water), 0);
+
+        assert_eq!(SurfaceType::Water.movement_cost(), None);
+        assert_eq!(SurfaceType::Water.movement_cost_for(Mobility { can_swim: true, can_fly: false }), Some(WATER_SWIM_COST));
+        assert_eq!(SurfaceType::Swamp.movement_cost_for(Mobility { can_swim: false, can_fly: true }), Some(2));
+    }
+
+    /// Share of horizontally or vertically adjacent tile pairs with the same surface
+    fn neighbour_agreement(zone: &Zone) -> f64 {
+        let mut same = 0;
+        let mut pairs = 0;
+        for y in 0..zone.height {
+            for x in 0..zone.width {
+                let surface = zone.tiles[y][x].surface_type;
+                for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+                    if let Some(tile) = zone.get_tile(nx, ny) {
+                        pairs += 1;
+                        same += usize::from(tile.surface_type == surface);
+                    }
+                }
+            }
+        }
+        same as f64 / pairs as f64
+    }
+
+    #[test]
+    fn test_smooth_terrain_is_spatially_correlated() {
+        // Independent tiles with the default ratios agree with probability 0.6² + 0.25² + 0.15²
+        let random_agreement = 0.6 * 0.6 + 0.25 * 0.25 + 0.15 * 0.15;
+
+        for seed in 0..20 {
+            let smooth = Zone::generate(format!("zone_{}", seed), seed);
+            let legacy = Zone::generate_with_config(format!("zone_{}", seed), seed, &ZoneGenConfig::legacy()).unwrap();
+
+            assert!(neighbour_agreement(&smooth) > random_agreement + 0.2, "seed {}: {}", seed, neighbour_agreement(&smooth));
+            assert!(neighbour_agreement(&legacy) < random_agreement + 0.1, "seed {}: {}", seed, neighbour_agreement(&legacy));
+
+            let plains = smooth.count_surface_type(SurfaceType::Plain);
+            assert_eq!(smooth.count_surface_type(SurfaceType::Obstacle), 135);
+            assert_eq!(smooth.count_surface_type(SurfaceType::Swamp), 225);
+            assert_eq!(plains, 540);
+        }
+    }
+
+    #[test]
+    fn test_smooth_terrain_is_deterministic() {
+        let config = ZoneGenConfig { width: 64, height: 48, water_ratio: 0.1, plain_ratio: 0.5, ..ZoneGenConfig::default() };
+        let first = Zone::generate_with_config("zone".to_string(), 77, &config).unwrap();
+        assert_eq!(first, Zone::generate_with_config("zone".to_string(), 77, &config).unwrap());
+        assert_ne!(first.tiles, Zone::generate_with_config("zone".to_string(), 78, &config).unwrap().tiles);
+    }
+
+    #[test]
+    fn test_compact_serialization_is_smaller_and_round_trips() {
+        let mut zone = Zone::generate("compact".to_string(), 12345);
+        zone.resources = vec![ResourceDeposit { x: 5, y: 5, amount: 100 }];
+
+        let compact = serde_json::to_string(&zone).unwrap();
+        let verbose = serde_json::json!({
+            "id": zone.id,
+            "tiles": zone.tiles,
+            "exits": zone.exits,
+            "entities": zone.entities,
+            "resources": zone.resources,
+        }).to_string();
+        assert!(verbose.len() >= 5 * compact.len(), "verbose {} bytes, compact {} bytes", verbose.len(), compact.len());
+
+        let value: serde_json::Value = serde_json::from_str(&compact).unwrap();
+        assert_eq!(value["tiles"].as_array().unwrap().len(), ZONE_SIZE);
+        assert_eq!(value["tiles"][0].as_str().unwrap().len(), ZONE_SIZE);
+
+        assert_eq!(serde_json::from_str::<Zone>(&compact).unwrap(), zone);
+        // Saves written before the compact format (no width/height, one object per tile)
+        assert_eq!(serde_json::from_str::<Zone>(&verbose).unwrap(), zone);
+    }
+
+    #[test]
+    fn test_compact_zone_rejects_bad_rows() {
+        let mut compact = Zone::generate("bad".to_string(), 1).to_compact();
+        compact.tiles[3].replace_range(0..1, "X");
+        assert!(Zone::from_compact(compact.clone()).unwrap_err().contains("Invalid tile code"));
+
+        compact.tiles[3].replace_range(0..1, "P");
+        compact.tiles[4].pop();
+        assert!(Zone::from_compact(compact.clone()).unwrap_err().contains("Row 4"));
+
+        compact.tiles.pop();
+        assert!(Zone::from_compact(compact).is_err());
+    }
+
+    #[test]
+    fn test_spawn_points_are_walkable_and_connected() {
+        for seed in 0..100 {
+            let zone = Zone::generate(format!("zone_{}", seed), seed);
+            assert!((1..=MAX_SPAWN_POINTS).contains(&zone.spawn_points.len()), "seed {}: {:?}", seed, zone.spawn_points);
+            let first = zone.spawn_points[0];
+            let reachable = pathfinding::reachable_tiles(&zone, first, Mobility::GROUND);
+            for &(x, y) in &zone.spawn_points {
+                assert!(x >= SPAWN_POINT_MARGIN && x + SPAWN_POINT_MARGIN < zone.width, "seed {}: x = {}", seed, x);
+                assert!(y >= SPAWN_POINT_MARGIN && y + SPAWN_POINT_MARGIN < zone.height, "seed {}: y = {}", seed, y);
+                assert!(matches!(zone.get_tile(x, y).unwrap().surface_type, SurfaceType::Plain | SurfaceType::Swamp));
+                assert!(reachable.contains(&(x, y)), "seed {}: ({}, {}) cannot be reached from {:?}", seed, x, y, first);
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_spawn_point_until_exhausted() {
+        let mut zone = Zone::generate("spawns".to_string(), 3);
+        let designated = zone.spawn_points.clone();
+        // An occupied spawn point is skipped
+        let (x, y) = designated[0];
+        zone.entities.push(EntityRef {
+            id: 1,
+            kind: "worker".to_string(),
+            owner: None,
+            x,
+            y,
+            hits: DEFAULT_ENTITY_HITS,
+            can_swim: false,
+            can_fly: false,
+        });
+
+        let taken: Vec<_> = std::iter::from_fn(|| zone.get_spawn_point()).collect();
+        assert_eq!(taken, designated[1..]);
+        assert_eq!(zone.get_spawn_point(), None);
+        assert_eq!(zone.spawn_points, vec![(x, y)]);
+
+        // Reloading designates the same points again
+        let reloaded = Zone::from_compact(zone.to_compact()).unwrap();
+        assert_eq!(reloaded.spawn_points, designated);
+    }
+
+    #[test]
+    fn test_difficulty_rates_obstacles_and_scarcity() {
+        let mut zone = Zone::generate("rated".to_string(), 5);
+        let density = zone.count_surface_type(SurfaceType::Obstacle) as f32 / (ZONE_SIZE * ZONE_SIZE) as f32;
+        assert!((zone.difficulty - (density + 1.0) / 2.0).abs() < 1e-6);
+
+        // Resources make a zone easier, obstacles harder
+        zone.resources = vec![ResourceDeposit { x: 5, y: 5, amount: RICH_ZONE_RESOURCES }];
+        assert!((zone.rate_difficulty() - density / 2.0).abs() < 1e-6);
+        for tile in zone.tiles.iter_mut().flatten() {
+            tile.surface_type = SurfaceType::Obstacle;
+        }
+        assert_eq!(zone.rate_difficulty(), 0.5);
+
+        // Loading rates the zone again
+        let reloaded = Zone::from_compact(zone.to_compact()).unwrap();
+        assert_eq!(reloaded.difficulty, 0.5);
+    }
 }
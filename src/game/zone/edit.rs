@@ -0,0 +1,284 @@
+//! Zone terrain editing
+//!
+//! Map authors and scenario tooling tweak generated zones with [`ZoneEdit`]s instead of
+//! writing template files: changing the surface of a tile, adding a resource deposit,
+//! adding or removing an exit. Edits keep the zone's invariants: exits lie on the edge
+//! they face, on tiles ground units can walk on; deposits and entities stay on tiles
+//! they can be on; and a zone whose exits can all reach each other stays that way,
+//! unless the batch is forced (see [`Zone::validate_connectivity`]).
+//!
+//! [`Zone::apply_edits`] applies a batch atomically: either every edit applies, or none
+//! does and the refused ones are returned with the reason.
+
+use serde::{Deserialize, Serialize};
+
+use super::{Exit, ExitDirection, Mobility, ResourceDeposit, SurfaceType, Zone, MAX_ZONE_EXITS};
+
+/// One change to a zone
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ZoneEdit {
+    /// Change the surface of a tile
+    SetTile {
+        /// X coordinate of the tile
+        x: usize,
+        /// Y coordinate of the tile
+        y: usize,
+        /// New surface
+        surface_type: SurfaceType,
+    },
+    /// Add a resource deposit, or add to the one already on the tile
+    AddResource {
+        /// X coordinate of the deposit
+        x: usize,
+        /// Y coordinate of the deposit
+        y: usize,
+        /// Amount of resources added
+        amount: u32,
+    },
+    /// Add an exit on the edge it faces
+    AddExit {
+        /// X coordinate of the exit
+        x: usize,
+        /// Y coordinate of the exit
+        y: usize,
+        /// Edge of the zone the exit leads out of
+        direction: ExitDirection,
+    },
+    /// Remove the exit on a tile
+    RemoveExit {
+        /// X coordinate of the exit
+        x: usize,
+        /// Y coordinate of the exit
+        y: usize,
+    },
+}
+
+impl ZoneEdit {
+    /// Whether the edit can cut an exit off from the others
+    fn may_disconnect(&self) -> bool {
+        match self {
+            ZoneEdit::SetTile { surface_type, .. } => surface_type.movement_cost().is_none(),
+            ZoneEdit::AddExit { .. } => true,
+            ZoneEdit::AddResource { .. } | ZoneEdit::RemoveExit { .. } => false,
+        }
+    }
+}
+
+/// An edit refused by [`Zone::apply_edits`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RejectedEdit {
+    /// The edit
+    pub edit: ZoneEdit,
+    /// Why it was refused
+    pub reason: String,
+}
+
+impl Zone {
+    /// Apply a batch of edits, all or none
+    ///
+    /// Edits apply in order, each checked against the zone as the previous ones left it.
+    /// If the zone's exits could all reach each other, they still must afterwards, unless
+    /// `force` is set; a batch breaking that is refused as a whole, blaming the edits that
+    /// made tiles impassable or added exits. Spawn points and difficulty follow the new
+    /// terrain.
+    pub fn apply_edits(&mut self, edits: &[ZoneEdit], force: bool) -> Result<(), Vec<RejectedEdit>> {
+        let mut edited = self.clone();
+        let rejected: Vec<RejectedEdit> = edits.iter()
+            .filter_map(|edit| edited.apply_edit(edit).err().map(|reason| RejectedEdit { edit: edit.clone(), reason }))
+            .collect();
+        if !rejected.is_empty() {
+            return Err(rejected);
+        }
+
+        if !force && self.validate_connectivity().is_ok() {
+            if let Err(reason) = edited.validate_connectivity() {
+                let reason = format!("{} (the zone would be disconnected)", reason);
+                return Err(edits.iter()
+                    .filter(|edit| edit.may_disconnect())
+                    .map(|edit| RejectedEdit { edit: edit.clone(), reason: reason.clone() })
+                    .collect());
+            }
+        }
+
+        edited.designate_spawn_points();
+        edited.difficulty = edited.rate_difficulty();
+        *self = edited;
+        Ok(())
+    }
+
+    /// Change the surface of a tile (see [`Zone::apply_edits`])
+    pub fn set_tile(&mut self, x: usize, y: usize, surface_type: SurfaceType) -> Result<(), String> {
+        self.apply_one(ZoneEdit::SetTile { x, y, surface_type })
+    }
+
+    /// Add an exit (see [`Zone::apply_edits`])
+    pub fn add_exit(&mut self, exit: Exit) -> Result<(), String> {
+        self.apply_one(ZoneEdit::AddExit { x: exit.x, y: exit.y, direction: exit.direction })
+    }
+
+    /// Remove the exit on a tile (see [`Zone::apply_edits`])
+    pub fn remove_exit(&mut self, x: usize, y: usize) -> Result<(), String> {
+        self.apply_one(ZoneEdit::RemoveExit { x, y })
+    }
+
+    /// Add a resource deposit, or add to the one on its tile (see [`Zone::apply_edits`])
+    pub fn add_resource_node(&mut self, deposit: ResourceDeposit) -> Result<(), String> {
+        self.apply_one(ZoneEdit::AddResource { x: deposit.x, y: deposit.y, amount: deposit.amount })
+    }
+
+    fn apply_one(&mut self, edit: ZoneEdit) -> Result<(), String> {
+        self.apply_edits(&[edit], false).map_err(|rejected| {
+            rejected.into_iter().next().map_or_else(String::new, |rejected| rejected.reason)
+        })
+    }
+
+    /// Apply one edit, checking everything but connectivity
+    fn apply_edit(&mut self, edit: &ZoneEdit) -> Result<(), String> {
+        match *edit {
+            ZoneEdit::SetTile { x, y, surface_type } => {
+                self.check_bounds(x, y)?;
+                if surface_type.movement_cost().is_none() {
+                    if self.exits.iter().any(|exit| exit.x == x && exit.y == y) {
+                        return Err(format!("Tile ({}, {}) holds an exit, which must stay walkable", x, y));
+                    }
+                    if self.resources.iter().any(|deposit| deposit.x == x && deposit.y == y) {
+                        return Err(format!("Tile ({}, {}) holds a resource deposit, which must stay walkable", x, y));
+                    }
+                }
+                if let Some(entity) = self.entities.iter()
+                    .find(|entity| entity.x == x && entity.y == y && surface_type.movement_cost_for(entity.mobility()).is_none())
+                {
+                    return Err(format!("Entity {} on tile ({}, {}) cannot stand on {:?}", entity.id, x, y, surface_type));
+                }
+                self.tiles[y][x].surface_type = surface_type;
+            }
+            ZoneEdit::AddResource { x, y, amount } => {
+                self.check_bounds(x, y)?;
+                if amount == 0 {
+                    return Err("Deposit amount must be positive".to_string());
+                }
+                self.check_walkable(x, y, "a resource deposit")?;
+                match self.resources.iter_mut().find(|deposit| deposit.x == x && deposit.y == y) {
+                    Some(deposit) => deposit.amount = deposit.amount.saturating_add(amount),
+                    None => self.resources.push(ResourceDeposit { x, y, amount }),
+                }
+            }
+            ZoneEdit::AddExit { x, y, direction } => {
+                self.check_bounds(x, y)?;
+                let on_edge = match direction {
+                    ExitDirection::North => y == 0,
+                    ExitDirection::South => y == self.height - 1,
+                    ExitDirection::East => x == self.width - 1,
+                    ExitDirection::West => x == 0,
+                };
+                if !on_edge {
+                    return Err(format!("Tile ({}, {}) is not on the {:?} edge", x, y, direction));
+                }
+                if self.exits.iter().any(|exit| exit.x == x && exit.y == y) {
+                    return Err(format!("Tile ({}, {}) already holds an exit", x, y));
+                }
+                if self.exits.len() >= MAX_ZONE_EXITS {
+                    return Err(format!("A zone has at most {} exits", MAX_ZONE_EXITS));
+                }
+                self.check_walkable(x, y, "an exit")?;
+                self.exits.push(Exit { x, y, direction });
+            }
+            ZoneEdit::RemoveExit { x, y } => {
+                let index = self.exits.iter()
+                    .position(|exit| exit.x == x && exit.y == y)
+                    .ok_or_else(|| format!("Tile ({}, {}) holds no exit", x, y))?;
+                self.exits.remove(index);
+            }
+        }
+        Ok(())
+    }
+
+    fn check_bounds(&self, x: usize, y: usize) -> Result<(), String> {
+        if x >= self.width || y >= self.height {
+            return Err(format!("Tile ({}, {}) is outside the {}x{} zone", x, y, self.width, self.height));
+        }
+        Ok(())
+    }
+
+    fn check_walkable(&self, x: usize, y: usize, what: &str) -> Result<(), String> {
+        let surface = self.tiles[y][x].surface_type;
+        if surface.movement_cost_for(Mobility::GROUND).is_none() {
+            return Err(format!("Tile ({}, {}) is {:?}: {} must be on a walkable tile", x, y, surface, what));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// All-Plain zone with exits in the middle of its West and East edges
+    fn open_zone() -> Zone {
+        let mut zone = Zone::generate("edited".to_string(), 1);
+        for tile in zone.tiles.iter_mut().flatten() {
+            tile.surface_type = SurfaceType::Plain;
+        }
+        zone.exits = vec![
+            Exit { x: 0, y: 15, direction: ExitDirection::West },
+            Exit { x: 29, y: 15, direction: ExitDirection::East },
+        ];
+        zone
+    }
+
+    #[test]
+    fn test_wall_between_exits_is_rejected_unless_forced() {
+        let mut zone = open_zone();
+        let wall: Vec<ZoneEdit> = (0..zone.height)
+            .map(|y| ZoneEdit::SetTile { x: 10, y, surface_type: SurfaceType::Obstacle })
+            .collect();
+
+        let rejected = zone.apply_edits(&wall, false).unwrap_err();
+        assert_eq!(rejected.len(), wall.len());
+        assert!(rejected[0].reason.contains("disconnected"));
+        assert_eq!(zone, open_zone());
+
+        // A gap keeps the exits connected
+        zone.apply_edits(&wall[1..], false).unwrap();
+        assert_eq!(zone.count_surface_type(SurfaceType::Obstacle), wall.len() - 1);
+
+        assert!(zone.set_tile(10, 0, SurfaceType::Water).is_err());
+        zone.apply_edits(&wall[..1], true).unwrap();
+        assert!(zone.validate_connectivity().is_err());
+    }
+
+    #[test]
+    fn test_invalid_edit_rejects_the_whole_batch() {
+        let mut zone = open_zone();
+        let edits = vec![
+            ZoneEdit::SetTile { x: 3, y: 3, surface_type: SurfaceType::Swamp },
+            ZoneEdit::AddExit { x: 5, y: 5, direction: ExitDirection::North },
+            ZoneEdit::SetTile { x: 0, y: 15, surface_type: SurfaceType::Obstacle },
+            ZoneEdit::RemoveExit { x: 1, y: 1 },
+            ZoneEdit::SetTile { x: 30, y: 0, surface_type: SurfaceType::Plain },
+        ];
+        let rejected = zone.apply_edits(&edits, false).unwrap_err();
+        let rejected: Vec<&ZoneEdit> = rejected.iter().map(|rejected| &rejected.edit).collect();
+        assert_eq!(rejected, edits[1..].iter().collect::<Vec<_>>());
+        assert_eq!(zone, open_zone());
+    }
+
+    #[test]
+    fn test_exits_and_resources_keep_their_invariants() {
+        let mut zone = open_zone();
+        zone.add_exit(Exit { x: 7, y: 0, direction: ExitDirection::North }).unwrap();
+        assert_eq!(zone.exits.len(), 3);
+        assert!(zone.add_exit(Exit { x: 7, y: 0, direction: ExitDirection::North }).unwrap_err().contains("already"));
+        zone.remove_exit(7, 0).unwrap();
+        assert_eq!(zone.exits.len(), 2);
+
+        zone.add_resource_node(ResourceDeposit { x: 4, y: 4, amount: 100 }).unwrap();
+        zone.add_resource_node(ResourceDeposit { x: 4, y: 4, amount: 50 }).unwrap();
+        assert_eq!(zone.resources, vec![ResourceDeposit { x: 4, y: 4, amount: 150 }]);
+        assert!(zone.set_tile(4, 4, SurfaceType::Water).unwrap_err().contains("resource deposit"));
+
+        zone.set_tile(6, 6, SurfaceType::Water).unwrap();
+        assert!(zone.add_resource_node(ResourceDeposit { x: 6, y: 6, amount: 10 }).unwrap_err().contains("walkable"));
+    }
+}
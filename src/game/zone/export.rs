@@ -0,0 +1,190 @@
+//! Zone export files
+//!
+//! Players share maps as self-contained JSON documents ([`ZoneExport`]): the terrain in
+//! compact rows, the exits, the resource deposits, and some metadata, but no entities
+//! or owner. A document starts with a format signature ([`ZONE_EXPORT_FORMAT`]) and
+//! version, and ends with a SHA-256 checksum of everything else, so a damaged or
+//! hand-edited file is refused instead of installing a different map.
+//!
+//! Importing checks the invariants every zone the server generates keeps: size between
+//! [`MIN_ZONE_SIZE`] and [`MAX_ZONE_SIZE`], known tile codes, at most [`MAX_ZONE_EXITS`]
+//! exits each on its own edge of the zone, and resource deposits inside the grid. Unlike
+//! map templates, exits need not be reachable from each other, since generated zones
+//! often have an exit blocked by water or obstacles and must round-trip exactly. The zone
+//! is always installed under a new ID.
+//!
+//! Documents of older versions stay loadable: [`Zone::import`] upgrades them before
+//! checking them; newer versions are refused with a clear message.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{CompactZone, Exit, ExitDirection, ResourceDeposit, Zone, MAX_ZONE_EXITS, MAX_ZONE_SIZE, MIN_ZONE_SIZE};
+
+/// Signature at the start of every zone export
+pub const ZONE_EXPORT_FORMAT: &str = "geekcraft-zone";
+
+/// Current version of the zone export format
+pub const ZONE_EXPORT_VERSION: u32 = 1;
+
+/// Largest zone export document accepted, in bytes
+pub const MAX_ZONE_EXPORT_SIZE: usize = 512 * 1024;
+
+/// Information about an exported zone, not needed to rebuild it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZoneExportMetadata {
+    /// ID of the zone on the server it was exported from
+    pub source_zone_id: String,
+    /// When the zone was exported (Unix timestamp, seconds)
+    pub exported_at: i64,
+    /// Server that exported the zone, e.g. `GeekCraft/0.2.0`
+    pub generator: String,
+}
+
+/// A zone as a shareable file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZoneExport {
+    /// Always [`ZONE_EXPORT_FORMAT`]
+    pub format: String,
+    /// Format version the document was written with
+    pub version: u32,
+    /// Where and when the zone was exported
+    pub metadata: ZoneExportMetadata,
+    /// Width in tiles
+    pub width: usize,
+    /// Height in tiles
+    pub height: usize,
+    /// Rows of tiles, one [`SurfaceType::code`](super::SurfaceType::code) per tile
+    pub tiles: Vec<String>,
+    /// Exits of the zone
+    pub exits: Vec<Exit>,
+    /// Resource deposits of the zone
+    pub resources: Vec<ResourceDeposit>,
+    /// Lowercase hex SHA-256 of the other fields (see [`ZoneExport::compute_checksum`])
+    pub checksum: String,
+}
+
+/// Fields covered by the checksum, in document order
+#[derive(Serialize)]
+struct ChecksumInput<'a> {
+    format: &'a str,
+    version: u32,
+    metadata: &'a ZoneExportMetadata,
+    width: usize,
+    height: usize,
+    tiles: &'a [String],
+    exits: &'a [Exit],
+    resources: &'a [ResourceDeposit],
+}
+
+impl ZoneExport {
+    /// Parse a document, refusing it past [`MAX_ZONE_EXPORT_SIZE`]
+    pub fn parse(document: &[u8]) -> Result<Self, String> {
+        if document.len() > MAX_ZONE_EXPORT_SIZE {
+            return Err(format!("Zone export is {} bytes, at most {} allowed", document.len(), MAX_ZONE_EXPORT_SIZE));
+        }
+        serde_json::from_slice(document).map_err(|e| format!("Malformed zone export: {}", e))
+    }
+
+    /// Checksum of the document's content, whatever its `checksum` field holds
+    pub fn compute_checksum(&self) -> String {
+        let input = ChecksumInput {
+            format: &self.format,
+            version: self.version,
+            metadata: &self.metadata,
+            width: self.width,
+            height: self.height,
+            tiles: &self.tiles,
+            exits: &self.exits,
+            resources: &self.resources,
+        };
+        let bytes = serde_json::to_vec(&input).expect("zone export serializes");
+        format!("{:x}", Sha256::digest(bytes))
+    }
+
+    /// Bring a document of an older version to the current one
+    ///
+    /// Version 1 is the first version, so there is nothing to upgrade yet; later
+    /// format changes add their conversion here.
+    fn upgrade(self) -> Result<Self, String> {
+        match self.version {
+            ZONE_EXPORT_VERSION => Ok(self),
+            version if version > ZONE_EXPORT_VERSION => Err(format!(
+                "Zone export version {} is newer than this server supports (up to {})",
+                version, ZONE_EXPORT_VERSION
+            )),
+            version => Err(format!("Unknown zone export version {}", version)),
+        }
+    }
+}
+
+impl Zone {
+    /// Export the zone's terrain, exits, and resource deposits as a shareable document
+    pub fn export(&self, exported_at: i64) -> ZoneExport {
+        let compact = self.to_compact();
+        let mut export = ZoneExport {
+            format: ZONE_EXPORT_FORMAT.to_string(),
+            version: ZONE_EXPORT_VERSION,
+            metadata: ZoneExportMetadata {
+                source_zone_id: self.id.clone(),
+                exported_at,
+                generator: concat!("GeekCraft/", env!("CARGO_PKG_VERSION")).to_string(),
+            },
+            width: compact.width,
+            height: compact.height,
+            tiles: compact.tiles,
+            exits: compact.exits,
+            resources: compact.resources,
+            checksum: String::new(),
+        };
+        export.checksum = export.compute_checksum();
+        export
+    }
+
+    /// Build zone `zone_id` from an export, checking its signature, version, checksum,
+    /// and the zone invariants
+    pub fn import(export: &ZoneExport, zone_id: String) -> Result<Zone, String> {
+        if export.format != ZONE_EXPORT_FORMAT {
+            return Err(format!("Not a zone export (format {:?}, expected {:?})", export.format, ZONE_EXPORT_FORMAT));
+        }
+        if export.checksum != export.compute_checksum() {
+            return Err("Zone export checksum does not match its content; the file is damaged or was edited".to_string());
+        }
+        let export = export.clone().upgrade()?;
+
+        let (width, height) = (export.width, export.height);
+        for (what, size) in [("width", width), ("height", height)] {
+            if !(MIN_ZONE_SIZE..=MAX_ZONE_SIZE).contains(&size) {
+                return Err(format!("Zone export {} is {}, expected {} to {}", what, size, MIN_ZONE_SIZE, MAX_ZONE_SIZE));
+            }
+        }
+        if export.exits.len() > MAX_ZONE_EXITS {
+            return Err(format!("Zone export has {} exits, at most {} allowed", export.exits.len(), MAX_ZONE_EXITS));
+        }
+        for exit in &export.exits {
+            let on_edge = match exit.direction {
+                ExitDirection::North => exit.y == 0,
+                ExitDirection::South => exit.y == height - 1,
+                ExitDirection::East => exit.x == width - 1,
+                ExitDirection::West => exit.x == 0,
+            };
+            if exit.x >= width || exit.y >= height || !on_edge {
+                return Err(format!("{:?} exit ({}, {}) is not on the {:?} edge of the zone", exit.direction, exit.x, exit.y, exit.direction));
+            }
+        }
+        if let Some(resource) = export.resources.iter().find(|resource| resource.x >= width || resource.y >= height) {
+            return Err(format!("Resource deposit ({}, {}) is outside the {}x{} zone", resource.x, resource.y, width, height));
+        }
+
+        Zone::from_compact(CompactZone {
+            id: zone_id,
+            width,
+            height,
+            tiles: export.tiles,
+            exits: export.exits,
+            entities: Vec::new(),
+            resources: export.resources,
+            owner: None,
+        })
+    }
+}
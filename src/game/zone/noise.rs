@@ -0,0 +1,85 @@
+//! Noise functions for terrain generation
+//!
+//! [`fractal_noise`] sums several octaves of 2D value noise: random values on an integer
+//! lattice, smoothly interpolated in between. Nearby points get similar values, so
+//! thresholding the field gives contiguous regions. [`hash_noise`] is the original
+//! per-tile hash, kept for [`TerrainStyle::Legacy`](super::TerrainStyle::Legacy) zones.
+
+/// Per-tile hash in `[0, 1)` (no spatial correlation)
+pub fn hash_noise(x: usize, y: usize, seed: u64) -> f64 {
+    // Combine position with seed for deterministic pseudo-random values
+    let hash = (x as u64).wrapping_mul(374761393)
+        .wrapping_add((y as u64).wrapping_mul(668265263))
+        .wrapping_add(seed);
+
+    // Mix the bits
+    let hash = hash ^ (hash >> 13);
+    let hash = hash.wrapping_mul(1274126177);
+    let hash = hash ^ (hash >> 16);
+
+    // Convert to 0.0-1.0 range
+    (hash % 10000) as f64 / 10000.0
+}
+
+/// Random value in `[0, 1)` at a lattice point
+fn lattice_value(x: i64, y: i64, seed: u64) -> f64 {
+    let mut z = seed
+        ^ (x as u64).wrapping_mul(0x9e3779b97f4a7c15)
+        ^ (y as u64).wrapping_mul(0xc2b2ae3d27d4eb4f);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Smoothstep easing, so the field has no visible creases along lattice lines
+fn fade(t: f64) -> f64 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// 2D value noise in `[0, 1)`, with one lattice cell per unit
+pub fn value_noise(x: f64, y: f64, seed: u64) -> f64 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (fade(x - x0), fade(y - y0));
+    let (ix, iy) = (x0 as i64, y0 as i64);
+
+    let top = lattice_value(ix, iy, seed) * (1.0 - tx) + lattice_value(ix + 1, iy, seed) * tx;
+    let bottom = lattice_value(ix, iy + 1, seed) * (1.0 - tx) + lattice_value(ix + 1, iy + 1, seed) * tx;
+    top * (1.0 - ty) + bottom * ty
+}
+
+/// Fractal (multi-octave) value noise in `[0, 1)`
+///
+/// Each octave doubles the frequency and halves the amplitude of the previous one.
+/// `frequency` is the number of lattice cells per tile of the first octave.
+pub fn fractal_noise(x: f64, y: f64, seed: u64, octaves: u32, frequency: f64) -> f64 {
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut max = 0.0;
+    let mut frequency = frequency;
+
+    for octave in 0..octaves.max(1) {
+        let octave_seed = seed.wrapping_add((octave as u64).wrapping_mul(0x632be59bd9b4e019));
+        total += value_noise(x * frequency, y * frequency, octave_seed) * amplitude;
+        max += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    total / max
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_noise_is_continuous_and_bounded() {
+        for i in 0..1000 {
+            let x = i as f64 * 0.037;
+            let value = fractal_noise(x, 1.5, 99, 4, 0.1);
+            assert!((0.0..1.0).contains(&value));
+            assert!((fractal_noise(x + 0.001, 1.5, 99, 4, 0.1) - value).abs() < 0.01);
+        }
+        assert_eq!(value_noise(3.0, 4.0, 1), lattice_value(3, 4, 1));
+    }
+}
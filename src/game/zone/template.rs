@@ -0,0 +1,266 @@
+//! Map template module
+//!
+//! Hand-authored zones for campaign scenarios. A template is a `<name>.json` or
+//! `<name>.toml` file in the maps directory (`GEEKCRAFT_MAPS_DIR`, default `./maps`)
+//! with a `grid` of one string per row in the compact tile codes (`P` Plain, `S` Swamp,
+//! `W` Water, `O` Obstacle), plus explicit `exits`, `resources`, and `buildings`.
+//!
+//! Templates are checked against the same invariants as generated zones: size between
+//! [`MIN_ZONE_SIZE`] and [`MAX_ZONE_SIZE`], exits on the matching edge of the zone, and
+//! every exit reachable from the others by ground units. Errors name the file and the
+//! row and column (both starting at 1, as in a text editor) of the offending tile.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    EntityRef, Exit, ExitDirection, Mobility, ResourceDeposit, SurfaceType, Tile, Zone,
+    DEFAULT_ENTITY_HITS, MAX_ZONE_EXITS, MAX_ZONE_SIZE, MIN_ZONE_SIZE,
+};
+
+/// A building placed on a template map
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateBuilding {
+    /// Building kind (`base`, `tower`, ...)
+    pub kind: String,
+    /// X coordinate within the zone
+    pub x: usize,
+    /// Y coordinate within the zone
+    pub y: usize,
+    /// Owning player (neutral if absent)
+    #[serde(default)]
+    pub owner: Option<String>,
+}
+
+/// A hand-authored zone definition
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapTemplate {
+    /// Template name (defaults to the file name without its extension)
+    #[serde(default)]
+    pub name: String,
+    /// Short description shown to players
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Rows of tiles, one surface code per tile
+    pub grid: Vec<String>,
+    /// Exits of the zone
+    #[serde(default)]
+    pub exits: Vec<Exit>,
+    /// Resource deposits
+    #[serde(default)]
+    pub resources: Vec<ResourceDeposit>,
+    /// Pre-placed buildings
+    #[serde(default)]
+    pub buildings: Vec<TemplateBuilding>,
+    /// File the template was read from (used in error messages)
+    #[serde(skip)]
+    pub source: String,
+}
+
+/// Format of a template file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateFormat {
+    /// `.json` file
+    Json,
+    /// `.toml` file
+    Toml,
+}
+
+/// Directory holding map templates by default
+pub fn default_maps_dir() -> PathBuf {
+    PathBuf::from(crate::config::MAPS_DIR)
+}
+
+/// Validate a template name (it becomes part of a file name and a zone ID)
+fn validate_template_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > 64 {
+        return Err("Map template name must be 1 to 64 characters long".to_string());
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(format!("Map template name {:?} can only contain alphanumeric characters, underscore, and hyphen", name));
+    }
+    Ok(())
+}
+
+impl MapTemplate {
+    /// Load the template `name` from a directory (`<name>.json`, else `<name>.toml`)
+    pub fn load_named(dir: &Path, name: &str) -> Result<Self, String> {
+        validate_template_name(name)?;
+
+        ["json", "toml"]
+            .iter()
+            .map(|extension| dir.join(format!("{}.{}", name, extension)))
+            .find(|path| path.is_file())
+            .ok_or_else(|| format!("Map template {} not found in {}", name, dir.display()))
+            .and_then(|path| Self::load(&path))
+    }
+
+    /// Load a template file, picking the format from its extension
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let file = path.display().to_string();
+        let format = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => TemplateFormat::Json,
+            Some("toml") => TemplateFormat::Toml,
+            _ => return Err(format!("{}: map templates must be .json or .toml files", file)),
+        };
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("{}: failed to read map template: {}", file, e))?;
+
+        let mut template = Self::parse(&source, format, &file)?;
+        if template.name.is_empty() {
+            template.name = path.file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default()
+                .to_string();
+        }
+        Ok(template)
+    }
+
+    /// Parse a template; `file` names the source in error messages
+    pub fn parse(source: &str, format: TemplateFormat, file: &str) -> Result<Self, String> {
+        let template: Result<Self, String> = match format {
+            TemplateFormat::Json => serde_json::from_str(source).map_err(|e| e.to_string()),
+            TemplateFormat::Toml => toml::from_str(source).map_err(|e| e.to_string()),
+        };
+
+        let mut template = template.map_err(|e| format!("{}: malformed map template: {}", file, e.trim_end()))?;
+        template.source = file.to_string();
+        Ok(template)
+    }
+
+    /// Build a zone from the template, checking the zone invariants
+    pub fn to_zone(&self, zone_id: String) -> Result<Zone, String> {
+        let file = &self.source;
+        let at = |x: usize, y: usize| format!("{}: row {}, column {}", file, y + 1, x + 1);
+
+        let height = self.grid.len();
+        if !(MIN_ZONE_SIZE..=MAX_ZONE_SIZE).contains(&height) {
+            return Err(format!("{}: grid has {} rows, expected {} to {}", file, height, MIN_ZONE_SIZE, MAX_ZONE_SIZE));
+        }
+        let width = self.grid[0].chars().count();
+        if !(MIN_ZONE_SIZE..=MAX_ZONE_SIZE).contains(&width) {
+            return Err(format!("{}: row 1 has {} tiles, expected {} to {}", file, width, MIN_ZONE_SIZE, MAX_ZONE_SIZE));
+        }
+
+        let mut tiles = Vec::with_capacity(height);
+        for (y, line) in self.grid.iter().enumerate() {
+            let mut row = Vec::with_capacity(width);
+            for (x, code) in line.chars().enumerate() {
+                let surface_type = SurfaceType::from_code(code)
+                    .ok_or_else(|| format!("{}: invalid tile code {:?} (expected P, S, W, or O)", at(x, y), code))?;
+                row.push(Tile { x, y, surface_type });
+            }
+            if row.len() != width {
+                return Err(format!("{}: row {} has {} tiles, expected {}", file, y + 1, row.len(), width));
+            }
+            tiles.push(row);
+        }
+        let surface = |x: usize, y: usize| tiles.get(y).and_then(|row| row.get(x)).map(|tile| tile.surface_type);
+
+        if self.exits.len() > MAX_ZONE_EXITS {
+            return Err(format!("{}: {} exits, at most {} allowed", file, self.exits.len(), MAX_ZONE_EXITS));
+        }
+        for exit in &self.exits {
+            let on_edge = match exit.direction {
+                ExitDirection::North => exit.y == 0,
+                ExitDirection::South => exit.y == height - 1,
+                ExitDirection::East => exit.x == width - 1,
+                ExitDirection::West => exit.x == 0,
+            };
+            match surface(exit.x, exit.y) {
+                None => return Err(format!("{}: exit is outside the {}x{} grid", at(exit.x, exit.y), width, height)),
+                Some(_) if !on_edge => {
+                    return Err(format!("{}: {:?} exit must be on the {:?} edge of the zone", at(exit.x, exit.y), exit.direction, exit.direction));
+                }
+                Some(surface) if surface.movement_cost_for(Mobility::GROUND).is_none() => {
+                    return Err(format!("{}: exit is on a {:?} tile", at(exit.x, exit.y), surface));
+                }
+                Some(_) => {}
+            }
+        }
+
+        let placements = self.resources.iter()
+            .map(|resource| ("resource deposit", resource.x, resource.y))
+            .chain(self.buildings.iter().map(|building| ("building", building.x, building.y)));
+        for (what, x, y) in placements {
+            match surface(x, y) {
+                None => return Err(format!("{}: {} is outside the {}x{} grid", at(x, y), what, width, height)),
+                Some(SurfaceType::Obstacle) => return Err(format!("{}: {} is on an obstacle", at(x, y), what)),
+                Some(_) => {}
+            }
+        }
+
+        let entities = self.buildings.iter()
+            .zip(1..)
+            .map(|(building, id)| EntityRef {
+                id,
+                kind: building.kind.clone(),
+                owner: building.owner.clone(),
+                x: building.x,
+                y: building.y,
+                hits: DEFAULT_ENTITY_HITS,
+                can_swim: false,
+                can_fly: false,
+            })
+            .collect();
+
+        let mut zone = Zone {
+            id: zone_id,
+            width,
+            height,
+            tiles,
+            exits: self.exits.clone(),
+            entities,
+            resources: self.resources.clone(),
+            owner: None,
+            version: 0,
+            terrain_version: 0,
+            spawn_points: Vec::new(),
+            difficulty: 0.0,
+        };
+        zone.designate_spawn_points();
+        zone.difficulty = zone.rate_difficulty();
+        zone.validate_connectivity().map_err(|e| format!("{}: {}", file, e))?;
+        Ok(zone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRID: &str = r#"grid = [
+    "OOOOPOOO",
+    "OPPPPPPO",
+    "PPPSSPPP",
+    "OPPWWPPO",
+    "OPPPPPPO",
+    "OPPPPPPO",
+    "OPPPPPPO",
+    "OOOOOOOO",
+]
+"#;
+
+    #[test]
+    fn test_template_errors_name_row_and_column() {
+        let source = format!("{}[[exits]]\nx = 0\ny = 2\ndirection = \"West\"\n", GRID.replace("OPPWWPPO", "OPPWXPPO"));
+        let template = MapTemplate::parse(&source, TemplateFormat::Toml, "maps/bad.toml").unwrap();
+        let err = template.to_zone("map_bad".to_string()).unwrap_err();
+        assert_eq!(err, "maps/bad.toml: row 4, column 5: invalid tile code 'X' (expected P, S, W, or O)");
+
+        let source = format!("{}[[exits]]\nx = 7\ny = 2\ndirection = \"West\"\n", GRID);
+        let template = MapTemplate::parse(&source, TemplateFormat::Toml, "maps/bad.toml").unwrap();
+        let err = template.to_zone("map_bad".to_string()).unwrap_err();
+        assert_eq!(err, "maps/bad.toml: row 3, column 8: West exit must be on the West edge of the zone");
+
+        let source = format!("{}[[buildings]]\nkind = \"base\"\nx = 0\ny = 0\n", GRID);
+        let template = MapTemplate::parse(&source, TemplateFormat::Toml, "maps/bad.toml").unwrap();
+        assert!(template.to_zone("map_bad".to_string()).unwrap_err().contains("row 1, column 1: building is on an obstacle"));
+
+        let err = MapTemplate::parse("grid = [", TemplateFormat::Toml, "maps/bad.toml").unwrap_err();
+        assert!(err.starts_with("maps/bad.toml: malformed map template"), "{}", err);
+        assert!(validate_template_name("../secret").is_err());
+    }
+}
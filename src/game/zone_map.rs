@@ -0,0 +1,315 @@
+//! Zone map module
+//!
+//! The zones of a [`World`](crate::game::world::World), each behind its own lock in a
+//! sharded map. The world changes a zone under that zone's lock only, so a
+//! [`ZoneReader`] taken from the world reads any zone without waiting for the world
+//! itself: reading zone B is never held up by a tick changing zone A, a zone being
+//! generated, or the world being saved. Only reads are sharded: the game loop still
+//! holds the world's lock for writing through a whole tick, so changes to the world
+//! (ticks, zone generation, admin edits) run one at a time. Ownership lives in the
+//! zones, so it is sharded along with them.
+//!
+//! The guards the map hands out ([`ZoneRef`], [`ZoneMut`]) hold the zone's lock until
+//! they are dropped: a zone must not be locked for writing while the same thread still
+//! holds a guard on it.
+//!
+//! For callers that need plain references, [`ZoneMap::snapshot`] hands out a copy of a
+//! zone and [`ZoneMap::checkout`] a zone to change, written back the next time the map
+//! is used. Until then, readers are told to go through the world (see
+//! [`ZoneReader::is_stale`]).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+
+use dashmap::DashMap;
+use elsa::sync::FrozenMap;
+use parking_lot::{ArcRwLockReadGuard, ArcRwLockWriteGuard, Mutex, RawRwLock, RwLock};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::game::zone::Zone;
+
+type Shards = DashMap<String, Arc<RwLock<Zone>>>;
+
+/// A zone locked for reading
+pub struct ZoneRef<'a> {
+    guard: ArcRwLockReadGuard<RawRwLock, Zone>,
+    _map: PhantomData<&'a ()>,
+}
+
+impl Deref for ZoneRef<'_> {
+    type Target = Zone;
+
+    fn deref(&self) -> &Zone {
+        &self.guard
+    }
+}
+
+impl PartialEq<&Zone> for ZoneRef<'_> {
+    fn eq(&self, other: &&Zone) -> bool {
+        **self == **other
+    }
+}
+
+impl fmt::Debug for ZoneRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// A zone locked for writing
+pub struct ZoneMut<'a> {
+    guard: ArcRwLockWriteGuard<RawRwLock, Zone>,
+    _map: PhantomData<&'a ()>,
+}
+
+impl Deref for ZoneMut<'_> {
+    type Target = Zone;
+
+    fn deref(&self) -> &Zone {
+        &self.guard
+    }
+}
+
+impl DerefMut for ZoneMut<'_> {
+    fn deref_mut(&mut self) -> &mut Zone {
+        &mut self.guard
+    }
+}
+
+impl fmt::Debug for ZoneMut<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Lock of a zone, taken out of its shard so the shard is not held while the zone is locked
+fn zone_lock(shards: &Shards, zone_id: &str) -> Option<Arc<RwLock<Zone>>> {
+    shards.get(zone_id).map(|lock| lock.value().clone())
+}
+
+/// Zones of a world by ID, each with its own lock
+///
+/// Cloning copies the zones; use [`ZoneMap::reader`] to read them from other tasks.
+#[derive(Default)]
+pub struct ZoneMap {
+    shards: Arc<Shards>,
+    /// Copy of a zone handed out by [`ZoneMap::checkout`], not written back yet
+    checked_out: Mutex<Option<Zone>>,
+    /// Whether `checked_out` holds a zone, shared with the readers
+    checkout_pending: Arc<AtomicBool>,
+    /// Copies handed out by [`ZoneMap::snapshot`], by zone ID and [`Zone::version`]
+    snapshots: FrozenMap<(String, u64), Box<Zone>>,
+}
+
+impl ZoneMap {
+    /// Create an empty map
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of zones
+    pub fn len(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Whether there are no zones
+    pub fn is_empty(&self) -> bool {
+        self.shards.is_empty()
+    }
+
+    /// Whether a zone exists
+    pub fn contains_key(&self, zone_id: &str) -> bool {
+        self.shards.contains_key(zone_id)
+    }
+
+    /// IDs of every zone, sorted
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.shards.iter().map(|entry| entry.key().clone()).collect();
+        ids.sort();
+        ids
+    }
+
+    /// Lock a zone for reading
+    pub fn get(&self, zone_id: &str) -> Option<ZoneRef<'_>> {
+        self.write_back();
+        let guard = zone_lock(&self.shards, zone_id)?.read_arc();
+        Some(ZoneRef { guard, _map: PhantomData })
+    }
+
+    /// Lock a zone for writing, while other zones can still be read and written
+    pub fn write(&self, zone_id: &str) -> Option<ZoneMut<'_>> {
+        self.write_back();
+        let guard = zone_lock(&self.shards, zone_id)?.write_arc();
+        Some(ZoneMut { guard, _map: PhantomData })
+    }
+
+    /// Lock a zone for writing, with exclusive access to the map
+    pub fn get_mut(&mut self, zone_id: &str) -> Option<ZoneMut<'_>> {
+        self.snapshots.as_mut().clear();
+        self.write(zone_id)
+    }
+
+    /// Lock every zone for reading, in ID order
+    pub fn values(&self) -> Vec<ZoneRef<'_>> {
+        self.ids().iter().filter_map(|zone_id| self.get(zone_id)).collect()
+    }
+
+    /// Add a zone under its ID, replacing any zone with that ID
+    pub fn insert(&mut self, zone: Zone) {
+        self.write_back();
+        self.snapshots.as_mut().clear();
+        self.shards.insert(zone.id.clone(), Arc::new(RwLock::new(zone)));
+    }
+
+    /// Remove a zone
+    pub fn remove(&mut self, zone_id: &str) -> Option<Zone> {
+        self.write_back();
+        self.snapshots.as_mut().clear();
+        let (_, lock) = self.shards.remove(zone_id)?;
+        // A reader may still hold the lock for a moment
+        Some(Arc::try_unwrap(lock).map_or_else(|lock| lock.read().clone(), RwLock::into_inner))
+    }
+
+    /// A copy of a zone as it is now, kept until the map is next changed with `&mut`
+    ///
+    /// The copy is made once per [`Zone::version`], which every change of the zone must bump.
+    pub fn snapshot(&self, zone_id: &str) -> Option<&Zone> {
+        let zone = self.get(zone_id)?;
+        let key = (zone_id.to_string(), zone.version);
+        Some(self.snapshots.get(&key).unwrap_or_else(|| self.snapshots.insert(key, Box::new(zone.clone()))))
+    }
+
+    /// A copy of a zone to change, written back the next time the map is used
+    ///
+    /// Until then, readers still see the zone as it was.
+    pub fn checkout(&mut self, zone_id: &str) -> Option<&mut Zone> {
+        let zone = self.get(zone_id)?.clone();
+        self.snapshots.as_mut().clear();
+        self.checkout_pending.store(true, Ordering::Release);
+        Some(self.checked_out.get_mut().insert(zone))
+    }
+
+    /// Write back the zone handed out by [`ZoneMap::checkout`], if any
+    fn write_back(&self) {
+        let Some(zone) = self.checked_out.lock().take() else {
+            return;
+        };
+        match zone_lock(&self.shards, &zone.id) {
+            Some(lock) => *lock.write() = zone,
+            None => log::warn!("Checked out zone {} was removed before being written back", zone.id),
+        }
+        self.checkout_pending.store(false, Ordering::Release);
+    }
+
+    /// Read access to the zones that does not need the world
+    ///
+    /// The reader follows the zones this map holds as they are added, changed and removed,
+    /// until the map is dropped.
+    pub fn reader(&self) -> ZoneReader {
+        self.write_back();
+        ZoneReader { shards: Arc::downgrade(&self.shards), checkout_pending: self.checkout_pending.clone() }
+    }
+}
+
+impl Clone for ZoneMap {
+    fn clone(&self) -> Self {
+        let mut zones = ZoneMap::new();
+        for zone in self.values() {
+            zones.insert(zone.clone());
+        }
+        zones
+    }
+}
+
+impl fmt::Debug for ZoneMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let zones = self.values();
+        f.debug_map().entries(zones.iter().map(|zone| (&zone.id, &**zone))).finish()
+    }
+}
+
+impl Serialize for ZoneMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let zones = self.values();
+        serializer.collect_map(zones.iter().map(|zone| (&zone.id, &**zone)))
+    }
+}
+
+impl<'de> Deserialize<'de> for ZoneMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut zones = ZoneMap::new();
+        for (zone_id, mut zone) in HashMap::<String, Zone>::deserialize(deserializer)? {
+            zone.id = zone_id;
+            zones.insert(zone);
+        }
+        Ok(zones)
+    }
+}
+
+/// Shared read access to the zones of a world (see [`ZoneMap::reader`])
+///
+/// Once the world is dropped (e.g. replaced by another one), the reader is detached and
+/// finds no zones.
+#[derive(Clone, Default)]
+pub struct ZoneReader {
+    shards: Weak<Shards>,
+    checkout_pending: Arc<AtomicBool>,
+}
+
+impl ZoneReader {
+    /// Run `read` on a zone under its read lock (`None` if the zone does not exist)
+    ///
+    /// Only that zone is locked, and only while `read` runs.
+    pub fn with_zone_read<R>(&self, zone_id: &str, read: impl FnOnce(&Zone) -> R) -> Option<R> {
+        let shards = self.shards.upgrade()?;
+        let lock = zone_lock(&shards, zone_id)?;
+        let zone = lock.read();
+        Some(read(&zone))
+    }
+
+    /// Whether the world this reader was taken from is gone
+    pub fn is_detached(&self) -> bool {
+        self.shards.strong_count() == 0
+    }
+
+    /// Whether the zones must be read through the world instead: it is gone, or holds a
+    /// zone handed out by [`ZoneMap::checkout`] that is not written back yet
+    pub fn is_stale(&self) -> bool {
+        self.is_detached() || self.checkout_pending.load(Ordering::Acquire)
+    }
+
+    /// Whether a zone exists
+    pub fn contains(&self, zone_id: &str) -> bool {
+        self.shards.upgrade().is_some_and(|shards| shards.contains_key(zone_id))
+    }
+
+    /// IDs of every zone, sorted
+    pub fn ids(&self) -> Vec<String> {
+        let Some(shards) = self.shards.upgrade() else {
+            return Vec::new();
+        };
+        let mut ids: Vec<String> = shards.iter().map(|entry| entry.key().clone()).collect();
+        ids.sort();
+        ids
+    }
+
+    /// Number of zones
+    pub fn len(&self) -> usize {
+        self.shards.upgrade().map_or(0, |shards| shards.len())
+    }
+
+    /// Whether there are no zones
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for ZoneReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZoneReader").field("zones", &self.len()).finish()
+    }
+}
@@ -5,6 +5,8 @@
 
 #![warn(missing_docs)]
 #![warn(clippy::all)]
+// The root endpoint listing in network::server is one large `json!` literal
+#![recursion_limit = "512"]
 
 /// Game management module (world, campaign, zones)
 pub mod game;
@@ -18,23 +20,17 @@ pub mod scripting;
 /// Authentication module (user management, sessions)
 pub mod auth;
 
+/// Client module (typed async client for the REST and WebSocket API)
+pub mod client;
+
+/// Logging module (tracing subscriber, JSON or human-readable output)
+pub mod logging;
+
+/// Utilities module (retries with backoff)
+pub mod utils;
+
 /// Game version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-/// Default server configuration
-pub mod config {
-    /// Default server port
-    pub const DEFAULT_PORT: u16 = 3030;
-    
-    /// Default server address
-    pub const DEFAULT_HOST: &str = "127.0.0.1";
-    
-    /// Number of ticks per second
-    pub const TICKS_PER_SECOND: u32 = 60;
-    
-    /// Maximum timeout for script execution (ms)
-    pub const SCRIPT_TIMEOUT_MS: u64 = 100;
-    
-    /// Maximum memory for a script (MB)
-    pub const SCRIPT_MAX_MEMORY_MB: usize = 128;
-}
\ No newline at end of file
+/// Server configuration (defaults, TOML file and environment overrides)
+pub mod config;
\ No newline at end of file
@@ -0,0 +1,75 @@
+//! Logging module
+//!
+//! Structured logging with `tracing`. Work is grouped in spans so a log line can be
+//! traced back to what caused it:
+//! - `http_request` (`method`, `path`, `request_id`, `user`, then `status`, `duration_ms`
+//!   and `outcome` when the response is sent)
+//! - `ws_connection` (`connection_id`, `user` once authenticated)
+//! - `tick` (`tick`, `script_tick` on script ticks, then `scripts` and `duration_ms`)
+//! - `script`, a child of `tick` per player script (`player`, `language`, then `cpu_ns`
+//!   and `outcome`)
+//!
+//! Records from the `log` macros are bridged into tracing and carry the current spans too.
+//! The output is human-readable by default, or one JSON object per line with
+//! `GEEKCRAFT_LOG_FORMAT=json`. `RUST_LOG` sets the filter (default `info`).
+
+use tracing::Subscriber;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// Format of the log output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines, with the fields of the enclosing spans
+    #[default]
+    Pretty,
+    /// One JSON object per line, with the enclosing spans in `spans`
+    Json,
+}
+
+impl LogFormat {
+    /// Parse a format name (`pretty` or `json`)
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("Unknown log format: {} (expected pretty or json)", other)),
+        }
+    }
+
+    /// Format selected by `GEEKCRAFT_LOG_FORMAT` (pretty if unset or invalid)
+    pub fn from_env() -> Self {
+        std::env::var("GEEKCRAFT_LOG_FORMAT")
+            .ok()
+            .and_then(|name| Self::from_name(&name).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Build a subscriber writing events accepted by `filter` to `writer`
+pub fn subscriber<W>(format: LogFormat, filter: EnvFilter, writer: W) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer);
+    match format {
+        LogFormat::Pretty => Box::new(builder.finish()),
+        LogFormat::Json => Box::new(builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(false)
+            .with_span_list(true)
+            .finish()),
+    }
+}
+
+/// Install the global subscriber, writing to stderr, and bridge the `log` macros into it
+pub fn init(format: LogFormat) -> Result<(), String> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    subscriber(format, filter, std::io::stderr)
+        .try_init()
+        .map_err(|e| format!("Failed to install the log subscriber: {}", e))
+}
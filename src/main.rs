@@ -2,18 +2,46 @@
 //! 
 //! Application entry point. Initializes the server and starts the game engine.
 
-use geekcraft::{game, network, scripting, auth};
-use log::{info, error};
+use geekcraft::{config, game, logging, network, scripting, auth};
+use log::{info, warn, error};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize logger
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    // Initialize logging (GEEKCRAFT_LOG_FORMAT=json for JSON lines)
+    if let Err(e) = logging::init(logging::LogFormat::from_env()) {
+        eprintln!("{}", e);
+    }
     
     info!("🎮 Starting GeekCraft v{}", env!("CARGO_PKG_VERSION"));
     
+    // Load settings: the config file (GEEKCRAFT_CONFIG, or ./geekcraft.toml if present),
+    // then environment variables, then defaults
+    let config_path = std::env::var("GEEKCRAFT_CONFIG").ok()
+        .or_else(|| std::path::Path::new(config::CONFIG_PATH).exists().then(|| config::CONFIG_PATH.to_string()));
+    let server_config = match &config_path {
+        Some(path) => {
+            info!("⚙️  Loading configuration from {}", path);
+            config::ServerConfig::from_file(path)?
+        }
+        None => config::ServerConfig::from_env(),
+    };
+    let problems = server_config.validate();
+    for problem in &problems {
+        if problem.is_fatal() {
+            error!("❌ Configuration: {}", problem);
+        } else {
+            warn!("⚠️  Configuration: {}", problem);
+        }
+    }
+    if problems.iter().any(|problem| problem.is_fatal()) {
+        anyhow::bail!("Invalid configuration");
+    }
+    if server_config.maintenance_mode {
+        warn!("🚧 Maintenance mode is on: only admins are served until POST /api/admin/maintenance/disable");
+    }
+    
     // Choose database backend based on environment variable
     // Options: INMEMORY (default), MONGODB
     let db_backend = std::env::var("GEEKCRAFT_DB_BACKEND")
@@ -38,39 +66,194 @@ async fn main() -> anyhow::Result<()> {
         .expect("Failed to initialize authentication database"));
     info!("✓ Authentication database initialized");
     
+    // Choose audit log store based on environment variable
+    // Options: SQLITE (default), INMEMORY, REDIS (feature `redis_backend`)
+    let audit_store: Arc<dyn auth::audit::AuditStore> = match std::env::var("GEEKCRAFT_AUDIT_STORE")
+        .unwrap_or_else(|_| "SQLITE".to_string())
+        .to_uppercase()
+        .as_str()
+    {
+        "INMEMORY" => {
+            info!("📦 Using In-Memory audit log (entries will be lost on restart)");
+            Arc::new(auth::audit::InMemoryAuditStore::new())
+        }
+        #[cfg(feature = "redis_backend")]
+        "REDIS" => {
+            let url = std::env::var("GEEKCRAFT_REDIS_URL")
+                .unwrap_or_else(|_| network::pubsub::DEFAULT_REDIS_URL.to_string());
+            info!("🗄️  Using Redis audit log at {}", url);
+            Arc::new(auth::audit::RedisAuditStore::open(&url)
+                .expect("Failed to open the Redis audit log"))
+        }
+        _ => {
+            let path = std::env::var("GEEKCRAFT_AUDIT_DB")
+                .unwrap_or_else(|_| geekcraft::config::AUDIT_DB_PATH.to_string());
+            info!("🗄️  Using SQLite audit log at {}", path);
+            Arc::new(auth::audit::SqliteAuditStore::open_with_readers(std::path::Path::new(&path), server_config.sqlite_read_connections)
+                .expect("Failed to open the audit database"))
+        }
+    };
+
     // Create authentication service
-    let auth_service = Arc::new(auth::AuthService::new(auth_db));
+    let auth_service = Arc::new(auth::AuthService::new(auth_db)
+        .with_max_alliance_size(server_config.max_alliance_size)
+        .with_session_duration(server_config.session_duration_secs)
+        .with_sliding_expiration(server_config.session_sliding_percent)
+        .with_max_session_lifetime(server_config.session_max_lifetime_secs)
+        .with_audit_store(audit_store));
     info!("✓ Authentication service initialized");
     
     // Create game world
-    let game_world = Arc::new(RwLock::new(game::world::World::new()));
-    info!("✓ Game world initialized");
+    // Choose world store based on environment variable
+    // Options: SQLITE (default), INMEMORY
+    let world_store: Arc<dyn game::store::WorldStore> = match std::env::var("GEEKCRAFT_WORLD_STORE")
+        .unwrap_or_else(|_| "SQLITE".to_string())
+        .to_uppercase()
+        .as_str()
+    {
+        "INMEMORY" => {
+            info!("📦 Using In-Memory world store (zones will be lost on restart)");
+            Arc::new(game::store::InMemoryWorldStore::new())
+        }
+        _ => {
+            let path = std::env::var("GEEKCRAFT_WORLD_DB")
+                .unwrap_or_else(|_| geekcraft::config::WORLD_DB_PATH.to_string());
+            info!("🗄️  Using SQLite world store at {}", path);
+            Arc::new(game::store::SqliteWorldStore::open_with_readers(std::path::Path::new(&path), server_config.sqlite_read_connections)
+                .expect("Failed to open world database"))
+        }
+    };
+
+    let mut world = game::world::World::open(server_config.world_config(), world_store)
+        .expect("Failed to load zones from the world store");
+    // Record moves, harvests and captures to an append-only file if asked to
+    if let Ok(path) = std::env::var("GEEKCRAFT_ACTION_LOG") {
+        let log = game::event_log::ActionLog::open(std::path::Path::new(&path))
+            .expect("Failed to open the action log");
+        info!("📜 Recording game actions to {} ({} entries already)", path, log.entries().len());
+        world.set_action_log(log);
+    }
+    info!("✓ Game world initialized ({}x{}, max {} zones, {} zones loaded)",
+        world.config().width, world.config().height, world.config().max_zones, world.get_zone_ids().len());
+    let zones = world.zone_reader();
+    let game_world = Arc::new(RwLock::new(world));
     
     // Create scripting engine
-    let script_engine = Arc::new(RwLock::new(scripting::sandbox::ScriptEngine::new()));
-    info!("✓ Scripting engine initialized");
+    let mut engine = scripting::sandbox::ScriptEngine::with_limits(server_config.script_limits());
+    let messages_allies_only = server_config.messages_allies_only;
+    engine.set_allies_only(messages_allies_only);
+    engine.set_inbox_limit(server_config.inbox_limit);
+    engine.set_max_concurrent_scripts(server_config.max_concurrent_scripts);
+    engine.set_script_queue_timeout(std::time::Duration::from_millis(server_config.script_queue_timeout_ms));
+    engine.set_min_api_version(server_config.min_api_version);
+    match auth_service.approved_libraries() {
+        Ok(libraries) => engine.set_libraries(libraries.into_iter().map(|library| (library.name, library.code)).collect()),
+        Err(e) => warn!("⚠️  Failed to load shared script libraries: {}", e),
+    }
+    let script_engine = scripting::handle::ScriptEngineHandle::new(engine);
+    info!("✓ Scripting engine initialized (bot messages {})",
+        if messages_allies_only { "between allies only" } else { "between any players" });
     
-    // Start network server
-    let server_handle = tokio::spawn(async move {
-        if let Err(e) = network::server::start_server(
-            game_world.clone(), 
-            script_engine.clone(),
-            auth_service.clone(),
-        ).await {
+    // Start game loop (settings are shared with the server, which can reload them)
+    let ticks_per_second = server_config.ticks_per_second;
+    let shared_config = Arc::new(std::sync::RwLock::new(server_config));
+    let sim_control = game::game_loop::SimControl::new();
+    let stats = game::stats::SharedStats::default();
+    // Edge instances of a multi-server deployment forward the ticks of the sim instance
+    #[cfg(feature = "redis_backend")]
+    let simulates = network::pubsub::Role::from_env().simulates();
+    #[cfg(not(feature = "redis_backend"))]
+    let simulates = true;
+    let mut game_loop = None;
+    if simulates {
+        let stats_feed = game::stats::spawn_stats_updater(stats.clone());
+        game_loop = Some(tokio::spawn(game::game_loop::run_game_loop(game_world.clone(), script_engine.clone(), shared_config.clone(), sim_control.clone(), stats_feed)));
+        info!("✓ Game loop started ({} ticks/s, scripts every {} ticks)",
+            ticks_per_second, game_world.read().await.config().script_tick_interval);
+    } else {
+        info!("✓ Edge instance: game loop not started, ticks come from the sim instance");
+    }
+    
+    // Start network server; it stops on SIGINT or SIGTERM
+    let (stop_server, server_stopped) = tokio::sync::oneshot::channel::<()>();
+    let server_world = game_world.clone();
+    let server_control = sim_control.clone();
+    let mut server_handle = tokio::spawn(async move {
+        let parts = network::server::ServerParts {
+            game_world: server_world,
+            zones,
+            script_engine: script_engine.clone(),
+            auth_service: auth_service.clone(),
+            config: shared_config,
+            config_path,
+            sim_control: server_control,
+            stats,
+        };
+        if let Err(e) = network::server::start_server(parts, async move { let _ = server_stopped.await; }).await {
             error!("❌ Server error: {}", e);
         }
     });
     
-    info!("✓ Network server started at http://localhost:3030");
-    info!("✓ WebSocket available at ws://localhost:3030/ws");
     info!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
     info!("🚀 GeekCraft is ready!");
     info!("📚 Check out the examples in /examples");
     info!("🔐 Authentication enabled - register to start playing");
     info!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
     
-    // Wait for server to finish
+    // Wait for the server to finish, or for a shutdown signal
+    tokio::select! {
+        result = &mut server_handle => {
+            result?;
+            return Ok(());
+        }
+        () = shutdown_signal() => {}
+    }
+
+    info!("🛑 Shutting down");
+    if let Some(game_loop) = game_loop {
+        network::shutdown::stop_game_loop(&sim_control, game_loop).await;
+        info!("✓ Game loop stopped");
+    }
+    let _ = stop_server.send(());
     server_handle.await?;
+
+    let save_dir = game::campaign::save_dir_from_env();
+    if network::shutdown::save_state(&game_world, &save_dir).await {
+        info!("👋 GeekCraft stopped");
+    } else {
+        warn!("⚠️  GeekCraft stopped, but some state could not be saved");
+    }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Complete on Ctrl+C (SIGINT) or, on Unix, SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("❌ Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                error!("❌ Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => info!("Received SIGINT"),
+        () = terminate => info!("Received SIGTERM"),
+    }
+}
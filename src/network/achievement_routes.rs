@@ -0,0 +1,73 @@
+//! Achievement routes module
+//!
+//! HTTP endpoint listing the caller's achievements, and the helper that unlocks
+//! achievements and notifies the player over WebSocket.
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+
+use crate::auth::achievements::{Achievement, PlayerStats};
+use crate::auth::AuthService;
+use crate::network::extract::AuthSession;
+use crate::network::server::AppState;
+use crate::network::ws_clients::WsClients;
+
+/// Response for the caller's achievements
+#[derive(Debug, Serialize)]
+pub struct AchievementsResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Achievements the caller has unlocked
+    pub unlocked: Vec<Achievement>,
+    /// Achievements still locked
+    pub locked: Vec<Achievement>,
+}
+
+/// Unlock the achievements `stats` earn a user and send each new one to their WebSocket connections
+///
+/// Sends `{"type": "achievementUnlocked", "achievement": {...}}` per achievement. Blocking
+/// (it queries the auth database).
+pub fn unlock_and_notify(auth_service: &AuthService, ws_clients: &WsClients, user_id: i64, stats: &PlayerStats) -> Vec<Achievement> {
+    let unlocked = auth_service.check_and_unlock_achievements(user_id, stats);
+    for achievement in &unlocked {
+        ws_clients.send_to_user(user_id, &serde_json::json!({
+            "type": "achievementUnlocked",
+            "achievement": achievement
+        }));
+    }
+    unlocked
+}
+
+/// Handler to list the caller's unlocked and locked achievements
+pub async fn my_achievements_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+) -> impl IntoResponse {
+    match state.auth_service.achievements_of(session.user_id) {
+        Ok((unlocked, locked)) => (
+            StatusCode::OK,
+            Json(AchievementsResponse {
+                success: true,
+                message: format!("{} of {} achievements unlocked", unlocked.len(), unlocked.len() + locked.len()),
+                unlocked,
+                locked,
+            })
+        ),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(AchievementsResponse {
+                success: false,
+                message: err,
+                unlocked: Vec::new(),
+                locked: Vec::new(),
+            })
+        ),
+    }
+}
@@ -0,0 +1,512 @@
+//! Admin routes module
+//!
+//! HTTP endpoints for server operators managing player accounts and invite codes,
+//! controlling the game loop (pause, resume, step), reloading the configuration,
+//! switching maintenance mode and reading the audit log (see [`crate::auth::audit`]).
+//! Every handler requires a user listed in `GEEKCRAFT_ADMIN_USERS`.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::audit::{AuditAction, AuditFilter};
+use crate::auth::{Invite, RegistrationMode, UserFilter};
+use crate::config::{ConfigError, ServerConfig};
+use crate::game::game_loop::RunState;
+use crate::network::extract::{JSON_BODY_LIMIT, ApiError, AuthSession, SizedBody, SizedJson};
+use crate::network::pagination::{PaginatedResponse, PaginationQuery};
+use crate::network::server::AppState;
+
+/// A user account, as listed by `GET /api/admin/users`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserSummary {
+    /// User ID
+    pub id: i64,
+    /// Username
+    pub username: String,
+    /// Account creation timestamp (Unix epoch)
+    pub created_at: i64,
+    /// ELO rating
+    pub rating: i32,
+    /// Whether the user has an active (non-expired) session
+    pub online: bool,
+    /// Whether the user is an admin
+    pub admin: bool,
+}
+
+/// Response listing user accounts
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserListResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Users, sorted by ID
+    pub users: Vec<UserSummary>,
+}
+
+fn user_list_error(status: StatusCode, message: String) -> (StatusCode, Json<UserListResponse>) {
+    (
+        status,
+        Json(UserListResponse {
+            success: false,
+            message,
+            users: Vec::new(),
+        })
+    )
+}
+
+/// Handler to list every user account (admin only)
+pub async fn list_users_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+) -> impl IntoResponse {
+    if !state.is_admin(&session.username) {
+        return user_list_error(StatusCode::FORBIDDEN, "Admin access required".to_string());
+    }
+
+    let users = match state.auth_service.list_users(0, u32::MAX, &UserFilter::default()) {
+        Ok((users, _)) => users,
+        Err(err) => return user_list_error(StatusCode::INTERNAL_SERVER_ERROR, err),
+    };
+    let summaries: Vec<UserSummary> = users.into_iter()
+        .map(|(user, online)| UserSummary {
+            id: user.id,
+            admin: state.is_admin(&user.username),
+            username: user.username,
+            created_at: user.created_at,
+            rating: user.rating,
+            online,
+        })
+        .collect();
+
+    (
+        StatusCode::OK,
+        Json(UserListResponse {
+            success: true,
+            message: format!("{} users", summaries.len()),
+            users: summaries,
+        })
+    )
+}
+
+fn default_invite_uses() -> u32 {
+    1
+}
+
+/// Request to create an invite code
+#[derive(Debug, Deserialize)]
+pub struct CreateInviteRequest {
+    /// Number of registrations the code allows (default 1)
+    #[serde(default = "default_invite_uses")]
+    pub max_uses: u32,
+    /// Seconds until the code expires (never by default)
+    #[serde(default)]
+    pub expires_in_secs: Option<i64>,
+}
+
+/// Response with a new invite code
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InviteResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// The invite code
+    pub invite: Invite,
+}
+
+/// Response listing invite codes
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InviteListResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Who can currently create an account
+    pub registration_mode: RegistrationMode,
+    /// Every invite code, oldest first, including used up and expired ones
+    pub invites: Vec<Invite>,
+}
+
+/// Handler to create an invite code (admin only)
+///
+/// Codes are accepted by `POST /api/auth/register` while `registration_mode` is `invite`.
+pub async fn create_invite_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<CreateInviteRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if !state.is_admin(&session.username) {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, "Admin access required"));
+    }
+    if payload.max_uses == 0 {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "max_uses must be at least 1"));
+    }
+    if payload.expires_in_secs.is_some_and(|secs| secs <= 0) {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "expires_in_secs must be positive"));
+    }
+
+    let expires_at = payload.expires_in_secs.map(|secs| chrono::Utc::now().timestamp().saturating_add(secs));
+    let (created_by, max_uses) = (session.username.clone(), payload.max_uses);
+    let invite = state.call_auth(move |auth_service| auth_service.create_invite(&created_by, max_uses, expires_at))
+        .await
+        .and_then(|invite| invite)
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    log::info!("Invite code for {} registrations created by {}", invite.max_uses, session.username);
+    Ok((
+        StatusCode::CREATED,
+        Json(InviteResponse {
+            success: true,
+            message: format!("Invite code created for {} registrations", invite.max_uses),
+            invite,
+        })
+    ))
+}
+
+/// Handler to list the invite codes (admin only)
+pub async fn list_invites_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+) -> Result<impl IntoResponse, ApiError> {
+    if !state.is_admin(&session.username) {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, "Admin access required"));
+    }
+    let invites = state.auth_service.list_invites()
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(InviteListResponse {
+        success: true,
+        message: format!("{} invite codes", invites.len()),
+        registration_mode: state.config().registration_mode,
+        invites,
+    }))
+}
+
+/// Handler to revoke an invite code, which stops being accepted (admin only)
+pub async fn revoke_invite_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path(code): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    if !state.is_admin(&session.username) {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, "Admin access required"));
+    }
+    let revoked = state.call_auth(move |auth_service| auth_service.revoke_invite(&code))
+        .await
+        .and_then(|revoked| revoked)
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    if !revoked {
+        return Err(ApiError::new(StatusCode::NOT_FOUND, "Invite code not found"));
+    }
+    log::info!("Invite code revoked by {}", session.username);
+    Ok(Json(serde_json::json!({"success": true, "message": "Invite code revoked"})))
+}
+
+/// Query parameters of `GET /api/admin/audit`
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditQuery {
+    /// Only entries of this user
+    pub user: Option<String>,
+    /// Only entries of this action (`login`, `logout`, `code_submission` or `admin`)
+    pub action: Option<String>,
+    /// Only entries at or after this time (Unix epoch seconds)
+    pub since: Option<i64>,
+    /// Page number, from 1 (default 1)
+    pub page: Option<u32>,
+    /// Entries per page
+    pub per_page: Option<u32>,
+}
+
+/// Handler to read the audit log, newest first, one page at a time (admin only)
+pub async fn audit_log_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Query(query): Query<AuditQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    if !state.is_admin(&session.username) {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, "Admin access required"));
+    }
+    let action = query.action.as_deref()
+        .map(AuditAction::parse)
+        .transpose()
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e))?;
+
+    let (page, per_page) = PaginationQuery { page: query.page, per_page: query.per_page }.resolve();
+    let filter = AuditFilter {
+        user: query.user,
+        action,
+        since: query.since,
+        offset: (page - 1).saturating_mul(per_page),
+        limit: per_page,
+    };
+    let (entries, total) = state.audit.query_audit(&filter)
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(PaginatedResponse::new(entries, page, per_page, total)))
+}
+
+/// Request to run a number of ticks while paused
+#[derive(Debug, Deserialize)]
+pub struct StepRequest {
+    /// Number of ticks to run before pausing again
+    pub ticks: u64,
+}
+
+/// Response for game loop control
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SimControlResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Run state after the request
+    pub run_state: RunState,
+    /// Current simulation tick
+    pub tick: u64,
+}
+
+async fn sim_response(state: &AppState, status: StatusCode, result: Result<String, String>) -> (StatusCode, Json<SimControlResponse>) {
+    let tick = state.game_world.read().await.get_tick();
+    let (success, message) = match result {
+        Ok(message) => (true, message),
+        Err(message) => (false, message),
+    };
+    (
+        status,
+        Json(SimControlResponse {
+            success,
+            message,
+            run_state: state.sim_control.state(),
+            tick,
+        })
+    )
+}
+
+/// Handler to pause the game loop (admin only)
+///
+/// The world stops changing; reads and code submissions keep working, but scripts
+/// don't run until the loop resumes or steps.
+pub async fn pause_sim_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+) -> impl IntoResponse {
+    if !state.is_admin(&session.username) {
+        return sim_response(&state, StatusCode::FORBIDDEN, Err("Admin access required".to_string())).await;
+    }
+    state.sim_control.pause();
+    log::info!("Simulation paused by {}", session.username);
+    sim_response(&state, StatusCode::OK, Ok("Simulation paused".to_string())).await
+}
+
+/// Handler to resume the game loop (admin only)
+pub async fn resume_sim_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+) -> impl IntoResponse {
+    if !state.is_admin(&session.username) {
+        return sim_response(&state, StatusCode::FORBIDDEN, Err("Admin access required".to_string())).await;
+    }
+    state.sim_control.resume();
+    log::info!("Simulation resumed by {}", session.username);
+    sim_response(&state, StatusCode::OK, Ok("Simulation resumed".to_string())).await
+}
+
+/// Handler to run a number of ticks of the paused game loop, then pause again (admin only)
+pub async fn step_sim_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<StepRequest>,
+) -> impl IntoResponse {
+    if !state.is_admin(&session.username) {
+        return sim_response(&state, StatusCode::FORBIDDEN, Err("Admin access required".to_string())).await;
+    }
+    match state.sim_control.step(payload.ticks) {
+        Ok(remaining) => {
+            let message = format!("Stepping {} ticks ({} left to run)", payload.ticks, remaining);
+            sim_response(&state, StatusCode::OK, Ok(message)).await
+        }
+        Err(err) => {
+            let status = if payload.ticks == 0 { StatusCode::BAD_REQUEST } else { StatusCode::CONFLICT };
+            sim_response(&state, status, Err(err)).await
+        }
+    }
+}
+
+/// Response carrying the server configuration
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfigResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Settings in effect after the request
+    pub config: Option<ServerConfig>,
+    /// Fields whose new value was ignored because they need a restart
+    #[serde(default)]
+    pub restart_required: Vec<String>,
+    /// Non-fatal problems with the new settings
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+fn config_error(status: StatusCode, message: String) -> (StatusCode, Json<ConfigResponse>) {
+    (
+        status,
+        Json(ConfigResponse {
+            success: false,
+            message,
+            config: None,
+            restart_required: Vec::new(),
+            warnings: Vec::new(),
+        })
+    )
+}
+
+/// Handler to get the current server configuration (admin only)
+pub async fn get_config_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+) -> impl IntoResponse {
+    if !state.is_admin(&session.username) {
+        return config_error(StatusCode::FORBIDDEN, "Admin access required".to_string());
+    }
+    (
+        StatusCode::OK,
+        Json(ConfigResponse {
+            success: true,
+            message: "Current configuration".to_string(),
+            config: Some(state.config()),
+            restart_required: Vec::new(),
+            warnings: Vec::new(),
+        })
+    )
+}
+
+/// Handler to reload the server configuration (admin only)
+///
+/// With an empty body the configuration file is read again; restart-only fields that
+/// changed in it are kept and listed in `restart_required`. A JSON object body instead
+/// changes the given fields of the current configuration, and is refused if it changes a
+/// restart-only field. Nothing changes if the new configuration is invalid.
+pub async fn reload_config_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedBody(body): SizedBody<JSON_BODY_LIMIT>,
+) -> impl IntoResponse {
+    if !state.is_admin(&session.username) {
+        return config_error(StatusCode::FORBIDDEN, "Admin access required".to_string());
+    }
+
+    let current = state.config();
+    let (config, ignored) = if body.iter().all(u8::is_ascii_whitespace) {
+        let Some(path) = &state.config_path else {
+            return config_error(StatusCode::BAD_REQUEST, "The server was not started from a configuration file".to_string());
+        };
+        let loaded = match ServerConfig::from_file(path) {
+            Ok(loaded) => loaded,
+            Err(err) => return config_error(StatusCode::BAD_REQUEST, err.to_string()),
+        };
+        match current.reloaded(loaded) {
+            Ok(reloaded) => reloaded,
+            Err(err) => return config_error(StatusCode::INTERNAL_SERVER_ERROR, err),
+        }
+    } else {
+        let patch = match serde_json::from_slice(&body) {
+            Ok(patch) => patch,
+            Err(err) => return config_error(StatusCode::BAD_REQUEST, format!("Invalid JSON: {}", err)),
+        };
+        match current.patched(patch) {
+            Ok(patched) => (patched, Vec::new()),
+            Err(err) => return config_error(StatusCode::BAD_REQUEST, err),
+        }
+    };
+
+    let (fatal, warnings): (Vec<ConfigError>, Vec<ConfigError>) = config.validate()
+        .into_iter()
+        .partition(ConfigError::is_fatal);
+    if !fatal.is_empty() {
+        let problems: Vec<String> = fatal.iter().map(ToString::to_string).collect();
+        return config_error(StatusCode::BAD_REQUEST, problems.join("; "));
+    }
+
+    state.apply_config(config.clone()).await;
+    log::info!("Configuration reloaded by {}", session.username);
+    (
+        StatusCode::OK,
+        Json(ConfigResponse {
+            success: true,
+            message: "Configuration reloaded".to_string(),
+            config: Some(config),
+            restart_required: ignored.into_iter().map(str::to_string).collect(),
+            warnings: warnings.iter().map(ToString::to_string).collect(),
+        })
+    )
+}
+
+/// Response for switching maintenance mode
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MaintenanceResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Whether the server is in maintenance mode after the request
+    pub maintenance_mode: bool,
+    /// Whether the new state was written to the configuration file, and survives a restart
+    pub persisted: bool,
+}
+
+fn set_maintenance(state: &AppState, username: &str, enabled: bool) -> (StatusCode, Json<MaintenanceResponse>) {
+    let response = |status: StatusCode, success: bool, message: String, persisted: bool| (
+        status,
+        Json(MaintenanceResponse {
+            success,
+            message,
+            maintenance_mode: state.in_maintenance(),
+            persisted,
+        })
+    );
+    if !state.is_admin(username) {
+        return response(StatusCode::FORBIDDEN, false, "Admin access required".to_string(), false);
+    }
+
+    let persisted = match &state.config_path {
+        Some(path) => match ServerConfig::write_field(path, "maintenance_mode", enabled) {
+            Ok(()) => true,
+            Err(err) => return response(StatusCode::INTERNAL_SERVER_ERROR, false, err, false),
+        },
+        None => false,
+    };
+    state.set_maintenance(enabled);
+
+    let mut message = format!("Maintenance mode {}", if enabled { "enabled" } else { "disabled" });
+    log::info!("{} by {}", message, username);
+    if !persisted {
+        message.push_str(" until the server restarts: it was not started from a configuration file");
+        log::warn!("⚠️  {}", message);
+    }
+    response(StatusCode::OK, true, message, persisted)
+}
+
+/// Handler to put the server in maintenance mode (admin only)
+///
+/// Requests of other users then get `503 Service Unavailable` with a `Retry-After`
+/// header. The mode is written to the configuration file the server was started from,
+/// so that it is still on after a restart; nothing changes if the file cannot be written.
+pub async fn enable_maintenance_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+) -> impl IntoResponse {
+    set_maintenance(&state, &session.username, true)
+}
+
+/// Handler to end maintenance mode (admin only)
+pub async fn disable_maintenance_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+) -> impl IntoResponse {
+    set_maintenance(&state, &session.username, false)
+}
@@ -0,0 +1,150 @@
+//! Alliance routes module
+//!
+//! HTTP endpoint handlers for alliances (create, invite, accept, leave). Alliances are
+//! stored in the auth database and mirrored into the game world, where members share
+//! visibility, see each other's zones on the map, and cannot attack each other. Unlike
+//! teams, allies keep their own resources.
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::auth::models::Alliance;
+use crate::network::extract::{AuthSession, SizedJson};
+use crate::network::server::AppState;
+
+/// Request to create an alliance
+#[derive(Debug, Deserialize)]
+pub struct CreateAllianceRequest {
+    /// Alliance name
+    pub name: String,
+}
+
+/// Request to invite a player to the caller's alliance
+#[derive(Debug, Deserialize)]
+pub struct InviteAllianceRequest {
+    /// Username of the player to invite
+    pub username: String,
+}
+
+/// Request to accept an alliance invitation
+#[derive(Debug, Deserialize)]
+pub struct AcceptAllianceRequest {
+    /// Alliance the caller was invited to
+    pub alliance_id: Uuid,
+}
+
+/// Response for alliance operations
+#[derive(Debug, Serialize)]
+pub struct AllianceResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Alliance state (if it still exists)
+    pub alliance: Option<Alliance>,
+}
+
+fn alliance_error(status: StatusCode, message: String) -> (StatusCode, Json<AllianceResponse>) {
+    (
+        status,
+        Json(AllianceResponse {
+            success: false,
+            message,
+            alliance: None,
+        })
+    )
+}
+
+/// Mirror an alliance's membership into the world (no members disbands it)
+async fn sync_alliance(state: &AppState, alliance: &Alliance) -> Result<(), String> {
+    let mut members = Vec::with_capacity(alliance.members.len());
+    for user_id in &alliance.members {
+        let user = state.auth_service.get_user(*user_id)?
+            .ok_or_else(|| format!("User {} not found", user_id))?;
+        members.push(user.username);
+    }
+
+    state.game_world.write().await.set_alliance_members(alliance.id, members);
+    Ok(())
+}
+
+async fn alliance_response(state: &AppState, result: Result<Alliance, String>, message: impl FnOnce(&Alliance) -> String) -> (StatusCode, Json<AllianceResponse>) {
+    let alliance = match result {
+        Ok(alliance) => alliance,
+        Err(err) => return alliance_error(StatusCode::BAD_REQUEST, err),
+    };
+
+    match sync_alliance(state, &alliance).await {
+        Ok(()) => {
+            let message = message(&alliance);
+            (
+                StatusCode::OK,
+                Json(AllianceResponse {
+                    success: true,
+                    message,
+                    alliance: (!alliance.members.is_empty()).then_some(alliance),
+                })
+            )
+        }
+        Err(err) => alliance_error(StatusCode::INTERNAL_SERVER_ERROR, err),
+    }
+}
+
+/// Handler to create an alliance (the creator is its first member)
+pub async fn create_alliance_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<CreateAllianceRequest>,
+) -> impl IntoResponse {
+    let result = state.auth_service.create_alliance(session.user_id, &payload.name);
+    alliance_response(&state, result, |alliance| {
+        log::info!("{} created alliance {} ({})", session.username, alliance.name, alliance.id);
+        format!("Alliance {} created", alliance.name)
+    }).await
+}
+
+/// Handler to invite a player to the caller's alliance
+pub async fn invite_to_alliance_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<InviteAllianceRequest>,
+) -> impl IntoResponse {
+    let result = state.auth_service.invite_to_alliance(session.user_id, &payload.username)
+        .map(|(alliance, _)| alliance);
+    alliance_response(&state, result, |alliance| {
+        format!("Invited {} to alliance {}", payload.username, alliance.name)
+    }).await
+}
+
+/// Handler to accept an invitation and join an alliance
+pub async fn accept_alliance_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<AcceptAllianceRequest>,
+) -> impl IntoResponse {
+    let result = state.auth_service.accept_alliance(session.user_id, &payload.alliance_id);
+    alliance_response(&state, result, |alliance| format!("Joined alliance {}", alliance.name)).await
+}
+
+/// Handler to leave the caller's alliance (the last member leaving disbands it)
+///
+/// The caller stops being an ally at the end of the current tick.
+pub async fn leave_alliance_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+) -> impl IntoResponse {
+    let result = state.auth_service.leave_alliance(session.user_id);
+    alliance_response(&state, result, |alliance| {
+        if alliance.members.is_empty() {
+            "Left alliance; the alliance was disbanded".to_string()
+        } else {
+            format!("Left alliance {}", alliance.name)
+        }
+    }).await
+}
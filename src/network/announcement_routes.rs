@@ -0,0 +1,172 @@
+//! Announcement routes module
+//!
+//! Server-wide announcements (maintenance warnings, tournament starts). An admin posts
+//! one to `POST /api/admin/announce`; it is pushed to every authenticated WebSocket
+//! connection as `{"type": "announcement", ...}`, and the last [`ANNOUNCEMENT_HISTORY_SIZE`]
+//! are kept for clients that connect later (`GET /api/announcements`).
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::network::extract::{ApiError, AuthSession, SizedJson};
+use crate::network::server::AppState;
+
+/// Number of announcements kept
+pub const ANNOUNCEMENT_HISTORY_SIZE: usize = 10;
+
+/// Maximum length of an announcement, in characters
+pub const MAX_ANNOUNCEMENT_LENGTH: usize = 1000;
+
+/// How urgent an announcement is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnouncementSeverity {
+    /// For information
+    #[default]
+    Info,
+    /// Something players should act on, e.g. upcoming maintenance
+    Warning,
+    /// Something happening now, e.g. an imminent shutdown
+    Critical,
+}
+
+/// An announcement, as pushed to WebSocket clients (`{"type": "announcement", ...}`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "announcement")]
+pub struct Announcement {
+    /// Announcement text
+    pub message: String,
+    /// How urgent the announcement is
+    pub severity: AnnouncementSeverity,
+    /// Username of the admin who posted it
+    pub from: String,
+    /// Unix timestamp (seconds)
+    pub timestamp: i64,
+}
+
+/// The most recent announcements
+#[derive(Debug, Default)]
+pub struct Announcements {
+    recent: Mutex<VecDeque<Announcement>>,
+}
+
+impl Announcements {
+    /// Create an empty list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep an announcement, dropping the oldest beyond [`ANNOUNCEMENT_HISTORY_SIZE`]
+    pub fn push(&self, announcement: Announcement) {
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == ANNOUNCEMENT_HISTORY_SIZE {
+            recent.pop_front();
+        }
+        recent.push_back(announcement);
+    }
+
+    /// Kept announcements, oldest first
+    pub fn recent(&self) -> Vec<Announcement> {
+        self.recent.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// Request to post an announcement
+#[derive(Debug, Deserialize)]
+pub struct AnnounceRequest {
+    /// Announcement text
+    pub message: String,
+    /// How urgent the announcement is (default `info`)
+    #[serde(default)]
+    pub severity: AnnouncementSeverity,
+}
+
+/// Response after posting an announcement
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnnounceResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Number of WebSocket connections the announcement was pushed to
+    pub delivered: usize,
+    /// The announcement
+    pub announcement: Announcement,
+}
+
+/// Response listing the kept announcements
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnnouncementsResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Recent announcements, oldest first
+    pub announcements: Vec<Announcement>,
+}
+
+/// Handler to post an announcement to every connected player (admin only)
+pub async fn announce_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<AnnounceRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if !state.is_admin(&session.username) {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, "Admin access required"));
+    }
+    let message = payload.message.trim();
+    if message.is_empty() {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "Announcement is empty"));
+    }
+    if message.chars().count() > MAX_ANNOUNCEMENT_LENGTH {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Announcement is longer than {} characters", MAX_ANNOUNCEMENT_LENGTH),
+        ));
+    }
+
+    let announcement = Announcement {
+        message: message.to_string(),
+        severity: payload.severity,
+        from: session.username.clone(),
+        timestamp: chrono::Utc::now().timestamp(),
+    };
+    let event = serde_json::to_value(&announcement).expect("announcements serialize to JSON");
+    state.announcements.push(announcement.clone());
+    let delivered = state.ws_clients.broadcast(&event);
+    log::info!("Announcement by {} pushed to {} connections", session.username, delivered);
+
+    Ok((
+        StatusCode::OK,
+        Json(AnnounceResponse {
+            success: true,
+            message: format!("Announcement sent to {} connections", delivered),
+            delivered,
+            announcement,
+        })
+    ))
+}
+
+/// Handler to list the recent announcements
+pub async fn list_announcements_handler(
+    State(state): State<AppState>,
+    AuthSession(_session): AuthSession,
+) -> impl IntoResponse {
+    let announcements = state.announcements.recent();
+    (
+        StatusCode::OK,
+        Json(AnnouncementsResponse {
+            success: true,
+            message: format!("{} announcements", announcements.len()),
+            announcements,
+        })
+    )
+}
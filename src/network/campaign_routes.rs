@@ -13,7 +13,8 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use lazy_static::lazy_static;
 
-use crate::game::campaign::CampaignManager;
+use crate::game::campaign::{CampaignError, CampaignManager, RunOptions};
+use crate::network::extract::SizedJson;
 use crate::network::server::AppState;
 
 lazy_static! {
@@ -22,90 +23,143 @@ lazy_static! {
     };
 }
 
+/// Shared campaign manager used by the campaign handlers and spectator streams
+pub fn campaign_manager() -> Arc<RwLock<CampaignManager>> {
+    CAMPAIGN_MANAGER.clone()
+}
+
 /// Request to start a campaign run
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct StartRunRequest {
+    /// Campaign run identifier
     pub run_id: String,
+    /// Whether spectators may watch the run (default: true)
+    #[serde(default)]
+    pub allow_spectators: Option<bool>,
+    /// Map template to start the run on (procedural start if absent)
+    #[serde(default)]
+    pub map_template: Option<String>,
+    /// Built-in NPC opponents to spawn, by behavior (`harvester`, `raider`, `turtle`)
+    #[serde(default)]
+    pub npc_opponents: Vec<String>,
+    /// Scenario to play, from the maps directory's `scenarios` or built in; it sets the
+    /// map template and NPC opponents
+    #[serde(default)]
+    pub scenario: Option<String>,
 }
 
 /// Response for start run
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct StartRunResponse {
+    /// Whether the operation succeeded
     pub success: bool,
+    /// Response message
     pub message: String,
+    /// Campaign run identifier
     pub run_id: Option<String>,
 }
 
 /// Query parameters for getting run state
 #[derive(Debug, Deserialize)]
 pub struct RunStateQuery {
+    /// Campaign run identifier
     pub run_id: String,
 }
 
 /// Response for run state
 #[derive(Debug, Serialize)]
 pub struct RunStateResponse {
+    /// Whether the operation succeeded
     pub success: bool,
+    /// Response message
     pub message: String,
+    /// Campaign run state (if found), with its `status` and the progress of its `objectives`
     pub run: Option<crate::game::campaign::CampaignRun>,
 }
 
 /// Request to stop a run
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct StopRunRequest {
+    /// Campaign run identifier
     pub run_id: String,
 }
 
 /// Response for stop run
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct StopRunResponse {
+    /// Whether the operation succeeded
     pub success: bool,
+    /// Response message
     pub message: String,
 }
 
 /// Request to save a run
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SaveRunRequest {
+    /// Campaign run identifier
     pub run_id: String,
 }
 
 /// Response for save run
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SaveRunResponse {
+    /// Whether the operation succeeded
     pub success: bool,
+    /// Response message
     pub message: String,
 }
 
 /// Response for listing saves
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ListSavesResponse {
+    /// Whether the operation succeeded
     pub success: bool,
+    /// Response message
     pub message: String,
+    /// Identifiers of saved runs
     pub saves: Vec<String>,
 }
 
 /// Request to load a run
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct LoadRunRequest {
+    /// Campaign run identifier
     pub run_id: String,
 }
 
 /// Response for load run
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct LoadRunResponse {
+    /// Whether the operation succeeded
     pub success: bool,
+    /// Response message
     pub message: String,
+    /// Campaign run state (if found)
     pub run: Option<crate::game::campaign::CampaignRun>,
 }
 
 /// Handler to start a campaign run
 pub async fn start_run_handler(
     State(_state): State<AppState>,
-    Json(payload): Json<StartRunRequest>,
+    SizedJson(payload): SizedJson<StartRunRequest>,
 ) -> impl IntoResponse {
     let mut manager = CAMPAIGN_MANAGER.write().await;
     
-    match manager.start_run(payload.run_id.clone()) {
+    let mut options = RunOptions::default();
+    if let Some(allow_spectators) = payload.allow_spectators {
+        options.allow_spectators = allow_spectators;
+    }
+    options.map_template = payload.map_template;
+    options.npc_opponents = payload.npc_opponents;
+    let started = match payload.scenario.map(|name| manager.scenario(&name)).transpose() {
+        Ok(scenario) => {
+            options.scenario = scenario;
+            manager.start_run_with_options(payload.run_id.clone(), options)
+        }
+        Err(err) => Err(err),
+    };
+    
+    match started {
         Ok(_run) => {
             log::info!("Started campaign run: {}", payload.run_id);
             (
@@ -163,15 +217,21 @@ pub async fn get_run_state_handler(
 }
 
 /// Handler to stop a run
+///
+/// The lobby whose match the run plays (if any) is finished.
 pub async fn stop_run_handler(
-    State(_state): State<AppState>,
-    Json(payload): Json<StopRunRequest>,
+    State(state): State<AppState>,
+    SizedJson(payload): SizedJson<StopRunRequest>,
 ) -> impl IntoResponse {
-    let mut manager = CAMPAIGN_MANAGER.write().await;
-    
-    match manager.stop_run(&payload.run_id) {
+    // The lobby handlers lock the lobbies before the campaign manager: release it first
+    let stopped = CAMPAIGN_MANAGER.write().await.stop_run(&payload.run_id);
+
+    match stopped {
         Ok(()) => {
             log::info!("Stopped campaign run: {}", payload.run_id);
+            if let Some(lobby) = state.lobby_manager.write().await.finish_run(&payload.run_id) {
+                log::info!("Lobby {} finished", lobby.name);
+            }
             (
                 StatusCode::OK,
                 Json(StopRunResponse {
@@ -196,9 +256,9 @@ pub async fn stop_run_handler(
 /// Handler to save a run to disk
 pub async fn save_run_handler(
     State(_state): State<AppState>,
-    Json(payload): Json<SaveRunRequest>,
+    SizedJson(payload): SizedJson<SaveRunRequest>,
 ) -> impl IntoResponse {
-    let manager = CAMPAIGN_MANAGER.read().await;
+    let mut manager = CAMPAIGN_MANAGER.write().await;
     
     match manager.save_run(&payload.run_id) {
         Ok(()) => {
@@ -213,11 +273,15 @@ pub async fn save_run_handler(
         }
         Err(err) => {
             log::warn!("Failed to save campaign run: {}", err);
+            let status = match err {
+                CampaignError::VersionConflict { .. } => StatusCode::CONFLICT,
+                _ => StatusCode::BAD_REQUEST,
+            };
             (
-                StatusCode::BAD_REQUEST,
+                status,
                 Json(SaveRunResponse {
                     success: false,
-                    message: err,
+                    message: err.to_string(),
                 })
             )
         }
@@ -258,7 +322,7 @@ pub async fn list_saves_handler(
 /// Handler to load a run from disk
 pub async fn load_run_handler(
     State(_state): State<AppState>,
-    Json(payload): Json<LoadRunRequest>,
+    SizedJson(payload): SizedJson<LoadRunRequest>,
 ) -> impl IntoResponse {
     let mut manager = CAMPAIGN_MANAGER.write().await;
     
@@ -0,0 +1,258 @@
+//! Chat module
+//!
+//! In-game chat over WebSocket. A `chat` command is sent to one channel: `global` (every
+//! authenticated connection), `team` (members of the sender's team), or `zone` (players
+//! with entities in a zone where the sender has entities). Messages are stripped of HTML
+//! and capped at [`MAX_CHAT_MESSAGE_LENGTH`] characters. The last [`CHAT_HISTORY_SIZE`]
+//! messages of each channel are kept for clients that join later (`getChatHistory`).
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::auth::models::Session;
+use crate::network::server::AppState;
+
+/// Maximum length of a chat message, in characters
+pub const MAX_CHAT_MESSAGE_LENGTH: usize = 500;
+
+/// Number of messages kept per channel
+pub const CHAT_HISTORY_SIZE: usize = 50;
+
+/// Chat channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatChannel {
+    /// Every authenticated player
+    Global,
+    /// Members of the sender's team
+    Team,
+    /// Players with entities in the same zone
+    Zone,
+}
+
+impl ChatChannel {
+    /// Parse a channel name (`global`, `team`, or `zone`)
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "global" => Ok(Self::Global),
+            "team" => Ok(Self::Team),
+            "zone" => Ok(Self::Zone),
+            other => Err(format!("Unknown chat channel: {} (expected global, team, or zone)", other)),
+        }
+    }
+}
+
+/// A chat message, as pushed to WebSocket clients (`{"type": "chat", ...}`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "chat")]
+pub struct ChatMessage {
+    /// Username of the sender
+    pub from: String,
+    /// Channel the message was sent to
+    pub channel: ChatChannel,
+    /// Sanitized message text
+    pub message: String,
+    /// Unix timestamp (seconds)
+    pub timestamp: i64,
+}
+
+/// A channel instance: team and zone channels are separate per team and per zone
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ChatScope {
+    /// The global channel
+    Global,
+    /// A team's channel
+    Team(Uuid),
+    /// A zone's channel
+    Zone(String),
+}
+
+/// Recent messages of each channel instance
+#[derive(Debug, Default)]
+pub struct ChatHistory {
+    channels: Mutex<HashMap<ChatScope, VecDeque<ChatMessage>>>,
+}
+
+impl ChatHistory {
+    /// Create an empty history
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a message, dropping the oldest one when the channel is full
+    pub fn push(&self, scope: ChatScope, message: ChatMessage) {
+        let mut channels = self.channels.lock().unwrap();
+        let messages = channels.entry(scope).or_default();
+        if messages.len() >= CHAT_HISTORY_SIZE {
+            messages.pop_front();
+        }
+        messages.push_back(message);
+    }
+
+    /// Messages of a channel instance, oldest first
+    pub fn recent(&self, scope: &ChatScope) -> Vec<ChatMessage> {
+        self.channels.lock().unwrap()
+            .get(scope)
+            .map(|messages| messages.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Strip HTML tags and control characters, trim, and cap the length
+///
+/// Everything from a `<` to the next `>` is removed (an unclosed tag removes the rest of
+/// the text). Returns an error if nothing is left.
+pub fn sanitize_message(text: &str) -> Result<String, String> {
+    let mut stripped = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if in_tag => {}
+            c if c.is_control() => stripped.push(' '),
+            c => stripped.push(c),
+        }
+    }
+
+    let message: String = stripped.trim().chars().take(MAX_CHAT_MESSAGE_LENGTH).collect();
+    let message = message.trim_end().to_string();
+    if message.is_empty() {
+        return Err("Chat message is empty".to_string());
+    }
+    Ok(message)
+}
+
+/// Channel instance and recipients (user IDs) of a message from `session`
+///
+/// Zone messages go to the zone named by `zone_id`, or by default the first zone where
+/// the sender has entities; the sender must have entities there.
+async fn route(state: &AppState, session: &Session, channel: ChatChannel, zone_id: Option<&str>) -> Result<(ChatScope, Option<Vec<i64>>), String> {
+    match channel {
+        ChatChannel::Global => Ok((ChatScope::Global, None)),
+        ChatChannel::Team => {
+            let team = state.auth_service.get_team_of_user(session.user_id)?
+                .ok_or_else(|| "You are not in a team".to_string())?;
+            Ok((ChatScope::Team(team.id), Some(team.members)))
+        }
+        ChatChannel::Zone => {
+            let world = state.game_world.read().await;
+            let zones = world.zones_of_player(&session.username);
+            let zone_id = match zone_id {
+                Some(zone_id) if zones.iter().any(|id| id == zone_id) => zone_id.to_string(),
+                Some(zone_id) => return Err(format!("You have no entities in zone {}", zone_id)),
+                None => zones.into_iter().next()
+                    .ok_or_else(|| "You have no entities in any zone".to_string())?,
+            };
+            let players = world.players_in_zone(&zone_id);
+            drop(world);
+
+            let mut recipients = Vec::with_capacity(players.len());
+            for player in players {
+                if let Some(user) = state.auth_service.get_user_by_username(&player)? {
+                    recipients.push(user.id);
+                }
+            }
+            Ok((ChatScope::Zone(zone_id), Some(recipients)))
+        }
+    }
+}
+
+/// Handle a `chat` command from an authenticated connection
+pub async fn handle_chat(command: &serde_json::Value, state: &AppState, session: &Session) -> serde_json::Value {
+    let error = |message: String| serde_json::json!({
+        "type": "chatResponse",
+        "success": false,
+        "message": message
+    });
+
+    let channel = match ChatChannel::from_name(command.get("channel").and_then(|v| v.as_str()).unwrap_or("global")) {
+        Ok(channel) => channel,
+        Err(err) => return error(err),
+    };
+    let text = match sanitize_message(command.get("message").and_then(|v| v.as_str()).unwrap_or("")) {
+        Ok(text) => text,
+        Err(err) => return error(err),
+    };
+    let (scope, recipients) = match route(state, session, channel, command.get("zoneId").and_then(|v| v.as_str())).await {
+        Ok(route) => route,
+        Err(err) => return error(err),
+    };
+
+    let message = ChatMessage {
+        from: session.username.clone(),
+        channel,
+        message: text,
+        timestamp: chrono::Utc::now().timestamp(),
+    };
+    let event = serde_json::to_value(&message).expect("chat messages serialize to JSON");
+    state.chat_history.push(scope, message);
+
+    let delivered = match recipients {
+        None => state.ws_clients.broadcast(&event),
+        Some(user_ids) => user_ids.iter()
+            .map(|user_id| state.ws_clients.send_to_user(*user_id, &event))
+            .sum(),
+    };
+    serde_json::json!({
+        "type": "chatResponse",
+        "success": true,
+        "delivered": delivered
+    })
+}
+
+/// Handle a `getChatHistory` command: recent messages of a channel the caller can read
+pub async fn handle_chat_history(command: &serde_json::Value, state: &AppState, session: &Session) -> serde_json::Value {
+    let channel = ChatChannel::from_name(command.get("channel").and_then(|v| v.as_str()).unwrap_or("global"));
+    let route = match channel {
+        Ok(channel) => route(state, session, channel, command.get("zoneId").and_then(|v| v.as_str())).await,
+        Err(err) => Err(err),
+    };
+
+    match route {
+        Ok((scope, _)) => serde_json::json!({
+            "type": "chatHistory",
+            "success": true,
+            "messages": state.chat_history.recent(&scope)
+        }),
+        Err(err) => serde_json::json!({
+            "type": "chatHistory",
+            "success": false,
+            "message": err,
+            "messages": []
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_strips_html_and_caps_length() {
+        assert_eq!(sanitize_message("  <b>gg</b> <script>alert(1)</script>wp\n").unwrap(), "gg alert(1)wp");
+        assert_eq!(sanitize_message("2 > 1 <img src=x onerror=alert(1)").unwrap(), "2 > 1");
+        assert!(sanitize_message("<br/>  ").is_err());
+        assert_eq!(sanitize_message(&"é".repeat(600)).unwrap().chars().count(), MAX_CHAT_MESSAGE_LENGTH);
+    }
+
+    #[test]
+    fn test_history_keeps_last_messages_per_channel() {
+        let history = ChatHistory::new();
+        for i in 0..CHAT_HISTORY_SIZE + 5 {
+            history.push(ChatScope::Global, ChatMessage {
+                from: "alice".to_string(),
+                channel: ChatChannel::Global,
+                message: i.to_string(),
+                timestamp: 0,
+            });
+        }
+        let recent = history.recent(&ChatScope::Global);
+        assert_eq!(recent.len(), CHAT_HISTORY_SIZE);
+        assert_eq!(recent[0].message, "5");
+        assert!(history.recent(&ChatScope::Zone("player_alice_zone".to_string())).is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+//! Response compression
+//!
+//! When `enable_compression` is set, HTTP responses of at least [`MIN_COMPRESSED_SIZE`]
+//! bytes are compressed with brotli or gzip, whichever the client's `Accept-Encoding`
+//! prefers (brotli on ties). WebSocket upgrades and responses that are already encoded are
+//! left alone.
+
+use std::io::Write;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{header, HeaderValue, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::network::server::AppState;
+
+/// Smallest response body worth compressing, in bytes
+pub const MIN_COMPRESSED_SIZE: usize = 1024;
+
+/// Responses at least this large get their compression ratio logged (at DEBUG)
+const LOG_RATIO_SIZE: usize = 10 * 1024;
+
+/// A content coding the server can produce
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// `br`
+    Brotli,
+    /// `gzip`
+    Gzip,
+}
+
+impl Encoding {
+    /// Name of the coding in `Accept-Encoding` and `Content-Encoding`
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    /// Preferred coding of an `Accept-Encoding` header value, if it accepts one we produce
+    pub fn negotiate(accept_encoding: &str) -> Option<Self> {
+        let mut best: Option<(Self, f32)> = None;
+        for item in accept_encoding.split(',') {
+            let mut parts = item.split(';');
+            let coding = parts.next().unwrap_or_default().trim();
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let encoding = match coding.to_ascii_lowercase().as_str() {
+                "br" => Encoding::Brotli,
+                "gzip" | "x-gzip" | "*" => Encoding::Gzip,
+                _ => continue,
+            };
+            let better = match best {
+                Some((current, q)) => quality > q || (quality == q && encoding == Encoding::Brotli && current != encoding),
+                None => true,
+            };
+            if quality > 0.0 && better {
+                best = Some((encoding, quality));
+            }
+        }
+        best.map(|(encoding, _)| encoding)
+    }
+
+    /// Compress a whole body
+    pub fn compress(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Brotli => {
+                // Quality 5 keeps dynamic responses fast to compress
+                let mut writer = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+                writer.write_all(data)?;
+                writer.flush()?;
+                Ok(writer.into_inner())
+            }
+            Encoding::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Compress responses for clients that accept it (see the module documentation)
+pub async fn compression_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let encoding = request.headers().get(header::ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Encoding::negotiate);
+    let upgrade = request.headers().contains_key(header::UPGRADE);
+    let enabled = state.config.read().unwrap().enable_compression;
+    let path = request.uri().path().to_string();
+
+    let response = next.run(request).await;
+    let Some(encoding) = encoding.filter(|_| enabled && !upgrade) else {
+        return response;
+    };
+    if response.status() == StatusCode::SWITCHING_PROTOCOLS
+        || response.headers().contains_key(header::CONTENT_ENCODING)
+        || is_precompressed(response.headers().get(header::CONTENT_TYPE))
+    {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let data = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(data) => data,
+        Err(e) => {
+            log::error!("Failed to read the response to {}: {}", path, e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    if data.len() < MIN_COMPRESSED_SIZE {
+        return Response::from_parts(parts, Body::from(data));
+    }
+    let compressed = match encoding.compress(&data) {
+        Ok(compressed) => compressed,
+        Err(e) => {
+            log::warn!("Failed to compress the response to {}: {}", path, e);
+            return Response::from_parts(parts, Body::from(data));
+        }
+    };
+
+    if data.len() >= LOG_RATIO_SIZE {
+        log::debug!(
+            "Compressed {} with {}: {} -> {} bytes ({:.1}%)",
+            path,
+            encoding.name(),
+            data.len(),
+            compressed.len(),
+            compressed.len() as f64 * 100.0 / data.len() as f64,
+        );
+    }
+    parts.headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding.name()));
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.append(header::VARY, HeaderValue::from_static("accept-encoding"));
+    Response::from_parts(parts, Body::from(compressed))
+}
+
+/// Content types that are already compressed
+fn is_precompressed(content_type: Option<&HeaderValue>) -> bool {
+    content_type
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("image/") || value.starts_with("application/zip"))
+}
@@ -0,0 +1,81 @@
+//! WebSocket connection limit module
+//!
+//! Tracks how many authenticated WebSocket connections each user holds so a single
+//! account cannot exhaust server resources.
+
+use std::sync::Arc;
+use dashmap::DashMap;
+
+/// Open authenticated WebSocket connections per user ID
+pub type ConnectionCounts = Arc<DashMap<i64, u32>>;
+
+/// One counted connection; the count is released when this is dropped
+///
+/// Dropping happens on every exit path of the connection task (close frame, socket
+/// error, abort), so counts stay correct after unexpected disconnects.
+#[derive(Debug)]
+pub struct ConnectionSlot {
+    counts: ConnectionCounts,
+    user_id: i64,
+}
+
+impl ConnectionSlot {
+    /// Reserve a connection for `user_id`, or `None` if the user already has `max` connections
+    pub fn acquire(counts: &ConnectionCounts, user_id: i64, max: u32) -> Option<Self> {
+        let mut count = counts.entry(user_id).or_insert(0);
+        if *count >= max {
+            return None;
+        }
+        *count += 1;
+        drop(count);
+
+        Some(ConnectionSlot {
+            counts: counts.clone(),
+            user_id,
+        })
+    }
+
+    /// User this slot belongs to
+    pub fn user_id(&self) -> i64 {
+        self.user_id
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        // Remove the entry entirely once the user has no connections left
+        self.counts.remove_if_mut(&self.user_id, |_, count| {
+            *count = count.saturating_sub(1);
+            *count == 0
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slots_limit_and_release() {
+        let counts = ConnectionCounts::default();
+
+        let first = ConnectionSlot::acquire(&counts, 1, 2).unwrap();
+        let _second = ConnectionSlot::acquire(&counts, 1, 2).unwrap();
+        assert!(ConnectionSlot::acquire(&counts, 1, 2).is_none());
+
+        // Other users are counted separately
+        assert!(ConnectionSlot::acquire(&counts, 2, 2).is_some());
+
+        drop(first);
+        assert_eq!(*counts.get(&1).unwrap(), 1);
+        assert!(ConnectionSlot::acquire(&counts, 1, 2).is_some());
+    }
+
+    #[test]
+    fn test_entry_removed_when_last_slot_dropped() {
+        let counts = ConnectionCounts::default();
+        let slot = ConnectionSlot::acquire(&counts, 7, 3).unwrap();
+        drop(slot);
+        assert!(counts.get(&7).is_none());
+    }
+}
@@ -0,0 +1,176 @@
+//! Local control socket
+//!
+//! A Unix domain socket for operators on the server's machine, configured with
+//! `control_socket` (`GEEKCRAFT_CONTROL_SOCKET`). It accepts one JSON command per line
+//! and answers each with one JSON line:
+//!
+//! ```text
+//! {"command": "stats"}
+//! {"command": "pause"}
+//! {"command": "resume"}
+//! {"command": "save-all"}
+//! {"command": "kick-player", "username": "alice"}
+//! ```
+//!
+//! There is no login: the socket is created readable and writable by its owner only, so
+//! access is granted with filesystem permissions.
+
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+use crate::game::game_loop::RunState;
+use crate::network::server::AppState;
+
+/// A command sent to the control socket
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum ControlCommand {
+    /// Report the current tick, zones and connections
+    Stats,
+    /// Pause the game loop
+    Pause,
+    /// Resume the game loop
+    Resume,
+    /// Write every zone to the world store
+    SaveAll,
+    /// Close every WebSocket connection of a player
+    KickPlayer {
+        /// Username of the player
+        username: String,
+    },
+}
+
+/// Counters reported by [`ControlCommand::Stats`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ControlStats {
+    /// Number of zones in the world
+    pub zones: usize,
+    /// Players with at least one authenticated WebSocket connection
+    pub connected_players: usize,
+    /// Authenticated WebSocket connections
+    pub connections: usize,
+}
+
+/// Reply to a control command
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ControlReply {
+    /// Whether the command succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Current simulation tick
+    pub tick: u64,
+    /// Run state of the game loop after the command
+    pub run_state: RunState,
+    /// Counters (stats only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<ControlStats>,
+}
+
+/// Create the socket, replacing a stale one left by a previous run
+pub fn bind(path: &Path) -> std::io::Result<UnixListener> {
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Accept control connections until the listener fails
+pub async fn serve(listener: UnixListener, state: AppState) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(handle_connection(stream, state.clone()));
+            }
+            Err(e) => {
+                log::error!("❌ Control socket stopped: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+async fn handle_connection(stream: UnixStream, state: AppState) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<ControlCommand>(&line) {
+            Ok(command) => execute(&state, command).await,
+            Err(e) => reply(&state, Err(format!("Invalid command: {}", e)), None).await,
+        };
+        let mut json = serde_json::to_string(&reply).unwrap_or_default();
+        json.push('\n');
+        if writer.write_all(json.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Run a control command
+pub async fn execute(state: &AppState, command: ControlCommand) -> ControlReply {
+    match command {
+        ControlCommand::Stats => {
+            let zones = state.game_world.read().await.get_zone_ids().len();
+            let (connected_players, connections) = state.ws_clients.counts();
+            let stats = ControlStats { zones, connected_players, connections };
+            reply(state, Ok("Server statistics".to_string()), Some(stats)).await
+        }
+        ControlCommand::Pause => {
+            state.sim_control.pause();
+            log::info!("Simulation paused from the control socket");
+            reply(state, Ok("Simulation paused".to_string()), None).await
+        }
+        ControlCommand::Resume => {
+            state.sim_control.resume();
+            log::info!("Simulation resumed from the control socket");
+            reply(state, Ok("Simulation resumed".to_string()), None).await
+        }
+        ControlCommand::SaveAll => {
+            let result = state.game_world.read().await.persist_all()
+                .map(|count| format!("Saved {} zones", count));
+            reply(state, result, None).await
+        }
+        ControlCommand::KickPlayer { username } => {
+            let result = match state.auth_service.get_user_by_username(&username) {
+                Ok(Some(user)) => {
+                    let closed = state.ws_clients.disconnect_user(user.id);
+                    log::info!("Kicked {} from the control socket ({} connections)", username, closed);
+                    Ok(format!("Closed {} connections of {}", closed, username))
+                }
+                Ok(None) => Err(format!("Unknown player {}", username)),
+                Err(e) => Err(e),
+            };
+            reply(state, result, None).await
+        }
+    }
+}
+
+async fn reply(state: &AppState, result: Result<String, String>, stats: Option<ControlStats>) -> ControlReply {
+    let tick = state.game_world.read().await.get_tick();
+    let (success, message) = match result {
+        Ok(message) => (true, message),
+        Err(message) => (false, message),
+    };
+    ControlReply {
+        success,
+        message,
+        tick,
+        run_state: state.sim_control.state(),
+        stats,
+    }
+}
@@ -0,0 +1,102 @@
+//! Email routes module
+//!
+//! HTTP endpoint handlers for verifying a player's email address. `POST
+//! /api/auth/verify-email` (authenticated) sets the address and emails a link to it;
+//! the link opens the public `GET /api/auth/verify-email?token=...`, which marks the
+//! address verified.
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::config::API_VERSION;
+use crate::network::extract::{AUTH_BODY_LIMIT, AuthSession, SizedJson};
+use crate::network::server::{public_base_url, AppState};
+
+/// Request to verify an email address
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailRequest {
+    /// Address to verify (the account's current one if absent, to send a new link)
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
+/// Query of a verification link
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailQuery {
+    /// Token sent in the email
+    pub token: String,
+}
+
+/// Response of the email verification endpoints
+#[derive(Debug, Serialize)]
+pub struct EmailResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+}
+
+fn email_response(status: StatusCode, result: Result<String, String>) -> (StatusCode, Json<EmailResponse>) {
+    match result {
+        Ok(message) => (StatusCode::OK, Json(EmailResponse { success: true, message })),
+        Err(message) => (status, Json(EmailResponse { success: false, message })),
+    }
+}
+
+/// Set a user's email and send the verification link, off the async runtime
+pub(crate) async fn send_verification(state: &AppState, headers: &HeaderMap, user_id: i64, email: String) -> Result<(), String> {
+    let verify_url = format!("{}/api/{}/auth/verify-email", public_base_url(state, headers)?, API_VERSION);
+    let auth_service = state.auth_service.clone();
+    let sender = state.email_sender.clone();
+    tokio::task::spawn_blocking(move || {
+        auth_service.send_verification_email(sender.as_ref(), user_id, &email, &verify_url)
+    })
+    .await
+    .map_err(|e| format!("Sending the email failed: {}", e))?
+}
+
+/// Handler setting the caller's email and sending them a verification link
+pub async fn request_verification_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    headers: HeaderMap,
+    SizedJson(payload): SizedJson<VerifyEmailRequest, AUTH_BODY_LIMIT>,
+) -> impl IntoResponse {
+    let email = match payload.email {
+        Some(email) => email,
+        None => match state.auth_service.get_user(session.user_id) {
+            Ok(Some(user)) if user.email_verified => {
+                return email_response(StatusCode::BAD_REQUEST, Err("Your email address is already verified".to_string()));
+            }
+            Ok(Some(user)) => match user.email {
+                Some(email) => email,
+                None => return email_response(StatusCode::BAD_REQUEST, Err("Missing email".to_string())),
+            },
+            Ok(None) => return email_response(StatusCode::NOT_FOUND, Err("User not found".to_string())),
+            Err(e) => return email_response(StatusCode::INTERNAL_SERVER_ERROR, Err(e)),
+        },
+    };
+    let result = send_verification(&state, &headers, session.user_id, email.clone()).await
+        .map(|()| format!("Verification link sent to {}", email.trim()));
+    if let Err(e) = &result {
+        log::warn!("Could not send a verification email to {}: {}", session.username, e);
+    }
+    email_response(StatusCode::BAD_REQUEST, result)
+}
+
+/// Handler for verification links
+pub async fn verify_email_handler(
+    State(state): State<AppState>,
+    Query(query): Query<VerifyEmailQuery>,
+) -> impl IntoResponse {
+    email_response(
+        StatusCode::BAD_REQUEST,
+        state.auth_service.verify_email(&query.token)
+            .map(|user| format!("Email address {} verified", user.email.unwrap_or_default())),
+    )
+}
@@ -0,0 +1,53 @@
+//! Script error feed
+//!
+//! Pushes the new errors of a player's script to their authenticated WebSocket
+//! connections as `{"type": "scriptError", "error": {...}}` (see
+//! [`LoggedError`](crate::scripting::errors::LoggedError)). Repeats of an error the player
+//! was already sent are only counted, in the errors of `/api/gamestate`.
+
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinHandle;
+
+use crate::network::ws_codec::Outgoing;
+use crate::scripting::errors::LoggedError;
+
+/// A connection's subscription to its player's script errors; it stops when dropped
+#[derive(Debug)]
+pub struct ErrorFeed {
+    task: JoinHandle<()>,
+}
+
+impl ErrorFeed {
+    /// Forward the errors of `player_id` from the script engine's error channel to a connection
+    pub fn start(
+        mut errors: broadcast::Receiver<(String, LoggedError)>,
+        player_id: String,
+        outgoing: UnboundedSender<Outgoing>,
+    ) -> Self {
+        let task = tokio::spawn(async move {
+            loop {
+                match errors.recv().await {
+                    Ok((player, error)) if player == player_id => {
+                        let message = serde_json::json!({"type": "scriptError", "error": error});
+                        if outgoing.send(Outgoing::Json(message)).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(missed)) => {
+                        log::debug!("Script error feed of {} missed {} errors", player_id, missed);
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+        Self { task }
+    }
+}
+
+impl Drop for ErrorFeed {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,109 @@
+//! ETags and conditional GETs
+//!
+//! Zone and game state responses carry an `ETag`: an FNV-1a hash of their JSON body. A
+//! client sending it back in `If-None-Match` gets `304 Not Modified` while the response
+//! would be the same. ETags are weak (`W/"..."`) since the compression layer may encode
+//! the same body differently.
+//!
+//! Zone responses and their ETags are kept in a [`ZoneCache`], so unchanged zones are
+//! not serialized again.
+
+use std::sync::Arc;
+
+use axum::body::{Body, Bytes};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+
+use crate::game::zone::Zone;
+use crate::network::zone_cache::ZoneCache;
+
+/// 64-bit FNV-1a hash
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Weak ETag of a response body
+pub fn etag_of(body: &[u8]) -> String {
+    format!("W/\"{:016x}\"", fnv1a(body))
+}
+
+/// Whether an `If-None-Match` header matches an ETag (weak comparison)
+pub fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    headers.get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+/// `304 Not Modified` with the ETag
+pub fn not_modified(etag: &str) -> Response {
+    (StatusCode::NOT_MODIFIED, [(header::ETAG, etag.to_string())]).into_response()
+}
+
+/// JSON response with its ETag, or `304 Not Modified` if the request already has it
+pub fn conditional_json<T: Serialize>(headers: &HeaderMap, status: StatusCode, value: &T) -> Response {
+    let body = match serde_json::to_vec(value) {
+        Ok(body) => body,
+        Err(e) => {
+            log::error!("Failed to serialize response: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let etag = etag_of(&body);
+    if if_none_match(headers, &etag) {
+        return not_modified(&etag);
+    }
+    json_with_etag(status, Body::from(body), etag)
+}
+
+/// Zone response with its ETag, or `304 Not Modified` if the request already has it
+///
+/// `value` builds the response body; it is only called when the cache has no response
+/// for the current version of the zone.
+pub fn conditional_zone<T: Serialize>(
+    cache: &ZoneCache,
+    headers: &HeaderMap,
+    zone: &Zone,
+    value: impl FnOnce() -> T,
+) -> Response {
+    let cached = match cache.get(zone) {
+        Some(cached) => cached,
+        None => {
+            let body = match serde_json::to_string(&value()) {
+                Ok(body) => body,
+                Err(e) => {
+                    log::error!("Failed to serialize zone {}: {}", zone.id, e);
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+            };
+            let etag = etag_of(body.as_bytes());
+            cache.insert(zone, etag, Arc::new(body))
+        }
+    };
+    if if_none_match(headers, &cached.etag) {
+        return not_modified(&cached.etag);
+    }
+    json_with_etag(StatusCode::OK, Body::from(Bytes::from_owner(SharedBody(cached.body))), cached.etag)
+}
+
+/// A cached body handed to a response without copying it
+struct SharedBody(Arc<String>);
+
+impl AsRef<[u8]> for SharedBody {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+fn json_with_etag(status: StatusCode, body: Body, etag: String) -> Response {
+    (
+        status,
+        [(header::CONTENT_TYPE, "application/json".to_string()), (header::ETAG, etag)],
+        body,
+    ).into_response()
+}
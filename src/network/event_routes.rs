@@ -0,0 +1,66 @@
+//! Event routes module
+//!
+//! HTTP endpoint listing the recent game events the caller can see (see
+//! [`World::events_visible_to`](crate::game::world::World::events_visible_to)).
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::game::events::GameEvent;
+use crate::network::extract::AuthSession;
+use crate::network::server::AppState;
+
+/// Events returned when `limit` is not given
+pub const DEFAULT_EVENTS_LIMIT: usize = 100;
+
+/// Maximum events returned by one request
+pub const MAX_EVENTS_LIMIT: usize = 1000;
+
+/// Query parameters for the event feed
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct EventsQuery {
+    /// Only events after this simulation tick (default 0)
+    pub since_tick: Option<u64>,
+    /// Maximum events returned (default [`DEFAULT_EVENTS_LIMIT`], at most [`MAX_EVENTS_LIMIT`])
+    pub limit: Option<usize>,
+}
+
+/// Response for the event feed
+#[derive(Debug, Serialize)]
+pub struct EventsResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Current simulation tick (pass it as `since_tick` to get only newer events)
+    pub tick: u64,
+    /// Events, oldest first
+    pub events: Vec<GameEvent>,
+}
+
+/// Handler to list the events visible to the caller after `since_tick`
+pub async fn events_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Query(query): Query<EventsQuery>,
+) -> impl IntoResponse {
+    let since_tick = query.since_tick.unwrap_or(0);
+    let limit = query.limit.unwrap_or(DEFAULT_EVENTS_LIMIT).clamp(1, MAX_EVENTS_LIMIT);
+
+    let world = state.game_world.read().await;
+    let events = world.events_visible_to(&session.username, since_tick, limit);
+    (
+        StatusCode::OK,
+        Json(EventsResponse {
+            success: true,
+            message: format!("{} events after tick {}", events.len(), since_tick),
+            tick: world.get_tick(),
+            events,
+        })
+    )
+}
@@ -0,0 +1,153 @@
+//! Request extractors module
+//!
+//! Extractors shared by the API handlers, so that every route reads its body and its
+//! session the same way and fails with the same `{"success": false, "message": "..."}`
+//! envelope:
+//! - [`SizedJson`] parses a JSON body of at most `LIMIT` bytes
+//! - [`SizedBody`] reads a raw body of at most `LIMIT` bytes
+//! - [`AuthSession`] is the session of the bearer token
+//!
+//! A body over its limit is refused with `413 Payload Too Large` as soon as it is known
+//! to be too large, without reading the rest.
+
+use axum::{
+    async_trait,
+    body::{Body, Bytes},
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures_util::StreamExt;
+use serde::de::DeserializeOwned;
+
+use crate::auth::models::Session;
+use crate::game::zone::export::MAX_ZONE_EXPORT_SIZE;
+use crate::network::server::AppState;
+use crate::scripting::bundle::MAX_BUNDLE_SIZE;
+
+/// Body limit of the authentication routes (register, login, email verification)
+pub const AUTH_BODY_LIMIT: usize = 4 * 1024;
+
+/// Body limit of the JSON routes without a limit of their own
+pub const JSON_BODY_LIMIT: usize = 64 * 1024;
+
+/// Body limit of the routes taking code: a full bundle, plus room for its JSON encoding
+pub const CODE_BODY_LIMIT: usize = 2 * MAX_BUNDLE_SIZE;
+
+/// Body limit of zone imports
+pub const IMPORT_BODY_LIMIT: usize = MAX_ZONE_EXPORT_SIZE;
+
+/// Error response in the standard envelope
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    /// Response status
+    pub status: StatusCode,
+    /// Response message
+    pub message: String,
+}
+
+impl ApiError {
+    /// Create an error response
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        ApiError { status, message: message.into() }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(serde_json::json!({"success": false, "message": self.message}))).into_response()
+    }
+}
+
+fn too_large(limit: usize) -> ApiError {
+    ApiError::new(StatusCode::PAYLOAD_TOO_LARGE, format!("Request body too large (max: {} bytes)", limit))
+}
+
+/// Read a request body, refusing it once it is over `limit` bytes
+async fn read_body(request: Request, limit: usize) -> Result<Bytes, ApiError> {
+    let declared = request.headers().get(header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse::<usize>().ok());
+    if declared.is_some_and(|length| length > limit) {
+        return Err(too_large(limit));
+    }
+
+    let mut stream = request.into_body().into_data_stream();
+    let mut body = Vec::with_capacity(declared.unwrap_or(0));
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, format!("Failed to read request body: {}", e)))?;
+        if body.len() + chunk.len() > limit {
+            return Err(too_large(limit));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(Bytes::from(body))
+}
+
+/// JSON body of at most `LIMIT` bytes
+///
+/// Refused with `413` when too large, `400` when it is not JSON and `422` when it does
+/// not match `T`.
+#[derive(Debug, Clone)]
+pub struct SizedJson<T, const LIMIT: usize = JSON_BODY_LIMIT>(pub T);
+
+#[async_trait]
+impl<T, S, const LIMIT: usize> FromRequest<S> for SizedJson<T, LIMIT>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request<Body>, _state: &S) -> Result<Self, Self::Rejection> {
+        let bytes = read_body(request, LIMIT).await?;
+        serde_json::from_slice(&bytes).map(SizedJson).map_err(|e| {
+            let status = if e.is_data() { StatusCode::UNPROCESSABLE_ENTITY } else { StatusCode::BAD_REQUEST };
+            ApiError::new(status, format!("Invalid JSON: {}", e))
+        })
+    }
+}
+
+/// Raw body of at most `LIMIT` bytes
+#[derive(Debug, Clone)]
+pub struct SizedBody<const LIMIT: usize>(pub Bytes);
+
+#[async_trait]
+impl<S, const LIMIT: usize> FromRequest<S> for SizedBody<LIMIT>
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request<Body>, _state: &S) -> Result<Self, Self::Rejection> {
+        read_body(request, LIMIT).await.map(SizedBody)
+    }
+}
+
+/// Session of the request's bearer token
+///
+/// Protected routes get it from the auth middleware; elsewhere the token is checked here.
+/// Refused with `401` when there is no valid token.
+#[derive(Debug, Clone)]
+pub struct AuthSession(pub Session);
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthSession {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        if let Some(session) = parts.extensions.get::<Session>() {
+            return Ok(AuthSession(session.clone()));
+        }
+        let token = parts.headers.get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        let session = match token {
+            Some(token) => state.validate_token(token).await,
+            None => None,
+        };
+        session.map(AuthSession)
+            .ok_or_else(|| ApiError::new(StatusCode::UNAUTHORIZED, "Authentication required"))
+    }
+}
@@ -0,0 +1,125 @@
+//! Friend routes module
+//!
+//! HTTP endpoint handlers for friends. A friendship needs both players: one sends a
+//! request, and it only takes effect once the other accepts it.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+
+use crate::network::extract::AuthSession;
+use crate::network::server::AppState;
+
+/// Response for friend request, accept, and remove operations
+#[derive(Debug, Serialize)]
+pub struct FriendResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+}
+
+/// A friend, as listed by `GET /api/friends`
+#[derive(Debug, Serialize)]
+pub struct FriendEntry {
+    /// Friend's username
+    pub username: String,
+    /// Whether the friend has an active (non-expired) session
+    pub online: bool,
+}
+
+/// Response for the caller's friend list
+#[derive(Debug, Serialize)]
+pub struct FriendListResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Friends, sorted by username
+    pub friends: Vec<FriendEntry>,
+    /// Usernames of players waiting for the caller to accept their request
+    pub incoming_requests: Vec<String>,
+}
+
+fn friend_response(result: Result<String, String>) -> (StatusCode, Json<FriendResponse>) {
+    match result {
+        Ok(message) => (StatusCode::OK, Json(FriendResponse { success: true, message })),
+        Err(message) => (StatusCode::BAD_REQUEST, Json(FriendResponse { success: false, message })),
+    }
+}
+
+/// Handler to send a friend request
+pub async fn request_friend_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path(username): Path<String>,
+) -> impl IntoResponse {
+    friend_response(
+        state.auth_service.request_friend(session.user_id, &username)
+            .map(|user| format!("Friend request sent to {}", user.username))
+    )
+}
+
+/// Handler to accept a pending friend request
+pub async fn accept_friend_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path(username): Path<String>,
+) -> impl IntoResponse {
+    let result = state.auth_service.accept_friend(session.user_id, &username);
+    if let Ok(user) = &result {
+        log::info!("{} and {} are now friends", session.username, user.username);
+    }
+    friend_response(result.map(|user| format!("You are now friends with {}", user.username)))
+}
+
+/// Handler to remove a friend (or cancel a request sent to them)
+pub async fn remove_friend_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path(username): Path<String>,
+) -> impl IntoResponse {
+    friend_response(
+        state.auth_service.remove_friend(session.user_id, &username)
+            .map(|user| format!("Removed {} from your friends", user.username))
+    )
+}
+
+/// Handler to list the caller's friends with their online status
+pub async fn list_friends_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+) -> impl IntoResponse {
+    let result = state.auth_service.friends_with_status(session.user_id)
+        .and_then(|friends| Ok((friends, state.auth_service.incoming_friend_requests(session.user_id)?)));
+
+    match result {
+        Ok((friends, requesters)) => {
+            let online = friends.iter().filter(|(_, online)| *online).count();
+            (
+                StatusCode::OK,
+                Json(FriendListResponse {
+                    success: true,
+                    message: format!("{} friends, {} online", friends.len(), online),
+                    friends: friends.into_iter()
+                        .map(|(user, online)| FriendEntry { username: user.username, online })
+                        .collect(),
+                    incoming_requests: requesters.into_iter().map(|user| user.username).collect(),
+                })
+            )
+        }
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(FriendListResponse {
+                success: false,
+                message: err,
+                friends: Vec::new(),
+                incoming_requests: Vec::new(),
+            })
+        ),
+    }
+}
@@ -0,0 +1,122 @@
+//! Health routes module
+//!
+//! Liveness and readiness probes. `GET /api/health/live` only tells that the process
+//! answers requests. `GET /api/health/ready` (also served as `GET /api/health`) checks
+//! that the server can do its job: the auth database answers a query, the game loop
+//! has had a heartbeat within `ready_max_tick_age_secs`, and the campaign save directory
+//! is writable. It answers `503 Service Unavailable` with the result of each check when
+//! any fails; a database check failing while the database circuit breaker is open or
+//! half-open says so.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::CircuitState;
+use crate::network::server::AppState;
+
+/// Result of one readiness check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckResult {
+    /// Whether the check passed
+    pub ok: bool,
+    /// What was found
+    pub message: String,
+}
+
+impl CheckResult {
+    fn from_result(result: Result<String, String>) -> Self {
+        match result {
+            Ok(message) => Self { ok: true, message },
+            Err(message) => Self { ok: false, message },
+        }
+    }
+}
+
+/// Response of the health endpoints
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HealthResponse {
+    /// `alive` (liveness), `healthy` or `unhealthy` (readiness)
+    pub status: String,
+    /// Service name
+    pub service: String,
+    /// Readiness checks by name (`database`, `game_loop`, `save_dir`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub checks: BTreeMap<String, CheckResult>,
+}
+
+/// Handler for `GET /api/health/live`: the process is up
+pub async fn live_handler() -> impl IntoResponse {
+    Json(HealthResponse {
+        status: "alive".to_string(),
+        service: "geekcraft".to_string(),
+        checks: BTreeMap::new(),
+    })
+}
+
+/// Handler for `GET /api/health/ready` and `GET /api/health`: every check passes
+pub async fn ready_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let auth_service = state.auth_service.clone();
+    let database = tokio::task::spawn_blocking(move || {
+        auth_service.ping().map(|()| "ok".to_string()).map_err(|e| match auth_service.circuit_status() {
+            CircuitState::Closed => e,
+            circuit => format!("{} (circuit {})", e, circuit),
+        })
+    })
+        .await
+        .unwrap_or_else(|e| Err(format!("Database check panicked: {}", e)));
+
+    let max_age_ms = state.config().ready_max_tick_age_secs.saturating_mul(1000) as i64;
+    let game_loop = match state.sim_control.last_heartbeat() {
+        None => Err("Game loop has not started".to_string()),
+        Some(at) => {
+            let age_ms = (chrono::Utc::now().timestamp_millis() - at).max(0);
+            let message = format!("Last heartbeat {}ms ago", age_ms);
+            if age_ms <= max_age_ms {
+                Ok(message)
+            } else {
+                Err(format!("{} (limit {}ms)", message, max_age_ms))
+            }
+        }
+    };
+
+    let save_dir = state.save_dir.clone();
+    let save_dir = tokio::task::spawn_blocking(move || check_writable(&save_dir))
+        .await
+        .unwrap_or_else(|e| Err(format!("Save directory check panicked: {}", e)));
+
+    let checks: BTreeMap<String, CheckResult> = [("database", database), ("game_loop", game_loop), ("save_dir", save_dir)]
+        .into_iter()
+        .map(|(name, result)| (name.to_string(), CheckResult::from_result(result)))
+        .collect();
+    let ready = checks.values().all(|check| check.ok);
+    if !ready {
+        for (name, check) in checks.iter().filter(|(_, check)| !check.ok) {
+            log::warn!("Readiness check {} failed: {}", name, check.message);
+        }
+    }
+
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(HealthResponse {
+        status: if ready { "healthy" } else { "unhealthy" }.to_string(),
+        service: "geekcraft".to_string(),
+        checks,
+    }))
+}
+
+/// Whether a file can be created in a directory (the probe file is removed)
+fn check_writable(dir: &Path) -> Result<String, String> {
+    let probe = dir.join(format!(".ready-{}", uuid::Uuid::new_v4().simple()));
+    std::fs::write(&probe, b"ok")
+        .map_err(|e| format!("{} is not writable: {}", dir.display(), e))?;
+    std::fs::remove_file(&probe)
+        .map_err(|e| format!("Could not remove {}: {}", probe.display(), e))?;
+    Ok(format!("{} is writable", dir.display()))
+}
@@ -0,0 +1,133 @@
+//! History routes module
+//!
+//! HTTP endpoints showing the world as it was at a past simulation tick, as the caller
+//! saw it then (see [`crate::game::history`]). Ticks older than the history answer
+//! `410 Gone` with the earliest tick still kept.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::game::clock::DayPhase;
+use crate::game::history::HistoryError;
+use crate::game::zone::Zone;
+use crate::network::extract::AuthSession;
+use crate::network::server::AppState;
+
+/// Query parameters of the history endpoints
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct HistoryQuery {
+    /// Simulation tick to look at
+    pub tick: u64,
+}
+
+/// Response for a past game state
+#[derive(Debug, Serialize)]
+pub struct HistoryGameStateResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Simulation tick shown
+    pub tick: u64,
+    /// Script tick at that simulation tick
+    pub script_tick: Option<u64>,
+    /// Phase of the day at that tick
+    pub day_phase: Option<DayPhase>,
+    /// What the caller's script would have been given at that tick (see `World::player_snapshot`)
+    pub snapshot: Option<serde_json::Value>,
+    /// Oldest tick still kept, when the requested one is not
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub earliest_tick: Option<u64>,
+}
+
+/// Response for a past zone
+#[derive(Debug, Serialize)]
+pub struct HistoryZoneResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Simulation tick shown
+    pub tick: u64,
+    /// The zone as the caller saw it (see `World::zone_view`)
+    pub zone: Option<Zone>,
+    /// Oldest tick still kept, when the requested one is not
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub earliest_tick: Option<u64>,
+}
+
+/// Status and earliest kept tick for a tick that cannot be shown
+fn history_error_status(err: &HistoryError) -> (StatusCode, Option<u64>) {
+    match err {
+        HistoryError::Expired { earliest } => (StatusCode::GONE, *earliest),
+        HistoryError::Future { .. } => (StatusCode::BAD_REQUEST, None),
+        HistoryError::Failed(e) => {
+            log::error!("Failed to rebuild history: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, None)
+        }
+    }
+}
+
+/// Handler to get the caller's view of the game at a past tick
+pub async fn history_game_state_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Query(query): Query<HistoryQuery>,
+) -> Response {
+    let past = state.game_world.read().await.state_at(query.tick);
+    match past {
+        Ok(world) => Json(HistoryGameStateResponse {
+            success: true,
+            message: format!("Game state at tick {}", query.tick),
+            tick: query.tick,
+            script_tick: Some(world.get_script_tick()),
+            day_phase: Some(world.day_phase()),
+            snapshot: Some(world.player_snapshot(&session.username)),
+            earliest_tick: None,
+        }).into_response(),
+        Err(err) => {
+            let (status, earliest_tick) = history_error_status(&err);
+            (status, Json(HistoryGameStateResponse {
+                success: false,
+                message: format!("Cannot show tick {}: {}", query.tick, err),
+                tick: query.tick,
+                script_tick: None,
+                day_phase: None,
+                snapshot: None,
+                earliest_tick,
+            })).into_response()
+        }
+    }
+}
+
+/// Handler to get a zone as the caller saw it at a past tick
+pub async fn history_zone_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path(zone_id): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> Response {
+    let past = state.game_world.read().await.state_at(query.tick);
+    let (status, message, zone, earliest_tick) = match past {
+        Ok(world) => match world.zone_view(&session.username, &zone_id) {
+            Some(zone) => (StatusCode::OK, format!("Zone {} at tick {}", zone_id, query.tick), Some(zone), None),
+            None => (StatusCode::NOT_FOUND, format!("Zone {} did not exist at tick {}", zone_id, query.tick), None, None),
+        },
+        Err(err) => {
+            let (status, earliest_tick) = history_error_status(&err);
+            (status, format!("Cannot show tick {}: {}", query.tick, err), None, earliest_tick)
+        }
+    };
+    (status, Json(HistoryZoneResponse {
+        success: status == StatusCode::OK,
+        message,
+        tick: query.tick,
+        zone,
+        earliest_tick,
+    })).into_response()
+}
@@ -0,0 +1,142 @@
+//! IP filter module
+//!
+//! Allowlist and blocklist of client addresses, as IPv4 or IPv6 CIDR ranges (a bare
+//! address is a single-address range). [`IpFilterLayer`] checks the peer address of
+//! every request, WebSocket upgrades included, before it reaches the routes. The ranges
+//! come from the configuration and are replaced when it is reloaded.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use futures_util::future::{self, Either, Ready};
+use ipnet::IpNet;
+use tower::{Layer, Service};
+
+/// Parse CIDR ranges such as `10.0.0.0/8`, `2001:db8::/32` or `192.168.1.7`
+pub fn parse_ranges(ranges: &[String]) -> Result<Vec<IpNet>, String> {
+    ranges.iter()
+        .map(|range| {
+            let range = range.trim();
+            range.parse::<IpNet>()
+                .or_else(|_| range.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| format!("{:?} is not an IP address or CIDR range", range))
+        })
+        .collect()
+}
+
+#[derive(Debug, Default)]
+struct Rules {
+    allow: Vec<IpNet>,
+    block: Vec<IpNet>,
+}
+
+/// Client addresses allowed to reach the server
+///
+/// Blocklisted addresses are refused. If the allowlist is not empty, only the addresses
+/// it contains are accepted.
+#[derive(Debug, Default)]
+pub struct IpFilter {
+    rules: RwLock<Rules>,
+}
+
+impl IpFilter {
+    /// Filter with the given ranges (see [`parse_ranges`])
+    pub fn new(allowlist: &[String], blocklist: &[String]) -> Result<Self, String> {
+        let filter = Self::default();
+        filter.set(allowlist, blocklist)?;
+        Ok(filter)
+    }
+
+    /// Replace the ranges; on error the previous ones are kept
+    pub fn set(&self, allowlist: &[String], blocklist: &[String]) -> Result<(), String> {
+        let allow = parse_ranges(allowlist)?;
+        let block = parse_ranges(blocklist)?;
+        *self.rules.write().unwrap_or_else(|e| e.into_inner()) = Rules { allow, block };
+        Ok(())
+    }
+
+    /// Whether the filter lets every address through
+    pub fn is_open(&self) -> bool {
+        let rules = self.rules.read().unwrap_or_else(|e| e.into_inner());
+        rules.allow.is_empty() && rules.block.is_empty()
+    }
+
+    /// Whether a client address may reach the server
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        // IPv4 clients of a dual-stack listener show up as `::ffff:a.b.c.d`
+        let ip = ip.to_canonical();
+        let rules = self.rules.read().unwrap_or_else(|e| e.into_inner());
+        if rules.block.iter().any(|range| range.contains(&ip)) {
+            return false;
+        }
+        rules.allow.is_empty() || rules.allow.iter().any(|range| range.contains(&ip))
+    }
+}
+
+/// Layer refusing requests from filtered addresses with `403 Forbidden`
+///
+/// The peer address comes from `ConnectInfo<SocketAddr>`, so the router must be served
+/// with `into_make_service_with_connect_info`. When the filter has ranges, requests
+/// without a known peer address are refused.
+#[derive(Debug, Clone)]
+pub struct IpFilterLayer {
+    filter: Arc<IpFilter>,
+}
+
+impl IpFilterLayer {
+    /// Layer checking requests against `filter`
+    pub fn new(filter: Arc<IpFilter>) -> Self {
+        Self { filter }
+    }
+}
+
+impl<S> Layer<S> for IpFilterLayer {
+    type Service = IpFilterService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IpFilterService {
+            inner,
+            filter: self.filter.clone(),
+        }
+    }
+}
+
+/// Service created by [`IpFilterLayer`]
+#[derive(Debug, Clone)]
+pub struct IpFilterService<S> {
+    inner: S,
+    filter: Arc<IpFilter>,
+}
+
+impl<S> Service<Request<Body>> for IpFilterService<S>
+where
+    S: Service<Request<Body>, Response = Response>,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Either<Ready<Result<Response, S::Error>>, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        if !self.filter.is_open() {
+            let peer = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0);
+            if !peer.is_some_and(|peer| self.filter.allows(peer.ip())) {
+                log::debug!("Refused request from {:?} to {}", peer, request.uri().path());
+                let forbidden = (
+                    StatusCode::FORBIDDEN,
+                    axum::Json(serde_json::json!({"success": false, "message": "Access denied"})),
+                );
+                return Either::Left(future::ready(Ok(forbidden.into_response())));
+            }
+        }
+        Either::Right(self.inner.call(request))
+    }
+}
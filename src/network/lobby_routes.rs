@@ -0,0 +1,186 @@
+//! Lobby routes module
+//!
+//! HTTP endpoint handlers for multiplayer lobbies (create, join, leave, start, list).
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::game::campaign::RunOptions;
+use crate::game::lobby::{Lobby, MatchConfig};
+use crate::network::extract::{AuthSession, SizedJson};
+use crate::network::campaign_routes::campaign_manager;
+use crate::network::server::AppState;
+
+/// Request to create a lobby
+#[derive(Debug, Deserialize)]
+pub struct CreateLobbyRequest {
+    /// Lobby display name
+    pub name: String,
+    /// Maximum number of players
+    pub max_players: u8,
+    /// Match settings
+    #[serde(default)]
+    pub config: MatchConfig,
+}
+
+/// Response for lobby operations
+#[derive(Debug, Serialize)]
+pub struct LobbyResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Lobby state (if it still exists)
+    pub lobby: Option<Lobby>,
+}
+
+/// Response for listing lobbies
+#[derive(Debug, Serialize)]
+pub struct ListLobbiesResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// All lobbies
+    pub lobbies: Vec<Lobby>,
+}
+
+fn lobby_error(status: StatusCode, message: String) -> (StatusCode, Json<LobbyResponse>) {
+    (
+        status,
+        Json(LobbyResponse {
+            success: false,
+            message,
+            lobby: None,
+        })
+    )
+}
+
+/// Handler to create a lobby (the creator joins as owner)
+pub async fn create_lobby_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<CreateLobbyRequest>,
+) -> impl IntoResponse {
+    let mut lobbies = state.lobby_manager.write().await;
+
+    match lobbies.create(&payload.name, session.user_id, &session.username, payload.max_players, payload.config) {
+        Ok(lobby) => {
+            log::info!("{} created lobby {} ({})", session.username, lobby.name, lobby.id);
+            (
+                StatusCode::OK,
+                Json(LobbyResponse {
+                    success: true,
+                    message: format!("Lobby {} created", lobby.name),
+                    lobby: Some(lobby),
+                })
+            )
+        }
+        Err(err) => lobby_error(StatusCode::BAD_REQUEST, err),
+    }
+}
+
+/// Handler to list lobbies
+pub async fn list_lobbies_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let lobbies = state.lobby_manager.read().await;
+
+    Json(ListLobbiesResponse {
+        success: true,
+        lobbies: lobbies.list(),
+    })
+}
+
+/// Handler to join a lobby
+pub async fn join_lobby_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path(lobby_id): Path<Uuid>,
+) -> impl IntoResponse {
+    let mut lobbies = state.lobby_manager.write().await;
+
+    match lobbies.join(&lobby_id, session.user_id, &session.username) {
+        Ok(lobby) => (
+            StatusCode::OK,
+            Json(LobbyResponse {
+                success: true,
+                message: format!("Joined lobby {}", lobby.name),
+                lobby: Some(lobby),
+            })
+        ),
+        Err(err) => lobby_error(StatusCode::BAD_REQUEST, err),
+    }
+}
+
+/// Handler to leave a lobby
+pub async fn leave_lobby_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path(lobby_id): Path<Uuid>,
+) -> impl IntoResponse {
+    let mut lobbies = state.lobby_manager.write().await;
+
+    match lobbies.leave(&lobby_id, session.user_id) {
+        Ok(lobby) => (
+            StatusCode::OK,
+            Json(LobbyResponse {
+                success: true,
+                message: "Left lobby".to_string(),
+                lobby,
+            })
+        ),
+        Err(err) => lobby_error(StatusCode::BAD_REQUEST, err),
+    }
+}
+
+/// Handler to start a lobby's match (owner only)
+///
+/// Creates the match world, starts a campaign run on it, and notifies all lobby members
+/// over WebSocket with a `lobbyStarted` message. The lobby is finished once the run stops.
+pub async fn start_lobby_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path(lobby_id): Path<Uuid>,
+) -> impl IntoResponse {
+    let mut lobbies = state.lobby_manager.write().await;
+
+    let (lobby, world) = match lobbies.start(&lobby_id, session.user_id) {
+        Ok(started) => started,
+        Err(err) => return lobby_error(StatusCode::BAD_REQUEST, err),
+    };
+    let run_id = lobby.run_id.clone().unwrap_or_default();
+
+    let options = RunOptions {
+        allow_spectators: lobby.config.allow_spectators,
+        ..RunOptions::default()
+    };
+    if let Err(err) = campaign_manager().write().await.start_run_with_world(run_id.clone(), options, world) {
+        log::error!("Failed to start run for lobby {}: {}", lobby_id, err);
+        lobbies.cancel_start(&lobby_id);
+        return lobby_error(StatusCode::INTERNAL_SERVER_ERROR, err);
+    }
+
+    let lobby = lobbies.mark_in_progress(&lobby_id).unwrap_or(lobby);
+    log::info!("Lobby {} started as run {}", lobby.name, run_id);
+
+    let notification = serde_json::json!({
+        "type": "lobbyStarted",
+        "lobby_id": lobby.id,
+        "run_id": run_id
+    });
+    for player_id in &lobby.players {
+        state.ws_clients.send_to_user(*player_id, &notification);
+    }
+
+    (
+        StatusCode::OK,
+        Json(LobbyResponse {
+            success: true,
+            message: format!("Lobby {} started", lobby.name),
+            lobby: Some(lobby),
+        })
+    )
+}
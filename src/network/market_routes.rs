@@ -0,0 +1,94 @@
+//! Market routes module
+//!
+//! HTTP endpoint handlers for the market: placing, listing, and cancelling orders.
+//! Orders are matched by the game loop (see [`crate::game::market`]).
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::game::market::{MarketOrder, OrderSide};
+use crate::game::zone::ResourceType;
+use crate::network::extract::{AuthSession, SizedJson};
+use crate::network::server::AppState;
+
+/// Request to place a market order
+#[derive(Debug, Deserialize)]
+pub struct PlaceOrderRequest {
+    /// `buy` or `sell`
+    pub side: OrderSide,
+    /// Resource to trade (`minerals` or `gas`)
+    pub resource: ResourceType,
+    /// Amount to trade
+    pub amount: u32,
+    /// Credits per unit
+    pub price: u32,
+}
+
+/// Response for placing or cancelling an order
+#[derive(Debug, Serialize)]
+pub struct OrderResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// The order placed or cancelled
+    pub order: Option<MarketOrder>,
+}
+
+/// Response for the open orders
+#[derive(Debug, Serialize)]
+pub struct OrdersResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Open orders, oldest first
+    pub orders: Vec<MarketOrder>,
+}
+
+fn order_response(result: Result<MarketOrder, String>, message: &str) -> (StatusCode, Json<OrderResponse>) {
+    match result {
+        Ok(order) => (StatusCode::OK, Json(OrderResponse { success: true, message: message.to_string(), order: Some(order) })),
+        Err(message) => (StatusCode::BAD_REQUEST, Json(OrderResponse { success: false, message, order: None })),
+    }
+}
+
+/// Handler to place a buy or sell order
+pub async fn place_order_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<PlaceOrderRequest>,
+) -> impl IntoResponse {
+    let result = state.game_world.write().await
+        .place_order(&session.username, payload.side, payload.resource, payload.amount, payload.price);
+    order_response(result, "Order placed")
+}
+
+/// Handler to list the open orders of every player
+pub async fn list_orders_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let orders = state.game_world.read().await.market_orders().to_vec();
+    (
+        StatusCode::OK,
+        Json(OrdersResponse {
+            success: true,
+            message: format!("{} open orders", orders.len()),
+            orders,
+        })
+    )
+}
+
+/// Handler to cancel one of the caller's orders
+pub async fn cancel_order_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path(order_id): Path<Uuid>,
+) -> impl IntoResponse {
+    let result = state.game_world.write().await.cancel_order(&session.username, order_id);
+    order_response(result, "Order cancelled")
+}
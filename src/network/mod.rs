@@ -3,5 +3,41 @@
 //! Handles HTTP/WebSocket server, REST API endpoints, and client communication.
 
 pub mod server;
+pub mod tls;
 pub mod campaign_routes;
-pub mod zone_routes;
\ No newline at end of file
+pub mod zone_routes;
+pub mod spectator;
+pub mod connection_limit;
+pub mod ip_filter;
+pub mod compression;
+pub mod etag;
+pub mod zone_cache;
+pub mod error_feed;
+pub mod email_routes;
+pub mod health_routes;
+pub mod lobby_routes;
+pub mod team_routes;
+pub mod ws_clients;
+pub mod tournament_routes;
+pub mod world_routes;
+pub mod state_sync;
+pub mod ws_codec;
+pub mod chat;
+pub mod achievement_routes;
+pub mod friend_routes;
+pub mod oauth_routes;
+pub mod pagination;
+pub mod event_routes;
+pub mod history_routes;
+pub mod announcement_routes;
+pub mod market_routes;
+pub mod alliance_routes;
+pub mod script_routes;
+pub mod admin_routes;
+pub mod extract;
+pub mod stats_routes;
+pub mod shutdown;
+#[cfg(feature = "redis_backend")]
+pub mod pubsub;
+#[cfg(unix)]
+pub mod control;
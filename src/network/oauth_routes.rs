@@ -0,0 +1,125 @@
+//! OAuth routes module
+//!
+//! HTTP endpoint handlers for signing in with an identity provider (see
+//! [`crate::auth::oauth`]). Both endpoints are public: the start endpoint redirects the
+//! player to the provider, and the callback answers like `POST /api/auth/login`.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::auth::models::AuthResponse;
+use crate::auth::oauth::OAuthProvider;
+use crate::auth::service::OAUTH_SIGNUP_REFUSED;
+use crate::config::API_VERSION;
+use crate::network::server::{assign_zone, public_base_url, AppState};
+
+/// Query string the provider sends the player back with
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    /// Authorization code
+    pub code: Option<String>,
+    /// State issued when the flow started
+    pub state: Option<String>,
+    /// Set instead of `code` when the player refused or the provider failed
+    pub error: Option<String>,
+}
+
+fn failure(status: StatusCode, message: String) -> Response {
+    (
+        status,
+        Json(AuthResponse {
+            success: false,
+            message,
+            token: None,
+            username: None,
+        }),
+    ).into_response()
+}
+
+/// Callback URL of a provider
+fn redirect_uri(state: &AppState, headers: &HeaderMap, provider: &OAuthProvider) -> Result<String, String> {
+    Ok(format!("{}/api/{}/auth/oauth/{}/callback", public_base_url(state, headers)?, API_VERSION, provider.name))
+}
+
+/// Handler starting a sign-in: redirects (303) to the provider's approval page
+pub async fn oauth_start_handler(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(provider) = state.oauth.provider(&provider) else {
+        return failure(StatusCode::NOT_FOUND, format!("Sign-in with {} is not enabled", provider));
+    };
+
+    let redirect = redirect_uri(&state, &headers, provider)
+        .and_then(|redirect| {
+            let flow = state.oauth.start(provider, chrono::Utc::now().timestamp());
+            provider.authorization_url(&flow, &redirect)
+        });
+    match redirect {
+        Ok(url) => Redirect::to(&url).into_response(),
+        Err(err) => {
+            log::error!("Could not start {} sign-in: {}", provider.name, err);
+            failure(StatusCode::INTERNAL_SERVER_ERROR, err)
+        }
+    }
+}
+
+/// Handler completing a sign-in: exchanges the code and logs the account's user in
+///
+/// The user is created on the first sign-in with an account, unless registration is not
+/// open (`403 Forbidden`).
+pub async fn oauth_callback_handler(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    Query(query): Query<CallbackQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(provider) = state.oauth.provider(&provider) else {
+        return failure(StatusCode::NOT_FOUND, format!("Sign-in with {} is not enabled", provider));
+    };
+    if let Some(error) = query.error {
+        return failure(StatusCode::BAD_REQUEST, format!("{} sign-in failed: {}", provider.name, error));
+    }
+    let (Some(code), Some(flow)) = (query.code, query.state) else {
+        return failure(StatusCode::BAD_REQUEST, "Missing code or state".to_string());
+    };
+    if !state.oauth.finish(provider, &flow, chrono::Utc::now().timestamp()) {
+        return failure(StatusCode::BAD_REQUEST, "Unknown or expired sign-in, start again".to_string());
+    }
+
+    let account = match redirect_uri(&state, &headers, provider) {
+        Ok(redirect) => match provider.exchange_code(state.oauth.http(), &code, &redirect).await {
+            Ok(access_token) => provider.fetch_account(state.oauth.http(), &access_token).await,
+            Err(err) => Err(err),
+        },
+        Err(err) => Err(err),
+    };
+    let (oauth_id, login) = match account {
+        Ok(account) => account,
+        Err(err) => {
+            log::warn!("{} sign-in failed: {}", provider.name, err);
+            return failure(StatusCode::BAD_GATEWAY, err);
+        }
+    };
+
+    let mode = state.config().registration_mode;
+    let response = state.auth_service.oauth_login(provider.name, &oauth_id, &login, mode);
+    if !response.success {
+        let status = if response.message.starts_with(OAUTH_SIGNUP_REFUSED) {
+            StatusCode::FORBIDDEN
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        };
+        return failure(status, response.message);
+    }
+    if let Some(username) = &response.username {
+        assign_zone(&state, username).await;
+    }
+    Json(response).into_response()
+}
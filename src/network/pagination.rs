@@ -0,0 +1,88 @@
+//! Pagination module
+//!
+//! `?page=&per_page=` query parameters and the response wrapper shared by list endpoints.
+//! Pages are numbered from 1; `per_page` defaults to [`DEFAULT_PER_PAGE`] and is capped
+//! at [`MAX_PER_PAGE`].
+
+use serde::{Deserialize, Serialize};
+
+/// Items per page when `per_page` is not given
+pub const DEFAULT_PER_PAGE: u32 = 50;
+
+/// Maximum items per page
+pub const MAX_PER_PAGE: u32 = 200;
+
+/// Pagination query parameters
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct PaginationQuery {
+    /// Page number, from 1 (default 1)
+    pub page: Option<u32>,
+    /// Items per page (default [`DEFAULT_PER_PAGE`], at most [`MAX_PER_PAGE`])
+    pub per_page: Option<u32>,
+}
+
+impl PaginationQuery {
+    /// Page number and page size, with defaults applied and out-of-range values clamped
+    pub fn resolve(&self) -> (u32, u32) {
+        (
+            self.page.unwrap_or(1).max(1),
+            self.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE),
+        )
+    }
+}
+
+/// One page of a list
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaginatedResponse<T> {
+    /// Items of the page
+    pub items: Vec<T>,
+    /// Page number, from 1
+    pub page: u32,
+    /// Maximum items per page
+    pub per_page: u32,
+    /// Number of items across all pages
+    pub total: u32,
+    /// Number of pages
+    pub total_pages: u32,
+}
+
+impl<T> PaginatedResponse<T> {
+    /// Wrap a page of items taken from a list of `total` items
+    pub fn new(items: Vec<T>, page: u32, per_page: u32, total: u32) -> Self {
+        Self {
+            items,
+            page,
+            per_page,
+            total,
+            total_pages: total.div_ceil(per_page.max(1)),
+        }
+    }
+
+    /// Take the requested page of a full list
+    pub fn from_items(items: Vec<T>, query: &PaginationQuery) -> Self {
+        let (page, per_page) = query.resolve();
+        let total = items.len() as u32;
+        let start = (page - 1) as usize * per_page as usize;
+        let items = items.into_iter().skip(start).take(per_page as usize).collect();
+        Self::new(items, page, per_page, total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pages_are_clamped_and_counted() {
+        let query = PaginationQuery { page: Some(0), per_page: Some(1000) };
+        assert_eq!(query.resolve(), (1, MAX_PER_PAGE));
+
+        let page = PaginatedResponse::from_items((0..120).collect(), &PaginationQuery { page: Some(3), per_page: Some(50) });
+        assert_eq!(page.items, (100..120).collect::<Vec<_>>());
+        assert_eq!((page.total, page.total_pages), (120, 3));
+
+        let past_end = PaginatedResponse::from_items((0..120).collect::<Vec<u32>>(), &PaginationQuery { page: Some(4), per_page: None });
+        assert!(past_end.items.is_empty());
+        assert_eq!(past_end.per_page, DEFAULT_PER_PAGE);
+    }
+}
@@ -0,0 +1,387 @@
+//! Redis pub/sub bridge
+//!
+//! Lets several GeekCraft instances serve WebSocket clients while a single one runs the
+//! simulation. The instance role comes from `GEEKCRAFT_ROLE`:
+//!
+//! - `sim` (default) runs the game loop. After every tick it sends each zone's changes
+//!   (the [`state_sync`](crate::network::state_sync) keyframes and deltas) and the new
+//!   game events to its own WebSocket clients, and publishes the same messages to Redis.
+//! - `edge` does not simulate. It subscribes to the Redis channels and forwards every
+//!   message to its WebSocket clients.
+//!
+//! Messages are [`TickMessage`]s as JSON, on [`TICKS_CHANNEL`] and [`EVENTS_CHANNEL`].
+//! When Redis cannot be reached (`GEEKCRAFT_REDIS_URL`, default [`DEFAULT_REDIS_URL`]), the
+//! instance warns once and keeps going in a degraded, local-only mode: the sim instance
+//! only reaches its own clients, edge instances receive nothing. It reconnects with
+//! exponential backoff, from [`MIN_BACKOFF`] up to [`MAX_BACKOFF`].
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+use crate::game::events::GameEvent;
+use crate::game::world::World;
+use crate::network::state_sync::{self, Delta, SyncState};
+use crate::network::ws_clients::WsClients;
+
+/// Redis server used when `GEEKCRAFT_REDIS_URL` is not set
+pub const DEFAULT_REDIS_URL: &str = "redis://127.0.0.1:6379";
+
+/// Channel of zone keyframes and deltas
+pub const TICKS_CHANNEL: &str = "geekcraft:ticks";
+
+/// Channel of game events
+pub const EVENTS_CHANNEL: &str = "geekcraft:events";
+
+/// First wait before reconnecting to Redis
+pub const MIN_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Longest wait between attempts to reconnect to Redis
+pub const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Longest wait for Redis to accept a connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// What an instance does in a multi-server deployment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Role {
+    /// Runs the simulation and publishes its ticks
+    #[default]
+    Sim,
+    /// Forwards the ticks published by the sim instance
+    Edge,
+}
+
+impl Role {
+    /// Parse a role name (`sim` or `edge`, case-insensitive)
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "sim" => Ok(Role::Sim),
+            "edge" => Ok(Role::Edge),
+            _ => Err(format!("Unknown role {:?}, expected sim or edge", name)),
+        }
+    }
+
+    /// Role named by `GEEKCRAFT_ROLE` (`sim` if unset or unknown)
+    pub fn from_env() -> Self {
+        match std::env::var("GEEKCRAFT_ROLE") {
+            Ok(name) => Role::parse(&name).unwrap_or_else(|e| {
+                log::warn!("⚠️  GEEKCRAFT_ROLE: {}; running the simulation", e);
+                Role::Sim
+            }),
+            Err(_) => Role::Sim,
+        }
+    }
+
+    /// Whether this instance runs the game loop
+    pub fn simulates(self) -> bool {
+        self == Role::Sim
+    }
+}
+
+/// Redis server named by `GEEKCRAFT_REDIS_URL`
+pub fn redis_url_from_env() -> String {
+    std::env::var("GEEKCRAFT_REDIS_URL").unwrap_or_else(|_| DEFAULT_REDIS_URL.to_string())
+}
+
+/// A message fanned out to every WebSocket client, as sent to them
+///
+/// Zone messages follow the delta protocol: each zone has its own sequence number, one
+/// higher in every message, starting with a keyframe.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TickMessage {
+    /// Full state of a zone
+    ZoneKeyframe {
+        /// Sequence number of the zone's stream
+        seq: u64,
+        /// Zone content
+        state: SyncState,
+    },
+    /// Changes of a zone since the previous message of its stream
+    ZoneDelta {
+        /// Zone that changed
+        zone_id: String,
+        /// Sequence number of the zone's stream
+        seq: u64,
+        /// Zone changes
+        delta: Delta,
+    },
+    /// Something that happened in the world
+    GameEvent {
+        /// The event
+        event: GameEvent,
+    },
+}
+
+impl TickMessage {
+    /// Redis channel the message is published on
+    pub fn channel(&self) -> &'static str {
+        match self {
+            TickMessage::GameEvent { .. } => EVENTS_CHANNEL,
+            _ => TICKS_CHANNEL,
+        }
+    }
+}
+
+/// Turns the world's ticks into [`TickMessage`]s
+///
+/// A zone gets a keyframe the first time it is seen and every `keyframe_ticks` ticks,
+/// deltas in between; zones that did not change get no message. Events are those recorded
+/// since the previous tick published (none for the first one).
+#[derive(Debug)]
+pub struct TickPublisher {
+    keyframe_ticks: u64,
+    /// Sequence number, last sent state, and tick of the last keyframe of each zone
+    zones: HashMap<String, (u64, SyncState, u64)>,
+    next_event_id: Option<u64>,
+    last_tick: Option<u64>,
+}
+
+impl TickPublisher {
+    /// Publisher sending each zone a keyframe every `keyframe_ticks` ticks (at least one)
+    pub fn new(keyframe_ticks: u64) -> Self {
+        Self {
+            keyframe_ticks: keyframe_ticks.max(1),
+            zones: HashMap::new(),
+            next_event_id: None,
+            last_tick: None,
+        }
+    }
+
+    /// Messages for the world's current tick (nothing if it was already published)
+    pub fn messages(&mut self, world: &World) -> Vec<TickMessage> {
+        let tick = world.get_tick();
+        if self.last_tick == Some(tick) {
+            return Vec::new();
+        }
+        self.last_tick = Some(tick);
+
+        let zone_ids = world.get_zone_ids();
+        self.zones.retain(|zone_id, _| zone_ids.contains(zone_id));
+
+        let mut messages = Vec::new();
+        for zone_id in zone_ids {
+            let Some(current) = world.with_zone_read(&zone_id, |zone| SyncState::new(tick, zone)) else {
+                continue;
+            };
+            match self.zones.get_mut(&zone_id) {
+                Some((seq, previous, keyframe_tick)) if tick < *keyframe_tick + self.keyframe_ticks => {
+                    let delta = state_sync::diff(previous, &current);
+                    if delta.is_empty() {
+                        continue;
+                    }
+                    *seq += 1;
+                    *previous = current;
+                    messages.push(TickMessage::ZoneDelta { zone_id, seq: *seq, delta });
+                }
+                stream => {
+                    let seq = stream.map_or(0, |(seq, _, _)| *seq + 1);
+                    self.zones.insert(zone_id, (seq, current.clone(), tick));
+                    messages.push(TickMessage::ZoneKeyframe { seq, state: current });
+                }
+            }
+        }
+
+        let mut events: Vec<&GameEvent> = self.zones.keys()
+            .flat_map(|zone_id| world.event_log().zone_events(zone_id))
+            .filter(|event| self.next_event_id.is_none_or(|next| event.id >= next))
+            .collect();
+        events.sort_by_key(|event| event.id);
+        let first_tick = self.next_event_id.is_none();
+        self.next_event_id = Some(events.last().map_or(self.next_event_id.unwrap_or(0), |event| event.id + 1));
+        if !first_tick {
+            messages.extend(events.into_iter().map(|event| TickMessage::GameEvent { event: event.clone() }));
+        }
+        messages
+    }
+}
+
+/// Exponential wait between reconnection attempts
+#[derive(Debug)]
+struct Backoff {
+    next: Duration,
+}
+
+impl Backoff {
+    fn new() -> Self {
+        Self { next: MIN_BACKOFF }
+    }
+
+    /// Wait before the next attempt, doubling the one after it
+    fn step(&mut self) -> Duration {
+        let wait = self.next;
+        self.next = (self.next * 2).min(MAX_BACKOFF);
+        wait
+    }
+
+    fn reset(&mut self) {
+        self.next = MIN_BACKOFF;
+    }
+}
+
+/// A running bridge; the bridge stops when this is dropped
+#[derive(Debug)]
+pub struct PubSubBridge {
+    role: Role,
+    degraded: Arc<AtomicBool>,
+    task: JoinHandle<()>,
+}
+
+impl PubSubBridge {
+    /// Role of the instance
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// Whether Redis is currently unreachable (local-only mode)
+    pub fn is_degraded(&self) -> bool {
+        self.degraded.load(Ordering::Relaxed)
+    }
+
+    /// Start publishing the world's ticks, checking for a new tick every `poll_interval`
+    ///
+    /// Messages also go to the instance's own clients, whether Redis is reachable or not.
+    pub fn start_sim(
+        redis_url: &str,
+        world: Arc<RwLock<World>>,
+        ws_clients: Arc<WsClients>,
+        poll_interval: Duration,
+        keyframe_ticks: u64,
+    ) -> Result<Self, String> {
+        let client = open_client(redis_url)?;
+        let degraded = Arc::new(AtomicBool::new(false));
+        let task_degraded = degraded.clone();
+
+        let task = tokio::spawn(async move {
+            let mut publisher = TickPublisher::new(keyframe_ticks);
+            let mut connection = None;
+            let mut backoff = Backoff::new();
+            let mut retry_at = tokio::time::Instant::now();
+            let mut interval = tokio::time::interval(poll_interval);
+
+            loop {
+                interval.tick().await;
+                let messages = publisher.messages(&*world.read().await);
+
+                if connection.is_none() && tokio::time::Instant::now() >= retry_at {
+                    match connect(&client).await {
+                        Ok(conn) => {
+                            set_connected(&task_degraded, &mut backoff);
+                            connection = Some(conn);
+                        }
+                        Err(e) => {
+                            set_degraded(&task_degraded, &e);
+                            retry_at = tokio::time::Instant::now() + backoff.step();
+                        }
+                    }
+                }
+
+                for message in &messages {
+                    let Ok(payload) = serde_json::to_string(message) else {
+                        continue;
+                    };
+                    if let Ok(value) = serde_json::from_str(&payload) {
+                        ws_clients.broadcast(&value);
+                    }
+                    let Some(conn) = connection.as_mut() else {
+                        continue;
+                    };
+                    let published: redis::RedisResult<i64> = conn.publish(message.channel(), payload).await;
+                    if let Err(e) = published {
+                        set_degraded(&task_degraded, &e.to_string());
+                        connection = None;
+                        retry_at = tokio::time::Instant::now() + backoff.step();
+                    }
+                }
+            }
+        });
+
+        Ok(Self { role: Role::Sim, degraded, task })
+    }
+
+    /// Start forwarding the published ticks to the instance's clients
+    pub fn start_edge(redis_url: &str, ws_clients: Arc<WsClients>) -> Result<Self, String> {
+        let client = open_client(redis_url)?;
+        let degraded = Arc::new(AtomicBool::new(false));
+        let task_degraded = degraded.clone();
+
+        let task = tokio::spawn(async move {
+            let mut backoff = Backoff::new();
+            loop {
+                match subscribe(&client).await {
+                    Ok(mut pubsub) => {
+                        set_connected(&task_degraded, &mut backoff);
+                        let mut messages = pubsub.on_message();
+                        while let Some(message) = messages.next().await {
+                            let forwarded = message.get_payload::<String>().ok()
+                                .and_then(|payload| serde_json::from_str::<TickMessage>(&payload).ok())
+                                .and_then(|message| serde_json::to_value(message).ok());
+                            match forwarded {
+                                Some(value) => {
+                                    ws_clients.broadcast(&value);
+                                }
+                                None => log::debug!("Ignoring malformed message on Redis channel {}", message.get_channel_name()),
+                            }
+                        }
+                        set_degraded(&task_degraded, "subscription closed");
+                    }
+                    Err(e) => set_degraded(&task_degraded, &e),
+                }
+                tokio::time::sleep(backoff.step()).await;
+            }
+        });
+
+        Ok(Self { role: Role::Edge, degraded, task })
+    }
+}
+
+impl Drop for PubSubBridge {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn open_client(redis_url: &str) -> Result<redis::Client, String> {
+    redis::Client::open(redis_url).map_err(|e| format!("Invalid Redis URL {}: {}", redis_url, e))
+}
+
+async fn connect(client: &redis::Client) -> Result<redis::aio::MultiplexedConnection, String> {
+    tokio::time::timeout(CONNECT_TIMEOUT, client.get_multiplexed_async_connection())
+        .await
+        .map_err(|_| "connection timed out".to_string())?
+        .map_err(|e| e.to_string())
+}
+
+async fn subscribe(client: &redis::Client) -> Result<redis::aio::PubSub, String> {
+    let mut pubsub = tokio::time::timeout(CONNECT_TIMEOUT, client.get_async_pubsub())
+        .await
+        .map_err(|_| "connection timed out".to_string())?
+        .map_err(|e| e.to_string())?;
+    pubsub.subscribe(&[TICKS_CHANNEL, EVENTS_CHANNEL]).await.map_err(|e| e.to_string())?;
+    Ok(pubsub)
+}
+
+/// Enter local-only mode, warning only on the way in
+fn set_degraded(degraded: &AtomicBool, reason: &str) {
+    if !degraded.swap(true, Ordering::Relaxed) {
+        log::warn!("⚠️  Redis unavailable ({}); serving local clients only until it is back", reason);
+    }
+}
+
+/// Leave local-only mode
+fn set_connected(degraded: &AtomicBool, backoff: &mut Backoff) {
+    backoff.reset();
+    if degraded.swap(false, Ordering::Relaxed) {
+        log::info!("✓ Redis connection restored");
+    } else {
+        log::info!("✓ Connected to Redis for tick fan-out");
+    }
+}
@@ -0,0 +1,343 @@
+//! Script module routes
+//!
+//! HTTP endpoint handlers to manage the modules of the caller's bot one at a time (list,
+//! add or replace, delete) instead of resubmitting the whole bundle, and to share
+//! libraries between players. Libraries are published by their author, approved by an
+//! admin, and then importable by every bot as `@community/<name>`. Changes take effect on
+//! the next script tick, like any code submission. Code can also be dry-run against the
+//! world as recorded at a recent script tick, without being submitted, and players can
+//! check how long their bot takes to run.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::auth::models::SharedLibrary;
+use crate::network::extract::{CODE_BODY_LIMIT, AuthSession, SizedJson};
+use crate::network::server::AppState;
+use crate::scripting::commands::BotCommand;
+use crate::scripting::sandbox::{ScriptEngine, ScriptStats};
+
+/// Request to add or replace a module
+#[derive(Debug, Deserialize)]
+pub struct SubmitModuleRequest {
+    /// Module name (e.g. `utils.js` or `lib/path.js`)
+    pub name: String,
+    /// Module source
+    pub code: String,
+}
+
+/// A module of the caller's bundle
+#[derive(Debug, Serialize)]
+pub struct ModuleInfo {
+    /// Module name
+    pub name: String,
+    /// Source size in bytes
+    pub size: usize,
+}
+
+/// Response for module operations
+#[derive(Debug, Serialize)]
+pub struct ModulesResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Modules of the caller's bundle, sorted by name
+    pub modules: Vec<ModuleInfo>,
+}
+
+fn modules_of(engine: &ScriptEngine, player_id: &str) -> Vec<ModuleInfo> {
+    engine.get_bundle(player_id)
+        .map(|bundle| bundle.modules().iter()
+            .map(|(name, code)| ModuleInfo { name: name.clone(), size: code.len() })
+            .collect())
+        .unwrap_or_default()
+}
+
+fn modules_response(engine: &ScriptEngine, player_id: &str, result: Result<String, String>) -> (StatusCode, Json<ModulesResponse>) {
+    let (status, success, message) = match result {
+        Ok(message) => (StatusCode::OK, true, message),
+        Err(err) => (StatusCode::BAD_REQUEST, false, err),
+    };
+    (
+        status,
+        Json(ModulesResponse {
+            success,
+            message,
+            modules: modules_of(engine, player_id),
+        })
+    )
+}
+
+/// Handler to list the modules of the caller's bundle
+pub async fn list_modules_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+) -> impl IntoResponse {
+    let engine = state.script_engine.read().await;
+    let count = engine.get_bundle(&session.username).map_or(0, |bundle| bundle.modules().len());
+    modules_response(&engine, &session.username, Ok(format!("{} modules", count)))
+}
+
+/// Handler to add or replace a module of the caller's bundle
+pub async fn submit_module_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<SubmitModuleRequest, CODE_BODY_LIMIT>,
+) -> impl IntoResponse {
+    let mut engine = state.script_engine.write().await;
+    let result = engine.submit_module(&session.username, &payload.name, &payload.code)
+        .map(|()| format!("Module {} submitted", payload.name));
+    if let Err(err) = &result {
+        log::warn!("Module submission failed for {}: {}", session.username, err);
+    }
+    modules_response(&engine, &session.username, result)
+}
+
+/// Handler to delete a module of the caller's bundle
+pub async fn delete_module_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let mut engine = state.script_engine.write().await;
+    let exists = engine.get_bundle(&session.username).is_some_and(|bundle| bundle.get(&name).is_some());
+    let result = engine.delete_module(&session.username, &name)
+        .map(|()| format!("Module {} deleted", name));
+    let (status, response) = modules_response(&engine, &session.username, result);
+    (if exists { status } else { StatusCode::NOT_FOUND }, response)
+}
+
+/// Request to publish a library (or a new version of one)
+#[derive(Debug, Deserialize)]
+pub struct PublishLibraryRequest {
+    /// Library name (imported as `@community/<name>`)
+    pub name: String,
+    /// JavaScript source
+    pub code: String,
+}
+
+/// Response for a single library version
+#[derive(Debug, Serialize)]
+pub struct LibraryResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// The library version (if successful)
+    pub library: Option<SharedLibrary>,
+}
+
+/// Response listing libraries
+#[derive(Debug, Serialize)]
+pub struct LibrariesResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Latest approved version of each library, sorted by name
+    pub libraries: Vec<SharedLibrary>,
+}
+
+fn library_error(status: StatusCode, message: String) -> (StatusCode, Json<LibraryResponse>) {
+    (
+        status,
+        Json(LibraryResponse {
+            success: false,
+            message,
+            library: None,
+        })
+    )
+}
+
+fn library_response(result: Result<SharedLibrary, String>, message: impl FnOnce(&SharedLibrary) -> String) -> (StatusCode, Json<LibraryResponse>) {
+    match result {
+        Ok(library) => (
+            StatusCode::OK,
+            Json(LibraryResponse {
+                success: true,
+                message: message(&library),
+                library: Some(library),
+            })
+        ),
+        Err(err) => library_error(StatusCode::BAD_REQUEST, err),
+    }
+}
+
+/// Load the approved libraries into the script engine
+async fn sync_libraries(state: &AppState) -> Result<(), String> {
+    let libraries = state.auth_service.approved_libraries()?.into_iter()
+        .map(|library| (library.name, library.code))
+        .collect();
+    state.script_engine.write().await.set_libraries(libraries);
+    Ok(())
+}
+
+/// Handler to publish a library, or a new version of one of the caller's libraries
+///
+/// New versions wait for approval; bots keep importing the latest approved version.
+pub async fn publish_library_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<PublishLibraryRequest, CODE_BODY_LIMIT>,
+) -> impl IntoResponse {
+    let result = state.auth_service.publish_library(session.user_id, &payload.name, &payload.code);
+    library_response(result, |library| {
+        log::info!("{} published library {} version {}", session.username, library.name, library.version);
+        format!("Library {} version {} published; it can be imported once approved", library.name, library.version)
+    })
+}
+
+/// Handler to list the latest approved version of every library
+pub async fn list_libraries_handler(State(state): State<AppState>) -> impl IntoResponse {
+    match state.auth_service.approved_libraries() {
+        Ok(libraries) => (
+            StatusCode::OK,
+            Json(LibrariesResponse {
+                success: true,
+                message: format!("{} libraries", libraries.len()),
+                libraries,
+            })
+        ),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(LibrariesResponse {
+                success: false,
+                message: err,
+                libraries: Vec::new(),
+            })
+        ),
+    }
+}
+
+/// Handler to approve a library version (admin only)
+pub async fn approve_library_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path(library_id): Path<Uuid>,
+) -> impl IntoResponse {
+    if !state.is_admin(&session.username) {
+        return library_error(StatusCode::FORBIDDEN, "Admin access required".to_string());
+    }
+
+    let result = state.auth_service.approve_library(&library_id);
+    if result.is_ok() {
+        if let Err(err) = sync_libraries(&state).await {
+            return library_error(StatusCode::INTERNAL_SERVER_ERROR, err);
+        }
+    }
+    library_response(result, |library| {
+        log::info!("{} approved library {} version {}", session.username, library.name, library.version);
+        format!("Library {} version {} approved", library.name, library.version)
+    })
+}
+
+/// Request to dry-run code against a recorded script tick
+#[derive(Debug, Deserialize)]
+pub struct DryRunRequest {
+    /// JavaScript source of the entry module
+    pub code: String,
+    /// Script tick of the replay history to run against
+    pub snapshot_tick: u64,
+}
+
+/// Response for a dry run
+#[derive(Debug, Serialize)]
+pub struct DryRunResponse {
+    /// Whether the snapshot was found and the script ran without error
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Syntax error, runtime exception, or timeout
+    pub error: Option<String>,
+    /// Console output of the script
+    pub logs: Vec<String>,
+    /// Commands the script issued (not applied)
+    pub commands: Vec<BotCommand>,
+    /// Time spent running the script, in nanoseconds
+    pub cpu_ns: u64,
+}
+
+/// Handler to run code against the world as recorded at a recent script tick
+///
+/// Nothing is applied: neither the live world, the caller's submitted code, nor their
+/// inbox changes.
+pub async fn dry_run_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<DryRunRequest, CODE_BODY_LIMIT>,
+) -> impl IntoResponse {
+    let snapshot = {
+        let world = state.game_world.read().await;
+        match world.replay().at(payload.snapshot_tick) {
+            Some(snapshot) => snapshot.clone(),
+            None => {
+                let message = match world.replay().range() {
+                    Some((oldest, newest)) => format!("No snapshot for script tick {} (history covers ticks {} to {})", payload.snapshot_tick, oldest, newest),
+                    None => format!("No snapshot for script tick {} (history is empty)", payload.snapshot_tick),
+                };
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(DryRunResponse {
+                        success: false,
+                        message,
+                        error: None,
+                        logs: Vec::new(),
+                        commands: Vec::new(),
+                        cpu_ns: 0,
+                    })
+                );
+            }
+        }
+    };
+
+    let result = state.script_engine.dry_run(session.username, payload.code, snapshot).await;
+    let message = match &result.error {
+        Some(_) => format!("Script failed at script tick {}", payload.snapshot_tick),
+        None => format!("Dry run at script tick {}: {} commands", payload.snapshot_tick, result.commands.len()),
+    };
+    (
+        StatusCode::OK,
+        Json(DryRunResponse {
+            success: result.error.is_none(),
+            message,
+            error: result.error,
+            logs: result.logs,
+            commands: result.commands,
+            cpu_ns: result.cpu_ns,
+        })
+    )
+}
+
+/// Response with the caller's script execution statistics
+#[derive(Debug, Serialize)]
+pub struct ScriptStatsResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Execution time statistics (all zero if the script has not run yet)
+    pub stats: ScriptStats,
+}
+
+/// Handler to get the execution time statistics of the caller's script
+pub async fn script_stats_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+) -> impl IntoResponse {
+    let stats = state.script_engine.read().await
+        .player_stats(&session.username)
+        .cloned()
+        .unwrap_or_default();
+    Json(ScriptStatsResponse {
+        success: true,
+        message: format!("{} executions", stats.total_executions),
+        stats,
+    })
+}
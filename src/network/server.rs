@@ -2,14 +2,20 @@
 //! 
 //! Manages HTTP/WebSocket communication, REST API endpoints, and client connections.
 
+use std::collections::{BTreeMap, HashSet};
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use axum::{
-    extract::{State, WebSocketUpgrade},
+    extract::{ConnectInfo, Query, State, WebSocketUpgrade},
     response::{IntoResponse, Response},
-    routing::{get, post},
+    routing::{delete, get, patch, post},
     Router, Json,
-    http::{Request, StatusCode},
+    http::{header, HeaderMap, HeaderValue, Request, StatusCode},
     middleware::{self, Next},
 };
 use axum::extract::ws::{WebSocket, Message};
@@ -17,11 +23,34 @@ use tower_http::cors::{CorsLayer, Any};
 use tower_http::trace::TraceLayer;
 use serde::{Deserialize, Serialize};
 use futures_util::{SinkExt, StreamExt};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tracing::Instrument;
 
+use crate::config::{FeatureFlag, ServerConfig, SharedConfig, API_VERSION, MAINTENANCE_RETRY_AFTER_SECS};
+use crate::game::campaign::save_dir_from_env;
+use crate::game::clock::DayPhase;
+use crate::game::game_loop::{RunState, SimControl};
+use crate::game::lobby::LobbyManager;
+use crate::game::stats::{PlayerStat, SharedStats};
+use crate::game::tournament::TournamentManager;
+use crate::game::tech::TechStatus;
 use crate::game::world::World;
-use crate::scripting::sandbox::ScriptEngine;
-use crate::auth::AuthService;
-use crate::auth::models::{RegisterRequest, LoginRequest};
+use crate::game::zone::Zone;
+use crate::game::zone_map::ZoneReader;
+use crate::scripting::bundle::ScriptBundle;
+use crate::scripting::commands::BotCommand;
+use crate::scripting::js_runtime::ScriptLimits;
+use crate::scripting::errors::LoggedError;
+use crate::scripting::messaging::BotMessage;
+use crate::scripting::runtime::{create_runtime, ScriptLanguage};
+use crate::scripting::sanitizer::CodeSanitizer;
+use crate::scripting::typescript::GAME_API_TYPES;
+use crate::scripting::handle::ScriptEngineHandle;
+use crate::auth::{AuthService, UserFilter};
+use crate::auth::audit::{AuditAction, AuditEntry, AuditOutcome, AuditStore};
+use crate::auth::email::{EmailSender, NoEmailSender, SmtpEmailSender};
+use crate::auth::models::{AuthResponse, RegisterRequest, LoginRequest, Session};
+use crate::network::extract::{AUTH_BODY_LIMIT, CODE_BODY_LIMIT, ApiError, AuthSession, SizedJson};
 use crate::network::campaign_routes::{
     start_run_handler,
     get_run_state_handler,
@@ -33,88 +62,699 @@ use crate::network::campaign_routes::{
 use crate::network::zone_routes::{
     generate_zone_handler,
     get_zone_handler,
+    export_zone_handler,
+    import_zone_handler,
+    edit_zone_tiles_handler,
+    edit_zone_exits_handler,
     list_zones_handler,
+    zone_owner_handler,
+    zone_tiles_handler,
+    capture_zone_handler,
+    my_zone_handler,
+    ensure_user_zone,
 };
+use crate::network::compression::compression_middleware;
+use crate::network::etag::conditional_json;
+use crate::network::zone_cache::ZoneCache;
+use crate::network::ip_filter::{IpFilter, IpFilterLayer};
+use crate::network::connection_limit::{ConnectionCounts, ConnectionSlot};
+use crate::network::lobby_routes::{
+    create_lobby_handler,
+    list_lobbies_handler,
+    join_lobby_handler,
+    leave_lobby_handler,
+    start_lobby_handler,
+};
+use crate::network::admin_routes::{
+    audit_log_handler,
+    create_invite_handler,
+    disable_maintenance_handler,
+    enable_maintenance_handler,
+    get_config_handler,
+    list_invites_handler,
+    list_users_handler,
+    pause_sim_handler,
+    reload_config_handler,
+    resume_sim_handler,
+    revoke_invite_handler,
+    step_sim_handler,
+};
+use crate::network::alliance_routes::{
+    accept_alliance_handler,
+    create_alliance_handler,
+    invite_to_alliance_handler,
+    leave_alliance_handler,
+};
+use crate::network::script_routes::{
+    approve_library_handler,
+    delete_module_handler,
+    dry_run_handler,
+    list_libraries_handler,
+    list_modules_handler,
+    publish_library_handler,
+    script_stats_handler,
+    submit_module_handler,
+};
+use crate::network::team_routes::{
+    create_team_handler,
+    invite_to_team_handler,
+    leave_team_handler,
+    team_status_handler,
+};
+use crate::network::stats_routes::{player_stats_handler, zone_stats_handler};
+use crate::network::tournament_routes::{
+    create_tournament_handler,
+    get_tournament_handler,
+    match_replay_handler,
+    start_tournament_handler,
+    tournament_status_handler,
+};
+use crate::network::world_routes::{
+    create_portal_handler,
+    delete_portal_handler,
+    generate_bulk_handler,
+    map_handler,
+    schedule_weather_handler,
+    world_config_handler,
+};
+use crate::network::pagination::{PaginatedResponse, PaginationQuery};
+use crate::network::tls;
+use crate::network::shutdown;
+use crate::network::ws_clients::{ClientRegistration, WsClients};
+use crate::network::error_feed::ErrorFeed;
+use crate::network::chat::{self, ChatHistory};
+use crate::network::achievement_routes::my_achievements_handler;
+use crate::network::event_routes::events_handler;
+use crate::network::history_routes::{history_game_state_handler, history_zone_handler};
+use crate::network::announcement_routes::{announce_handler, list_announcements_handler, Announcements};
+use crate::network::friend_routes::{
+    accept_friend_handler,
+    list_friends_handler,
+    remove_friend_handler,
+    request_friend_handler,
+};
+use crate::network::market_routes::{cancel_order_handler, list_orders_handler, place_order_handler};
+use crate::network::email_routes::{request_verification_handler, send_verification, verify_email_handler};
+use crate::network::health_routes::{live_handler, ready_handler};
+use crate::network::oauth_routes::{oauth_callback_handler, oauth_start_handler};
+use crate::auth::oauth::OAuth;
+use crate::network::ws_codec::{self, Outgoing, WireEncoding};
+use crate::network::spectator::{self, SpectateTarget, SpectatorStream, ZoneSnapshots, SPECTATOR_ALLOWED_COMMANDS};
 
 /// Shared application state
 #[derive(Clone)]
 pub struct AppState {
+    /// Shared game world
     pub game_world: Arc<RwLock<World>>,
-    pub script_engine: Arc<RwLock<ScriptEngine>>,
+    /// Zones of the game world, read one zone at a time without the world lock
+    ///
+    /// Taken from the world when the state is created: if the whole world is replaced,
+    /// [`AppState::read_zone`] goes through the world lock instead.
+    pub zones: ZoneReader,
+    /// Shared scripting engine
+    pub script_engine: ScriptEngineHandle,
+    /// Authentication service
     pub auth_service: Arc<AuthService>,
+    /// Open authenticated WebSocket connections per user ID
+    pub connected_ws_per_user: ConnectionCounts,
+    /// Authenticated WebSocket connections, for pushing messages to users
+    pub ws_clients: Arc<WsClients>,
+    /// Multiplayer lobbies
+    pub lobby_manager: Arc<RwLock<LobbyManager>>,
+    /// Last zone state sent to each zone spectator stream, for delta frames
+    pub zone_snapshots: ZoneSnapshots,
+    /// Bot tournaments
+    pub tournaments: Arc<RwLock<TournamentManager>>,
+    /// Usernames allowed to use admin endpoints
+    pub admin_users: Arc<HashSet<String>>,
+    /// Whether only admins are served (see [`ServerConfig::maintenance_mode`])
+    pub maintenance_mode: Arc<AtomicBool>,
+    /// Recent chat messages of each channel, for clients that join later
+    pub chat_history: Arc<ChatHistory>,
+    /// Recent announcements, for clients that connect later
+    pub announcements: Arc<Announcements>,
+    /// Current settings, replaced by `POST /api/admin/config/reload`
+    pub config: SharedConfig,
+    /// File the settings were loaded from, read again on reload
+    pub config_path: Option<String>,
+    /// Pause / resume / step control of the game loop
+    pub sim_control: SimControl,
+    /// Player and zone statistics, fed by the game loop
+    pub stats: SharedStats,
+    /// Client address allowlist and blocklist, replaced when the configuration is reloaded
+    pub ip_filter: Arc<IpFilter>,
+    /// Serialized zone responses and their ETags, by zone version
+    pub zone_cache: ZoneCache,
+    /// Identity providers players can sign in with
+    pub oauth: Arc<OAuth>,
+    /// Directory campaign runs are saved to, checked by `/api/health/ready`
+    pub save_dir: PathBuf,
+    /// Delivers verification emails
+    pub email_sender: Arc<dyn EmailSender>,
+    /// Write-once trail of logins, logouts, code submissions and admin actions (the auth
+    /// service's store, see [`AuthService::with_audit_store`])
+    pub audit: Arc<dyn AuditStore>,
+}
+
+impl AppState {
+    /// Create application state serving `world`, with the settings of the environment
+    /// (see [`ServerConfig::from_env`])
+    pub fn new(
+        world: World,
+        script_engine: ScriptEngineHandle,
+        auth_service: Arc<AuthService>,
+    ) -> Self {
+        let zones = world.zone_reader();
+        Self::with_config(Arc::new(RwLock::new(world)), zones, script_engine, auth_service, ServerConfig::from_env())
+    }
+
+    /// Create application state with the given settings
+    ///
+    /// `zones` is the [`World::zone_reader`] of the game world, taken before it was shared.
+    pub fn with_config(
+        game_world: Arc<RwLock<World>>,
+        zones: ZoneReader,
+        script_engine: ScriptEngineHandle,
+        auth_service: Arc<AuthService>,
+        config: ServerConfig,
+    ) -> Self {
+        let ip_filter = IpFilter::new(&config.ip_allowlist, &config.ip_blocklist).unwrap_or_else(|e| {
+            log::error!("❌ Invalid IP filter, serving every address: {}", e);
+            IpFilter::default()
+        });
+        let email_sender: Arc<dyn EmailSender> = match SmtpEmailSender::from_config(&config) {
+            Ok(Some(sender)) => Arc::new(sender),
+            Ok(None) => Arc::new(NoEmailSender),
+            Err(e) => {
+                log::error!("❌ Email disabled: {}", e);
+                Arc::new(NoEmailSender)
+            }
+        };
+        let audit = auth_service.audit_store();
+        AppState {
+            game_world,
+            zones,
+            script_engine,
+            auth_service,
+            connected_ws_per_user: ConnectionCounts::default(),
+            ws_clients: Arc::new(WsClients::new()),
+            lobby_manager: Arc::new(RwLock::new(LobbyManager::new())),
+            zone_snapshots: ZoneSnapshots::default(),
+            tournaments: Arc::new(RwLock::new(TournamentManager::new(config.tournament_max_ticks.max(1)))),
+            admin_users: Arc::new(config.admin_users.iter().cloned().collect()),
+            maintenance_mode: Arc::new(AtomicBool::new(config.maintenance_mode)),
+            chat_history: Arc::new(ChatHistory::new()),
+            announcements: Arc::new(Announcements::new()),
+            oauth: Arc::new(OAuth::from_config(&config)),
+            zone_cache: ZoneCache::new(config.zone_cache_size),
+            config: Arc::new(std::sync::RwLock::new(config)),
+            config_path: None,
+            sim_control: SimControl::new(),
+            stats: SharedStats::default(),
+            ip_filter: Arc::new(ip_filter),
+            save_dir: save_dir_from_env(),
+            email_sender,
+            audit,
+        }
+    }
+
+    /// Run `read` on a zone of the game world (`None` if the zone does not exist)
+    ///
+    /// Only that zone is locked, not the world, unless the reader is stale (see
+    /// [`ZoneReader::is_stale`]).
+    pub async fn read_zone<R>(&self, zone_id: &str, read: impl FnOnce(&Zone) -> R) -> Option<R> {
+        if self.zones.is_stale() {
+            return self.game_world.read().await.with_zone_read(zone_id, read);
+        }
+        self.zones.with_zone_read(zone_id, read)
+    }
+
+    /// IDs of every zone of the game world, sorted (without the world lock, like [`AppState::read_zone`])
+    pub async fn zone_ids(&self) -> Vec<String> {
+        if self.zones.is_stale() {
+            return self.game_world.read().await.get_zone_ids();
+        }
+        self.zones.ids()
+    }
+
+    /// Run an auth service call on the blocking pool
+    ///
+    /// Its database calls block, and retry transient failures after a pause (see
+    /// [`crate::utils::retry`]), so they must not hold up the async workers.
+    pub async fn call_auth<T: Send + 'static>(&self, call: impl FnOnce(&AuthService) -> T + Send + 'static) -> Result<T, String> {
+        let auth_service = self.auth_service.clone();
+        tokio::task::spawn_blocking(move || call(&auth_service))
+            .await
+            .map_err(|e| format!("Authentication call failed: {}", e))
+    }
+
+    /// Session of a token, if it is valid (checked on the blocking pool, see [`AppState::call_auth`])
+    pub async fn validate_token(&self, token: &str) -> Option<Session> {
+        let token = token.to_string();
+        self.call_auth(move |auth_service| auth_service.validate_token(&token)).await.ok().flatten()
+    }
+
+    /// Whether a user may use the admin endpoints
+    pub fn is_admin(&self, username: &str) -> bool {
+        self.admin_users.contains(username)
+    }
+
+    /// Whether a gameplay feature is enabled in the current settings
+    pub fn is_feature_enabled(&self, flag: FeatureFlag) -> bool {
+        self.config.read().unwrap().features.is_enabled(flag)
+    }
+
+    /// Whether the server is in maintenance mode
+    pub fn in_maintenance(&self) -> bool {
+        self.maintenance_mode.load(Ordering::Relaxed)
+    }
+
+    /// Whether maintenance mode turns a user away (everyone but admins)
+    pub fn refused_by_maintenance(&self, username: &str) -> bool {
+        self.in_maintenance() && !self.is_admin(username)
+    }
+
+    /// Turn maintenance mode on or off, in the flag and in the current settings
+    pub fn set_maintenance(&self, enabled: bool) {
+        self.config.write().unwrap().maintenance_mode = enabled;
+        self.maintenance_mode.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Append an entry to the audit log
+    ///
+    /// Failures are logged: the action being audited has already happened.
+    pub fn audit(&self, entry: AuditEntry) {
+        if let Err(e) = self.audit.append_audit(&entry) {
+            log::warn!("Failed to record {} by {} in the audit log: {}", entry.action.as_str(), entry.user, e);
+        }
+    }
+
+    /// A copy of the current settings
+    pub fn config(&self) -> ServerConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Replace the settings and apply them to the world, scripting engine and tournaments
+    ///
+    /// Restart-only fields must already be kept (see [`ServerConfig::reloaded`]).
+    pub async fn apply_config(&self, config: ServerConfig) {
+        self.game_world.write().await.set_config(config.world_config());
+        {
+            let mut engine = self.script_engine.write().await;
+            engine.set_inbox_limit(config.inbox_limit);
+            engine.set_max_concurrent_scripts(config.max_concurrent_scripts);
+            engine.set_script_queue_timeout(Duration::from_millis(config.script_queue_timeout_ms));
+            engine.set_allies_only(config.messages_allies_only);
+            engine.set_min_api_version(config.min_api_version);
+        }
+        self.tournaments.write().await.set_max_ticks(config.tournament_max_ticks.max(1));
+        self.maintenance_mode.store(config.maintenance_mode, Ordering::Relaxed);
+        if let Err(e) = self.ip_filter.set(&config.ip_allowlist, &config.ip_blocklist) {
+            log::error!("❌ Keeping the previous IP filter: {}", e);
+        }
+        *self.config.write().unwrap() = config;
+    }
 }
 
 /// Request to submit player code
-#[derive(Debug, Deserialize)]
+///
+/// Exactly one of `code` (legacy single script) or `modules` (multi-file bundle) is required.
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CodeSubmission {
-    pub code: String,
+    /// Player code
+    #[serde(default)]
+    pub code: Option<String>,
+    /// Bundle modules (module name -> source), must include `main.js`
+    #[serde(default)]
+    pub modules: Option<BTreeMap<String, String>>,
+    /// Script language: "javascript" (default), "typescript", "lua", or "wasm"
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Bot API version the code is written for (default: the `@api` pragma of the entry
+    /// module, or the latest version)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<u32>,
+}
+
+impl CodeSubmission {
+    /// Convert the submission into a validated bundle
+    pub fn into_bundle(self) -> Result<ScriptBundle, String> {
+        let language = match &self.language {
+            Some(name) => ScriptLanguage::from_name(name)?,
+            None => ScriptLanguage::default(),
+        };
+
+        let bundle = match (self.code, self.modules) {
+            (Some(code), None) => ScriptBundle::single(code)?,
+            (None, Some(modules)) => ScriptBundle::from_modules(modules)?,
+            _ => return Err("Submission requires exactly one of code or modules".to_string()),
+        };
+        match self.api_version {
+            Some(version) => bundle.with_language(language).with_api_version(version),
+            None => Ok(bundle.with_language(language)),
+        }
+    }
 }
 
 /// Response after code submission
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CodeSubmissionResponse {
+    /// Whether the operation succeeded
     pub success: bool,
+    /// Response message
     pub message: String,
 }
 
-/// Response for getting player code
+/// Response for a dry-run code validation
 #[derive(Debug, Serialize)]
+pub struct ValidationResponse {
+    /// Whether the code parsed and survived one simulated tick
+    pub valid: bool,
+    /// Syntax error, runtime exception (with stack trace), or timeout
+    pub error: Option<String>,
+    /// Console output of the simulated tick
+    pub logs: Vec<String>,
+    /// Commands the script would have issued
+    pub commands: Vec<BotCommand>,
+}
+
+impl ValidationResponse {
+    fn rejected(error: String) -> Self {
+        ValidationResponse {
+            valid: false,
+            error: Some(error),
+            logs: Vec::new(),
+            commands: Vec::new(),
+        }
+    }
+}
+
+/// Response for getting player code
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PlayerCodeResponse {
+    /// Player identifier
     pub player_id: String,
+    /// Player code (entry module)
     pub code: Option<String>,
+    /// All modules of the player's bundle
+    pub modules: Option<BTreeMap<String, String>>,
+    /// Language of the player's bundle
+    pub language: Option<ScriptLanguage>,
+    /// Bot API version the player's bundle is bound to
+    #[serde(default)]
+    pub api_version: Option<u32>,
 }
 
-/// Response for listing players
+/// Response for the caller's message inbox
 #[derive(Debug, Serialize)]
-pub struct PlayersListResponse {
-    pub players: Vec<String>,
+pub struct MessagesResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Delivered, unread messages, oldest first
+    pub messages: Vec<BotMessage>,
+}
+
+/// Query parameters of `GET /api/players`
+#[derive(Debug, Default, Deserialize)]
+pub struct PlayersQuery {
+    /// Only players whose username starts with this, ignoring case
+    pub q: Option<String>,
+    /// `profiles` (default), or `ids` for the usernames of players with submitted code
+    pub format: Option<String>,
+    /// Page number, from 1 (default 1)
+    pub page: Option<u32>,
+    /// Players per page
+    pub per_page: Option<u32>,
+}
+
+/// Public profile of a registered player
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlayerProfile {
+    /// Username
+    pub username: String,
+    /// Account creation timestamp (Unix epoch)
+    pub created_at: i64,
+    /// Whether the player has submitted code
+    pub has_code: bool,
+    /// Number of zones where the player owns entities
+    pub zones: usize,
+    /// Whether the player has a WebSocket connection or an unexpired session
+    pub online: bool,
+    /// Game statistics, once the player has been seen in play
+    pub stats: Option<PlayerStat>,
 }
 
 /// Game state response
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GameStateResponse {
+    /// Current simulation tick
     pub tick: u64,
+    /// Current script tick (the tick scripts see as `game.tick`)
+    pub script_tick: u64,
+    /// Current phase of the day
+    pub day_phase: DayPhase,
+    /// Usernames of players with submitted code
     pub players: Vec<String>,
+    /// Whether the game loop is running, paused, or stepping
+    pub run_state: RunState,
+    /// Techs of the authenticated player
+    pub tech: TechStatus,
+    /// Number of errors the authenticated player's script raised during the latest script tick
+    pub errors_last_tick: usize,
+    /// Latest distinct errors of the authenticated player's script, oldest first
+    pub errors: Vec<LoggedError>,
+    /// Hash of the world's state (see [`World::state_hash`]), only when the world is deterministic
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_hash: Option<u64>,
+}
+
+/// What the server shares with the rest of the process (see [`start_server`])
+pub struct ServerParts {
+    /// Shared game world
+    pub game_world: Arc<RwLock<World>>,
+    /// Zone reader of the game world (see [`World::zone_reader`]), taken before it was shared
+    pub zones: ZoneReader,
+    /// Shared scripting engine
+    pub script_engine: ScriptEngineHandle,
+    /// Authentication service
+    pub auth_service: Arc<AuthService>,
+    /// Current settings, shared with the game loop
+    pub config: SharedConfig,
+    /// File the settings were loaded from, read again on reload
+    pub config_path: Option<String>,
+    /// Pause / resume / step control of the game loop
+    pub sim_control: SimControl,
+    /// Player and zone statistics, fed by the game loop
+    pub stats: SharedStats,
 }
 
 /// Start the Axum HTTP and WebSocket server
+///
+/// Runs until `shutdown` completes, then closes the WebSocket connections (see
+/// [`shutdown::drain_websockets`]).
 pub async fn start_server(
-    game_world: Arc<RwLock<World>>, 
-    script_engine: Arc<RwLock<ScriptEngine>>,
-    auth_service: Arc<AuthService>,
+    parts: ServerParts,
+    shutdown: impl Future<Output = ()> + Send + 'static,
 ) -> anyhow::Result<()> {
-    let app_state = AppState {
-        game_world,
-        script_engine,
-        auth_service,
+    let ServerParts { game_world, zones, script_engine, auth_service, config, config_path, sim_control, stats } = parts;
+    let server_config = config.read().unwrap().clone();
+    // Bind to address
+    let addr = format!("{}:{}", server_config.host, server_config.port);
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    let tls = server_config.tls_paths().and_then(|(cert_path, key_path)| {
+        match tls::get_tls_config(&cert_path, &key_path) {
+            Ok(tls) => Some(tls),
+            Err(e) => {
+                log::error!("❌ TLS disabled: {}", e);
+                log::warn!("⚠️  Falling back to plain HTTP");
+                None
+            }
+        }
+    });
+    let (http, ws) = if tls.is_some() { ("https", "wss") } else { ("http", "ws") };
+    let control_socket = server_config.control_socket.clone();
+
+    let mut app_state = AppState::with_config(game_world, zones, script_engine, auth_service, server_config);
+    app_state.config = config;
+    app_state.config_path = config_path;
+    app_state.sim_control = sim_control;
+    app_state.stats = stats;
+    if let Some(path) = control_socket {
+        #[cfg(unix)]
+        match crate::network::control::bind(&path) {
+            Ok(listener) => {
+                log::info!("✓ Control socket listening on {}", path.display());
+                tokio::spawn(crate::network::control::serve(listener, app_state.clone()));
+            }
+            Err(e) => log::error!("❌ Control socket {} disabled: {}", path.display(), e),
+        }
+        #[cfg(not(unix))]
+        log::warn!("⚠️  Control socket {} ignored: Unix sockets are not supported on this platform", path.display());
+    }
+    #[cfg(feature = "redis_backend")]
+    let _pubsub = {
+        use crate::network::pubsub::{self, PubSubBridge, Role};
+        let config = app_state.config();
+        let redis_url = pubsub::redis_url_from_env();
+        let bridge = match Role::from_env() {
+            Role::Sim => PubSubBridge::start_sim(
+                &redis_url,
+                app_state.game_world.clone(),
+                app_state.ws_clients.clone(),
+                Duration::from_millis(1000 / u64::from(config.ticks_per_second.max(1))),
+                config.keyframe_interval_secs * u64::from(config.ticks_per_second),
+            ),
+            Role::Edge => PubSubBridge::start_edge(&redis_url, app_state.ws_clients.clone()),
+        };
+        match bridge {
+            Ok(bridge) => {
+                log::info!("✓ Redis pub/sub bridge started ({:?} instance, {})", bridge.role(), redis_url);
+                Some(bridge)
+            }
+            Err(e) => {
+                log::error!("❌ Redis pub/sub bridge disabled: {}", e);
+                None
+            }
+        }
     };
+    let ws_clients = app_state.ws_clients.clone();
+    let app = create_router(app_state);
+    
+    log::info!("✓ Axum server listening on {}://{}", http, addr);
+    log::info!("✓ WebSocket endpoint: {}://{}/ws", ws, addr);
+    log::info!("✓ API endpoints (also served under /api/{}; unversioned /api paths are deprecated):", API_VERSION);
+    log::info!("  - GET  /");
+    log::info!("  - GET  /api/health (alias of /api/health/ready)");
+    log::info!("  - GET  /api/health/live");
+    log::info!("  - GET  /api/health/ready");
+    log::info!("  - GET  /api/scripting/types.d.ts");
+    log::info!("  - POST /api/auth/register");
+    log::info!("  - POST /api/auth/login");
+    log::info!("  - POST /api/auth/oauth/:provider/start");
+    log::info!("  - GET  /api/auth/oauth/:provider/callback");
+    log::info!("  - POST /api/auth/logout (requires auth)");
+    log::info!("  - POST /api/auth/verify-email (requires auth)");
+    log::info!("  - GET  /api/auth/verify-email");
+    log::info!("  - POST /api/submit (requires auth)");
+    log::info!("  - GET  /api/code (requires auth)");
+    log::info!("  - GET  /api/scripts/modules (requires auth)");
+    log::info!("  - POST /api/scripts/modules (requires auth)");
+    log::info!("  - DELETE /api/scripts/modules/:name (requires auth)");
+    log::info!("  - GET  /api/scripts/libraries (requires auth)");
+    log::info!("  - POST /api/scripts/libraries (requires auth)");
+    log::info!("  - POST /api/scripts/dryrun (requires auth)");
+    log::info!("  - GET  /api/scripts/stats (requires auth)");
+    log::info!("  - GET  /api/messages (requires auth)");
+    log::info!("  - POST /api/validate (requires auth)");
+    log::info!("  - GET  /api/players?page=&per_page=&q=&format= (requires auth)");
+    log::info!("  - GET  /api/gamestate (requires auth)");
+    log::info!("  - GET  /api/world/config (requires auth)");
+    log::info!("  - GET  /api/map (requires auth)");
+    log::info!("  - GET  /api/lobbies (requires auth)");
+    log::info!("  - POST /api/lobbies/create (requires auth)");
+    log::info!("  - POST /api/lobbies/:id/join (requires auth)");
+    log::info!("  - POST /api/lobbies/:id/leave (requires auth)");
+    log::info!("  - POST /api/lobbies/:id/start (requires auth)");
+    log::info!("  - POST /api/teams/create (requires auth)");
+    log::info!("  - POST /api/teams/:id/invite/:user_id (requires auth)");
+    log::info!("  - POST /api/teams/:id/leave (requires auth)");
+    log::info!("  - GET  /api/teams/:id/status (requires auth)");
+    log::info!("  - POST /api/alliance/create (requires auth)");
+    log::info!("  - POST /api/alliance/invite (requires auth)");
+    log::info!("  - POST /api/alliance/accept (requires auth)");
+    log::info!("  - POST /api/alliance/leave (requires auth)");
+    log::info!("  - GET  /api/achievements/me (requires auth)");
+    log::info!("  - GET  /api/events?since_tick=&limit= (requires auth)");
+    log::info!("  - GET  /api/history/gamestate?tick= (requires auth)");
+    log::info!("  - GET  /api/history/zone/:zone_id?tick= (requires auth)");
+    log::info!("  - GET  /api/announcements (requires auth)");
+    log::info!("  - GET  /api/friends (requires auth)");
+    log::info!("  - POST /api/friends/request/:username (requires auth)");
+    log::info!("  - POST /api/friends/accept/:username (requires auth)");
+    log::info!("  - DELETE /api/friends/:username (requires auth)");
+    log::info!("  - POST /api/market/order (requires auth)");
+    log::info!("  - GET  /api/market/orders (requires auth)");
+    log::info!("  - DELETE /api/market/orders/:id (requires auth)");
+    log::info!("  - GET  /api/stats/players/:username (requires auth)");
+    log::info!("  - GET  /api/stats/zones/:id (requires auth)");
+    log::info!("  - GET  /api/tournament/:id (requires auth)");
+    log::info!("  - GET  /api/tournament/:id/matches/:index/replay (requires auth)");
+    log::info!("  - GET  /api/admin/users (requires admin)");
+    log::info!("  - POST /api/admin/invites (requires admin)");
+    log::info!("  - GET  /api/admin/invites (requires admin)");
+    log::info!("  - DELETE /api/admin/invites/:code (requires admin)");
+    log::info!("  - GET  /api/admin/audit?user=&action=&since=&page=&per_page= (requires admin)");
+    log::info!("  - POST /api/admin/sim/pause (requires admin)");
+    log::info!("  - POST /api/admin/sim/resume (requires admin)");
+    log::info!("  - POST /api/admin/sim/step (requires admin)");
+    log::info!("  - GET  /api/admin/config (requires admin)");
+    log::info!("  - POST /api/admin/config/reload (requires admin)");
+    log::info!("  - POST /api/admin/maintenance/enable (requires admin)");
+    log::info!("  - POST /api/admin/maintenance/disable (requires admin)");
+    log::info!("  - POST /api/admin/announce (requires admin)");
+    log::info!("  - POST /api/admin/tournament (requires admin)");
+    log::info!("  - POST /api/admin/tournament/start (requires admin)");
+    log::info!("  - GET  /api/admin/tournament/:id/status (requires admin)");
+    log::info!("  - POST /api/admin/world/portals (requires admin)");
+    log::info!("  - DELETE /api/admin/world/portals/:id (requires admin)");
+    log::info!("  - POST /api/admin/world/weather (requires admin)");
+    log::info!("  - POST /api/admin/world/generate-bulk (requires admin)");
+    log::info!("  - PATCH /api/admin/zone/:zone_id/tiles (requires admin)");
+    log::info!("  - PATCH /api/admin/zone/:zone_id/exits (requires admin)");
+    log::info!("  - POST /api/admin/scripts/libraries/:id/approve (requires admin)");
+    log::info!("  - POST /api/campaign/start");
+    log::info!("  - GET  /api/campaign/state");
+    log::info!("  - POST /api/campaign/stop");
+    log::info!("  - POST /api/campaign/save");
+    log::info!("  - GET  /api/campaign/saves");
+    log::info!("  - POST /api/campaign/load");
+    log::info!("  - POST /api/zone/generate");
+    log::info!("  - GET  /api/zone/mine (requires auth)");
+    log::info!("  - GET  /api/zone/:zone_id");
+    log::info!("  - GET  /api/zone/:zone_id/export");
+    log::info!("  - POST /api/zone/import (admin only)");
+    log::info!("  - GET  /api/zones?page=&per_page=");
+    log::info!("  - GET  /api/zones/:zone_id/tiles?cursor=&limit=");
+    log::info!("  - GET  /api/zones/:zone_id/owner");
+    log::info!("  - POST /api/zones/:zone_id/capture (requires auth)");
 
-    // Build the router with all endpoints
-    let app = Router::new()
+    // Start the server, until the shutdown signal
+    tls::serve_with_shutdown(listener, app, tls, shutdown).await?;
+    log::info!("✓ HTTP server stopped");
+
+    let remaining = shutdown::drain_websockets(&ws_clients, shutdown::WS_DRAIN_TIMEOUT).await;
+    if remaining == 0 {
+        log::info!("✓ WebSocket connections closed");
+    } else {
+        log::warn!("⚠️  {} WebSocket connections still open after {:?}", remaining, shutdown::WS_DRAIN_TIMEOUT);
+    }
+    
+    Ok(())
+}
+
+/// Build the router with all endpoints and middleware
+///
+/// The API is served both under the versioned prefix (`/api/v1`) and, for backward
+/// compatibility, under the deprecated unversioned prefix (`/api`).
+pub fn create_router(app_state: AppState) -> Router {
+    let versioned_prefix = format!("/api/{}", API_VERSION);
+    let ip_filter = app_state.ip_filter.clone();
+    
+    Router::new()
         // Public endpoints (no auth required)
         .route("/", get(root_handler))
-        .route("/api/health", get(health_handler))
-        .route("/api/auth/register", post(register_handler))
-        .route("/api/auth/login", post(login_handler))
-        // Campaign endpoints (no auth required for now)
-        .route("/api/campaign/start", post(start_run_handler))
-        .route("/api/campaign/state", get(get_run_state_handler))
-        .route("/api/campaign/stop", post(stop_run_handler))
-        .route("/api/campaign/save", post(save_run_handler))
-        .route("/api/campaign/saves", get(list_saves_handler))
-        .route("/api/campaign/load", post(load_run_handler))
-        // Zone endpoints (no auth required for now)
-        .route("/api/zone/generate", post(generate_zone_handler))
-        .route("/api/zone/:zone_id", get(get_zone_handler))
-        .route("/api/zones", get(list_zones_handler))
-        // Protected endpoints (auth required)
-        .route("/api/auth/logout", post(logout_handler))
-        .route("/api/submit", post(submit_code_handler))
-        .route("/api/players", get(list_players_handler))
-        .route("/api/gamestate", get(game_state_handler))
+        .nest(&versioned_prefix, api_routes(&app_state))
+        .nest("/api", api_routes(&app_state))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), audit_middleware))
         .route_layer(middleware::from_fn_with_state(app_state.clone(), auth_middleware))
+        // Log API version and flag deprecated unversioned paths
+        .route_layer(middleware::from_fn(api_version_middleware))
         // WebSocket endpoint
         .route("/ws", get(websocket_handler))
+        // Compress large responses (WebSocket upgrades are skipped)
+        .layer(middleware::from_fn_with_state(app_state.clone(), compression_middleware))
         // Add state
         .with_state(app_state)
         // Add CORS middleware
@@ -127,74 +767,278 @@ pub async fn start_server(
                 .allow_methods(Any)
                 .allow_headers(Any)
         )
-        // Add tracing middleware
-        .layer(TraceLayer::new_for_http());
+        // Refuse filtered client addresses before anything else runs
+        .layer(IpFilterLayer::new(ip_filter))
+        // One `http_request` span per request (see `crate::logging`)
+        .layer(TraceLayer::new_for_http()
+            .make_span_with(request_span)
+            .on_response(record_response))
+}
 
-    // Bind to address
-    let addr = "0.0.0.0:3030";
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    
-    log::info!("✓ Axum server listening on http://{}", addr);
-    log::info!("✓ WebSocket endpoint: ws://{}/ws", addr);
-    log::info!("✓ API endpoints:");
-    log::info!("  - GET  /");
-    log::info!("  - GET  /api/health");
-    log::info!("  - POST /api/auth/register");
-    log::info!("  - POST /api/auth/login");
-    log::info!("  - POST /api/auth/logout (requires auth)");
-    log::info!("  - POST /api/submit (requires auth)");
-    log::info!("  - GET  /api/players (requires auth)");
-    log::info!("  - GET  /api/gamestate (requires auth)");
-    log::info!("  - POST /api/campaign/start");
-    log::info!("  - GET  /api/campaign/state");
-    log::info!("  - POST /api/campaign/stop");
-    log::info!("  - POST /api/campaign/save");
-    log::info!("  - GET  /api/campaign/saves");
-    log::info!("  - POST /api/campaign/load");
-    log::info!("  - POST /api/zone/generate");
-    log::info!("  - GET  /api/zone/:zone_id");
-    log::info!("  - GET  /api/zones");
+/// Span of an HTTP request; `user` is recorded by the auth middleware
+fn request_span(request: &Request<axum::body::Body>) -> tracing::Span {
+    tracing::info_span!(
+        "http_request",
+        method = %request.method(),
+        path = request.uri().path(),
+        request_id = %uuid::Uuid::new_v4(),
+        user = tracing::field::Empty,
+        status = tracing::field::Empty,
+        duration_ms = tracing::field::Empty,
+        outcome = tracing::field::Empty,
+    )
+}
+
+/// Record the status, duration and outcome of a request on its span and log it
+fn record_response(response: &Response, latency: Duration, span: &tracing::Span) {
+    let status = response.status();
+    let outcome = if status.is_server_error() {
+        "server_error"
+    } else if status.is_client_error() {
+        "client_error"
+    } else {
+        "success"
+    };
+    let duration_ms = latency.as_secs_f64() * 1000.0;
+    span.record("status", status.as_u16());
+    span.record("duration_ms", duration_ms);
+    span.record("outcome", outcome);
+    tracing::info!(status = status.as_u16(), duration_ms, outcome, "request completed");
+}
+
+/// API routes, relative to the API prefix (`/api` or `/api/v1`)
+fn api_routes(app_state: &AppState) -> Router<AppState> {
+    let feature = |flag: FeatureFlag| middleware::from_fn_with_state((app_state.clone(), flag), feature_middleware);
+    Router::new()
+        // Public endpoints (no auth required)
+        .route("/health", get(ready_handler))
+        .route("/health/live", get(live_handler))
+        .route("/health/ready", get(ready_handler))
+        .route("/scripting/types.d.ts", get(scripting_types_handler))
+        .route("/auth/register", post(register_handler))
+        .route("/auth/login", post(login_handler))
+        .route("/auth/oauth/:provider/start", post(oauth_start_handler))
+        .route("/auth/oauth/:provider/callback", get(oauth_callback_handler))
+        // Campaign endpoints (no auth required for now)
+        .route("/campaign/start", post(start_run_handler))
+        .route("/campaign/state", get(get_run_state_handler))
+        .route("/campaign/stop", post(stop_run_handler))
+        .route("/campaign/save", post(save_run_handler))
+        .route("/campaign/saves", get(list_saves_handler))
+        .route("/campaign/load", post(load_run_handler))
+        // Zone endpoints (no auth required for now)
+        .route("/zone/generate", post(generate_zone_handler))
+        .route("/zone/mine", get(my_zone_handler))
+        .route("/zone/:zone_id", get(get_zone_handler))
+        .route("/zone/:zone_id/export", get(export_zone_handler))
+        .route("/zone/import", post(import_zone_handler))
+        .route("/zones", get(list_zones_handler))
+        .route("/zones/:zone_id/owner", get(zone_owner_handler))
+        .route("/zones/:zone_id/tiles", get(zone_tiles_handler))
+        .route("/zones/:zone_id/capture", post(capture_zone_handler))
+        // Protected endpoints (auth required)
+        .route("/auth/logout", post(logout_handler))
+        .route("/auth/verify-email", post(request_verification_handler).get(verify_email_handler))
+        .route("/submit", post(submit_code_handler))
+        .route("/code", get(get_code_handler))
+        .route("/scripts/modules", get(list_modules_handler).post(submit_module_handler))
+        .route("/scripts/modules/*name", delete(delete_module_handler))
+        .route("/scripts/libraries", get(list_libraries_handler).post(publish_library_handler))
+        .route("/scripts/dryrun", post(dry_run_handler))
+        .route("/scripts/stats", get(script_stats_handler))
+        .route("/messages", get(messages_handler))
+        .route("/validate", post(validate_code_handler))
+        .route("/players", get(list_players_handler))
+        .route("/gamestate", get(game_state_handler))
+        .route("/world/config", get(world_config_handler))
+        .route("/map", get(map_handler))
+        .route("/lobbies", get(list_lobbies_handler))
+        .route("/lobbies/create", post(create_lobby_handler))
+        .route("/lobbies/:lobby_id/join", post(join_lobby_handler))
+        .route("/lobbies/:lobby_id/leave", post(leave_lobby_handler))
+        .route("/lobbies/:lobby_id/start", post(start_lobby_handler))
+        .route("/teams/create", post(create_team_handler))
+        .route("/teams/:team_id/invite/:user_id", post(invite_to_team_handler))
+        .route("/teams/:team_id/leave", post(leave_team_handler))
+        .route("/teams/:team_id/status", get(team_status_handler))
+        .route("/alliance/create", post(create_alliance_handler))
+        .route("/alliance/invite", post(invite_to_alliance_handler))
+        .route("/alliance/accept", post(accept_alliance_handler))
+        .route("/alliance/leave", post(leave_alliance_handler))
+        .route("/achievements/me", get(my_achievements_handler))
+        .route("/events", get(events_handler))
+        .route("/history/gamestate", get(history_game_state_handler))
+        .route("/history/zone/:zone_id", get(history_zone_handler))
+        .route("/announcements", get(list_announcements_handler))
+        .route("/friends", get(list_friends_handler))
+        .route("/friends/request/:username", post(request_friend_handler))
+        .route("/friends/accept/:username", post(accept_friend_handler))
+        .route("/friends/:username", delete(remove_friend_handler))
+        .route("/market/order", post(place_order_handler))
+        .route("/market/orders", get(list_orders_handler))
+        .route("/market/orders/:order_id", delete(cancel_order_handler))
+        .route("/stats/players/:username", get(player_stats_handler))
+        .route("/stats/zones/:zone_id", get(zone_stats_handler))
+        .route("/tournament/:tournament_id", get(get_tournament_handler)
+            .route_layer(feature(FeatureFlag::Tournament)))
+        .route("/tournament/:tournament_id/matches/:match_index/replay", get(match_replay_handler)
+            .route_layer(feature(FeatureFlag::Tournament)))
+        // Admin endpoints (auth + admin required)
+        .route("/admin/users", get(list_users_handler))
+        .route("/admin/invites", post(create_invite_handler).get(list_invites_handler))
+        .route("/admin/invites/:code", delete(revoke_invite_handler))
+        .route("/admin/audit", get(audit_log_handler))
+        .route("/admin/sim/pause", post(pause_sim_handler))
+        .route("/admin/sim/resume", post(resume_sim_handler))
+        .route("/admin/sim/step", post(step_sim_handler))
+        .route("/admin/config", get(get_config_handler))
+        .route("/admin/config/reload", post(reload_config_handler))
+        .route("/admin/maintenance/enable", post(enable_maintenance_handler))
+        .route("/admin/maintenance/disable", post(disable_maintenance_handler))
+        .route("/admin/announce", post(announce_handler))
+        .route("/admin/tournament", post(create_tournament_handler)
+            .route_layer(feature(FeatureFlag::Tournament)))
+        .route("/admin/tournament/start", post(start_tournament_handler)
+            .route_layer(feature(FeatureFlag::Tournament)))
+        .route("/admin/tournament/:tournament_id/status", get(tournament_status_handler)
+            .route_layer(feature(FeatureFlag::Tournament)))
+        .route("/admin/world/portals", post(create_portal_handler)
+            .route_layer(feature(FeatureFlag::Portals)))
+        .route("/admin/world/portals/:portal_id", delete(delete_portal_handler)
+            .route_layer(feature(FeatureFlag::Portals)))
+        .route("/admin/world/weather", post(schedule_weather_handler)
+            .route_layer(feature(FeatureFlag::Weather)))
+        .route("/admin/world/generate-bulk", post(generate_bulk_handler))
+        .route("/admin/zone/:zone_id/tiles", patch(edit_zone_tiles_handler))
+        .route("/admin/zone/:zone_id/exits", patch(edit_zone_exits_handler))
+        .route("/admin/scripts/libraries/:library_id/approve", post(approve_library_handler))
+}
+
+/// Map a versioned API path (`/api/v1/...`) to its unversioned form (`/api/...`)
+///
+/// Other paths are returned unchanged.
+pub fn unversioned_path(path: &str) -> String {
+    let versioned_prefix = format!("/api/{}", API_VERSION);
+    match path.strip_prefix(&versioned_prefix) {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("/api{}", rest),
+        _ => path.to_string(),
+    }
+}
 
-    // Start the server
-    axum::serve(listener, app).await?;
+/// API version middleware
+///
+/// Logs which API version the client is using and adds a `Deprecation` header
+/// (plus a `Link` to the versioned successor) to responses from unversioned paths.
+async fn api_version_middleware(
+    request: Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_string();
+    let versioned = unversioned_path(&path) != path;
     
-    Ok(())
+    if !path.starts_with("/api") {
+        return next.run(request).await;
+    }
+    
+    log::debug!(
+        "API request {} {} (version: {})",
+        request.method(),
+        path,
+        if versioned { API_VERSION } else { "unversioned" }
+    );
+    
+    let mut response = next.run(request).await;
+    
+    if !versioned {
+        let successor = format!("</api/{}{}>; rel=\"successor-version\"", API_VERSION, path.trim_start_matches("/api"));
+        let headers = response.headers_mut();
+        headers.insert("Deprecation", HeaderValue::from_static("true"));
+        if let Ok(link) = HeaderValue::from_str(&successor) {
+            headers.insert("Link", link);
+        }
+    }
+    
+    response
+}
+
+/// Response to the requests of non-admin users during maintenance
+fn maintenance_response() -> Response {
+    let body = Json(serde_json::json!({"code": "maintenance", "message": "Server is under maintenance"}));
+    let mut response = (StatusCode::SERVICE_UNAVAILABLE, body).into_response();
+    response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(MAINTENANCE_RETRY_AFTER_SECS));
+    response
+}
+
+/// Answer the requests of a feature-gated endpoint with `501 Not Implemented` while its feature is disabled
+///
+/// Runs after [`auth_middleware`], so unauthenticated requests still get `401`.
+async fn feature_middleware(
+    State((state, flag)): State<(AppState, FeatureFlag)>,
+    request: Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    if state.is_feature_enabled(flag) {
+        return next.run(request).await;
+    }
+    let message = format!("The {} feature is disabled on this server", flag.name());
+    (StatusCode::NOT_IMPLEMENTED, Json(serde_json::json!({"code": "feature_disabled", "message": message}))).into_response()
 }
 
 /// Authentication middleware
+///
+/// In maintenance mode, requests carrying the token of a user who is not an admin get
+/// `503 Service Unavailable`, on public endpoints too; only the root, health checks and
+/// login stay open.
 async fn auth_middleware(
     State(state): State<AppState>,
     mut request: Request<axum::body::Body>,
     next: Next,
 ) -> Result<Response, StatusCode> {
-    // Skip auth for public endpoints
-    let path = request.uri().path();
+    let path = unversioned_path(request.uri().path());
+    let token = request
+        .headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|header| header.strip_prefix("Bearer "));
+
+    // Maintenance turns users away from public endpoints too, all but the root, health
+    // checks and login; anonymous requests to public endpoints still go through
+    let always_open = path == "/" || path == "/api/health" || path.starts_with("/api/health/") || path == "/api/auth/login";
+    let session = match token {
+        Some(token) if state.in_maintenance() && !always_open => state.validate_token(token).await,
+        _ => None,
+    };
+    if session.as_ref().is_some_and(|session| state.refused_by_maintenance(&session.username)) {
+        return Ok(maintenance_response());
+    }
+
+    // Skip auth for public endpoints (versioned paths are checked in their unversioned form)
     if path == "/" 
         || path == "/api/health" 
+        || path.starts_with("/api/health/")
         || path == "/api/auth/register" 
         || path == "/api/auth/login" 
+        || path.starts_with("/api/auth/oauth/")
+        || (path == "/api/auth/verify-email" && request.method() == axum::http::Method::GET)
+        || path == "/api/scripting/types.d.ts"
         || path == "/ws"
         || path.starts_with("/api/campaign/")
         || path.starts_with("/api/zone") {
         return Ok(next.run(request).await);
     }
     
-    // Get Authorization header
-    let auth_header = request
-        .headers()
-        .get("Authorization")
-        .and_then(|v| v.to_str().ok());
-    
-    let token = match auth_header {
-        Some(header) if header.starts_with("Bearer ") => {
-            header.trim_start_matches("Bearer ")
-        }
-        _ => return Err(StatusCode::UNAUTHORIZED),
+    let Some(token) = token else {
+        return Err(StatusCode::UNAUTHORIZED);
     };
     
-    // Validate token
-    match state.auth_service.validate_token(token) {
+    // Validate token (unless it was for maintenance)
+    let session = match session {
+        Some(session) => Some(session),
+        None => state.validate_token(token).await,
+    };
+    match session {
         Some(session) => {
+            tracing::Span::current().record("user", session.username.as_str());
             // Add user info to request extensions
             request.extensions_mut().insert(session);
             Ok(next.run(request).await)
@@ -203,38 +1047,124 @@ async fn auth_middleware(
     }
 }
 
+/// Record the changes made through admin endpoints in the audit log
+///
+/// Runs after [`auth_middleware`]: requests that reach it have a session. Reads are not
+/// recorded; refused changes (e.g. by a user who is not an admin) are, as failures.
+async fn audit_middleware(
+    State(state): State<AppState>,
+    request: Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    let path = unversioned_path(request.uri().path()).to_string();
+    if !path.starts_with("/api/admin/") || request.method() == axum::http::Method::GET {
+        return next.run(request).await;
+    }
+    let detail = format!("{} {}", request.method(), path);
+    let user = request.extensions().get::<Session>().map(|session| session.username.clone()).unwrap_or_default();
+    let ip = client_ip(request.extensions().get::<ConnectInfo<SocketAddr>>().cloned());
+
+    let response = next.run(request).await;
+    let status = response.status();
+    let detail = if status.is_success() { detail } else { format!("{} ({})", detail, status) };
+    state.audit(AuditEntry::new(&user, ip, AuditAction::Admin, AuditOutcome::of(status.is_success()), detail));
+    response
+}
+
+/// Address of the client (`None` if the server was not started with connection info)
+pub(crate) fn client_ip(connect_info: Option<ConnectInfo<SocketAddr>>) -> Option<IpAddr> {
+    connect_info.map(|ConnectInfo(addr)| addr.ip())
+}
+
+/// Response of a registration or login that could not run
+fn auth_failure(message: String) -> AuthResponse {
+    AuthResponse { success: false, message, token: None, username: None }
+}
+
 /// Register handler
 async fn register_handler(
     State(state): State<AppState>,
-    Json(payload): Json<RegisterRequest>,
+    headers: HeaderMap,
+    SizedJson(payload): SizedJson<RegisterRequest, AUTH_BODY_LIMIT>,
 ) -> impl IntoResponse {
-    let response = state.auth_service.register(&payload.username, &payload.password);
+    let mode = state.config().registration_mode;
+    let (username, password, invite_code) = (payload.username.clone(), payload.password.clone(), payload.invite_code.clone());
+    let mut response = state.call_auth(move |auth_service| auth_service.register(&username, &password, mode, invite_code.as_deref()))
+        .await
+        .unwrap_or_else(auth_failure);
+    if response.success {
+        assign_zone(&state, &payload.username).await;
+        if let Some(email) = payload.email.filter(|email| !email.trim().is_empty()) {
+            // The account exists either way; a failed email can be sent again with POST /api/auth/verify-email
+            let sent = match state.auth_service.get_user_by_username(&payload.username) {
+                Ok(Some(user)) => send_verification(&state, &headers, user.id, email).await,
+                Ok(None) => Err("User not found".to_string()),
+                Err(e) => Err(e),
+            };
+            response.message = match sent {
+                Ok(()) => format!("{}, check your email to verify it", response.message),
+                Err(e) => {
+                    log::warn!("Could not send a verification email to {}: {}", payload.username, e);
+                    format!("{}, but the verification email was not sent: {}", response.message, e)
+                }
+            };
+        }
+    }
     Json(response)
 }
 
 /// Login handler
+///
+/// Every attempt is audited with the username tried, never the password.
 async fn login_handler(
     State(state): State<AppState>,
-    Json(payload): Json<LoginRequest>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    SizedJson(payload): SizedJson<LoginRequest, AUTH_BODY_LIMIT>,
 ) -> impl IntoResponse {
-    let response = state.auth_service.login(&payload.username, &payload.password);
+    let (username, password) = (payload.username.clone(), payload.password.clone());
+    let response = state.call_auth(move |auth_service| auth_service.login(&username, &password))
+        .await
+        .unwrap_or_else(auth_failure);
+    let detail = if response.success { String::new() } else { response.message.clone() };
+    state.audit(AuditEntry::new(&payload.username, client_ip(connect_info), AuditAction::Login, AuditOutcome::of(response.success), detail));
+    if response.success {
+        assign_zone(&state, &payload.username).await;
+    }
     Json(response)
 }
 
+/// Base of the URLs players reach the server at: `public_url`, or else the host the request was sent to
+pub(crate) fn public_base_url(state: &AppState, headers: &HeaderMap) -> Result<String, String> {
+    let config = state.config();
+    if let Some(url) = config.public_url {
+        return Ok(url.trim_end_matches('/').to_string());
+    }
+    let host = headers.get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .ok_or("Set public_url to build links to the server")?;
+    let scheme = if config.tls_paths().is_some() { "https" } else { "http" };
+    Ok(format!("{}://{}", scheme, host))
+}
+
+/// Make sure a player who just registered or logged in has a zone
+///
+/// Failures (e.g. a full world) do not fail the request; `GET /api/zone/mine` retries.
+pub(crate) async fn assign_zone(state: &AppState, username: &str) {
+    match ensure_user_zone(state, username).await {
+        Ok(zone_id) => log::debug!("{} has zone {}", username, zone_id),
+        Err(err) => log::warn!("Could not assign a zone to {}: {}", username, err),
+    }
+}
+
 /// Logout handler
 async fn logout_handler(
     State(state): State<AppState>,
-    request: Request<axum::body::Body>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    AuthSession(session): AuthSession,
 ) -> impl IntoResponse {
-    // Get token from Authorization header
-    let token = request
-        .headers()
-        .get("Authorization")
-        .and_then(|v| v.to_str().ok())
-        .and_then(|v| v.strip_prefix("Bearer "))
-        .unwrap_or("");
-    
-    let response = state.auth_service.logout(token);
+    let response = state.auth_service.logout(&session.token);
+    let detail = if response.success { String::new() } else { response.message.clone() };
+    state.audit(AuditEntry::new(&session.username, client_ip(connect_info), AuditAction::Logout, AuditOutcome::of(response.success), detail));
     Json(response)
 }
 
@@ -243,123 +1173,325 @@ async fn root_handler() -> impl IntoResponse {
     Json(serde_json::json!({
         "name": "GeekCraft API Server",
         "version": env!("CARGO_PKG_VERSION"),
+        "api_version": API_VERSION,
+        "api_prefix": format!("/api/{}", API_VERSION),
+        "deprecated_prefix": "/api",
         "endpoints": {
-            "health": "GET /api/health",
+            "health": "GET /api/health (alias of /api/health/ready)",
+            "health_live": "GET /api/health/live",
+            "health_ready": "GET /api/health/ready",
+            "scripting_types": "GET /api/scripting/types.d.ts",
             "register": "POST /api/auth/register",
             "login": "POST /api/auth/login",
+            "oauth_start": "POST /api/auth/oauth/:provider/start",
+            "oauth_callback": "GET /api/auth/oauth/:provider/callback",
             "logout": "POST /api/auth/logout (requires auth)",
+            "request_email_verification": "POST /api/auth/verify-email (requires auth)",
+            "verify_email": "GET /api/auth/verify-email?token=...",
             "submit_code": "POST /api/submit (requires auth)",
-            "list_players": "GET /api/players (requires auth)",
+            "get_code": "GET /api/code (requires auth)",
+            "list_modules": "GET /api/scripts/modules (requires auth)",
+            "submit_module": "POST /api/scripts/modules (requires auth)",
+            "delete_module": "DELETE /api/scripts/modules/:name (requires auth)",
+            "list_libraries": "GET /api/scripts/libraries (requires auth)",
+            "publish_library": "POST /api/scripts/libraries (requires auth)",
+            "dry_run": "POST /api/scripts/dryrun (requires auth)",
+            "script_stats": "GET /api/scripts/stats (requires auth)",
+            "messages": "GET /api/messages (requires auth)",
+            "validate_code": "POST /api/validate (requires auth)",
+            "list_players": "GET /api/players?page=&per_page=&q=&format= (requires auth)",
             "game_state": "GET /api/gamestate (requires auth)",
+            "world_config": "GET /api/world/config (requires auth)",
+            "map": "GET /api/map (requires auth)",
+            "lobbies": "GET /api/lobbies (requires auth)",
+            "lobby_create": "POST /api/lobbies/create (requires auth)",
+            "lobby_join": "POST /api/lobbies/:id/join (requires auth)",
+            "lobby_leave": "POST /api/lobbies/:id/leave (requires auth)",
+            "lobby_start": "POST /api/lobbies/:id/start (requires auth)",
+            "team_create": "POST /api/teams/create (requires auth)",
+            "team_invite": "POST /api/teams/:id/invite/:user_id (requires auth)",
+            "team_leave": "POST /api/teams/:id/leave (requires auth)",
+            "team_status": "GET /api/teams/:id/status (requires auth)",
+            "alliance_create": "POST /api/alliance/create (requires auth)",
+            "alliance_invite": "POST /api/alliance/invite (requires auth)",
+            "alliance_accept": "POST /api/alliance/accept (requires auth)",
+            "alliance_leave": "POST /api/alliance/leave (requires auth)",
+            "achievements": "GET /api/achievements/me (requires auth)",
+            "events": "GET /api/events?since_tick=&limit= (requires auth)",
+            "history_game_state": "GET /api/history/gamestate?tick= (requires auth)",
+            "history_zone": "GET /api/history/zone/:zone_id?tick= (requires auth)",
+            "announcements": "GET /api/announcements (requires auth)",
+            "friends": "GET /api/friends (requires auth)",
+            "friend_request": "POST /api/friends/request/:username (requires auth)",
+            "friend_accept": "POST /api/friends/accept/:username (requires auth)",
+            "friend_remove": "DELETE /api/friends/:username (requires auth)",
+            "market_order": "POST /api/market/order (requires auth)",
+            "market_orders": "GET /api/market/orders (requires auth)",
+            "market_cancel": "DELETE /api/market/orders/:id (requires auth)",
+            "player_stats": "GET /api/stats/players/:username (requires auth)",
+            "zone_stats": "GET /api/stats/zones/:id (requires auth)",
+            "tournament": "GET /api/tournament/:id (requires auth)",
+            "tournament_replay": "GET /api/tournament/:id/matches/:index/replay (requires auth)",
+            "admin_users": "GET /api/admin/users (requires admin)",
+            "invite_create": "POST /api/admin/invites (requires admin)",
+            "invite_list": "GET /api/admin/invites (requires admin)",
+            "invite_revoke": "DELETE /api/admin/invites/:code (requires admin)",
+            "admin_audit": "GET /api/admin/audit?user=&action=&since=&page=&per_page= (requires admin)",
+            "sim_pause": "POST /api/admin/sim/pause (requires admin)",
+            "sim_resume": "POST /api/admin/sim/resume (requires admin)",
+            "sim_step": "POST /api/admin/sim/step (requires admin)",
+            "admin_config": "GET /api/admin/config (requires admin)",
+            "admin_config_reload": "POST /api/admin/config/reload (requires admin)",
+            "maintenance_enable": "POST /api/admin/maintenance/enable (requires admin)",
+            "maintenance_disable": "POST /api/admin/maintenance/disable (requires admin)",
+            "announce": "POST /api/admin/announce (requires admin)",
+            "tournament_create": "POST /api/admin/tournament (requires admin)",
+            "tournament_start": "POST /api/admin/tournament/start (requires admin)",
+            "tournament_status": "GET /api/admin/tournament/:id/status (requires admin)",
+            "portal_create": "POST /api/admin/world/portals (requires admin)",
+            "portal_delete": "DELETE /api/admin/world/portals/:id (requires admin)",
+            "weather_schedule": "POST /api/admin/world/weather (requires admin)",
+            "zone_generate_bulk": "POST /api/admin/world/generate-bulk (requires admin)",
+            "zone_edit_tiles": "PATCH /api/admin/zone/:id/tiles (requires admin)",
+            "zone_edit_exits": "PATCH /api/admin/zone/:id/exits (requires admin)",
+            "library_approve": "POST /api/admin/scripts/libraries/:id/approve (requires admin)",
             "websocket": "WS /ws",
             "campaign_start": "POST /api/campaign/start",
             "campaign_state": "GET /api/campaign/state",
             "campaign_stop": "POST /api/campaign/stop",
             "campaign_save": "POST /api/campaign/save",
             "campaign_saves": "GET /api/campaign/saves",
-            "campaign_load": "POST /api/campaign/load"
+            "campaign_load": "POST /api/campaign/load",
+            "zone_mine": "GET /api/zone/mine (requires auth)",
+            "zone_export": "GET /api/zone/:id/export",
+            "zone_import": "POST /api/zone/import (admin only)",
+            "zone_owner": "GET /api/zones/:id/owner",
+            "zone_tiles": "GET /api/zones/:id/tiles?cursor=&limit=",
+            "zone_capture": "POST /api/zones/:id/capture (requires auth)"
+        },
+        "websocket_encodings": {
+            "json": "Text frames, one JSON object per frame (default)",
+            "msgpack": "Binary frames, one MessagePack map per frame with the same field names as the JSON object; select with \"encoding\": \"msgpack\" in auth or spectate (applies from that command's response on). Commands may be sent as JSON text or MessagePack binary frames in either mode; malformed binary frames get an error message"
         }
     }))
 }
 
-/// Health check handler
-async fn health_handler() -> impl IntoResponse {
-    Json(serde_json::json!({
-        "status": "healthy",
-        "service": "geekcraft"
-    }))
-}
-
 /// Handler to submit player code
 async fn submit_code_handler(
     State(state): State<AppState>,
-    request: Request<axum::body::Body>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<CodeSubmission, CODE_BODY_LIMIT>,
 ) -> impl IntoResponse {
-    // Get session from request extensions
-    let session = request.extensions().get::<crate::auth::models::Session>().cloned();
+    match submit_player_code(&state, &session, client_ip(connect_info), payload).await {
+        Ok(message) => (
+            StatusCode::OK,
+            Json(CodeSubmissionResponse {
+                success: true,
+                message,
+            })
+        ),
+        Err(message) => (
+            StatusCode::BAD_REQUEST,
+            Json(CodeSubmissionResponse {
+                success: false,
+                message,
+            })
+        ),
+    }
+}
+
+/// Activate a player's code, for `POST /api/submit` and the WebSocket `submitCode` command
+///
+/// The code is compiled before it replaces the active code. On success, every connection
+/// of the player gets a `codeReloaded` notification: the new code runs from the next
+/// script tick. Submissions are audited, with the client address `ip` if known. Suspicious
+/// patterns are logged, and too many of them get the code refused (see [`CodeSanitizer`]).
+async fn submit_player_code(state: &AppState, session: &Session, ip: Option<IpAddr>, payload: CodeSubmission) -> Result<String, String> {
+    log::info!("Received code submission from player: {}", session.username);
     
-    let player_id = match session {
-        Some(s) => s.username,
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(CodeSubmissionResponse {
-                    success: false,
-                    message: "Unauthorized".to_string(),
-                })
-            );
+    let max_suspicious_patterns = state.config().max_suspicious_patterns;
+    let scanned = payload.into_bundle().and_then(|bundle| {
+        let warnings = CodeSanitizer::check_bundle(&bundle, max_suspicious_patterns)?;
+        for (module, warning) in &warnings {
+            log::warn!("Suspicious pattern `{}` in code from {}: {} line {}", warning.pattern, session.username, module, warning.line);
         }
-    };
-    
-    // Parse request body with size limit (1MB for code submissions)
-    let bytes = match axum::body::to_bytes(request.into_body(), 1_048_576).await {
-        Ok(b) => b,
-        Err(e) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(CodeSubmissionResponse {
-                    success: false,
-                    message: format!("Failed to read request body: {}", e),
-                })
-            );
+        Ok(bundle)
+    });
+    let result = match scanned {
+        Ok(bundle) => {
+            let language = bundle.language();
+            let modules: Vec<String> = bundle.modules().keys().cloned().collect();
+            state.script_engine.write().await.submit(session.username.clone(), bundle)
+                .map(|()| (language, modules))
         }
+        Err(err) => Err(err),
     };
+    let (outcome, detail) = match &result {
+        Ok((language, modules)) => (AuditOutcome::Success, format!("{} ({} modules)", language.name(), modules.len())),
+        Err(err) => (AuditOutcome::Failure, err.clone()),
+    };
+    state.audit(AuditEntry::new(&session.username, ip, AuditAction::CodeSubmission, outcome, detail));
+    let (language, modules) = result.inspect_err(|err| log::warn!("Code submission failed: {}", err))?;
     
-    let payload: CodeSubmission = match serde_json::from_slice(&bytes) {
-        Ok(p) => p,
-        Err(e) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(CodeSubmissionResponse {
-                    success: false,
-                    message: format!("Invalid JSON: {}", e),
-                })
-            );
+    let script_tick = state.game_world.read().await.get_script_tick();
+    state.ws_clients.send_to_user(session.user_id, &serde_json::json!({
+        "type": "codeReloaded",
+        "language": language.name(),
+        "modules": modules,
+        "script_tick": script_tick
+    }));
+    Ok(format!("Code submitted successfully for player {}", session.username))
+}
+
+/// Handler serving the TypeScript declarations of the bot API
+async fn scripting_types_handler() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/typescript; charset=utf-8")], GAME_API_TYPES)
+}
+
+/// Handler to dry-run player code without activating it
+///
+/// Runs the code once in a throwaway context against a snapshot of the caller's current
+/// view, with a stricter timeout. The active script and the world are left untouched.
+async fn validate_code_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<CodeSubmission, CODE_BODY_LIMIT>,
+) -> impl IntoResponse {
+    let player_id = session.username;
+    let bundle = match payload.into_bundle() {
+        Ok(b) => b.with_libraries(state.script_engine.read().await.libraries().clone()),
+        Err(err) => {
+            return (StatusCode::BAD_REQUEST, Json(ValidationResponse::rejected(err)));
         }
     };
     
-    log::info!("Received code submission from player: {}", player_id);
+    let snapshot = state.game_world.read().await.player_snapshot(&player_id);
     
-    let mut engine = state.script_engine.write().await;
+    // Script execution is blocking; keep it off the async workers
+    let result = tokio::task::spawn_blocking(move || {
+        create_runtime(bundle.language(), ScriptLimits::validation()).execute_tick(&bundle, &snapshot)
+    }).await;
     
-    match engine.submit_code(player_id.clone(), payload.code) {
-        Ok(()) => (
+    match result {
+        Ok(result) => (
             StatusCode::OK,
-            Json(CodeSubmissionResponse {
-                success: true,
-                message: format!("Code submitted successfully for player {}", player_id),
+            Json(ValidationResponse {
+                valid: result.error.is_none(),
+                error: result.error,
+                logs: result.logs,
+                commands: result.commands,
             })
         ),
-        Err(err) => {
-            log::warn!("Code submission failed: {}", err);
+        Err(e) => {
+            log::error!("Validation task failed: {}", e);
             (
-                StatusCode::BAD_REQUEST,
-                Json(CodeSubmissionResponse {
-                    success: false,
-                    message: err,
-                })
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ValidationResponse::rejected("Validation failed".to_string()))
             )
         }
     }
 }
 
-/// Handler to list all players
-async fn list_players_handler(State(state): State<AppState>) -> impl IntoResponse {
+/// Handler to get the authenticated player's code bundle
+async fn get_code_handler(
+    State(state): State<AppState>,
+    request: Request<axum::body::Body>,
+) -> impl IntoResponse {
+    let player_id = match request.extensions().get::<Session>() {
+        Some(session) => session.username.clone(),
+        None => return StatusCode::UNAUTHORIZED.into_response(),
+    };
+    
     let engine = state.script_engine.read().await;
-    let players = engine.list_players();
+    let bundle = engine.get_bundle(&player_id);
     
-    Json(PlayersListResponse { players })
+    Json(PlayerCodeResponse {
+        player_id,
+        code: bundle.map(|b| b.entry().clone()),
+        modules: bundle.map(|b| b.modules().clone()),
+        language: bundle.map(|b| b.language()),
+        api_version: bundle.map(|b| b.api_version()),
+    }).into_response()
+}
+
+/// Handler to peek at the authenticated player's message inbox (for debugging bots)
+///
+/// Messages are left in the inbox; the bot still receives them on its next tick.
+async fn messages_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+) -> impl IntoResponse {
+    let messages = state.script_engine.read().await.inbox(&session.username);
+    Json(MessagesResponse {
+        success: true,
+        message: format!("{} unread messages", messages.len()),
+        messages,
+    })
+}
+
+/// Handler to list the profiles of registered players, sorted by account ID, one page at a time
+///
+/// `?format=ids` lists the usernames of players with submitted code instead, sorted.
+async fn list_players_handler(
+    State(state): State<AppState>,
+    Query(query): Query<PlayersQuery>,
+) -> Result<Response, ApiError> {
+    let (page, per_page) = PaginationQuery { page: query.page, per_page: query.per_page }.resolve();
+    match query.format.as_deref() {
+        None | Some("profiles") => {}
+        Some("ids") => {
+            let (players, total) = state.script_engine.read().await.list_players_paginated(page, per_page);
+            return Ok(Json(PaginatedResponse::new(players, page, per_page, total)).into_response());
+        }
+        Some(other) => {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                format!("Unknown format '{}' (expected profiles or ids)", other),
+            ));
+        }
+    }
+
+    let filter = UserFilter { username_prefix: query.q.filter(|q| !q.is_empty()) };
+    let (users, total) = state.auth_service.list_users((page - 1).saturating_mul(per_page), per_page, &filter)
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let world = state.game_world.read().await;
+    let engine = state.script_engine.read().await;
+    let stats = state.stats.read();
+    let profiles: Vec<PlayerProfile> = users.into_iter()
+        .map(|(user, session_active)| PlayerProfile {
+            has_code: engine.get_bundle(&user.username).is_some(),
+            zones: world.zones_of_player(&user.username).len(),
+            online: session_active || state.ws_clients.is_connected(user.id),
+            stats: stats.player(&user.username).cloned(),
+            username: user.username,
+            created_at: user.created_at,
+        })
+        .collect();
+
+    Ok(Json(PaginatedResponse::new(profiles, page, per_page, total)).into_response())
 }
 
 /// Handler to get current game state
-async fn game_state_handler(State(state): State<AppState>) -> impl IntoResponse {
+async fn game_state_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    headers: HeaderMap,
+) -> impl IntoResponse {
     let world = state.game_world.read().await;
     let engine = state.script_engine.read().await;
     let players = engine.list_players();
     
-    Json(GameStateResponse {
+    conditional_json(&headers, StatusCode::OK, &GameStateResponse {
         tick: world.get_tick(),
+        script_tick: world.get_script_tick(),
+        day_phase: world.day_phase(),
         players,
+        run_state: state.sim_control.state(),
+        tech: world.tech_status(&session.username),
+        errors_last_tick: engine.errors_last_tick(&session.username),
+        errors: engine.get_errors(&session.username),
+        state_hash: world.is_deterministic().then(|| world.state_hash()),
     })
 }
 
@@ -367,18 +1499,76 @@ async fn game_state_handler(State(state): State<AppState>) -> impl IntoResponse
 async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
 ) -> Response {
-    ws.on_upgrade(|socket| handle_websocket(socket, state))
+    let span = tracing::info_span!(
+        "ws_connection",
+        connection_id = %uuid::Uuid::new_v4(),
+        user = tracing::field::Empty,
+    );
+    let ip = client_ip(connect_info);
+    ws.on_upgrade(move |socket| handle_websocket(socket, state, ip).instrument(span))
+}
+
+/// Per-connection WebSocket state
+struct ConnectionState {
+    /// Authenticated session (if any)
+    session: Option<Session>,
+    /// Active spectator stream (if spectating)
+    spectating: Option<SpectatorStream>,
+    /// Outgoing message queue for this connection
+    outgoing: UnboundedSender<Outgoing>,
+    /// Encoding of messages sent to this connection
+    encoding: WireEncoding,
+    /// Reserved slot in the per-user connection count (once authenticated)
+    slot: Option<ConnectionSlot>,
+    /// Registration for server-pushed messages (once authenticated)
+    registration: Option<ClientRegistration>,
+    /// Feed of the player's new script errors (once authenticated)
+    error_feed: Option<ErrorFeed>,
+    /// Set when the connection must be closed after the current response
+    closing: bool,
+    /// Client address (if known), for the audit log
+    ip: Option<IpAddr>,
 }
 
 /// Handle WebSocket connection with authentication support
-async fn handle_websocket(socket: WebSocket, state: AppState) {
+async fn handle_websocket(socket: WebSocket, state: AppState, ip: Option<IpAddr>) {
     let (mut sender, mut receiver) = socket.split();
     
     log::info!("WebSocket client connected");
     
-    // Track authenticated session
-    let mut authenticated_session: Option<crate::auth::models::Session> = None;
+    // All outgoing messages (responses and pushed streams) go through one queue
+    let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<Outgoing>();
+    let writer = tokio::spawn(async move {
+        // Encoding switches are queued too, so they apply in order with the messages
+        let mut encoding = WireEncoding::default();
+        while let Some(item) = outgoing_rx.recv().await {
+            let msg = match item {
+                Outgoing::Json(value) => encoding.encode(&value),
+                Outgoing::Encoding(next) => {
+                    encoding = next;
+                    continue;
+                }
+                Outgoing::Close => Message::Close(None),
+            };
+            if sender.send(msg).await.is_err() {
+                break;
+            }
+        }
+    });
+    
+    let mut connection = ConnectionState {
+        session: None,
+        spectating: None,
+        outgoing,
+        encoding: WireEncoding::default(),
+        slot: None,
+        registration: None,
+        error_feed: None,
+        closing: false,
+        ip,
+    };
     
     // Send welcome message
     let welcome = serde_json::json!({
@@ -388,31 +1578,53 @@ async fn handle_websocket(socket: WebSocket, state: AppState) {
         "requiresAuth": true
     });
     
-    if let Ok(msg) = serde_json::to_string(&welcome) {
-        let _ = sender.send(Message::Text(msg)).await;
-    }
+    let _ = connection.outgoing.send(Outgoing::Json(welcome));
     
     // Handle incoming messages
     while let Some(msg) = receiver.next().await {
-        match msg {
+        // Commands arrive as JSON text or MessagePack binary frames
+        let command = match msg {
             Ok(Message::Text(text)) => {
                 log::debug!("Received WebSocket message: {}", text);
                 
+                // The largest command is a code submission, held to the size limit of POST /api/submit
+                if text.len() > CODE_BODY_LIMIT {
+                    let _ = connection.outgoing.send(Outgoing::Json(serde_json::json!({
+                        "type": "error",
+                        "message": format!("Message too large: {} bytes (max: {} bytes)", text.len(), CODE_BODY_LIMIT)
+                    })));
+                    continue;
+                }
+                
                 // Try to parse as JSON command
-                if let Ok(command) = serde_json::from_str::<serde_json::Value>(&text) {
-                    let response = handle_websocket_command(
-                        command,
-                        &state,
-                        &mut authenticated_session
-                    ).await;
-                    
-                    if let Ok(response_text) = serde_json::to_string(&response) {
-                        let _ = sender.send(Message::Text(response_text)).await;
+                match serde_json::from_str::<serde_json::Value>(&text) {
+                    Ok(command) => command,
+                    Err(_) => continue,
+                }
+            }
+            Ok(Message::Binary(bytes)) => {
+                match ws_codec::decode_binary(&bytes) {
+                    // Code travels as JSON text only
+                    Ok(command) if command.get("type").and_then(|v| v.as_str()) == Some("submitCode") => {
+                        let _ = connection.outgoing.send(Outgoing::Json(serde_json::json!({
+                            "type": "submitCodeResponse",
+                            "success": false,
+                            "message": "submitCode must be sent as a JSON text frame"
+                        })));
+                        continue;
+                    }
+                    Ok(command) => command,
+                    Err(err) => {
+                        let _ = connection.outgoing.send(Outgoing::Json(serde_json::json!({
+                            "type": "error",
+                            "message": err
+                        })));
+                        continue;
                     }
                 }
             }
             Ok(Message::Close(_)) => {
-                if let Some(session) = &authenticated_session {
+                if let Some(session) = &connection.session {
                     log::info!("WebSocket client {} disconnected", session.username);
                 } else {
                     log::info!("WebSocket client disconnected");
@@ -423,28 +1635,115 @@ async fn handle_websocket(socket: WebSocket, state: AppState) {
                 log::error!("WebSocket error: {}", e);
                 break;
             }
-            _ => {}
+            _ => continue,
+        };
+        
+        let response = handle_websocket_command(
+            command,
+            &state,
+            &mut connection
+        ).await;
+        let _ = connection.outgoing.send(Outgoing::Json(response));
+        
+        if connection.closing {
+            let _ = connection.outgoing.send(Outgoing::Close);
+            break;
         }
     }
+    
+    // Dropping the connection state stops any spectator stream and releases the
+    // connection slot; give the writer a moment to flush queued messages
+    drop(connection);
+    let mut writer = writer;
+    if tokio::time::timeout(Duration::from_secs(1), &mut writer).await.is_err() {
+        writer.abort();
+    }
 }
 
 /// Handle WebSocket commands with authentication support
 async fn handle_websocket_command(
     command: serde_json::Value, 
     state: &AppState,
-    authenticated_session: &mut Option<crate::auth::models::Session>,
+    connection: &mut ConnectionState,
 ) -> serde_json::Value {
     let cmd_type = command.get("type").and_then(|v| v.as_str()).unwrap_or("");
     
+    // Spectators are read-only: refuse anything that isn't a query or a spectate change
+    if connection.spectating.as_ref().is_some_and(|s| !s.is_active()) {
+        connection.spectating = None;
+    }
+    if connection.spectating.is_some() && !SPECTATOR_ALLOWED_COMMANDS.contains(&cmd_type) {
+        return serde_json::json!({
+            "type": "error",
+            "message": "Spectators cannot issue commands. Send unspectate first."
+        });
+    }
+    
+    // auth and spectate may switch the encoding, starting with their own response
+    if matches!(cmd_type, "auth" | "spectate") {
+        if let Some(name) = command.get("encoding").and_then(|v| v.as_str()) {
+            match WireEncoding::from_name(name) {
+                Ok(encoding) => {
+                    if encoding != connection.encoding {
+                        connection.encoding = encoding;
+                        let _ = connection.outgoing.send(Outgoing::Encoding(encoding));
+                    }
+                }
+                Err(err) => {
+                    return serde_json::json!({
+                        "type": "error",
+                        "message": err
+                    });
+                }
+            }
+        }
+    }
+    
     match cmd_type {
         "auth" => {
             // Authenticate via WebSocket
             let token = command.get("token").and_then(|v| v.as_str()).unwrap_or("");
             
-            match state.auth_service.validate_token(token) {
+            match state.validate_token(token).await {
+                Some(session) if state.refused_by_maintenance(&session.username) => {
+                    serde_json::json!({
+                        "type": "authResponse",
+                        "success": false,
+                        "code": "maintenance",
+                        "message": "Server is under maintenance"
+                    })
+                }
                 Some(session) => {
+                    // Re-authenticating as the same user keeps the existing slot
+                    let has_slot = connection.slot.as_ref()
+                        .is_some_and(|slot| slot.user_id() == session.user_id);
+                    if !has_slot {
+                        connection.slot = None;
+                        connection.registration = None;
+                        match ConnectionSlot::acquire(&state.connected_ws_per_user, session.user_id, state.config.read().unwrap().max_ws_per_user.max(1)) {
+                            Some(slot) => {
+                                connection.slot = Some(slot);
+                                connection.registration = Some(
+                                    state.ws_clients.register(session.user_id, connection.outgoing.clone())
+                                );
+                            }
+                            None => {
+                                log::warn!("WebSocket connection limit reached for {}", session.username);
+                                connection.session = None;
+                                connection.closing = true;
+                                return serde_json::json!({
+                                    "type": "error",
+                                    "message": "Connection limit reached"
+                                });
+                            }
+                        }
+                    }
+                    
                     let username = session.username.clone();
-                    *authenticated_session = Some(session);
+                    let errors = state.script_engine.read().await.subscribe_errors();
+                    connection.error_feed = Some(ErrorFeed::start(errors, username.clone(), connection.outgoing.clone()));
+                    tracing::Span::current().record("user", username.as_str());
+                    connection.session = Some(session);
                     serde_json::json!({
                         "type": "authResponse",
                         "success": true,
@@ -462,7 +1761,7 @@ async fn handle_websocket_command(
         }
         "getPlayers" => {
             // Require authentication
-            if authenticated_session.is_none() {
+            if connection.session.is_none() {
                 return serde_json::json!({
                     "type": "error",
                     "message": "Authentication required. Send auth command first."
@@ -476,24 +1775,150 @@ async fn handle_websocket_command(
                 "players": players
             })
         }
+        "chat" | "getChatHistory" => {
+            let Some(session) = connection.session.as_ref() else {
+                return serde_json::json!({
+                    "type": "error",
+                    "message": "Authentication required. Send auth command first."
+                });
+            };
+            
+            if cmd_type == "chat" {
+                chat::handle_chat(&command, state, session).await
+            } else {
+                chat::handle_chat_history(&command, state, session).await
+            }
+        }
         "getGameState" => {
             // Require authentication
-            if authenticated_session.is_none() {
+            if connection.session.is_none() {
                 return serde_json::json!({
                     "type": "error",
                     "message": "Authentication required. Send auth command first."
                 });
             }
             
+            let username = &connection.session.as_ref().expect("checked above").username;
             let world = state.game_world.read().await;
             let engine = state.script_engine.read().await;
             let players = engine.list_players();
-            serde_json::json!({
+            let mut response = serde_json::json!({
                 "type": "gameStateResponse",
                 "tick": world.get_tick(),
-                "players": players
+                "script_tick": world.get_script_tick(),
+                "day_phase": world.day_phase(),
+                "players": players,
+                "run_state": state.sim_control.state(),
+                "tech": world.tech_status(username),
+                "errors_last_tick": engine.errors_last_tick(username),
+                "errors": engine.get_errors(username)
+            });
+            if world.is_deterministic() {
+                response["state_hash"] = world.state_hash().into();
+            }
+            response
+        }
+        "spectate" => {
+            // Require authentication
+            if connection.session.is_none() {
+                return serde_json::json!({
+                    "type": "error",
+                    "message": "Authentication required. Send auth command first."
+                });
+            }
+            
+            let target = match SpectateTarget::from_command(&command) {
+                Ok(target) => target,
+                Err(err) => {
+                    return serde_json::json!({
+                        "type": "spectateResponse",
+                        "success": false,
+                        "message": err
+                    });
+                }
+            };
+            
+            // Replace any previous stream
+            connection.spectating = None;
+            
+            match spectator::start_stream(state, target, connection.outgoing.clone()).await {
+                Ok(stream) => {
+                    let response = serde_json::json!({
+                        "type": "spectateResponse",
+                        "success": true,
+                        "target": stream.target.kind(),
+                        "id": stream.target.id(),
+                        "frameRate": state.config.read().unwrap().spectator_frame_rate.max(1)
+                    });
+                    connection.spectating = Some(stream);
+                    response
+                }
+                Err(err) => {
+                    serde_json::json!({
+                        "type": "spectateResponse",
+                        "success": false,
+                        "message": err
+                    })
+                }
+            }
+        }
+        "submitCode" => {
+            let Some(session) = connection.session.as_ref() else {
+                return serde_json::json!({
+                    "type": "error",
+                    "message": "Authentication required. Send auth command first."
+                });
+            };
+            // The connection may have been authenticated before maintenance started
+            if state.refused_by_maintenance(&session.username) {
+                return serde_json::json!({
+                    "type": "submitCodeResponse",
+                    "success": false,
+                    "code": "maintenance",
+                    "message": "Server is under maintenance"
+                });
+            }
+            
+            let result = match serde_json::from_value::<CodeSubmission>(command) {
+                Ok(payload) => submit_player_code(state, session, connection.ip, payload).await,
+                Err(e) => Err(format!("Invalid submission: {}", e)),
+            };
+            match result {
+                Ok(message) => serde_json::json!({
+                    "type": "submitCodeResponse",
+                    "success": true,
+                    "message": message
+                }),
+                Err(message) => serde_json::json!({
+                    "type": "submitCodeResponse",
+                    "success": false,
+                    "message": message
+                }),
+            }
+        }
+        "unspectate" => {
+            let was_spectating = connection.spectating.take().is_some();
+            serde_json::json!({
+                "type": "unspectateResponse",
+                "success": was_spectating
             })
         }
+        "resync" => {
+            // The next frame of the stream will be a keyframe
+            match &connection.spectating {
+                Some(stream) => {
+                    stream.resync();
+                    serde_json::json!({
+                        "type": "resyncResponse",
+                        "success": true
+                    })
+                }
+                None => serde_json::json!({
+                    "type": "error",
+                    "message": "Not spectating"
+                }),
+            }
+        }
         _ => {
             serde_json::json!({
                 "type": "error",
@@ -501,4 +1926,4 @@ async fn handle_websocket_command(
             })
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,111 @@
+//! Graceful shutdown
+//!
+//! On SIGINT or SIGTERM the server stops the game loop, stops accepting requests, asks
+//! every WebSocket client to close (waiting at most [`WS_DRAIN_TIMEOUT`]), and then
+//! saves what would otherwise be lost: the world to [`WORLD_SAVE_FILE`] in the save
+//! directory, the buffered entries of its action log, and every running campaign.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+use crate::game::game_loop::SimControl;
+use crate::game::world::World;
+use crate::network::campaign_routes::campaign_manager;
+use crate::network::ws_clients::WsClients;
+
+/// Longest wait for WebSocket clients to close their connections (and for the game loop to finish its tick)
+pub const WS_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// File the world is saved to on shutdown, in the campaign save directory
+pub const WORLD_SAVE_FILE: &str = "world.json";
+
+/// How often the remaining WebSocket connections are counted while draining
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Ask every WebSocket connection to close and wait for them to be gone
+///
+/// Returns the number of connections still open after `timeout`.
+pub async fn drain_websockets(ws_clients: &WsClients, timeout: Duration) -> usize {
+    let closing = ws_clients.disconnect_all();
+    if closing > 0 {
+        log::info!("Closing {} WebSocket connections", closing);
+    }
+    let drained = tokio::time::timeout(timeout, async {
+        while ws_clients.counts().1 > 0 {
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+    });
+    match drained.await {
+        Ok(()) => 0,
+        Err(_) => ws_clients.counts().1,
+    }
+}
+
+/// Stop the game loop between two ticks
+///
+/// The loop is paused, then stopped once it is back to waiting for its next tick (or
+/// after [`WS_DRAIN_TIMEOUT`] if it does not get there, e.g. in a long script tick).
+pub async fn stop_game_loop(control: &SimControl, game_loop: JoinHandle<()>) {
+    let paused_at = chrono::Utc::now().timestamp_millis();
+    control.pause();
+    let idle = tokio::time::timeout(WS_DRAIN_TIMEOUT, async {
+        while control.last_heartbeat().is_none_or(|at| at <= paused_at) {
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+    });
+    if idle.await.is_err() {
+        log::warn!("⚠️  Game loop still busy after {:?}, stopping it mid-tick", WS_DRAIN_TIMEOUT);
+    }
+    game_loop.abort();
+    let _ = game_loop.await;
+}
+
+/// Where the world is saved on shutdown
+pub fn world_save_path(save_dir: &Path) -> PathBuf {
+    save_dir.join(WORLD_SAVE_FILE)
+}
+
+/// Save the world, flush its action log and save every running campaign
+///
+/// Every step is attempted even if an earlier one fails; returns whether all succeeded.
+pub async fn save_state(world: &RwLock<World>, save_dir: &Path) -> bool {
+    let mut saved = true;
+    let mut world = world.write().await;
+
+    let path = world_save_path(save_dir);
+    let written = std::fs::create_dir_all(save_dir)
+        .map_err(|e| format!("Failed to create {}: {}", save_dir.display(), e))
+        .and_then(|()| world.save(&path));
+    match written {
+        Ok(()) => log::info!("✓ World saved to {} (tick {})", path.display(), world.get_tick()),
+        Err(e) => {
+            log::error!("❌ Failed to save the world: {}", e);
+            saved = false;
+        }
+    }
+
+    match world.flush_action_log() {
+        Ok(()) => log::info!("✓ Action log flushed"),
+        Err(e) => {
+            log::error!("❌ Failed to flush the action log: {}", e);
+            saved = false;
+        }
+    }
+    drop(world);
+
+    let manager = campaign_manager();
+    let mut manager = manager.write().await;
+    for (run_id, result) in manager.save_running_runs() {
+        match result {
+            Ok(()) => log::info!("✓ Campaign run {} saved", run_id),
+            Err(e) => {
+                log::error!("❌ Failed to save campaign run {}: {}", run_id, e);
+                saved = false;
+            }
+        }
+    }
+    saved
+}
@@ -0,0 +1,235 @@
+//! Spectator module
+//!
+//! Read-only WebSocket state streams for users watching a match (campaign run) or zone
+//! without participating. Spectators get full visibility of the target and receive frames
+//! at their own frame rate, independent of the player update rate. Zone spectators use
+//! the [`state_sync`](crate::network::state_sync) protocol: a full `spectatorFrame`
+//! keyframe, then `spectatorDelta` frames with only what changed. Every frame has a `seq`
+//! number; keyframes are resent every `keyframe_interval` or on `resync`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use dashmap::DashMap;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinHandle;
+
+use crate::network::campaign_routes::campaign_manager;
+use crate::network::server::AppState;
+use crate::network::state_sync::{self, SyncState};
+use crate::network::ws_codec::Outgoing;
+
+/// Last zone state sent to each spectator stream (stream ID -> state)
+pub type ZoneSnapshots = Arc<DashMap<u64, SyncState>>;
+
+/// Source of spectator stream IDs
+static NEXT_STREAM_ID: AtomicU64 = AtomicU64::new(0);
+
+/// WebSocket commands a spectator may still send (everything else is refused)
+pub const SPECTATOR_ALLOWED_COMMANDS: &[&str] = &["spectate", "unspectate", "resync", "getPlayers", "getGameState"];
+
+/// What a spectator is watching
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpectateTarget {
+    /// A campaign run, by run ID
+    Match(String),
+    /// A zone, by zone ID
+    Zone(String),
+}
+
+impl SpectateTarget {
+    /// Parse the target from a `spectate` command (`match_id` or `zone_id`)
+    pub fn from_command(command: &serde_json::Value) -> Result<Self, String> {
+        let match_id = command.get("match_id").and_then(|v| v.as_str());
+        let zone_id = command.get("zone_id").and_then(|v| v.as_str());
+
+        match (match_id, zone_id) {
+            (Some(id), None) => Ok(SpectateTarget::Match(id.to_string())),
+            (None, Some(id)) => Ok(SpectateTarget::Zone(id.to_string())),
+            _ => Err("Spectate requires exactly one of match_id or zone_id".to_string()),
+        }
+    }
+
+    /// Target kind as sent to clients ("match" or "zone")
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SpectateTarget::Match(_) => "match",
+            SpectateTarget::Zone(_) => "zone",
+        }
+    }
+
+    /// Target identifier
+    pub fn id(&self) -> &str {
+        match self {
+            SpectateTarget::Match(id) | SpectateTarget::Zone(id) => id,
+        }
+    }
+}
+
+/// An active spectator stream; the stream stops when this is dropped
+pub struct SpectatorStream {
+    /// What is being watched
+    pub target: SpectateTarget,
+    id: u64,
+    snapshots: ZoneSnapshots,
+    task: JoinHandle<()>,
+}
+
+impl SpectatorStream {
+    /// Whether frames are still being streamed
+    pub fn is_active(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Make the next frame a keyframe
+    pub fn resync(&self) {
+        self.snapshots.remove(&self.id);
+    }
+}
+
+impl Drop for SpectatorStream {
+    fn drop(&mut self) {
+        self.task.abort();
+        self.snapshots.remove(&self.id);
+    }
+}
+
+/// Build one full-visibility frame for the target
+///
+/// Fails if the target does not exist or does not allow spectators.
+pub async fn build_frame(state: &AppState, target: &SpectateTarget) -> Result<serde_json::Value, String> {
+    match target {
+        SpectateTarget::Match(run_id) => {
+            let manager = campaign_manager();
+            let manager = manager.read().await;
+            let run = manager.get_run_state(run_id)
+                .ok_or_else(|| format!("Match {} not found", run_id))?;
+
+            if !run.allow_spectators {
+                return Err(format!("Spectators are not allowed in match {}", run_id));
+            }
+
+            Ok(serde_json::json!({
+                "type": "spectatorFrame",
+                "target": target.kind(),
+                "id": run_id,
+                "tick": run.tick,
+                "run": run
+            }))
+        }
+        SpectateTarget::Zone(zone_id) => {
+            let world = state.game_world.read().await;
+            world.with_zone_read(zone_id, |zone| serde_json::json!({
+                "type": "spectatorFrame",
+                "target": target.kind(),
+                "id": zone_id,
+                "tick": world.get_tick(),
+                "day_phase": world.day_phase(),
+                "zone": zone
+            }))
+            .ok_or_else(|| format!("Zone {} not found", zone_id))
+        }
+    }
+}
+
+/// Build the next frame of a stream
+///
+/// For zones, a keyframe (`spectatorFrame`) is sent when the stream has no snapshot yet
+/// (first frame, resync, keyframe interval); otherwise a `spectatorDelta` against the
+/// state this stream last sent.
+async fn next_frame(state: &AppState, target: &SpectateTarget, stream_id: u64, seq: u64) -> Result<serde_json::Value, String> {
+    let SpectateTarget::Zone(zone_id) = target else {
+        let mut frame = build_frame(state, target).await?;
+        frame["seq"] = serde_json::json!(seq);
+        return Ok(frame);
+    };
+
+    let world = state.game_world.read().await;
+    let current = world.with_zone_read(zone_id, |zone| SyncState::new(world.get_tick(), zone))
+        .ok_or_else(|| format!("Zone {} not found", zone_id))?;
+
+    if let Some(mut previous) = state.zone_snapshots.get_mut(&stream_id) {
+        let delta = state_sync::diff(&previous, &current);
+        *previous = current;
+        return Ok(serde_json::json!({
+            "type": "spectatorDelta",
+            "target": target.kind(),
+            "id": zone_id,
+            "seq": seq,
+            "tick": delta.tick,
+            "day_phase": world.day_phase(),
+            "diff": delta.diff
+        }));
+    }
+
+    let frame = serde_json::json!({
+        "type": "spectatorFrame",
+        "target": target.kind(),
+        "id": zone_id,
+        "seq": seq,
+        "tick": current.tick,
+        "day_phase": world.day_phase(),
+        "zone": current.zone
+    });
+    state.zone_snapshots.insert(stream_id, current);
+    Ok(frame)
+}
+
+/// Start streaming frames for `target` to a connection's outgoing queue
+///
+/// Frames are sent at the configured `spectator_frame_rate` per second. The stream ends with an
+/// error message if the target disappears or stops allowing spectators.
+pub async fn start_stream(
+    state: &AppState,
+    target: SpectateTarget,
+    outgoing: UnboundedSender<Outgoing>,
+) -> Result<SpectatorStream, String> {
+    // Validate up front so the client gets a direct answer
+    build_frame(state, &target).await?;
+
+    let config = state.config();
+    let period = Duration::from_millis(1000 / u64::from(config.spectator_frame_rate.max(1)));
+    let id = NEXT_STREAM_ID.fetch_add(1, Ordering::Relaxed);
+    let stream_state = state.clone();
+    let stream_target = target.clone();
+
+    let keyframe_interval = Duration::from_secs(config.keyframe_interval_secs.max(1));
+    let snapshots = state.zone_snapshots.clone();
+
+    let task = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        let mut last_keyframe = tokio::time::Instant::now();
+        for seq in 0.. {
+            interval.tick().await;
+
+            if last_keyframe.elapsed() >= keyframe_interval {
+                snapshots.remove(&id);
+            }
+            let frame = match next_frame(&stream_state, &stream_target, id, seq).await {
+                Ok(frame) => frame,
+                Err(err) => {
+                    let error = serde_json::json!({
+                        "type": "error",
+                        "message": format!("Spectator stream ended: {}", err)
+                    });
+                    let _ = outgoing.send(Outgoing::Json(error));
+                    break;
+                }
+            };
+            if frame["type"] == "spectatorFrame" {
+                last_keyframe = tokio::time::Instant::now();
+            }
+
+            if outgoing.send(Outgoing::Json(frame)).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(SpectatorStream {
+        target,
+        id,
+        snapshots: state.zone_snapshots.clone(),
+        task,
+    })
+}
@@ -0,0 +1,255 @@
+//! State sync module
+//!
+//! Delta protocol for streaming zone state over WebSocket. A subscriber first receives a
+//! keyframe with the full state (tiles, entities, resources), then deltas with only what
+//! changed. Every message carries a sequence number one higher than the previous one.
+//! A new keyframe is sent periodically or when the client sends `{"type": "resync"}`;
+//! clients that see a gap or out-of-order sequence number should ask for one.
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::zone::{Zone, ZoneDiff};
+
+/// Full synchronized state of a zone at a tick
+///
+/// Entities are kept sorted by ID and resources by position, so states built from the
+/// same zone content compare equal regardless of insertion order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncState {
+    /// World tick
+    pub tick: u64,
+    /// Zone content
+    pub zone: Zone,
+}
+
+impl SyncState {
+    /// Capture a zone at a tick
+    pub fn new(tick: u64, zone: &Zone) -> Self {
+        let mut zone = zone.clone();
+        normalize(&mut zone);
+        Self { tick, zone }
+    }
+}
+
+/// Changes between two states of the same zone
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Delta {
+    /// Tick of the newer state
+    pub tick: u64,
+    /// Zone changes
+    pub diff: ZoneDiff,
+}
+
+impl Delta {
+    /// Whether nothing but (possibly) the tick changed
+    pub fn is_empty(&self) -> bool {
+        self.diff.is_empty()
+    }
+}
+
+/// Compute the delta that turns `prev` into `next`
+pub fn diff(prev: &SyncState, next: &SyncState) -> Delta {
+    Delta {
+        tick: next.tick,
+        diff: prev.zone.diff(&next.zone),
+    }
+}
+
+/// Apply a delta produced by [`diff`] to a state
+pub fn apply(state: &mut SyncState, delta: &Delta) -> Result<(), String> {
+    let zone = &mut state.zone;
+
+    for change in &delta.diff.changed_tiles {
+        let tile = zone.tiles.get_mut(change.y)
+            .and_then(|row| row.get_mut(change.x))
+            .ok_or_else(|| format!("Tile ({}, {}) is outside zone {}", change.x, change.y, zone.id))?;
+        tile.surface_type = change.new;
+    }
+
+    zone.entities.retain(|entity| !delta.diff.removed_entities.contains(&entity.id));
+    for entity in &delta.diff.added_entities {
+        match zone.entities.iter_mut().find(|existing| existing.id == entity.id) {
+            Some(existing) => *existing = entity.clone(),
+            None => zone.entities.push(entity.clone()),
+        }
+    }
+
+    for change in &delta.diff.changed_resources {
+        let position = zone.resources.iter().position(|r| r.x == change.x && r.y == change.y);
+        match (position, change.new_amount) {
+            (Some(index), 0) => {
+                zone.resources.remove(index);
+            }
+            (Some(index), amount) => zone.resources[index].amount = amount,
+            (None, 0) => {}
+            (None, amount) => zone.resources.push(crate::game::zone::ResourceDeposit {
+                x: change.x,
+                y: change.y,
+                amount,
+            }),
+        }
+    }
+
+    normalize(zone);
+    state.tick = delta.tick;
+    Ok(())
+}
+
+/// Sort entities and resources into their canonical order
+fn normalize(zone: &mut Zone) {
+    zone.entities.sort_by_key(|entity| entity.id);
+    zone.resources.retain(|resource| resource.amount > 0);
+    zone.resources.sort_by_key(|resource| (resource.y, resource.x));
+}
+
+/// Client-side copy of a streamed state
+///
+/// Tracks the last sequence number; a delta that does not directly follow it (or arrives
+/// before any keyframe) is rejected and the client should request a resync.
+#[derive(Debug, Default)]
+pub struct StateReplica {
+    seq: Option<u64>,
+    state: Option<SyncState>,
+}
+
+impl StateReplica {
+    /// Create an empty replica (waiting for a keyframe)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the state with a keyframe
+    pub fn apply_keyframe(&mut self, seq: u64, state: SyncState) {
+        self.seq = Some(seq);
+        self.state = Some(state);
+    }
+
+    /// Apply the delta with sequence number `seq`; an error means a resync is needed
+    pub fn apply_delta(&mut self, seq: u64, delta: &Delta) -> Result<(), String> {
+        let (Some(last), Some(state)) = (self.seq, self.state.as_mut()) else {
+            return Err("No keyframe received".to_string());
+        };
+        if seq != last + 1 {
+            return Err(format!("Expected sequence {}, got {}", last + 1, seq));
+        }
+
+        apply(state, delta)?;
+        self.seq = Some(seq);
+        Ok(())
+    }
+
+    /// Last applied sequence number
+    pub fn seq(&self) -> Option<u64> {
+        self.seq
+    }
+
+    /// Current state (if a keyframe was received)
+    pub fn state(&self) -> Option<&SyncState> {
+        self.state.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::zone::{EntityRef, ResourceDeposit, SurfaceType, DEFAULT_ENTITY_HITS, ZONE_SIZE};
+
+    /// Small deterministic generator for randomized mutations
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((self.0 >> 33) as usize) % bound
+        }
+    }
+
+    /// Apply one random change (entity spawn/move/despawn, resource change, tile change)
+    fn mutate(zone: &mut Zone, rng: &mut Lcg, next_id: &mut u32) {
+        match rng.next(5) {
+            0 => {
+                zone.entities.push(EntityRef {
+                    id: *next_id,
+                    kind: "worker".to_string(),
+                    owner: None,
+                    x: rng.next(ZONE_SIZE),
+                    y: rng.next(ZONE_SIZE),
+                    hits: DEFAULT_ENTITY_HITS,
+                    can_swim: false,
+                    can_fly: false,
+                });
+                *next_id += 1;
+            }
+            1 if !zone.entities.is_empty() => {
+                let index = rng.next(zone.entities.len());
+                zone.entities[index].x = rng.next(ZONE_SIZE);
+            }
+            2 if !zone.entities.is_empty() => {
+                let index = rng.next(zone.entities.len());
+                zone.entities.remove(index);
+            }
+            3 => {
+                let (x, y) = (rng.next(4), rng.next(4));
+                let amount = rng.next(3) as u32 * 50;
+                zone.resources.retain(|r| (r.x, r.y) != (x, y));
+                if amount > 0 {
+                    zone.resources.push(ResourceDeposit { x, y, amount });
+                }
+            }
+            _ => {
+                let tile = &mut zone.tiles[rng.next(ZONE_SIZE)][rng.next(ZONE_SIZE)];
+                tile.surface_type = match tile.surface_type {
+                    SurfaceType::Plain => SurfaceType::Swamp,
+                    _ => SurfaceType::Plain,
+                };
+            }
+        }
+    }
+
+    #[test]
+    fn test_unchanged_state_gives_empty_delta() {
+        let zone = Zone::generate("zone".to_string(), 7);
+        let delta = diff(&SyncState::new(1, &zone), &SyncState::new(2, &zone));
+        assert!(delta.is_empty());
+        assert_eq!(delta.tick, 2);
+    }
+
+    #[test]
+    fn test_keyframe_plus_deltas_equals_latest_state() {
+        for seed in 0..20 {
+            let mut rng = Lcg(seed);
+            let mut zone = Zone::generate(format!("zone_{}", seed), seed);
+            let mut next_id = 1;
+
+            let mut replica = StateReplica::new();
+            let mut previous = SyncState::new(0, &zone);
+            replica.apply_keyframe(0, previous.clone());
+
+            for tick in 1..=50 {
+                for _ in 0..rng.next(4) {
+                    mutate(&mut zone, &mut rng, &mut next_id);
+                }
+                let latest = SyncState::new(tick, &zone);
+                replica.apply_delta(tick, &diff(&previous, &latest)).unwrap();
+                assert_eq!(replica.state(), Some(&latest), "seed {} tick {}", seed, tick);
+                previous = latest;
+            }
+        }
+    }
+
+    #[test]
+    fn test_sequence_gap_requires_resync() {
+        let zone = Zone::generate("zone".to_string(), 7);
+        let state = SyncState::new(0, &zone);
+        let delta = diff(&state, &SyncState::new(1, &zone));
+
+        let mut replica = StateReplica::new();
+        assert!(replica.apply_delta(1, &delta).is_err());
+
+        replica.apply_keyframe(5, state);
+        assert!(replica.apply_delta(7, &delta).unwrap_err().contains("Expected sequence 6"));
+        assert!(replica.apply_delta(5, &delta).is_err());
+        replica.apply_delta(6, &delta).unwrap();
+        assert_eq!(replica.seq(), Some(6));
+    }
+}
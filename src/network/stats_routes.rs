@@ -0,0 +1,62 @@
+//! Statistics routes module
+//!
+//! HTTP endpoints for player and zone statistics. They read the statistics read model
+//! only, never the world, so they answer even while a tick holds the world lock.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+
+use crate::game::stats::{PlayerStat, ZoneStat};
+use crate::network::extract::AuthSession;
+use crate::network::server::AppState;
+
+/// Response for statistics
+#[derive(Debug, Serialize)]
+pub struct StatsResponse<T> {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Tick the statistics are up to date with
+    pub tick: u64,
+    /// Statistics (if found)
+    pub stats: Option<T>,
+}
+
+fn stats_response<T: Serialize>(tick: u64, stats: Option<T>, what: String) -> (StatusCode, Json<StatsResponse<T>>) {
+    match stats {
+        Some(stats) => (
+            StatusCode::OK,
+            Json(StatsResponse { success: true, message: format!("Statistics of {}", what), tick, stats: Some(stats) }),
+        ),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(StatsResponse { success: false, message: format!("No statistics for {}", what), tick, stats: None }),
+        ),
+    }
+}
+
+/// Handler to get a player's statistics
+pub async fn player_stats_handler(
+    State(state): State<AppState>,
+    AuthSession(_session): AuthSession,
+    Path(username): Path<String>,
+) -> impl IntoResponse {
+    let stats = state.stats.read();
+    stats_response::<PlayerStat>(stats.tick, stats.player(&username).cloned(), format!("player {}", username))
+}
+
+/// Handler to get a zone's statistics
+pub async fn zone_stats_handler(
+    State(state): State<AppState>,
+    AuthSession(_session): AuthSession,
+    Path(zone_id): Path<String>,
+) -> impl IntoResponse {
+    let stats = state.stats.read();
+    stats_response::<ZoneStat>(stats.tick, stats.zone(&zone_id).cloned(), format!("zone {}", zone_id))
+}
@@ -0,0 +1,222 @@
+//! Team routes module
+//!
+//! HTTP endpoint handlers for teams (create, invite, leave, status). Team membership is
+//! stored in the auth database and mirrored into the game world, where members share a
+//! resource pool and count their entities together when capturing zones.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::auth::models::Team;
+use crate::game::zone::ResourceType;
+use crate::network::extract::{AuthSession, SizedJson};
+use crate::network::server::AppState;
+
+/// Request to create a team
+#[derive(Debug, Deserialize)]
+pub struct CreateTeamRequest {
+    /// Team name
+    pub name: String,
+}
+
+/// Response for team operations
+#[derive(Debug, Serialize)]
+pub struct TeamResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Team state (if it still exists)
+    pub team: Option<Team>,
+}
+
+/// Response for a team's status
+#[derive(Debug, Serialize)]
+pub struct TeamStatusResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Team name
+    pub name: Option<String>,
+    /// Usernames of the members
+    pub members: Vec<String>,
+    /// Resources shared by the members
+    pub resource_pool: HashMap<ResourceType, u32>,
+    /// Number of zones owned by members
+    pub controlled_zones: usize,
+}
+
+fn team_error(status: StatusCode, message: String) -> (StatusCode, Json<TeamResponse>) {
+    (
+        status,
+        Json(TeamResponse {
+            success: false,
+            message,
+            team: None,
+        })
+    )
+}
+
+/// Mirror a team's membership into the world and store its current resource pool
+///
+/// Returns the team and the usernames of its members.
+async fn sync_team(state: &AppState, mut team: Team) -> Result<(Team, Vec<String>), String> {
+    let mut members = Vec::with_capacity(team.members.len());
+    for user_id in &team.members {
+        let user = state.auth_service.get_user(*user_id)?
+            .ok_or_else(|| format!("User {} not found", user_id))?;
+        members.push(user.username);
+    }
+
+    let mut world = state.game_world.write().await;
+    world.set_team_members(team.id, members.clone());
+    team.resource_pool = world.team_pool(&team.id);
+    drop(world);
+
+    state.auth_service.save_team(&team)?;
+    Ok((team, members))
+}
+
+/// Handler to create a team (the creator is its first member)
+pub async fn create_team_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<CreateTeamRequest>,
+) -> impl IntoResponse {
+    let team = match state.auth_service.create_team(session.user_id, &payload.name) {
+        Ok(team) => team,
+        Err(err) => return team_error(StatusCode::BAD_REQUEST, err),
+    };
+
+    match sync_team(&state, team).await {
+        Ok((team, _)) => {
+            log::info!("{} created team {} ({})", session.username, team.name, team.id);
+            (
+                StatusCode::OK,
+                Json(TeamResponse {
+                    success: true,
+                    message: format!("Team {} created", team.name),
+                    team: Some(team),
+                })
+            )
+        }
+        Err(err) => team_error(StatusCode::INTERNAL_SERVER_ERROR, err),
+    }
+}
+
+/// Handler to add a player to a team (members only)
+pub async fn invite_to_team_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path((team_id, user_id)): Path<(Uuid, i64)>,
+) -> impl IntoResponse {
+    let team = match state.auth_service.invite_to_team(&team_id, session.user_id, user_id) {
+        Ok(team) => team,
+        Err(err) => return team_error(StatusCode::BAD_REQUEST, err),
+    };
+
+    match sync_team(&state, team).await {
+        Ok((team, _)) => (
+            StatusCode::OK,
+            Json(TeamResponse {
+                success: true,
+                message: format!("User {} joined team {}", user_id, team.name),
+                team: Some(team),
+            })
+        ),
+        Err(err) => team_error(StatusCode::INTERNAL_SERVER_ERROR, err),
+    }
+}
+
+/// Handler to leave a team (the last member leaving disbands it)
+pub async fn leave_team_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path(team_id): Path<Uuid>,
+) -> impl IntoResponse {
+    let team = match state.auth_service.leave_team(&team_id, session.user_id) {
+        Ok(team) => team,
+        Err(err) => return team_error(StatusCode::BAD_REQUEST, err),
+    };
+
+    let Some(team) = team else {
+        state.game_world.write().await.set_team_members(team_id, Vec::new());
+        return (
+            StatusCode::OK,
+            Json(TeamResponse {
+                success: true,
+                message: "Left team; the team was disbanded".to_string(),
+                team: None,
+            })
+        );
+    };
+
+    match sync_team(&state, team).await {
+        Ok((team, _)) => (
+            StatusCode::OK,
+            Json(TeamResponse {
+                success: true,
+                message: format!("Left team {}", team.name),
+                team: Some(team),
+            })
+        ),
+        Err(err) => team_error(StatusCode::INTERNAL_SERVER_ERROR, err),
+    }
+}
+
+/// Handler to get a team's members, resource pool, and controlled zone count (members only)
+pub async fn team_status_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path(team_id): Path<Uuid>,
+) -> impl IntoResponse {
+    let error = |status: StatusCode, message: String| {
+        (
+            status,
+            Json(TeamStatusResponse {
+                success: false,
+                message,
+                name: None,
+                members: Vec::new(),
+                resource_pool: HashMap::new(),
+                controlled_zones: 0,
+            })
+        )
+    };
+
+    let team = match state.auth_service.get_team(&team_id) {
+        Ok(Some(team)) => team,
+        Ok(None) => return error(StatusCode::NOT_FOUND, format!("Team {} not found", team_id)),
+        Err(err) => return error(StatusCode::INTERNAL_SERVER_ERROR, err),
+    };
+    if !team.members.contains(&session.user_id) {
+        return error(StatusCode::FORBIDDEN, "Only team members can see the team status".to_string());
+    }
+
+    match sync_team(&state, team).await {
+        Ok((team, members)) => {
+            let controlled_zones = state.game_world.read().await.team_zone_count(&team.id);
+            (
+                StatusCode::OK,
+                Json(TeamStatusResponse {
+                    success: true,
+                    message: format!("Team {} has {} members", team.name, members.len()),
+                    name: Some(team.name),
+                    members,
+                    resource_pool: team.resource_pool,
+                    controlled_zones,
+                })
+            )
+        }
+        Err(err) => error(StatusCode::INTERNAL_SERVER_ERROR, err),
+    }
+}
@@ -0,0 +1,133 @@
+//! TLS termination
+//!
+//! When the configuration names a certificate and a private key (see
+//! [`ServerConfig::tls_paths`](crate::config::ServerConfig::tls_paths)), the server
+//! speaks HTTPS, and WebSocket clients connect with `wss://` on the same port.
+
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+/// Longest wait for requests in progress once shutdown has begun
+pub const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Problem loading the certificate or the private key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TlsError {
+    /// A PEM file could not be read
+    Io {
+        /// Path of the file
+        path: String,
+        /// Underlying error
+        message: String,
+    },
+    /// The certificate file holds no certificate
+    NoCertificate(String),
+    /// The key file holds no private key
+    NoPrivateKey(String),
+    /// rustls rejected the certificate or the key
+    Invalid(String),
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsError::Io { path, message } => write!(f, "Failed to read {}: {}", path, message),
+            TlsError::NoCertificate(path) => write!(f, "No PEM certificate in {}", path),
+            TlsError::NoPrivateKey(path) => write!(f, "No PEM private key in {}", path),
+            TlsError::Invalid(message) => write!(f, "Invalid certificate or key: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for TlsError {}
+
+/// Build the rustls configuration from PEM certificate chain and private key files
+///
+/// Only HTTP/1.1 is negotiated, since WebSocket upgrades need it.
+pub fn get_tls_config(cert_path: &str, key_path: &str) -> Result<rustls::ServerConfig, TlsError> {
+    let open = |path: &str| {
+        File::open(path)
+            .map(BufReader::new)
+            .map_err(|e| TlsError::Io { path: path.to_string(), message: e.to_string() })
+    };
+
+    let certs: Vec<CertificateDer<'static>> = rustls_pemfile::certs(&mut open(cert_path)?)
+        .collect::<Result<_, _>>()
+        .map_err(|e| TlsError::Io { path: cert_path.to_string(), message: e.to_string() })?;
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificate(cert_path.to_string()));
+    }
+    let key: PrivateKeyDer<'static> = rustls_pemfile::private_key(&mut open(key_path)?)
+        .map_err(|e| TlsError::Io { path: key_path.to_string(), message: e.to_string() })?
+        .ok_or_else(|| TlsError::NoPrivateKey(key_path.to_string()))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| TlsError::Invalid(e.to_string()))?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| TlsError::Invalid(e.to_string()))?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// Serve the router on a bound listener, over TLS if a configuration is given
+///
+/// Handlers can extract the client address as `ConnectInfo<SocketAddr>`.
+pub async fn serve(listener: tokio::net::TcpListener, app: Router, tls: Option<rustls::ServerConfig>) -> std::io::Result<()> {
+    serve_with_shutdown(listener, app, tls, std::future::pending()).await
+}
+
+/// Like [`serve`], but stop accepting connections once `shutdown` completes
+///
+/// Requests in progress are given [`SHUTDOWN_GRACE_PERIOD`] to finish.
+pub async fn serve_with_shutdown(
+    listener: tokio::net::TcpListener,
+    app: Router,
+    tls: Option<rustls::ServerConfig>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    match tls {
+        Some(config) => {
+            let config = RustlsConfig::from_config(Arc::new(config));
+            let handle = axum_server::Handle::new();
+            let trigger = handle.clone();
+            tokio::spawn(async move {
+                shutdown.await;
+                trigger.graceful_shutdown(Some(SHUTDOWN_GRACE_PERIOD));
+            });
+            axum_server::from_tcp_rustls(listener.into_std()?, config)
+                .handle(handle)
+                .serve(service)
+                .await
+        }
+        None => {
+            let (started, shutting_down) = tokio::sync::oneshot::channel();
+            let server = axum::serve(listener, service).with_graceful_shutdown(async move {
+                shutdown.await;
+                let _ = started.send(());
+            });
+            tokio::select! {
+                result = server => result,
+                _ = async {
+                    if shutting_down.await.is_ok() {
+                        tokio::time::sleep(SHUTDOWN_GRACE_PERIOD).await;
+                    } else {
+                        std::future::pending::<()>().await;
+                    }
+                } => Ok(()),
+            }
+        }
+    }
+}
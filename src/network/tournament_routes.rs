@@ -0,0 +1,369 @@
+//! Tournament routes module
+//!
+//! HTTP endpoints to run bot tournaments and follow them. Creating a tournament is
+//! reserved to admins, the usernames listed in `GEEKCRAFT_ADMIN_USERS`
+//! (comma-separated); any player can follow its standings.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::auth::achievements::PlayerStats;
+use crate::game::replay::ReplayHistory;
+use crate::game::tournament::{run_match_with_progress, MatchSettings, Standing, TournamentFormat, TournamentMatch, TournamentRun};
+use crate::network::extract::{AuthSession, SizedJson};
+use crate::network::achievement_routes::unlock_and_notify;
+use crate::network::server::AppState;
+use crate::scripting::bundle::ScriptBundle;
+
+/// Request to create a tournament
+#[derive(Debug, Deserialize)]
+pub struct CreateTournamentRequest {
+    /// Participating players, in seeding order (empty for every player with submitted code)
+    #[serde(default)]
+    pub players: Vec<String>,
+    /// How players are paired
+    #[serde(default = "default_format")]
+    pub format: TournamentFormat,
+    /// Match settings
+    #[serde(default)]
+    pub settings: MatchSettingsRequest,
+    /// Play the matches of a round one after the other instead of in parallel
+    #[serde(default)]
+    pub sequential: bool,
+}
+
+fn default_format() -> TournamentFormat {
+    TournamentFormat::RoundRobin
+}
+
+/// Match settings of a tournament request
+#[derive(Debug, Default, Deserialize)]
+pub struct MatchSettingsRequest {
+    /// Map seed (random maps if omitted)
+    pub seed: Option<u64>,
+    /// Ticks after which a match ends (`GEEKCRAFT_TOURNAMENT_MAX_TICKS` if omitted)
+    pub max_ticks: Option<u64>,
+}
+
+/// Query of a match replay
+#[derive(Debug, Deserialize)]
+pub struct ReplayQuery {
+    /// Tick to view (the last one kept if omitted)
+    pub tick: Option<u64>,
+}
+/// Response for starting a tournament
+#[derive(Debug, Serialize)]
+pub struct StartTournamentResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Tournament identifier (poll its status)
+    pub tournament_id: Option<String>,
+}
+
+/// Response for tournament status
+#[derive(Debug, Serialize)]
+pub struct TournamentStatusResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Tournament state (if found)
+    pub tournament: Option<TournamentRun>,
+    /// Players' records, best first (if found)
+    pub standings: Option<Vec<Standing>>,
+}
+
+/// Response for a match replay
+#[derive(Debug, Serialize)]
+pub struct ReplayResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Tick shown
+    pub tick: Option<u64>,
+    /// Ticks kept in the replay
+    pub ticks: Vec<u64>,
+    /// What each player's script got at that tick
+    pub players: Option<HashMap<String, serde_json::Value>>,
+}
+
+/// Bundles of every player with submitted code
+async fn submitted_bundles(state: &AppState) -> HashMap<String, ScriptBundle> {
+    let engine = state.script_engine.read().await;
+    engine.list_players()
+        .into_iter()
+        .filter_map(|player| engine.get_bundle(&player).cloned().map(|bundle| (player, bundle)))
+        .collect()
+}
+
+fn start_response(status: StatusCode, message: String, tournament_id: Option<String>) -> (StatusCode, Json<StartTournamentResponse>) {
+    (status, Json(StartTournamentResponse { success: tournament_id.is_some(), message, tournament_id }))
+}
+
+/// Handler to start a tournament between all players with submitted code
+///
+/// Pairs the players once. Returns immediately; matches run in parallel on blocking
+/// threads.
+pub async fn start_tournament_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+) -> impl IntoResponse {
+    if !state.is_admin(&session.username) {
+        return start_response(StatusCode::FORBIDDEN, "Admin access required".to_string(), None);
+    }
+
+    let bundles = submitted_bundles(&state).await;
+    let mut players: Vec<String> = bundles.keys().cloned().collect();
+    players.sort();
+
+    let run = match state.tournaments.write().await.create(players) {
+        Ok(run) => run,
+        Err(err) => return start_response(StatusCode::BAD_REQUEST, err, None),
+    };
+
+    log::info!("{} started tournament {} with {} players", session.username, run.id, run.players.len());
+    let message = format!("Tournament started with {} matches", run.expected_matches);
+    tokio::spawn(run_tournament(state, run.id.clone(), bundles, false));
+    start_response(StatusCode::OK, message, Some(run.id))
+}
+
+/// Handler to create a tournament between chosen players
+///
+/// Every player must have submitted code; their code at this moment is used for the
+/// whole tournament. Returns immediately; follow the tournament with
+/// `GET /api/tournament/:id`.
+pub async fn create_tournament_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<CreateTournamentRequest>,
+) -> impl IntoResponse {
+    if !state.is_admin(&session.username) {
+        return start_response(StatusCode::FORBIDDEN, "Admin access required".to_string(), None);
+    }
+
+    let mut bundles = submitted_bundles(&state).await;
+    let players = if payload.players.is_empty() {
+        let mut players: Vec<String> = bundles.keys().cloned().collect();
+        players.sort();
+        players
+    } else {
+        if let Some(player) = payload.players.iter().find(|player| !bundles.contains_key(*player)) {
+            return start_response(StatusCode::BAD_REQUEST, format!("{} has no submitted code", player), None);
+        }
+        bundles.retain(|player, _| payload.players.contains(player));
+        payload.players
+    };
+
+    let settings = MatchSettings {
+        max_ticks: match payload.settings.max_ticks {
+            Some(max_ticks) => max_ticks,
+            None => state.tournaments.read().await.max_ticks(),
+        },
+        seed: payload.settings.seed,
+    };
+    let run = match state.tournaments.write().await.create_with(players, payload.format, settings) {
+        Ok(run) => run,
+        Err(err) => return start_response(StatusCode::BAD_REQUEST, err, None),
+    };
+
+    log::info!("{} created {:?} tournament {} with {} players", session.username, run.format, run.id, run.players.len());
+    let message = format!("Tournament created with {} matches", run.expected_matches);
+    tokio::spawn(run_tournament(state, run.id.clone(), bundles, payload.sequential));
+    start_response(StatusCode::OK, message, Some(run.id))
+}
+
+/// Play a tournament round after round until it completes
+async fn run_tournament(state: AppState, tournament_id: String, bundles: HashMap<String, ScriptBundle>, sequential: bool) {
+    loop {
+        let Some(pairs) = state.tournaments.write().await.next_round(&tournament_id) else {
+            break;
+        };
+        let Some(settings) = state.tournaments.read().await.get(&tournament_id).map(TournamentRun::settings) else {
+            break;
+        };
+
+        let mut matches = Vec::new();
+        for (player_a, player_b) in pairs {
+            let bundle_a = bundles[&player_a].clone();
+            let bundle_b = bundles[&player_b].clone();
+            let game = tokio::spawn(play_match(
+                state.clone(),
+                tournament_id.clone(),
+                (player_a, bundle_a),
+                (player_b, bundle_b),
+                settings,
+            ));
+            if sequential {
+                let _ = game.await;
+            } else {
+                matches.push(game);
+            }
+        }
+        futures_util::future::join_all(matches).await;
+    }
+    log::info!("Tournament {} completed", tournament_id);
+}
+
+/// Play one tournament match on a blocking thread, record its result and report it
+async fn play_match(
+    state: AppState,
+    tournament_id: String,
+    (player_a, bundle_a): (String, ScriptBundle),
+    (player_b, bundle_b): (String, ScriptBundle),
+    settings: MatchSettings,
+) {
+    let auth_service = state.auth_service.clone();
+    let ws_clients = state.ws_clients.clone();
+    let (a, b) = (player_a.clone(), player_b.clone());
+    let result = tokio::task::spawn_blocking(move || {
+        // Players are looked up once; bots without an account earn no achievements
+        let user_ids: HashMap<String, i64> = [&a, &b].into_iter()
+            .filter_map(|player| match auth_service.get_user_by_username(player) {
+                Ok(user) => user.map(|user| (player.clone(), user.id)),
+                Err(err) => {
+                    log::warn!("Could not look up {}: {}", player, err);
+                    None
+                }
+            })
+            .collect();
+        let unlock = |player: &str, stats: &PlayerStats| {
+            if let Some(&user_id) = user_ids.get(player) {
+                unlock_and_notify(&auth_service, &ws_clients, user_id, stats);
+            }
+        };
+
+        let (mut result, replay) = run_match_with_progress((&a, &bundle_a), (&b, &bundle_b), settings, unlock);
+        match auth_service.record_match_result(&a, &b, result.outcome()) {
+            Ok(_) => result.recorded = true,
+            Err(err) => log::warn!("Could not record match {} vs {}: {}", a, b, err),
+        }
+
+        for (player, survived, opponent_error) in [(&a, result.survived_a, &result.error_b), (&b, result.survived_b, &result.error_a)] {
+            let matches_won = auth_service.matches_won(player).unwrap_or_else(|err| {
+                log::warn!("Could not count the wins of {}: {}", player, err);
+                0
+            });
+            unlock(player, &PlayerStats {
+                highest_tick: survived,
+                enemies_eliminated: u32::from(opponent_error.is_some()),
+                matches_won,
+                ..PlayerStats::default()
+            });
+        }
+        (result, replay)
+    }).await;
+
+    let (result, replay) = result.unwrap_or_else(|e| {
+        log::error!("Tournament match {} vs {} failed: {}", player_a, player_b, e);
+        (TournamentMatch::aborted(&player_a, &player_b, "Match failed"), ReplayHistory::new())
+    });
+    state.tournaments.write().await.report_match(&tournament_id, result, replay);
+}
+
+/// Status, results and standings of a tournament
+async fn tournament_status(state: &AppState, tournament_id: &str) -> (StatusCode, Json<TournamentStatusResponse>) {
+    match state.tournaments.read().await.get(tournament_id) {
+        Some(run) => (
+            StatusCode::OK,
+            Json(TournamentStatusResponse {
+                success: true,
+                message: format!("Tournament is {:?} ({}/{} matches played)", run.status, run.matches.len(), run.expected_matches),
+                standings: Some(run.standings()),
+                tournament: Some(run.clone()),
+            })
+        ),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(TournamentStatusResponse {
+                success: false,
+                message: format!("Tournament {} not found", tournament_id),
+                tournament: None,
+                standings: None,
+            })
+        ),
+    }
+}
+
+/// Handler to get a tournament's status and results (admin)
+pub async fn tournament_status_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path(tournament_id): Path<String>,
+) -> impl IntoResponse {
+    if !state.is_admin(&session.username) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(TournamentStatusResponse {
+                success: false,
+                message: "Admin access required".to_string(),
+                tournament: None,
+                standings: None,
+            })
+        );
+    }
+    tournament_status(&state, &tournament_id).await
+}
+
+/// Handler to get a tournament's standings and results, also while it runs
+pub async fn get_tournament_handler(
+    State(state): State<AppState>,
+    AuthSession(_session): AuthSession,
+    Path(tournament_id): Path<String>,
+) -> impl IntoResponse {
+    tournament_status(&state, &tournament_id).await
+}
+
+/// Handler to view a tournament match at one of the ticks kept in its replay
+pub async fn match_replay_handler(
+    State(state): State<AppState>,
+    AuthSession(_session): AuthSession,
+    Path((tournament_id, match_index)): Path<(String, usize)>,
+    Query(query): Query<ReplayQuery>,
+) -> impl IntoResponse {
+    let error = |status: StatusCode, message: String, ticks: Vec<u64>| (
+        status,
+        Json(ReplayResponse { success: false, message, tick: None, ticks, players: None }),
+    );
+
+    let tournaments = state.tournaments.read().await;
+    let Some(run) = tournaments.get(&tournament_id) else {
+        return error(StatusCode::NOT_FOUND, format!("Tournament {} not found", tournament_id), Vec::new());
+    };
+    let (Some(result), Some(replay)) = (run.matches.get(match_index), tournaments.replay(&tournament_id, match_index)) else {
+        return error(StatusCode::NOT_FOUND, format!("No replay for match {}", match_index), Vec::new());
+    };
+    let ticks = replay.ticks();
+    let tick = query.tick.or(ticks.last().copied()).unwrap_or_default();
+    let Some(snapshot) = replay.at(tick) else {
+        return error(StatusCode::NOT_FOUND, format!("Tick {} is not in the replay", tick), ticks);
+    };
+
+    let mut players = HashMap::new();
+    for player in [&result.player_a, &result.player_b] {
+        match snapshot.player_view(player) {
+            Ok(view) => {
+                players.insert(player.clone(), view);
+            }
+            Err(err) => return error(StatusCode::INTERNAL_SERVER_ERROR, err, ticks),
+        }
+    }
+    (
+        StatusCode::OK,
+        Json(ReplayResponse {
+            success: true,
+            message: format!("{} vs {} at tick {}", result.player_a, result.player_b, tick),
+            tick: Some(tick),
+            ticks,
+            players: Some(players),
+        })
+    )
+}
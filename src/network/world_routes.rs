@@ -0,0 +1,320 @@
+//! World routes module
+//!
+//! HTTP endpoints for the shared world: its configuration, the map of zones, and
+//! admin-only edits (portals between zones, weather, generating many zones at once).
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::config::FeatureFlag;
+use crate::game::weather::{WeatherEvent, WeatherEventType};
+use crate::game::world::{Portal, World, WorldConfig, ZoneSummary};
+use crate::network::extract::{ApiError, AuthSession, SizedJson};
+use crate::network::server::AppState;
+
+/// Handler to get the world dimensions and limits
+pub async fn world_config_handler(State(state): State<AppState>) -> Json<WorldConfig> {
+    Json(state.game_world.read().await.config().clone())
+}
+
+/// Response for the world map
+#[derive(Debug, Serialize)]
+pub struct MapResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Every zone, sorted by ID
+    pub zones: Vec<ZoneSummary>,
+}
+
+/// Handler to get the world map as seen by the caller
+///
+/// Zones owned by the caller or an ally include their unit and structure counts.
+pub async fn map_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+) -> impl IntoResponse {
+    let zones = state.game_world.read().await.map_for(&session.username);
+    (
+        StatusCode::OK,
+        Json(MapResponse {
+            success: true,
+            message: format!("{} zones", zones.len()),
+            zones,
+        })
+    )
+}
+
+/// Request to create a portal
+#[derive(Debug, Deserialize)]
+pub struct CreatePortalRequest {
+    /// Zone containing the portal tile
+    pub from_zone_id: String,
+    /// X coordinate of the portal tile
+    pub from_x: usize,
+    /// Y coordinate of the portal tile
+    pub from_y: usize,
+    /// Destination zone
+    pub to_zone_id: String,
+    /// X coordinate of the destination tile
+    pub to_x: usize,
+    /// Y coordinate of the destination tile
+    pub to_y: usize,
+}
+
+/// Response for portal operations
+#[derive(Debug, Serialize)]
+pub struct PortalResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Created or removed portal
+    pub portal: Option<Portal>,
+}
+
+fn portal_error(status: StatusCode, message: String) -> (StatusCode, Json<PortalResponse>) {
+    (
+        status,
+        Json(PortalResponse {
+            success: false,
+            message,
+            portal: None,
+        })
+    )
+}
+
+/// Handler to create a portal between two zones
+pub async fn create_portal_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<CreatePortalRequest>,
+) -> impl IntoResponse {
+    if !state.is_admin(&session.username) {
+        return portal_error(StatusCode::FORBIDDEN, "Admin access required".to_string());
+    }
+
+    let portal = Portal {
+        id: Uuid::new_v4(),
+        from_zone_id: payload.from_zone_id,
+        from_x: payload.from_x,
+        from_y: payload.from_y,
+        to_zone_id: payload.to_zone_id,
+        to_x: payload.to_x,
+        to_y: payload.to_y,
+    };
+
+    match state.game_world.write().await.add_portal(portal.clone()) {
+        Ok(()) => {
+            log::info!("{} created portal {} ({} -> {})", session.username, portal.id, portal.from_zone_id, portal.to_zone_id);
+            (
+                StatusCode::OK,
+                Json(PortalResponse {
+                    success: true,
+                    message: format!("Portal {} created", portal.id),
+                    portal: Some(portal),
+                })
+            )
+        }
+        Err(err) => portal_error(StatusCode::BAD_REQUEST, err),
+    }
+}
+
+/// Handler to remove a portal
+pub async fn delete_portal_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path(portal_id): Path<Uuid>,
+) -> impl IntoResponse {
+    if !state.is_admin(&session.username) {
+        return portal_error(StatusCode::FORBIDDEN, "Admin access required".to_string());
+    }
+
+    match state.game_world.write().await.remove_portal(portal_id) {
+        Some(portal) => (
+            StatusCode::OK,
+            Json(PortalResponse {
+                success: true,
+                message: format!("Portal {} removed", portal_id),
+                portal: Some(portal),
+            })
+        ),
+        None => portal_error(StatusCode::NOT_FOUND, format!("Portal {} not found", portal_id)),
+    }
+}
+
+/// Request to schedule a weather event
+#[derive(Debug, Deserialize)]
+pub struct ScheduleWeatherRequest {
+    /// Kind of event (`Rain`, `Fog`, `Storm`)
+    pub event_type: WeatherEventType,
+    /// Zone the event applies to
+    pub affected_zone_id: String,
+    /// First tick of the event (defaults to the current tick)
+    pub start_tick: Option<u64>,
+    /// Number of ticks the event lasts
+    pub duration_ticks: u64,
+    /// Strength of the event
+    pub magnitude: f32,
+}
+
+/// Response for weather operations
+#[derive(Debug, Serialize)]
+pub struct WeatherResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Scheduled event
+    pub event: Option<WeatherEvent>,
+}
+
+/// Handler to schedule a weather event on a zone
+pub async fn schedule_weather_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<ScheduleWeatherRequest>,
+) -> impl IntoResponse {
+    let error = |status: StatusCode, message: String| {
+        (
+            status,
+            Json(WeatherResponse {
+                success: false,
+                message,
+                event: None,
+            })
+        )
+    };
+
+    if !state.is_admin(&session.username) {
+        return error(StatusCode::FORBIDDEN, "Admin access required".to_string());
+    }
+
+    let mut world = state.game_world.write().await;
+    let event = WeatherEvent {
+        event_type: payload.event_type,
+        affected_zone_id: payload.affected_zone_id,
+        start_tick: payload.start_tick.unwrap_or_else(|| world.get_tick()),
+        duration_ticks: payload.duration_ticks,
+        magnitude: payload.magnitude,
+    };
+
+    match world.schedule_weather(event.clone()) {
+        Ok(()) => {
+            log::info!("{} scheduled {:?} on {} (ticks {}..{})", session.username, event.event_type, event.affected_zone_id, event.start_tick, event.end_tick());
+            (
+                StatusCode::OK,
+                Json(WeatherResponse {
+                    success: true,
+                    message: format!("{:?} scheduled on {}", event.event_type, event.affected_zone_id),
+                    event: Some(event),
+                })
+            )
+        }
+        Err(err) => error(StatusCode::BAD_REQUEST, err),
+    }
+}
+
+/// Most zones generated by one bulk generation request
+pub const MAX_BULK_ZONES: usize = 200;
+
+/// Request to generate many zones at once
+#[derive(Debug, Deserialize)]
+pub struct GenerateBulkRequest {
+    /// Number of zones to generate (1 to [`MAX_BULK_ZONES`])
+    pub count: usize,
+    /// Whether to link the zones in a grid through their exits, with portals (see
+    /// [`World::add_zone_grid`])
+    #[serde(default)]
+    pub connect_exits: bool,
+}
+
+/// Response after generating many zones at once
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GenerateBulkResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// IDs of the generated zones, in grid order
+    pub zone_ids: Vec<String>,
+    /// Portals created between the zones
+    pub portals: Vec<Portal>,
+}
+
+/// Handler to pre-generate zones, e.g. before players join (admin only)
+///
+/// Zones are generated in parallel on blocking threads, without holding the world lock,
+/// then added together.
+pub async fn generate_bulk_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedJson(payload): SizedJson<GenerateBulkRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if !state.is_admin(&session.username) {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, "Admin access required"));
+    }
+    if payload.count == 0 || payload.count > MAX_BULK_ZONES {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("count must be between 1 and {}", MAX_BULK_ZONES),
+        ));
+    }
+
+    if payload.connect_exits && !state.is_feature_enabled(FeatureFlag::Portals) {
+        return Err(ApiError::new(
+            StatusCode::NOT_IMPLEMENTED,
+            "The portals feature is disabled on this server, zones cannot be connected",
+        ));
+    }
+
+    let (zone_ids, config) = {
+        let world = state.game_world.read().await;
+        let max_zones = world.config().max_zones;
+        let zones = world.get_zone_ids().len();
+        if zones + payload.count > max_zones {
+            return Err(ApiError::new(
+                StatusCode::CONFLICT,
+                format!("World is full ({} zones): {} more do not fit next to the {} there", max_zones, payload.count, zones),
+            ));
+        }
+        (world.next_generated_zone_ids(payload.count), world.config().default_zone_config.clone())
+    };
+
+    let connect_exits = payload.connect_exits;
+    let tasks: Vec<_> = zone_ids.iter()
+        .map(|zone_id| {
+            let (zone_id, config) = (zone_id.clone(), config.clone());
+            tokio::task::spawn_blocking(move || World::generate_grid_zone(&zone_id, &config, connect_exits))
+        })
+        .collect();
+    let mut zones = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        let zone = task.await
+            .map_err(|e| format!("Zone generation task failed: {}", e))
+            .and_then(|zone| zone)
+            .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        zones.push(zone);
+    }
+
+    let portals = state.game_world.write().await.add_zone_grid(zones, connect_exits)
+        .map_err(|e| ApiError::new(StatusCode::CONFLICT, e))?;
+    log::info!("{} generated {} zones ({} portals)", session.username, zone_ids.len(), portals.len());
+
+    Ok((
+        StatusCode::CREATED,
+        Json(GenerateBulkResponse {
+            success: true,
+            message: format!("{} zones generated", zone_ids.len()),
+            zone_ids,
+            portals,
+        })
+    ))
+}
@@ -0,0 +1,108 @@
+//! WebSocket clients module
+//!
+//! Registry of authenticated WebSocket connections by user ID, used to push
+//! server-initiated messages (e.g. lobby events) to specific users or to everyone.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use dashmap::DashMap;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::network::ws_codec::Outgoing;
+
+/// Outgoing queues of authenticated WebSocket connections
+#[derive(Debug, Default)]
+pub struct WsClients {
+    next_id: AtomicU64,
+    senders: DashMap<i64, Vec<(u64, UnboundedSender<Outgoing>)>>,
+}
+
+/// A registered connection; it is unregistered when this is dropped
+#[derive(Debug)]
+pub struct ClientRegistration {
+    clients: Arc<WsClients>,
+    user_id: i64,
+    id: u64,
+}
+
+impl WsClients {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a connection's outgoing queue for `user_id`
+    pub fn register(self: &Arc<Self>, user_id: i64, sender: UnboundedSender<Outgoing>) -> ClientRegistration {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.senders.entry(user_id).or_default().push((id, sender));
+        ClientRegistration {
+            clients: self.clone(),
+            user_id,
+            id,
+        }
+    }
+
+    /// Send a JSON message to every connection of a user; returns the number of connections reached
+    pub fn send_to_user(&self, user_id: i64, message: &serde_json::Value) -> usize {
+        let Some(senders) = self.senders.get(&user_id) else {
+            return 0;
+        };
+
+        senders.iter()
+            .filter(|(_, sender)| sender.send(Outgoing::Json(message.clone())).is_ok())
+            .count()
+    }
+
+    /// Send a JSON message to every registered connection; returns the number of connections reached
+    pub fn broadcast(&self, message: &serde_json::Value) -> usize {
+        self.senders.iter()
+            .map(|senders| {
+                senders.iter()
+                    .filter(|(_, sender)| sender.send(Outgoing::Json(message.clone())).is_ok())
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Close every connection of a user; returns the number of connections closed
+    pub fn disconnect_user(&self, user_id: i64) -> usize {
+        let Some(senders) = self.senders.get(&user_id) else {
+            return 0;
+        };
+
+        senders.iter()
+            .filter(|(_, sender)| sender.send(Outgoing::Close).is_ok())
+            .count()
+    }
+
+    /// Close every registered connection; returns the number of connections closed
+    pub fn disconnect_all(&self) -> usize {
+        self.senders.iter()
+            .map(|senders| {
+                senders.iter()
+                    .filter(|(_, sender)| sender.send(Outgoing::Close).is_ok())
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Whether a user has at least one registered connection
+    pub fn is_connected(&self, user_id: i64) -> bool {
+        self.senders.contains_key(&user_id)
+    }
+
+    /// Number of connected users and of connections
+    pub fn counts(&self) -> (usize, usize) {
+        let connections = self.senders.iter().map(|senders| senders.len()).sum();
+        (self.senders.len(), connections)
+    }
+}
+
+impl Drop for ClientRegistration {
+    fn drop(&mut self) {
+        self.clients.senders.remove_if_mut(&self.user_id, |_, senders| {
+            senders.retain(|(id, _)| *id != self.id);
+            senders.is_empty()
+        });
+    }
+}
@@ -0,0 +1,86 @@
+//! WebSocket codec module
+//!
+//! Frame encoding for WebSocket connections. Clients pick an encoding with the
+//! `"encoding"` field of `auth` or `spectate` (`"json"` or `"msgpack"`); starting with
+//! that command's response, everything the server sends uses it. With `msgpack`, each server message
+//! is one `Binary` frame holding the MessagePack encoding of the same object that would
+//! be sent as JSON (string keys, same field names). Clients may send commands as JSON
+//! text frames or MessagePack binary frames in either mode.
+
+use axum::extract::ws::Message;
+use serde::{Deserialize, Serialize};
+
+/// Encoding of server-to-client frames
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WireEncoding {
+    /// JSON text frames
+    #[default]
+    Json,
+    /// MessagePack binary frames
+    MsgPack,
+}
+
+impl WireEncoding {
+    /// Parse an encoding name as sent by clients
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(WireEncoding::Json),
+            "msgpack" | "messagepack" => Ok(WireEncoding::MsgPack),
+            other => Err(format!("Unsupported encoding: {} (supported: json, msgpack)", other)),
+        }
+    }
+
+    /// Encode a message for the wire
+    pub fn encode(&self, message: &serde_json::Value) -> Message {
+        match self {
+            WireEncoding::Json => Message::Text(message.to_string()),
+            WireEncoding::MsgPack => match rmp_serde::to_vec_named(message) {
+                Ok(bytes) => Message::Binary(bytes),
+                // Values built from JSON always encode; fall back to text just in case
+                Err(_) => Message::Text(message.to_string()),
+            },
+        }
+    }
+}
+
+/// An item in a connection's outgoing queue
+///
+/// Messages are encoded by the connection's writer, so pushed topics (spectator
+/// streams, lobby events) follow the connection's encoding.
+#[derive(Debug, Clone)]
+pub enum Outgoing {
+    /// A message object to encode and send
+    Json(serde_json::Value),
+    /// Switch the encoding of the messages that follow
+    Encoding(WireEncoding),
+    /// Close the connection
+    Close,
+}
+
+/// Decode a binary (MessagePack) command frame from a client
+pub fn decode_binary(bytes: &[u8]) -> Result<serde_json::Value, String> {
+    let value: serde_json::Value = rmp_serde::from_slice(bytes)
+        .map_err(|e| format!("Malformed binary frame: {}", e))?;
+
+    if !value.is_object() {
+        return Err("Malformed binary frame: expected a map".to_string());
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_msgpack_round_trip() {
+        let message = serde_json::json!({"type": "gameStateResponse", "tick": 42, "players": ["alice"]});
+        let Message::Binary(bytes) = WireEncoding::MsgPack.encode(&message) else {
+            panic!("expected a binary frame");
+        };
+        assert_eq!(decode_binary(&bytes).unwrap(), message);
+        assert!(decode_binary(&[0xc1]).unwrap_err().contains("Malformed binary frame"));
+        assert!(WireEncoding::from_name("xml").is_err());
+    }
+}
@@ -0,0 +1,84 @@
+//! Zone response cache
+//!
+//! `GET /api/zone/:zone_id` is the most requested endpoint, and a popular zone is
+//! serialized again for every player polling it. [`ZoneCache`] keeps the JSON body and
+//! ETag of the last `zone_cache_size` zones served, least recently used first out.
+//!
+//! Entries are stamped with [`Zone::version`]: the world gives a zone a new version
+//! whenever it changes it (units moving, resources harvested, captures...), so a
+//! changed zone no longer matches its entry, which is dropped on the next lookup.
+
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+
+use lru::LruCache;
+
+use crate::game::zone::Zone;
+
+/// Serialized response of a zone
+#[derive(Debug, Clone)]
+pub struct CachedZone {
+    /// Version of the zone the response was built from
+    pub version: u64,
+    /// Weak ETag of the body
+    pub etag: String,
+    /// JSON body, shared with every response serving it
+    pub body: Arc<String>,
+}
+
+/// Serialized zone responses of the most recently served zones
+#[derive(Clone)]
+pub struct ZoneCache {
+    entries: Arc<Mutex<LruCache<String, CachedZone>>>,
+}
+
+impl ZoneCache {
+    /// Cache holding up to `capacity` zones (at least one)
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self { entries: Arc::new(Mutex::new(LruCache::new(capacity))) }
+    }
+
+    /// Response cached for the current version of a zone
+    ///
+    /// An entry built from an older version is removed.
+    pub fn get(&self, zone: &Zone) -> Option<CachedZone> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(&zone.id) {
+            Some(cached) if cached.version == zone.version => Some(cached.clone()),
+            Some(_) => {
+                entries.pop(&zone.id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Cache a zone's response, evicting the least recently used zone when full
+    pub fn insert(&self, zone: &Zone, etag: String, body: Arc<String>) -> CachedZone {
+        let cached = CachedZone { version: zone.version, etag, body };
+        self.entries.lock().unwrap().put(zone.id.clone(), cached.clone());
+        cached
+    }
+
+    /// Whether a response is cached for a zone, whatever its version
+    pub fn contains(&self, zone_id: &str) -> bool {
+        self.entries.lock().unwrap().contains(zone_id)
+    }
+
+    /// Number of zones cached
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Whether no zone is cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for ZoneCache {
+    fn default() -> Self {
+        Self::new(crate::config::ZONE_CACHE_SIZE)
+    }
+}
@@ -1,56 +1,127 @@
 //! Zone routes module
 //! 
-//! HTTP endpoint handlers for zone generation and retrieval.
+//! HTTP endpoint handlers for zone generation, retrieval, capture, export files, and
+//! admin edits of a zone's terrain (see [`crate::game::zone::edit`]).
 
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
+use base64::Engine as _;
 use serde::{Deserialize, Serialize};
 
-use crate::game::zone::Zone;
+use crate::auth::models::Session;
+use crate::game::world::{CaptureError, EditZoneError, World};
+use crate::game::zone::edit::{RejectedEdit, ZoneEdit};
+use crate::game::zone::export::ZoneExport;
+use crate::game::zone::{Tile, Zone, ZoneGenConfig};
+use crate::network::extract::{IMPORT_BODY_LIMIT, AuthSession, SizedBody, SizedJson};
+use crate::network::etag::conditional_zone;
+use crate::network::pagination::{PaginatedResponse, PaginationQuery};
 use crate::network::server::AppState;
 
 /// Request to generate a new zone
 #[derive(Debug, Deserialize)]
 pub struct GenerateZoneRequest {
+    /// Player identifier
     pub player_id: String,
+    /// Custom generation parameters (admins only; defaults otherwise)
+    #[serde(default)]
+    pub config: Option<ZoneGenConfig>,
 }
 
 /// Response for zone generation
 #[derive(Debug, Serialize)]
 pub struct GenerateZoneResponse {
+    /// Whether the operation succeeded
     pub success: bool,
+    /// Response message
     pub message: String,
+    /// Generated zone identifier (if successful)
     pub zone_id: Option<String>,
 }
 
 /// Response for getting a zone
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GetZoneResponse {
+    /// Whether the operation succeeded
     pub success: bool,
+    /// Response message
     pub message: String,
+    /// Zone data (if found)
     pub zone: Option<Zone>,
 }
 
-/// Response for listing all zones
+/// Response for a zone's owner (and for captures)
 #[derive(Debug, Serialize)]
-pub struct ListZonesResponse {
+pub struct ZoneOwnerResponse {
+    /// Whether the operation succeeded
     pub success: bool,
+    /// Response message
     pub message: String,
-    pub zone_ids: Vec<String>,
+    /// Zone identifier
+    pub zone_id: String,
+    /// Player owning the zone (if any)
+    pub owner: Option<String>,
+}
+
+/// Session of the request's bearer token (zone routes are public, so the middleware does not check it)
+async fn bearer_session(state: &AppState, headers: &HeaderMap) -> Option<Session> {
+    let token = headers.get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|header| header.strip_prefix("Bearer "))?;
+    state.validate_token(token).await
+}
+
+fn generate_error(status: StatusCode, message: String) -> (StatusCode, Json<GenerateZoneResponse>) {
+    (
+        status,
+        Json(GenerateZoneResponse {
+            success: false,
+            message,
+            zone_id: None,
+        })
+    )
 }
 
 /// Handler to generate a new zone for a player
+///
+/// The endpoint is public, but a custom `config` requires an admin bearer token. A
+/// zone with unlock requirements the player has not cleared yet is refused with `403`.
 pub async fn generate_zone_handler(
     State(state): State<AppState>,
-    Json(payload): Json<GenerateZoneRequest>,
+    headers: HeaderMap,
+    SizedJson(payload): SizedJson<GenerateZoneRequest>,
 ) -> impl IntoResponse {
+    let config = match payload.config {
+        Some(config) => {
+            let is_admin = bearer_session(&state, &headers).await
+                .is_some_and(|session| state.is_admin(&session.username));
+            if !is_admin {
+                return generate_error(StatusCode::FORBIDDEN, "Admin access required for custom zone configuration".to_string());
+            }
+            config
+        }
+        None => ZoneGenConfig::default(),
+    };
+
     let mut world = state.game_world.write().await;
+
+    let zone_id = World::player_zone_id(&payload.player_id);
+    if !world.is_zone_unlocked(&payload.player_id, &zone_id) {
+        let missing: Vec<&str> = world.zone_unlock_requirements(&zone_id).iter()
+            .filter(|prerequisite| !world.is_zone_cleared(&payload.player_id, prerequisite))
+            .map(String::as_str)
+            .collect();
+        return generate_error(StatusCode::FORBIDDEN, format!("Zone {} is locked: clear {} first", zone_id, missing.join(", ")));
+    }
     
-    let zone_id = world.generate_player_zone(&payload.player_id);
+    let zone_id = match world.generate_player_zone_with_config(&payload.player_id, &config) {
+        Ok(zone_id) => zone_id,
+        Err(err) => return generate_error(StatusCode::BAD_REQUEST, err),
+    };
     
     log::info!("Generated zone {} for player {}", zone_id, payload.player_id);
     
@@ -65,23 +136,22 @@ pub async fn generate_zone_handler(
 }
 
 /// Handler to get a specific zone by ID
+///
+/// Answers `304 Not Modified` to an `If-None-Match` with the zone's current ETag.
 pub async fn get_zone_handler(
     State(state): State<AppState>,
     Path(zone_id): Path<String>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    let world = state.game_world.read().await;
-    
-    match world.get_zone(&zone_id) {
-        Some(zone) => {
-            (
-                StatusCode::OK,
-                Json(GetZoneResponse {
-                    success: true,
-                    message: format!("Zone {} retrieved successfully", zone_id),
-                    zone: Some(zone.clone()),
-                })
-            )
-        }
+    let response = state.read_zone(&zone_id, |zone| {
+        conditional_zone(&state.zone_cache, &headers, zone, || GetZoneResponse {
+            success: true,
+            message: format!("Zone {} retrieved successfully", zone_id),
+            zone: Some(zone.clone()),
+        })
+    }).await;
+    match response {
+        Some(response) => response,
         None => {
             (
                 StatusCode::NOT_FOUND,
@@ -90,25 +160,402 @@ pub async fn get_zone_handler(
                     message: format!("Zone {} not found", zone_id),
                     zone: None,
                 })
+            ).into_response()
+        }
+    }
+}
+
+/// Handler to download a zone as a shareable file (see [`crate::game::zone::export`])
+pub async fn export_zone_handler(
+    State(state): State<AppState>,
+    Path(zone_id): Path<String>,
+) -> Response {
+    let now = chrono::Utc::now().timestamp();
+    let Some(export) = state.read_zone(&zone_id, |zone| zone.export(now)).await else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(GetZoneResponse {
+                success: false,
+                message: format!("Zone {} not found", zone_id),
+                zone: None,
+            })
+        ).into_response();
+    };
+
+    let disposition = format!("attachment; filename=\"{}.json\"", zone_id);
+    ([(header::CONTENT_DISPOSITION, disposition)], Json(export)).into_response()
+}
+
+/// Handler to install an exported zone under a new ID (admin bearer token required)
+///
+/// Existing zones are never replaced.
+pub async fn import_zone_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    SizedBody(body): SizedBody<IMPORT_BODY_LIMIT>,
+) -> impl IntoResponse {
+    if !state.is_admin(&session.username) {
+        return generate_error(StatusCode::FORBIDDEN, "Admin access required".to_string());
+    }
+
+    let export = match ZoneExport::parse(&body) {
+        Ok(export) => export,
+        Err(err) => return generate_error(StatusCode::BAD_REQUEST, err),
+    };
+    match state.game_world.write().await.import_zone(&export) {
+        Ok(zone_id) => {
+            log::info!("{} imported zone {} as {}", session.username, export.metadata.source_zone_id, zone_id);
+            (
+                StatusCode::OK,
+                Json(GenerateZoneResponse {
+                    success: true,
+                    message: format!("Zone {} imported as {}", export.metadata.source_zone_id, zone_id),
+                    zone_id: Some(zone_id),
+                })
             )
         }
+        Err(err) => generate_error(StatusCode::BAD_REQUEST, err),
     }
 }
 
-/// Handler to list all zones
+/// Zone of a registered user, generating it if missing (see [`World::ensure_player_zone`])
+///
+/// [`World::ensure_player_zone`]: crate::game::world::World::ensure_player_zone
+pub async fn ensure_user_zone(state: &AppState, username: &str) -> Result<String, String> {
+    let user = state.auth_service.get_user_by_username(username)?
+        .ok_or_else(|| format!("User {} not found", username))?;
+    state.game_world.write().await.ensure_player_zone(user.id, &user.username)
+}
+
+/// Handler to get the caller's zone, generating it if missing (bearer token required)
+pub async fn my_zone_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let error = |status: StatusCode, message: String| {
+        (
+            status,
+            Json(GetZoneResponse {
+                success: false,
+                message,
+                zone: None,
+            })
+        )
+    };
+
+    let Some(session) = bearer_session(&state, &headers).await else {
+        return error(StatusCode::UNAUTHORIZED, "Authentication required".to_string());
+    };
+    let zone_id = match ensure_user_zone(&state, &session.username).await {
+        Ok(zone_id) => zone_id,
+        Err(err) => return error(StatusCode::SERVICE_UNAVAILABLE, err),
+    };
+
+    match state.read_zone(&zone_id, Zone::clone).await {
+        Some(zone) => (
+            StatusCode::OK,
+            Json(GetZoneResponse {
+                success: true,
+                message: format!("Zone {} retrieved successfully", zone_id),
+                zone: Some(zone),
+            })
+        ),
+        None => error(StatusCode::NOT_FOUND, format!("Zone {} not found", zone_id)),
+    }
+}
+
+/// Handler to list zone IDs, sorted, one page at a time
 pub async fn list_zones_handler(
     State(state): State<AppState>,
+    Query(query): Query<PaginationQuery>,
 ) -> impl IntoResponse {
-    let world = state.game_world.read().await;
-    
-    let zone_ids = world.get_zone_ids();
-    
+    let zone_ids = state.zone_ids().await;
+
+    (StatusCode::OK, Json(PaginatedResponse::from_items(zone_ids, &query)))
+}
+
+/// Tiles returned per batch when `limit` is not given
+pub const DEFAULT_TILE_BATCH: usize = 100;
+
+/// Maximum tiles returned per batch
+pub const MAX_TILE_BATCH: usize = 1000;
+
+/// Query parameters for a batch of zone tiles
+#[derive(Debug, Default, Deserialize)]
+pub struct ZoneTilesQuery {
+    /// Position of the first tile (from a previous `next_cursor`; the zone's first tile by default)
+    pub cursor: Option<String>,
+    /// Maximum tiles returned (default [`DEFAULT_TILE_BATCH`], at most [`MAX_TILE_BATCH`])
+    pub limit: Option<usize>,
+}
+
+/// Response for a batch of zone tiles
+#[derive(Debug, Serialize)]
+pub struct ZoneTilesResponse {
+    /// Whether the operation succeeded
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Tiles in row-major order
+    pub tiles: Vec<Tile>,
+    /// Cursor of the next batch (`None` after the last tile)
+    pub next_cursor: Option<String>,
+}
+
+/// Encode a tile position as a cursor (base64 of `"x,y"`)
+pub fn encode_tile_cursor(x: usize, y: usize) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(format!("{},{}", x, y))
+}
+
+/// Decode a cursor made by [`encode_tile_cursor`]
+pub fn decode_tile_cursor(cursor: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("Invalid cursor: {}", cursor);
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+    let text = String::from_utf8(bytes).map_err(|_| invalid())?;
+    let (x, y) = text.split_once(',').ok_or_else(invalid)?;
+    Ok((x.parse().map_err(|_| invalid())?, y.parse().map_err(|_| invalid())?))
+}
+
+/// Handler to get a batch of a zone's tiles, starting at a cursor
+pub async fn zone_tiles_handler(
+    State(state): State<AppState>,
+    Path(zone_id): Path<String>,
+    Query(query): Query<ZoneTilesQuery>,
+) -> impl IntoResponse {
+    let error = |status: StatusCode, message: String| {
+        (
+            status,
+            Json(ZoneTilesResponse {
+                success: false,
+                message,
+                tiles: Vec::new(),
+                next_cursor: None,
+            })
+        )
+    };
+
+    let (x, y) = match query.cursor.as_deref().map(decode_tile_cursor).transpose() {
+        Ok(start) => start.unwrap_or((0, 0)),
+        Err(err) => return error(StatusCode::BAD_REQUEST, err),
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_TILE_BATCH).clamp(1, MAX_TILE_BATCH);
+
+    let batch = state.read_zone(&zone_id, |zone| {
+        if x >= zone.width || y >= zone.height {
+            return None;
+        }
+        let (tiles, next) = zone.get_tiles_paginated(x, y, limit);
+        Some((tiles.into_iter().cloned().collect::<Vec<Tile>>(), next))
+    }).await;
+    let (tiles, next) = match batch {
+        Some(Some(batch)) => batch,
+        Some(None) => return error(StatusCode::BAD_REQUEST, format!("Cursor ({}, {}) is outside zone {}", x, y, zone_id)),
+        None => return error(StatusCode::NOT_FOUND, format!("Zone {} not found", zone_id)),
+    };
     (
         StatusCode::OK,
-        Json(ListZonesResponse {
+        Json(ZoneTilesResponse {
             success: true,
-            message: format!("Found {} zones", zone_ids.len()),
-            zone_ids,
+            message: format!("{} tiles of zone {} from ({}, {})", tiles.len(), zone_id, x, y),
+            tiles,
+            next_cursor: next.map(|(x, y)| encode_tile_cursor(x, y)),
         })
     )
 }
+
+/// Handler to get the owner of a zone
+pub async fn zone_owner_handler(
+    State(state): State<AppState>,
+    Path(zone_id): Path<String>,
+) -> impl IntoResponse {
+    match state.read_zone(&zone_id, |zone| zone.owner.clone()).await {
+        Some(owner) => (
+            StatusCode::OK,
+            Json(ZoneOwnerResponse {
+                success: true,
+                message: match &owner {
+                    Some(owner) => format!("Zone {} is owned by {}", zone_id, owner),
+                    None => format!("Zone {} has no owner", zone_id),
+                },
+                owner,
+                zone_id,
+            })
+        ),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(ZoneOwnerResponse {
+                success: false,
+                message: format!("Zone {} not found", zone_id),
+                zone_id,
+                owner: None,
+            })
+        ),
+    }
+}
+
+/// Handler to capture a zone for the authenticated player
+///
+/// Requires a bearer token. On success, every WebSocket client is sent a
+/// `zoneCaptured` event.
+pub async fn capture_zone_handler(
+    State(state): State<AppState>,
+    Path(zone_id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let Some(session) = bearer_session(&state, &headers).await else {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(ZoneOwnerResponse {
+                success: false,
+                message: "Authentication required".to_string(),
+                zone_id,
+                owner: None,
+            })
+        );
+    };
+
+    let mut world = state.game_world.write().await;
+    let previous_owner = world.zone_owner(&zone_id);
+
+    if let Err(err) = world.capture_zone(&zone_id, &session.username) {
+        let status = match err {
+            CaptureError::ZoneNotFound(_) => StatusCode::NOT_FOUND,
+            CaptureError::InsufficientPresence | CaptureError::Contested => StatusCode::CONFLICT,
+        };
+        return (
+            status,
+            Json(ZoneOwnerResponse {
+                success: false,
+                message: err.to_string(),
+                zone_id,
+                owner: previous_owner,
+            })
+        );
+    }
+    // Unchanged if the player's team already held the zone
+    let owner = world.zone_owner(&zone_id);
+    drop(world);
+
+    if owner != previous_owner {
+        log::info!("{} captured zone {}", session.username, zone_id);
+        state.ws_clients.broadcast(&serde_json::json!({
+            "type": "zoneCaptured",
+            "zone_id": zone_id,
+            "new_owner": session.username,
+        }));
+    }
+
+    (
+        StatusCode::OK,
+        Json(ZoneOwnerResponse {
+            success: true,
+            message: match &owner {
+                Some(owner) if *owner != session.username => format!("Zone {} is already held by teammate {}", zone_id, owner),
+                _ => format!("Zone {} captured by {}", zone_id, session.username),
+            },
+            zone_id,
+            owner,
+        })
+    )
+}
+
+/// Request to edit a zone (admin only)
+#[derive(Debug, Deserialize)]
+pub struct EditZoneRequest {
+    /// Edits, applied in order, all or none
+    pub changes: Vec<ZoneEdit>,
+    /// Apply the edits even if they cut the zone's exits off from each other
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Response after editing a zone
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EditZoneResponse {
+    /// Whether the edits were applied
+    pub success: bool,
+    /// Response message
+    pub message: String,
+    /// Zone identifier
+    pub zone_id: String,
+    /// Edits refused, with the reason (none were applied if any is listed)
+    pub rejected: Vec<RejectedEdit>,
+}
+
+/// Apply the edits of a request to a zone, refusing those `allowed` rejects
+async fn edit_zone(
+    state: &AppState,
+    session: &Session,
+    zone_id: String,
+    payload: EditZoneRequest,
+    allowed: fn(&ZoneEdit) -> Result<(), String>,
+) -> (StatusCode, Json<EditZoneResponse>) {
+    let response = |status: StatusCode, message: String, zone_id: String, rejected: Vec<RejectedEdit>| {
+        (
+            status,
+            Json(EditZoneResponse {
+                success: status.is_success(),
+                message,
+                zone_id,
+                rejected,
+            })
+        )
+    };
+
+    if !state.is_admin(&session.username) {
+        return response(StatusCode::FORBIDDEN, "Admin access required".to_string(), zone_id, Vec::new());
+    }
+    let rejected: Vec<RejectedEdit> = payload.changes.iter()
+        .filter_map(|edit| allowed(edit).err().map(|reason| RejectedEdit { edit: edit.clone(), reason }))
+        .collect();
+    if !rejected.is_empty() {
+        return response(StatusCode::BAD_REQUEST, format!("{} edits rejected, none applied", rejected.len()), zone_id, rejected);
+    }
+
+    match state.game_world.write().await.edit_zone(&zone_id, &payload.changes, payload.force) {
+        Ok(()) => {
+            log::info!("{} applied {} edits to zone {}", session.username, payload.changes.len(), zone_id);
+            let message = format!("{} edits applied to zone {}", payload.changes.len(), zone_id);
+            response(StatusCode::OK, message, zone_id, Vec::new())
+        }
+        Err(EditZoneError::ZoneNotFound(_)) => {
+            response(StatusCode::NOT_FOUND, format!("Zone {} not found", zone_id), zone_id, Vec::new())
+        }
+        Err(EditZoneError::Rejected(rejected)) => {
+            let message = format!("{} edits rejected, none applied", rejected.len());
+            response(StatusCode::BAD_REQUEST, message, zone_id, rejected)
+        }
+    }
+}
+
+/// Handler to change tile surfaces and add resource deposits in a zone (admin only)
+///
+/// Takes `set_tile` and `add_resource` edits (see [`ZoneEdit`]). Either every edit is
+/// applied or none is, and the refused ones are listed with the reason.
+pub async fn edit_zone_tiles_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path(zone_id): Path<String>,
+    SizedJson(payload): SizedJson<EditZoneRequest>,
+) -> impl IntoResponse {
+    edit_zone(&state, &session, zone_id, payload, |edit| match edit {
+        ZoneEdit::SetTile { .. } | ZoneEdit::AddResource { .. } => Ok(()),
+        _ => Err("Exits are edited through PATCH /api/admin/zone/:id/exits".to_string()),
+    }).await
+}
+
+/// Handler to add and remove the exits of a zone (admin only)
+///
+/// Takes `add_exit` and `remove_exit` edits (see [`ZoneEdit`]). Either every edit is
+/// applied or none is, and the refused ones are listed with the reason.
+pub async fn edit_zone_exits_handler(
+    State(state): State<AppState>,
+    AuthSession(session): AuthSession,
+    Path(zone_id): Path<String>,
+    SizedJson(payload): SizedJson<EditZoneRequest>,
+) -> impl IntoResponse {
+    edit_zone(&state, &session, zone_id, payload, |edit| match edit {
+        ZoneEdit::AddExit { .. } | ZoneEdit::RemoveExit { .. } => Ok(()),
+        _ => Err("Tiles and deposits are edited through PATCH /api/admin/zone/:id/tiles".to_string()),
+    }).await
+}
@@ -0,0 +1,142 @@
+//! Script API versions
+//!
+//! The `game` object evolves: methods get renamed or removed. A bot is bound to one API
+//! version, declared with a `// @api v1` pragma (`-- @api v1` in Lua) at the top of its
+//! entry module or with the `api_version` of its submission, and otherwise to the
+//! latest version at the time it is submitted. The version is kept with the code until
+//! the bot is resubmitted.
+//!
+//! The bindings are written for [`LATEST_API_VERSION`]; a compatibility layer then gives
+//! the `game` object the shape of the bot's version, as described by [`API_CHANGES`]:
+//! older bots still reach renamed methods under their old name and removed methods
+//! through shims, while newer bots calling an old name get an error naming its
+//! replacement.
+
+use serde::Serialize;
+
+/// Current version of the bot API
+pub const LATEST_API_VERSION: u32 = 2;
+
+/// Oldest version the compatibility layer can present
+pub const OLDEST_API_VERSION: u32 = 1;
+
+/// What happened to a method of the `game` object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiChangeKind {
+    /// The method has a new name and the same behavior
+    Renamed,
+    /// The method is gone; older versions get it from a shim
+    Removed,
+}
+
+/// A change of the `game` object between two API versions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ApiChange {
+    /// First version with the change
+    pub version: u32,
+    /// Method name before the change
+    pub name: &'static str,
+    /// New name of a renamed method, or what to call instead of a removed one
+    pub replacement: &'static str,
+    /// Whether the method was renamed or removed
+    pub kind: ApiChangeKind,
+}
+
+impl ApiChange {
+    /// Error raised when a bot bound to this version or a later one calls the old method
+    pub fn upgrade_error(&self) -> String {
+        match self.kind {
+            ApiChangeKind::Renamed => format!(
+                "Upgraded API v{}: game.{}() was renamed to game.{}()",
+                self.version, self.name, self.replacement
+            ),
+            ApiChangeKind::Removed => format!(
+                "Upgraded API v{}: game.{}() was removed, use game.{} instead",
+                self.version, self.name, self.replacement
+            ),
+        }
+    }
+}
+
+/// Every change of the `game` object, oldest first
+pub const API_CHANGES: &[ApiChange] = &[
+    ApiChange { version: 2, name: "inbox", replacement: "peekMessages", kind: ApiChangeKind::Renamed },
+    ApiChange { version: 2, name: "moveUnit", replacement: "getUnitById(id).moveTo(position)", kind: ApiChangeKind::Removed },
+];
+
+/// Check that the compatibility layer can present an API version
+pub fn check_api_version(version: u32) -> Result<u32, String> {
+    if (OLDEST_API_VERSION..=LATEST_API_VERSION).contains(&version) {
+        Ok(version)
+    } else {
+        Err(format!(
+            "Unsupported API version {} (supported: v{} to v{})",
+            version, OLDEST_API_VERSION, LATEST_API_VERSION
+        ))
+    }
+}
+
+/// API version declared by an `@api` pragma in the leading comments of a source
+///
+/// The pragma is a line comment (`//` or `--`) reading `@api v<N>`, before the first line
+/// of code.
+pub fn parse_pragma(source: &str) -> Result<Option<u32>, String> {
+    for line in source.lines().map(str::trim) {
+        let Some(comment) = line.strip_prefix("//").or_else(|| line.strip_prefix("--")) else {
+            if line.is_empty() {
+                continue;
+            }
+            break;
+        };
+        let Some(declared) = comment.trim().strip_prefix("@api")
+            .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace)) else {
+            continue;
+        };
+        let declared = declared.trim();
+        let version = declared.strip_prefix('v').unwrap_or(declared).parse()
+            .map_err(|_| format!("Invalid @api pragma '{}': expected e.g. // @api v{}", line, LATEST_API_VERSION))?;
+        return check_api_version(version).map(Some);
+    }
+    Ok(None)
+}
+
+/// The changes and their errors, newest first, as passed to the compatibility layer of each runtime
+pub fn changes_json() -> serde_json::Value {
+    API_CHANGES.iter().rev()
+        .map(|change| {
+            let mut value = serde_json::to_value(change).unwrap_or_default();
+            value["error"] = serde_json::Value::String(change.upgrade_error());
+            value
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pragma() {
+        assert_eq!(parse_pragma("// @api v1\nmodule.exports = {};"), Ok(Some(1)));
+        assert_eq!(parse_pragma("-- Lua bot\n-- @api 2\nreturn {}"), Ok(Some(2)));
+        assert_eq!(parse_pragma("\n// a bot\n\n// @api v1"), Ok(Some(1)));
+        assert_eq!(parse_pragma("const x = 1;\n// @api v1"), Ok(None));
+        assert_eq!(parse_pragma("game.getMyUnits();"), Ok(None));
+        assert_eq!(parse_pragma("// @apiary notes
+return {}"), Ok(None));
+        assert!(parse_pragma("// @api v9").unwrap_err().contains("Unsupported API version 9"));
+        assert!(parse_pragma("// @api latest").unwrap_err().starts_with("Invalid @api pragma"));
+    }
+
+    #[test]
+    fn test_changes_follow_versions() {
+        let mut previous = OLDEST_API_VERSION;
+        for change in API_CHANGES {
+            assert!(change.version > OLDEST_API_VERSION && change.version <= LATEST_API_VERSION);
+            assert!(change.version >= previous, "{} is out of order", change.name);
+            previous = change.version;
+        }
+        assert_eq!(changes_json()[1]["error"], "Upgraded API v2: game.inbox() was renamed to game.peekMessages()");
+    }
+}
@@ -0,0 +1,270 @@
+//! Script bundles
+//!
+//! A player's code is a bundle of named JavaScript modules (`main.js`, `utils/path.js`, ...).
+//! The legacy single-string submission is a bundle containing only `main.js`.
+//! Modules can only `require` other modules of the same bundle, and the approved shared
+//! libraries attached to it (`@community/<name>`).
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::scripting::api_version::{self, LATEST_API_VERSION, OLDEST_API_VERSION};
+use crate::scripting::runtime::ScriptLanguage;
+
+/// Module executed first when running a bundle
+pub const ENTRY_MODULE: &str = "main.js";
+
+/// Maximum total size of all modules in a bundle (1MB)
+pub const MAX_BUNDLE_SIZE: usize = 1_000_000;
+
+/// Maximum number of modules in a bundle
+pub const MAX_BUNDLE_FILES: usize = 64;
+
+/// Prefix of the specifiers of shared libraries (`@community/<name>`)
+pub const LIBRARY_PREFIX: &str = "@community/";
+
+/// Maximum number of modules in a bundle built one module at a time
+pub const MAX_PLAYER_MODULES: usize = 10;
+
+/// Maximum size of a module submitted on its own (100KB)
+pub const MAX_MODULE_SIZE: usize = 100_000;
+
+/// Maximum length of a module name
+const MAX_MODULE_NAME_LENGTH: usize = 255;
+
+/// A player's code: module name -> source
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ScriptBundle {
+    modules: BTreeMap<String, String>,
+    #[serde(default)]
+    language: ScriptLanguage,
+    /// Version of the bot API the bundle is bound to (bundles saved before versioning use the first)
+    #[serde(default = "oldest_api_version")]
+    api_version: u32,
+    /// Shared libraries the modules can import (library name -> source)
+    #[serde(skip)]
+    libraries: Arc<BTreeMap<String, String>>,
+}
+
+impl ScriptBundle {
+    /// Wrap a single code string as a bundle (stored as `main.js`)
+    pub fn single(code: String) -> Result<Self, String> {
+        let mut modules = BTreeMap::new();
+        modules.insert(ENTRY_MODULE.to_string(), code);
+        Self::from_modules(modules)
+    }
+
+    /// Build a bundle from a module map, enforcing size, count, and naming rules
+    ///
+    /// The bundle is bound to the API version declared by the `@api` pragma of its entry
+    /// module, or to the latest one.
+    pub fn from_modules(modules: BTreeMap<String, String>) -> Result<Self, String> {
+        if modules.len() > MAX_BUNDLE_FILES {
+            return Err(format!("Too many modules: {} (max: {})", modules.len(), MAX_BUNDLE_FILES));
+        }
+
+        for name in modules.keys() {
+            validate_module_name(name)?;
+        }
+
+        if !modules.contains_key(ENTRY_MODULE) {
+            return Err(format!("Bundle must contain an entry module named {}", ENTRY_MODULE));
+        }
+
+        let total_size: usize = modules.values().map(|code| code.len()).sum();
+        if total_size > MAX_BUNDLE_SIZE {
+            return Err(format!("Code too large: {} bytes (max: {} bytes)", total_size, MAX_BUNDLE_SIZE));
+        }
+
+        let api_version = api_version::parse_pragma(&modules[ENTRY_MODULE])
+            .map_err(|e| format!("{}: {}", ENTRY_MODULE, e))?
+            .unwrap_or(LATEST_API_VERSION);
+
+        Ok(ScriptBundle {
+            modules,
+            language: ScriptLanguage::default(),
+            api_version,
+            libraries: Arc::default(),
+        })
+    }
+
+    /// Set the language the bundle is written in
+    pub fn with_language(mut self, language: ScriptLanguage) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// Bind the bundle to an API version; it must match the `@api` pragma, if any
+    pub fn with_api_version(mut self, version: u32) -> Result<Self, String> {
+        let version = api_version::check_api_version(version)?;
+        match self.declared_api_version() {
+            Some(declared) if declared != version => Err(format!(
+                "API version {} does not match the @api v{} pragma of {}",
+                version, declared, ENTRY_MODULE
+            )),
+            _ => {
+                self.api_version = version;
+                Ok(self)
+            }
+        }
+    }
+
+    /// Version of the bot API the bundle is bound to
+    pub fn api_version(&self) -> u32 {
+        self.api_version
+    }
+
+    /// API version declared by the `@api` pragma of the entry module, if any
+    pub fn declared_api_version(&self) -> Option<u32> {
+        api_version::parse_pragma(self.entry()).ok().flatten()
+    }
+
+    /// Attach the shared libraries the modules can import
+    pub fn with_libraries(mut self, libraries: Arc<BTreeMap<String, String>>) -> Self {
+        self.libraries = libraries;
+        self
+    }
+
+    /// Shared libraries the modules can import
+    pub fn libraries(&self) -> &Arc<BTreeMap<String, String>> {
+        &self.libraries
+    }
+
+    /// Source of a shared library by its specifier (`@community/<name>`)
+    pub fn library(&self, specifier: &str) -> Option<&String> {
+        self.libraries.get(specifier.strip_prefix(LIBRARY_PREFIX)?)
+    }
+
+    /// Language the bundle is written in
+    pub fn language(&self) -> ScriptLanguage {
+        self.language
+    }
+
+    /// Source of the entry module
+    pub fn entry(&self) -> &String {
+        &self.modules[ENTRY_MODULE]
+    }
+
+    /// All modules in the bundle
+    pub fn modules(&self) -> &BTreeMap<String, String> {
+        &self.modules
+    }
+
+    /// Source of a module by its exact name
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.modules.get(name)
+    }
+
+    /// Total size of all module sources in bytes
+    pub fn total_size(&self) -> usize {
+        self.modules.values().map(|code| code.len()).sum()
+    }
+
+    /// Resolve a `require` specifier relative to the requiring module
+    ///
+    /// Only relative (`./`, `../`) specifiers and shared libraries (`@community/<name>`)
+    /// are supported. The `.js` extension and `/index.js` are tried when the exact name
+    /// does not exist.
+    pub fn resolve(&self, specifier: &str, from: &str) -> Result<String, String> {
+        if specifier.starts_with(LIBRARY_PREFIX) {
+            return match self.library(specifier) {
+                Some(_) => Ok(specifier.to_string()),
+                None => Err(format!("Cannot find module '{}' from {}: no approved library with this name", specifier, from)),
+            };
+        }
+        if !specifier.starts_with("./") && !specifier.starts_with("../") {
+            return Err(format!("Cannot find module '{}' from {}: only relative imports within your bundle are allowed", specifier, from));
+        }
+
+        // Start from the directory of the requiring module
+        let mut segments: Vec<&str> = from.split('/').collect();
+        segments.pop();
+
+        for segment in specifier.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => {
+                    if segments.pop().is_none() {
+                        return Err(format!("Cannot find module '{}' from {}: path escapes the bundle", specifier, from));
+                    }
+                }
+                other => segments.push(other),
+            }
+        }
+
+        let base = segments.join("/");
+        let candidates = [base.clone(), format!("{}.js", base), format!("{}/index.js", base)];
+
+        candidates
+            .into_iter()
+            .find(|name| self.modules.contains_key(name))
+            .ok_or_else(|| format!("Cannot find module '{}' from {}", specifier, from))
+    }
+}
+
+fn oldest_api_version() -> u32 {
+    OLDEST_API_VERSION
+}
+
+/// Validate a module name (relative path ending in `.js`, no traversal)
+pub fn validate_module_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_MODULE_NAME_LENGTH {
+        return Err(format!("Module name must be between 1 and {} characters", MAX_MODULE_NAME_LENGTH));
+    }
+
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' || c == '/') {
+        return Err(format!("Module name '{}' can only contain letters, numbers, underscore, hyphen, dot, and '/'", name));
+    }
+
+    if name.split('/').any(|segment| segment.is_empty() || segment == "." || segment == "..") {
+        return Err(format!("Module name '{}' must be a relative path without '.' or '..' segments", name));
+    }
+
+    if !name.ends_with(".js") {
+        return Err(format!("Module name '{}' must end with .js", name));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle(names: &[&str]) -> ScriptBundle {
+        let modules = names.iter().map(|n| (n.to_string(), String::new())).collect();
+        ScriptBundle::from_modules(modules).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_relative_paths() {
+        let b = bundle(&["main.js", "utils/path.js", "utils/index.js", "lib/a.js"]);
+
+        assert_eq!(b.resolve("./utils/path", "main.js").unwrap(), "utils/path.js");
+        assert_eq!(b.resolve("./utils/path.js", "main.js").unwrap(), "utils/path.js");
+        assert_eq!(b.resolve("./utils", "main.js").unwrap(), "utils/index.js");
+        assert_eq!(b.resolve("../utils/path", "lib/a.js").unwrap(), "utils/path.js");
+        assert_eq!(b.resolve("./path", "utils/index.js").unwrap(), "utils/path.js");
+    }
+
+    #[test]
+    fn test_resolve_rejects_outside_bundle() {
+        let b = bundle(&["main.js"]);
+
+        assert!(b.resolve("fs", "main.js").is_err());
+        assert!(b.resolve("../secret", "main.js").is_err());
+        assert!(b.resolve("./missing", "main.js").is_err());
+    }
+
+    #[test]
+    fn test_module_name_validation() {
+        assert!(validate_module_name("main.js").is_ok());
+        assert!(validate_module_name("utils/path.js").is_ok());
+        assert!(validate_module_name("../etc/passwd.js").is_err());
+        assert!(validate_module_name("/abs.js").is_err());
+        assert!(validate_module_name("a/./b.js").is_err());
+        assert!(validate_module_name("a\\b.js").is_err());
+        assert!(validate_module_name("notes.txt").is_err());
+    }
+}
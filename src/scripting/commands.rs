@@ -0,0 +1,25 @@
+//! Bot commands
+//!
+//! Actions a script requests during a tick (move, harvest, attack, ...). Scripts never
+//! mutate the world directly; the commands they issue are collected and applied by the server.
+
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of commands kept from one script execution
+pub const MAX_COMMANDS_PER_TICK: usize = 1000;
+
+/// A single action issued by a script
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BotCommand {
+    /// Action name (`moveTo`, `harvest`, `attack`, `buildStructure`, ...)
+    pub action: String,
+    /// Unit or structure performing the action (`None` for player-level actions)
+    pub actor: Option<String>,
+    /// Action parameters (position, target ID, unit type, ...)
+    #[serde(default)]
+    pub params: serde_json::Value,
+    /// Player whose script issued the command, set by the sandbox whatever the script
+    /// claims (`None` for commands the server builds itself, e.g. for NPCs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player_id: Option<String>,
+}
@@ -0,0 +1,177 @@
+//! Script errors
+//!
+//! Errors raised by players' scripts are kept per player so they can see why their bot
+//! failed. Each [`LoggedError`] is located in the submitted code: the module, line and
+//! column of the innermost stack frame that belongs to the bundle. An error identical to
+//! one already kept (same message and location) only increments that entry's count.
+//!
+//! A [`ScriptError`] tells why an execution did not complete, including when the script
+//! never got to run.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::scripting::bundle::ScriptBundle;
+use crate::scripting::js_runtime::MODULE_PREFIX;
+use crate::scripting::runtime::ScriptLanguage;
+
+/// Maximum number of distinct errors kept per player (the oldest are dropped beyond it)
+pub const MAX_PLAYER_ERRORS: usize = 10;
+
+/// Module name of Lua scripts in error messages
+const LUA_MODULE: &str = "main.lua";
+
+/// Reason a script execution did not complete
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptError {
+    /// The player has no code
+    NoCode,
+    /// The script raised an error (syntax error, exception, missing module, timeout)
+    Raised(String),
+    /// No execution slot became free within the queue timeout: the script did not run
+    Queued,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::NoCode => write!(f, "No code submitted"),
+            ScriptError::Raised(error) => write!(f, "{}", error),
+            ScriptError::Queued => write!(f, "No execution slot became free in time"),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// An error raised by a player's script, as kept in their [`ErrorLog`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoggedError {
+    /// First line of the error (`Error: ...`, `runtime error: ...`)
+    pub message: String,
+    /// Stack trace, if the runtime gave one
+    pub stack: Option<String>,
+    /// Module of the submitted code the error was raised in
+    pub module: Option<String>,
+    /// Line in that module (from 1)
+    pub line: Option<u32>,
+    /// Column in that line (from 1), if the runtime reports it
+    pub column: Option<u32>,
+    /// Script tick of the first occurrence
+    pub tick: u64,
+    /// Script tick of the latest occurrence
+    pub last_tick: u64,
+    /// Number of occurrences
+    pub count: u32,
+}
+
+impl LoggedError {
+    /// Parse the error text of an execution of `bundle` at a script tick
+    pub fn parse(error: &str, bundle: &ScriptBundle, tick: u64) -> Self {
+        let (message, stack) = match error.split_once('\n') {
+            Some((message, stack)) => (message, Some(stack.trim_end().to_string()).filter(|stack| !stack.is_empty())),
+            None => (error, None),
+        };
+
+        let mut names: Vec<&str> = bundle.modules().keys().map(String::as_str).collect();
+        names.extend(bundle.libraries().keys().map(String::as_str));
+        if bundle.language() == ScriptLanguage::Lua {
+            names.push(LUA_MODULE);
+        }
+        let location = locate(error, &names).map(|(module, line, column)| {
+            // The first line of a JavaScript module is prefixed by its wrapper, and QuickJS
+            // counts the columns of a script's first line from 0
+            let column = match (bundle.language(), line) {
+                (ScriptLanguage::JavaScript | ScriptLanguage::TypeScript, 1) => {
+                    column.map(|column| column.saturating_sub(MODULE_PREFIX.len() as u32 - 1).max(1))
+                }
+                _ => column,
+            };
+            (module, line, column)
+        });
+
+        Self {
+            message: message.trim().to_string(),
+            stack,
+            module: location.as_ref().map(|(module, _, _)| module.to_string()),
+            line: location.as_ref().map(|(_, line, _)| *line),
+            column: location.and_then(|(_, _, column)| column),
+            tick,
+            last_tick: tick,
+            count: 1,
+        }
+    }
+
+    /// Whether two errors have the same message and location
+    pub fn same_as(&self, other: &LoggedError) -> bool {
+        self.message == other.message
+            && self.module == other.module
+            && self.line == other.line
+            && self.column == other.column
+    }
+}
+
+/// Latest distinct errors of one player, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct ErrorLog {
+    errors: VecDeque<LoggedError>,
+}
+
+impl ErrorLog {
+    /// Add an error; returns whether it is new (not a repeat of a kept error)
+    ///
+    /// A repeat is counted on the kept entry, which becomes the latest.
+    pub fn record(&mut self, error: LoggedError) -> bool {
+        if let Some(index) = self.errors.iter().position(|kept| kept.same_as(&error)) {
+            let mut kept = self.errors.remove(index).expect("index is in bounds");
+            kept.count = kept.count.saturating_add(1);
+            kept.last_tick = error.last_tick;
+            self.errors.push_back(kept);
+            return false;
+        }
+
+        if self.errors.len() >= MAX_PLAYER_ERRORS {
+            self.errors.pop_front();
+        }
+        self.errors.push_back(error);
+        true
+    }
+
+    /// Kept errors, oldest first
+    pub fn errors(&self) -> Vec<LoggedError> {
+        self.errors.iter().cloned().collect()
+    }
+
+    /// Number of kept errors that occurred at a script tick
+    pub fn count_at(&self, tick: u64) -> usize {
+        self.errors.iter().filter(|error| error.last_tick == tick).count()
+    }
+}
+
+/// First `<module>:<line>[:<column>]` reference to one of `names` in an error text
+fn locate<'a>(error: &str, names: &[&'a str]) -> Option<(&'a str, u32, Option<u32>)> {
+    names.iter()
+        .flat_map(|name| {
+            error.match_indices(*name)
+                .filter(|(start, _)| {
+                    // Skip names that are the end of a longer one
+                    error[..*start].chars().next_back().is_none_or(|c| !(c.is_alphanumeric() || "_-./@".contains(c)))
+                })
+                .filter_map(move |(start, _)| {
+                    let mut numbers = error[start + name.len()..].strip_prefix(':')?.split(':');
+                    let line = leading_number(numbers.next()?)?;
+                    let column = numbers.next().and_then(leading_number);
+                    Some((start, *name, line, column))
+                })
+        })
+        .min_by_key(|(start, _, _, _)| *start)
+        .map(|(_, name, line, column)| (name, line, column))
+}
+
+/// Number made of the digits at the start of a string
+fn leading_number(text: &str) -> Option<u32> {
+    let digits = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    text[..digits].parse().ok()
+}
@@ -0,0 +1,364 @@
+//! ES module syntax
+//!
+//! Modules of a JavaScript bundle may use `import` and `export` declarations instead of
+//! `require` and `module.exports`. Before a module runs, its top-level declarations are
+//! rewritten to CommonJS: `import utils from './utils'` becomes a `require`, and exported
+//! bindings are copied onto `exports` once the module body has run. A default import of a
+//! CommonJS module gives its `module.exports`, so both styles can be mixed in a bundle.
+//! Rewritten declarations keep their lines, so line numbers in errors match the source.
+
+use crate::scripting::typescript::{error_at, tokenize, Token, TokenKind};
+
+/// Default import of a required module (`module.exports` unless it has ES exports)
+const INTEROP_DEFAULT: &str = "(m => m && m.__esModule ? m.default : m)";
+
+/// Rewrite the top-level `import` and `export` declarations of a module to CommonJS
+pub fn to_commonjs(source: &str) -> Result<String, String> {
+    if !source.contains("import") && !source.contains("export") {
+        return Ok(source.to_string());
+    }
+
+    let mut rewriter = Rewriter {
+        source,
+        tokens: tokenize(source)?,
+        edits: Vec::new(),
+        exports: Vec::new(),
+        has_exports: false,
+    };
+    rewriter.run()?;
+    Ok(rewriter.output())
+}
+
+struct Rewriter<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    /// (start, end, replacement) byte ranges of the source, in order
+    edits: Vec<(usize, usize, String)>,
+    /// (exported name, local binding) copied onto `exports` after the module body
+    exports: Vec<(String, String)>,
+    has_exports: bool,
+}
+
+impl Rewriter<'_> {
+    fn text(&self, i: usize) -> &str {
+        self.tokens.get(i).map_or("", |t| &self.source[t.start..t.end])
+    }
+
+    fn is_ident(&self, i: usize) -> bool {
+        self.tokens.get(i).is_some_and(|t| t.kind == TokenKind::Ident)
+    }
+
+    fn error(&self, i: usize, message: &str) -> String {
+        let offset = self.tokens.get(i).map_or(self.source.len(), |t| t.start);
+        error_at(self.source, offset, message)
+    }
+
+    fn expect(&self, i: usize, text: &str) -> Result<usize, String> {
+        if self.text(i) == text {
+            Ok(i + 1)
+        } else {
+            Err(self.error(i, &format!("expected '{}'", text)))
+        }
+    }
+
+    fn expect_ident(&self, i: usize) -> Result<String, String> {
+        if self.is_ident(i) {
+            Ok(self.text(i).to_string())
+        } else {
+            Err(self.error(i, "expected a name"))
+        }
+    }
+
+    /// The module specifier at `i` (a string literal, quotes included)
+    fn specifier(&self, i: usize) -> Result<String, String> {
+        let text = self.text(i);
+        if self.tokens.get(i).is_some_and(|t| t.kind == TokenKind::Literal) && (text.starts_with('\'') || text.starts_with('"')) {
+            Ok(text.to_string())
+        } else {
+            Err(self.error(i, "expected a module path string"))
+        }
+    }
+
+    /// `from '<specifier>'` at `i`: the specifier and the index after it
+    fn source_clause(&self, i: usize) -> Result<(String, usize), String> {
+        let i = self.expect(i, "from")?;
+        Ok((self.specifier(i)?, i + 1))
+    }
+
+    /// `{ a, b as c }` at `i`: the (name, alias) pairs and the index after the `}`
+    fn name_list(&self, i: usize) -> Result<(Vec<(String, String)>, usize), String> {
+        let mut i = self.expect(i, "{")?;
+        let mut names = Vec::new();
+        while self.text(i) != "}" {
+            let name = self.expect_ident(i)?;
+            i += 1;
+            let alias = if self.text(i) == "as" {
+                i += 1;
+                let alias = self.expect_ident(i)?;
+                i += 1;
+                alias
+            } else {
+                name.clone()
+            };
+            names.push((name, alias));
+            match self.text(i) {
+                "," => i += 1,
+                "}" => {}
+                _ => return Err(self.error(i, "expected ',' or '}'")),
+            }
+        }
+        Ok((names, i + 1))
+    }
+
+    /// Replace the tokens `from..to` (and a trailing `;`) with `code`, keeping their lines
+    fn replace_statement(&mut self, from: usize, to: usize, code: String) -> usize {
+        let to = if self.text(to) == ";" { to + 1 } else { to };
+        let start = self.tokens[from].start;
+        let end = self.tokens[to - 1].end;
+        let lines = self.source[start..end].matches('\n').count();
+        self.edits.push((start, end, code + &"\n".repeat(lines)));
+        to
+    }
+
+    /// Remove the tokens `from..to`, leaving the declaration that follows
+    fn remove(&mut self, from: usize, to: usize) {
+        self.edits.push((self.tokens[from].start, self.tokens[to].start, String::new()));
+    }
+
+    fn run(&mut self) -> Result<(), String> {
+        let mut depth = 0usize;
+        let mut i = 0;
+        while i < self.tokens.len() {
+            let top_level = depth == 0 && !matches!(i.checked_sub(1).map(|p| self.text(p)), Some("." | "?."));
+            match self.text(i) {
+                "{" | "(" | "[" => depth += 1,
+                "}" | ")" | "]" => depth = depth.saturating_sub(1),
+                "import" if top_level && !matches!(self.text(i + 1), "(" | ".") => {
+                    i = self.rewrite_import(i)?;
+                    continue;
+                }
+                "export" if top_level => {
+                    self.has_exports = true;
+                    i = self.rewrite_export(i)?;
+                    continue;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        Ok(())
+    }
+
+    fn rewrite_import(&mut self, start: usize) -> Result<usize, String> {
+        let mut i = start + 1;
+        if self.tokens.get(i).is_some_and(|t| t.kind == TokenKind::Literal) {
+            let code = format!("require({});", self.specifier(i)?);
+            return Ok(self.replace_statement(start, i + 1, code));
+        }
+
+        let mut default = None;
+        let mut namespace = None;
+        let mut named = None;
+        if self.is_ident(i) && self.text(i) != "from" {
+            default = Some(self.expect_ident(i)?);
+            i += 1;
+            if self.text(i) == "," {
+                i += 1;
+            }
+        }
+        if self.text(i) == "*" {
+            i = self.expect(i + 1, "as")?;
+            namespace = Some(self.expect_ident(i)?);
+            i += 1;
+        } else if self.text(i) == "{" {
+            let (names, next) = self.name_list(i)?;
+            named = Some(names);
+            i = next;
+        }
+        if default.is_none() && namespace.is_none() && named.is_none() {
+            return Err(self.error(i, "expected the names to import"));
+        }
+        let (specifier, end) = self.source_clause(i)?;
+
+        let mut code = String::new();
+        if let Some(name) = default {
+            code += &format!("const {} = {}(require({}));", name, INTEROP_DEFAULT, specifier);
+        }
+        if let Some(name) = namespace {
+            code += &format!("const {} = require({});", name, specifier);
+        }
+        if let Some(names) = named {
+            let bindings: Vec<String> = names.iter()
+                .map(|(name, alias)| if name == alias { name.clone() } else { format!("{}: {}", name, alias) })
+                .collect();
+            code += &format!("const {{ {} }} = require({});", bindings.join(", "), specifier);
+        }
+        Ok(self.replace_statement(start, end, code))
+    }
+
+    fn rewrite_export(&mut self, start: usize) -> Result<usize, String> {
+        let i = start + 1;
+        match self.text(i) {
+            "default" => {
+                let mut name_at = i + 1;
+                if self.text(name_at) == "async" && self.text(name_at + 1) == "function" {
+                    name_at += 1;
+                }
+                if matches!(self.text(name_at), "function" | "class") {
+                    name_at += 1;
+                    if self.text(name_at) == "*" {
+                        name_at += 1;
+                    }
+                    if self.is_ident(name_at) && self.text(name_at) != "extends" {
+                        // A named declaration stays a declaration
+                        let name = self.text(name_at).to_string();
+                        self.exports.push(("default".to_string(), name));
+                        self.remove(start, i + 1);
+                        return Ok(i + 1);
+                    }
+                }
+                self.edits.push((self.tokens[start].start, self.tokens[i].end, "exports.default =".to_string()));
+                Ok(i + 1)
+            }
+            "function" | "class" | "async" => {
+                let mut name_at = i + 1;
+                if self.text(i) == "async" {
+                    name_at = self.expect(name_at, "function")?;
+                }
+                if self.text(name_at) == "*" {
+                    name_at += 1;
+                }
+                let name = self.expect_ident(name_at)?;
+                self.exports.push((name.clone(), name));
+                self.remove(start, i);
+                Ok(i)
+            }
+            "const" | "let" | "var" => {
+                for name in self.declared_names(i + 1)? {
+                    self.exports.push((name.clone(), name));
+                }
+                self.remove(start, i);
+                Ok(i)
+            }
+            "{" => {
+                let (names, next) = self.name_list(i)?;
+                if self.text(next) == "from" {
+                    let (specifier, end) = self.source_clause(next)?;
+                    let copies: Vec<String> = names.iter()
+                        .map(|(name, alias)| format!("exports.{} = m.{};", alias, name))
+                        .collect();
+                    let code = format!("(m => {{ {} }})(require({}));", copies.join(" "), specifier);
+                    return Ok(self.replace_statement(start, end, code));
+                }
+                for (name, alias) in names {
+                    self.exports.push((alias, name));
+                }
+                Ok(self.replace_statement(start, next, String::new()))
+            }
+            "*" => {
+                if self.text(i + 1) == "as" {
+                    let name = self.expect_ident(i + 2)?;
+                    let (specifier, end) = self.source_clause(i + 3)?;
+                    return Ok(self.replace_statement(start, end, format!("exports.{} = require({});", name, specifier)));
+                }
+                let (specifier, end) = self.source_clause(i + 1)?;
+                Ok(self.replace_statement(start, end, format!("Object.assign(exports, require({}));", specifier)))
+            }
+            _ => Err(self.error(i, "unsupported export")),
+        }
+    }
+
+    /// Names declared by a `const`/`let`/`var` statement whose first declarator is at `i`
+    fn declared_names(&self, mut i: usize) -> Result<Vec<String>, String> {
+        let mut names = Vec::new();
+        loop {
+            if matches!(self.text(i), "{" | "[") {
+                return Err(self.error(i, "exporting destructured bindings is not supported"));
+            }
+            names.push(self.expect_ident(i)?);
+
+            // Skip the initializer up to the next declarator or the end of the statement
+            let mut depth = 0usize;
+            i += 1;
+            loop {
+                let Some(token) = self.tokens.get(i) else {
+                    return Ok(names);
+                };
+                let ends_expression = i.checked_sub(1).is_some_and(|p| {
+                    self.tokens[p].kind != TokenKind::Punct || matches!(self.text(p), ")" | "]" | "}")
+                });
+                match self.text(i) {
+                    "{" | "(" | "[" => depth += 1,
+                    "}" | ")" | "]" => depth = depth.saturating_sub(1),
+                    ";" if depth == 0 => return Ok(names),
+                    "," if depth == 0 => break,
+                    _ if depth == 0 && token.newline_before && token.kind == TokenKind::Ident && ends_expression => {
+                        return Ok(names);
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+            i += 1;
+        }
+    }
+
+    fn output(&self) -> String {
+        let mut code = String::with_capacity(self.source.len());
+        if self.has_exports {
+            // Stays on the first line so line numbers are unchanged
+            code += "Object.defineProperty(exports, '__esModule', { value: true }); ";
+        }
+
+        let mut pos = 0;
+        for (start, end, replacement) in &self.edits {
+            code += &self.source[pos..*start];
+            code += replacement;
+            pos = *end;
+        }
+        code += &self.source[pos..];
+
+        if !self.exports.is_empty() {
+            code += "\n;";
+            for (name, local) in &self.exports {
+                code += &format!("exports.{} = {};", name, local);
+            }
+        }
+        code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewrites_imports() {
+        assert_eq!(to_commonjs("import './setup';").unwrap(), "require('./setup');");
+        assert_eq!(
+            to_commonjs("import * as path from './path'\nimport { a, b as c } from \"./lib\";").unwrap(),
+            "const path = require('./path');\nconst { a, b: c } = require(\"./lib\");",
+        );
+        let code = to_commonjs("import utils, { helper } from './utils';\nutils.run();").unwrap();
+        assert!(code.starts_with("const utils = (m => m && m.__esModule ? m.default : m)(require('./utils'));const { helper } = require('./utils');\n"));
+        assert!(code.ends_with("\nutils.run();"));
+    }
+
+    #[test]
+    fn test_rewrites_exports() {
+        let code = to_commonjs("export function helper() {}\nexport const a = 1, b = [2, 3]\nlet c = 4;\nexport { c as d };\nexport default class Bot {}").unwrap();
+        assert_eq!(code, "Object.defineProperty(exports, '__esModule', { value: true }); function helper() {}\n\
+            const a = 1, b = [2, 3]\nlet c = 4;\n\nclass Bot {}\n\
+            ;exports.helper = helper;exports.a = a;exports.b = b;exports.d = c;exports.default = Bot;");
+        assert!(to_commonjs("export default 42;").unwrap().ends_with("exports.default = 42;"));
+        assert!(to_commonjs("export * from './all';").unwrap().ends_with("Object.assign(exports, require('./all'));"));
+    }
+
+    #[test]
+    fn test_leaves_other_code_alone() {
+        let source = "const m = import('./x'); obj.import = { export: 1 };\nconsole.log(\"import a from 'b'\");";
+        assert_eq!(to_commonjs(source).unwrap(), source);
+        assert!(to_commonjs("export const { a } = obj;").unwrap_err().contains("destructured"));
+        assert!(to_commonjs("import from './x';").unwrap_err().starts_with("line 1"));
+    }
+}
@@ -0,0 +1,178 @@
+// GeekCraft bot API type definitions
+//
+// Describes the `game` object passed to JavaScript and TypeScript bots (see game_api.js
+// and examples/API_REFERENCE.md). Served at GET /api/scripting/types.d.ts. Every
+// declaration names the Rust type it mirrors with an `@rust` tag; a test checks that
+// those types exist and that every `GameAPI` method is implemented by game_api.js.
+
+/** A tile position within a zone. @rust game::zone::Tile */
+interface Position {
+    x: number;
+    y: number;
+}
+
+/** A unit, with its ID as `"<zone_id>:<entity_id>"`. @rust game::zone::EntityRef */
+interface Unit {
+    id: string;
+    /** Unit kind (`worker`, `soldier`, ...) */
+    type: string;
+    owner: string | null;
+    zone_id: string;
+    position: Position;
+    hits: number;
+    /** `"moving"` while the unit walks a path */
+    action: string | null;
+    carrying?: number;
+    moveTo(position: Position): void;
+    stop(): void;
+    harvest(resource: ResourceNode): void;
+    deposit(): void;
+    attack(target: Unit | Structure): void;
+    defend(position: Position): void;
+    isIdle(): boolean;
+    isCarryingResource(): boolean;
+    getCarriedAmount(): number;
+    canAttack(target: Unit | Structure | null): boolean;
+    getDistanceTo(position: Position): number;
+}
+
+/** A building (`base`, `turret`, `factory`, `tower`). @rust game::zone::EntityRef */
+interface Structure {
+    id: string;
+    type: string;
+    owner: string | null;
+    zone_id: string;
+    position: Position;
+    hits: number;
+    action: string | null;
+    produceUnit(unitType: string): void;
+    canProduceUnit(): boolean;
+    /** Research a tech (bases only) */
+    research(techId: string): void;
+}
+
+/** A harvestable resource deposit. @rust game::zone::ResourceDeposit */
+interface ResourceNode {
+    id: string;
+    position: Position;
+    amount: number;
+}
+
+/** A zone as listed by GET /api/map. @rust game::world::ZoneSummary */
+interface ZoneInfo {
+    zone_id: string;
+    position: [number, number] | null;
+    owner: string | null;
+    /** Only for zones of the player and their allies */
+    units: number | null;
+    structures: number | null;
+}
+
+/** An action recorded by the bot API. @rust scripting::commands::BotCommand */
+interface BotCommand {
+    action: string;
+    actor: string | null;
+    params: Record<string, unknown>;
+}
+
+/** An event seen since the previous script tick. @rust game::events::GameEvent */
+interface GameEvent {
+    id: number;
+    type: 'UnitCreated' | 'UnitMoved' | 'UnitDestroyed' | 'ResourceCollected' | 'BuildingCompleted' | 'ResearchCompleted' | 'ResearchCancelled' | 'PlayerDefeated';
+    tick: number;
+    zone_id: string;
+    players: string[];
+    unit_id?: number;
+    kind?: string;
+    x?: number;
+    y?: number;
+    resource?: string;
+    amount?: number;
+    tech?: string;
+}
+
+/** A research under way at a base. @rust game::tech::Research */
+interface Research {
+    tech: string;
+    zone_id: string;
+    base_id: number;
+    x: number;
+    y: number;
+    completes_at: number;
+}
+
+/** The player's techs. @rust game::tech::TechStatus */
+interface TechStatus {
+    researched: string[];
+    research: Research | null;
+    /** Techs whose prerequisites are researched */
+    available: string[];
+}
+
+/** Progress towards an objective of the campaign scenario. @rust game::scenario::ObjectiveProgress */
+interface Objective {
+    id: string;
+    description: string;
+    /** The run fails if a mandatory objective does */
+    mandatory: boolean;
+    status: 'in_progress' | 'completed' | 'failed';
+    /** Resources collected, entities destroyed, or ticks survived so far */
+    progress: number;
+    target: number;
+}
+
+/** A message from another player's bot. @rust scripting::messaging::BotMessage */
+interface BotMessage {
+    from: string;
+    data: unknown;
+    sentAtTick: number;
+}
+
+/** Time of day. @rust game::clock::DayPhase */
+type DayPhase = 'Dawn' | 'Day' | 'Dusk' | 'Night';
+
+/** The player's resources. @rust game::zone::ResourceType */
+interface Resources {
+    minerals: number;
+    gas: number;
+    supply: number;
+    maxSupply: number;
+    [resource: string]: number;
+}
+
+/** The `game` object passed to `onTick`. @rust game::world::World::player_snapshot */
+interface GameAPI {
+    readonly tick: number;
+    readonly playerId: string;
+    getMyUnits(): Unit[];
+    getEnemyUnits(): Unit[];
+    getAllUnits(): Unit[];
+    getUnitById(id: string): Unit | null;
+    getMyResources(): Resources;
+    getAllResources(): ResourceNode[];
+    findNearestResource(position: Position): ResourceNode | null;
+    getMyBases(): Structure[];
+    getMyMainBase(): Structure | null;
+    buildStructure(type: string, position: Position): boolean;
+    isStructureAt(position: Position): boolean;
+    getMapSize(): { width: number; height: number };
+    getDayPhase(): DayPhase;
+    events(): GameEvent[];
+    allies(): string[];
+    isDefeated(): boolean;
+    tech(): TechStatus;
+    /** Research a tech at the main base; false without a base */
+    research(techId: string): boolean;
+    /** Commands rejected on the previous script tick */
+    commandErrors(): string[];
+    /** Objectives of the campaign scenario; empty outside campaign runs */
+    objectives(): Objective[];
+    isWalkable(position: Position): boolean;
+    sendMessage(toPlayer: string, data: unknown): boolean;
+    peekMessages(): BotMessage[];
+    receiveMessages(): BotMessage[];
+}
+
+declare const game: GameAPI;
+declare const module: { exports: unknown };
+declare function require(name: string): any;
@@ -0,0 +1,126 @@
+// GeekCraft bot API
+//
+// Wraps a plain JSON snapshot of the player's view into the `gameState` object described
+// in examples/API_REFERENCE.md, in the shape of the latest API version (game_api_compat.js
+// adapts it to older ones). Actions do not change the snapshot; they are recorded
+// through `host.issue(action, actor, params)` and applied by the server after the tick.
+(function (snapshot, host) {
+    const issue = host.issue;
+    const playerId = snapshot.player_id;
+    const units = (snapshot.units || []).concat(snapshot.enemy_units || []);
+    const resources = snapshot.resources || [];
+    const structures = (snapshot.structures || []).concat(snapshot.enemy_structures || []);
+    const obstacles = snapshot.obstacles || [];
+    const mapSize = snapshot.map_size || { width: 0, height: 0 };
+    const dayPhase = snapshot.day_phase || 'Day';
+    const events = snapshot.events || [];
+    const allies = snapshot.allies || [];
+    const tech = snapshot.tech || { researched: [], research: null, available: [] };
+    const commandErrors = snapshot.command_errors || [];
+    const objectives = snapshot.objectives || [];
+    let inbox = snapshot.messages || [];
+
+    function point(position) {
+        return { x: position.x, y: position.y };
+    }
+
+    function distance(a, b) {
+        return Math.hypot(a.x - b.x, a.y - b.y);
+    }
+
+    function toMessage(m) {
+        return { from: m.from, data: m.payload, sentAtTick: m.sent_at_tick };
+    }
+
+    function makeUnit(data) {
+        const unit = Object.assign({}, data);
+        unit.moveTo = function (position) { issue('moveTo', data.id, { position: point(position) }); };
+        unit.stop = function () { issue('stop', data.id, {}); };
+        unit.harvest = function (resource) { issue('harvest', data.id, { resource: resource.id }); };
+        unit.deposit = function () { issue('deposit', data.id, {}); };
+        unit.attack = function (target) { issue('attack', data.id, { target: target.id }); };
+        unit.defend = function (position) { issue('defend', data.id, { position: point(position) }); };
+        unit.isIdle = function () { return !data.action; };
+        unit.isCarryingResource = function () { return (data.carrying || 0) > 0; };
+        unit.getCarriedAmount = function () { return data.carrying || 0; };
+        unit.canAttack = function (target) { return !!target && target.owner !== playerId; };
+        unit.getDistanceTo = function (position) { return distance(data.position, position); };
+        return unit;
+    }
+
+    function makeStructure(data) {
+        const structure = Object.assign({}, data);
+        structure.produceUnit = function (unitType) { issue('produceUnit', data.id, { unitType: unitType }); };
+        structure.canProduceUnit = function () { return data.owner === playerId; };
+        structure.research = function (techId) { issue('research', data.id, { tech: techId }); };
+        return structure;
+    }
+
+    const allUnits = units.map(makeUnit);
+    const allStructures = structures.map(makeStructure);
+
+    return {
+        tick: snapshot.tick,
+        playerId: playerId,
+        getMyUnits: function () { return allUnits.filter(function (u) { return u.owner === playerId; }); },
+        getEnemyUnits: function () { return allUnits.filter(function (u) { return u.owner !== playerId; }); },
+        getAllUnits: function () { return allUnits.slice(); },
+        getUnitById: function (id) { return allUnits.find(function (u) { return u.id === id; }) || null; },
+        getMyResources: function () {
+            return Object.assign({ minerals: 0, gas: 0, supply: 0, maxSupply: 0 }, snapshot.stockpile || {});
+        },
+        getAllResources: function () { return resources.slice(); },
+        findNearestResource: function (position) {
+            let nearest = null;
+            for (const resource of resources) {
+                if (!nearest || distance(position, resource.position) < distance(position, nearest.position)) {
+                    nearest = resource;
+                }
+            }
+            return nearest;
+        },
+        getMyBases: function () {
+            return allStructures.filter(function (s) { return s.owner === playerId && s.type === 'base'; });
+        },
+        getMyMainBase: function () { return this.getMyBases()[0] || null; },
+        buildStructure: function (type, position) {
+            issue('buildStructure', null, { structureType: type, position: point(position) });
+            return true;
+        },
+        isStructureAt: function (position) {
+            return allStructures.some(function (s) { return s.position.x === position.x && s.position.y === position.y; });
+        },
+        getMapSize: function () { return { width: mapSize.width, height: mapSize.height }; },
+        getDayPhase: function () { return dayPhase; },
+        events: function () { return events.slice(); },
+        allies: function () { return allies.slice(); },
+        tech: function () {
+            return { researched: tech.researched.slice(), research: tech.research, available: tech.available.slice() };
+        },
+        research: function (techId) {
+            const base = this.getMyMainBase();
+            if (!base) {
+                return false;
+            }
+            base.research(techId);
+            return true;
+        },
+        commandErrors: function () { return commandErrors.slice(); },
+        objectives: function () { return objectives.map(function (o) { return Object.assign({}, o); }); },
+        isDefeated: function () { return !!snapshot.defeated; },
+        isWalkable: function (position) {
+            if (position.x < 0 || position.y < 0 || position.x >= mapSize.width || position.y >= mapSize.height) {
+                return false;
+            }
+            return !obstacles.some(function (o) { return o.x === position.x && o.y === position.y; });
+        },
+        sendMessage: function (toPlayer, data) { return host.sendMessage(String(toPlayer), data); },
+        peekMessages: function () { return inbox.map(toMessage); },
+        receiveMessages: function () {
+            const messages = inbox.map(toMessage);
+            inbox = [];
+            host.markMessagesRead();
+            return messages;
+        },
+    };
+})
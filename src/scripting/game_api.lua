@@ -0,0 +1,170 @@
+-- GeekCraft bot API (Lua)
+--
+-- Same API as game_api.js (in the latest version's shape, adapted to older ones by
+-- game_api_compat.lua), over the same JSON snapshot: functions are called with a dot
+-- (`game.getMyUnits()`, `unit.moveTo({x = 1, y = 2})`). Lists returned to scripts are
+-- tagged as arrays so they stay arrays when logged or sent as messages, and `null` is the
+-- snapshot's JSON null. Actions are recorded through `host.issue(action, actor, params)`.
+return function (snapshot, host, null, array)
+    local issue = host.issue
+    local playerId = snapshot.player_id
+    local function field(value, default)
+        if value == nil or value == null then return default end
+        return value
+    end
+    local units = field(snapshot.units, {})
+    local resources = field(snapshot.resources, {})
+    local structures = field(snapshot.structures, {})
+    local obstacles = field(snapshot.obstacles, {})
+    local mapSize = field(snapshot.map_size, { width = 0, height = 0 })
+    local dayPhase = field(snapshot.day_phase, 'Day')
+    local events = field(snapshot.events, {})
+    local allies = field(snapshot.allies, {})
+    local tech = field(snapshot.tech, {})
+    local commandErrors = field(snapshot.command_errors, {})
+    local objectives = field(snapshot.objectives, {})
+    local inbox = field(snapshot.messages, {})
+
+    local function point(position)
+        return { x = position.x, y = position.y }
+    end
+
+    local function distance(a, b)
+        return math.sqrt((a.x - b.x) ^ 2 + (a.y - b.y) ^ 2)
+    end
+
+    local function copy(data)
+        local object = {}
+        for key, value in pairs(data) do object[key] = value end
+        return object
+    end
+
+    local function filter(list, keep)
+        local result = array({})
+        for _, item in ipairs(list) do
+            if keep(item) then result[#result + 1] = item end
+        end
+        return result
+    end
+
+    local function makeUnit(data)
+        local unit = copy(data)
+        unit.moveTo = function (position) issue('moveTo', data.id, { position = point(position) }) end
+        unit.stop = function () issue('stop', data.id, {}) end
+        unit.harvest = function (resource) issue('harvest', data.id, { resource = resource.id }) end
+        unit.deposit = function () issue('deposit', data.id, {}) end
+        unit.attack = function (target) issue('attack', data.id, { target = target.id }) end
+        unit.defend = function (position) issue('defend', data.id, { position = point(position) }) end
+        unit.isIdle = function () return field(data.action, false) == false end
+        unit.isCarryingResource = function () return field(data.carrying, 0) > 0 end
+        unit.getCarriedAmount = function () return field(data.carrying, 0) end
+        unit.canAttack = function (target) return target ~= nil and target.owner ~= playerId end
+        unit.getDistanceTo = function (position) return distance(data.position, position) end
+        return unit
+    end
+
+    local function makeStructure(data)
+        local structure = copy(data)
+        structure.produceUnit = function (unitType) issue('produceUnit', data.id, { unitType = unitType }) end
+        structure.canProduceUnit = function () return data.owner == playerId end
+        structure.research = function (techId) issue('research', data.id, { tech = techId }) end
+        return structure
+    end
+
+    local allUnits = array({})
+    for _, data in ipairs(units) do allUnits[#allUnits + 1] = makeUnit(data) end
+    for _, data in ipairs(field(snapshot.enemy_units, {})) do allUnits[#allUnits + 1] = makeUnit(data) end
+    local allStructures = array({})
+    for _, data in ipairs(structures) do allStructures[#allStructures + 1] = makeStructure(data) end
+    for _, data in ipairs(field(snapshot.enemy_structures, {})) do allStructures[#allStructures + 1] = makeStructure(data) end
+
+    local game = {
+        tick = snapshot.tick,
+        playerId = playerId,
+    }
+
+    function game.getMyUnits() return filter(allUnits, function (u) return u.owner == playerId end) end
+    function game.getEnemyUnits() return filter(allUnits, function (u) return u.owner ~= playerId end) end
+    function game.getAllUnits() return filter(allUnits, function () return true end) end
+    function game.getUnitById(id)
+        for _, unit in ipairs(allUnits) do
+            if unit.id == id then return unit end
+        end
+        return nil
+    end
+    function game.getMyResources()
+        local stockpile = { minerals = 0, gas = 0, supply = 0, maxSupply = 0 }
+        for resource, amount in pairs(field(snapshot.stockpile, {})) do stockpile[resource] = amount end
+        return stockpile
+    end
+    function game.getAllResources() return filter(resources, function () return true end) end
+    function game.findNearestResource(position)
+        local nearest = nil
+        for _, resource in ipairs(resources) do
+            if nearest == nil or distance(position, resource.position) < distance(position, nearest.position) then
+                nearest = resource
+            end
+        end
+        return nearest
+    end
+    function game.getMyBases()
+        return filter(allStructures, function (s) return s.owner == playerId and s.type == 'base' end)
+    end
+    function game.getMyMainBase() return game.getMyBases()[1] end
+    function game.buildStructure(structureType, position)
+        issue('buildStructure', nil, { structureType = structureType, position = point(position) })
+        return true
+    end
+    function game.isStructureAt(position)
+        for _, s in ipairs(allStructures) do
+            if s.position.x == position.x and s.position.y == position.y then return true end
+        end
+        return false
+    end
+    function game.getMapSize() return { width = mapSize.width, height = mapSize.height } end
+    function game.getDayPhase() return dayPhase end
+    function game.events() return filter(events, function () return true end) end
+    function game.allies() return filter(allies, function () return true end) end
+    function game.tech()
+        return {
+            researched = filter(field(tech.researched, {}), function () return true end),
+            research = field(tech.research, nil),
+            available = filter(field(tech.available, {}), function () return true end),
+        }
+    end
+    function game.research(techId)
+        local base = game.getMyMainBase()
+        if base == nil then return false end
+        base.research(techId)
+        return true
+    end
+    function game.commandErrors() return filter(commandErrors, function () return true end) end
+    function game.objectives() return filter(objectives, function () return true end) end
+    function game.isDefeated() return field(snapshot.defeated, false) == true end
+    function game.isWalkable(position)
+        if position.x < 0 or position.y < 0 or position.x >= mapSize.width or position.y >= mapSize.height then
+            return false
+        end
+        for _, o in ipairs(obstacles) do
+            if o.x == position.x and o.y == position.y then return false end
+        end
+        return true
+    end
+    function game.sendMessage(toPlayer, data) return host.sendMessage(tostring(toPlayer), data) end
+    local function peekMessages()
+        local messages = array({})
+        for i, m in ipairs(inbox) do
+            messages[i] = { from = m.from, data = m.payload, sentAtTick = m.sent_at_tick }
+        end
+        return messages
+    end
+    game.peekMessages = peekMessages
+    function game.receiveMessages()
+        local messages = peekMessages()
+        inbox = {}
+        host.markMessagesRead()
+        return messages
+    end
+
+    return game
+end
@@ -0,0 +1,33 @@
+// GeekCraft bot API compatibility layer
+//
+// Gives the `game` object built by game_api.js the shape of the API version the bot is
+// bound to, from the changes listed in api_version.rs (newest first). For a bot bound to
+// an older version, renamed methods go back to their old name and removed methods come
+// back from `legacy`; for a bot bound to the change's version or a later one, calling the
+// old name throws an error naming the replacement.
+(function (game, snapshot, host, apiVersion, changes) {
+    const legacy = {
+        // Removed in v2: move a unit by ID; a bare number is an entity ID in the player's home zone
+        moveUnit: function (unitId, x, y) {
+            const id = typeof unitId === 'number' ? snapshot.zone_id + ':' + unitId : String(unitId);
+            host.issue('moveTo', id, { position: { x: x, y: y } });
+            return true;
+        },
+    };
+
+    function upgraded(message) {
+        return function () { throw new Error(message); };
+    }
+
+    for (const change of changes) {
+        if (apiVersion >= change.version) {
+            game[change.name] = upgraded(change.error);
+        } else if (change.kind === 'renamed') {
+            game[change.name] = game[change.replacement];
+            delete game[change.replacement];
+        } else {
+            game[change.name] = legacy[change.name];
+        }
+    }
+    return game;
+})
@@ -0,0 +1,28 @@
+-- GeekCraft bot API compatibility layer (Lua)
+--
+-- Same as game_api_compat.js: gives the `game` table built by game_api.lua the shape of
+-- the API version the bot is bound to, from the changes listed in api_version.rs (newest
+-- first).
+return function (game, snapshot, host, apiVersion, changes)
+    local legacy = {
+        -- Removed in v2: move a unit by ID; a bare number is an entity ID in the player's home zone
+        moveUnit = function (unitId, x, y)
+            local id = type(unitId) == 'number' and (snapshot.zone_id .. ':' .. (math.tointeger(unitId) or unitId)) or tostring(unitId)
+            host.issue('moveTo', id, { position = { x = x, y = y } })
+            return true
+        end,
+    }
+
+    for _, change in ipairs(changes) do
+        if apiVersion >= change.version then
+            local message = change.error
+            game[change.name] = function () error(message, 2) end
+        elseif change.kind == 'renamed' then
+            game[change.name] = game[change.replacement]
+            game[change.replacement] = nil
+        else
+            game[change.name] = legacy[change.name]
+        end
+    end
+    return game
+end
@@ -0,0 +1,97 @@
+//! Script engine handle
+//!
+//! Shared access to the [`ScriptEngine`] for the server and the game loop. The engine
+//! is only locked to prepare and finish a tick; scripts run in between on a thread pool,
+//! so players can submit code while a tick is executing.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::game::replay::WorldSnapshot;
+use crate::scripting::js_runtime::ScriptExecutionResult;
+use crate::scripting::sandbox::ScriptEngine;
+
+/// Cloneable handle to the script engine and its execution pool
+#[derive(Clone)]
+pub struct ScriptEngineHandle {
+    engine: Arc<RwLock<ScriptEngine>>,
+    pool: Arc<ThreadPool>,
+}
+
+impl ScriptEngineHandle {
+    /// Wrap an engine, executing scripts on one thread per CPU core
+    pub fn new(engine: ScriptEngine) -> Self {
+        Self::with_threads(engine, 0)
+    }
+
+    /// Wrap an engine, executing scripts on `threads` threads (0 = one per CPU core)
+    pub fn with_threads(engine: ScriptEngine, threads: usize) -> Self {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("geekcraft-script-{}", i))
+            .build()
+            .expect("Failed to create script thread pool");
+
+        Self {
+            engine: Arc::new(RwLock::new(engine)),
+            pool: Arc::new(pool),
+        }
+    }
+
+    /// Number of threads scripts are executed on
+    pub fn threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Lock the engine for reading
+    pub async fn read(&self) -> RwLockReadGuard<'_, ScriptEngine> {
+        self.engine.read().await
+    }
+
+    /// Lock the engine for writing
+    pub async fn write(&self) -> RwLockWriteGuard<'_, ScriptEngine> {
+        self.engine.write().await
+    }
+
+    /// Run one tick for every player with code and a snapshot, in parallel
+    ///
+    /// Results are returned (and their messages applied) in player ID order, so the
+    /// caller can apply the commands deterministically.
+    pub async fn run_tick(&self, tick: u64, snapshots: &BTreeMap<String, serde_json::Value>) -> Vec<(String, ScriptExecutionResult)> {
+        let batch = self.engine.write().await.prepare_tick(tick, snapshots);
+
+        // Script spans are children of the caller's (the tick's) span
+        let pool = self.pool.clone();
+        let span = tracing::Span::current();
+        let results = match tokio::task::spawn_blocking(move || span.in_scope(|| batch.execute(&pool))).await {
+            Ok(results) => results,
+            Err(e) => {
+                log::error!("Script execution for tick {} failed: {}", tick, e);
+                Vec::new()
+            }
+        };
+
+        self.engine.write().await.finish_tick(results)
+    }
+
+    /// Dry-run code against a world snapshot off the async workers (see [`ScriptEngine::dry_run`])
+    pub async fn dry_run(&self, player_id: String, code: String, world_snapshot: WorldSnapshot) -> ScriptExecutionResult {
+        let engine = self.engine.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            engine.blocking_read().dry_run(&player_id, &code, &world_snapshot)
+        }).await;
+        result.unwrap_or_else(|e| ScriptExecutionResult {
+            error: Some(format!("Dry run failed: {}", e)),
+            ..Default::default()
+        })
+    }
+}
+
+impl Default for ScriptEngineHandle {
+    fn default() -> Self {
+        Self::new(ScriptEngine::new())
+    }
+}
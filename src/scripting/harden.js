@@ -0,0 +1,117 @@
+// GeekCraft script hardening
+//
+// Locks a context down once the host has set it up and before the first line of bot
+// code runs (see `ScriptLimits` in js_runtime.rs for the options).
+//
+// Strict mode removes every global not in `allowed` (eval, performance, Atomics, WeakRef,
+// ...) and makes the constructors of every kind of function throw, so no code can be
+// built from strings. Freezing the intrinsics deep-freezes the standard objects, their
+// prototypes and the `console` host object, and makes the globals present at that point
+// read-only. Bots can then no longer change the builtins the bot API relies on, nor add
+// `toJSON` methods or getters that would run while the host marshals their values.
+(function (allowed, strict, freeze) {
+    const global = globalThis;
+
+    if (strict) {
+        for (const key of Reflect.ownKeys(global)) {
+            if (typeof key === 'string' && !allowed.includes(key)) {
+                delete global[key];
+            }
+        }
+
+        const functionPrototypes = [
+            Function.prototype,
+            Object.getPrototypeOf(async function () {}),
+            Object.getPrototypeOf(function* () {}),
+            Object.getPrototypeOf(async function* () {}),
+        ];
+        for (const prototype of functionPrototypes) {
+            const denied = function () {
+                throw new EvalError('Code generation from strings is disabled');
+            };
+            // `instanceof` keeps working against the replaced constructors
+            denied.prototype = prototype;
+            Object.defineProperty(prototype, 'constructor', { value: denied, writable: false, configurable: false });
+        }
+        Object.defineProperty(global, 'Function', { value: Function.prototype.constructor, writable: true, configurable: true });
+    }
+
+    if (!freeze) {
+        return;
+    }
+
+    // Assigning to a property inherited from a frozen prototype throws in strict code
+    // (`this.name = ...` in an Error subclass): these become accessors that define an own
+    // property on the object assigned to instead.
+    function tame(object, key) {
+        const descriptor = Reflect.getOwnPropertyDescriptor(object, key);
+        if (!descriptor || !('value' in descriptor)) {
+            return;
+        }
+        const value = descriptor.value;
+        Object.defineProperty(object, key, {
+            get: function () { return value; },
+            set: function (newValue) {
+                if (this === object) {
+                    throw new TypeError('Cannot assign to read only property \'' + String(key) + '\' of a builtin');
+                }
+                Object.defineProperty(this, key, { value: newValue, writable: true, enumerable: true, configurable: true });
+            },
+            enumerable: descriptor.enumerable,
+            configurable: false,
+        });
+    }
+    for (const key of ['constructor', 'toString', 'valueOf', 'toLocaleString', 'hasOwnProperty']) {
+        tame(Object.prototype, key);
+    }
+    for (const error of [Error, EvalError, RangeError, ReferenceError, SyntaxError, TypeError, URIError, AggregateError]) {
+        for (const key of ['constructor', 'name', 'message', 'toString']) {
+            tame(error.prototype, key);
+        }
+    }
+    tame(Function.prototype, 'toString');
+
+    // Intrinsics only reachable through instances
+    const roots = [
+        Object.getPrototypeOf([][Symbol.iterator]()),
+        Object.getPrototypeOf(''[Symbol.iterator]()),
+        Object.getPrototypeOf(new Map().entries()),
+        Object.getPrototypeOf(new Set().values()),
+        Object.getPrototypeOf(/./[Symbol.matchAll]('')),
+        Object.getPrototypeOf(function* () {}),
+        Object.getPrototypeOf(function* () {}()),
+        Object.getPrototypeOf(async function () {}),
+        Object.getPrototypeOf(async function* () {}),
+        Object.getPrototypeOf(async function* () {}()),
+    ];
+    if (typeof Iterator === 'function') {
+        roots.push(Object.getPrototypeOf([].values().map(function (x) { return x; })));
+    }
+    for (const key of Reflect.ownKeys(global)) {
+        if (key !== 'game' && key !== 'globalThis') {
+            roots.push(global[key]);
+        }
+    }
+
+    const frozen = new WeakSet();
+    while (roots.length > 0) {
+        const value = roots.pop();
+        if ((typeof value !== 'object' && typeof value !== 'function') || value === null || value === global || frozen.has(value)) {
+            continue;
+        }
+        frozen.add(value);
+        Object.freeze(value);
+        roots.push(Object.getPrototypeOf(value));
+        for (const key of Reflect.ownKeys(value)) {
+            const descriptor = Reflect.getOwnPropertyDescriptor(value, key);
+            roots.push(descriptor.value, descriptor.get, descriptor.set);
+        }
+    }
+
+    for (const key of Reflect.ownKeys(global)) {
+        const descriptor = Reflect.getOwnPropertyDescriptor(global, key);
+        if (descriptor.configurable && 'value' in descriptor) {
+            Object.defineProperty(global, key, { writable: false, configurable: false });
+        }
+    }
+})
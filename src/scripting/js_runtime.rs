@@ -0,0 +1,497 @@
+//! JavaScript runtime
+//!
+//! Executes a player's script bundle in a fresh, isolated QuickJS context with time and
+//! memory limits. Modules are CommonJS-style (`module.exports` / `require`), or use
+//! `import` / `export` declarations which are rewritten to CommonJS, and can only load
+//! other modules of the same bundle; there is no filesystem or network access.
+//!
+//! The entry module may export a bot class or object with an `onTick(game)` method
+//! (as in the examples), or simply run its logic at the top level using the global `game`.
+//! `game` is the bot API (`game_api.js`) built over a JSON snapshot of the player's view,
+//! in the shape of the bundle's API version (see [`crate::scripting::api_version`]);
+//! its actions are collected as [`BotCommand`]s and its outgoing messages as
+//! [`OutgoingMessage`]s. The player's inbox is passed in the snapshot's `messages` field.
+//!
+//! The host sets the context up (bot API, `console`) and locks it down (`harden.js`, per
+//! [`ScriptLimits::strict`] and [`ScriptLimits::freeze_intrinsics`]) before the first
+//! line of bot code runs; no host code is evaluated after that. The time limit starts
+//! there too, so host setup never counts against it. There are no timers: promise
+//! callbacks queued by the bot run once it returns, within the same time limit, and
+//! those still pending when the tick ends are dropped with the context. What a bot can
+//! reach is declared in [`capability_report`].
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use rquickjs::function::Rest;
+use rquickjs::{CatchResultExt, Context, Ctx, Exception, Function, Module, Object, Runtime, Value};
+use serde::Serialize;
+
+use crate::scripting::api_version;
+use crate::scripting::bundle::{ScriptBundle, ENTRY_MODULE};
+use crate::scripting::commands::{BotCommand, MAX_COMMANDS_PER_TICK};
+use crate::scripting::es_modules::to_commonjs;
+use crate::scripting::messaging::{check_payload, OutgoingMessage, MAX_MESSAGES_PER_TICK};
+use crate::scripting::runtime::{ScriptLanguage, ScriptRuntime};
+
+/// Maximum number of console lines kept per execution
+pub(crate) const MAX_LOG_LINES: usize = 100;
+
+/// Builds the `gameState` object from a snapshot and host callbacks
+const GAME_API: &str = include_str!("game_api.js");
+
+/// Gives the `gameState` object the shape of the bundle's API version
+const GAME_API_COMPAT: &str = include_str!("game_api_compat.js");
+
+/// Locks the context down before bot code runs
+const HARDEN: &str = include_str!("harden.js");
+
+/// Standard globals bots can reach (the only ones left in strict mode)
+pub const STANDARD_GLOBALS: &[&str] = &[
+    "AggregateError", "Array", "ArrayBuffer", "BigInt", "BigInt64Array", "BigUint64Array", "Boolean",
+    "DataView", "Date", "Error", "EvalError", "Float16Array", "Float32Array", "Float64Array", "Function",
+    "Infinity", "Int16Array", "Int32Array", "Int8Array", "InternalError", "Iterator", "JSON", "Map", "Math",
+    "NaN", "Number", "Object", "Promise", "Proxy", "RangeError", "ReferenceError", "Reflect", "RegExp", "Set",
+    "String", "Symbol", "SyntaxError", "TypeError", "URIError", "Uint16Array", "Uint32Array", "Uint8Array",
+    "Uint8ClampedArray", "WeakMap", "WeakSet", "decodeURI", "decodeURIComponent", "encodeURI",
+    "encodeURIComponent", "escape", "globalThis", "isFinite", "isNaN", "parseFloat", "parseInt",
+    "queueMicrotask", "undefined", "unescape",
+];
+
+/// Engine globals removed in strict mode: they evaluate strings, expose host timers or
+/// shared memory, or observe the garbage collector
+pub const UNSAFE_GLOBALS: &[&str] = &["Atomics", "FinalizationRegistry", "SharedArrayBuffer", "WeakRef", "eval", "performance"];
+
+/// Globals installed by the host
+pub const HOST_GLOBALS: &[&str] = &["console", "game"];
+
+/// Members of the `game` object, for every API version (see `game_api.js` and `game_api_compat.js`)
+pub const GAME_MEMBERS: &[&str] = &[
+    "allies", "buildStructure", "commandErrors", "events", "findNearestResource", "getAllResources",
+    "getAllUnits", "getDayPhase", "getEnemyUnits", "getMapSize", "getMyBases", "getMyMainBase",
+    "getMyResources", "getMyUnits", "getUnitById", "inbox", "isDefeated", "isStructureAt", "isWalkable",
+    "moveUnit", "objectives", "peekMessages", "playerId", "receiveMessages", "research", "sendMessage",
+    "tech", "tick",
+];
+
+/// Methods added to each unit returned by `game`, besides its snapshot fields
+pub const UNIT_METHODS: &[&str] = &[
+    "attack", "canAttack", "defend", "deposit", "getCarriedAmount", "getDistanceTo", "harvest",
+    "isCarryingResource", "isIdle", "moveTo", "stop",
+];
+
+/// Methods added to each structure returned by `game`, besides its snapshot fields
+pub const STRUCTURE_METHODS: &[&str] = &["canProduceUnit", "produceUnit", "research"];
+
+/// Members of the `console` object
+pub const CONSOLE_METHODS: &[&str] = &["log"];
+
+/// Start of the first line of every module once wrapped (see `wrap_module`)
+pub(crate) const MODULE_PREFIX: &str = "export default function (module, exports, require) {";
+
+/// Calls the exported bot (class, object, or function) with the game state
+const RUN_BOT: &str = r#"
+(function (exported, game) {
+    if (typeof exported === 'function' && exported.prototype) {
+        exported = new exported();
+    } else if (typeof exported === 'function') {
+        return exported(game);
+    }
+    if (exported && typeof exported.onTick === 'function') {
+        exported.onTick(game);
+    }
+})
+"#;
+
+/// Resource limits and hardening applied to one script execution
+#[derive(Debug, Clone)]
+pub struct ScriptLimits {
+    /// Maximum wall-clock execution time of bot code (host setup is not counted)
+    pub timeout: Duration,
+    /// Maximum heap size of the JavaScript runtime in bytes
+    pub max_memory_bytes: usize,
+    /// Strict mode: only [`STANDARD_GLOBALS`] and [`HOST_GLOBALS`] are left, and no code
+    /// can be built from strings (`eval`, the `Function` constructors)
+    pub strict: bool,
+    /// Whether the standard objects, their prototypes and `console` are frozen before
+    /// bot code runs, so bots cannot change what the bot API and the host rely on
+    pub freeze_intrinsics: bool,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_millis(crate::config::SCRIPT_TIMEOUT_MS),
+            max_memory_bytes: crate::config::SCRIPT_MAX_MEMORY_MB * 1024 * 1024,
+            strict: crate::config::SCRIPT_STRICT_MODE,
+            freeze_intrinsics: crate::config::SCRIPT_FREEZE_INTRINSICS,
+        }
+    }
+}
+
+impl ScriptLimits {
+    /// Stricter limits for dry-run validation
+    pub fn validation() -> Self {
+        Self {
+            timeout: Duration::from_millis(crate::config::SCRIPT_VALIDATE_TIMEOUT_MS),
+            ..Self::default()
+        }
+    }
+}
+
+/// Outcome of running a script once
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScriptExecutionResult {
+    /// Lines written with `console.log`
+    pub logs: Vec<String>,
+    /// Commands issued through the bot API, in order
+    pub commands: Vec<BotCommand>,
+    /// Messages sent with `game.sendMessage`, in order
+    pub sent_messages: Vec<OutgoingMessage>,
+    /// Whether the script read its inbox with `game.receiveMessages`
+    pub messages_read: bool,
+    /// Error raised by the script (syntax error, exception, missing module, timeout)
+    pub error: Option<String>,
+    /// Time spent running the script, in nanoseconds
+    pub cpu_ns: u64,
+}
+
+/// What a bot can reach in the JavaScript runtime (TypeScript bots run on it too)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CapabilityReport {
+    /// Whether strict mode is on (see [`ScriptLimits::strict`])
+    pub strict: bool,
+    /// Whether the intrinsics are frozen (see [`ScriptLimits::freeze_intrinsics`])
+    pub frozen_intrinsics: bool,
+    /// Whether code can be built from strings (`eval`, the `Function` constructors)
+    pub code_from_strings: bool,
+    /// Globals, sorted
+    pub globals: Vec<&'static str>,
+    /// Members of `game`, for every API version
+    pub game: &'static [&'static str],
+    /// Methods of the units returned by `game`
+    pub unit: &'static [&'static str],
+    /// Methods of the structures returned by `game`
+    pub structure: &'static [&'static str],
+    /// Members of `console`
+    pub console: &'static [&'static str],
+}
+
+/// What a bot can reach under `limits`
+///
+/// Every binding exposed to bots must be listed here: the sandbox tests check that bots
+/// find nothing else.
+pub fn capability_report(limits: &ScriptLimits) -> CapabilityReport {
+    let mut globals: Vec<&'static str> = STANDARD_GLOBALS.iter().chain(HOST_GLOBALS).copied().collect();
+    if !limits.strict {
+        globals.extend(UNSAFE_GLOBALS);
+    }
+    globals.sort_unstable();
+    CapabilityReport {
+        strict: limits.strict,
+        frozen_intrinsics: limits.freeze_intrinsics,
+        code_from_strings: !limits.strict,
+        globals,
+        game: GAME_MEMBERS,
+        unit: UNIT_METHODS,
+        structure: STRUCTURE_METHODS,
+        console: CONSOLE_METHODS,
+    }
+}
+
+/// JavaScript implementation of [`ScriptRuntime`]
+#[derive(Debug, Clone, Default)]
+pub struct JsRuntime {
+    limits: ScriptLimits,
+}
+
+impl JsRuntime {
+    /// Create a JavaScript runtime with the given limits
+    pub fn new(limits: ScriptLimits) -> Self {
+        Self { limits }
+    }
+}
+
+impl ScriptRuntime for JsRuntime {
+    fn language(&self) -> ScriptLanguage {
+        ScriptLanguage::JavaScript
+    }
+
+    fn compile(&self, bundle: &ScriptBundle) -> Result<(), String> {
+        let runtime = Runtime::new().map_err(|e| format!("Failed to create script runtime: {}", e))?;
+        runtime.set_memory_limit(self.limits.max_memory_bytes);
+        let context = Context::full(&runtime).map_err(|e| format!("Failed to create script context: {}", e))?;
+
+        context.with(|ctx| {
+            for (name, source) in bundle.modules() {
+                let source = to_commonjs(source).map_err(|e| format!("{}: {}", name, e))?;
+                Module::declare(ctx.clone(), name.as_str(), wrap_module(&source))
+                    .catch(&ctx)
+                    .map_err(|e| e.to_string().trim_end().to_string())?;
+            }
+            Ok(())
+        })
+    }
+
+    fn execute_tick(&self, bundle: &ScriptBundle, game_state: &serde_json::Value) -> ScriptExecutionResult {
+        execute_bundle(bundle, game_state, &self.limits)
+    }
+
+    fn limits(&self) -> &ScriptLimits {
+        &self.limits
+    }
+}
+
+/// Run a bundle once against a snapshot of the player's view
+///
+/// The snapshot is plain JSON (`tick`, `player_id`, `units`, `resources`, `structures`,
+/// `obstacles`, `map_size`, `messages`); missing fields are treated as empty.
+pub fn execute_bundle(
+    bundle: &ScriptBundle,
+    game_state: &serde_json::Value,
+    limits: &ScriptLimits,
+) -> ScriptExecutionResult {
+    let mut result = ScriptExecutionResult::default();
+
+    let runtime = match Runtime::new() {
+        Ok(rt) => rt,
+        Err(e) => {
+            result.error = Some(format!("Failed to create script runtime: {}", e));
+            return result;
+        }
+    };
+    runtime.set_memory_limit(limits.max_memory_bytes);
+
+    // The time limit only covers bot code: it starts once the host API is set up
+    let deadline = Arc::new(Deadline::default());
+    let handler = deadline.clone();
+    runtime.set_interrupt_handler(Some(Box::new(move || handler.interrupt())));
+
+    let context = match Context::full(&runtime) {
+        Ok(ctx) => ctx,
+        Err(e) => {
+            result.error = Some(format!("Failed to create script context: {}", e));
+            return result;
+        }
+    };
+
+    let output = Rc::new(RefCell::new(ScriptExecutionResult::default()));
+    let bundle = Rc::new(bundle.clone());
+
+    let error = context.with(|ctx| {
+        let error = run_bundle(&ctx, bundle, game_state, limits, &deadline, output.clone())
+            .catch(&ctx)
+            .err()
+            .map(|e| e.to_string().trim_end().to_string());
+        // Promise callbacks get what is left of the time limit; the rest are dropped
+        deadline.start(limits.timeout);
+        while !deadline.passed() && ctx.execute_pending_job() {}
+        error
+    });
+
+    result = output.take();
+    // Errors raised before the time limit ran out are reported as is
+    result.error = error.map(|e| {
+        if deadline.interrupted.load(Ordering::Relaxed) {
+            format!("Script exceeded time limit of {}ms", limits.timeout.as_millis())
+        } else {
+            e
+        }
+    });
+    result
+}
+
+/// Time limit of a run, interrupting the script once passed
+#[derive(Debug, Default)]
+struct Deadline {
+    at: OnceLock<Instant>,
+    interrupted: AtomicBool,
+}
+
+impl Deadline {
+    /// Start the time limit (once; later calls keep the first deadline)
+    fn start(&self, timeout: Duration) {
+        let _ = self.at.set(Instant::now() + timeout);
+    }
+
+    fn passed(&self) -> bool {
+        self.at.get().is_some_and(|at| Instant::now() >= *at)
+    }
+
+    /// Interrupt handler of the runtime, remembering that it stopped the script
+    fn interrupt(&self) -> bool {
+        let passed = self.passed();
+        if passed {
+            self.interrupted.store(true, Ordering::Relaxed);
+        }
+        passed
+    }
+}
+
+/// Loads bundle modules on demand and caches their `module` objects
+struct ModuleLoader<'js> {
+    bundle: Rc<ScriptBundle>,
+    cache: RefCell<HashMap<String, Object<'js>>>,
+}
+
+fn run_bundle<'js>(
+    ctx: &Ctx<'js>,
+    bundle: Rc<ScriptBundle>,
+    game_state: &serde_json::Value,
+    limits: &ScriptLimits,
+    deadline: &Deadline,
+    output: Rc<RefCell<ScriptExecutionResult>>,
+) -> rquickjs::Result<()> {
+    // Host code is evaluated before the first line of bot code
+    let build_game: Function = ctx.eval(GAME_API)?;
+    let compat: Function = ctx.eval(GAME_API_COMPAT)?;
+    let run_bot: Function = ctx.eval(RUN_BOT)?;
+    let harden: Function = ctx.eval(HARDEN)?;
+
+    install_console(ctx, output.clone())?;
+
+    let snapshot = ctx.json_parse(game_state.to_string())?;
+    let host = Object::new(ctx.clone())?;
+    host.set("issue", Function::new(ctx.clone(), issue_fn(output.clone()))?)?;
+    host.set("sendMessage", Function::new(ctx.clone(), send_message_fn(output.clone()))?)?;
+    host.set("markMessagesRead", Function::new(ctx.clone(), move || {
+        output.borrow_mut().messages_read = true;
+    })?)?;
+
+    let game: Value = build_game.call((snapshot.clone(), host.clone()))?;
+    let changes = ctx.json_parse(api_version::changes_json().to_string())?;
+    let game: Value = compat.call((game, snapshot, host, bundle.api_version(), changes))?;
+    ctx.globals().set("game", game.clone())?;
+
+    let allowed: Vec<&str> = STANDARD_GLOBALS.iter().chain(HOST_GLOBALS).copied().collect();
+    harden.call::<_, ()>((allowed, limits.strict, limits.freeze_intrinsics))?;
+
+    let loader = Rc::new(ModuleLoader {
+        bundle,
+        cache: RefCell::new(HashMap::new()),
+    });
+    deadline.start(limits.timeout);
+    let exported = require_module(ctx, &loader, ENTRY_MODULE)?;
+    run_bot.call::<_, ()>((exported, game))
+}
+
+/// Install a `console` object whose output is captured instead of printed
+fn install_console<'js>(ctx: &Ctx<'js>, output: Rc<RefCell<ScriptExecutionResult>>) -> rquickjs::Result<()> {
+    let console = Object::new(ctx.clone())?;
+    console.set("log", Function::new(ctx.clone(), console_log(output))?)?;
+    ctx.globals().set("console", console)
+}
+
+fn console_log<'js>(output: Rc<RefCell<ScriptExecutionResult>>) -> impl Fn(Ctx<'js>, Rest<Value<'js>>) -> rquickjs::Result<()> + 'js {
+    move |ctx: Ctx<'js>, args: Rest<Value<'js>>| {
+        if output.borrow().logs.len() >= MAX_LOG_LINES {
+            return Ok(());
+        }
+
+        // Converting runs the bot's `toJSON` methods, which may call back into the host:
+        // the output is only borrowed once the values are converted
+        let mut parts = Vec::with_capacity(args.0.len());
+        for arg in args.0 {
+            let text = match arg.as_string() {
+                Some(s) => s.to_string()?,
+                None => ctx.json_stringify(arg)?
+                    .map(|s| s.to_string())
+                    .transpose()?
+                    .unwrap_or_else(|| "undefined".to_string()),
+            };
+            parts.push(text);
+        }
+        let mut output = output.borrow_mut();
+        if output.logs.len() < MAX_LOG_LINES {
+            output.logs.push(parts.join(" "));
+        }
+        Ok(())
+    }
+}
+
+fn issue_fn<'js>(output: Rc<RefCell<ScriptExecutionResult>>) -> impl Fn(Ctx<'js>, String, Option<String>, Value<'js>) -> rquickjs::Result<()> + 'js {
+    move |ctx: Ctx<'js>, action: String, actor: Option<String>, params: Value<'js>| {
+        if output.borrow().commands.len() >= MAX_COMMANDS_PER_TICK {
+            return Ok(());
+        }
+
+        let params = to_json(&ctx, params)?;
+        let mut output = output.borrow_mut();
+        if output.commands.len() < MAX_COMMANDS_PER_TICK {
+            output.commands.push(BotCommand { action, actor, params, player_id: None });
+        }
+        Ok(())
+    }
+}
+
+fn send_message_fn<'js>(output: Rc<RefCell<ScriptExecutionResult>>) -> impl Fn(Ctx<'js>, String, Value<'js>) -> rquickjs::Result<bool> + 'js {
+    move |ctx: Ctx<'js>, to: String, data: Value<'js>| {
+        if output.borrow().sent_messages.len() >= MAX_MESSAGES_PER_TICK {
+            return Ok(false);
+        }
+
+        let payload = to_json(&ctx, data)?;
+        if check_payload(&payload).is_err() {
+            return Ok(false);
+        }
+        let mut output = output.borrow_mut();
+        if output.sent_messages.len() >= MAX_MESSAGES_PER_TICK {
+            return Ok(false);
+        }
+        output.sent_messages.push(OutgoingMessage { to, payload });
+        Ok(true)
+    }
+}
+
+/// Convert a script value to JSON (`null` for values JSON cannot represent)
+fn to_json<'js>(ctx: &Ctx<'js>, value: Value<'js>) -> rquickjs::Result<serde_json::Value> {
+    Ok(match ctx.json_stringify(value)? {
+        Some(json) => serde_json::from_str(&json.to_string()?).unwrap_or(serde_json::Value::Null),
+        None => serde_json::Value::Null,
+    })
+}
+
+/// Evaluate a bundle module (once) and return its `module.exports`
+fn require_module<'js>(ctx: &Ctx<'js>, loader: &Rc<ModuleLoader<'js>>, name: &str) -> rquickjs::Result<Value<'js>> {
+    if let Some(module) = loader.cache.borrow().get(name) {
+        return module.get("exports");
+    }
+
+    let source = loader.bundle.get(name)
+        .or_else(|| loader.bundle.library(name))
+        .ok_or_else(|| Exception::throw_message(ctx, &format!("Cannot find module {}", name)))?;
+    let source = to_commonjs(source)
+        .map_err(|e| Exception::throw_syntax(ctx, &format!("{}: {}", name, e)))?;
+
+    let (declared, promise) = Module::declare(ctx.clone(), name, wrap_module(&source))?.eval()?;
+    promise.finish::<()>()?;
+    let factory: Function = declared.get("default")?;
+
+    let module = Object::new(ctx.clone())?;
+    let exports = Object::new(ctx.clone())?;
+    module.set("exports", exports.clone())?;
+    loader.cache.borrow_mut().insert(name.to_string(), module.clone());
+
+    let require = Function::new(ctx.clone(), require_fn(loader.clone(), name.to_string()))?;
+    factory.call::<_, ()>((module.clone(), exports, require))?;
+
+    module.get("exports")
+}
+
+/// Wrap a CommonJS module source as an ES module exporting its factory
+fn wrap_module(source: &str) -> String {
+    // Keep the user's code on the first line so line numbers in errors match the source
+    format!("{}{}\n}}", MODULE_PREFIX, source)
+}
+
+fn require_fn<'js>(loader: Rc<ModuleLoader<'js>>, from: String) -> impl Fn(Ctx<'js>, String) -> rquickjs::Result<Value<'js>> + 'js {
+    move |ctx: Ctx<'js>, specifier: String| {
+        let name = loader.bundle.resolve(&specifier, &from)
+            .map_err(|e| Exception::throw_message(&ctx, &e))?;
+        require_module(&ctx, &loader, &name)
+    }
+}
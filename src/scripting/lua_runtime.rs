@@ -0,0 +1,266 @@
+//! Lua runtime
+//!
+//! Executes a player's Lua script (Lua 5.4) in a fresh state with time and memory
+//! limits. Only the `string`, `table`, `math`, `utf8`, and `coroutine` libraries are
+//! loaded, and `load`, `loadfile`, and `dofile` are removed: scripts cannot touch the
+//! filesystem, the network, or precompiled bytecode.
+//!
+//! The bot API (`game_api.lua`) matches the JavaScript one over the same JSON snapshot,
+//! so `game.getMyUnits()` returns the same data in both languages, and is adapted to the
+//! bundle's API version the same way. The script may return a bot table with an
+//! `onTick(self, game)` method or a function taking `game`, or simply run its logic at
+//! the top level using the global `game`. `print` output is captured like `console.log`
+//! (tables are logged as JSON). Lua bots are a single file.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Instant;
+
+use mlua::{
+    Function, HookTriggers, Lua, LuaOptions, LuaSerdeExt, MultiValue,
+    StdLib, Table, Value,
+};
+
+use crate::scripting::api_version;
+use crate::scripting::bundle::ScriptBundle;
+use crate::scripting::commands::{BotCommand, MAX_COMMANDS_PER_TICK};
+use crate::scripting::js_runtime::{ScriptExecutionResult, ScriptLimits, MAX_LOG_LINES};
+use crate::scripting::messaging::{check_payload, OutgoingMessage, MAX_MESSAGES_PER_TICK};
+use crate::scripting::runtime::{ScriptLanguage, ScriptRuntime};
+
+/// Builds the `game` table from a snapshot and host callbacks
+const GAME_API: &str = include_str!("game_api.lua");
+
+/// Gives the `game` table the shape of the bundle's API version
+const GAME_API_COMPAT: &str = include_str!("game_api_compat.lua");
+
+/// Chunk name of the player's script in error messages
+const CHUNK_NAME: &str = "=main.lua";
+
+/// Tables nested deeper than this are converted to `null` (also stops recursive tables)
+const MAX_JSON_DEPTH: usize = 64;
+
+/// VM instructions between two time limit checks
+const HOOK_INSTRUCTIONS: u32 = 1000;
+
+/// Lua implementation of [`ScriptRuntime`]
+#[derive(Debug, Clone, Default)]
+pub struct LuaRuntime {
+    limits: ScriptLimits,
+}
+
+impl LuaRuntime {
+    /// Create a Lua runtime with the given limits
+    pub fn new(limits: ScriptLimits) -> Self {
+        Self { limits }
+    }
+}
+
+impl ScriptRuntime for LuaRuntime {
+    fn language(&self) -> ScriptLanguage {
+        ScriptLanguage::Lua
+    }
+
+    fn compile(&self, bundle: &ScriptBundle) -> Result<(), String> {
+        let source = single_source(bundle)?;
+        let lua = new_state(&self.limits)?;
+        lua.load(source)
+            .set_name(CHUNK_NAME)
+            .into_function()
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    fn execute_tick(&self, bundle: &ScriptBundle, game_state: &serde_json::Value) -> ScriptExecutionResult {
+        let mut result = ScriptExecutionResult::default();
+        let source = match single_source(bundle) {
+            Ok(source) => source,
+            Err(e) => {
+                result.error = Some(e);
+                return result;
+            }
+        };
+        let lua = match new_state(&self.limits) {
+            Ok(lua) => lua,
+            Err(e) => {
+                result.error = Some(e);
+                return result;
+            }
+        };
+
+        let deadline = Instant::now() + self.limits.timeout;
+        lua.set_hook(HookTriggers::new().every_nth_instruction(HOOK_INSTRUCTIONS), move |_, _| {
+            if Instant::now() >= deadline {
+                Err(mlua::Error::runtime("time limit exceeded"))
+            } else {
+                Ok(())
+            }
+        });
+
+        let output = Rc::new(RefCell::new(ScriptExecutionResult::default()));
+        let error = run_script(&lua, source, bundle.api_version(), game_state, output.clone()).err();
+
+        result = output.take();
+        result.error = error.map(|e| {
+            if Instant::now() >= deadline {
+                format!("Script exceeded time limit of {}ms", self.limits.timeout.as_millis())
+            } else {
+                e.to_string().trim_end().to_string()
+            }
+        });
+        result
+    }
+
+    fn limits(&self) -> &ScriptLimits {
+        &self.limits
+    }
+}
+
+/// The script of a Lua bundle (Lua bots are a single file)
+fn single_source(bundle: &ScriptBundle) -> Result<&str, String> {
+    if bundle.modules().len() != 1 {
+        return Err("Lua bundles must contain a single script".to_string());
+    }
+    Ok(bundle.entry())
+}
+
+/// Create a Lua state with only the safe libraries and the memory limit applied
+fn new_state(limits: &ScriptLimits) -> Result<Lua, String> {
+    let libs = StdLib::STRING | StdLib::TABLE | StdLib::MATH | StdLib::UTF8 | StdLib::COROUTINE;
+    let lua = Lua::new_with(libs, LuaOptions::default())
+        .map_err(|e| format!("Failed to create script runtime: {}", e))?;
+    for name in ["load", "loadfile", "dofile"] {
+        lua.globals().raw_remove(name).map_err(|e| format!("Failed to create script runtime: {}", e))?;
+    }
+    lua.set_memory_limit(limits.max_memory_bytes)
+        .map_err(|e| format!("Failed to create script runtime: {}", e))?;
+    Ok(lua)
+}
+
+fn run_script(
+    lua: &Lua,
+    source: &str,
+    api_version: u32,
+    game_state: &serde_json::Value,
+    output: Rc<RefCell<ScriptExecutionResult>>,
+) -> mlua::Result<()> {
+    lua.globals().set("print", print_fn(lua, output.clone())?)?;
+
+    let snapshot = lua.to_value(game_state)?;
+    let host = lua.create_table()?;
+    host.set("issue", issue_fn(lua, output.clone())?)?;
+    host.set("sendMessage", send_message_fn(lua, output.clone())?)?;
+    host.set("markMessagesRead", lua.create_function(move |_, ()| {
+        output.borrow_mut().messages_read = true;
+        Ok(())
+    })?)?;
+    let array = lua.create_function(|lua, table: Table| {
+        table.set_metatable(Some(lua.array_metatable()));
+        Ok(table)
+    })?;
+
+    let build_game: Function = lua.load(GAME_API).set_name("=game_api.lua").eval()?;
+    let game: Table = build_game.call((snapshot.clone(), host.clone(), lua.null(), array))?;
+    let compat: Function = lua.load(GAME_API_COMPAT).set_name("=game_api_compat.lua").eval()?;
+    let changes = lua.to_value(&api_version::changes_json())?;
+    let game: Table = compat.call((game, snapshot, host, api_version, changes))?;
+    lua.globals().set("game", game.clone())?;
+
+    let exported: Value = lua.load(source).set_name(CHUNK_NAME).eval()?;
+    match exported {
+        Value::Function(bot) => bot.call(game),
+        Value::Table(bot) => match bot.get::<_, Value>("onTick")? {
+            Value::Function(on_tick) => on_tick.call((bot, game)),
+            _ => Ok(()),
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Convert a script value to JSON
+///
+/// Functions are skipped (like `JSON.stringify`), tables tagged as arrays or holding a
+/// non-empty sequence become arrays, and values JSON cannot represent become `null`.
+fn to_json(lua: &Lua, value: Value) -> serde_json::Value {
+    table_to_json(lua, value, 0)
+}
+
+fn table_to_json(lua: &Lua, value: Value, depth: usize) -> serde_json::Value {
+    let table = match value {
+        Value::Table(table) if depth < MAX_JSON_DEPTH => table,
+        other => return lua.from_value(other).unwrap_or(serde_json::Value::Null),
+    };
+
+    let is_array = table.raw_len() > 0 || table.get_metatable().is_some_and(|mt| mt == lua.array_metatable());
+    if is_array {
+        return table.sequence_values::<Value>()
+            .filter_map(Result::ok)
+            .filter(|value| !matches!(value, Value::Function(_)))
+            .map(|value| table_to_json(lua, value, depth + 1))
+            .collect();
+    }
+    table.pairs::<Value, Value>()
+        .filter_map(Result::ok)
+        .filter(|(_, value)| !matches!(value, Value::Function(_)))
+        .filter_map(|(key, value)| {
+            let key = match key {
+                Value::String(key) => key.to_str().ok()?.to_string(),
+                Value::Integer(key) => key.to_string(),
+                _ => return None,
+            };
+            Some((key, table_to_json(lua, value, depth + 1)))
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+fn print_fn(lua: &Lua, output: Rc<RefCell<ScriptExecutionResult>>) -> mlua::Result<Function<'_>> {
+    lua.create_function(move |lua, args: MultiValue| {
+        let mut output = output.borrow_mut();
+        if output.logs.len() >= MAX_LOG_LINES {
+            return Ok(());
+        }
+
+        let mut parts = Vec::with_capacity(args.len());
+        for arg in args {
+            let text = match arg {
+                Value::String(s) => s.to_str()?.to_string(),
+                Value::Nil => "nil".to_string(),
+                Value::Function(_) => "function".to_string(),
+                other => to_json(lua, other).to_string(),
+            };
+            parts.push(text);
+        }
+        output.logs.push(parts.join(" "));
+        Ok(())
+    })
+}
+
+fn issue_fn(lua: &Lua, output: Rc<RefCell<ScriptExecutionResult>>) -> mlua::Result<Function<'_>> {
+    lua.create_function(move |lua, (action, actor, params): (String, Option<String>, Value)| {
+        let mut output = output.borrow_mut();
+        if output.commands.len() >= MAX_COMMANDS_PER_TICK {
+            return Ok(());
+        }
+
+        let params = to_json(lua, params);
+        output.commands.push(BotCommand { action, actor, params, player_id: None });
+        Ok(())
+    })
+}
+
+fn send_message_fn(lua: &Lua, output: Rc<RefCell<ScriptExecutionResult>>) -> mlua::Result<Function<'_>> {
+    lua.create_function(move |lua, (to, data): (String, Value)| {
+        let mut output = output.borrow_mut();
+        if output.sent_messages.len() >= MAX_MESSAGES_PER_TICK {
+            return Ok(false);
+        }
+
+        let payload = to_json(lua, data);
+        if check_payload(&payload).is_err() {
+            return Ok(false);
+        }
+        output.sent_messages.push(OutgoingMessage { to, payload });
+        Ok(true)
+    })
+}
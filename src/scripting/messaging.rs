@@ -0,0 +1,46 @@
+//! Inter-bot messaging
+//!
+//! Bots can send JSON messages to other players' bots. Messages sent during tick N are
+//! delivered to the recipient's inbox at the start of tick N+1 and consumed when read.
+//! Payloads are limited to [`MAX_MESSAGE_BYTES`] of JSON and each player can send at most
+//! [`MAX_MESSAGES_PER_TICK`] messages per script tick.
+
+use serde::{Deserialize, Serialize};
+
+/// Default maximum number of undelivered plus unread messages per player
+pub const MAX_INBOX_MESSAGES: usize = 100;
+
+/// Maximum size of a message payload, serialized as JSON
+pub const MAX_MESSAGE_BYTES: usize = 1024;
+
+/// Maximum number of messages a player can send per script tick
+pub const MAX_MESSAGES_PER_TICK: usize = 10;
+
+/// A message delivered to a bot's inbox
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BotMessage {
+    /// Sending player
+    pub from: String,
+    /// Message data
+    pub payload: serde_json::Value,
+    /// Tick during which the message was sent
+    pub sent_at_tick: u64,
+}
+
+/// A message sent by a script during one execution
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutgoingMessage {
+    /// Recipient player
+    pub to: String,
+    /// Message data
+    pub payload: serde_json::Value,
+}
+
+/// Check that a payload fits in [`MAX_MESSAGE_BYTES`] once serialized
+pub fn check_payload(payload: &serde_json::Value) -> Result<(), String> {
+    let size = payload.to_string().len();
+    if size > MAX_MESSAGE_BYTES {
+        return Err(format!("Message payload is {} bytes (max {})", size, MAX_MESSAGE_BYTES));
+    }
+    Ok(())
+}
@@ -2,6 +2,21 @@
 //! 
 //! Provides secure sandbox environment for executing player-submitted JavaScript code.
 
+pub mod api_version;
+pub mod bundle;
+pub mod commands;
+pub mod errors;
+pub mod es_modules;
+pub mod handle;
+pub mod js_runtime;
+pub mod lua_runtime;
+pub mod messaging;
+pub mod runtime;
+pub mod sanitizer;
 pub mod sandbox; 
+pub mod scheduler;
+pub mod slots;
+pub mod typescript;
+pub mod wasm_runtime;
 
-pub use sandbox::*;
\ No newline at end of file
+pub use sandbox::*;
@@ -0,0 +1,110 @@
+//! Script runtimes
+//!
+//! Each supported scripting language has a [`ScriptRuntime`]; the sandbox routes a
+//! player's bundle to the runtime of the language it was submitted in. All runtimes
+//! share the same inputs (a JSON snapshot) and outputs (logs, commands, messages).
+
+use serde::{Deserialize, Serialize};
+
+use crate::scripting::bundle::ScriptBundle;
+use crate::scripting::js_runtime::{JsRuntime, ScriptExecutionResult, ScriptLimits};
+use crate::scripting::lua_runtime::LuaRuntime;
+use crate::scripting::typescript::TsRuntime;
+use crate::scripting::wasm_runtime::WasmRuntime;
+
+/// Language a bundle is written in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptLanguage {
+    /// JavaScript (QuickJS)
+    #[default]
+    JavaScript,
+    /// WebAssembly module, submitted base64-encoded (wasmtime)
+    Wasm,
+    /// Lua 5.4 script
+    Lua,
+    /// TypeScript, run as JavaScript once its types are removed
+    TypeScript,
+}
+
+impl ScriptLanguage {
+    /// All languages this server can run
+    pub const SUPPORTED: &'static [ScriptLanguage] = &[
+        ScriptLanguage::JavaScript,
+        ScriptLanguage::Wasm,
+        ScriptLanguage::Lua,
+        ScriptLanguage::TypeScript,
+    ];
+
+    /// Parse a language name as sent by clients (case-insensitive)
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "javascript" | "js" => Ok(ScriptLanguage::JavaScript),
+            "wasm" | "webassembly" => Ok(ScriptLanguage::Wasm),
+            "lua" => Ok(ScriptLanguage::Lua),
+            "typescript" | "ts" => Ok(ScriptLanguage::TypeScript),
+            other => Err(format!(
+                "Unsupported language: {} (supported: {})",
+                other,
+                Self::SUPPORTED.iter().map(|l| l.name()).collect::<Vec<_>>().join(", ")
+            )),
+        }
+    }
+
+    /// Canonical language name
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScriptLanguage::JavaScript => "javascript",
+            ScriptLanguage::Wasm => "wasm",
+            ScriptLanguage::Lua => "lua",
+            ScriptLanguage::TypeScript => "typescript",
+        }
+    }
+}
+
+/// A scripting language implementation
+pub trait ScriptRuntime: Send + Sync {
+    /// Language this runtime executes
+    fn language(&self) -> ScriptLanguage;
+
+    /// Check that every module of the bundle compiles, without running it
+    fn compile(&self, bundle: &ScriptBundle) -> Result<(), String>;
+
+    /// Run the bundle for one tick against a snapshot of the player's view
+    fn execute_tick(&self, bundle: &ScriptBundle, game_state: &serde_json::Value) -> ScriptExecutionResult;
+
+    /// Resource limits applied to each execution
+    fn limits(&self) -> &ScriptLimits;
+}
+
+/// Create the runtime for a language with the given limits
+pub fn create_runtime(language: ScriptLanguage, limits: ScriptLimits) -> Box<dyn ScriptRuntime> {
+    match language {
+        ScriptLanguage::JavaScript => Box::new(JsRuntime::new(limits)),
+        ScriptLanguage::Wasm => Box::new(WasmRuntime::new(limits)),
+        ScriptLanguage::Lua => Box::new(LuaRuntime::new(limits)),
+        ScriptLanguage::TypeScript => Box::new(TsRuntime::new(limits)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::world::World;
+    use crate::scripting::sandbox::Sandbox;
+
+    #[test]
+    fn test_each_bundle_runs_on_its_language_runtime() {
+        let mut sandbox = Sandbox::new();
+        // Each bot is a syntax error in the other language
+        sandbox.submit_code("js_bot".to_string(), "console.log(typeof Symbol);".to_string()).unwrap();
+        let lua = ScriptBundle::single("print(_VERSION)".to_string()).unwrap().with_language(ScriptLanguage::Lua);
+        sandbox.submit("lua_bot".to_string(), lua).unwrap();
+
+        let results = sandbox.tick_execute_all(&mut World::new());
+        let logs: Vec<(&str, Option<&str>, &[String])> = results.iter()
+            .map(|(player_id, result)| (player_id.as_str(), result.error.as_deref(), result.logs.as_slice()))
+            .collect();
+        assert_eq!(logs, [("js_bot", None, &["function".to_string()][..]), ("lua_bot", None, &["Lua 5.4".to_string()][..])]);
+    }
+}
@@ -2,25 +2,185 @@
 //! 
 //! Provides isolation for player code from the rest of the system.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::game::replay::WorldSnapshot;
+use crate::game::world::World;
+use crate::scripting::api_version::{LATEST_API_VERSION, OLDEST_API_VERSION};
+use crate::scripting::bundle::{ScriptBundle, ENTRY_MODULE, MAX_MODULE_SIZE, MAX_PLAYER_MODULES};
+use crate::scripting::errors::{ErrorLog, LoggedError, ScriptError};
+use crate::scripting::js_runtime::{capability_report, CapabilityReport, ScriptExecutionResult, ScriptLimits};
+use crate::scripting::messaging::{check_payload, BotMessage, MAX_INBOX_MESSAGES, MAX_MESSAGES_PER_TICK};
+use crate::scripting::runtime::{create_runtime, ScriptLanguage, ScriptRuntime};
+use crate::scripting::scheduler::{FairScheduler, ScriptJob};
+use crate::scripting::slots::{default_max_concurrent_scripts, ExecutionSlots};
 
 /// Script execution sandbox
 pub struct Sandbox {
     /// Variables accessible in the sandbox
     variables: HashMap<String, f64>,
-    /// Player code submissions (player_id -> code)
-    codes: HashMap<String, String>,
+    /// Player code submissions (player_id -> bundle)
+    bundles: HashMap<String, Arc<ScriptBundle>>,
+    /// Current tick (set by `begin_tick`)
+    tick: u64,
+    /// Messages sent this tick, delivered at the start of the next one (recipient, message)
+    pending_messages: Vec<(String, BotMessage)>,
+    /// Delivered, unread messages per player
+    inboxes: HashMap<String, VecDeque<BotMessage>>,
+    /// Maximum pending plus unread messages per player (the oldest are dropped beyond it)
+    inbox_limit: usize,
+    /// Messages sent by each player during the current tick
+    sent_this_tick: HashMap<String, usize>,
+    /// Whether players can only message their allies
+    allies_only: bool,
+    /// Allies of each player, as of their latest snapshot
+    allies: HashMap<String, Vec<String>>,
+    /// Approved shared libraries attached to every bundle (library name -> source)
+    libraries: Arc<BTreeMap<String, String>>,
+    /// Oldest API version bots can be bound to; older bots must be resubmitted
+    min_api_version: u32,
+    /// Runtime for each supported language (shared with in-flight tick batches)
+    runtimes: Arc<Runtimes>,
+    /// Bound on the scripts running at once (shared with in-flight tick batches)
+    slots: ExecutionSlots,
+    /// Execution time statistics per player
+    player_stats: HashMap<String, ScriptStats>,
+    /// Latest script errors per player
+    errors: HashMap<String, ErrorLog>,
+    /// New (not repeated) script errors, with the player who raised them
+    error_events: broadcast::Sender<(String, LoggedError)>,
+}
+
+/// Capacity of the new script error channel (slower subscribers miss errors)
+const ERROR_EVENTS_CAPACITY: usize = 256;
+
+/// Each execution counts for 1/`CPU_AVERAGE_WEIGHT` of [`ScriptStats::average_cpu_ns`]
+const CPU_AVERAGE_WEIGHT: u64 = 8;
+
+/// Execution time statistics of a player's script (dry runs are not counted)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ScriptStats {
+    /// Number of times the script ran
+    pub total_executions: u64,
+    /// Time spent in all executions, in nanoseconds
+    pub total_cpu_ns: u64,
+    /// Longest execution, in nanoseconds
+    pub max_cpu_ns: u64,
+    /// Time spent in the latest execution, in nanoseconds
+    pub last_execution_cpu_ns: u64,
+    /// Time per execution, averaged with more weight on the recent ones, in nanoseconds
+    pub average_cpu_ns: u64,
+    /// Number of times the script waited too long for an execution slot and was put off to the next tick
+    pub queued_executions: u64,
+}
+
+impl ScriptStats {
+    /// Add one execution
+    pub fn record(&mut self, cpu_ns: u64) {
+        self.total_executions += 1;
+        self.total_cpu_ns = self.total_cpu_ns.saturating_add(cpu_ns);
+        self.max_cpu_ns = self.max_cpu_ns.max(cpu_ns);
+        self.last_execution_cpu_ns = cpu_ns;
+        self.average_cpu_ns = if self.total_executions == 1 {
+            cpu_ns
+        } else {
+            self.average_cpu_ns - self.average_cpu_ns / CPU_AVERAGE_WEIGHT + cpu_ns / CPU_AVERAGE_WEIGHT
+        };
+    }
+}
+
+/// Runtime for each supported language
+type Runtimes = HashMap<ScriptLanguage, Box<dyn ScriptRuntime>>;
+
+/// The script executions of one tick, detached from the sandbox
+///
+/// Built by [`Sandbox::prepare_tick`]; executing it needs no access to the sandbox, so
+/// code can be submitted while a batch runs. Results go back through [`Sandbox::finish_tick`].
+pub struct TickBatch {
+    /// (player_id, bundle, snapshot with inbox), in fair order (see [`FairScheduler`])
+    jobs: Vec<(String, Arc<ScriptBundle>, serde_json::Value)>,
+    runtimes: Arc<Runtimes>,
+    slots: ExecutionSlots,
+    min_api_version: u32,
+}
+
+impl TickBatch {
+    /// Number of scripts in the batch
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    /// Whether the batch has no scripts
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// Run every script of the batch on `pool` (blocking); results are sorted by player ID
+    ///
+    /// Each execution gets a `script` span, a child of the span current when this is called.
+    /// Scripts start in fair order; those beyond the engine's concurrency limit wait for a
+    /// slot (see [`Sandbox::set_max_concurrent_scripts`]), and fail with
+    /// [`ScriptError::Queued`] if none frees up in time.
+    pub fn execute(self, pool: &ThreadPool) -> Vec<(String, Result<ScriptExecutionResult, ScriptError>)> {
+        let runtimes = self.runtimes;
+        let slots = self.slots;
+        let min_api_version = self.min_api_version;
+        let parent = tracing::Span::current();
+        let mut results: Vec<(String, Result<ScriptExecutionResult, ScriptError>)> = pool.install(|| {
+            // Bridged, so idle threads take the jobs in order
+            self.jobs.into_iter().par_bridge()
+                .map(|(player_id, bundle, snapshot)| {
+                    let result = match check_min_api_version(&bundle, min_api_version) {
+                        Ok(()) => parent.in_scope(|| execute_traced(&runtimes, &slots, &player_id, &bundle, &snapshot)),
+                        Err(error) => Ok(ScriptExecutionResult { error: Some(error), ..Default::default() }),
+                    };
+                    (player_id, result)
+                })
+                .collect()
+        });
+        results.sort_by(|(a, _), (b, _)| a.cmp(b));
+        results
+    }
 }
 
 /// Type alias for ScriptEngine
 pub type ScriptEngine = Sandbox;
 
 impl Sandbox {
-    /// Create a new sandbox
+    /// Create a new sandbox with the default script limits
     pub fn new() -> Self {
+        Self::with_limits(ScriptLimits::default())
+    }
+
+    /// Create a new sandbox whose runtimes apply `limits` to every execution
+    pub fn with_limits(limits: ScriptLimits) -> Self {
+        let slots = ExecutionSlots::new(default_max_concurrent_scripts(), limits.timeout);
         Sandbox {
             variables: HashMap::new(),
-            codes: HashMap::new(),
+            bundles: HashMap::new(),
+            tick: 0,
+            pending_messages: Vec::new(),
+            inboxes: HashMap::new(),
+            inbox_limit: MAX_INBOX_MESSAGES,
+            sent_this_tick: HashMap::new(),
+            allies_only: false,
+            allies: HashMap::new(),
+            libraries: Arc::default(),
+            min_api_version: OLDEST_API_VERSION,
+            runtimes: Arc::new(ScriptLanguage::SUPPORTED.iter()
+                .map(|language| (*language, create_runtime(*language, limits.clone())))
+                .collect()),
+            slots,
+            player_stats: HashMap::new(),
+            errors: HashMap::new(),
+            error_events: broadcast::channel(ERROR_EVENTS_CAPACITY).0,
         }
     }
 
@@ -34,41 +194,522 @@ impl Sandbox {
         self.variables.get(name)
     }
 
-    /// Submit player code
+    /// Submit player JavaScript code (stored as a single-module bundle)
     pub fn submit_code(&mut self, player_id: String, code: String) -> Result<(), String> {
-        const MAX_CODE_LENGTH: usize = 1_000_000; // 1MB limit
-        
-        if code.len() > MAX_CODE_LENGTH {
-            return Err(format!("Code too large: {} bytes (max: {} bytes)", code.len(), MAX_CODE_LENGTH));
-        }
-        
+        self.submit(player_id, ScriptBundle::single(code)?)
+    }
+
+    /// Submit a multi-module JavaScript bundle (module name -> source)
+    pub fn submit_bundle(&mut self, player_id: String, modules: BTreeMap<String, String>) -> Result<(), String> {
+        self.submit(player_id, ScriptBundle::from_modules(modules)?)
+    }
+
+    /// Submit a bundle in any supported language, replacing the player's active code
+    pub fn submit(&mut self, player_id: String, bundle: ScriptBundle) -> Result<(), String> {
         if player_id.trim().is_empty() {
             return Err("Player ID cannot be empty".to_string());
         }
-        
-        self.codes.insert(player_id, code);
+
+        let runtime = self.runtimes.get(&bundle.language())
+            .ok_or_else(|| format!("Unsupported language: {}", bundle.language().name()))?;
+        if bundle.api_version() < self.min_api_version {
+            return Err(format!(
+                "API v{} is no longer supported (minimum: v{}), update your bot to API v{}",
+                bundle.api_version(), self.min_api_version, LATEST_API_VERSION
+            ));
+        }
+
+        // Syntax errors (and for compiled modules, imports, exports and size) are reported up front
+        runtime.compile(&bundle)?;
+
+        self.bundles.insert(player_id, Arc::new(bundle.with_libraries(self.libraries.clone())));
         Ok(())
     }
 
-    /// Get player code
+    /// Replace the shared libraries every bot can import as `@community/<name>`
+    pub fn set_libraries(&mut self, libraries: BTreeMap<String, String>) {
+        self.libraries = Arc::new(libraries);
+        for bundle in self.bundles.values_mut() {
+            *bundle = Arc::new(bundle.as_ref().clone().with_libraries(self.libraries.clone()));
+        }
+    }
+
+    /// Shared libraries every bot can import
+    pub fn libraries(&self) -> &Arc<BTreeMap<String, String>> {
+        &self.libraries
+    }
+
+    /// Add or replace one module of a player's JavaScript or TypeScript bundle
+    ///
+    /// The entry module (`main.js`) must be submitted first. Bundles built this way hold
+    /// at most [`MAX_PLAYER_MODULES`] modules of [`MAX_MODULE_SIZE`] bytes each.
+    pub fn submit_module(&mut self, player_id: &str, module_name: &str, code: &str) -> Result<(), String> {
+        if code.len() > MAX_MODULE_SIZE {
+            return Err(format!("Module too large: {} bytes (max: {} bytes)", code.len(), MAX_MODULE_SIZE));
+        }
+
+        let (mut modules, language, api_version) = match self.bundles.get(player_id) {
+            Some(bundle) => (bundle.modules().clone(), bundle.language(), Some(bundle.api_version())),
+            None if module_name == ENTRY_MODULE => (BTreeMap::new(), ScriptLanguage::JavaScript, None),
+            None => return Err(format!("Submit the entry module {} before other modules", ENTRY_MODULE)),
+        };
+        if !matches!(language, ScriptLanguage::JavaScript | ScriptLanguage::TypeScript) {
+            return Err(format!("Modules cannot be added to {} bots", language.name()));
+        }
+        if !modules.contains_key(module_name) && modules.len() >= MAX_PLAYER_MODULES {
+            return Err(format!("Too many modules: {} (max: {})", modules.len() + 1, MAX_PLAYER_MODULES));
+        }
+
+        modules.insert(module_name.to_string(), code.to_string());
+        let bundle = ScriptBundle::from_modules(modules)?.with_language(language);
+        self.submit(player_id.to_string(), keep_api_version(bundle, api_version)?)
+    }
+
+    /// Remove one module from a player's bundle (the entry module cannot be removed)
+    pub fn delete_module(&mut self, player_id: &str, module_name: &str) -> Result<(), String> {
+        if module_name == ENTRY_MODULE {
+            return Err(format!("The entry module {} cannot be deleted", ENTRY_MODULE));
+        }
+
+        let bundle = self.bundles.get(player_id)
+            .filter(|bundle| bundle.get(module_name).is_some())
+            .ok_or_else(|| format!("Module not found: {}", module_name))?;
+        let mut modules = bundle.modules().clone();
+        let language = bundle.language();
+        let api_version = bundle.api_version();
+        modules.remove(module_name);
+        let bundle = ScriptBundle::from_modules(modules)?.with_language(language);
+        self.submit(player_id.to_string(), keep_api_version(bundle, Some(api_version))?)
+    }
+
+    /// Get player code (source of the entry module)
     pub fn get_code(&self, player_id: &str) -> Option<&String> {
-        self.codes.get(player_id)
+        self.bundles.get(player_id).map(|bundle| bundle.entry())
+    }
+
+    /// Get a player's full bundle
+    pub fn get_bundle(&self, player_id: &str) -> Option<&ScriptBundle> {
+        self.bundles.get(player_id).map(Arc::as_ref)
     }
 
     /// List all players with submitted code
     pub fn list_players(&self) -> Vec<String> {
-        self.codes.keys().cloned().collect()
+        self.bundles.keys().cloned().collect()
     }
 
-    /// Execute a script in the sandbox
-    pub fn execute_script(&self, _script: &str) -> Result<(), String> {
-        // Placeholder for future script execution logic
+    /// One page (from 1) of the players with submitted code, sorted by ID, and the total player count
+    pub fn list_players_paginated(&self, page: u32, per_page: u32) -> (Vec<String>, u32) {
+        let mut players: Vec<&String> = self.bundles.keys().collect();
+        players.sort();
+        let start = page.saturating_sub(1) as usize * per_page as usize;
+        let items = players.iter().skip(start).take(per_page as usize).map(|player| player.to_string()).collect();
+        (items, players.len() as u32)
+    }
+
+    /// Run a player's bundle once against the game state
+    ///
+    /// Fails with [`ScriptError::NoCode`] if the player has no code, and with
+    /// [`ScriptError::Queued`] if no execution slot freed up in time. Script errors are
+    /// reported in the result and never affect other players. The player's inbox is
+    /// exposed to the script and emptied if it reads it; messages it sends are queued for
+    /// next tick.
+    pub fn execute_player(&mut self, player_id: &str, game_state: &serde_json::Value) -> Result<ScriptExecutionResult, ScriptError> {
+        let bundle = self.bundles.get(player_id).ok_or(ScriptError::NoCode)?.clone();
+        self.record_allies(player_id, game_state);
+        let snapshot = self.with_inbox(player_id, game_state);
+
+        let mut result = match check_min_api_version(&bundle, self.min_api_version) {
+            Ok(()) => execute_traced(&self.runtimes, &self.slots, player_id, &bundle, &snapshot),
+            Err(error) => Ok(ScriptExecutionResult { error: Some(error), ..Default::default() }),
+        };
+        self.apply_result(player_id, &mut result);
+        result
+    }
+
+    /// Run code once against a player's view of a recorded world, without side effects
+    ///
+    /// The script sees the snapshot as it was at that tick, plus the player's current
+    /// inbox. Nothing is applied: the commands and messages it issues are only returned,
+    /// the inbox is not consumed, and neither the submitted code nor the live world change.
+    pub fn dry_run(&self, player_id: &str, code: &str, world_snapshot: &WorldSnapshot) -> ScriptExecutionResult {
+        let prepared = ScriptBundle::single(code.to_string())
+            .and_then(|bundle| Ok((bundle, world_snapshot.player_view(player_id)?)));
+        match prepared {
+            Ok((bundle, game_state)) => {
+                let bundle = bundle.with_libraries(self.libraries.clone());
+                self.execute_bundle(&bundle, &self.with_inbox(player_id, &game_state))
+                    .unwrap_or_else(|error| ScriptExecutionResult { error: Some(error.to_string()), ..Default::default() })
+            }
+            Err(error) => ScriptExecutionResult {
+                error: Some(error),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Start a tick and detach the executions of every player that has code and a snapshot
+    ///
+    /// Bundles and inboxes are captured now; code submitted while the batch runs takes
+    /// effect on the next tick.
+    pub fn prepare_tick(&mut self, tick: u64, snapshots: &BTreeMap<String, serde_json::Value>) -> TickBatch {
+        self.begin_tick(tick);
+        for (player_id, game_state) in snapshots {
+            self.record_allies(player_id, game_state);
+        }
+
+        let jobs = self.schedule(snapshots.keys()).drain().into_iter()
+            .map(|job| {
+                let snapshot = self.with_inbox(&job.player_id, &snapshots[&job.player_id]);
+                (job.player_id, job.bundle, snapshot)
+            })
+            .collect();
+
+        TickBatch {
+            jobs,
+            runtimes: self.runtimes.clone(),
+            slots: self.slots.clone(),
+            min_api_version: self.min_api_version,
+        }
+    }
+
+    /// Run a script tick for every player with code, one script at a time, in fair order
+    ///
+    /// Each script runs on the player's current snapshot of `world`, and its commands are
+    /// applied before the next script runs. Returns the results in execution order; scripts
+    /// that got no execution slot in time are left out (they run again next tick).
+    /// [`ScriptEngineHandle::run_tick`](crate::scripting::handle::ScriptEngineHandle::run_tick)
+    /// runs the scripts in parallel instead.
+    pub fn tick_execute_all(&mut self, world: &mut World) -> Vec<(String, ScriptExecutionResult)> {
+        self.begin_tick(world.get_script_tick());
+        let players: Vec<String> = self.bundles.keys().cloned().collect();
+        let mut scheduler = self.schedule(players.iter());
+
+        let mut results = Vec::with_capacity(scheduler.len());
+        while let Some(job) = scheduler.pop() {
+            let game_state = world.player_snapshot(&job.player_id);
+            self.record_allies(&job.player_id, &game_state);
+            let snapshot = self.with_inbox(&job.player_id, &game_state);
+            let mut result = match check_min_api_version(&job.bundle, self.min_api_version) {
+                Ok(()) => execute_traced(&self.runtimes, &self.slots, &job.player_id, &job.bundle, &snapshot),
+                Err(error) => Ok(ScriptExecutionResult { error: Some(error), ..Default::default() }),
+            };
+            self.apply_result(&job.player_id, &mut result);
+            let Ok(result) = result else { continue };
+            if result.error.is_none() {
+                for error in world.apply_commands(&job.player_id, &result.commands) {
+                    log::debug!("Command of {} rejected: {}", job.player_id, error);
+                }
+            }
+            results.push((job.player_id, result));
+        }
+        results
+    }
+
+    /// Queue the scripts of the given players that have code, by their average CPU time
+    fn schedule<'a>(&self, players: impl Iterator<Item = &'a String>) -> FairScheduler {
+        let mut scheduler = FairScheduler::new();
+        for player_id in players {
+            if let Some(bundle) = self.bundles.get(player_id) {
+                scheduler.push(ScriptJob {
+                    player_id: player_id.clone(),
+                    bundle: bundle.clone(),
+                    average_cpu_ns: self.player_stats.get(player_id).map_or(0, |stats| stats.average_cpu_ns),
+                });
+            }
+        }
+        scheduler
+    }
+
+    /// Apply the results of an executed batch in player ID order (inbox reads, sent messages)
+    ///
+    /// Returns the results of the scripts that ran; those that got no execution slot in
+    /// time are only counted in their [`ScriptStats::queued_executions`].
+    pub fn finish_tick(&mut self, mut results: Vec<(String, Result<ScriptExecutionResult, ScriptError>)>) -> Vec<(String, ScriptExecutionResult)> {
+        results.sort_by(|(a, _), (b, _)| a.cmp(b));
+        results.into_iter()
+            .filter_map(|(player_id, mut result)| {
+                self.apply_result(&player_id, &mut result);
+                Some((player_id, result.ok()?))
+            })
+            .collect()
+    }
+
+    /// Copy of the game state with the player's inbox in its `messages` field
+    fn with_inbox(&self, player_id: &str, game_state: &serde_json::Value) -> serde_json::Value {
+        let mut snapshot = game_state.clone();
+        if let Some(fields) = snapshot.as_object_mut() {
+            let inbox: Vec<&BotMessage> = self.inboxes.get(player_id).into_iter().flatten().collect();
+            fields.insert("messages".to_string(), serde_json::json!(inbox));
+        }
+        snapshot
+    }
+
+    /// Remember the allies listed in a player's snapshot (checked when messaging is allies-only)
+    fn record_allies(&mut self, player_id: &str, game_state: &serde_json::Value) {
+        let allies = game_state.get("allies")
+            .and_then(|allies| serde_json::from_value(allies.clone()).ok())
+            .unwrap_or_default();
+        self.set_allies(player_id, allies);
+    }
+
+    /// Record the execution time and error, consume the inbox if the script read it and queue the messages it sent
+    fn apply_result(&mut self, player_id: &str, result: &mut Result<ScriptExecutionResult, ScriptError>) {
+        let stats = self.player_stats.entry(player_id.to_string()).or_default();
+        let result = match result {
+            Ok(result) => result,
+            Err(_) => {
+                log::warn!("Script of {} got no execution slot within {:?}, running it next tick", player_id, self.slots.queue_timeout());
+                stats.queued_executions += 1;
+                return;
+            }
+        };
+        stats.record(result.cpu_ns);
+        if let (Some(error), Some(bundle)) = (&result.error, self.bundles.get(player_id)) {
+            let error = LoggedError::parse(error, bundle, self.tick);
+            if self.errors.entry(player_id.to_string()).or_default().record(error.clone()) {
+                // Nobody may be listening
+                let _ = self.error_events.send((player_id.to_string(), error));
+            }
+        }
+        if result.messages_read {
+            self.inboxes.remove(player_id);
+        }
+        for message in std::mem::take(&mut result.sent_messages) {
+            if let Err(err) = self.send_message(player_id, &message.to, message.payload.clone()) {
+                result.logs.push(format!("sendMessage to {} failed: {}", message.to, err));
+            }
+            result.sent_messages.push(message);
+        }
+    }
+
+    /// Start a new tick, delivering messages sent during the previous one
+    pub fn begin_tick(&mut self, tick: u64) {
+        self.tick = tick;
+        self.sent_this_tick.clear();
+        for (to, message) in self.pending_messages.drain(..) {
+            self.inboxes.entry(to).or_default().push_back(message);
+        }
+    }
+
+    /// Current tick as set by `begin_tick`
+    pub fn current_tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Set the maximum pending plus unread messages per player
+    pub fn set_inbox_limit(&mut self, limit: usize) {
+        self.inbox_limit = limit;
+    }
+
+    /// Set how many scripts can run at once, across ticks, direct executions and dry runs
+    ///
+    /// Executions already running keep the previous limit.
+    pub fn set_max_concurrent_scripts(&mut self, max_scripts: usize) {
+        if max_scripts != self.slots.max_scripts() {
+            self.slots = ExecutionSlots::new(max_scripts, self.slots.queue_timeout());
+        }
+    }
+
+    /// Set how long a script waits for an execution slot before it is put off to the next tick
+    pub fn set_script_queue_timeout(&mut self, queue_timeout: Duration) {
+        self.slots.set_queue_timeout(queue_timeout);
+    }
+
+    /// Permits of the scripts running at once (one is held during each execution)
+    pub fn execution_slots(&self) -> ExecutionSlots {
+        self.slots.clone()
+    }
+
+    /// Set the oldest API version bots can be bound to
+    ///
+    /// Bots bound to an older version stop running, with an error asking to resubmit
+    /// them, and submissions bound to one are rejected.
+    pub fn set_min_api_version(&mut self, version: u32) {
+        self.min_api_version = version;
+    }
+
+    /// Oldest API version bots can be bound to
+    pub fn min_api_version(&self) -> u32 {
+        self.min_api_version
+    }
+
+    /// Only let players message their allies
+    pub fn set_allies_only(&mut self, allies_only: bool) {
+        self.allies_only = allies_only;
+    }
+
+    /// Set a player's allies (recorded from each snapshot the player's script runs on)
+    pub fn set_allies(&mut self, player_id: &str, allies: Vec<String>) {
+        if allies.is_empty() {
+            self.allies.remove(player_id);
+        } else {
+            self.allies.insert(player_id.to_string(), allies);
+        }
+    }
+
+    /// Send a message to another player's bot (delivered at the start of next tick)
+    ///
+    /// If the recipient's inbox is full, its oldest message is dropped.
+    pub fn send_message(&mut self, from_player: &str, to_player: &str, message: serde_json::Value) -> Result<(), String> {
+        if !self.bundles.contains_key(to_player) {
+            return Err(format!("Unknown player: {}", to_player));
+        }
+        if self.allies_only && !self.allies.get(from_player).is_some_and(|allies| allies.iter().any(|ally| ally == to_player)) {
+            return Err(format!("{} is not an ally", to_player));
+        }
+        check_payload(&message)?;
+
+        let sent = self.sent_this_tick.entry(from_player.to_string()).or_default();
+        if *sent >= MAX_MESSAGES_PER_TICK {
+            return Err(format!("Cannot send more than {} messages per tick", MAX_MESSAGES_PER_TICK));
+        }
+        *sent += 1;
+
+        let pending = self.pending_messages.iter().filter(|(to, _)| to == to_player).count();
+        let unread = self.inboxes.get(to_player).map_or(0, |inbox| inbox.len());
+        if pending + unread >= self.inbox_limit {
+            match self.inboxes.get_mut(to_player).filter(|inbox| !inbox.is_empty()) {
+                Some(inbox) => {
+                    inbox.pop_front();
+                }
+                None => {
+                    if let Some(oldest) = self.pending_messages.iter().position(|(to, _)| to == to_player) {
+                        self.pending_messages.remove(oldest);
+                    }
+                }
+            }
+        }
+
+        self.pending_messages.push((to_player.to_string(), BotMessage {
+            from: from_player.to_string(),
+            payload: message,
+            sent_at_tick: self.tick,
+        }));
         Ok(())
     }
+
+    /// Execution time statistics of a player's script, if it ran at least once
+    pub fn player_stats(&self, player_id: &str) -> Option<&ScriptStats> {
+        self.player_stats.get(player_id)
+    }
+
+    /// Latest distinct errors raised by a player's script, oldest first
+    pub fn get_errors(&self, player_id: &str) -> Vec<LoggedError> {
+        self.errors.get(player_id).map(ErrorLog::errors).unwrap_or_default()
+    }
+
+    /// Number of errors a player's script raised during the latest script tick
+    pub fn errors_last_tick(&self, player_id: &str) -> usize {
+        self.errors.get(player_id).map_or(0, |log| log.count_at(self.tick))
+    }
+
+    /// Receive every new script error (repeats of a kept error are not sent again)
+    pub fn subscribe_errors(&self) -> broadcast::Receiver<(String, LoggedError)> {
+        self.error_events.subscribe()
+    }
+
+    /// Delivered, unread messages for a player (left in the inbox)
+    pub fn inbox(&self, player_id: &str) -> Vec<BotMessage> {
+        self.inboxes.get(player_id)
+            .map(|inbox| inbox.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Take all delivered messages for a player (consumed on read)
+    pub fn receive_messages(&mut self, player_id: &str) -> Vec<BotMessage> {
+        self.inboxes.remove(player_id)
+            .map(Vec::from)
+            .unwrap_or_default()
+    }
+
+    /// Run a bundle once against the game state, using the runtime of its language
+    ///
+    /// Waits for an execution slot like tick executions do, and fails with
+    /// [`ScriptError::Queued`] if none frees up in time.
+    pub fn execute_bundle(&self, bundle: &ScriptBundle, game_state: &serde_json::Value) -> Result<ScriptExecutionResult, ScriptError> {
+        self.slots.run(|| execute_with(&self.runtimes, bundle, game_state))
+    }
+
+    /// What bots can reach in the JavaScript runtime (see [`capability_report`])
+    pub fn capability_report(&self) -> CapabilityReport {
+        let runtime = self.runtimes.get(&ScriptLanguage::JavaScript).expect("JavaScript is always supported");
+        capability_report(runtime.limits())
+    }
+
+    /// Execute a script in the sandbox
+    pub fn execute_script(&self, script: &str) -> Result<(), ScriptError> {
+        let bundle = ScriptBundle::single(script.to_string()).map_err(ScriptError::Raised)?;
+        match self.execute_bundle(&bundle, &serde_json::Value::Null)?.error {
+            Some(error) => Err(ScriptError::Raised(error)),
+            None => Ok(()),
+        }
+    }
 }
 
 impl Default for Sandbox {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+/// Error for a bundle bound to an API version older than `min_api_version`
+fn check_min_api_version(bundle: &ScriptBundle, min_api_version: u32) -> Result<(), String> {
+    if bundle.api_version() < min_api_version {
+        return Err(format!(
+            "Your bot is bound to API v{}, which is no longer supported (minimum: v{}): resubmit it for API v{}",
+            bundle.api_version(), min_api_version, LATEST_API_VERSION
+        ));
+    }
+    Ok(())
+}
+
+/// Keep the API version of the bundle a module edit replaces, unless the new entry module declares one
+fn keep_api_version(bundle: ScriptBundle, previous: Option<u32>) -> Result<ScriptBundle, String> {
+    match previous {
+        Some(version) if bundle.declared_api_version().is_none() => bundle.with_api_version(version),
+        _ => Ok(bundle),
+    }
+}
+
+/// Run a player's bundle in a `script` span recording its run time and outcome
+///
+/// The commands it issues are stamped with the player's ID. The span also covers the
+/// wait for an execution slot.
+fn execute_traced(runtimes: &Runtimes, slots: &ExecutionSlots, player_id: &str, bundle: &ScriptBundle, game_state: &serde_json::Value) -> Result<ScriptExecutionResult, ScriptError> {
+    let span = tracing::info_span!(
+        "script",
+        player = player_id,
+        language = bundle.language().name(),
+        cpu_ns = tracing::field::Empty,
+        outcome = tracing::field::Empty,
+    );
+    let _entered = span.enter();
+    let result = slots.run(|| execute_with(runtimes, bundle, game_state));
+    let (cpu_ns, outcome) = match &result {
+        Ok(result) => (result.cpu_ns, if result.error.is_some() { "error" } else { "ok" }),
+        Err(_) => (0, "queued"),
+    };
+    span.record("cpu_ns", cpu_ns);
+    span.record("outcome", outcome);
+    tracing::debug!(cpu_ns, outcome, "script executed");
+    result.map(|mut result| {
+        for command in &mut result.commands {
+            command.player_id = Some(player_id.to_string());
+        }
+        result
+    })
+}
+
+/// Run a bundle with the runtime of its language, timing the execution
+fn execute_with(runtimes: &Runtimes, bundle: &ScriptBundle, game_state: &serde_json::Value) -> ScriptExecutionResult {
+    let start = Instant::now();
+    let mut result = match runtimes.get(&bundle.language()) {
+        Some(runtime) => runtime.execute_tick(bundle, game_state),
+        None => ScriptExecutionResult {
+            error: Some(format!("Unsupported language: {}", bundle.language().name())),
+            ..Default::default()
+        },
+    };
+    result.cpu_ns = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+    result
+}
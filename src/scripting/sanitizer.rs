@@ -0,0 +1,133 @@
+//! Submission scanning
+//!
+//! Submitted code is scanned for patterns that only make sense when trying to reach
+//! outside the sandbox: `eval(`, `Function(`, `process.`, `__proto__`, and `require(` or
+//! `import(` of anything but the bundle's own modules and the shared libraries. The scan is
+//! a plain text search, not a security boundary (the sandbox is): matches are warnings,
+//! logged for abuse detection, and only a submission with many of them is refused.
+
+use serde::{Deserialize, Serialize};
+
+use crate::scripting::bundle::{ScriptBundle, LIBRARY_PREFIX};
+
+/// Default number of suspicious patterns that gets a submission refused
+pub const DEFAULT_MAX_SUSPICIOUS_PATTERNS: u32 = 3;
+
+/// Patterns looked for, matched at the start of an identifier
+const SUSPICIOUS_PATTERNS: &[&str] = &["require(", "process.", "__proto__", "eval(", "Function(", "import("];
+
+/// Patterns allowed when their argument is a bundle module or a shared library
+const MODULE_PATTERNS: &[&str] = &["require(", "import("];
+
+/// A suspicious pattern found in submitted code
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SanitizationWarning {
+    /// The pattern
+    pub pattern: String,
+    /// Line of the match, starting at 1
+    pub line: u32,
+}
+
+/// Scanner for submitted code
+pub struct CodeSanitizer;
+
+impl CodeSanitizer {
+    /// Find the suspicious patterns in a source, one warning per match
+    pub fn scan(code: &str) -> Vec<SanitizationWarning> {
+        let mut warnings = Vec::new();
+        for (index, line) in code.lines().enumerate() {
+            for pattern in SUSPICIOUS_PATTERNS {
+                for (start, _) in line.match_indices(pattern) {
+                    let after_identifier = line[..start].chars().next_back()
+                        .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '$');
+                    if after_identifier {
+                        continue;
+                    }
+                    if MODULE_PATTERNS.contains(pattern) && imports_own_module(&line[start + pattern.len()..]) {
+                        continue;
+                    }
+                    warnings.push(SanitizationWarning { pattern: pattern.to_string(), line: index as u32 + 1 });
+                }
+            }
+        }
+        warnings.sort_by_key(|warning| warning.line);
+        warnings
+    }
+
+    /// Scan every module of a bundle, refusing it at `max_suspicious_patterns` matches
+    ///
+    /// Returns the warnings with the name of their module.
+    pub fn check_bundle(bundle: &ScriptBundle, max_suspicious_patterns: u32) -> Result<Vec<(String, SanitizationWarning)>, String> {
+        let warnings: Vec<(String, SanitizationWarning)> = bundle.modules().iter()
+            .flat_map(|(name, code)| Self::scan(code).into_iter().map(move |warning| (name.clone(), warning)))
+            .collect();
+        if !warnings.is_empty() && warnings.len() as u64 >= u64::from(max_suspicious_patterns) {
+            let (name, first) = &warnings[0];
+            return Err(format!(
+                "Submission refused: {} suspicious patterns (at most {} allowed), first `{}` in {} line {}",
+                warnings.len(), max_suspicious_patterns.saturating_sub(1), first.pattern, name, first.line
+            ));
+        }
+        Ok(warnings)
+    }
+}
+
+/// Whether the arguments of a `require(` or `import(` start with a bundle-relative or library specifier
+fn imports_own_module(arguments: &str) -> bool {
+    let arguments = arguments.trim_start();
+    let Some(quote) = arguments.chars().next().filter(|c| matches!(c, '\'' | '"' | '`')) else {
+        return false;
+    };
+    let specifier = &arguments[quote.len_utf8()..];
+    specifier.starts_with("./") || specifier.starts_with("../") || specifier.starts_with(LIBRARY_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval_is_reported_with_its_line() {
+        let warnings = CodeSanitizer::scan("const a = 1;\nconst b = eval(\"a + 1\");");
+        assert_eq!(warnings, vec![SanitizationWarning { pattern: "eval(".to_string(), line: 2 }]);
+    }
+
+    #[test]
+    fn test_clean_code_passes() {
+        let code = "\
+            const { findPath } = require('./utils/path');\n\
+            const lib = require(\"@community/astar\");\n\
+            function retrieval(unit) { return unit.evaluate(); }\n\
+            const myFunction = (x) => x.process;\n\
+            for (const unit of game.getMyUnits()) { unit.moveTo(findPath(unit)); }";
+        assert_eq!(CodeSanitizer::scan(code), Vec::new());
+
+        let bundle = ScriptBundle::single(code.to_string()).unwrap();
+        assert_eq!(CodeSanitizer::check_bundle(&bundle, DEFAULT_MAX_SUSPICIOUS_PATTERNS), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_too_many_patterns_are_refused() {
+        let code = "const fs = require('fs');\nprocess.exit(1);\n({}).__proto__.x = 1;";
+        assert_eq!(CodeSanitizer::scan(code).len(), 3);
+
+        let bundle = ScriptBundle::single(code.to_string()).unwrap();
+        let err = CodeSanitizer::check_bundle(&bundle, DEFAULT_MAX_SUSPICIOUS_PATTERNS).unwrap_err();
+        assert!(err.contains("3 suspicious patterns"), "{}", err);
+        assert!(err.contains("`require(` in main.js line 1"), "{}", err);
+
+        // Below the limit, the warnings are returned
+        let warnings = CodeSanitizer::check_bundle(&bundle, 4).unwrap();
+        assert_eq!(warnings.len(), 3);
+        assert_eq!(warnings[1].1, SanitizationWarning { pattern: "process.".to_string(), line: 2 });
+    }
+
+    #[test]
+    fn test_limit_of_zero_refuses_any_match() {
+        let clean = ScriptBundle::single("console.log('hello');".to_string()).unwrap();
+        assert_eq!(CodeSanitizer::check_bundle(&clean, 0), Ok(Vec::new()));
+
+        let suspicious = ScriptBundle::single("eval('1');".to_string()).unwrap();
+        assert!(CodeSanitizer::check_bundle(&suspicious, 0).is_err());
+    }
+}
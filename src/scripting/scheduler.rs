@@ -0,0 +1,112 @@
+//! Fair script scheduling
+//!
+//! When there are more scripts than execution slots (see
+//! [`Sandbox::set_max_concurrent_scripts`](crate::scripting::sandbox::Sandbox::set_max_concurrent_scripts)),
+//! the order they start in decides who waits, and who gets queued to the next tick. A
+//! [`FairScheduler`] starts first the scripts using the least CPU time on average (see
+//! [`ScriptStats::average_cpu_ns`](crate::scripting::sandbox::ScriptStats::average_cpu_ns)),
+//! ties broken by player ID, so heavy scripts cannot starve light ones by keeping a single
+//! tick cheap, and a player who just joined (no CPU time used yet) goes first.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+
+use crate::scripting::bundle::ScriptBundle;
+
+/// A script waiting to run this tick
+#[derive(Debug, Clone)]
+pub struct ScriptJob {
+    /// Player the script belongs to
+    pub player_id: String,
+    /// The player's code
+    pub bundle: Arc<ScriptBundle>,
+    /// Average CPU time of the script's executions, in nanoseconds (0 if it never ran)
+    pub average_cpu_ns: u64,
+}
+
+impl ScriptJob {
+    fn key(&self) -> (u64, &str) {
+        (self.average_cpu_ns, &self.player_id)
+    }
+}
+
+impl PartialEq for ScriptJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for ScriptJob {}
+
+impl PartialOrd for ScriptJob {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScriptJob {
+    /// Reversed, so the heap yields the least CPU time (then the lowest player ID) first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key().cmp(&self.key())
+    }
+}
+
+/// Queue of the scripts of a tick, in fair order (see the [module documentation](self))
+#[derive(Debug, Default)]
+pub struct FairScheduler {
+    queue: BinaryHeap<ScriptJob>,
+}
+
+impl FairScheduler {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a script
+    pub fn push(&mut self, job: ScriptJob) {
+        self.queue.push(job);
+    }
+
+    /// Take the script to run next
+    pub fn pop(&mut self) -> Option<ScriptJob> {
+        self.queue.pop()
+    }
+
+    /// Take every queued script, in the order they should run
+    pub fn drain(&mut self) -> Vec<ScriptJob> {
+        std::iter::from_fn(|| self.pop()).collect()
+    }
+
+    /// Number of queued scripts
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Whether no script is queued
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripts_using_less_cpu_run_first() {
+        let bundle = Arc::new(ScriptBundle::single("console.log('hi');".to_string()).unwrap());
+        // 10 players: bot_0 used the most CPU time, bot_8 and bot_9 the same, bot_5 never ran
+        let cpu_ns = [9_000, 8_000, 7_000, 6_000, 5_000, 0, 3_000, 2_000, 1_000, 1_000];
+        let mut scheduler = FairScheduler::new();
+        for (i, average_cpu_ns) in cpu_ns.into_iter().enumerate() {
+            scheduler.push(ScriptJob { player_id: format!("bot_{}", i), bundle: bundle.clone(), average_cpu_ns });
+        }
+        assert_eq!(scheduler.len(), 10);
+
+        let order: Vec<String> = scheduler.drain().into_iter().map(|job| job.player_id).collect();
+        assert_eq!(order, ["bot_5", "bot_8", "bot_9", "bot_7", "bot_6", "bot_4", "bot_3", "bot_2", "bot_1", "bot_0"]);
+        assert!(scheduler.is_empty());
+    }
+}
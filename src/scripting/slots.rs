@@ -0,0 +1,138 @@
+//! Script execution slots
+//!
+//! At most a fixed number of scripts run at once (see
+//! [`Sandbox::set_max_concurrent_scripts`](crate::scripting::sandbox::Sandbox::set_max_concurrent_scripts)).
+//! Tick batches, direct executions and dry runs share the same [`ExecutionSlots`]: each
+//! execution holds a [`SlotPermit`], and a script that gets none within the queue timeout
+//! does not run, failing with [`ScriptError::Queued`]. Waiting blocks the calling thread
+//! (a script pool thread) until a permit is given back or the timeout expires.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::scripting::errors::ScriptError;
+
+/// Default number of scripts running at once: one per CPU core
+pub fn default_max_concurrent_scripts() -> usize {
+    std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+}
+
+/// Permits to run a script (cloning shares them)
+#[derive(Clone)]
+pub struct ExecutionSlots {
+    inner: Arc<Slots>,
+    queue_timeout: Duration,
+}
+
+struct Slots {
+    /// Number of permits not held
+    free: Mutex<usize>,
+    /// Notified each time a permit is given back
+    released: Condvar,
+    max_scripts: usize,
+}
+
+/// A held permit, given back when dropped
+pub struct SlotPermit<'a> {
+    slots: &'a Slots,
+}
+
+impl ExecutionSlots {
+    /// Slots for `max_scripts` scripts at once (at least one), waited for up to `queue_timeout`
+    pub fn new(max_scripts: usize, queue_timeout: Duration) -> Self {
+        let max_scripts = max_scripts.max(1);
+        Self {
+            inner: Arc::new(Slots { free: Mutex::new(max_scripts), released: Condvar::new(), max_scripts }),
+            queue_timeout,
+        }
+    }
+
+    /// Number of scripts that can run at once
+    pub fn max_scripts(&self) -> usize {
+        self.inner.max_scripts
+    }
+
+    /// How long a script waits for a permit
+    pub fn queue_timeout(&self) -> Duration {
+        self.queue_timeout
+    }
+
+    /// Set how long a script waits for a permit
+    pub fn set_queue_timeout(&mut self, queue_timeout: Duration) {
+        self.queue_timeout = queue_timeout;
+    }
+
+    /// Number of permits not held
+    pub fn available(&self) -> usize {
+        *self.inner.free.lock()
+    }
+
+    /// Take a permit if one is free
+    pub fn try_acquire(&self) -> Option<SlotPermit<'_>> {
+        let mut free = self.inner.free.lock();
+        self.inner.take(&mut free)
+    }
+
+    /// Wait for a permit until the queue timeout (blocking the calling thread)
+    pub fn acquire(&self) -> Result<SlotPermit<'_>, ScriptError> {
+        let mut free = self.inner.free.lock();
+        // A spurious or stolen wakeup only waits again, until the timeout expires
+        let _ = self.inner.released.wait_while_for(&mut free, |free| *free == 0, self.queue_timeout);
+        self.inner.take(&mut free).ok_or(ScriptError::Queued)
+    }
+
+    /// Run `execute` once a permit is free, holding it during the execution
+    pub fn run<T>(&self, execute: impl FnOnce() -> T) -> Result<T, ScriptError> {
+        let _permit = self.acquire()?;
+        Ok(execute())
+    }
+}
+
+impl Slots {
+    fn take(&self, free: &mut usize) -> Option<SlotPermit<'_>> {
+        if *free == 0 {
+            return None;
+        }
+        *free -= 1;
+        Some(SlotPermit { slots: self })
+    }
+}
+
+impl Drop for SlotPermit<'_> {
+    fn drop(&mut self) {
+        *self.slots.free.lock() += 1;
+        self.slots.released.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_waiters_wake_up_when_a_permit_is_given_back() {
+        let slots = ExecutionSlots::new(1, Duration::from_secs(10));
+        let held = slots.try_acquire().unwrap();
+        assert_eq!(slots.available(), 0);
+
+        let start = Instant::now();
+        let waiter = std::thread::spawn({
+            let slots = slots.clone();
+            move || slots.run(|| slots.available())
+        });
+        std::thread::sleep(Duration::from_millis(20));
+        drop(held);
+        assert_eq!(waiter.join().unwrap(), Ok(0));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(slots.available(), 1);
+
+        // Without a free permit, waiting gives up at the timeout
+        let mut slots = slots;
+        slots.set_queue_timeout(Duration::from_millis(10));
+        let _held = slots.try_acquire().unwrap();
+        assert_eq!(slots.run(|| ()), Err(ScriptError::Queued));
+    }
+}
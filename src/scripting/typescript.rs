@@ -0,0 +1,1001 @@
+//! TypeScript support
+//!
+//! TypeScript bots run on the JavaScript runtime once their types are removed. Only
+//! erasable syntax is supported: type annotations, `interface` and `type` declarations,
+//! `declare` statements, generic parameters of functions, classes, and methods, `as` /
+//! `satisfies` casts, non-null assertions, `implements` clauses, and class member
+//! modifiers. Syntax that generates code (`enum`, `namespace`, parameter properties)
+//! is rejected. Removed text is replaced by spaces, so line and column numbers in
+//! errors match the TypeScript source.
+//!
+//! [`GAME_API_TYPES`] declares the bot API for editors and type checkers.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use crate::scripting::bundle::ScriptBundle;
+use crate::scripting::js_runtime::{JsRuntime, ScriptExecutionResult, ScriptLimits};
+use crate::scripting::runtime::{ScriptLanguage, ScriptRuntime};
+
+/// TypeScript declarations of the bot API (`game_api.d.ts`)
+pub const GAME_API_TYPES: &str = include_str!("game_api.d.ts");
+
+/// Keywords after which an expression starts (so `/` starts a regex, `!` is a negation)
+const EXPRESSION_KEYWORDS: &[&str] = &[
+    "return", "typeof", "instanceof", "in", "of", "new", "delete", "void", "throw", "case",
+    "do", "else", "yield", "await", "let", "const", "var", "extends",
+];
+
+/// Class member modifiers that only exist in TypeScript
+const MEMBER_MODIFIERS: &[&str] = &["public", "private", "protected", "readonly", "declare", "override", "abstract"];
+
+/// Maximum number of transpiled bundles kept in the cache
+const MAX_CACHED_BUNDLES: usize = 256;
+
+/// TypeScript implementation of [`ScriptRuntime`]
+///
+/// Bundles are stripped of their types once and cached by their source, so each tick only
+/// runs the resulting JavaScript on a [`JsRuntime`].
+#[derive(Debug, Default)]
+pub struct TsRuntime {
+    js: JsRuntime,
+    cache: Mutex<HashMap<ScriptBundle, Arc<ScriptBundle>>>,
+}
+
+impl TsRuntime {
+    /// Create a TypeScript runtime with the given limits
+    pub fn new(limits: ScriptLimits) -> Self {
+        Self { js: JsRuntime::new(limits), cache: Mutex::new(HashMap::new()) }
+    }
+
+    /// Strip the types of a bundle (cached)
+    fn transpile(&self, bundle: &ScriptBundle) -> Result<Arc<ScriptBundle>, String> {
+        if let Some(transpiled) = self.cache.lock().unwrap().get(bundle) {
+            return Ok(transpiled.clone());
+        }
+
+        let transpiled = Arc::new(transpile_bundle(bundle)?);
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_CACHED_BUNDLES {
+            cache.clear();
+        }
+        cache.insert(bundle.clone(), transpiled.clone());
+        Ok(transpiled)
+    }
+}
+
+impl ScriptRuntime for TsRuntime {
+    fn language(&self) -> ScriptLanguage {
+        ScriptLanguage::TypeScript
+    }
+
+    fn compile(&self, bundle: &ScriptBundle) -> Result<(), String> {
+        self.transpile(bundle).and_then(|bundle| self.js.compile(&bundle))
+    }
+
+    fn execute_tick(&self, bundle: &ScriptBundle, game_state: &serde_json::Value) -> ScriptExecutionResult {
+        match self.transpile(bundle) {
+            Ok(bundle) => self.js.execute_tick(&bundle, game_state),
+            Err(e) => ScriptExecutionResult {
+                error: Some(e),
+                ..ScriptExecutionResult::default()
+            },
+        }
+    }
+
+    fn limits(&self) -> &ScriptLimits {
+        self.js.limits()
+    }
+}
+
+/// Strip the types of every module of a TypeScript bundle, giving a JavaScript bundle
+pub fn transpile_bundle(bundle: &ScriptBundle) -> Result<ScriptBundle, String> {
+    let modules = bundle.modules().iter()
+        .map(|(name, source)| {
+            strip_types(source)
+                .map(|code| (name.clone(), code))
+                .map_err(|e| format!("{}: {}", name, e))
+        })
+        .collect::<Result<BTreeMap<_, _>, _>>()?;
+    Ok(ScriptBundle::from_modules(modules)?
+        .with_language(ScriptLanguage::JavaScript)
+        .with_api_version(bundle.api_version())?
+        .with_libraries(bundle.libraries().clone()))
+}
+
+/// Remove the TypeScript syntax of a source, keeping its layout
+pub fn strip_types(source: &str) -> Result<String, String> {
+    let tokens = tokenize(source)?;
+    let mut stripper = Stripper {
+        source,
+        blank: vec![false; tokens.len()],
+        tokens,
+        scopes: Vec::new(),
+        declaration_depth: None,
+        class_depth: None,
+    };
+    stripper.run()?;
+    stripper.output()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TokenKind {
+    Ident,
+    Punct,
+    Literal,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Token {
+    pub(crate) kind: TokenKind,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) newline_before: bool,
+}
+
+/// Split a source into tokens (comments and whitespace are dropped)
+pub(crate) fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    const PUNCTUATORS: &[&str] = &[
+        "...", "===", "!==", "**=", "??=", "&&=", "||=", "=>", "?.", "??", "==", "!=", "&&", "||",
+        "++", "--", "+=", "-=", "*=", "/=", "%=", "**", "&=", "|=", "^=",
+    ];
+
+    let bytes = source.as_bytes();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut pos = 0;
+    let mut newline_before = false;
+
+    while pos < bytes.len() {
+        let c = bytes[pos];
+        let start = pos;
+        let kind = match c {
+            b'\n' => {
+                newline_before = true;
+                pos += 1;
+                continue;
+            }
+            _ if c.is_ascii_whitespace() => {
+                pos += 1;
+                continue;
+            }
+            b'/' if bytes.get(pos + 1) == Some(&b'/') => {
+                pos = source[pos..].find('\n').map_or(bytes.len(), |n| pos + n);
+                continue;
+            }
+            b'/' if bytes.get(pos + 1) == Some(&b'*') => {
+                let end = source[pos + 2..].find("*/")
+                    .ok_or_else(|| error_at(source, pos, "unterminated comment"))?;
+                newline_before |= source[pos..pos + 2 + end].contains('\n');
+                pos += end + 4;
+                continue;
+            }
+            b'\'' | b'"' => {
+                pos = skip_string(source, pos)?;
+                TokenKind::Literal
+            }
+            b'`' => {
+                pos = skip_template(source, pos)?;
+                TokenKind::Literal
+            }
+            b'/' if regex_allowed(source, tokens.last()) => {
+                pos = skip_regex(source, pos)?;
+                TokenKind::Literal
+            }
+            b'0'..=b'9' => {
+                pos = skip_number(bytes, pos);
+                TokenKind::Literal
+            }
+            b'.' if bytes.get(pos + 1).is_some_and(u8::is_ascii_digit) => {
+                pos = skip_number(bytes, pos);
+                TokenKind::Literal
+            }
+            _ if is_ident_byte(c) || c == b'#' => {
+                pos += 1;
+                while pos < bytes.len() && is_ident_byte(bytes[pos]) {
+                    pos += 1;
+                }
+                TokenKind::Ident
+            }
+            _ => {
+                let rest = &source[pos..];
+                // `?.5` is a conditional followed by a number, not optional chaining
+                let punct = PUNCTUATORS.iter()
+                    .find(|p| rest.starts_with(**p) && !(**p == "?." && rest.as_bytes().get(2).is_some_and(u8::is_ascii_digit)));
+                pos += punct.map_or_else(|| rest.chars().next().map_or(1, char::len_utf8), |p| p.len());
+                TokenKind::Punct
+            }
+        };
+        tokens.push(Token { kind, start, end: pos, newline_before });
+        newline_before = false;
+    }
+    Ok(tokens)
+}
+
+fn is_ident_byte(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || c >= 0x80
+}
+
+/// Whether a `/` after `previous` starts a regular expression rather than a division
+fn regex_allowed(source: &str, previous: Option<&Token>) -> bool {
+    let Some(previous) = previous else {
+        return true;
+    };
+    let text = &source[previous.start..previous.end];
+    match previous.kind {
+        TokenKind::Ident => EXPRESSION_KEYWORDS.contains(&text),
+        TokenKind::Literal => false,
+        TokenKind::Punct => !matches!(text, ")" | "]" | "++" | "--"),
+    }
+}
+
+fn skip_string(source: &str, start: usize) -> Result<usize, String> {
+    let bytes = source.as_bytes();
+    let quote = bytes[start];
+    let mut pos = start + 1;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\\' => pos += 2,
+            b'\n' => break,
+            c if c == quote => return Ok(pos + 1),
+            _ => pos += 1,
+        }
+    }
+    Err(error_at(source, start, "unterminated string"))
+}
+
+fn skip_template(source: &str, start: usize) -> Result<usize, String> {
+    let bytes = source.as_bytes();
+    let mut pos = start + 1;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\\' => pos += 2,
+            b'`' => return Ok(pos + 1),
+            b'$' if bytes.get(pos + 1) == Some(&b'{') => pos = skip_substitution(source, pos + 2)?,
+            _ => pos += 1,
+        }
+    }
+    Err(error_at(source, start, "unterminated template literal"))
+}
+
+/// Skip a template substitution up to and including its closing `}`
+fn skip_substitution(source: &str, start: usize) -> Result<usize, String> {
+    let bytes = source.as_bytes();
+    let mut depth = 0;
+    let mut pos = start;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\'' | b'"' => pos = skip_string(source, pos)?,
+            b'`' => pos = skip_template(source, pos)?,
+            b'{' => {
+                depth += 1;
+                pos += 1;
+            }
+            b'}' if depth == 0 => return Ok(pos + 1),
+            b'}' => {
+                depth -= 1;
+                pos += 1;
+            }
+            _ => pos += 1,
+        }
+    }
+    Err(error_at(source, start, "unterminated template literal"))
+}
+
+fn skip_regex(source: &str, start: usize) -> Result<usize, String> {
+    let bytes = source.as_bytes();
+    let mut pos = start + 1;
+    let mut in_class = false;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\\' => pos += 1,
+            b'\n' => break,
+            b'[' => in_class = true,
+            b']' => in_class = false,
+            b'/' if !in_class => {
+                pos += 1;
+                while pos < bytes.len() && is_ident_byte(bytes[pos]) {
+                    pos += 1;
+                }
+                return Ok(pos);
+            }
+            _ => {}
+        }
+        pos += 1;
+    }
+    Err(error_at(source, start, "unterminated regular expression"))
+}
+
+fn skip_number(bytes: &[u8], start: usize) -> usize {
+    let mut pos = start;
+    while pos < bytes.len() {
+        let c = bytes[pos];
+        let exponent_sign = (c == b'+' || c == b'-') && matches!(bytes[pos - 1], b'e' | b'E')
+            && !bytes[start..pos].starts_with(b"0x") && !bytes[start..pos].starts_with(b"0X");
+        if c.is_ascii_alphanumeric() || c == b'_' || c == b'.' || exponent_sign {
+            pos += 1;
+        } else {
+            break;
+        }
+    }
+    pos
+}
+
+/// "line L, column C: message" for a byte offset of the source
+pub(crate) fn error_at(source: &str, offset: usize, message: &str) -> String {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |line| line.chars().count()) + 1;
+    format!("line {}, column {}: {}", line, column, message)
+}
+
+/// What an open bracket contains
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    /// Statements
+    Block,
+    /// Object literal (or destructuring pattern)
+    Object,
+    /// Class body; `initializer` is set between a field's `=` and the end of the field
+    Class { initializer: bool },
+    /// Parameter list; `default` is set between a parameter's `=` and the next `,`
+    Params { default: bool },
+    /// Any other parentheses or brackets
+    Group,
+}
+
+struct Stripper<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    blank: Vec<bool>,
+    scopes: Vec<Scope>,
+    /// Scope depth of the `let` / `const` / `var` declaration being parsed
+    declaration_depth: Option<usize>,
+    /// Scope depth of a `class` whose body has not started yet
+    class_depth: Option<usize>,
+}
+
+impl Stripper<'_> {
+    fn text(&self, i: usize) -> &str {
+        self.tokens.get(i).map_or("", |token| &self.source[token.start..token.end])
+    }
+
+    fn is(&self, i: usize, text: &str) -> bool {
+        self.text(i) == text
+    }
+
+    fn is_ident(&self, i: usize) -> bool {
+        self.tokens.get(i).is_some_and(|token| token.kind == TokenKind::Ident)
+    }
+
+    fn newline_before(&self, i: usize) -> bool {
+        self.tokens.get(i).is_some_and(|token| token.newline_before)
+    }
+
+    /// Index of the closest token before `i` that is kept
+    fn previous(&self, i: usize) -> Option<usize> {
+        (0..i).rev().find(|&j| !self.blank[j])
+    }
+
+    fn previous_text(&self, i: usize) -> &str {
+        self.previous(i).map_or("", |j| self.text(j))
+    }
+
+    /// Whether the kept token before `i` ends an expression (so `i` can be a postfix or `as`)
+    fn after_expression(&self, i: usize) -> bool {
+        let Some(j) = self.previous(i) else {
+            return false;
+        };
+        match self.tokens[j].kind {
+            TokenKind::Ident => !EXPRESSION_KEYWORDS.contains(&self.text(j)),
+            TokenKind::Literal => true,
+            TokenKind::Punct => matches!(self.text(j), ")" | "]" | "}"),
+        }
+    }
+
+    fn at_statement_start(&self, i: usize) -> bool {
+        self.newline_before(i) || matches!(self.previous_text(i), "" | ";" | "{" | "}")
+    }
+
+    fn error(&self, i: usize, message: &str) -> String {
+        let offset = self.tokens.get(i).map_or(self.source.len(), |token| token.start);
+        error_at(self.source, offset, message)
+    }
+
+    fn blank_range(&mut self, from: usize, to: usize) {
+        let to = to.min(self.tokens.len());
+        for blank in &mut self.blank[from.min(to)..to] {
+            *blank = true;
+        }
+    }
+
+    /// Index after the bracket closing the one at `i`
+    fn matching(&self, i: usize) -> usize {
+        let (open, close) = match self.text(i) {
+            "(" => ("(", ")"),
+            "[" => ("[", "]"),
+            "{" => ("{", "}"),
+            _ => ("<", ">"),
+        };
+        let mut depth = 0;
+        for j in i..self.tokens.len() {
+            if self.is(j, open) {
+                depth += 1;
+            } else if self.is(j, close) {
+                depth -= 1;
+                if depth == 0 {
+                    return j + 1;
+                }
+            }
+        }
+        self.tokens.len()
+    }
+
+    /// Index after the type starting at `i`
+    fn skip_type(&self, i: usize) -> Result<usize, String> {
+        let mut i = i;
+        if self.is(i, "|") || self.is(i, "&") {
+            i += 1;
+        }
+        loop {
+            i = self.skip_type_operand(i)?;
+            if self.is(i, "|") || self.is(i, "&") {
+                i += 1;
+            } else if self.is(i, "is") && !self.newline_before(i) {
+                // Type predicate (`x is Unit`)
+                i += 1;
+            } else if self.is(i, "extends") && !self.newline_before(i) {
+                // Conditional type (`A extends B ? C : D`)
+                i = self.skip_type_operand(i + 1)?;
+                if !self.is(i, "?") {
+                    return Err(self.error(i, "expected '?' in conditional type"));
+                }
+                i = self.skip_type(i + 1)?;
+                if !self.is(i, ":") {
+                    return Err(self.error(i, "expected ':' in conditional type"));
+                }
+                i += 1;
+            } else {
+                return Ok(i);
+            }
+        }
+    }
+
+    fn skip_type_operand(&self, i: usize) -> Result<usize, String> {
+        let mut i = i;
+        while matches!(self.text(i), "keyof" | "typeof" | "readonly" | "unique" | "infer" | "asserts")
+            && (self.is_ident(i + 1) || matches!(self.text(i + 1), "(" | "[" | "{")) {
+            i += 1;
+        }
+
+        let token = self.tokens.get(i).ok_or_else(|| self.error(i, "expected a type"))?;
+        i = match (token.kind, self.text(i)) {
+            (TokenKind::Ident, "new") if self.is(i + 1, "(") => self.skip_function_type(i + 1)?,
+            (TokenKind::Ident, _) => {
+                let mut j = i + 1;
+                while self.is(j, ".") && self.is_ident(j + 1) {
+                    j += 2;
+                }
+                if self.is(j, "<") && !self.newline_before(j) {
+                    j = self.matching(j);
+                }
+                j
+            }
+            (TokenKind::Literal, _) => i + 1,
+            (TokenKind::Punct, "-") => i + 2,
+            (TokenKind::Punct, "(") => self.skip_function_type(i)?,
+            (TokenKind::Punct, "<") => self.skip_function_type(self.matching(i))?,
+            (TokenKind::Punct, "{" | "[") => self.matching(i),
+            _ => return Err(self.error(i, "expected a type")),
+        };
+
+        // Array types and indexed access (`Unit[]`, `T['id']`)
+        while self.is(i, "[") && !self.newline_before(i) {
+            i = self.matching(i);
+        }
+        Ok(i)
+    }
+
+    /// Skip `(...)` and, for function types, `=> ReturnType`
+    fn skip_function_type(&self, i: usize) -> Result<usize, String> {
+        let end = self.matching(i);
+        if self.is(end, "=>") {
+            self.skip_type(end + 1)
+        } else {
+            Ok(end)
+        }
+    }
+
+    /// Blank `: Type` at `i` (if present); returns the index after it
+    fn strip_annotation(&mut self, i: usize) -> Result<usize, String> {
+        if !self.is(i, ":") {
+            return Ok(i);
+        }
+        let end = self.skip_type(i + 1)?;
+        self.blank_range(i, end);
+        Ok(end)
+    }
+
+    /// Blank `<...>` at `i` (if present)
+    fn strip_type_parameters(&mut self, i: usize) {
+        if self.is(i, "<") {
+            let end = self.matching(i);
+            self.blank_range(i, end);
+        }
+    }
+
+    /// Strip the annotations of a declared binding (`x!: T`, `{ a, b }: T`) starting at `i`
+    fn strip_binding(&mut self, i: usize) -> Result<(), String> {
+        let end = if self.is(i, "{") || self.is(i, "[") {
+            self.matching(i)
+        } else if self.is_ident(i) {
+            i + 1
+        } else {
+            return Ok(());
+        };
+        if self.is(end, "!") && self.is(end + 1, ":") {
+            self.blank[end] = true;
+            self.strip_annotation(end + 1)?;
+        } else {
+            self.strip_annotation(end)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the `(` at `i` opens a parameter list
+    fn opens_params(&self, i: usize) -> Result<bool, String> {
+        let p1 = self.previous(i);
+        let p2 = p1.and_then(|j| self.previous(j));
+        let text = |j: Option<usize>| j.map_or("", |j| self.text(j));
+
+        if text(p1) == "catch" || text(p1) == "function" || (text(p1) == "*" && text(p2) == "function") {
+            return Ok(true);
+        }
+        if p1.is_some_and(|j| self.is_ident(j)) && matches!(text(p2), "function" | "*") {
+            return Ok(true);
+        }
+        let member_name = p1.is_some_and(|j| self.is_ident(j) || self.is(j, "]") || self.tokens[j].kind == TokenKind::Literal);
+        match self.scopes.last() {
+            Some(Scope::Class { initializer: false }) if member_name => return Ok(true),
+            Some(Scope::Object) if member_name && matches!(text(p2), "{" | "," | "get" | "set" | "async" | "*") => return Ok(true),
+            _ => {}
+        }
+
+        // Arrow function, possibly with a return type
+        let end = self.matching(i);
+        if self.is(end, "=>") {
+            return Ok(true);
+        }
+        Ok(self.is(end, ":") && self.skip_type(end + 1).is_ok_and(|after| self.is(after, "=>")))
+    }
+
+    /// Scope opened by the `{` at `i`
+    fn brace_scope(&mut self, i: usize) -> Scope {
+        if self.class_depth == Some(self.scopes.len()) {
+            self.class_depth = None;
+            return Scope::Class { initializer: false };
+        }
+        let previous = self.previous(i);
+        let expression_start = match previous.map(|j| (self.tokens[j].kind, self.text(j))) {
+            Some((TokenKind::Ident, text)) => EXPRESSION_KEYWORDS.contains(&text) && text != "else" && text != "do",
+            Some((TokenKind::Punct, ":")) => !matches!(self.scopes.last(), None | Some(Scope::Block | Scope::Class { .. })),
+            Some((TokenKind::Punct, text)) => !matches!(text, ")" | "]" | "}" | "=>" | ";" | "{"),
+            _ => false,
+        };
+        if expression_start {
+            Scope::Object
+        } else {
+            Scope::Block
+        }
+    }
+
+    fn run(&mut self) -> Result<(), String> {
+        let mut i = 0;
+        while i < self.tokens.len() {
+            if !self.blank[i] {
+                self.visit(i)?;
+            }
+            i += 1;
+        }
+        Ok(())
+    }
+
+    fn visit(&mut self, i: usize) -> Result<(), String> {
+        let text = self.text(i).to_string();
+        let scope = self.scopes.last().copied();
+        match text.as_str() {
+            "(" => {
+                let params = self.opens_params(i)?;
+                self.scopes.push(if params { Scope::Params { default: false } } else { Scope::Group });
+            }
+            "[" => self.scopes.push(Scope::Group),
+            "{" => {
+                let scope = self.brace_scope(i);
+                self.scopes.push(scope);
+            }
+            ")" | "]" | "}" => {
+                let closed = self.scopes.pop();
+                if self.declaration_depth.is_some_and(|depth| depth > self.scopes.len()) {
+                    self.declaration_depth = None;
+                }
+                if text == ")" && matches!(closed, Some(Scope::Params { .. })) {
+                    // Return type
+                    self.strip_annotation(i + 1)?;
+                }
+            }
+            ":" => match scope {
+                Some(Scope::Params { default: false }) | Some(Scope::Class { initializer: false }) => {
+                    self.strip_annotation(i)?;
+                }
+                _ => {}
+            },
+            "?" => match scope {
+                // Optional parameter or member (`x?: T`, `x?,`, `foo?()`)
+                Some(Scope::Params { default: false }) | Some(Scope::Class { initializer: false })
+                    if matches!(self.text(i + 1), ":" | "," | ")" | "=" | ";" | "(") =>
+                {
+                    self.blank[i] = true;
+                }
+                _ => {}
+            },
+            "!" if self.after_expression(i) && !self.newline_before(i) => {
+                // Non-null assertion
+                self.blank[i] = true;
+            }
+            "=" => match self.scopes.last_mut() {
+                Some(scope @ Scope::Params { .. }) => *scope = Scope::Params { default: true },
+                Some(scope @ Scope::Class { .. }) => *scope = Scope::Class { initializer: true },
+                _ => {}
+            },
+            "," => {
+                if matches!(scope, Some(Scope::Params { .. })) {
+                    *self.scopes.last_mut().expect("scope checked above") = Scope::Params { default: false };
+                }
+                if self.declaration_depth == Some(self.scopes.len()) {
+                    self.strip_binding(i + 1)?;
+                }
+            }
+            ";" => {
+                if self.declaration_depth == Some(self.scopes.len()) {
+                    self.declaration_depth = None;
+                }
+                if matches!(scope, Some(Scope::Class { .. })) {
+                    *self.scopes.last_mut().expect("scope checked above") = Scope::Class { initializer: false };
+                }
+            }
+            _ if self.is_ident(i) => self.visit_word(i, &text, scope)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn visit_word(&mut self, i: usize, word: &str, scope: Option<Scope>) -> Result<(), String> {
+        if let Some(Scope::Class { initializer: true }) = scope {
+            if self.newline_before(i) && !self.after_operator(i) {
+                *self.scopes.last_mut().expect("scope checked above") = Scope::Class { initializer: false };
+            }
+        }
+        let scope = self.scopes.last().copied();
+        let statement_start = self.at_statement_start(i);
+
+        match word {
+            "interface" if statement_start && self.is_ident(i + 1) => {
+                let mut body = i + 2;
+                while body < self.tokens.len() && !self.is(body, "{") {
+                    body += 1;
+                }
+                let end = self.matching(body);
+                self.blank_range(i, end);
+            }
+            "type" if statement_start && self.is_ident(i + 1) && matches!(self.text(i + 2), "=" | "<") => {
+                let mut j = i + 2;
+                if self.is(j, "<") {
+                    j = self.matching(j);
+                }
+                if !self.is(j, "=") {
+                    return Err(self.error(j, "expected '=' in type alias"));
+                }
+                let mut end = self.skip_type(j + 1)?;
+                if self.is(end, ";") {
+                    end += 1;
+                }
+                self.blank_range(i, end);
+            }
+            "declare" if statement_start && self.is_ident(i + 1) && !matches!(scope, Some(Scope::Class { .. })) => {
+                let end = self.declaration_end(i + 1);
+                self.blank_range(i, end);
+            }
+            "enum" if statement_start && self.is_ident(i + 1) => {
+                return Err(self.error(i, "enums are not supported (only type annotations can be removed)"));
+            }
+            "const" if statement_start && self.is(i + 1, "enum") => {
+                return Err(self.error(i, "enums are not supported (only type annotations can be removed)"));
+            }
+            "namespace" | "module" if statement_start && self.is_ident(i + 1) && self.is(i + 2, "{") => {
+                return Err(self.error(i, "namespaces are not supported (only type annotations can be removed)"));
+            }
+            "abstract" if self.is(i + 1, "class") => {
+                return Err(self.error(i, "abstract classes are not supported (only type annotations can be removed)"));
+            }
+            "let" | "const" | "var" => {
+                self.declaration_depth = Some(self.scopes.len());
+                self.strip_binding(i + 1)?;
+            }
+            "function" => {
+                let mut j = i + 1;
+                if self.is(j, "*") {
+                    j += 1;
+                }
+                if self.is_ident(j) {
+                    j += 1;
+                }
+                self.strip_type_parameters(j);
+            }
+            "class" => {
+                self.class_depth = Some(self.scopes.len());
+                if self.is_ident(i + 1) && !self.is(i + 1, "extends") && !self.is(i + 1, "implements") {
+                    self.strip_type_parameters(i + 2);
+                }
+            }
+            "extends" if self.class_depth == Some(self.scopes.len()) => {
+                // Type arguments of the base class (`extends Base<T>`)
+                let mut j = i + 1;
+                while self.is_ident(j) && self.is(j + 1, ".") {
+                    j += 2;
+                }
+                self.strip_type_parameters(j + 1);
+            }
+            "implements" if self.class_depth == Some(self.scopes.len()) => {
+                let mut end = i;
+                while end < self.tokens.len() && !self.is(end, "{") {
+                    end += 1;
+                }
+                self.blank_range(i, end);
+            }
+            "as" | "satisfies" if self.after_expression(i) && !self.newline_before(i) => {
+                let end = self.skip_type(i + 1)?;
+                self.blank_range(i, end);
+            }
+            _ if MEMBER_MODIFIERS.contains(&word) => self.visit_modifier(i, scope)?,
+            _ => {
+                // Type parameters of a method (`foo<T>(x: T)`)
+                let member = matches!(scope, Some(Scope::Class { initializer: false }) | Some(Scope::Object));
+                if member && self.is(i + 1, "<") && self.is(self.matching(i + 1), "(") {
+                    self.strip_type_parameters(i + 1);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Blank a class member modifier; reject parameter properties
+    fn visit_modifier(&mut self, i: usize, scope: Option<Scope>) -> Result<(), String> {
+        let modifies_next = self.is_ident(i + 1) || matches!(self.text(i + 1), "[" | "{" | "*");
+        if !modifies_next || self.newline_before(i + 1) {
+            return Ok(());
+        }
+        match scope {
+            Some(Scope::Class { initializer: false }) => {
+                let member_start = matches!(self.previous_text(i), "{" | ";" | "}" | "static" | "async")
+                    || MEMBER_MODIFIERS.contains(&self.previous_text(i))
+                    || self.newline_before(i);
+                if member_start {
+                    self.blank[i] = true;
+                }
+                Ok(())
+            }
+            Some(Scope::Params { default: false }) if matches!(self.previous_text(i), "(" | ",") => {
+                Err(self.error(i, "parameter properties are not supported (only type annotations can be removed)"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the token before `i` is an operator continuing an expression on the next line
+    fn after_operator(&self, i: usize) -> bool {
+        self.previous(i).is_some_and(|j| {
+            self.tokens[j].kind == TokenKind::Punct && !matches!(self.text(j), ")" | "]" | "}" | "++" | "--")
+        })
+    }
+
+    /// Index after a `declare` statement whose first word is at `i`
+    fn declaration_end(&self, i: usize) -> usize {
+        let mut depth = 0i32;
+        let mut j = i;
+        while j < self.tokens.len() {
+            match self.text(j) {
+                "(" | "[" | "{" => depth += 1,
+                ")" | "]" => depth -= 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 && matches!(self.text(i), "class" | "module" | "namespace" | "global" | "enum") {
+                        return j + 1;
+                    }
+                }
+                ";" if depth == 0 => return j + 1,
+                _ if depth == 0 && j > i + 1 && self.newline_before(j) && !self.after_operator(j) => return j,
+                _ => {}
+            }
+            j += 1;
+        }
+        j
+    }
+
+    /// The source with blanked tokens replaced by spaces (line breaks are kept)
+    ///
+    /// Substitutions of template literals are stripped on their own.
+    fn output(&self) -> Result<String, String> {
+        let mut output = String::with_capacity(self.source.len());
+        let mut copied = 0;
+        let mut i = 0;
+        while i < self.tokens.len() {
+            let Token { start, end, .. } = self.tokens[i];
+            let text = &self.source[start..end];
+            if !self.blank[i] {
+                if text.starts_with('`') && text.contains("${") {
+                    output.push_str(&self.source[copied..start]);
+                    output.push_str(&strip_template(text).map_err(|e| self.error(i, &e))?);
+                    copied = end;
+                }
+                i += 1;
+                continue;
+            }
+            while i < self.tokens.len() && self.blank[i] {
+                i += 1;
+            }
+            let end = self.tokens[i - 1].end;
+            output.push_str(&self.source[copied..start]);
+            output.extend(self.source[start..end].chars().map(|c| if c == '\n' || c == '\r' { c } else { ' ' }));
+            copied = end;
+        }
+        output.push_str(&self.source[copied..]);
+        Ok(output)
+    }
+}
+
+/// Strip the types of the substitutions of a template literal
+fn strip_template(template: &str) -> Result<String, String> {
+    let bytes = template.as_bytes();
+    let mut output = String::with_capacity(template.len());
+    let mut copied = 0;
+    let mut pos = 1;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\\' => pos += 2,
+            b'$' if bytes.get(pos + 1) == Some(&b'{') => {
+                let end = skip_substitution(template, pos + 2)?;
+                output.push_str(&template[copied..pos + 2]);
+                output.push_str(&strip_types(&template[pos + 2..end - 1])?);
+                copied = end - 1;
+                pos = end;
+            }
+            _ => pos += 1,
+        }
+    }
+    output.push_str(&template[copied..]);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Strip the types of a source and compare the result with `expected`, ignoring whitespace
+    fn assert_strips(source: &str, expected: &str) {
+        let output = strip_types(source).unwrap();
+        assert_eq!(output.lines().count(), source.lines().count(), "line count changed:\n{}", output);
+        let squash = |code: &str| code.split_whitespace().collect::<String>();
+        assert_eq!(squash(&output), squash(expected), "{}", output);
+    }
+
+    #[test]
+    fn test_strips_annotations() {
+        assert_strips("let count: number = 0, names: string[] = [];", "let count = 0, names = [];");
+        assert_strips("const { a, b: c }: Pair = pair;", "const { a, b: c } = pair;");
+        assert_strips(
+            "function move<T extends Unit>(unit: T, to?: Position): void { unit.moveTo(to!); }",
+            "function move(unit, to) { unit.moveTo(to); }",
+        );
+        assert_strips(
+            "const f = (a: number, b = c ? 1 : 2): number => a + b;",
+            "const f = (a, b = c ? 1 : 2) => a + b;",
+        );
+        assert_strips(
+            "const o = { x: 1, y: cond ? a : b, f(u: Unit): boolean { return u as any; } };",
+            "const o = { x: 1, y: cond ? a : b, f(u) { return u; } };",
+        );
+        assert_strips("for (const u of units as Unit[]) { log(u.hits / 2); }", "for (const u of units) { log(u.hits / 2); }");
+        assert_strips("try { x(); } catch (e: unknown) { log(`${e as string}`); }", "try { x(); } catch (e) { log(`${e}`); }");
+    }
+
+    #[test]
+    fn test_strips_declarations_and_classes() {
+        let source = "\
+interface Memory {
+    target: string | null;
+}
+type Pair<T> = [T, T];
+declare const debug: boolean;
+class Bot<T> extends Base<T> implements Runner {
+    private static readonly limit: number = 5;
+    memory?: Memory;
+    count!: number
+    constructor(name: string) { super(name); }
+    onTick(game: GameAPI): void {
+        const units = game.getMyUnits() satisfies Unit[];
+    }
+}
+module.exports = Bot;
+";
+        assert_strips(source, "class Bot extends Base { static limit = 5; memory; count constructor(name) { super(name); } \
+onTick(game) { const units = game.getMyUnits(); } } module.exports = Bot;");
+    }
+
+    #[test]
+    fn test_keeps_plain_javascript() {
+        let source = "const re = /a:b/g; let x = a ? b : c; const type = 'base'; label: for (;;) { break label; }\n\
+if (!done && x !== y) { switch (k) { case 1: { f(); } } }";
+        assert_eq!(strip_types(source).unwrap(), source);
+    }
+
+    #[test]
+    fn test_bundles_are_transpiled_once() {
+        let runtime = TsRuntime::new(ScriptLimits::default());
+        let bundle = ScriptBundle::single("const n: number = 1; console.log(n);".to_string()).unwrap()
+            .with_language(ScriptLanguage::TypeScript);
+        runtime.compile(&bundle).unwrap();
+        let transpiled = runtime.transpile(&bundle).unwrap();
+        assert_eq!(transpiled.entry(), "const n         = 1; console.log(n);");
+
+        let result = runtime.execute_tick(&bundle, &serde_json::json!({}));
+        assert_eq!(result.error, None);
+        assert_eq!(result.logs, ["1"]);
+        assert!(Arc::ptr_eq(&runtime.transpile(&bundle).unwrap(), &transpiled));
+        assert_eq!(runtime.cache.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_rejects_code_generating_syntax() {
+        assert!(strip_types("enum Kind { Worker }").unwrap_err().contains("enums are not supported"));
+        assert!(strip_types("namespace Bot { }").unwrap_err().starts_with("line 1, column 1"));
+        let err = strip_types("class A {\n  constructor(private x: number) {}\n}").unwrap_err();
+        assert_eq!(err, "line 2, column 15: parameter properties are not supported (only type annotations can be removed)");
+    }
+
+    #[test]
+    fn test_declarations_have_rust_counterparts() {
+        let root = env!("CARGO_MANIFEST_DIR");
+        let mut declared = 0;
+        for (index, line) in GAME_API_TYPES.lines().enumerate() {
+            let Some(name) = line.strip_prefix("interface ").or_else(|| line.strip_prefix("type "))
+                .and_then(|rest| rest.split([' ', '<']).next()) else {
+                continue;
+            };
+            declared += 1;
+
+            let doc = GAME_API_TYPES.lines().nth(index.wrapping_sub(1)).unwrap_or("");
+            let path = doc.split("@rust ").nth(1).and_then(|rest| rest.split_whitespace().next())
+                .unwrap_or_else(|| panic!("{} has no @rust tag", name));
+            let mut segments: Vec<&str> = path.split("::").collect();
+            let item = segments.pop().unwrap();
+            let method = std::fs::read_to_string(format!("{}/src/{}.rs", root, segments.join("/"))).is_err();
+            let owner = if method { segments.pop().unwrap() } else { item };
+            let file = std::fs::read_to_string(format!("{}/src/{}.rs", root, segments.join("/")))
+                .unwrap_or_else(|_| panic!("{}: no module for {}", name, path));
+
+            let defined = if method {
+                file.contains(&format!("impl {} {{", owner)) && file.contains(&format!("pub fn {}(", item))
+            } else {
+                file.contains(&format!("pub struct {} ", owner)) || file.contains(&format!("pub enum {} ", owner))
+            };
+            assert!(defined, "{}: {} not found", name, path);
+        }
+        assert!(declared >= 5);
+
+        // Every method of GameAPI is implemented by the JavaScript API
+        let game_api = include_str!("game_api.js");
+        let body = GAME_API_TYPES.split("interface GameAPI {").nth(1).unwrap().split("\n}").next().unwrap();
+        for method in body.lines().filter_map(|line| line.trim().split_once('(').map(|(name, _)| name)) {
+            assert!(game_api.contains(&format!("{}: function", method)), "game_api.js has no {}", method);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! WebAssembly runtime
+//!
+//! Runs a player's compiled WebAssembly module (submitted base64-encoded as the bundle's
+//! entry) with wasmtime. CPU time is limited with fuel and memory with a store limiter.
+//! Modules talk to the host through a JSON-over-linear-memory ABI:
+//!
+//! - exports: `memory`, `alloc(len: i32) -> i32`, `on_tick(ptr: i32, len: i32)`
+//! - imports (module `geekcraft`): `log(ptr, len)`, `issue(ptr, len)`,
+//!   `send_message(ptr, len) -> i32`, `mark_messages_read()`
+//!
+//! Each tick the host reserves a buffer with `alloc`, writes the JSON snapshot into it
+//! and calls `on_tick`. `issue` takes a JSON [`BotCommand`] (`{"action", "actor", "params"}`),
+//! `send_message` a JSON [`OutgoingMessage`] (`{"to", "payload"}`, returns 1 if queued),
+//! and `log` UTF-8 text. Modules importing anything else are rejected.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use base64::Engine as _;
+use sha2::{Digest, Sha256};
+use wasmtime::{
+    Caller, Config, Engine, ExternType, InstancePre, Linker, Module, Store, StoreLimits,
+    StoreLimitsBuilder, Trap,
+};
+
+use crate::scripting::bundle::ScriptBundle;
+use crate::scripting::commands::{BotCommand, MAX_COMMANDS_PER_TICK};
+use crate::scripting::js_runtime::{ScriptExecutionResult, ScriptLimits, MAX_LOG_LINES};
+use crate::scripting::messaging::{check_payload, OutgoingMessage, MAX_MESSAGES_PER_TICK};
+use crate::scripting::runtime::{ScriptLanguage, ScriptRuntime};
+
+/// Maximum size of a decoded WebAssembly module (512KB)
+pub const MAX_WASM_MODULE_SIZE: usize = 512 * 1024;
+
+/// Import module providing the host functions
+const HOST_MODULE: &str = "geekcraft";
+
+/// Host functions a module may import
+const HOST_FUNCTIONS: &[&str] = &["log", "issue", "send_message", "mark_messages_read"];
+
+/// Maximum number of prepared modules kept in the cache
+const MAX_CACHED_MODULES: usize = 256;
+
+/// Per-execution store data
+struct HostState {
+    output: ScriptExecutionResult,
+    limits: StoreLimits,
+}
+
+/// WebAssembly implementation of [`ScriptRuntime`]
+///
+/// Prepared modules are cached by the SHA-256 digest of their source, so a player's module
+/// is compiled and linked once and only instantiated on each tick.
+pub struct WasmRuntime {
+    limits: ScriptLimits,
+    engine: Engine,
+    linker: Linker<HostState>,
+    cache: Mutex<HashMap<[u8; 32], InstancePre<HostState>>>,
+}
+
+impl WasmRuntime {
+    /// Create a WebAssembly runtime with the given limits
+    pub fn new(limits: ScriptLimits) -> Self {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).expect("Failed to create WASM engine");
+
+        Self {
+            linker: host_linker(&engine),
+            engine,
+            limits,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Fuel granted to one execution
+    pub fn fuel(&self) -> u64 {
+        self.limits.timeout.as_millis() as u64 * crate::config::WASM_FUEL_PER_MS
+    }
+
+    /// Compile, validate, and link a bundle's module (cached)
+    fn prepare(&self, bundle: &ScriptBundle) -> Result<InstancePre<HostState>, String> {
+        if bundle.modules().len() != 1 {
+            return Err("WASM bundles must contain a single base64-encoded module".to_string());
+        }
+
+        let source = bundle.entry().trim();
+        let key: [u8; 32] = Sha256::digest(source).into();
+        if let Some(prepared) = self.cache.lock().unwrap().get(&key) {
+            return Ok(prepared.clone());
+        }
+
+        let bytes = base64::engine::general_purpose::STANDARD.decode(source)
+            .map_err(|e| format!("Invalid base64 WASM module: {}", e))?;
+        if bytes.len() > MAX_WASM_MODULE_SIZE {
+            return Err(format!("WASM module too large: {} bytes (max: {} bytes)", bytes.len(), MAX_WASM_MODULE_SIZE));
+        }
+
+        let module = Module::new(&self.engine, &bytes)
+            .map_err(|e| format!("Invalid WASM module: {:#}", e))?;
+        validate_module(&module)?;
+        let prepared = self.linker.instantiate_pre(&module)
+            .map_err(|e| format!("Invalid WASM module: {:#}", e))?;
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_CACHED_MODULES {
+            cache.clear();
+        }
+        cache.insert(key, prepared.clone());
+        Ok(prepared)
+    }
+}
+
+impl ScriptRuntime for WasmRuntime {
+    fn language(&self) -> ScriptLanguage {
+        ScriptLanguage::Wasm
+    }
+
+    fn compile(&self, bundle: &ScriptBundle) -> Result<(), String> {
+        self.prepare(bundle).map(|_| ())
+    }
+
+    fn execute_tick(&self, bundle: &ScriptBundle, game_state: &serde_json::Value) -> ScriptExecutionResult {
+        let prepared = match self.prepare(bundle) {
+            Ok(prepared) => prepared,
+            Err(error) => {
+                return ScriptExecutionResult {
+                    error: Some(error),
+                    ..Default::default()
+                };
+            }
+        };
+
+        let mut store = Store::new(&self.engine, HostState {
+            output: ScriptExecutionResult::default(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(self.limits.max_memory_bytes)
+                .instances(1)
+                .build(),
+        });
+        store.limiter(|state| &mut state.limits);
+
+        let fuel = self.fuel();
+        let error = store.set_fuel(fuel)
+            .and_then(|_| run_tick(&mut store, &prepared, game_state))
+            .err()
+            .map(|e| match e.downcast_ref::<Trap>() {
+                Some(Trap::OutOfFuel) => format!("Script exceeded fuel limit of {}", fuel),
+                _ => format!("{:#}", e),
+            });
+
+        let mut result = std::mem::take(&mut store.data_mut().output);
+        result.error = error;
+        result
+    }
+
+    fn limits(&self) -> &ScriptLimits {
+        &self.limits
+    }
+}
+
+/// Reject modules that import anything but the host functions or lack the ABI exports
+fn validate_module(module: &Module) -> Result<(), String> {
+    for import in module.imports() {
+        let allowed = import.module() == HOST_MODULE
+            && HOST_FUNCTIONS.contains(&import.name())
+            && matches!(import.ty(), ExternType::Func(_));
+        if !allowed {
+            return Err(format!(
+                "Forbidden import: {}.{} (only {} functions are allowed: {})",
+                import.module(),
+                import.name(),
+                HOST_MODULE,
+                HOST_FUNCTIONS.join(", ")
+            ));
+        }
+    }
+
+    for (name, is_valid) in [
+        ("memory", module.get_export("memory").is_some_and(|ty| ty.memory().is_some())),
+        ("alloc", module.get_export("alloc").is_some_and(|ty| ty.func().is_some())),
+        ("on_tick", module.get_export("on_tick").is_some_and(|ty| ty.func().is_some())),
+    ] {
+        if !is_valid {
+            return Err(format!("WASM module must export {}", name));
+        }
+    }
+    Ok(())
+}
+
+/// Instantiate the module, pass it the snapshot, and run `on_tick`
+fn run_tick(
+    store: &mut Store<HostState>,
+    prepared: &InstancePre<HostState>,
+    game_state: &serde_json::Value,
+) -> wasmtime::Result<()> {
+    let instance = prepared.instantiate(&mut *store)?;
+    let memory = instance.get_memory(&mut *store, "memory")
+        .ok_or_else(|| wasmtime::Error::msg("WASM module must export memory"))?;
+    let alloc = instance.get_typed_func::<i32, i32>(&mut *store, "alloc")?;
+    let on_tick = instance.get_typed_func::<(i32, i32), ()>(&mut *store, "on_tick")?;
+
+    let snapshot = game_state.to_string();
+    let len = i32::try_from(snapshot.len())?;
+    let ptr = alloc.call(&mut *store, len)?;
+    memory.write(&mut *store, ptr as u32 as usize, snapshot.as_bytes())?;
+
+    on_tick.call(&mut *store, (ptr, len))
+}
+
+/// Copy a buffer out of the calling module's memory
+fn guest_bytes(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> wasmtime::Result<Vec<u8>> {
+    let memory = caller.get_export("memory")
+        .and_then(|export| export.into_memory())
+        .ok_or_else(|| wasmtime::Error::msg("WASM module must export memory"))?;
+
+    let start = ptr as u32 as usize;
+    let end = start + len as u32 as usize;
+    memory.data(&caller)
+        .get(start..end)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| wasmtime::Error::msg(format!("Host call buffer out of bounds: {}..{}", start, end)))
+}
+
+/// Host functions exposed to modules under the `geekcraft` import module
+fn host_linker(engine: &Engine) -> Linker<HostState> {
+    let mut linker = Linker::new(engine);
+
+    linker.func_wrap(HOST_MODULE, "log", |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+        let bytes = guest_bytes(&mut caller, ptr, len)?;
+        let output = &mut caller.data_mut().output;
+        if output.logs.len() < MAX_LOG_LINES {
+            output.logs.push(String::from_utf8_lossy(&bytes).into_owned());
+        }
+        Ok(())
+    }).expect("Failed to define geekcraft.log");
+
+    linker.func_wrap(HOST_MODULE, "issue", |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+        let bytes = guest_bytes(&mut caller, ptr, len)?;
+        let command: BotCommand = serde_json::from_slice(&bytes)
+            .map_err(|e| wasmtime::Error::msg(format!("Invalid command JSON: {}", e)))?;
+        let output = &mut caller.data_mut().output;
+        if output.commands.len() < MAX_COMMANDS_PER_TICK {
+            output.commands.push(command);
+        }
+        Ok(())
+    }).expect("Failed to define geekcraft.issue");
+
+    linker.func_wrap(HOST_MODULE, "send_message", |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+        let bytes = guest_bytes(&mut caller, ptr, len)?;
+        let message: OutgoingMessage = serde_json::from_slice(&bytes)
+            .map_err(|e| wasmtime::Error::msg(format!("Invalid message JSON: {}", e)))?;
+        let output = &mut caller.data_mut().output;
+        if output.sent_messages.len() >= MAX_MESSAGES_PER_TICK || check_payload(&message.payload).is_err() {
+            return Ok(0);
+        }
+        output.sent_messages.push(message);
+        Ok(1)
+    }).expect("Failed to define geekcraft.send_message");
+
+    linker.func_wrap(HOST_MODULE, "mark_messages_read", |mut caller: Caller<'_, HostState>| {
+        caller.data_mut().output.messages_read = true;
+    }).expect("Failed to define geekcraft.mark_messages_read");
+
+    linker
+}
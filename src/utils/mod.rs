@@ -0,0 +1,8 @@
+//! Utilities module
+//!
+//! Helpers shared by the other modules.
+
+pub mod retry;
+pub mod sqlite_pool;
+
+pub use retry::{is_retryable, retry_with_backoff};
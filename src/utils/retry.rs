@@ -0,0 +1,105 @@
+//! Retry module
+//!
+//! Retries operations that failed for a reason expected to go away on its own: a busy
+//! or locked SQLite file, a dropped database connection, a timeout. Each retry waits
+//! twice as long as the previous one, starting from `base_delay`.
+//!
+//! Errors are told apart by their message, like the rest of the auth layer: an error
+//! about the request itself (a constraint violation, a missing row) fails the same way
+//! however often it is retried, so it is returned at once.
+
+use std::fmt::Display;
+use std::time::Duration;
+
+/// Attempts made by default, including the first
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry by default
+pub const DEFAULT_BASE_DELAY: Duration = Duration::from_millis(20);
+
+/// Longest wait between two attempts
+pub const MAX_DELAY: Duration = Duration::from_secs(1);
+
+/// Error messages of failures worth retrying
+const TRANSIENT_ERRORS: [&str; 6] = ["busy", "locked", "connection", "timed out", "timeout", "broken pipe"];
+
+/// Whether an error message describes a failure worth retrying
+pub fn is_retryable(error: &str) -> bool {
+    let error = error.to_lowercase();
+    TRANSIENT_ERRORS.iter().any(|transient| error.contains(transient))
+}
+
+/// Run `operation` until it succeeds, fails with an error that is not retryable, or
+/// has been attempted `max_attempts` times
+///
+/// Waits `base_delay`, then twice that, and so on (up to [`MAX_DELAY`]) between
+/// attempts. This blocks the calling thread, like the database calls it wraps: async
+/// code runs it on the blocking pool (see
+/// [`AppState::call_auth`](crate::network::server::AppState::call_auth)). Only wrap
+/// operations that can safely run twice.
+pub fn retry_with_backoff<F, T, E>(mut operation: F, max_attempts: u32, base_delay: Duration) -> Result<T, E>
+where
+    F: FnMut() -> Result<T, E>,
+    E: Display,
+{
+    let mut attempt = 1;
+    loop {
+        match operation() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < max_attempts && is_retryable(&e.to_string()) => {
+                let delay = base_delay.saturating_mul(1 << (attempt - 1).min(16)).min(MAX_DELAY);
+                log::warn!("Attempt {}/{} failed ({}), retrying in {:?}", attempt, max_attempts, e, delay);
+                std::thread::sleep(delay);
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// Database failing with `error` on its first `failures` calls
+    struct FlakyDatabase {
+        failures: u32,
+        error: &'static str,
+        calls: Cell<u32>,
+    }
+
+    impl FlakyDatabase {
+        fn create_user(&self, username: &str) -> Result<String, String> {
+            self.calls.set(self.calls.get() + 1);
+            if self.calls.get() <= self.failures {
+                return Err(self.error.to_string());
+            }
+            Ok(username.to_string())
+        }
+    }
+
+    #[test]
+    fn test_transient_failures_are_retried() {
+        let db = FlakyDatabase { failures: 2, error: "Failed to create user: database is locked", calls: Cell::new(0) };
+        let result = retry_with_backoff(|| db.create_user("alice"), 3, Duration::from_millis(1));
+        assert_eq!(result, Ok("alice".to_string()));
+        assert_eq!(db.calls.get(), 3);
+
+        // Gives up after the last attempt
+        let db = FlakyDatabase { failures: 5, error: "Connection reset by peer", calls: Cell::new(0) };
+        let result = retry_with_backoff(|| db.create_user("alice"), 3, Duration::from_millis(1));
+        assert_eq!(result, Err("Connection reset by peer".to_string()));
+        assert_eq!(db.calls.get(), 3);
+    }
+
+    #[test]
+    fn test_request_errors_are_not_retried() {
+        for error in ["Username already exists", "User not found", "UNIQUE constraint failed: users.username", "Database unavailable"] {
+            let db = FlakyDatabase { failures: 1, error, calls: Cell::new(0) };
+            let result = retry_with_backoff(|| db.create_user("alice"), 3, Duration::from_millis(1));
+            assert_eq!(result, Err(error.to_string()));
+            assert_eq!(db.calls.get(), 1, "{}", error);
+        }
+    }
+}
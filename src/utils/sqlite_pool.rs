@@ -0,0 +1,151 @@
+//! SQLite connection pool
+//!
+//! SQLite lets one connection write while others read, as long as the database is in
+//! WAL journal mode. A [`SqlitePool`] holds one write connection and a few read-only
+//! connections to the same file, all in WAL mode with a busy timeout, so reads do not
+//! queue behind a slow write. Schemas are set up through the write connection, once.
+//!
+//! A private in-memory database cannot be shared between connections: its pool has the
+//! write connection only, which then serves reads too.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use rusqlite::{Connection, OpenFlags};
+
+/// Default number of read connections of a pool
+pub const DEFAULT_SQLITE_READ_CONNECTIONS: usize = 4;
+
+/// How long a connection waits for a lock held by another one before failing
+pub const SQLITE_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One write connection and several read connections to a SQLite database
+pub struct SqlitePool {
+    writer: Mutex<Connection>,
+    readers: Vec<Mutex<Connection>>,
+    /// Reader the next read tries first
+    next_reader: AtomicUsize,
+}
+
+impl SqlitePool {
+    /// Open (or create) a database file with `readers` read connections
+    ///
+    /// With no read connections, reads go through the write connection.
+    pub fn open(path: &Path, readers: usize) -> rusqlite::Result<Self> {
+        let writer = Connection::open(path)?;
+        configure(&writer)?;
+        let readers = (0..readers)
+            .map(|_| {
+                let reader = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+                reader.busy_timeout(SQLITE_BUSY_TIMEOUT)?;
+                Ok(Mutex::new(reader))
+            })
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(Self { writer: Mutex::new(writer), readers, next_reader: AtomicUsize::new(0) })
+    }
+
+    /// Open a private in-memory database (a single connection)
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Ok(Self { writer: Mutex::new(Connection::open_in_memory()?), readers: Vec::new(), next_reader: AtomicUsize::new(0) })
+    }
+
+    /// The write connection
+    pub fn write(&self) -> MutexGuard<'_, Connection> {
+        self.writer.lock().unwrap()
+    }
+
+    /// A free read connection, waiting for one if they are all in use
+    pub fn read(&self) -> MutexGuard<'_, Connection> {
+        if self.readers.is_empty() {
+            return self.write();
+        }
+        let first = self.next_reader.fetch_add(1, Ordering::Relaxed);
+        let count = self.readers.len();
+        (0..count)
+            .find_map(|offset| self.readers[(first + offset) % count].try_lock().ok())
+            .unwrap_or_else(|| self.readers[first % count].lock().unwrap())
+    }
+
+    /// Number of read connections
+    pub fn read_connections(&self) -> usize {
+        self.readers.len()
+    }
+}
+
+/// Switch a database to WAL journaling and set the busy timeout
+fn configure(conn: &Connection) -> rusqlite::Result<()> {
+    conn.busy_timeout(SQLITE_BUSY_TIMEOUT)?;
+    conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get::<_, String>(0))?;
+    // Safe with WAL: a crash may lose the latest commits, never corrupt the database
+    conn.pragma_update(None, "synchronous", "NORMAL")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("geekcraft_pool_{}.db", uuid::Uuid::new_v4()))
+    }
+
+    fn remove(path: &Path) {
+        for suffix in ["", "-wal", "-shm"] {
+            std::fs::remove_file(format!("{}{}", path.display(), suffix)).ok();
+        }
+    }
+
+    #[test]
+    fn test_reads_do_not_wait_for_the_writer() {
+        let path = temp_path();
+        let pool = SqlitePool::open(&path, 2).unwrap();
+        pool.write().execute_batch("CREATE TABLE t (x INTEGER); INSERT INTO t VALUES (1);").unwrap();
+        let mode: String = pool.read().query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+        assert_eq!(mode, "wal");
+
+        // An open write transaction neither blocks readers nor shows them its changes
+        let writer = pool.write();
+        writer.execute_batch("BEGIN; INSERT INTO t VALUES (2);").unwrap();
+        let count: i64 = pool.read().query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 1);
+        writer.execute_batch("COMMIT;").unwrap();
+        drop(writer);
+
+        // Both readers can be held at once, and they are read-only
+        let (first, second) = (pool.read(), pool.read());
+        assert!(second.execute("INSERT INTO t VALUES (3)", []).is_err());
+        let count: i64 = first.query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 2);
+        drop((first, second));
+        drop(pool);
+        remove(&path);
+    }
+
+    #[test]
+    fn test_reads_keep_flowing_during_writes() {
+        let path = temp_path();
+        let pool = SqlitePool::open(&path, 4).unwrap();
+        pool.write().execute_batch("CREATE TABLE sessions (token TEXT PRIMARY KEY, user TEXT); INSERT INTO sessions VALUES ('t0', 'alice');").unwrap();
+
+        // Like a session lookup while a user is being created: the read gets the committed row
+        let mut writer = pool.write();
+        let transaction = writer.transaction().unwrap();
+        transaction.execute("INSERT INTO sessions VALUES ('t1', 'bob')", []).unwrap();
+        let user: String = pool.read().query_row("SELECT user FROM sessions WHERE token = 't0'", [], |row| row.get(0)).unwrap();
+        assert_eq!(user, "alice");
+        let uncommitted: i64 = pool.read().query_row("SELECT COUNT(*) FROM sessions WHERE token = 't1'", [], |row| row.get(0)).unwrap();
+        assert_eq!(uncommitted, 0);
+        transaction.commit().unwrap();
+        drop(writer);
+        drop(pool);
+        remove(&path);
+
+        // Without read connections, reads need the write connection, held for the whole transaction
+        let single = SqlitePool::open_in_memory().unwrap();
+        let writer = single.write();
+        assert!(single.writer.try_lock().is_err());
+        drop(writer);
+        assert!(single.writer.try_lock().is_ok());
+    }
+}
@@ -0,0 +1,160 @@
+// Tests for the geekcraft-cli binary.
+// Each test runs its own server instance on an ephemeral port and a separate HOME
+// directory for the CLI's config file.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use assert_cmd::Command;
+use predicates::str::contains;
+use tokio::runtime::Runtime;
+
+use geekcraft::auth::{AuthDatabase, AuthService, DatabaseBackend};
+use geekcraft::game::world::World;
+use geekcraft::network::server::{create_router, AppState};
+use geekcraft::scripting::handle::ScriptEngineHandle;
+
+/// A server running on its own runtime, and the HOME directory of the CLI under test
+struct TestServer {
+    runtime: Runtime,
+    url: String,
+    state: AppState,
+    db: Arc<AuthDatabase>,
+    home: PathBuf,
+}
+
+impl TestServer {
+    fn start(name: &str, admins: &[&str]) -> Self {
+        let runtime = Runtime::new().unwrap();
+        let db = Arc::new(AuthDatabase::new(DatabaseBackend::InMemory)
+            .expect("Failed to create In-Memory database"));
+        let mut state = AppState::new(
+            World::new(),
+            ScriptEngineHandle::default(),
+            Arc::new(AuthService::new(db.clone())),
+        );
+        state.admin_users = Arc::new(admins.iter().map(|admin| admin.to_string()).collect());
+
+        let listener = runtime.block_on(tokio::net::TcpListener::bind("127.0.0.1:0")).unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let router = create_router(state.clone());
+        runtime.spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        let home = std::env::temp_dir().join(format!("geekcraft_cli_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&home);
+        std::fs::create_dir_all(&home).unwrap();
+        TestServer { runtime, url, state, db, home }
+    }
+
+    /// The CLI, pointed at this server and HOME directory
+    fn cli(&self) -> Command {
+        let mut command = Command::cargo_bin("geekcraft-cli").unwrap();
+        command.env("HOME", &self.home).args(["--server", &self.url]);
+        command
+    }
+
+    /// Create a user with a valid session, bypassing bcrypt, and return the token
+    fn create_session(&self, username: &str) -> String {
+        let user = self.db.create_user(username, "unused_hash").expect("Failed to create user");
+        let token = format!("token-{}", username);
+        self.db.create_session(&token, user.id, chrono::Utc::now().timestamp() + 3600)
+            .expect("Failed to create session");
+        token
+    }
+
+    /// Write a CLI config holding a session token
+    fn save_token(&self, token: &str) {
+        let dir = self.home.join(".geekcraft");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("config.toml"), format!("token = \"{}\"\n", token)).unwrap();
+    }
+}
+
+fn stdout_of(output: &std::process::Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn test_cli_register_login_submit_and_pull() {
+    let server = TestServer::start("login", &[]);
+
+    server.cli().args(["register", "cli_player", "--password", "secret123"]).assert().success();
+    let output = server.cli().args(["login", "cli_player", "--password", "secret123"]).assert().success();
+    assert!(stdout_of(output.get_output()).contains("Logged in as cli_player"));
+
+    // The token and server are kept for later commands
+    let config = std::fs::read_to_string(server.home.join(".geekcraft/config.toml")).unwrap();
+    assert!(config.contains("token = "));
+    assert!(config.contains(&server.url));
+
+    let bot = server.home.join("bot.js");
+    let code = "module.exports = { onTick(game) { console.log(game.tick); } };";
+    std::fs::write(&bot, code).unwrap();
+    let output = server.cli().arg("submit").arg(&bot).assert().success();
+    assert!(stdout_of(output.get_output()).contains("Code submitted successfully"));
+    let submitted = server.runtime.block_on(async {
+        server.state.script_engine.read().await.get_code("cli_player").cloned()
+    });
+    assert_eq!(submitted.as_deref(), Some(code));
+
+    let output = server.cli().args(["code", "pull"]).assert().success();
+    assert_eq!(stdout_of(output.get_output()).trim_end(), code);
+
+    let output = server.cli().args(["gamestate", "--json"]).assert().success();
+    let state: serde_json::Value = serde_json::from_str(&stdout_of(output.get_output())).unwrap();
+    assert_eq!(state["players"], serde_json::json!(["cli_player"]));
+}
+
+#[test]
+fn test_cli_zone_show_draws_tiles() {
+    let server = TestServer::start("zone", &[]);
+    let zone_id = server.runtime.block_on(async {
+        server.state.game_world.write().await.generate_player_zone("drawn").unwrap()
+    });
+
+    let output = server.cli().args(["zone", "show", &zone_id]).assert().success();
+    let drawing = stdout_of(output.get_output());
+    let rows: Vec<&str> = drawing.lines().collect();
+    let (width, height) = server.runtime.block_on(async {
+        server.state.game_world.read().await.with_zone_read(&zone_id, |zone| (zone.width, zone.height)).unwrap()
+    });
+    assert_eq!(rows.len(), height);
+    assert!(rows.iter().all(|row| row.len() == width));
+    assert!(rows.iter().all(|row| row.chars().all(|c| "PSW#".contains(c))));
+    assert!(drawing.contains('P'));
+
+    let output = server.cli().args(["--json", "zone", "show", &zone_id]).assert().success();
+    let zone: serde_json::Value = serde_json::from_str(&stdout_of(output.get_output())).unwrap();
+    assert_eq!(zone["id"], zone_id.as_str());
+}
+
+#[test]
+fn test_cli_errors_exit_nonzero_with_server_message() {
+    let server = TestServer::start("errors", &["cli_admin"]);
+
+    server.cli().args(["login", "nobody", "--password", "wrong-password"])
+        .assert()
+        .failure()
+        .stderr(contains("Invalid username or password"));
+    assert!(!server.home.join(".geekcraft/config.toml").exists());
+
+    server.cli().args(["zone", "show", "missing_zone"])
+        .assert()
+        .failure()
+        .stderr(contains("Zone missing_zone not found"));
+
+    // Admin commands need an admin account
+    server.save_token(&server.create_session("cli_player"));
+    server.cli().args(["admin", "users", "list"])
+        .assert()
+        .failure()
+        .stderr(contains("Admin access required"));
+
+    server.save_token(&server.create_session("cli_admin"));
+    let output = server.cli().args(["admin", "users", "list"]).assert().success();
+    let listing = stdout_of(output.get_output());
+    assert!(listing.contains("cli_player"));
+    assert!(listing.contains("online, admin"));
+}
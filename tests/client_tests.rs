@@ -0,0 +1,116 @@
+// Tests for the geekcraft::client library.
+// Each test runs its own server instance on an ephemeral port and talks to it only
+// through the client.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+
+use geekcraft::auth::{AuthDatabase, AuthService, DatabaseBackend};
+use geekcraft::client::{Client, ClientError};
+use geekcraft::game::world::World;
+use geekcraft::network::server::{create_router, AppState, CodeSubmission};
+use geekcraft::scripting::handle::ScriptEngineHandle;
+
+/// Serve a fresh server on an ephemeral port, with the world ticking in the background
+async fn spawn_server() -> (SocketAddr, AppState, Arc<AuthDatabase>) {
+    let db = Arc::new(AuthDatabase::new(DatabaseBackend::InMemory)
+        .expect("Failed to create In-Memory database"));
+    let state = AppState::new(
+        World::new(),
+        ScriptEngineHandle::default(),
+        Arc::new(AuthService::new(db.clone())),
+    );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let router = create_router(state.clone());
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    let world = state.game_world.clone();
+    tokio::spawn(async move {
+        loop {
+            world.write().await.advance_tick();
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    });
+    (addr, state, db)
+}
+
+#[tokio::test]
+async fn test_client_register_submit_subscribe() {
+    let (addr, state, db) = spawn_server().await;
+    let client = Client::new(format!("http://{}", addr));
+
+    let registered = client.register("client_player", "secret123").await.unwrap();
+    assert_eq!(registered.username.as_deref(), Some("client_player"));
+    let logged_in = client.login("client_player", "secret123").await.unwrap();
+    assert!(logged_in.token.is_some());
+    assert_eq!(client.token(), logged_in.token);
+
+    let submission = CodeSubmission {
+        code: Some("module.exports = { onTick(game) {} };".to_string()),
+        modules: None,
+        language: None,
+        api_version: None,
+    };
+    let submitted = client.submit_code(&submission).await.unwrap();
+    assert!(submitted.success);
+
+    let game_state = client.get_gamestate().await.unwrap();
+    assert!(game_state.players.contains(&"client_player".to_string()));
+
+    let user = db.get_user_by_username("client_player").unwrap().unwrap();
+    let zone_id = state.game_world.read().await.assigned_zone(user.id).unwrap().to_string();
+    let zone = client.get_zone(&zone_id).await.unwrap();
+    assert_eq!(zone.id, zone_id);
+
+    // An expired session is renewed with the stored credentials
+    let old_token = client.token().unwrap();
+    db.delete_session(&old_token).unwrap();
+    assert!(client.get_gamestate().await.is_ok());
+    assert_ne!(client.token().unwrap(), old_token);
+
+    let updates = client.subscribe_gamestate(Duration::from_millis(10)).await.unwrap();
+    let updates: Vec<_> = tokio::time::timeout(Duration::from_secs(5), updates.take(3).collect::<Vec<_>>())
+        .await
+        .expect("Timed out waiting for game state updates");
+    let ticks: Vec<u64> = updates.into_iter().map(|update| update.unwrap().tick).collect();
+    assert_eq!(ticks.len(), 3);
+    assert!(ticks.windows(2).all(|pair| pair[0] < pair[1]), "ticks should increase: {:?}", ticks);
+}
+
+#[tokio::test]
+async fn test_client_errors_use_response_envelope() {
+    let (addr, _state, _db) = spawn_server().await;
+    let client = Client::new(format!("http://{}", addr));
+
+    // success: false with 200
+    match client.login("nobody", "wrong-password").await {
+        Err(ClientError::Api { status, message }) => {
+            assert_eq!(status, 200);
+            assert!(!message.is_empty());
+        }
+        other => panic!("expected an API error, got {:?}", other.map(|_| ())),
+    }
+
+    // No credentials to renew the session with
+    match client.get_gamestate().await {
+        Err(ClientError::Api { status, .. }) => assert_eq!(status, 401),
+        other => panic!("expected an API error, got {:?}", other.map(|_| ())),
+    }
+
+    let client = Client::new(format!("http://{}", addr)).with_token("invalid");
+    match client.get_zone("missing_zone").await {
+        Err(ClientError::Api { status, message }) => {
+            assert_eq!(status, 404);
+            assert!(message.contains("missing_zone"));
+        }
+        other => panic!("expected an API error, got {:?}", other.map(|_| ())),
+    }
+    assert!(client.subscribe_gamestate(Duration::from_millis(10)).await.is_err());
+}
@@ -0,0 +1,25 @@
+;; Minimal WASM bot: logs a greeting and moves unit w1 to (5, 7) every tick.
+;; Build with: wat2wasm move_bot.wat -o move_bot.wasm
+(module
+  (import "geekcraft" "log" (func $log (param i32 i32)))
+  (import "geekcraft" "issue" (func $issue (param i32 i32)))
+
+  (memory (export "memory") 1)
+
+  (data (i32.const 0) "{\"action\":\"moveTo\",\"actor\":\"w1\",\"params\":{\"position\":{\"x\":5,\"y\":7}}}")
+  (data (i32.const 512) "wasm bot online")
+
+  ;; The snapshot is written at offset 1024; grow memory until it fits
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $pages i32)
+    (local.set $pages
+      (i32.sub
+        (i32.div_u (i32.add (i32.add (i32.const 1024) (local.get $len)) (i32.const 65535)) (i32.const 65536))
+        (memory.size)))
+    (if (i32.gt_s (local.get $pages) (i32.const 0))
+      (then (drop (memory.grow (local.get $pages)))))
+    (i32.const 1024))
+
+  (func (export "on_tick") (param $ptr i32) (param $len i32)
+    (call $log (i32.const 512) (i32.const 15))
+    (call $issue (i32.const 0) (i32.const 68))))
@@ -2,9 +2,51 @@
 // Note: Integration tests are compiled as a separate crate,
 // so we must use the crate name as the path root.
 
-use geekcraft::game::world::World;
-use geekcraft::game::zone::{Zone, SurfaceType, ZONE_SIZE};
-use geekcraft::auth::{AuthDatabase, DatabaseBackend};
+use geekcraft::config::{ConfigError, FeatureFlags, ServerConfig};
+use geekcraft::game::campaign::{CampaignError, CampaignManager, RunOptions, RunStatus};
+use geekcraft::game::event_log::{ActionLog, Checkpoint, GameAction};
+use geekcraft::game::events::GameEventKind;
+use geekcraft::game::game_loop::run_simulation_tick;
+use geekcraft::game::history::HistoryError;
+use geekcraft::game::market::OrderSide;
+use geekcraft::game::npc::NPC_DEPOSIT_AMOUNT;
+use geekcraft::game::pathfinding::find_path;
+use geekcraft::game::replay::WorldSnapshot;
+use geekcraft::game::scenario::{ObjectiveStatus, Scenario};
+use geekcraft::game::stats::{OwnershipChange, StatsReadModel};
+use geekcraft::game::store::{self, SqliteWorldStore};
+use geekcraft::game::tech;
+use geekcraft::game::weather::{WeatherEvent, WeatherEventType, STORM_DAMAGE};
+use geekcraft::game::world::{unit_cost, CaptureError, EditZoneError, Portal, RespawnMode, World, WorldConfig, WorldEvent, ATTACK_DAMAGE, HARVEST_AMOUNT, RESPAWN_CLEAR_RADIUS};
+use geekcraft::game::zone::edit::ZoneEdit;
+use geekcraft::game::zone::export::{ZoneExport, ZONE_EXPORT_VERSION};
+use geekcraft::game::zone::{EntityRef, ExitDirection, Mobility, ResourceDeposit, ResourceType, SurfaceType, Zone, DEFAULT_ENTITY_HITS, ZONE_SIZE};
+use geekcraft::scripting::commands::BotCommand;
+use geekcraft::auth::{AuthDatabase, AuthService, DatabaseBackend, Invite, RegistrationMode, UserFilter};
+use geekcraft::auth::service::SESSION_TOUCH_INTERVAL_SECS;
+use geekcraft::scripting::api_version::LATEST_API_VERSION;
+use geekcraft::scripting::bundle::ScriptBundle;
+use geekcraft::scripting::errors::ScriptError;
+use geekcraft::scripting::handle::ScriptEngineHandle;
+use geekcraft::scripting::js_runtime::{JsRuntime, ScriptLimits};
+use geekcraft::scripting::messaging::{MAX_MESSAGES_PER_TICK, MAX_MESSAGE_BYTES};
+use geekcraft::scripting::runtime::{create_runtime, ScriptLanguage, ScriptRuntime};
+use geekcraft::scripting::sandbox::Sandbox;
+use base64::Engine as _;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+fn wasm_bundle(bytes: &[u8]) -> ScriptBundle {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+    ScriptBundle::single(encoded).unwrap().with_language(ScriptLanguage::Wasm)
+}
+
+fn modules(files: &[(&str, &str)]) -> BTreeMap<String, String> {
+    files.iter().map(|(name, code)| (name.to_string(), code.to_string())).collect()
+}
 
 #[test]
 fn test_game_world_initialization() {
@@ -65,18 +107,167 @@ fn test_auth_database_inmemory() {
     assert!(deleted_session.is_none(), "Session should be deleted");
 }
 
+#[test]
+fn test_sliding_session_expiration() {
+    let db = Arc::new(AuthDatabase::new(DatabaseBackend::InMemory).unwrap());
+    let user = db.create_user("slider", "hash").unwrap();
+    let auth = AuthService::new(db.clone())
+        .with_session_duration(3600)
+        .with_sliding_expiration(50)
+        .with_max_session_lifetime(3 * 3600);
+    db.create_session("slide-token", user.id, chrono::Utc::now().timestamp() + 3600).unwrap();
+    let session = db.get_session("slide-token").unwrap().unwrap();
+    let (start, deadline) = (session.expires_at - 3600, session.created_at + 3 * 3600);
+    let expires_at = || db.get_session("slide-token").unwrap().unwrap().expires_at;
+
+    // Extended once more than half of the lifetime has elapsed
+    assert_eq!(auth.validate_token_at("slide-token", start + 1000).unwrap().expires_at, start + 3600);
+    assert_eq!(auth.validate_token_at("slide-token", start + 2000).unwrap().expires_at, start + 5600);
+    assert_eq!(expires_at(), start + 5600);
+    assert_eq!(auth.validate_token_at("slide-token", start + 2500).unwrap().expires_at, start + 5600);
+
+    // Fixed expiration when sliding is off
+    let fixed = AuthService::new(db.clone()).with_session_duration(3600);
+    assert_eq!(fixed.validate_token_at("slide-token", start + 5000).unwrap().expires_at, start + 5600);
+
+    // However short the threshold, at most one extension per touch interval
+    let eager = AuthService::new(db.clone())
+        .with_session_duration(3600)
+        .with_sliding_expiration(1)
+        .with_max_session_lifetime(3 * 3600);
+    assert_eq!(eager.validate_token_at("slide-token", start + 2000 + SESSION_TOUCH_INTERVAL_SECS - 1).unwrap().expires_at, start + 5600);
+    let touched_at = start + 2000 + SESSION_TOUCH_INTERVAL_SECS;
+    assert_eq!(eager.validate_token_at("slide-token", touched_at).unwrap().expires_at, touched_at + 3600);
+    assert_eq!(eager.validate_token_at("slide-token", touched_at + 60).unwrap().expires_at, touched_at + 3600);
+
+    // Extensions stop at the maximum lifetime, then the session ends even if used
+    let mut now = touched_at;
+    while let Some(session) = auth.validate_token_at("slide-token", now) {
+        assert!(session.expires_at <= deadline);
+        now += 1800;
+    }
+    assert_eq!(expires_at(), deadline);
+    assert!(now > deadline && now <= deadline + 1800);
+
+    // Sessions older than the maximum lifetime are deleted
+    let short = AuthService::new(db.clone()).with_session_duration(3600).with_max_session_lifetime(600);
+    assert!(short.validate_token_at("slide-token", session.created_at + 600).is_none());
+    assert!(db.get_session("slide-token").unwrap().is_none());
+}
+
+#[test]
+fn test_list_users_pages_and_filters() {
+    let db = AuthDatabase::new(DatabaseBackend::InMemory).unwrap();
+    for name in ["alice", "Albert", "bob", "alfred", "carol"] {
+        db.create_user(name, "hash").unwrap();
+    }
+    let all = UserFilter::default();
+    let names = |(users, total): (Vec<geekcraft::auth::User>, u32)| {
+        (users.into_iter().map(|user| user.username).collect::<Vec<_>>(), total)
+    };
+
+    // Sorted by ID, i.e. in order of registration
+    assert_eq!(names(db.list_users(0, 2, &all).unwrap()), (vec!["alice".to_string(), "Albert".to_string()], 5));
+    assert_eq!(names(db.list_users(4, 2, &all).unwrap()), (vec!["carol".to_string()], 5));
+    assert_eq!(names(db.list_users(3, 2, &all).unwrap()).0.len(), 2);
+    assert_eq!(names(db.list_users(5, 2, &all).unwrap()), (vec![], 5));
+    assert_eq!(names(db.list_users(0, 0, &all).unwrap()), (vec![], 5));
+    assert_eq!(names(db.list_users(0, u32::MAX, &all).unwrap()).0.len(), 5);
+
+    // The prefix ignores case and the total only counts matching users
+    let al = UserFilter { username_prefix: Some("AL".to_string()) };
+    assert_eq!(
+        names(db.list_users(0, 10, &al).unwrap()),
+        (vec!["alice".to_string(), "Albert".to_string(), "alfred".to_string()], 3)
+    );
+    assert_eq!(names(db.list_users(2, 10, &al).unwrap()), (vec!["alfred".to_string()], 3));
+    let none = UserFilter { username_prefix: Some("alicea".to_string()) };
+    assert_eq!(names(db.list_users(0, 10, &none).unwrap()), (vec![], 0));
+    let exact = UserFilter { username_prefix: Some("bob".to_string()) };
+    assert_eq!(names(db.list_users(0, 10, &exact).unwrap()), (vec!["bob".to_string()], 1));
+}
+
+#[test]
+fn test_registration_modes_and_invite_codes() {
+    let db = Arc::new(AuthDatabase::new(DatabaseBackend::InMemory).unwrap());
+    let auth = AuthService::new(db.clone());
+    let now = chrono::Utc::now().timestamp();
+    let uses_left = |code: &str| auth.list_invites().unwrap().into_iter().find(|invite| invite.code == code).unwrap().uses_left;
+
+    assert!(auth.register("early_bird", "secret123", RegistrationMode::Open, None).success);
+
+    // Closed: nobody registers, but players can still log in
+    let refused = auth.register("latecomer", "secret123", RegistrationMode::Closed, None);
+    assert!(!refused.success);
+    assert!(refused.message.starts_with("Registration is closed"), "{}", refused.message);
+    assert!(auth.login("early_bird", "secret123").success);
+
+    // Invite: a code is needed, and each registration takes one of its uses
+    let invite = auth.create_invite("admin", 2, None).unwrap();
+    assert_eq!((invite.max_uses, invite.uses_left, invite.expires_at), (2, 2, None));
+    let register = |username: &str, code: Option<&str>| auth.register(username, "secret123", RegistrationMode::Invite, code);
+    assert!(register("guest_1", None).message.contains("requires an invite code"));
+    assert_eq!(register("guest_1", Some("not-a-code")).message, "Invite code not found");
+    assert!(register("guest_1", Some(&invite.code)).success);
+    assert_eq!(uses_left(&invite.code), 1);
+    // Failed registrations do not use the code up
+    assert_eq!(register("guest_1", Some(&invite.code)).message, "Username already exists");
+    assert!(!register("x", Some(&invite.code)).success);
+    assert_eq!(uses_left(&invite.code), 1);
+    assert!(register("guest_2", Some(&invite.code)).success);
+    assert_eq!(uses_left(&invite.code), 0);
+    assert_eq!(register("guest_3", Some(&invite.code)).message, "Invite code has already been used");
+
+    // Expired and revoked codes are refused
+    db.create_invite(&Invite {
+        code: "expired".to_string(),
+        created_by: "admin".to_string(),
+        created_at: now - 100,
+        max_uses: 5,
+        uses_left: 5,
+        expires_at: Some(now - 1),
+    }).unwrap();
+    assert_eq!(register("guest_3", Some("expired")).message, "Invite code has already expired");
+    assert!(auth.create_invite("admin", 1, Some(now - 1)).is_err());
+    assert!(auth.create_invite("admin", 0, None).is_err());
+    let revoked = auth.create_invite("admin", 1, Some(now + 3600)).unwrap();
+    assert_eq!(auth.revoke_invite(&revoked.code), Ok(true));
+    assert_eq!(auth.revoke_invite(&revoked.code), Ok(false));
+    assert_eq!(register("guest_3", Some(&revoked.code)).message, "Invite code not found");
+
+    // Codes are listed oldest first
+    let listed: Vec<String> = auth.list_invites().unwrap().into_iter().map(|invite| invite.code).collect();
+    assert_eq!(listed, vec!["expired".to_string(), invite.code.clone()]);
+
+    // Concurrent uses never take more than the code allows
+    let shared = auth.create_invite("admin", 3, None).unwrap();
+    let start = Arc::new(std::sync::Barrier::new(8));
+    let takers: Vec<_> = (0..8)
+        .map(|_| {
+            let (db, start, code) = (db.clone(), start.clone(), shared.code.clone());
+            std::thread::spawn(move || {
+                start.wait();
+                db.consume_invite(&code, chrono::Utc::now().timestamp()).is_ok()
+            })
+        })
+        .collect();
+    let taken = takers.into_iter().map(|taker| taker.join().unwrap()).filter(|&ok| ok).count();
+    assert_eq!(taken, 3);
+    assert_eq!(uses_left(&shared.code), 0);
+}
+
 #[test]
 fn test_zone_generation_and_world_integration() {
     let mut world = World::new();
     
     // Generate a zone for a player
-    let zone_id = world.generate_player_zone("player1");
+    let zone_id = world.generate_player_zone("player1").unwrap();
     
     // Verify zone was created
     assert_eq!(zone_id, "player_player1_zone");
     
     // Retrieve the zone
-    let zone = world.get_zone(&zone_id)
+    let zone = world.with_zone_read(&zone_id, Zone::clone)
         .expect("Zone should exist in world");
     
     // Verify zone properties
@@ -100,14 +291,14 @@ fn test_multiple_zones_in_world() {
     let mut world = World::new();
     
     // Generate zones for multiple players
-    let zone1_id = world.generate_player_zone("player1");
-    let zone2_id = world.generate_player_zone("player2");
-    let zone3_id = world.generate_player_zone("player3");
+    let zone1_id = world.generate_player_zone("player1").unwrap();
+    let zone2_id = world.generate_player_zone("player2").unwrap();
+    let zone3_id = world.generate_player_zone("player3").unwrap();
     
     // Verify all zones exist
-    assert!(world.get_zone(&zone1_id).is_some());
-    assert!(world.get_zone(&zone2_id).is_some());
-    assert!(world.get_zone(&zone3_id).is_some());
+    assert!(world.with_zone_read(&zone1_id, |_| ()).is_some());
+    assert!(world.with_zone_read(&zone2_id, |_| ()).is_some());
+    assert!(world.with_zone_read(&zone3_id, |_| ()).is_some());
     
     // Verify zone IDs are listed
     let zone_ids = world.get_zone_ids();
@@ -123,14 +314,2625 @@ fn test_zone_deterministic_for_same_player() {
     let mut world2 = World::new();
     
     // Generate zone for same player in different worlds
-    let zone1_id = world1.generate_player_zone("player1");
-    let zone2_id = world2.generate_player_zone("player1");
+    let zone1_id = world1.generate_player_zone("player1").unwrap();
+    let zone2_id = world2.generate_player_zone("player1").unwrap();
     
-    let zone1 = world1.get_zone(&zone1_id).unwrap();
-    let zone2 = world2.get_zone(&zone2_id).unwrap();
+    let zone1 = world1.with_zone_read(&zone1_id, Zone::clone).unwrap();
+    let zone2 = world2.with_zone_read(&zone2_id, Zone::clone).unwrap();
     
     // Zones should be identical for same player ID
     assert_eq!(zone1.tiles[0][0].surface_type, zone2.tiles[0][0].surface_type);
     assert_eq!(zone1.tiles[15][15].surface_type, zone2.tiles[15][15].surface_type);
     assert_eq!(zone1.exits.len(), zone2.exits.len());
-}
\ No newline at end of file
+}
+
+/// First walkable tile of a zone, scanning from the given offset
+fn walkable_tile(world: &World, zone_id: &str, from: usize) -> (usize, usize) {
+    let zone = world.with_zone_read(zone_id, Zone::clone).unwrap();
+    (from..ZONE_SIZE * ZONE_SIZE)
+        .map(|i| (i % ZONE_SIZE, i / ZONE_SIZE))
+        .find(|(x, y)| zone.get_tile(*x, *y).unwrap().surface_type != SurfaceType::Obstacle)
+        .unwrap()
+}
+
+#[test]
+fn test_entity_through_portal_appears_in_destination_zone() {
+    let mut world = World::new();
+    let zone_a = world.generate_player_zone("alice").unwrap();
+    let zone_b = world.generate_player_zone("bob").unwrap();
+
+    let start = walkable_tile(&world, &zone_a, 0);
+    let (from_x, from_y) = walkable_tile(&world, &zone_a, 100);
+    let (to_x, to_y) = walkable_tile(&world, &zone_b, 400);
+
+    world.with_zone_write(&zone_a, |zone| zone.entities.push(EntityRef {
+        id: 7,
+        kind: "worker".to_string(),
+        owner: Some("alice".to_string()),
+        x: start.0,
+        y: start.1,
+        hits: DEFAULT_ENTITY_HITS,
+        can_swim: false,
+        can_fly: false,
+    })).unwrap();
+
+    let portal = Portal {
+        id: Uuid::new_v4(),
+        from_zone_id: zone_a.clone(),
+        from_x,
+        from_y,
+        to_zone_id: zone_b.clone(),
+        to_x,
+        to_y,
+    };
+    world.add_portal(portal.clone()).unwrap();
+    assert!(world.add_portal(Portal { id: Uuid::new_v4(), ..portal.clone() }).is_err(), "Only one portal per tile");
+
+    let moved = world.move_entity(&zone_a, 7, from_x, from_y).unwrap();
+    assert_eq!(moved, (zone_b.clone(), 7, to_x, to_y));
+    assert!(world.with_zone_read(&zone_a, |zone| zone.entities.is_empty()).unwrap());
+    let arrived = &world.with_zone_read(&zone_b, Zone::clone).unwrap().entities[0];
+    assert_eq!((arrived.id, arrived.x, arrived.y), (7, to_x, to_y));
+
+    // Portals survive a save/load round trip
+    let path = std::env::temp_dir().join(format!("geekcraft_world_{}.json", portal.id));
+    world.save(&path).unwrap();
+    let loaded = World::load(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+    assert_eq!(loaded.portals().to_vec(), vec![portal.clone()]);
+
+    assert_eq!(world.remove_portal(portal.id), Some(portal));
+    assert!(world.portals().is_empty());
+}
+
+#[test]
+fn test_zone_grid_is_connected_both_ways() {
+    let mut world = World::with_config(WorldConfig { max_zones: 5, ..WorldConfig::default() });
+    let config = world.config().default_zone_config.clone();
+    let zones: Vec<_> = world.next_generated_zone_ids(4).iter()
+        .map(|zone_id| World::generate_grid_zone(zone_id, &config, true).unwrap())
+        .collect();
+    assert!(zones.iter().all(|zone| zone.exits.len() == 4));
+
+    // Two rows of two: two East-West and two North-South links
+    let portals = world.add_zone_grid(zones.clone(), true).unwrap();
+    assert_eq!(portals.len(), 8);
+    assert_eq!(world.portals(), portals.as_slice());
+    let linked = |from: &str, to: &str| portals.iter().any(|portal| portal.from_zone_id == from && portal.to_zone_id == to);
+    for (a, b) in [("generated_0001", "generated_0002"), ("generated_0001", "generated_0003"), ("generated_0002", "generated_0004"), ("generated_0003", "generated_0004")] {
+        assert!(linked(a, b) && linked(b, a), "{} and {} are not linked", a, b);
+    }
+    assert!(!linked("generated_0002", "generated_0003"));
+
+    // Each exit leads to one place, and zones are only added once
+    let err = world.connect_zones("generated_0001", ExitDirection::North, "generated_0003").unwrap_err();
+    assert!(err.contains("A portal already starts"), "{}", err);
+    let err = world.add_zone_grid(zones[..1].to_vec(), false).unwrap_err();
+    assert!(err.contains("generated_0001 already exists"), "{}", err);
+    assert_eq!(world.next_generated_zone_ids(1), vec!["generated_0005".to_string()]);
+
+    let extra: Vec<_> = ["extra_a", "extra_b"].iter().map(|zone_id| World::generate_grid_zone(zone_id, &config, false).unwrap()).collect();
+    let err = world.add_zone_grid(extra, false).unwrap_err();
+    assert!(err.contains("World is full"), "{}", err);
+    assert_eq!(world.get_zone_ids().len(), 4);
+}
+
+#[test]
+fn test_world_database_migrations() {
+    let path = std::env::temp_dir().join(format!("geekcraft_world_{}.db", Uuid::new_v4()));
+    let applied = |conn: &rusqlite::Connection| -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM refinery_schema_history", [], |row| row.get(0)).unwrap()
+    };
+
+    // Opening the store twice applies the initial schema once
+    let mut world = World::open(WorldConfig::default(), Arc::new(SqliteWorldStore::open(&path).unwrap())).unwrap();
+    let zone_id = world.generate_player_zone("migrant").unwrap();
+    drop(world);
+    SqliteWorldStore::open(&path).unwrap();
+    let mut conn = rusqlite::Connection::open(&path).unwrap();
+    assert_eq!(store::schema_version(&mut conn), Ok(1));
+    assert_eq!(applied(&conn), 1);
+    assert_eq!(store::migrate(&mut conn, &store::migrations()), Ok(1));
+    assert_eq!(applied(&conn), 1);
+
+    // A later migration adds a column to the existing rows
+    let mut migrations = store::migrations();
+    migrations.push(refinery::Migration::unapplied("V2__zone_biome", "ALTER TABLE zones ADD COLUMN biome TEXT NOT NULL DEFAULT 'temperate';").unwrap());
+    assert_eq!(store::migrate(&mut conn, &migrations), Ok(2));
+    assert_eq!(store::migrate(&mut conn, &migrations), Ok(2));
+    let biome: String = conn.query_row("SELECT biome FROM zones WHERE id = ?1", [&zone_id], |row| row.get(0)).unwrap();
+    assert_eq!(biome, "temperate");
+    drop(conn);
+
+    // This server only knows version 1, so it refuses the newer database
+    let err = SqliteWorldStore::open(&path).unwrap_err();
+    std::fs::remove_file(&path).ok();
+    assert!(err.contains("schema version 2 is newer"), "{}", err);
+}
+
+#[test]
+fn test_zones_survive_restart_from_sqlite_store() {
+    let path = std::env::temp_dir().join(format!("geekcraft_world_{}.db", Uuid::new_v4()));
+    let store = Arc::new(SqliteWorldStore::open(&path).unwrap());
+    let mut world = World::open(WorldConfig::default(), store).unwrap();
+
+    let zone_a = world.generate_player_zone("alice").unwrap();
+    let zone_b = world.generate_player_zone("bob").unwrap();
+    let zone_c = world.generate_player_zone("carol").unwrap();
+    let (from_x, from_y) = walkable_tile(&world, &zone_a, 0);
+    let (to_x, to_y) = walkable_tile(&world, &zone_b, 0);
+    let portal = Portal {
+        id: Uuid::new_v4(),
+        from_zone_id: zone_a.clone(),
+        from_x,
+        from_y,
+        to_zone_id: zone_b.clone(),
+        to_x,
+        to_y,
+    };
+    world.add_portal(portal.clone()).unwrap();
+
+    let (x, y) = walkable_tile(&world, &zone_a, 0);
+    world.with_zone_write(&zone_a, |zone| zone.entities.push(EntityRef {
+        id: 1,
+        kind: "worker".to_string(),
+        owner: Some("alice".to_string()),
+        x,
+        y,
+        hits: DEFAULT_ENTITY_HITS,
+        can_swim: false,
+        can_fly: false,
+    })).unwrap();
+    world.capture_zone(&zone_a, "alice").unwrap();
+    world.remove_zone(&zone_c).unwrap();
+    let before: Vec<_> = [&zone_a, &zone_b].iter().map(|id| world.with_zone_read(id, Zone::clone).unwrap()).collect();
+    drop(world);
+
+    let restarted = World::open(WorldConfig::default(), Arc::new(SqliteWorldStore::open(&path).unwrap())).unwrap();
+    std::fs::remove_file(&path).ok();
+
+    let mut zone_ids = restarted.get_zone_ids();
+    zone_ids.sort();
+    assert_eq!(zone_ids, vec![zone_a.clone(), zone_b.clone()]);
+    for zone in &before {
+        let reloaded = restarted.with_zone_read(&zone.id, Zone::clone).unwrap();
+        assert_eq!(reloaded.tiles, zone.tiles);
+        assert_eq!(reloaded.exits, zone.exits);
+        assert_eq!(&reloaded, zone);
+    }
+    assert_eq!(restarted.zone_owner(&zone_a).as_deref(), Some("alice"));
+    assert_eq!(restarted.portals().to_vec(), vec![portal]);
+}
+
+fn entity(id: u32, kind: &str, owner: &str, (x, y): (usize, usize)) -> EntityRef {
+    EntityRef {
+        id,
+        kind: kind.to_string(),
+        owner: Some(owner.to_string()),
+        x,
+        y,
+        hits: DEFAULT_ENTITY_HITS,
+        can_swim: false,
+        can_fly: false,
+    }
+}
+
+#[test]
+fn test_defeated_player_respawns_after_cooldown() {
+    let mut world = World::with_config(WorldConfig { respawn_cooldown_ticks: 10, ..WorldConfig::default() });
+    let zone_id = world.generate_player_zone("alice").unwrap();
+    let base = walkable_tile(&world, &zone_id, 0);
+    world.with_zone_write(&zone_id, |zone| {
+        zone.entities.push(entity(1, "base", "alice", base));
+        zone.entities.push(entity(2, "turret", "bob", (zone.width / 2, zone.height / 2)));
+        zone.entities.push(entity(3, "turret", "bob", (0, 0)));
+    }).unwrap();
+    world.advance_tick();
+    assert!(world.drain_events().is_empty());
+    assert!(!world.is_defeated("alice"));
+
+    // Alice loses her last building
+    world.with_zone_write(&zone_id, |zone| zone.entities.retain(|entity| entity.owner.as_deref() != Some("alice"))).unwrap();
+    world.advance_tick();
+    let defeated_at = world.get_tick();
+    assert_eq!(world.drain_events(), vec![WorldEvent::PlayerDefeated {
+        player_id: "alice".to_string(),
+        tick: defeated_at,
+        respawn_tick: defeated_at + 10,
+    }]);
+    assert_eq!(world.player_record("alice").unwrap().defeats, 1);
+
+    // The flag reaches the bot API during the cooldown
+    let snapshot = world.player_snapshot("alice");
+    assert_eq!(snapshot["defeated"], true);
+    assert_eq!(snapshot["respawn_tick"], defeated_at + 10);
+    let runtime = create_runtime(ScriptLanguage::JavaScript, ScriptLimits::default());
+    let bundle = ScriptBundle::single("console.log(game.isDefeated());".to_string()).unwrap();
+    assert_eq!(runtime.execute_tick(&bundle, &snapshot).logs, vec!["true".to_string()]);
+
+    for _ in 0..9 {
+        world.advance_tick();
+    }
+    assert!(world.is_defeated("alice"));
+    assert!(world.drain_events().is_empty());
+
+    world.advance_tick();
+    assert_eq!(world.get_tick(), defeated_at + 10);
+    assert_eq!(world.drain_events(), vec![WorldEvent::PlayerRespawned {
+        player_id: "alice".to_string(),
+        zone_id: zone_id.clone(),
+        tick: defeated_at + 10,
+    }]);
+    assert!(!world.is_defeated("alice"));
+    assert_eq!(world.player_snapshot("alice")["defeated"], false);
+
+    let zone = world.with_zone_read(&zone_id, Zone::clone).unwrap();
+    let owned: Vec<&EntityRef> = zone.entities.iter().filter(|entity| entity.owner.as_deref() == Some("alice")).collect();
+    assert_eq!(owned.iter().map(|entity| entity.kind.as_str()).collect::<Vec<_>>(), vec!["base", "worker"]);
+    for entity in &owned {
+        let tile = zone.get_tile(entity.x, entity.y).unwrap();
+        assert!(matches!(tile.surface_type, SurfaceType::Plain | SurfaceType::Swamp));
+    }
+    assert_eq!(owned[0].x.abs_diff(owned[1].x) + owned[0].y.abs_diff(owned[1].y), 1);
+
+    // Enemy structures near the new base are gone, farther ones stay
+    let (bx, by) = (owned[0].x, owned[0].y);
+    assert!(zone.entities.iter()
+        .filter(|entity| entity.owner.as_deref() == Some("bob"))
+        .all(|entity| entity.x.abs_diff(bx) > RESPAWN_CLEAR_RADIUS || entity.y.abs_diff(by) > RESPAWN_CLEAR_RADIUS));
+    assert!(zone.entities.iter().any(|entity| entity.id == 3));
+}
+
+#[test]
+fn test_respawn_in_new_zone() {
+    let mut world = World::with_config(WorldConfig {
+        respawn_mode: RespawnMode::NewZone,
+        respawn_cooldown_ticks: 3,
+        ..WorldConfig::default()
+    });
+    let zone_id = world.generate_player_zone("alice").unwrap();
+    let tile = walkable_tile(&world, &zone_id, 0);
+    world.with_zone_write(&zone_id, |zone| zone.entities.push(entity(1, "worker", "alice", tile))).unwrap();
+    world.advance_tick();
+
+    world.with_zone_write(&zone_id, |zone| zone.entities.clear()).unwrap();
+    for _ in 0..4 {
+        world.advance_tick();
+    }
+    let events = world.drain_events();
+    let Some(WorldEvent::PlayerRespawned { zone_id: new_zone, .. }) = events.last() else {
+        panic!("No respawn in {:?}", events);
+    };
+    assert_ne!(new_zone, &zone_id);
+    assert_eq!(world.with_zone_read(new_zone, |zone| zone.entities.len()).unwrap(), 2);
+    assert_eq!(world.player_record("alice").unwrap().home_zone.as_ref(), Some(new_zone));
+}
+
+#[test]
+fn test_players_start_on_spawn_points() {
+    let mut world = World::new();
+    let zone_id = world.generate_player_zone("alice").unwrap();
+    let designated = world.with_zone_read(&zone_id, |zone| zone.spawn_points.clone()).unwrap();
+    assert!(!designated.is_empty());
+
+    let players: Vec<String> = (0..designated.len()).map(|i| format!("player_{}", i)).collect();
+    for (player, point) in players.iter().zip(&designated) {
+        assert_eq!(world.place_player_start(player, &zone_id), Ok(*point));
+    }
+    let zone = world.with_zone_read(&zone_id, Zone::clone).unwrap();
+    for (player, &(x, y)) in players.iter().zip(&designated) {
+        let worker = zone.entities.iter().find(|entity| entity.owner.as_ref() == Some(player)).unwrap();
+        assert_eq!((worker.kind.as_str(), worker.x, worker.y), ("worker", x, y));
+    }
+
+    assert!(world.place_player_start("late", &zone_id).unwrap_err().contains("no free spawn point"));
+    assert!(world.place_player_start("alice", "missing").unwrap_err().contains("not found"));
+}
+
+#[test]
+fn test_zone_edits_persist_to_the_store() {
+    let path = std::env::temp_dir().join(format!("geekcraft_world_{}.db", Uuid::new_v4()));
+    let mut world = World::open(WorldConfig::default(), Arc::new(SqliteWorldStore::open(&path).unwrap())).unwrap();
+    let zone_id = world.generate_player_zone("alice").unwrap();
+    let (x, y) = walkable_tile(&world, &zone_id, 0);
+    let terrain_version = world.with_zone_read(&zone_id, |zone| zone.terrain_version).unwrap();
+
+    let edits = [
+        ZoneEdit::SetTile { x, y, surface_type: SurfaceType::Swamp },
+        ZoneEdit::AddResource { x, y, amount: 300 },
+    ];
+    world.edit_zone(&zone_id, &edits, false).unwrap();
+    let edited = world.with_zone_read(&zone_id, Zone::clone).unwrap();
+    assert!(edited.terrain_version > terrain_version);
+    assert_eq!(edited.get_tile(x, y).unwrap().surface_type, SurfaceType::Swamp);
+
+    let Err(EditZoneError::Rejected(rejected)) = world.edit_zone(&zone_id, &[ZoneEdit::RemoveExit { x: 99, y: 99 }], false) else {
+        panic!("Removing a missing exit was accepted");
+    };
+    assert_eq!(rejected.len(), 1);
+    assert_eq!(world.edit_zone("missing", &edits, false), Err(EditZoneError::ZoneNotFound("missing".to_string())));
+    drop(world);
+
+    let restarted = World::open(WorldConfig::default(), Arc::new(SqliteWorldStore::open(&path).unwrap())).unwrap();
+    std::fs::remove_file(&path).ok();
+    assert_eq!(restarted.with_zone_read(&zone_id, Zone::clone).unwrap(), edited);
+}
+
+#[test]
+fn test_zone_unlocks_after_prerequisites_are_cleared() {
+    let mut world = World::new();
+    world.set_zone_unlock_requirements("cave", vec!["forest".to_string(), "ruins".to_string()]);
+    assert!(world.is_zone_unlocked("alice", "forest"));
+    assert!(!world.is_zone_unlocked("alice", "cave"));
+
+    world.mark_zone_cleared("alice", "forest");
+    assert!(!world.is_zone_unlocked("alice", "cave"));
+    world.mark_zone_cleared("alice", "ruins");
+    assert!(world.is_zone_unlocked("alice", "cave"));
+    // Clearing is per player
+    assert!(!world.is_zone_unlocked("bob", "cave"));
+
+    // Requirements and cleared zones are saved with the world
+    let path = std::env::temp_dir().join(format!("geekcraft_unlocks_{}.json", Uuid::new_v4()));
+    world.save(&path).unwrap();
+    let loaded = World::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.zone_unlock_requirements("cave"), ["forest", "ruins"]);
+    assert!(loaded.is_zone_unlocked("alice", "cave"));
+
+    world.set_zone_unlock_requirements("cave", Vec::new());
+    assert!(world.is_zone_unlocked("bob", "cave"));
+}
+
+#[test]
+fn test_two_module_bundle_runs() {
+    let mut sandbox = Sandbox::new();
+    sandbox.submit_bundle("alice".to_string(), modules(&[
+        ("main.js", "const path = require('./utils/path');\n\
+            class Bot { onTick(game) { console.log('step', path.step(game.tick)); } }\n\
+            module.exports = Bot;"),
+        ("utils/path.js", "exports.step = function (tick) { return tick + 1; };"),
+    ])).unwrap();
+
+    let result = sandbox.execute_player("alice", &serde_json::json!({"tick": 41})).unwrap();
+    assert_eq!(result.error, None);
+    assert_eq!(result.logs, vec!["step 42".to_string()]);
+    assert_eq!(sandbox.get_bundle("alice").unwrap().modules().len(), 2);
+}
+
+#[test]
+fn test_missing_module_is_per_player_error() {
+    let mut sandbox = Sandbox::new();
+    sandbox.submit_bundle("broken".to_string(), modules(&[
+        ("main.js", "require('./missing');"),
+    ])).unwrap();
+    sandbox.submit_code("healthy".to_string(), "console.log('ok');".to_string()).unwrap();
+
+    let broken = sandbox.execute_player("broken", &serde_json::json!({})).unwrap();
+    assert!(broken.error.unwrap().contains("Cannot find module './missing'"));
+
+    // Other players are unaffected
+    let healthy = sandbox.execute_player("healthy", &serde_json::json!({})).unwrap();
+    assert_eq!(healthy.error, None);
+    assert_eq!(healthy.logs, vec!["ok".to_string()]);
+}
+
+#[test]
+fn test_script_stats_report_cpu_time() {
+    let mut sandbox = Sandbox::new();
+    sandbox.submit_code("spinner".to_string(), "while (true) {}".to_string()).unwrap();
+    sandbox.submit_code("idle".to_string(), "console.log('ok');".to_string()).unwrap();
+
+    // A script stopped at the time limit ran for about the whole limit
+    let timeout_ns = geekcraft::config::SCRIPT_TIMEOUT_MS * 1_000_000;
+    let result = sandbox.execute_player("spinner", &serde_json::json!({})).unwrap();
+    assert!(result.error.unwrap().contains("time limit"));
+    assert!(result.cpu_ns >= timeout_ns, "{}ns is below the {}ns limit", result.cpu_ns, timeout_ns);
+    assert!(result.cpu_ns < timeout_ns * 2, "{}ns is far above the {}ns limit", result.cpu_ns, timeout_ns);
+
+    let idle = sandbox.execute_player("idle", &serde_json::json!({})).unwrap();
+    assert!(idle.cpu_ns < result.cpu_ns);
+    sandbox.execute_player("idle", &serde_json::json!({})).unwrap();
+
+    let stats = sandbox.player_stats("spinner").unwrap();
+    assert_eq!(stats.total_executions, 1);
+    assert_eq!(stats.total_cpu_ns, result.cpu_ns);
+    assert_eq!(stats.max_cpu_ns, result.cpu_ns);
+    assert_eq!(stats.last_execution_cpu_ns, result.cpu_ns);
+    let stats = sandbox.player_stats("idle").unwrap();
+    assert_eq!(stats.total_executions, 2);
+    assert!(stats.max_cpu_ns >= stats.last_execution_cpu_ns);
+    assert!(sandbox.player_stats("nobody").is_none());
+}
+
+#[test]
+fn test_bundle_limits_enforced() {
+    let mut sandbox = Sandbox::new();
+
+    let big = "x".repeat(600_000);
+    let err = sandbox.submit_bundle("big".to_string(), modules(&[
+        ("main.js", big.as_str()),
+        ("other.js", big.as_str()),
+    ])).unwrap_err();
+    assert!(err.contains("Code too large"));
+
+    let many: BTreeMap<String, String> = (0..65)
+        .map(|i| if i == 0 { "main.js".to_string() } else { format!("m{}.js", i) })
+        .map(|name| (name, String::new()))
+        .collect();
+    assert!(sandbox.submit_bundle("many".to_string(), many).unwrap_err().contains("Too many modules"));
+
+    let traversal = modules(&[("main.js", ""), ("../other_player/main.js", "")]);
+    assert!(sandbox.submit_bundle("sneaky".to_string(), traversal).is_err());
+
+    assert!(sandbox.list_players().is_empty());
+}
+
+#[test]
+fn test_submitted_modules_are_imported() {
+    let mut sandbox = Sandbox::new();
+    assert!(sandbox.submit_module("alice", "utils.js", "export const x = 1;").unwrap_err().contains("main.js"));
+
+    sandbox.submit_module("alice", "main.js", "\
+        import double, { greet } from './utils';\n\
+        const legacy = require('./legacy');\n\
+        console.log(greet('bob'), double(21), legacy.answer);").unwrap();
+    sandbox.submit_module("alice", "utils.js", "\
+        export function greet(name) { return 'hello ' + name; }\n\
+        export default function (n) { return n * 2; }").unwrap();
+    sandbox.submit_module("alice", "legacy.js", "module.exports = { answer: 42 };").unwrap();
+
+    let result = sandbox.execute_player("alice", &serde_json::json!({"tick": 1})).unwrap();
+    assert_eq!(result.error, None);
+    assert_eq!(result.logs, vec!["hello bob 42 42".to_string()]);
+
+    // Limits: module count and size, and the entry module stays
+    for i in 3..10 {
+        sandbox.submit_module("alice", &format!("m{}.js", i), "").unwrap();
+    }
+    assert!(sandbox.submit_module("alice", "m10.js", "").unwrap_err().contains("Too many modules"));
+    assert!(sandbox.submit_module("alice", "utils.js", &"x".repeat(100_001)).unwrap_err().contains("too large"));
+    assert!(sandbox.delete_module("alice", "main.js").is_err());
+    sandbox.delete_module("alice", "legacy.js").unwrap();
+    let result = sandbox.execute_player("alice", &serde_json::json!({"tick": 2})).unwrap();
+    assert!(result.error.unwrap().contains("Cannot find module './legacy'"));
+}
+
+#[test]
+fn test_shared_libraries_versions_and_imports() {
+    let db = Arc::new(AuthDatabase::new(DatabaseBackend::InMemory).unwrap());
+    let author = db.create_user("lib_author", "hash").unwrap();
+    let other = db.create_user("lib_other", "hash").unwrap();
+    let auth = AuthService::new(db);
+
+    let v1 = auth.publish_library(author.id, "priority-queue",
+        "export function pq() { return 'v1'; }").unwrap();
+    assert_eq!((v1.version, v1.approved), (1, false));
+    assert!(auth.publish_library(other.id, "priority-queue", "").unwrap_err().contains("another player"));
+    assert!(auth.publish_library(author.id, "Bad Name", "").is_err());
+    assert!(auth.approved_libraries().unwrap().is_empty());
+
+    // Unapproved libraries cannot be imported
+    let mut sandbox = Sandbox::new();
+    let bot = "import { pq } from '@community/priority-queue';\nconsole.log(pq());";
+    sandbox.submit_code("alice".to_string(), bot.to_string()).unwrap();
+    sandbox.submit_code("bob".to_string(), bot.to_string()).unwrap();
+    let library_sources = |auth: &AuthService| auth.approved_libraries().unwrap().into_iter()
+        .map(|library| (library.name, library.code))
+        .collect();
+    sandbox.set_libraries(library_sources(&auth));
+    let result = sandbox.execute_player("alice", &serde_json::json!({"tick": 1})).unwrap();
+    assert!(result.error.unwrap().contains("no approved library"));
+
+    // Approved ones are available to every player
+    auth.approve_library(&v1.id).unwrap();
+    sandbox.set_libraries(library_sources(&auth));
+    for player in ["alice", "bob"] {
+        let result = sandbox.execute_player(player, &serde_json::json!({"tick": 1})).unwrap();
+        assert_eq!(result.error, None);
+        assert_eq!(result.logs, vec!["v1".to_string()]);
+    }
+
+    // An update is a new version, used once approved
+    let v2 = auth.publish_library(author.id, "priority-queue",
+        "export function pq() { return 'v2'; }").unwrap();
+    assert_eq!(v2.version, 2);
+    assert_eq!(auth.approved_libraries().unwrap()[0].version, 1);
+    auth.approve_library(&v2.id).unwrap();
+    sandbox.set_libraries(library_sources(&auth));
+    let result = sandbox.execute_player("bob", &serde_json::json!({"tick": 2})).unwrap();
+    assert_eq!(result.logs, vec!["v2".to_string()]);
+}
+
+#[test]
+fn test_message_delivered_next_tick() {
+    let mut sandbox = Sandbox::new();
+    sandbox.submit_code("alice".to_string(), "game.sendMessage('bob', {attack: [3, 4]});".to_string()).unwrap();
+    sandbox.submit_code("bob".to_string(), "\
+        const messages = game.receiveMessages();\n\
+        console.log(messages.length, JSON.stringify(messages));".to_string()).unwrap();
+
+    // Tick N: alice sends, bob sees nothing yet
+    sandbox.begin_tick(5);
+    let sent = sandbox.execute_player("alice", &serde_json::json!({"tick": 5})).unwrap();
+    assert_eq!(sent.error, None);
+    assert_eq!(sent.sent_messages.len(), 1);
+    let same_tick = sandbox.execute_player("bob", &serde_json::json!({"tick": 5})).unwrap();
+    assert_eq!(same_tick.logs, vec!["0 []".to_string()]);
+
+    // Tick N+1: the message is delivered and consumed on read
+    sandbox.begin_tick(6);
+    let next_tick = sandbox.execute_player("bob", &serde_json::json!({"tick": 6})).unwrap();
+    assert_eq!(next_tick.logs, vec![r#"1 [{"from":"alice","data":{"attack":[3,4]},"sentAtTick":5}]"#.to_string()]);
+    assert!(sandbox.receive_messages("bob").is_empty());
+}
+
+#[test]
+fn test_message_queue_and_inbox_limit() {
+    let mut sandbox = Sandbox::new();
+    sandbox.submit_code("alice".to_string(), "// bot".to_string()).unwrap();
+    sandbox.submit_code("bob".to_string(), "// bot".to_string()).unwrap();
+    sandbox.set_inbox_limit(2);
+
+    sandbox.begin_tick(1);
+    sandbox.send_message("alice", "bob", serde_json::json!(1)).unwrap();
+    assert!(sandbox.receive_messages("bob").is_empty());
+    sandbox.send_message("alice", "bob", serde_json::json!(2)).unwrap();
+    sandbox.send_message("alice", "bob", serde_json::json!(3)).unwrap();
+    assert!(sandbox.send_message("alice", "nobody", serde_json::json!(4)).is_err());
+
+    // A full inbox drops its oldest message
+    sandbox.begin_tick(2);
+    assert_eq!(sandbox.inbox("bob").len(), 2);
+    sandbox.send_message("alice", "bob", serde_json::json!(5)).unwrap();
+    let received = sandbox.receive_messages("bob");
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].from, "alice");
+    assert_eq!(received[0].payload, serde_json::json!(3));
+    assert_eq!(received[0].sent_at_tick, 1);
+
+    sandbox.begin_tick(3);
+    let received: Vec<_> = sandbox.receive_messages("bob").into_iter().map(|m| m.payload).collect();
+    assert_eq!(received, vec![serde_json::json!(5)]);
+}
+
+#[test]
+fn test_messages_round_trip_across_ticks() {
+    let mut sandbox = Sandbox::new();
+    sandbox.submit_code("alice".to_string(), "\
+        for (const m of game.peekMessages()) console.log(m.from, JSON.stringify(m.data));\n\
+        if (game.tick === 1) game.sendMessage('bob', {ping: 1});".to_string()).unwrap();
+    sandbox.submit_code("bob".to_string(), "\
+        for (const m of game.receiveMessages()) game.sendMessage(m.from, {pong: m.data.ping});".to_string()).unwrap();
+
+    for tick in 1..=3 {
+        sandbox.begin_tick(tick);
+        let alice = sandbox.execute_player("alice", &serde_json::json!({"tick": tick})).unwrap();
+        sandbox.execute_player("bob", &serde_json::json!({"tick": tick})).unwrap();
+        if tick == 3 {
+            assert_eq!(alice.logs, vec![r#"bob {"pong":1}"#.to_string()]);
+        } else {
+            assert!(alice.logs.is_empty());
+        }
+    }
+
+    // peekMessages() does not consume: the reply is still there
+    assert_eq!(sandbox.inbox("alice").len(), 1);
+    assert!(sandbox.inbox("bob").is_empty());
+}
+
+#[test]
+fn test_message_rate_limit() {
+    let mut sandbox = Sandbox::new();
+    sandbox.submit_code("alice".to_string(), "\
+        let sent = 0;\n\
+        for (let i = 0; i < 12; i++) if (game.sendMessage('bob', i)) sent++;\n\
+        console.log(sent);".to_string()).unwrap();
+    sandbox.submit_code("bob".to_string(), "// bot".to_string()).unwrap();
+
+    sandbox.begin_tick(1);
+    let result = sandbox.execute_player("alice", &serde_json::json!({"tick": 1})).unwrap();
+    assert_eq!(result.logs, vec!["10".to_string()]);
+    assert_eq!(result.sent_messages.len(), MAX_MESSAGES_PER_TICK);
+    let err = sandbox.send_message("alice", "bob", serde_json::json!("one more")).unwrap_err();
+    assert!(err.contains("per tick"), "{}", err);
+
+    // The limit resets every tick
+    sandbox.begin_tick(2);
+    sandbox.send_message("alice", "bob", serde_json::json!("next tick")).unwrap();
+    assert_eq!(sandbox.inbox("bob").len(), MAX_MESSAGES_PER_TICK);
+}
+
+#[test]
+fn test_oversized_message_rejected() {
+    let mut sandbox = Sandbox::new();
+    sandbox.submit_code("alice".to_string(), "\
+        console.log(game.sendMessage('bob', 'x'.repeat(2000)), game.sendMessage('bob', 'x'.repeat(100)));".to_string()).unwrap();
+    sandbox.submit_code("bob".to_string(), "// bot".to_string()).unwrap();
+
+    sandbox.begin_tick(1);
+    let result = sandbox.execute_player("alice", &serde_json::json!({"tick": 1})).unwrap();
+    assert_eq!(result.logs, vec!["false true".to_string()]);
+    let payload = serde_json::json!("x".repeat(MAX_MESSAGE_BYTES));
+    assert!(sandbox.send_message("alice", "bob", payload).unwrap_err().contains("bytes"));
+
+    sandbox.begin_tick(2);
+    assert_eq!(sandbox.inbox("bob").len(), 1);
+}
+
+#[test]
+fn test_messages_allies_only() {
+    let mut sandbox = Sandbox::new();
+    sandbox.submit_code("alice".to_string(), "console.log(game.sendMessage('bob', 'hi'));".to_string()).unwrap();
+    sandbox.submit_code("bob".to_string(), "// bot".to_string()).unwrap();
+    sandbox.submit_code("carol".to_string(), "// bot".to_string()).unwrap();
+    sandbox.set_allies_only(true);
+
+    sandbox.begin_tick(1);
+    let result = sandbox.execute_player("alice", &serde_json::json!({"tick": 1, "allies": []})).unwrap();
+    assert!(result.logs.iter().any(|line| line.contains("bob is not an ally")), "{:?}", result.logs);
+    let result = sandbox.execute_player("alice", &serde_json::json!({"tick": 1, "allies": ["bob"]})).unwrap();
+    assert_eq!(result.logs, vec!["true".to_string()]);
+    assert!(sandbox.send_message("alice", "carol", serde_json::json!("hi")).unwrap_err().contains("not an ally"));
+
+    sandbox.begin_tick(2);
+    assert_eq!(sandbox.inbox("bob").len(), 1);
+    assert!(sandbox.inbox("carol").is_empty());
+}
+
+#[test]
+fn test_move_worker_east_through_runtime() {
+    let code = "\
+        class Bot {\n\
+            onTick(game) {\n\
+                for (const unit of game.getMyUnits()) {\n\
+                    if (unit.type === 'worker') {\n\
+                        unit.moveTo({x: unit.position.x + 1, y: unit.position.y});\n\
+                    }\n\
+                }\n\
+            }\n\
+        }\n\
+        module.exports = Bot;";
+    let snapshot = serde_json::json!({
+        "tick": 1,
+        "player_id": "alice",
+        "units": [
+            {"id": "w1", "type": "worker", "owner": "alice", "position": {"x": 4, "y": 7}},
+            {"id": "e1", "type": "worker", "owner": "bob", "position": {"x": 9, "y": 9}}
+        ]
+    });
+
+    let mut sandbox = Sandbox::new();
+    sandbox.submit_code("alice".to_string(), code.to_string()).unwrap();
+    assert_eq!(sandbox.get_bundle("alice").unwrap().language(), ScriptLanguage::JavaScript);
+
+    let result = sandbox.execute_player("alice", &snapshot).unwrap();
+    assert_eq!(result.error, None);
+    assert_eq!(result.commands.len(), 1);
+    assert_eq!(result.commands[0].action, "moveTo");
+    assert_eq!(result.commands[0].actor.as_deref(), Some("w1"));
+    assert_eq!(result.commands[0].params, serde_json::json!({"position": {"x": 5, "y": 7}}));
+    assert_eq!(result.commands[0].player_id.as_deref(), Some("alice"));
+
+    // The runtime interface gives the same result as the sandbox, except for the issuer the sandbox adds
+    let runtime = create_runtime(ScriptLanguage::JavaScript, ScriptLimits::default());
+    let bundle = ScriptBundle::single(code.to_string()).unwrap();
+    assert!(runtime.compile(&bundle).is_ok());
+    let mut commands = runtime.execute_tick(&bundle, &snapshot).commands;
+    assert_eq!(commands[0].player_id, None);
+    commands[0].player_id = Some("alice".to_string());
+    assert_eq!(commands, result.commands);
+}
+
+#[test]
+fn test_runtime_compile_reports_syntax_errors() {
+    let runtime = JsRuntime::new(ScriptLimits::default());
+    let bundle = ScriptBundle::single("let x = ;".to_string()).unwrap();
+    assert!(runtime.compile(&bundle).unwrap_err().contains("main.js:1"));
+
+    assert!(ScriptLanguage::from_name("JavaScript").is_ok());
+    assert!(ScriptLanguage::from_name("cobol").unwrap_err().contains("Unsupported language"));
+}
+
+#[test]
+fn test_js_time_limit_starts_with_bot_code() {
+    // Setting up the bot API takes longer than this limit, but does not count against it
+    let runtime = JsRuntime::new(ScriptLimits { timeout: Duration::from_millis(2), ..ScriptLimits::default() });
+    let snapshot = serde_json::json!({"tick": 1, "player_id": "quick"});
+
+    let result = runtime.execute_tick(&ScriptBundle::single("throw new Error('kaboom');".to_string()).unwrap(), &snapshot);
+    assert!(result.error.as_deref().unwrap_or_default().contains("kaboom"), "{:?}", result.error);
+
+    let result = runtime.execute_tick(&ScriptBundle::single("while (true) {}".to_string()).unwrap(), &snapshot);
+    assert_eq!(result.error.as_deref(), Some("Script exceeded time limit of 2ms"));
+}
+
+#[test]
+fn test_lua_game_api_matches_javascript() {
+    let mut world = World::new();
+    let zone_id = world.generate_player_zone("alice").unwrap();
+    let start = walkable_tile(&world, &zone_id, 0);
+    world.with_zone_write(&zone_id, |zone| {
+        zone.entities.push(entity(1, "worker", "alice", start));
+        zone.entities.push(entity(2, "soldier", "alice", start));
+    }).unwrap();
+    world.deposit_resources("alice", ResourceType::Minerals, 40);
+    let snapshot = world.player_snapshot("alice");
+
+    let js = ScriptBundle::single("\
+        const units = game.getMyUnits();\n\
+        game.sendMessage('observer', units);\n\
+        units[0].moveTo({x: 3, y: 4});\n\
+        console.log(units.length, game.getMyResources().minerals);".to_string()).unwrap();
+    let lua = ScriptBundle::single("\
+        local units = game.getMyUnits()\n\
+        game.sendMessage('observer', units)\n\
+        units[1].moveTo({x = 3, y = 4})\n\
+        print(#units, game.getMyResources().minerals)".to_string()).unwrap()
+        .with_language(ScriptLanguage::Lua);
+
+    let mut sandbox = Sandbox::new();
+    sandbox.submit("lua_bot".to_string(), lua.clone()).unwrap();
+    assert_eq!(sandbox.get_bundle("lua_bot").unwrap().language(), ScriptLanguage::Lua);
+
+    let js = create_runtime(ScriptLanguage::JavaScript, ScriptLimits::default()).execute_tick(&js, &snapshot);
+    let lua = create_runtime(ScriptLanguage::Lua, ScriptLimits::default()).execute_tick(&lua, &snapshot);
+
+    assert_eq!(lua.error, None);
+    assert_eq!(lua.logs, vec!["2 40".to_string()]);
+    assert_eq!(lua.logs, js.logs);
+    assert_eq!(lua.commands, js.commands);
+    assert_eq!(lua.sent_messages[0].payload.as_array().unwrap().len(), 2);
+    assert_eq!(lua.sent_messages[0].payload, js.sent_messages[0].payload);
+}
+
+#[test]
+fn test_api_v1_shims_match_the_current_api() {
+    let mut world = World::new();
+    let zone_id = world.generate_player_zone("alice").unwrap();
+    let start = walkable_tile(&world, &zone_id, 0);
+    world.with_zone_write(&zone_id, |zone| zone.entities.push(entity(1, "worker", "alice", start))).unwrap();
+    let mut snapshot = world.player_snapshot("alice");
+    snapshot["messages"] = serde_json::json!([{"from": "bob", "payload": {"ping": 1}, "sent_at_tick": 0}]);
+
+    let v1_bot = "module.exports = { onTick(game) {\n\
+        for (const m of game.inbox()) console.log(m.from, JSON.stringify(m.data));\n\
+        game.moveUnit(1, 3, 4);\n\
+    } };";
+    let current_bot = "module.exports = { onTick(game) {\n\
+        for (const m of game.peekMessages()) console.log(m.from, JSON.stringify(m.data));\n\
+        game.getUnitById(game.getMyUnits()[0].id).moveTo({x: 3, y: 4});\n\
+    } };";
+    let runtime = create_runtime(ScriptLanguage::JavaScript, ScriptLimits::default());
+
+    let v1 = ScriptBundle::single(v1_bot.to_string()).unwrap().with_api_version(1).unwrap();
+    let current = ScriptBundle::single(current_bot.to_string()).unwrap();
+    assert_eq!(current.api_version(), LATEST_API_VERSION);
+    let v1 = runtime.execute_tick(&v1, &snapshot);
+    let current = runtime.execute_tick(&current, &snapshot);
+    assert_eq!(v1.error, None);
+    assert_eq!(current.error, None);
+    assert_eq!(v1.logs, vec![r#"bob {"ping":1}"#.to_string()]);
+    assert_eq!(v1.logs, current.logs);
+    assert_eq!(v1.commands, vec![BotCommand {
+        action: "moveTo".to_string(),
+        actor: Some(format!("{}:1", zone_id)),
+        params: serde_json::json!({"position": {"x": 3, "y": 4}}),
+        player_id: None,
+    }]);
+    assert_eq!(v1.commands, current.commands);
+
+    // Version 1 bots see the game object as it was then
+    let shape = ScriptBundle::single("console.log(typeof game.peekMessages, typeof game.moveUnit);".to_string()).unwrap();
+    let shape = runtime.execute_tick(&shape.with_api_version(1).unwrap(), &snapshot);
+    assert_eq!(shape.logs, vec!["undefined function".to_string()]);
+
+    // Bound to the current API, the old names explain what replaced them
+    let upgraded = runtime.execute_tick(&ScriptBundle::single(v1_bot.to_string()).unwrap(), &snapshot);
+    assert!(upgraded.error.unwrap().contains("Upgraded API v2: game.inbox() was renamed to game.peekMessages()"));
+    let removed = ScriptBundle::single("game.moveUnit(1, 3, 4);".to_string()).unwrap();
+    let removed = runtime.execute_tick(&removed, &snapshot);
+    assert!(removed.commands.is_empty());
+    assert!(removed.error.unwrap().contains("Upgraded API v2: game.moveUnit() was removed, use game.getUnitById(id).moveTo(position) instead"));
+
+    // Lua bots get the same shims, and declare their version with a pragma
+    let lua = create_runtime(ScriptLanguage::Lua, ScriptLimits::default());
+    let lua_v1 = ScriptBundle::single("-- @api v1\ngame.moveUnit(1, 3, 4)".to_string()).unwrap()
+        .with_language(ScriptLanguage::Lua);
+    assert_eq!(lua_v1.api_version(), 1);
+    assert_eq!(lua.execute_tick(&lua_v1, &snapshot).commands, current.commands);
+    let lua_current = ScriptBundle::single("game.moveUnit(1, 3, 4)".to_string()).unwrap()
+        .with_language(ScriptLanguage::Lua);
+    assert!(lua.execute_tick(&lua_current, &snapshot).error.unwrap().contains("game.moveUnit() was removed"));
+
+    // A declared version cannot be contradicted, and unknown versions are rejected
+    let declared = ScriptBundle::single("// @api v1\ngame.moveUnit(1, 3, 4);".to_string()).unwrap();
+    assert_eq!(declared.api_version(), 1);
+    assert!(declared.with_api_version(2).unwrap_err().contains("does not match the @api v1 pragma"));
+    assert!(ScriptBundle::single("// @api v7\n".to_string()).unwrap_err().contains("Unsupported API version 7"));
+}
+
+#[test]
+fn test_lua_sandbox_limits() {
+    let limits = ScriptLimits { timeout: Duration::from_millis(50), ..ScriptLimits::default() };
+    let runtime = create_runtime(ScriptLanguage::Lua, limits);
+    let snapshot = serde_json::json!({"tick": 1, "player_id": "lua"});
+    let lua = |code: &str| ScriptBundle::single(code.to_string()).unwrap().with_language(ScriptLanguage::Lua);
+
+    let result = runtime.execute_tick(&lua("while true do end"), &snapshot);
+    assert_eq!(result.error.as_deref(), Some("Script exceeded time limit of 50ms"));
+
+    let result = runtime.execute_tick(&lua("print(os, io, require, load)"), &snapshot);
+    assert_eq!(result.logs, vec!["nil nil nil nil".to_string()]);
+
+    // Bot tables with an onTick method run like exported JavaScript bots
+    let result = runtime.execute_tick(&lua("local Bot = {} function Bot:onTick(game) print(game.tick) end return Bot"), &snapshot);
+    assert_eq!(result.logs, vec!["1".to_string()]);
+
+    assert!(runtime.compile(&lua("local x = ")).unwrap_err().contains("main.lua:1"));
+    assert_eq!(ScriptLanguage::from_name("Lua"), Ok(ScriptLanguage::Lua));
+}
+
+#[test]
+fn test_typescript_bot_runs_as_javascript() {
+    let mut world = World::new();
+    let zone_id = world.generate_player_zone("alice").unwrap();
+    let start = walkable_tile(&world, &zone_id, 0);
+    world.with_zone_write(&zone_id, |zone| zone.entities.push(entity(1, "worker", "alice", start))).unwrap();
+    let snapshot = world.player_snapshot("alice");
+
+    let modules = BTreeMap::from([
+        ("main.js".to_string(), "\
+interface Target { x: number; y: number }
+const { nextTarget } = require('./plan') as { nextTarget: (unit: Unit) => Target };
+class Bot {
+    private moves: number = 0;
+    onTick(game: GameAPI): void {
+        for (const unit of game.getMyUnits() as Unit[]) {
+            unit.moveTo(nextTarget(unit)!);
+            this.moves++;
+        }
+        console.log(`moves: ${this.moves as number}`);
+    }
+}
+module.exports = Bot;
+".to_string()),
+        ("plan.js".to_string(), "exports.nextTarget = (unit: Unit): Position => ({ x: unit.position.x, y: 0 });".to_string()),
+    ]);
+    let bundle = ScriptBundle::from_modules(modules).unwrap().with_language(ScriptLanguage::TypeScript);
+    let runtime = create_runtime(ScriptLanguage::TypeScript, ScriptLimits::default());
+    runtime.compile(&bundle).unwrap();
+
+    let result = runtime.execute_tick(&bundle, &snapshot);
+    assert_eq!(result.error, None);
+    assert_eq!(result.logs, vec!["moves: 1".to_string()]);
+    assert_eq!(result.commands[0].params["position"], serde_json::json!({"x": start.0, "y": 0}));
+
+    // Errors point at the TypeScript source
+    let broken = ScriptBundle::single("let ok: number = 1;\nenum Kind { Worker }".to_string()).unwrap()
+        .with_language(ScriptLanguage::TypeScript);
+    assert_eq!(
+        runtime.compile(&broken).unwrap_err(),
+        "main.js: line 2, column 1: enums are not supported (only type annotations can be removed)"
+    );
+    assert_eq!(ScriptLanguage::from_name("ts"), Ok(ScriptLanguage::TypeScript));
+}
+
+#[test]
+fn test_wasm_fixture_bot_issues_move() {
+    let mut sandbox = Sandbox::new();
+    sandbox.submit("rustacean".to_string(), wasm_bundle(include_bytes!("fixtures/move_bot.wasm"))).unwrap();
+
+    let snapshot = serde_json::json!({"tick": 1, "player_id": "rustacean", "units": []});
+    let result = sandbox.execute_player("rustacean", &snapshot).unwrap();
+    assert_eq!(result.error, None);
+    assert_eq!(result.logs, vec!["wasm bot online".to_string()]);
+    assert_eq!(result.commands.len(), 1);
+    assert_eq!(result.commands[0].action, "moveTo");
+    assert_eq!(result.commands[0].actor.as_deref(), Some("w1"));
+    assert_eq!(result.commands[0].params, serde_json::json!({"position": {"x": 5, "y": 7}}));
+}
+
+#[test]
+fn test_wasm_forbidden_import_rejected() {
+    let module = wat::parse_str(r#"
+        (module
+          (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) (i32.const 0))
+          (func (export "on_tick") (param i32 i32)))
+    "#).unwrap();
+
+    let mut sandbox = Sandbox::new();
+    let err = sandbox.submit("sneaky".to_string(), wasm_bundle(&module)).unwrap_err();
+    assert!(err.contains("Forbidden import: wasi_snapshot_preview1.fd_write"), "{}", err);
+    assert!(sandbox.get_bundle("sneaky").is_none());
+
+    let err = sandbox.submit("garbage".to_string(), wasm_bundle(b"not wasm")).unwrap_err();
+    assert!(err.contains("Invalid WASM module"), "{}", err);
+}
+
+#[test]
+fn test_wasm_fuel_exhaustion_is_a_player_error() {
+    let module = wat::parse_str(r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) (i32.const 0))
+          (func (export "on_tick") (param i32 i32)
+            (loop $spin (br $spin))))
+    "#).unwrap();
+
+    let mut sandbox = Sandbox::new();
+    sandbox.submit("spinner".to_string(), wasm_bundle(&module)).unwrap();
+    sandbox.submit("steady".to_string(), wasm_bundle(include_bytes!("fixtures/move_bot.wasm"))).unwrap();
+
+    let snapshot = serde_json::json!({"tick": 1});
+    let result = sandbox.execute_player("spinner", &snapshot).unwrap();
+    assert!(result.error.unwrap().contains("exceeded fuel limit"));
+
+    // Other players keep running normally
+    let result = sandbox.execute_player("steady", &snapshot).unwrap();
+    assert_eq!(result.error, None);
+    assert_eq!(result.commands.len(), 1);
+}
+
+/// Sandbox with `count` players running `code`, and a snapshot for each
+fn engine_with_players(count: usize, code: &str) -> (Sandbox, BTreeMap<String, serde_json::Value>) {
+    // Scripts share few cores: a generous time limit keeps a slow machine from timing them out
+    let mut engine = Sandbox::with_limits(ScriptLimits { timeout: Duration::from_secs(5), ..ScriptLimits::default() });
+    let mut snapshots = BTreeMap::new();
+    for i in 0..count {
+        let player_id = format!("bot_{:03}", i);
+        engine.submit_code(player_id.clone(), code.to_string()).unwrap();
+        snapshots.insert(player_id, serde_json::json!({"tick": 1}));
+    }
+    (engine, snapshots)
+}
+
+/// Time to run one tick of 32 scripts busy-waiting 5ms each, checking every command is kept
+async fn run_parallel_tick(threads: usize) -> Duration {
+    const PLAYERS: usize = 32;
+    let code = "const end = Date.now() + 5; while (Date.now() < end) {} game.buildStructure('turret', {x: 1, y: 1});";
+    let (engine, snapshots) = engine_with_players(PLAYERS, code);
+    let handle = ScriptEngineHandle::with_threads(engine, threads);
+
+    let start = Instant::now();
+    let results = handle.run_tick(1, &snapshots).await;
+    let elapsed = start.elapsed();
+
+    // Every player ran once, in player ID order, and every command was kept
+    let player_ids: Vec<&String> = results.iter().map(|(player_id, _)| player_id).collect();
+    assert_eq!(player_ids, snapshots.keys().collect::<Vec<_>>());
+    for (player_id, result) in &results {
+        assert!(result.error.is_none() && result.commands.len() == 1, "{}: {:?}", player_id, result);
+    }
+    elapsed
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_parallel_tick_without_lost_commands() {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get()).min(4);
+    for threads in [1, cores] {
+        run_parallel_tick(threads).await;
+    }
+}
+
+/// Benchmark: `cargo test --test integration_tests -- --ignored bench_parallel_tick_speedup`
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "timing-dependent benchmark"]
+async fn bench_parallel_tick_speedup() {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get()).min(4);
+    let sequential = run_parallel_tick(1).await;
+    let parallel = run_parallel_tick(cores).await;
+    let speedup = sequential.as_secs_f64() / parallel.as_secs_f64();
+    assert!(speedup > cores as f64 * 0.6, "expected near-linear speedup, got {:.1}x on {} threads", speedup, cores);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_submission_while_tick_executes() {
+    let (engine, snapshots) = engine_with_players(4, "const end = Date.now() + 40; while (Date.now() < end) {} console.log('old');");
+    let handle = ScriptEngineHandle::with_threads(engine, 1);
+
+    let tick = tokio::spawn({
+        let handle = handle.clone();
+        async move { handle.run_tick(1, &snapshots).await }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // The engine is not locked while scripts run
+    let mut engine = tokio::time::timeout(Duration::from_millis(50), handle.write())
+        .await
+        .expect("Submission blocked by running tick");
+    engine.submit_code("bot_000".to_string(), "console.log('new');".to_string()).unwrap();
+    drop(engine);
+
+    // The running tick keeps the code it started with
+    let results = tick.await.unwrap();
+    assert_eq!(results.len(), 4);
+    assert_eq!(results[0].1.logs, vec!["old".to_string()]);
+
+    let snapshot = serde_json::json!({"tick": 2});
+    let result = handle.write().await.execute_player("bot_000", &snapshot).unwrap();
+    assert_eq!(result.logs, vec!["new".to_string()]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_concurrent_scripts_are_bounded() {
+    const PLAYERS: usize = 8;
+    // Each script busy-waits 30ms: with 2 slots, 8 scripts take at least 4 rounds
+    let code = "const end = Date.now() + 30; while (Date.now() < end) {} game.buildStructure('turret', {x: 1, y: 1});";
+    let (_, snapshots) = engine_with_players(PLAYERS, code);
+    let mut engine = Sandbox::with_limits(ScriptLimits { timeout: Duration::from_secs(5), ..ScriptLimits::default() });
+    for player_id in snapshots.keys() {
+        engine.submit_code(player_id.clone(), code.to_string()).unwrap();
+    }
+    engine.set_max_concurrent_scripts(2);
+    engine.set_script_queue_timeout(Duration::from_secs(10));
+    let slots = engine.execution_slots();
+    let handle = ScriptEngineHandle::with_threads(engine, PLAYERS);
+
+    let tick = tokio::spawn({
+        let handle = handle.clone();
+        async move { handle.run_tick(1, &snapshots).await }
+    });
+    // A dry run competes for the same slots
+    let dry_run = handle.dry_run("bot_000".to_string(), "console.log('dry');".to_string(), WorldSnapshot::capture(&World::new()).unwrap());
+
+    let start = Instant::now();
+    let (results, dry_run) = tokio::time::timeout(Duration::from_secs(10), async { tokio::join!(tick, dry_run) })
+        .await
+        .expect("Script executions deadlocked");
+    let results = results.unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(4 * 30), "8 scripts ran in {:?} on 2 slots", start.elapsed());
+
+    assert_eq!(results.len(), PLAYERS);
+    for (player_id, result) in &results {
+        assert!(result.error.is_none() && result.commands.len() == 1, "{}: {:?}", player_id, result);
+    }
+    assert_eq!(dry_run.logs, vec!["dry".to_string()]);
+    // Every permit was given back
+    assert_eq!(slots.available(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_script_without_a_slot_runs_next_tick() {
+    let (mut engine, snapshots) = engine_with_players(3, "game.buildStructure('turret', {x: 1, y: 1});");
+    engine.set_max_concurrent_scripts(1);
+    engine.set_script_queue_timeout(Duration::from_millis(20));
+    let slots = engine.execution_slots();
+    let handle = ScriptEngineHandle::with_threads(engine, 3);
+
+    // While the only slot is held, scripts give up waiting without running
+    let held = slots.try_acquire().unwrap();
+    let results = handle.run_tick(1, &snapshots).await;
+    assert!(results.is_empty(), "{:?}", results);
+    {
+        let mut engine = handle.write().await;
+        let stats = engine.player_stats("bot_000").unwrap();
+        assert_eq!((stats.queued_executions, stats.total_executions), (1, 0));
+        assert!(engine.get_errors("bot_000").is_empty());
+        assert_eq!(engine.execute_script("1 + 1;"), Err(ScriptError::Queued));
+        assert_eq!(engine.execute_player("bot_000", &snapshots["bot_000"]).unwrap_err(), ScriptError::Queued);
+        assert_eq!(engine.player_stats("bot_000").unwrap().queued_executions, 2);
+    }
+    drop(held);
+    handle.write().await.set_script_queue_timeout(Duration::from_secs(10));
+
+    // They run on the next tick
+    let results = handle.run_tick(2, &snapshots).await;
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(|(_, result)| result.commands.len() == 1));
+    assert_eq!(handle.read().await.player_stats("bot_000").unwrap().total_executions, 1);
+}
+
+#[test]
+fn test_tick_execute_all_runs_light_scripts_first() {
+    let mut world = World::new();
+    let mut engine = Sandbox::with_limits(ScriptLimits { timeout: Duration::from_secs(5), ..ScriptLimits::default() });
+    // 10 players busy-waiting from 18ms (bot_0) down to 0ms (bot_9)
+    for i in 0..10 {
+        let code = format!("const end = Date.now() + {}; while (Date.now() < end) {{}} console.log('ran');", (9 - i) * 2);
+        engine.submit_code(format!("bot_{}", i), code).unwrap();
+    }
+    let order = |results: &[(String, _)]| results.iter().map(|(player_id, _)| player_id.clone()).collect::<Vec<_>>();
+
+    // Nobody has used CPU time yet: player ID order
+    let first = engine.tick_execute_all(&mut world);
+    assert_eq!(order(&first), (0..10).map(|i| format!("bot_{}", i)).collect::<Vec<_>>());
+    assert!(first.iter().all(|(_, result)| result.logs == vec!["ran".to_string()]));
+
+    // Then the lightest on average (a single execution so far) goes first, after a newcomer who used none
+    let mut expected: Vec<(u64, String)> = first.iter().map(|(player_id, result)| (result.cpu_ns, player_id.clone())).collect();
+    expected.sort();
+    engine.submit_code("newcomer".to_string(), "console.log('ran');".to_string()).unwrap();
+    let second = engine.tick_execute_all(&mut world);
+    let expected: Vec<String> = std::iter::once("newcomer".to_string()).chain(expected.into_iter().map(|(_, player_id)| player_id)).collect();
+    assert_eq!(order(&second), expected);
+
+    // CPU time adds up across ticks
+    let stats = engine.player_stats("bot_0").unwrap();
+    assert_eq!(stats.total_executions, 2);
+    assert!(stats.total_cpu_ns >= 2 * 18_000_000, "{:?}", stats);
+}
+
+#[test]
+fn test_one_cheap_tick_does_not_put_a_heavy_script_first() {
+    let mut world = World::new();
+    let mut engine = Sandbox::with_limits(ScriptLimits { timeout: Duration::from_secs(5), ..ScriptLimits::default() });
+    let busy = |ms: u32| format!("const end = Date.now() + {}; while (Date.now() < end) {{}}", ms);
+    // Far apart, so that the runtime's own overhead on a loaded machine cannot swap them
+    engine.submit_code("heavy".to_string(), busy(500)).unwrap();
+    engine.submit_code("light".to_string(), busy(100)).unwrap();
+    let order = |results: &[(String, _)]| results.iter().map(|(player_id, _)| player_id.clone()).collect::<Vec<_>>();
+    for _ in 0..3 {
+        engine.tick_execute_all(&mut world);
+    }
+
+    // The heavy script's latest execution is now the cheapest, but not its average
+    engine.submit_code("heavy".to_string(), "console.log('cheap');".to_string()).unwrap();
+    engine.tick_execute_all(&mut world);
+    let (heavy, light) = (engine.player_stats("heavy").unwrap(), engine.player_stats("light").unwrap());
+    assert!(heavy.last_execution_cpu_ns < light.last_execution_cpu_ns, "{:?} {:?}", heavy, light);
+    assert!(heavy.average_cpu_ns > light.average_cpu_ns, "{:?} {:?}", heavy, light);
+    assert_eq!(order(&engine.tick_execute_all(&mut world)), ["light", "heavy"]);
+}
+
+#[test]
+fn test_day_phase_exposed_to_scripts() {
+    let mut world = World::new();
+    let mut sandbox = Sandbox::new();
+    sandbox.submit_code("owl".to_string(), "console.log(game.getDayPhase());".to_string()).unwrap();
+
+    let mut phases = Vec::new();
+    for _ in 0..4 {
+        let result = sandbox.execute_player("owl", &world.player_snapshot("owl")).unwrap();
+        phases.extend(result.logs);
+        for _ in 0..25 {
+            world.advance_tick();
+        }
+    }
+    assert_eq!(phases, vec!["Dawn", "Day", "Dusk", "Night"]);
+    assert_eq!(world.player_snapshot("owl")["visibility_radius"], 10);
+}
+
+fn weather(event_type: WeatherEventType, zone_id: &str, start_tick: u64, duration_ticks: u64, magnitude: f32) -> WeatherEvent {
+    WeatherEvent {
+        event_type,
+        affected_zone_id: zone_id.to_string(),
+        start_tick,
+        duration_ticks,
+        magnitude,
+    }
+}
+
+#[test]
+fn test_fog_reduces_visibility_until_it_expires() {
+    let mut world = World::new();
+    let zone_id = world.generate_player_zone("foggy").unwrap();
+    let other_zone = world.generate_player_zone("clear").unwrap();
+
+    world.schedule_weather(weather(WeatherEventType::Fog, &zone_id, 2, 3, 0.5)).unwrap();
+    assert!(world.schedule_weather(weather(WeatherEventType::Fog, "nowhere", 2, 3, 0.5)).is_err());
+    assert!(world.schedule_weather(weather(WeatherEventType::Fog, &zone_id, 2, 0, 0.5)).is_err());
+
+    let mut radii = Vec::new();
+    for _ in 0..6 {
+        radii.push(world.player_snapshot("foggy")["visibility_radius"].as_u64().unwrap());
+        assert_eq!(world.visibility_radius(&other_zone), 10);
+        world.advance_tick();
+    }
+    // Active on ticks 2, 3 and 4, then removed
+    assert_eq!(radii, vec![10, 10, 5, 5, 5, 10]);
+    assert!(world.weather_events().is_empty());
+}
+
+#[test]
+fn test_rain_and_storm_effects() {
+    let mut world = World::new();
+    let zone_id = world.generate_player_zone("stormy").unwrap();
+    world.schedule_weather(weather(WeatherEventType::Rain, &zone_id, 0, 1, 0.5)).unwrap();
+    assert_eq!(world.movement_cost(&zone_id, SurfaceType::Swamp), Some(6));
+    assert_eq!(world.movement_cost(&zone_id, SurfaceType::Plain), Some(2));
+
+    // Place a worker on an outdoor tile and one sheltered by an obstacle
+    let (outdoor, sheltered) = {
+        let zone = world.with_zone_read(&zone_id, Zone::clone).unwrap();
+        let is_obstacle = |x: usize, y: usize| zone.tiles[y][x].surface_type == SurfaceType::Obstacle;
+        let neighbours = |x: usize, y: usize| [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)];
+        let inner = || (1..ZONE_SIZE - 1).flat_map(|y| (1..ZONE_SIZE - 1).map(move |x| (x, y)));
+        let outdoor = inner().find(|&(x, y)| !is_obstacle(x, y) && !neighbours(x, y).iter().any(|&(nx, ny)| is_obstacle(nx, ny))).unwrap();
+        let sheltered = inner().find(|&(x, y)| !is_obstacle(x, y) && neighbours(x, y).iter().any(|&(nx, ny)| is_obstacle(nx, ny))).unwrap();
+        (outdoor, sheltered)
+    };
+    for (id, (x, y)) in [(1, outdoor), (2, sheltered)] {
+        world.with_zone_write(&zone_id, |zone| zone.entities.push(EntityRef {
+            id,
+            kind: "worker".to_string(),
+            owner: Some("stormy".to_string()),
+            x,
+            y,
+            hits: DEFAULT_ENTITY_HITS,
+            can_swim: false,
+            can_fly: false,
+        })).unwrap();
+    }
+
+    world.schedule_weather(weather(WeatherEventType::Storm, &zone_id, 1, 2, 1.0)).unwrap();
+    world.advance_tick();
+    world.advance_tick();
+    world.advance_tick();
+
+    let entities = &world.with_zone_read(&zone_id, Zone::clone).unwrap().entities;
+    assert_eq!(entities[0].hits, DEFAULT_ENTITY_HITS - 2 * STORM_DAMAGE);
+    assert_eq!(entities[1].hits, DEFAULT_ENTITY_HITS);
+    assert_eq!(world.movement_cost(&zone_id, SurfaceType::Swamp), Some(4));
+    assert!(world.weather_events().is_empty());
+}
+
+/// World seeded with `seed` where two players play the same scripted game under a storm
+fn scripted_world(seed: u64, ticks: u64) -> Vec<u64> {
+    let mut world = World::with_seed(seed);
+    let players = ["alice", "bob"];
+    for player in players {
+        let zone_id = world.spawn_player(player).unwrap();
+        world.schedule_weather(weather(WeatherEventType::Storm, &zone_id, 0, ticks, 0.05)).unwrap();
+    }
+
+    let mut hashes = Vec::new();
+    for tick in 1..=ticks {
+        if world.is_script_tick() {
+            let script_tick = world.get_script_tick() as usize;
+            for player in players {
+                let zone_id = format!("player_{}_zone", player);
+                let commands = [
+                    command("moveTo", &format!("{}:2", zone_id), serde_json::json!({"position": {"x": 20 + script_tick % 8, "y": 24}})),
+                    command("harvest", &format!("{}:2", zone_id), serde_json::json!({})),
+                    command("produceUnit", &format!("{}:1", zone_id), serde_json::json!({"unitType": "worker"})),
+                ];
+                world.apply_commands(player, &commands);
+            }
+        }
+        world.advance_tick();
+        if tick % 100 == 0 {
+            hashes.push(world.state_hash());
+        }
+    }
+    hashes
+}
+
+#[test]
+fn test_seeded_worlds_stay_in_lockstep() {
+    let first = scripted_world(42, 1000);
+    assert_eq!(first.len(), 10);
+    assert_eq!(first, scripted_world(42, 1000));
+    assert_ne!(first, scripted_world(7, 1000));
+
+    assert!(World::with_seed(42).is_deterministic());
+    assert!(!World::new().is_deterministic());
+    assert_eq!(World::with_seed(1).state_hash(), World::with_seed(2).state_hash());
+}
+
+#[test]
+fn test_zone_generation_fails_after_max_zones() {
+    let mut world = World::with_config(WorldConfig { width: 4, height: 2, max_zones: 2, ..WorldConfig::default() });
+    let first = world.generate_player_zone("first").unwrap();
+    world.generate_player_zone("second").unwrap();
+
+    let err = world.generate_player_zone("third").unwrap_err();
+    assert!(err.contains("World is full"), "{}", err);
+    assert_eq!(world.get_zone_ids().len(), 2);
+    // Regenerating an existing zone does not add one
+    assert_eq!(world.generate_player_zone("first").unwrap(), first);
+
+    world.set_zone_position(&first, 3, 1).unwrap();
+    assert_eq!(world.zone_position(&first), Some((3, 1)));
+    assert!(world.set_zone_position(&first, 4, 0).is_err());
+    assert!(world.set_zone_position(&first, 0, 2).is_err());
+    assert!(world.set_zone_position("player_second_zone", 3, 1).unwrap_err().contains("taken"));
+}
+
+#[test]
+fn test_zone_capture_requires_majority_presence() {
+    let mut world = World::new();
+    let zone_id = world.generate_player_zone("contested").unwrap();
+    let worker = |id: u32, owner: &str| EntityRef {
+        id,
+        kind: "worker".to_string(),
+        owner: Some(owner.to_string()),
+        x: 0,
+        y: 0,
+        hits: DEFAULT_ENTITY_HITS,
+        can_swim: false,
+        can_fly: false,
+    };
+
+    assert_eq!(world.capture_zone(&zone_id, "alice"), Err(CaptureError::InsufficientPresence));
+    assert!(matches!(world.capture_zone("nowhere", "alice"), Err(CaptureError::ZoneNotFound(_))));
+
+    world.with_zone_write(&zone_id, |zone| zone.entities.extend([worker(1, "alice"), worker(2, "bob"), worker(3, "bob")])).unwrap();
+    assert_eq!(world.capture_zone(&zone_id, "alice"), Err(CaptureError::Contested));
+    assert_eq!(world.zone_owner(&zone_id), None);
+    assert!(world.stockpile("alice").is_empty());
+
+    world.with_zone_write(&zone_id, |zone| zone.entities.extend([worker(4, "alice"), worker(5, "alice")])).unwrap();
+    world.capture_zone(&zone_id, "alice").unwrap();
+    assert_eq!(world.zone_owner(&zone_id).as_deref(), Some("alice"));
+    assert_eq!(world.stockpile("alice")[&ResourceType::Minerals], 100);
+    assert_eq!(world.stockpile("alice")[&ResourceType::Gas], 50);
+    assert_eq!(world.player_snapshot("alice")["stockpile"]["minerals"], 100);
+
+    // Capturing an owned zone again awards nothing
+    world.capture_zone(&zone_id, "alice").unwrap();
+    assert_eq!(world.stockpile("alice")[&ResourceType::Minerals], 100);
+    assert_eq!(world.capture_zone(&zone_id, "bob"), Err(CaptureError::Contested));
+}
+
+#[test]
+fn test_zone_from_map_template() {
+    let maps_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/maps");
+    let mut world = World::with_config(WorldConfig { maps_dir, ..WorldConfig::default() });
+
+    let zone_id = world.add_zone_from_template("outpost").unwrap();
+    assert_eq!(zone_id, "map_outpost");
+    let zone = world.with_zone_read(&zone_id, Zone::clone).unwrap();
+    assert_eq!((zone.width, zone.height), (10, 8));
+    assert_eq!(zone.to_compact().tiles, vec![
+        "OOOOPOOOOO",
+        "OPPPPPPPSO",
+        "OPPSSPPPPO",
+        "PPPPWWPPPP",
+        "OPPPWWPPPO",
+        "OPPPPPPPPO",
+        "OPSSPPPPPO",
+        "OOOOOPOOOO",
+    ]);
+    assert_eq!(zone.get_tile(4, 3).unwrap().surface_type, SurfaceType::Water);
+    assert_eq!(zone.exits.len(), 4);
+    assert_eq!(zone.resources[0].amount, 500);
+    assert_eq!((zone.entities[0].kind.as_str(), zone.entities[0].x, zone.entities[0].y), ("tower", 2, 5));
+
+    let err = world.add_zone_from_template("interior_exit").unwrap_err();
+    assert!(err.ends_with("interior_exit.json: row 5, column 5: North exit must be on the North edge of the zone"), "{}", err);
+    assert!(world.add_zone_from_template("missing").unwrap_err().contains("not found"));
+    assert_eq!(world.get_zone_ids(), vec![zone_id]);
+}
+
+#[test]
+fn test_zone_export_round_trip_and_checksum() {
+    let mut world = World::new();
+    let zone_id = world.generate_player_zone("cartographer").unwrap();
+    world.with_zone_write(&zone_id, |zone| zone.resources.push(ResourceDeposit { x: 1, y: 1, amount: 321 })).unwrap();
+    let original = world.with_zone_read(&zone_id, Zone::clone).unwrap();
+    let document = serde_json::to_string(&original.export(1_700_000_000)).unwrap();
+
+    // Changing one tile code breaks the checksum
+    let row = &original.to_compact().tiles[1];
+    let swapped = if row.starts_with('P') { row.replacen('P', "S", 1) } else { row.replacen(&row[..1], "P", 1) };
+    let corrupted = document.replacen(row.as_str(), &swapped, 1);
+    assert_ne!(corrupted, document);
+    let err = world.import_zone(&ZoneExport::parse(corrupted.as_bytes()).unwrap()).unwrap_err();
+    assert!(err.contains("checksum"), "{}", err);
+
+    // Documents from a newer server are refused even with a valid checksum
+    let mut newer = ZoneExport::parse(document.as_bytes()).unwrap();
+    newer.version = ZONE_EXPORT_VERSION + 1;
+    newer.checksum = newer.compute_checksum();
+    assert!(world.import_zone(&newer).unwrap_err().contains("newer"));
+
+    // The valid copy is installed under a new ID, tile for tile
+    let imported_id = world.import_zone(&ZoneExport::parse(document.as_bytes()).unwrap()).unwrap();
+    assert_ne!(imported_id, zone_id);
+    let imported = world.with_zone_read(&imported_id, Zone::clone).unwrap();
+    assert_eq!((imported.width, imported.height), (original.width, original.height));
+    for (imported_row, original_row) in imported.tiles.iter().zip(&original.tiles) {
+        assert_eq!(imported_row, original_row);
+    }
+    assert_eq!(imported.exits, original.exits);
+    assert_eq!(imported.resources, original.resources);
+    assert!(imported.entities.is_empty() && imported.owner.is_none());
+    assert_eq!(world.with_zone_read(&zone_id, Zone::clone).unwrap(), original);
+    assert_eq!(world.get_zone_ids().len(), 2);
+}
+
+#[test]
+fn test_team_shares_pool_and_capture_presence() {
+    let mut world = World::new();
+    let zone_id = world.generate_player_zone("border").unwrap();
+    let worker = |id: u32, owner: &str| EntityRef {
+        id,
+        kind: "worker".to_string(),
+        owner: Some(owner.to_string()),
+        x: 0,
+        y: 0,
+        hits: DEFAULT_ENTITY_HITS,
+        can_swim: false,
+        can_fly: false,
+    };
+    world.with_zone_write(&zone_id, |zone| {
+        zone.entities.extend([worker(1, "alice"), worker(2, "alice"), worker(3, "bob"), worker(4, "bob")]);
+        zone.entities.extend([worker(5, "carol"), worker(6, "carol"), worker(7, "carol")]);
+    }).unwrap();
+
+    // Alone, alice and bob are each outnumbered by carol
+    assert_eq!(world.capture_zone(&zone_id, "alice"), Err(CaptureError::Contested));
+    world.deposit_resources("alice", ResourceType::Minerals, 30);
+
+    let team = Uuid::new_v4();
+    world.set_team_members(team, vec!["alice".to_string(), "bob".to_string()]);
+    assert_eq!(world.team_of("bob"), Some(team));
+    world.deposit_resources("bob", ResourceType::Minerals, 20);
+    assert_eq!(world.stockpile("alice")[&ResourceType::Minerals], 50);
+    assert_eq!(world.stockpile("bob"), world.team_pool(&team));
+
+    // Together they outnumber carol
+    world.capture_zone(&zone_id, "bob").unwrap();
+    assert_eq!(world.zone_owner(&zone_id).as_deref(), Some("bob"));
+    assert_eq!(world.team_pool(&team)[&ResourceType::Minerals], 150);
+    assert_eq!(world.team_zone_count(&team), 1);
+    assert_eq!(world.capture_zone(&zone_id, "carol"), Err(CaptureError::Contested));
+
+    // A teammate re-capturing changes nothing
+    world.capture_zone(&zone_id, "alice").unwrap();
+    assert_eq!(world.zone_owner(&zone_id).as_deref(), Some("bob"));
+    assert_eq!(world.team_pool(&team)[&ResourceType::Minerals], 150);
+
+    let snapshot = world.player_snapshot("alice");
+    assert_eq!(snapshot["team"]["teammates"], serde_json::json!(["bob"]));
+    assert_eq!(snapshot["allied_units"].as_array().unwrap().len(), 2);
+    assert_eq!(snapshot["allied_units"][0]["owner"], "bob");
+}
+
+#[tokio::test]
+async fn test_scripts_run_every_script_tick_interval() {
+    let world = RwLock::new(World::with_config(WorldConfig { script_tick_interval: 5, ..WorldConfig::default() }));
+    let mut engine = Sandbox::new();
+    engine.submit_code("ticker".to_string(), "console.log(game.tick);".to_string()).unwrap();
+    let handle = ScriptEngineHandle::with_threads(engine, 1);
+
+    let mut executions = Vec::new();
+    for _ in 0..20 {
+        let tick = world.read().await.get_tick();
+        if run_simulation_tick(&world, &handle).await > 0 {
+            executions.push(tick);
+        }
+    }
+
+    assert_eq!(executions, vec![0, 5, 10, 15]);
+    let world = world.read().await;
+    assert_eq!(world.get_tick(), 20);
+    assert_eq!(world.get_script_tick(), 4);
+    assert_eq!(world.player_snapshot("ticker")["tick"], 4);
+}
+
+#[tokio::test]
+async fn test_multi_tile_move_continues_between_script_ticks() {
+    let mut world = World::with_config(WorldConfig { script_tick_interval: 10, ..WorldConfig::default() });
+    let zone_id = world.generate_player_zone("walker").unwrap();
+    let start = walkable_tile(&world, &zone_id, 0);
+    world.with_zone_write(&zone_id, |zone| zone.entities.push(entity(1, "worker", "walker", start))).unwrap();
+
+    // A destination a few tiles away, reachable before the next script tick
+    let zone = world.with_zone_read(&zone_id, Zone::clone).unwrap();
+    let mobility = zone.entities[0].mobility();
+    let (target, steps) = (0..ZONE_SIZE * ZONE_SIZE)
+        .map(|i| (i % ZONE_SIZE, i / ZONE_SIZE))
+        .filter_map(|tile| find_path(&zone, start, tile, mobility).map(|(path, _)| (tile, path.len() - 1)))
+        .find(|(_, steps)| (3..10).contains(steps))
+        .expect("a tile 3 to 9 steps away");
+
+    let mut engine = Sandbox::new();
+    let code = format!("const unit = game.getMyUnits()[0]; if (unit.action !== 'moving') unit.moveTo({{x: {}, y: {}}});", target.0, target.1);
+    engine.submit_code("walker".to_string(), code).unwrap();
+    let handle = ScriptEngineHandle::with_threads(engine, 1);
+    let world = RwLock::new(world);
+
+    assert_eq!(run_simulation_tick(&world, &handle).await, 1);
+    // One tile per simulation tick, the first on the script tick itself
+    for _ in 1..steps {
+        assert!(world.read().await.is_moving(&zone_id, 1));
+        assert_eq!(run_simulation_tick(&world, &handle).await, 0);
+    }
+
+    let world = world.read().await;
+    let unit = &world.with_zone_read(&zone_id, Zone::clone).unwrap().entities[0];
+    assert_eq!((unit.x, unit.y), target);
+    assert!(!world.is_moving(&zone_id, 1));
+    assert_eq!(world.get_script_tick(), 0);
+}
+
+fn command(action: &str, actor: &str, params: serde_json::Value) -> BotCommand {
+    BotCommand { action: action.to_string(), actor: Some(actor.to_string()), params, player_id: None }
+}
+
+#[test]
+fn test_stats_read_model_follows_world_events() {
+    let mut world = World::new();
+    let zone_id = world.generate_player_zone("alice").unwrap();
+    let (x, y) = walkable_tile(&world, &zone_id, 0);
+    world.with_zone_write(&zone_id, |zone| {
+        zone.entities.push(entity(101, "worker", "alice", (x, y)));
+        zone.entities.push(EntityRef { hits: ATTACK_DAMAGE * 2, ..entity(102, "soldier", "bob", (x + 1, y)) });
+        zone.resources.push(ResourceDeposit { x, y, amount: 500 });
+    }).unwrap();
+    let worker = format!("{}:101", zone_id);
+    let soldier = format!("{}:102", zone_id);
+
+    // Harvest on tick 1, destroy Bob's only unit on tick 3, then two quiet ticks
+    assert!(world.apply_commands("alice", &[command("harvest", &worker, serde_json::json!({}))]).is_empty());
+    world.advance_tick();
+    for _ in 0..2 {
+        assert!(world.apply_commands("alice", &[command("attack", &worker, serde_json::json!({"target": soldier}))]).is_empty());
+        world.advance_tick();
+    }
+    world.advance_tick();
+    world.advance_tick();
+
+    let mut stats = StatsReadModel::new();
+    stats.apply(&world.drain_stats());
+    assert_eq!(stats.tick, 5);
+
+    let alice = stats.player("alice").unwrap();
+    assert_eq!(alice.units_killed, 1);
+    assert_eq!(alice.units_lost, 0);
+    assert_eq!(alice.resources_collected, u64::from(HARVEST_AMOUNT));
+    assert_eq!(alice.ticks_survived, 5);
+    assert!(!alice.defeated);
+
+    let bob = stats.player("bob").unwrap();
+    assert_eq!(bob.units_killed, 0);
+    assert_eq!(bob.units_lost, 1);
+    assert_eq!(bob.defeats, 1);
+    assert!(bob.defeated);
+    assert_eq!(bob.ticks_survived, 0);
+
+    let zone = stats.zone(&zone_id).unwrap();
+    assert_eq!(zone.entity_count, world.with_zone_read(&zone_id, |zone| zone.entities.len()).unwrap());
+    assert_eq!(zone.ownership_history, vec![OwnershipChange { tick: 5, owner: None }]);
+
+    // Later batches add up, and an ownership change is appended to the history
+    world.with_zone_write(&zone_id, |zone| zone.owner = Some("carol".to_string())).unwrap();
+    world.advance_tick();
+    stats.apply(&world.drain_stats());
+    assert_eq!(stats.player("alice").unwrap().ticks_survived, 6);
+    assert_eq!(stats.player("carol").unwrap().ticks_survived, 1);
+    assert_eq!(stats.zone(&zone_id).unwrap().ownership_history.last(),
+        Some(&OwnershipChange { tick: 6, owner: Some("carol".to_string()) }));
+}
+
+#[test]
+fn test_action_log_replays_to_the_same_world() {
+    let mut world = World::new();
+    let zone_id = world.generate_player_zone("alice").unwrap();
+    let (x, y) = walkable_tile(&world, &zone_id, 0);
+    world.with_zone_write(&zone_id, |zone| {
+        zone.entities.push(entity(101, "worker", "alice", (x, y)));
+        zone.resources.push(ResourceDeposit { x, y, amount: HARVEST_AMOUNT * 4 });
+    }).unwrap();
+    let worker = format!("{}:101", zone_id);
+
+    let path = std::env::temp_dir().join(format!("geekcraft_actions_{}.jsonl", Uuid::new_v4()));
+    let checkpoint_path = path.with_extension("checkpoint.json");
+    let log = ActionLog::open(&path).unwrap();
+    log.checkpoint(&world).save(&checkpoint_path).unwrap();
+    world.set_action_log(log);
+
+    // 4 harvests, 5 moves and a capture
+    for _ in 0..4 {
+        assert!(world.apply_commands("alice", &[command("harvest", &worker, serde_json::json!({}))]).is_empty());
+        world.advance_tick();
+    }
+    let mut from = 0;
+    for _ in 0..5 {
+        let (tx, ty) = walkable_tile(&world, &zone_id, from + 1);
+        world.move_entity(&zone_id, 101, tx, ty).unwrap();
+        from = ty * ZONE_SIZE + tx;
+    }
+    world.capture_zone(&zone_id, "alice").unwrap();
+
+    let entries = world.action_log().unwrap().entries().to_vec();
+    assert_eq!(entries.len(), 10);
+    assert!(entries.iter().all(|entry| entry.applied));
+    assert!(matches!(entries[0].action, GameAction::ResourceHarvested { amount, .. } if amount == HARVEST_AMOUNT));
+    assert!(matches!(&entries[9].action, GameAction::ZoneCaptured { player_id, .. } if player_id == "alice"));
+    // Dropping the log writes what is left in its buffer
+    drop(world.take_action_log());
+
+    // An entry appended but never applied is not replayed
+    let mut log = ActionLog::open(&path).unwrap();
+    assert_eq!(log.entries(), &entries[..]);
+    let (x, y) = walkable_tile(&world, &zone_id, 0);
+    assert_eq!(log.append(world.get_tick(), GameAction::EntityMoved { zone_id: zone_id.clone(), entity_id: 101, x, y }), 10);
+
+    let checkpoint = Checkpoint::load(&checkpoint_path).unwrap();
+    let replayed = log.replay_from(&checkpoint).unwrap();
+    assert_eq!(replayed.state_hash(), world.state_hash());
+    assert_eq!(replayed.zone_owner(&zone_id).as_deref(), Some("alice"));
+    assert_eq!(replayed.stockpile("alice"), world.stockpile("alice"));
+    assert!(replayed.with_zone_read(&zone_id, |zone| zone.resources.iter().all(|deposit| (deposit.x, deposit.y) != (x, y))).unwrap());
+
+    // Replaying from a later checkpoint only applies what follows it
+    let later = log.checkpoint(&replayed);
+    assert_eq!(log.replay_from(&later).unwrap().state_hash(), world.state_hash());
+
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&checkpoint_path);
+}
+
+#[test]
+fn test_history_rebuilds_past_ticks_as_they_were() {
+    let mut world = World::with_config(WorldConfig { history_keyframe_interval: 10, ..WorldConfig::default() });
+    let zone_id = world.generate_player_zone("alice").unwrap();
+    let (x, y) = walkable_tile(&world, &zone_id, 0);
+    world.with_zone_write(&zone_id, |zone| {
+        zone.entities.push(entity(101, "worker", "alice", (x, y)));
+        zone.resources.push(ResourceDeposit { x, y, amount: HARVEST_AMOUNT * 20 });
+    }).unwrap();
+    let worker = format!("{}:101", zone_id);
+
+    // Harvests, then a move every tick, then a capture; the state is captured live at 3 ticks
+    let mut live = BTreeMap::new();
+    let mut from = 0;
+    for tick in 1..=40 {
+        world.advance_tick();
+        assert_eq!(world.get_tick(), tick);
+        if tick <= 12 {
+            assert!(world.apply_commands("alice", &[command("harvest", &worker, serde_json::json!({}))]).is_empty());
+        } else if tick <= 30 {
+            let (tx, ty) = walkable_tile(&world, &zone_id, from + 1);
+            world.move_entity(&zone_id, 101, tx, ty).unwrap();
+            from = ty * ZONE_SIZE + tx;
+        } else if tick == 34 {
+            world.capture_zone(&zone_id, "alice").unwrap();
+        }
+        if [7, 23, 35].contains(&tick) {
+            live.insert(tick, (world.state_hash(), world.zone_view("alice", &zone_id).unwrap(), world.player_snapshot("alice")));
+        }
+    }
+
+    for (tick, (hash, zone, snapshot)) in &live {
+        let past = world.state_at(*tick).unwrap();
+        assert_eq!(past.get_tick(), *tick);
+        assert_eq!(past.state_hash(), *hash, "tick {}", tick);
+        assert_eq!(past.zone_view("alice", &zone_id).as_ref(), Some(zone));
+        let past_snapshot = past.player_snapshot("alice");
+        for key in ["tick", "day_phase", "stockpile", "resources", "enemy_units"] {
+            assert_eq!(past_snapshot[key], snapshot[key], "{} at tick {}", key, tick);
+        }
+    }
+    assert_eq!(world.state_at(35).unwrap().zone_owner(&zone_id).as_deref(), Some("alice"));
+    assert_eq!(world.state_at(33).unwrap().zone_owner(&zone_id), None);
+    assert!(matches!(world.state_at(41), Err(HistoryError::Future { latest: 40 })));
+
+    // Shrinking the retention window prunes the oldest keyframes
+    world.set_config(WorldConfig { history_retention_ticks: 15, ..world.config().clone() });
+    for _ in 0..10 {
+        world.advance_tick();
+    }
+    assert_eq!(world.history().earliest_tick(), Some(30));
+    assert_eq!(world.state_at(23).unwrap_err(), HistoryError::Expired { earliest: Some(30) });
+    assert_eq!(world.state_at(35).unwrap().state_hash(), live[&35].0);
+}
+
+#[test]
+fn test_scripts_see_events_since_their_last_run() {
+    let mut world = World::with_config(WorldConfig { script_tick_interval: 10, ..WorldConfig::default() });
+    let zone_id = world.generate_player_zone("alice").unwrap();
+    let (x, y) = walkable_tile(&world, &zone_id, 0);
+    world.with_zone_write(&zone_id, |zone| {
+        zone.entities.push(entity(1, "worker", "alice", (x, y)));
+        zone.entities.push(entity(2, "soldier", "bob", (x + 1, y)));
+        zone.entities.push(entity(3, "worker", "carol", (ZONE_SIZE - 1, ZONE_SIZE - 1)));
+        zone.resources.push(ResourceDeposit { x, y, amount: 500 });
+    }).unwrap();
+    let worker = format!("{}:1", zone_id);
+    let soldier = format!("{}:2", zone_id);
+
+    // Harvest on tick 1, then attack until the soldier is destroyed on tick 11
+    assert!(world.apply_commands("alice", &[command("harvest", &worker, serde_json::json!({}))]).is_empty());
+    world.advance_tick();
+    for _ in 0..DEFAULT_ENTITY_HITS / ATTACK_DAMAGE {
+        world.apply_commands("alice", &[command("attack", &worker, serde_json::json!({"target": soldier}))]);
+        world.advance_tick();
+    }
+    assert_eq!(world.stockpile("alice")[&ResourceType::Minerals], HARVEST_AMOUNT);
+    assert!(world.with_zone_read(&zone_id, |zone| zone.entities.iter().all(|entity| entity.id != 2)).unwrap());
+
+    let types = |events: &[geekcraft::game::events::GameEvent]| -> Vec<(String, u64)> {
+        events.iter()
+            .map(|event| (serde_json::to_value(event).unwrap()["type"].as_str().unwrap().to_string(), event.tick))
+            .collect()
+    };
+    assert_eq!(types(&world.events_visible_to("alice", 0, 100)), vec![
+        ("ResourceCollected".to_string(), 1),
+        ("UnitDestroyed".to_string(), 11),
+    ]);
+    // Too far away to see the fight
+    assert!(world.events_visible_to("carol", 0, 100).is_empty());
+
+    // At script tick 2 (simulation tick 20) the script only sees ticks 11 to 20
+    while world.get_tick() < 20 {
+        world.advance_tick();
+    }
+    let bundle = ScriptBundle::single("console.log(JSON.stringify(game.events().map(e => [e.type, e.tick, e.unit_id])));".to_string()).unwrap();
+    let runtime = create_runtime(ScriptLanguage::JavaScript, ScriptLimits::default());
+    let result = runtime.execute_tick(&bundle, &world.player_snapshot("alice"));
+    assert_eq!(result.logs, vec![r#"[["UnitDestroyed",11,2]]"#.to_string()]);
+}
+
+fn market_world() -> World {
+    let mut world = World::with_config(WorldConfig {
+        market_match_interval_ticks: 1,
+        market_order_ttl_ticks: 10,
+        ..WorldConfig::default()
+    });
+    world.deposit_resources("alice", ResourceType::Minerals, 100);
+    world.deposit_resources("bob", ResourceType::Credits, 1000);
+    world.deposit_resources("carol", ResourceType::Minerals, 100);
+    world
+}
+
+fn trades(world: &mut World) -> Vec<(String, String, u32, u32)> {
+    world.drain_events().into_iter()
+        .filter_map(|event| match event {
+            WorldEvent::TradeExecuted { trade, .. } => Some((trade.buyer, trade.seller, trade.amount, trade.price)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_market_order_fully_filled_at_sell_price() {
+    let mut world = market_world();
+    world.place_order("alice", OrderSide::Sell, ResourceType::Minerals, 50, 3).unwrap();
+    world.place_order("bob", OrderSide::Buy, ResourceType::Minerals, 50, 4).unwrap();
+    world.advance_tick();
+
+    assert_eq!(trades(&mut world), vec![("bob".to_string(), "alice".to_string(), 50, 3)]);
+    assert!(world.market_orders().is_empty());
+    assert_eq!(world.stockpile("alice")[&ResourceType::Minerals], 50);
+    assert_eq!(world.stockpile("alice")[&ResourceType::Credits], 150);
+    assert_eq!(world.stockpile("bob")[&ResourceType::Minerals], 50);
+    assert_eq!(world.stockpile("bob")[&ResourceType::Credits], 850);
+}
+
+#[test]
+fn test_market_order_partially_filled_by_two_sellers() {
+    let mut world = market_world();
+    world.place_order("carol", OrderSide::Sell, ResourceType::Minerals, 60, 5).unwrap();
+    world.place_order("alice", OrderSide::Sell, ResourceType::Minerals, 60, 4).unwrap();
+    world.place_order("bob", OrderSide::Buy, ResourceType::Minerals, 100, 5).unwrap();
+    world.advance_tick();
+
+    // Cheapest first: all of alice's order, then part of carol's
+    assert_eq!(trades(&mut world), vec![
+        ("bob".to_string(), "alice".to_string(), 60, 4),
+        ("bob".to_string(), "carol".to_string(), 40, 5),
+    ]);
+    assert_eq!(world.stockpile("bob")[&ResourceType::Minerals], 100);
+    assert_eq!(world.stockpile("bob")[&ResourceType::Credits], 1000 - 240 - 200);
+    let open = world.market_orders();
+    assert_eq!(open.len(), 1);
+    assert_eq!((open[0].player_id.as_str(), open[0].amount, open[0].remaining), ("carol", 60, 20));
+
+    // The rest of carol's order stays open for later buyers, and only she can cancel it
+    let order_id = open[0].id;
+    assert!(world.cancel_order("bob", order_id).is_err());
+    world.cancel_order("carol", order_id).unwrap();
+    assert!(world.market_orders().is_empty());
+}
+
+#[test]
+fn test_market_order_expires() {
+    let mut world = market_world();
+    let order = world.place_order("alice", OrderSide::Sell, ResourceType::Minerals, 10, 50).unwrap();
+    assert_eq!(order.expires_tick, 10);
+    // Too expensive for bob
+    world.place_order("bob", OrderSide::Buy, ResourceType::Minerals, 10, 20).unwrap();
+
+    for _ in 0..9 {
+        world.advance_tick();
+    }
+    assert_eq!(world.market_orders().len(), 2);
+    world.advance_tick();
+    assert!(world.market_orders().is_empty());
+    assert!(trades(&mut world).is_empty());
+    assert_eq!(world.stockpile("alice")[&ResourceType::Minerals], 100);
+}
+
+#[test]
+fn test_market_ledgers_never_go_negative() {
+    let mut world = market_world();
+    // Orders need the resource or credits when placed
+    assert!(world.place_order("alice", OrderSide::Sell, ResourceType::Minerals, 101, 1).is_err());
+    assert!(world.place_order("bob", OrderSide::Buy, ResourceType::Minerals, 1001, 1).is_err());
+    assert!(world.place_order("bob", OrderSide::Sell, ResourceType::Credits, 1, 1).is_err());
+
+    // Nothing is escrowed: spending it before the match cancels the order
+    world.place_order("alice", OrderSide::Sell, ResourceType::Minerals, 80, 2).unwrap();
+    world.place_order("carol", OrderSide::Sell, ResourceType::Minerals, 50, 3).unwrap();
+    world.place_order("bob", OrderSide::Buy, ResourceType::Minerals, 100, 10).unwrap();
+    world.withdraw_resources("alice", ResourceType::Minerals, 50).unwrap();
+    world.withdraw_resources("bob", ResourceType::Credits, 900).unwrap();
+    world.advance_tick();
+
+    // Alice's order is cancelled, then bob can only afford carol's 50 at 3 with 100 credits
+    // short of 150: his order is cancelled too and nobody trades
+    assert!(trades(&mut world).is_empty());
+    assert!(world.market_orders().iter().all(|order| order.player_id == "carol"));
+    assert_eq!(world.stockpile("alice")[&ResourceType::Minerals], 50);
+    assert_eq!(world.stockpile("bob")[&ResourceType::Credits], 100);
+    assert_eq!(world.stockpile("carol")[&ResourceType::Minerals], 100);
+    assert!(world.withdraw_resources("bob", ResourceType::Credits, 101).is_err());
+}
+
+#[test]
+fn test_alliance_shares_visibility_and_blocks_friendly_fire() {
+    let mut world = World::new();
+    let zone_id = world.generate_player_zone("alice").unwrap();
+    let (x, y) = walkable_tile(&world, &zone_id, 0);
+    world.with_zone_write(&zone_id, |zone| {
+        zone.owner = Some("alice".to_string());
+        zone.entities.push(entity(1, "worker", "alice", (x, y)));
+        zone.entities.push(entity(2, "soldier", "bob", (x + 1, y)));
+        zone.entities.push(entity(3, "worker", "carol", (ZONE_SIZE - 1, ZONE_SIZE - 1)));
+    }).unwrap();
+    world.advance_tick();
+    world.record_event(&zone_id, vec!["bob".to_string()], GameEventKind::UnitMoved { unit_id: 2, x: x + 1, y });
+    let alice_worker = format!("{}:1", zone_id);
+    let carol_worker = format!("{}:3", zone_id);
+
+    // Carol is too far away to see bob move, and only sees who owns alice's zone
+    assert!(world.events_visible_to("carol", 0, 100).is_empty());
+    assert_eq!(world.map_for("carol")[0].units, None);
+
+    let alliance = Uuid::new_v4();
+    world.set_alliance_members(alliance, vec!["alice".to_string(), "carol".to_string()]);
+    assert_eq!(world.events_visible_to("carol", 0, 100).len(), 1);
+    assert_eq!((world.map_for("carol")[0].units, world.map_for("carol")[0].structures), (Some(3), Some(0)));
+    assert_eq!(world.player_snapshot("carol")["allies"], serde_json::json!(["alice"]));
+    let bundle = ScriptBundle::single("console.log(JSON.stringify(game.allies()));".to_string()).unwrap();
+    let runtime = create_runtime(ScriptLanguage::JavaScript, ScriptLimits::default());
+    assert_eq!(runtime.execute_tick(&bundle, &world.player_snapshot("alice")).logs, vec![r#"["carol"]"#.to_string()]);
+
+    // Allies cannot attack each other; enemies still can
+    let errors = world.apply_commands("carol", &[command("attack", &carol_worker, serde_json::json!({"target": alice_worker}))]);
+    assert_eq!(errors, vec![format!("attack failed: Friendly fire: target {} belongs to your ally alice", alice_worker)]);
+    let soldier = format!("{}:2", zone_id);
+    assert!(world.apply_commands("alice", &[command("attack", &alice_worker, serde_json::json!({"target": soldier}))]).is_empty());
+
+    // Leaving takes effect at the end of the tick
+    world.set_alliance_members(alliance, vec!["alice".to_string()]);
+    assert!(world.are_allied("alice", "carol"));
+    world.advance_tick();
+    assert!(!world.are_allied("alice", "carol"));
+    assert!(world.allies_of("alice").is_empty());
+    assert!(world.apply_commands("carol", &[command("attack", &carol_worker, serde_json::json!({"target": alice_worker}))]).is_empty());
+}
+
+#[tokio::test]
+async fn test_dry_run_leaves_world_unchanged() {
+    let world = Arc::new(RwLock::new(World::new()));
+    let engine = ScriptEngineHandle::default();
+    let zone_id = {
+        let mut world = world.write().await;
+        let zone_id = world.generate_player_zone("dry_runner").unwrap();
+        world.with_zone_write(&zone_id, |zone| zone.entities.push(EntityRef {
+            id: 1,
+            kind: "worker".to_string(),
+            owner: Some("dry_runner".to_string()),
+            x: 2,
+            y: 3,
+            hits: DEFAULT_ENTITY_HITS,
+            can_swim: false,
+            can_fly: false,
+        })).unwrap();
+        zone_id
+    };
+
+    // The game loop records the world at every script tick
+    run_simulation_tick(&world, &engine).await;
+    let snapshot = world.read().await.replay().at(0).cloned().unwrap();
+    assert_eq!(world.read().await.replay().range(), Some((0, 0)));
+
+    let code = "// @api v1\nmodule.exports = { onTick(game) { game.moveUnit(1, 5, 5); } };";
+    let result = engine.dry_run("dry_runner".to_string(), code.to_string(), snapshot).await;
+    assert_eq!(result.error, None);
+    assert_eq!(result.commands, vec![BotCommand {
+        action: "moveTo".to_string(),
+        actor: Some(format!("{}:1", zone_id)),
+        params: serde_json::json!({"position": {"x": 5, "y": 5}}),
+        player_id: None,
+    }]);
+
+    // Nothing was submitted or applied
+    assert!(engine.read().await.list_players().is_empty());
+    for _ in 0..10 {
+        run_simulation_tick(&world, &engine).await;
+    }
+    let world = world.read().await;
+    let worker = world.with_zone_read(&zone_id, |zone| {
+        zone.entities.iter().find(|entity| entity.id == 1).map(|worker| (worker.x, worker.y))
+    }).flatten();
+    assert_eq!(worker, Some((2, 3)));
+}
+
+#[test]
+fn test_server_config_from_toml_file() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/server_config.toml");
+    let config = ServerConfig::from_file(path.to_str().unwrap()).unwrap();
+
+    assert_eq!(config, ServerConfig {
+        host: "127.0.0.1".to_string(),
+        port: 8080,
+        tls_cert_path: None,
+        tls_key_path: None,
+        control_socket: None,
+        admin_users: vec!["alice".to_string(), "bob".to_string()],
+        maintenance_mode: false,
+        registration_mode: RegistrationMode::Open,
+        ip_allowlist: Vec::new(),
+        ip_blocklist: Vec::new(),
+        enable_compression: true,
+        session_duration_secs: 3600,
+        session_sliding_percent: 50,
+        session_max_lifetime_secs: 86400,
+        github_client_id: Some("Iv1.geekcraft".to_string()),
+        discord_client_id: None,
+        public_url: Some("https://geekcraft.example.com".to_string()),
+        smtp_host: Some("smtp.example.com".to_string()),
+        smtp_port: 2525,
+        smtp_username: Some("geekcraft".to_string()),
+        email_from: Some("GeekCraft <noreply@example.com>".to_string()),
+        max_ws_per_user: 5,
+        spectator_frame_rate: 20,
+        keyframe_interval_secs: 30,
+        ticks_per_second: 20,
+        ready_max_tick_age_secs: 30,
+        zone_cache_size: 10,
+        sqlite_read_connections: 2,
+        tournament_max_ticks: 500,
+        script_timeout_ms: 250,
+        script_max_memory_mb: 64,
+        script_strict_mode: true,
+        script_freeze_intrinsics: false,
+        max_concurrent_scripts: 3,
+        script_queue_timeout_ms: 250,
+        inbox_limit: 50,
+        messages_allies_only: true,
+        max_suspicious_patterns: 5,
+        min_api_version: 1,
+        max_alliance_size: 4,
+        world_width: 40,
+        world_height: 30,
+        max_zones: 200,
+        maps_dir: "./tests/fixtures/maps".into(),
+        respawn_mode: RespawnMode::NewZone,
+        respawn_cooldown_ticks: 0,
+        script_tick_interval: 10,
+        market_match_interval_ticks: 120,
+        market_order_ttl_ticks: 7200,
+        history_retention_ticks: 5000,
+        history_keyframe_interval: 50,
+        zone_capture_reward_minerals: 250,
+        zone_capture_reward_gas: 75,
+        world_seed: Some(42),
+        features: FeatureFlags { weather_enabled: false, fog_of_war_enabled: false, ..FeatureFlags::default() },
+    });
+    assert_eq!(config.validate(), Vec::new());
+
+    // Derived settings
+    assert_eq!(config.tick_interval(), Duration::from_millis(50));
+    let limits = config.script_limits();
+    assert_eq!(limits.timeout, Duration::from_millis(250));
+    assert_eq!(limits.max_memory_bytes, 64 * 1024 * 1024);
+    let world_config = config.world_config();
+    assert_eq!((world_config.width, world_config.height, world_config.max_zones), (40, 30, 200));
+    assert_eq!(world_config.respawn_mode, RespawnMode::NewZone);
+    assert_eq!(world_config.script_tick_interval, 10);
+    assert_eq!(world_config.zone_capture_reward_resources[&ResourceType::Minerals], 250);
+    assert_eq!(world_config.zone_capture_reward_resources[&ResourceType::Gas], 75);
+    assert_eq!(world_config.seed, Some(42));
+    assert!(!world_config.fog_of_war);
+    assert_eq!((world_config.history_retention_ticks, world_config.history_keyframe_interval), (5000, 50));
+}
+
+#[test]
+fn test_server_config_fallbacks_and_validation() {
+    // Fields missing from the file come from the environment, then the defaults
+    std::env::set_var("GEEKCRAFT_SESSION_DURATION_SECS", "7200");
+    let config = ServerConfig::from_toml("port = 4000\nticks_per_second = 0").unwrap();
+    std::env::remove_var("GEEKCRAFT_SESSION_DURATION_SECS");
+    assert_eq!(config.port, 4000);
+    assert_eq!(config.session_duration_secs, 7200);
+    assert_eq!(config.script_timeout_ms, geekcraft::config::SCRIPT_TIMEOUT_MS);
+    assert_eq!(config.world_width, geekcraft::config::WORLD_WIDTH);
+
+    // A table of the file only overrides the flags it sets
+    std::env::set_var("GEEKCRAFT_FEATURE_PORTALS", "false");
+    let flags = ServerConfig::from_toml("[features]\nweather_enabled = false").unwrap().features;
+    std::env::remove_var("GEEKCRAFT_FEATURE_PORTALS");
+    assert_eq!(flags, FeatureFlags { weather_enabled: false, portals_enabled: false, ..FeatureFlags::default() });
+    let patched = config.patched(serde_json::json!({"features": {"tournament_enabled": false}})).unwrap();
+    assert_eq!(patched.features, FeatureFlags { tournament_enabled: false, ..FeatureFlags::default() });
+
+    let problems = config.validate();
+    assert_eq!(problems, vec![ConfigError::Invalid {
+        field: "ticks_per_second",
+        message: "must be greater than 0".to_string(),
+    }]);
+    assert!(problems[0].is_fatal());
+
+    // Privileged ports and scripts outliving a script tick are only warnings
+    let config = ServerConfig::from_toml("port = 80\nscript_timeout_ms = 2000").unwrap();
+    let problems = config.validate();
+    assert_eq!(problems.len(), 2);
+    assert!(problems.iter().all(|problem| !problem.is_fatal()));
+    assert!(problems[0].to_string().contains("port 80 is privileged"));
+    assert!(problems[1].to_string().contains("longer than the 500ms between two script ticks"));
+
+    // TLS needs both files
+    let config = ServerConfig::from_toml("tls_cert_path = \"cert.pem\"").unwrap();
+    assert_eq!(config.tls_paths(), None);
+    assert!(config.validate()[0].to_string().contains("tls_key_path: TLS needs both"));
+    let config = ServerConfig::from_toml("tls_cert_path = \"cert.pem\"\ntls_key_path = \"key.pem\"").unwrap();
+    assert_eq!(config.tls_paths(), Some(("cert.pem".to_string(), "key.pem".to_string())));
+
+    // Typos and wrong types are rejected
+    assert!(ServerConfig::from_toml("prot = 3030").unwrap_err().contains("prot"));
+    assert!(ServerConfig::from_toml("port = \"high\"").is_err());
+    assert!(matches!(ServerConfig::from_file("./missing.toml"), Err(ConfigError::Io { .. })));
+}
+
+/// Start a campaign run against a raider NPC, with an intruder's worker near the raider's base
+fn raider_campaign(run_id: &str) -> (CampaignManager, String, String) {
+    std::env::set_var("GEEKCRAFT_SAVE_DIR", std::env::temp_dir().join("geekcraft_test_saves"));
+    let mut manager = CampaignManager::new();
+    let options = RunOptions { npc_opponents: vec!["raider".to_string()], ..RunOptions::default() };
+    manager.start_run_with_options(run_id.to_string(), options).unwrap();
+    assert_eq!(manager.npcs(run_id).len(), 1);
+    let npc = manager.npcs(run_id)[0].player_id().to_string();
+    assert_eq!(npc, "npc_raider_1");
+
+    let world = manager.world_mut(run_id).unwrap();
+    let zone_id = format!("player_{}_zone", npc);
+    world.with_zone_write(&zone_id, |zone| {
+        let base = zone.entities.iter().find(|entity| entity.kind == "base").map(|base| (base.x, base.y)).unwrap();
+        let intruder = (0..ZONE_SIZE * ZONE_SIZE)
+            .map(|i| (i % ZONE_SIZE, i / ZONE_SIZE))
+            .filter(|&(x, y)| x.abs_diff(base.0).max(y.abs_diff(base.1)) == 4)
+            .find(|&(x, y)| find_path(zone, base, (x, y), Mobility::GROUND).is_some())
+            .unwrap();
+        zone.entities.push(entity(50, "worker", "intruder", intruder));
+    }).unwrap();
+    (manager, npc, zone_id)
+}
+
+#[test]
+fn test_campaign_against_raider_npc() {
+    let (mut manager, npc, zone_id) = raider_campaign("raider_run");
+    let world = manager.world_mut("raider_run").unwrap();
+    assert!(world.stockpile(&npc).is_empty());
+    assert_eq!(world.with_zone_read(&zone_id, |zone| zone.resources.len()).unwrap(), 1);
+
+    // Fog of war: the raider sees the intruder, but not entities of other zones
+    let outsider_zone = world.generate_player_zone("outsider").unwrap();
+    let (x, y) = walkable_tile(world, &outsider_zone, 0);
+    world.with_zone_write(&outsider_zone, |zone| zone.entities.push(entity(1, "worker", "outsider", (x, y)))).unwrap();
+    let snapshot = world.player_snapshot(&npc);
+    assert_eq!(snapshot["enemy_units"].as_array().unwrap().len(), 1);
+    assert_eq!(snapshot["enemy_units"][0]["owner"], "intruder");
+    assert_eq!(snapshot["resources"].as_array().unwrap().len(), 1);
+    let intruder_view = world.player_snapshot("intruder");
+    assert_eq!(intruder_view["enemy_structures"][0]["owner"], npc.as_str());
+
+    // Units cost minerals, for NPCs like anyone else
+    let base = snapshot["structures"][0]["id"].as_str().unwrap().to_string();
+    let errors = world.apply_commands(&npc, &[command("produceUnit", &base, serde_json::json!({"unitType": "tank"}))]);
+    assert_eq!(errors, vec!["produceUnit failed: Unknown unit type \"tank\"".to_string()]);
+    assert!(world.apply_commands(&npc, &[command("produceUnit", &base, serde_json::json!({"unitType": "soldier"}))]).is_empty());
+    world.advance_tick();
+    assert_eq!(world.with_zone_read(&zone_id, |zone| zone.entities.len()).unwrap(), 3);
+
+    let soldiers = |manager: &CampaignManager| manager.world("raider_run").unwrap()
+        .with_zone_read(&zone_id, Zone::clone).unwrap()
+        .entities.iter()
+        .filter(|entity| entity.kind == "soldier" && entity.owner.as_deref() == Some(npc.as_str()))
+        .count();
+    let intruder_hits = |manager: &CampaignManager| manager.world("raider_run").unwrap()
+        .with_zone_read(&zone_id, Zone::clone).unwrap()
+        .entities.iter()
+        .find(|entity| entity.owner.as_deref() == Some("intruder"))
+        .map_or(0, |entity| entity.hits);
+
+    // The raider harvests, pays for soldiers, and sends them after the intruder
+    let mut ticks = 0;
+    while intruder_hits(&manager) == DEFAULT_ENTITY_HITS && ticks < 3000 {
+        manager.tick_run("raider_run").unwrap();
+        ticks += 1;
+    }
+    assert!(soldiers(&manager) >= 1, "no soldier after {} ticks", ticks);
+    assert!(intruder_hits(&manager) < DEFAULT_ENTITY_HITS, "no attack after {} ticks", ticks);
+    let world = manager.world("raider_run").unwrap();
+    let spent = soldiers(&manager) as u32 * unit_cost("soldier").unwrap();
+    let harvested = NPC_DEPOSIT_AMOUNT - world.with_zone_read(&zone_id, |zone| zone.resources[0].amount).unwrap();
+    assert_eq!(world.stockpile(&npc).get(&ResourceType::Minerals).copied().unwrap_or(0), harvested - spent);
+    assert_eq!(manager.get_run_state("raider_run").unwrap().tick, ticks);
+
+    // The same run plays out the same way
+    let (mut replay, _, _) = raider_campaign("raider_replay");
+    for _ in 0..ticks {
+        replay.tick_run("raider_replay").unwrap();
+    }
+    assert_eq!(
+        replay.world("raider_replay").unwrap().with_zone_read(&zone_id, Zone::clone).unwrap().entities,
+        manager.world("raider_run").unwrap().with_zone_read(&zone_id, Zone::clone).unwrap().entities,
+    );
+
+    let unknown = RunOptions { npc_opponents: vec!["sleeper".to_string()], ..RunOptions::default() };
+    let err = manager.start_run_with_options("sleeper_run".to_string(), unknown).unwrap_err();
+    assert!(err.contains("Unknown NPC behavior sleeper"), "{}", err);
+    assert!(manager.get_run_state("sleeper_run").is_none());
+}
+
+fn event_types(world: &World, player_id: &str) -> Vec<String> {
+    world.events_visible_to(player_id, 0, 1000).iter()
+        .map(|event| serde_json::to_value(event).unwrap()["type"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn test_research_requires_prerequisites_and_funds() {
+    let mut world = World::new();
+    let zone_id = world.generate_player_zone("alice").unwrap();
+    let (x, y) = walkable_tile(&world, &zone_id, 0);
+    world.with_zone_write(&zone_id, |zone| zone.entities.push(entity(1, "base", "alice", (x, y)))).unwrap();
+    let base = format!("{}:1", zone_id);
+    let research = |tech: &str| command("research", &base, serde_json::json!({"tech": tech}));
+
+    let errors = world.apply_commands("alice", &[research("fast_workers"), research("improved_harvesting"), research("warp_drive")]);
+    assert_eq!(errors, vec![
+        "research failed: fast_workers requires improved_harvesting".to_string(),
+        "research failed: improved_harvesting costs 150 minerals, you have 0".to_string(),
+        "research failed: Unknown tech \"warp_drive\"".to_string(),
+    ]);
+    // Scripts see why their commands were rejected
+    let bundle = ScriptBundle::single("console.log(game.commandErrors().length, JSON.stringify(game.tech().available));".to_string()).unwrap();
+    let runtime = create_runtime(ScriptLanguage::JavaScript, ScriptLimits::default());
+    let result = runtime.execute_tick(&bundle, &world.player_snapshot("alice"));
+    assert_eq!(result.logs, vec![r#"3 ["improved_harvesting","soldier_armor_1"]"#.to_string()]);
+
+    // Paid when the research starts, done after its research time
+    world.deposit_resources("alice", ResourceType::Minerals, 200);
+    assert!(world.apply_commands("alice", &[research("improved_harvesting")]).is_empty());
+    world.advance_tick();
+    assert_eq!(world.stockpile("alice")[&ResourceType::Minerals], 50);
+    let status = world.tech_status("alice");
+    assert_eq!(status.research.as_ref().unwrap().completes_at, 1 + tech::tech("improved_harvesting").unwrap().research_ticks);
+    assert_eq!(world.apply_commands("alice", &[research("soldier_armor_1")]), vec!["research failed: Already researching improved_harvesting".to_string()]);
+    while world.tech_status("alice").research.is_some() {
+        world.advance_tick();
+    }
+    assert_eq!(world.get_tick(), 61);
+    assert_eq!(world.tech_status("alice").researched, vec!["improved_harvesting".to_string()]);
+    assert!(world.tech_status("alice").available.contains(&"fast_workers".to_string()));
+    assert_eq!(
+        world.apply_commands("alice", &[research("improved_harvesting")]),
+        vec!["research failed: improved_harvesting is already researched".to_string()],
+    );
+
+    // Losing the base cancels the research, without refund
+    world.deposit_resources("alice", ResourceType::Minerals, 50);
+    assert!(world.apply_commands("alice", &[research("soldier_armor_1")]).is_empty());
+    world.advance_tick();
+    assert_eq!(world.stockpile("alice")[&ResourceType::Minerals], 0);
+    world.with_zone_write(&zone_id, |zone| zone.entities.retain(|entity| entity.id != 1)).unwrap();
+    world.advance_tick();
+    let status = world.tech_status("alice");
+    assert!(status.research.is_none());
+    assert_eq!(status.researched, vec!["improved_harvesting".to_string()]);
+    assert_eq!(world.stockpile("alice")[&ResourceType::Minerals], 0);
+    assert_eq!(event_types(&world, "alice"), vec!["ResearchCompleted", "ResearchCancelled", "PlayerDefeated"]);
+}
+
+#[test]
+fn test_fog_of_war_can_be_turned_off() {
+    let mut world = World::new();
+    let zone_id = world.generate_player_zone("alice").unwrap();
+    world.with_zone_write(&zone_id, |zone| {
+        zone.entities.push(entity(1, "worker", "alice", (0, 0)));
+        zone.entities.push(entity(2, "worker", "bob", (ZONE_SIZE - 1, ZONE_SIZE - 1)));
+    }).unwrap();
+    assert!(world.player_snapshot("alice")["enemy_units"].as_array().unwrap().is_empty());
+
+    world.set_config(WorldConfig { fog_of_war: false, ..world.config().clone() });
+    let snapshot = world.player_snapshot("alice");
+    assert_eq!(snapshot["enemy_units"].as_array().unwrap().len(), 1);
+    assert_eq!(snapshot["enemy_units"][0]["owner"], "bob");
+    // Other zones stay hidden
+    assert!(world.player_snapshot("carol")["enemy_units"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn test_tech_modifiers_change_harvest_movement_and_damage() {
+    let mut world = World::new();
+    let zone_id = world.generate_player_zone("alice").unwrap();
+    let start = walkable_tile(&world, &zone_id, 0);
+    world.with_zone_write(&zone_id, |zone| {
+        zone.entities.push(entity(1, "worker", "alice", start));
+        zone.resources.push(ResourceDeposit { x: start.0, y: start.1, amount: 500 });
+    }).unwrap();
+    let worker = format!("{}:1", zone_id);
+    let harvest = command("harvest", &worker, serde_json::json!({}));
+
+    world.apply_commands("alice", std::slice::from_ref(&harvest));
+    world.advance_tick();
+    assert_eq!(world.stockpile("alice")[&ResourceType::Minerals], HARVEST_AMOUNT);
+    world.set_researched_techs("alice", ["improved_harvesting".to_string()].into());
+    world.apply_commands("alice", &[harvest]);
+    world.advance_tick();
+    assert_eq!(world.stockpile("alice")[&ResourceType::Minerals], 2 * HARVEST_AMOUNT + 5);
+
+    // Fast workers walk two tiles per tick
+    let zone = world.with_zone_read(&zone_id, Zone::clone).unwrap();
+    let path = (0..ZONE_SIZE * ZONE_SIZE)
+        .map(|i| (i % ZONE_SIZE, i / ZONE_SIZE))
+        .filter_map(|tile| find_path(&zone, start, tile, Mobility::GROUND).map(|(path, _)| path))
+        .find(|path| path.len() == 8)
+        .expect("a tile 7 steps away");
+    let target = path[7];
+    world.apply_commands("alice", &[command("moveTo", &worker, serde_json::json!({"position": {"x": target.0, "y": target.1}}))]);
+    let position = |world: &World| {
+        world.with_zone_read(&zone_id, |zone| {
+            let unit = zone.entities.iter().find(|entity| entity.id == 1).unwrap();
+            (unit.x, unit.y)
+        }).unwrap()
+    };
+    world.advance_tick();
+    assert_eq!(position(&world), path[1]);
+    world.set_researched_techs("alice", ["improved_harvesting".to_string(), "fast_workers".to_string()].into());
+    world.advance_tick();
+    assert_eq!(position(&world), path[3]);
+    world.advance_tick();
+    world.advance_tick();
+    assert_eq!(position(&world), target);
+    assert!(!world.is_moving(&zone_id, 1));
+
+    // Armored soldiers take less damage
+    let (x, y) = position(&world);
+    let neighbour = [(x + 1, y), (x, y + 1), (x.wrapping_sub(1), y), (x, y.wrapping_sub(1))]
+        .into_iter()
+        .find(|&(nx, ny)| world.with_zone_read(&zone_id, |zone| zone.get_tile(nx, ny)
+            .is_some_and(|tile| tile.surface_type != SurfaceType::Obstacle)).unwrap())
+        .unwrap();
+    world.with_zone_write(&zone_id, |zone| zone.entities.push(entity(2, "soldier", "bob", neighbour))).unwrap();
+    world.set_researched_techs("bob", ["soldier_armor_1".to_string(), "soldier_armor_2".to_string()].into());
+    world.apply_commands("alice", &[command("attack", &worker, serde_json::json!({"target": format!("{}:2", zone_id)}))]);
+    world.advance_tick();
+    let hits = world.with_zone_read(&zone_id, |zone| zone.entities.iter().find(|entity| entity.id == 2).unwrap().hits).unwrap();
+    assert_eq!(hits, DEFAULT_ENTITY_HITS - (ATTACK_DAMAGE - 6));
+}
+
+#[test]
+fn test_forged_and_stale_commands_are_not_applied() {
+    let mut world = World::new();
+    let zone_id = world.generate_player_zone("alice").unwrap();
+    let start = walkable_tile(&world, &zone_id, 0);
+    let (x, y) = start;
+    let neighbour = [(x + 1, y), (x, y + 1), (x.wrapping_sub(1), y), (x, y.wrapping_sub(1))]
+        .into_iter()
+        .find(|&(nx, ny)| world.with_zone_read(&zone_id, |zone| zone.get_tile(nx, ny)
+            .is_some_and(|tile| tile.surface_type != SurfaceType::Obstacle)).unwrap())
+        .unwrap();
+    world.with_zone_write(&zone_id, |zone| {
+        zone.entities.push(entity(1, "worker", "alice", start));
+        zone.entities.push(entity(2, "soldier", "bob", neighbour));
+        zone.resources.push(ResourceDeposit { x, y, amount: 500 });
+    }).unwrap();
+    let worker = format!("{}:1", zone_id);
+    let soldier = format!("{}:2", zone_id);
+    let minerals = |world: &World| world.stockpile("alice").get(&ResourceType::Minerals).copied().unwrap_or(0);
+
+    // Another player's unit, a command issued by someone else, and targets that cannot be attacked
+    let mut forged = command("harvest", &worker, serde_json::json!({}));
+    forged.player_id = Some("bob".to_string());
+    let errors = world.apply_commands("alice", &[
+        command("moveTo", &soldier, serde_json::json!({"position": {"x": x, "y": y}})),
+        forged,
+        command("attack", &worker, serde_json::json!({"target": worker})),
+        command("attack", &worker, serde_json::json!({"target": format!("{}:99", zone_id)})),
+    ]);
+    assert_eq!(errors, vec![
+        format!("moveTo failed: Entity {} is not yours", soldier),
+        "harvest failed: Command issued by bob cannot be applied for alice".to_string(),
+        format!("attack failed: Target {} is your own", worker),
+        format!("attack failed: Target {}:99 not found", zone_id),
+    ]);
+    assert_eq!(world.command_violations("alice"), 4);
+    world.advance_tick();
+    assert!(!world.is_moving(&zone_id, 2));
+    assert_eq!(minerals(&world), 0);
+    assert_eq!(world.with_zone_read(&zone_id, Zone::clone).unwrap().entities.iter().map(|entity| entity.hits).collect::<Vec<_>>(),
+        vec![DEFAULT_ENTITY_HITS, DEFAULT_ENTITY_HITS]);
+
+    // Commands stamped by the sandbox with the right player pass
+    let mut issued = command("harvest", &worker, serde_json::json!({}));
+    issued.player_id = Some("alice".to_string());
+    assert!(world.apply_commands("alice", &[issued]).is_empty());
+    world.advance_tick();
+    assert_eq!(minerals(&world), HARVEST_AMOUNT);
+
+    // Entities destroyed between the script tick and the resolution of its commands
+    assert!(world.apply_commands("alice", &[
+        command("attack", &worker, serde_json::json!({"target": soldier})),
+    ]).is_empty());
+    world.with_zone_write(&zone_id, |zone| zone.entities.retain(|entity| entity.id != 2)).unwrap();
+    world.advance_tick();
+    assert_eq!(world.command_violations("alice"), 5);
+    assert_eq!(world.player_snapshot("alice")["command_errors"], serde_json::json!([
+        format!("attack failed: Target {} not found", soldier),
+    ]));
+    assert!(world.apply_commands("alice", &[command("harvest", &worker, serde_json::json!({}))]).is_empty());
+    world.with_zone_write(&zone_id, |zone| zone.entities.clear()).unwrap();
+    world.advance_tick();
+    assert_eq!(minerals(&world), HARVEST_AMOUNT);
+    assert_eq!(world.command_violations("alice"), 6);
+    assert_eq!(world.command_violations("bob"), 0);
+    assert_eq!(world.player_snapshot("alice")["command_errors"], serde_json::json!([
+        format!("harvest failed: Entity {} not found", worker),
+    ]));
+}
+
+#[test]
+fn test_researched_techs_survive_campaign_save_and_load() {
+    let (mut manager, npc, _) = raider_campaign("tech_run");
+    let world = manager.world_mut("tech_run").unwrap();
+    world.set_researched_techs(&npc, ["soldier_armor_1".to_string()].into());
+    manager.save_run("tech_run").unwrap();
+
+    let mut loaded = CampaignManager::new();
+    let run = loaded.load_run("tech_run").unwrap();
+    assert_eq!(run.researched_techs[&npc], ["soldier_armor_1".to_string()].into());
+    let status = loaded.world("tech_run").unwrap().tech_status(&npc);
+    assert_eq!(status.researched, vec!["soldier_armor_1".to_string()]);
+    assert!(status.available.contains(&"soldier_armor_2".to_string()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_concurrent_run_updates_conflict() {
+    std::env::set_var("GEEKCRAFT_SAVE_DIR", std::env::temp_dir().join("geekcraft_test_saves"));
+    let mut manager = CampaignManager::new();
+    let run = manager.start_run("locked_run".to_string(), None).unwrap();
+    assert_eq!(run.version, 1);
+    manager.tick_run("locked_run").unwrap();
+    assert_eq!(manager.get_run_state("locked_run").unwrap().version, 2);
+    let manager = Arc::new(RwLock::new(manager));
+
+    // Both clients read version 2, then race to update the run
+    let barrier = Arc::new(tokio::sync::Barrier::new(2));
+    let updates = ["alice", "bob"].map(|owner| {
+        let (manager, barrier) = (manager.clone(), barrier.clone());
+        tokio::spawn(async move {
+            let version = manager.read().await.get_run_state("locked_run").unwrap().version;
+            barrier.wait().await;
+            manager.write().await.update_run("locked_run", version, |run| run.allow_spectators = owner == "alice")
+        })
+    });
+    let mut results = Vec::new();
+    for update in updates {
+        results.push(update.await.unwrap());
+    }
+    let (won, lost): (Vec<_>, Vec<_>) = results.into_iter().partition(Result::is_ok);
+    assert_eq!((won.len(), lost.len()), (1, 1));
+    assert_eq!(won[0].as_ref().unwrap().version, 3);
+    assert_eq!(
+        lost[0].as_ref().unwrap_err(),
+        &CampaignError::VersionConflict { run_id: "locked_run".to_string(), expected: 2, found: 3 }
+    );
+
+    // The winner's change is both in memory and on disk
+    let allow_spectators = won[0].as_ref().unwrap().allow_spectators;
+    assert_eq!(manager.read().await.get_run_state("locked_run").unwrap().allow_spectators, allow_spectators);
+    let saved = CampaignManager::new().load_run("locked_run").unwrap();
+    assert_eq!((saved.version, saved.allow_spectators), (3, allow_spectators));
+}
+
+#[test]
+fn test_saving_over_a_newer_save_conflicts() {
+    let (mut manager, _, _) = raider_campaign("contended_run");
+    manager.save_run("contended_run").unwrap();
+
+    let mut first = CampaignManager::new();
+    let mut second = CampaignManager::new();
+    let version = first.load_run("contended_run").unwrap().version;
+    second.load_run("contended_run").unwrap();
+
+    first.tick_run("contended_run").unwrap();
+    first.save_run("contended_run").unwrap();
+    second.tick_run("contended_run").unwrap();
+    assert_eq!(
+        second.save_run("contended_run"),
+        Err(CampaignError::VersionConflict { run_id: "contended_run".to_string(), expected: version, found: version + 1 })
+    );
+
+    // Reloading picks up the other save, after which saving works again
+    second.load_run("contended_run").unwrap();
+    second.tick_run("contended_run").unwrap();
+    second.save_run("contended_run").unwrap();
+    assert_eq!(CampaignManager::new().load_run("contended_run").unwrap().version, version + 2);
+}
+
+/// Harvest from a deposit put under the worker of `player1` in a scenario run, then tick the run
+fn harvest_once(manager: &mut CampaignManager, run_id: &str) {
+    let world = manager.world_mut(run_id).unwrap();
+    world.with_zone_write("player_player1_zone", |zone| {
+        let worker = zone.entities.iter().find(|entity| entity.kind == "worker").map(|worker| (worker.x, worker.y)).unwrap();
+        if zone.resources.is_empty() {
+            zone.resources.push(ResourceDeposit { x: worker.0, y: worker.1, amount: 100 });
+        }
+    }).unwrap();
+    let errors = world.apply_commands("player1", &[command("harvest", "player_player1_zone:2", serde_json::json!({}))]);
+    assert!(errors.is_empty(), "{:?}", errors);
+    manager.tick_run(run_id).unwrap();
+}
+
+#[test]
+fn test_scenario_objectives_complete_and_fail_runs() {
+    std::env::set_var("GEEKCRAFT_SAVE_DIR", std::env::temp_dir().join("geekcraft_test_saves"));
+    let scenario = Scenario::find(std::path::Path::new("./tests/fixtures/maps"), "tiny_harvest").unwrap();
+    assert_eq!(scenario.name, "tiny_harvest");
+    assert_eq!(scenario.player, "player1");
+    let mut manager = CampaignManager::new();
+    assert_eq!(manager.scenario("crossroads_harvest").unwrap().map_template.as_deref(), Some("crossroads"));
+    assert!(manager.scenario("moon_landing").unwrap_err().contains("not found"));
+
+    let options = RunOptions { scenario: Some(scenario.clone()), ..RunOptions::default() };
+    let run = manager.start_run_with_options("scenario_run".to_string(), options).unwrap();
+    assert_eq!(run.status, RunStatus::InProgress);
+    assert_eq!((run.objectives[0].progress, run.objectives[0].target), (0, 20));
+
+    // Half way there: the progress survives a save and load, and scripts see it
+    harvest_once(&mut manager, "scenario_run");
+    let run = manager.get_run_state("scenario_run").unwrap();
+    assert_eq!((run.status, run.objectives[0].progress), (RunStatus::InProgress, 10));
+    assert_eq!(run.objectives[1].progress, 1);
+    manager.save_run("scenario_run").unwrap();
+    let mut manager = CampaignManager::new();
+    let run = manager.load_run("scenario_run").unwrap();
+    assert_eq!(run.objectives[0].progress, 10);
+    let snapshot = manager.world("scenario_run").unwrap().player_snapshot("player1");
+    assert_eq!(snapshot["objectives"][0]["progress"], 10);
+    assert_eq!(snapshot["objectives"][0]["status"], "in_progress");
+
+    // Meeting the only mandatory objective completes the run and stops it
+    harvest_once(&mut manager, "scenario_run");
+    let run = manager.get_run_state("scenario_run").unwrap();
+    assert_eq!(run.status, RunStatus::Completed);
+    assert_eq!(run.objectives[0].status, ObjectiveStatus::Completed);
+    assert_eq!(run.objectives[1].status, ObjectiveStatus::InProgress);
+    assert!(!run.running);
+    assert!(manager.tick_run("scenario_run").is_err());
+
+    // A mandatory objective missing its deadline fails the run
+    let mut rushed = scenario.clone();
+    rushed.objectives[0].deadline_ticks = Some(3);
+    let options = RunOptions { scenario: Some(rushed), ..RunOptions::default() };
+    manager.start_run_with_options("rushed_run".to_string(), options).unwrap();
+    for _ in 0..3 {
+        manager.tick_run("rushed_run").unwrap();
+    }
+    let run = manager.get_run_state("rushed_run").unwrap();
+    assert_eq!((run.status, run.objectives[0].status), (RunStatus::Failed, ObjectiveStatus::Failed));
+    assert!(!run.running);
+
+    // Built-in scenarios are played on the crossroads map, where player1 already has a base
+    let options = RunOptions { scenario: Some(manager.scenario("crossroads_conquest").unwrap()), ..RunOptions::default() };
+    let run = manager.start_run_with_options("conquest_run".to_string(), options).unwrap();
+    assert_eq!((run.objectives[0].id.as_str(), run.objectives[0].target), ("destroy_base", 1));
+    let world = manager.world("conquest_run").unwrap();
+    assert_eq!(world.zones_of_player("player1"), vec!["map_crossroads".to_string()]);
+    assert_eq!(world.stockpile("player1")[&ResourceType::Minerals], 100);
+
+    let options = RunOptions { scenario: Some(scenario), npc_opponents: vec!["raider".to_string()], ..RunOptions::default() };
+    let err = manager.start_run_with_options("mixed_run".to_string(), options).unwrap_err();
+    assert!(err.contains("sets the map template and NPC opponents"), "{}", err);
+}
+
+#[test]
+fn test_snapshot_cache_rebuilds_only_changed_fragments() {
+    let mut world = World::new();
+    let zone_id = world.generate_player_zone("alice").unwrap();
+    world.generate_player_zone("carol").unwrap();
+    let (x, y) = walkable_tile(&world, &zone_id, 0);
+    let base_tile = walkable_tile(&world, &zone_id, ZONE_SIZE * 10);
+    world.with_zone_write(&zone_id, |zone| {
+        zone.entities.push(entity(100, "base", "alice", base_tile));
+        zone.entities.push(entity(101, "worker", "alice", (x, y)));
+        zone.entities.push(EntityRef { hits: ATTACK_DAMAGE * 2, ..entity(102, "soldier", "bob", (x + 1, y)) });
+        zone.resources.push(ResourceDeposit { x, y, amount: 500 });
+    }).unwrap();
+    world.deposit_resources("alice", ResourceType::Minerals, unit_cost("worker").unwrap());
+    let (base, worker, soldier) = (format!("{}:100", zone_id), format!("{}:101", zone_id), format!("{}:102", zone_id));
+
+    world.player_snapshot("carol");
+    world.player_snapshot("alice");
+    let built = world.snapshot_cache_builds();
+    assert_eq!((built.terrain, built.contents), (2, 2));
+    world.player_snapshot("alice");
+    assert_eq!(world.snapshot_cache_builds(), built);
+
+    // Snapshots match those of a world without cached fragments, and only the fragments
+    // of Alice's zone were built again
+    let check = |world: &World, step: &str, terrain: u64, contents: u64| {
+        let alice = world.player_snapshot("alice");
+        world.player_snapshot("carol");
+        let builds = world.snapshot_cache_builds();
+        assert_eq!((builds.terrain, builds.contents), (built.terrain + terrain, built.contents + contents), "after {}", step);
+        assert_eq!(alice, world.clone().player_snapshot("alice"), "stale fragments after {}", step);
+        alice
+    };
+
+    // Combat, harvesting, construction and movement leave the terrain alone
+    assert!(world.apply_commands("alice", &[command("attack", &worker, serde_json::json!({"target": soldier}))]).is_empty());
+    world.advance_tick();
+    let alice = check(&world, "attack", 0, 1);
+    assert_eq!(alice["enemy_units"][0]["hits"], ATTACK_DAMAGE);
+
+    assert!(world.apply_commands("alice", &[command("harvest", &worker, serde_json::json!({}))]).is_empty());
+    world.advance_tick();
+    let alice = check(&world, "harvest", 0, 2);
+    assert_eq!(alice["resources"][0]["amount"], 500 - HARVEST_AMOUNT);
+
+    assert!(world.apply_commands("alice", &[command("produceUnit", &base, serde_json::json!({"unitType": "worker"}))]).is_empty());
+    world.advance_tick();
+    let alice = check(&world, "produceUnit", 0, 3);
+    assert_eq!(alice["units"].as_array().unwrap().len(), 2);
+
+    let (mx, my) = walkable_tile(&world, &zone_id, ZONE_SIZE * 20);
+    let moved = GameAction::EntityMoved { zone_id: zone_id.clone(), entity_id: 101, x: mx, y: my };
+    world.apply_action(world.get_tick(), &moved).unwrap();
+    let alice = check(&world, "move", 0, 4);
+    assert_eq!(alice["units"][0]["position"], serde_json::json!({"x": mx, "y": my}));
+
+    // Changing tiles rebuilds the terrain too
+    let obstacles = alice["obstacles"].as_array().unwrap().len();
+    let (ox, oy) = walkable_tile(&world, &zone_id, ZONE_SIZE * 2);
+    world.with_zone_write(&zone_id, |zone| zone.tiles[oy][ox].surface_type = SurfaceType::Obstacle).unwrap();
+    let alice = check(&world, "tile change", 1, 5);
+    assert_eq!(alice["obstacles"].as_array().unwrap().len(), obstacles + 1);
+}
+
+#[test]
+#[ignore = "timing-dependent benchmark"]
+fn test_snapshot_cache_speeds_up_idle_worlds() {
+    let mut world = World::with_seed(7);
+    let players: Vec<String> = (0..50).map(|i| format!("idle_{:02}", i)).collect();
+    for player in &players {
+        let zone_id = world.spawn_player(player).unwrap();
+        let tiles: Vec<(usize, usize)> = (1..=5).map(|i| walkable_tile(&world, &zone_id, i * ZONE_SIZE * 4)).collect();
+        world.with_zone_write(&zone_id, |zone| {
+            for (i, tile) in tiles.into_iter().enumerate() {
+                zone.entities.push(entity(100 + i as u32, "worker", player, tile));
+            }
+        }).unwrap();
+    }
+
+    // Best of a few script ticks, each building every player's snapshot
+    let rebuild_all = |world: &World, cached: bool| {
+        (0..5)
+            .map(|_| {
+                let start = Instant::now();
+                for player in &players {
+                    if !cached {
+                        world.clear_snapshot_cache();
+                    }
+                    std::hint::black_box(world.player_snapshot(player));
+                }
+                start.elapsed()
+            })
+            .min()
+            .unwrap()
+    };
+    let uncached = rebuild_all(&world, false);
+    let cached = rebuild_all(&world, true);
+    assert!(uncached >= cached * 5, "{:?} without the cache, {:?} with it", uncached, cached);
+}
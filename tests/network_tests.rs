@@ -0,0 +1,3378 @@
+// Network-level tests for GeekCraft (HTTP router and WebSocket).
+// Each test runs its own server instance on an ephemeral port.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use tower::ServiceExt;
+
+use geekcraft::auth::audit::SqliteAuditStore;
+use geekcraft::auth::email::{MockEmailSender, EMAIL_VERIFICATION_TTL_SECS};
+use geekcraft::auth::oauth::{OAuth, OAuthProvider};
+use geekcraft::auth::{AuthDatabase, AuthService, DatabaseBackend, RegistrationMode, UserFilter};
+use geekcraft::config::FeatureFlag;
+use geekcraft::game::game_loop::{run_game_loop, RunState};
+use geekcraft::game::lobby::LobbyStatus;
+use geekcraft::game::stats::spawn_stats_updater;
+use geekcraft::game::store::{InMemoryWorldStore, WorldStore};
+use geekcraft::game::world::{World, WorldConfig, ATTACK_DAMAGE};
+use geekcraft::game::zone::{EntityRef, Exit, ExitDirection, ResourceDeposit, ResourceType, SurfaceType, Zone, DEFAULT_ENTITY_HITS, ZONE_SIZE};
+use geekcraft::network::campaign_routes::campaign_manager;
+use geekcraft::network::compression::MIN_COMPRESSED_SIZE;
+use geekcraft::network::extract::{AUTH_BODY_LIMIT, CODE_BODY_LIMIT, IMPORT_BODY_LIMIT, JSON_BODY_LIMIT};
+use geekcraft::network::server::{create_router, AppState};
+use geekcraft::network::tls::{self, get_tls_config, TlsError};
+use geekcraft::network::zone_cache::ZoneCache;
+use geekcraft::network::state_sync::{StateReplica, SyncState};
+use geekcraft::scripting::commands::BotCommand;
+use geekcraft::scripting::handle::ScriptEngineHandle;
+
+type WsClient = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Build a fresh application state backed by the in-memory database
+fn test_state() -> (AppState, Arc<AuthDatabase>) {
+    // Keep campaign saves out of the working tree
+    std::env::set_var("GEEKCRAFT_SAVE_DIR", std::env::temp_dir().join("geekcraft_test_saves"));
+
+    let db = Arc::new(AuthDatabase::new(DatabaseBackend::InMemory)
+        .expect("Failed to create In-Memory database"));
+    let state = AppState::new(
+        World::new(),
+        ScriptEngineHandle::default(),
+        Arc::new(AuthService::new(db.clone())),
+    );
+    (state, db)
+}
+
+/// Create a user with a valid session, bypassing bcrypt, and return the token
+fn create_session(db: &AuthDatabase, username: &str) -> String {
+    let user = db.create_user(username, "unused_hash").expect("Failed to create user");
+    let token = format!("token-{}", username);
+    db.create_session(&token, user.id, chrono::Utc::now().timestamp() + 3600)
+        .expect("Failed to create session");
+    token
+}
+
+/// Serve the router on an ephemeral port
+async fn spawn_server(state: AppState) -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        tls::serve(listener, create_router(state), None).await.unwrap();
+    });
+    addr
+}
+
+/// GET a path through the router as if sent from `peer`
+async fn get_from(state: &AppState, peer: &str, uri: &str) -> StatusCode {
+    let peer: SocketAddr = peer.parse().unwrap();
+    let mut request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+    request.extensions_mut().insert(axum::extract::ConnectInfo(peer));
+    create_router(state.clone()).oneshot(request).await.unwrap().status()
+}
+
+/// Send a JSON request through the router without a network round-trip
+async fn post_json(state: &AppState, uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+    let request = Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = create_router(state.clone()).oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+}
+
+/// Send a GET request through the router, optionally with a bearer token
+async fn get_with_token(state: &AppState, uri: &str, token: Option<&str>) -> axum::response::Response {
+    let mut request = Request::builder().method("GET").uri(uri);
+    if let Some(token) = token {
+        request = request.header("Authorization", format!("Bearer {}", token));
+    }
+    create_router(state.clone()).oneshot(request.body(Body::empty()).unwrap()).await.unwrap()
+}
+
+/// POST a JSON body with a bearer token through the router
+async fn post_json_with_token(state: &AppState, uri: &str, token: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+    send_json_with_token(state, "POST", uri, token, body).await
+}
+
+/// Send a JSON body with a bearer token through the router
+async fn send_json_with_token(state: &AppState, method: &str, uri: &str, token: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("Content-Type", "application/json")
+        .header("Authorization", format!("Bearer {}", token))
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = create_router(state.clone()).oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+}
+
+/// Read the next JSON text frame (fails after a short timeout)
+async fn next_json(ws: &mut WsClient) -> serde_json::Value {
+    loop {
+        let msg = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .expect("Timed out waiting for WebSocket message")
+            .expect("WebSocket closed")
+            .expect("WebSocket error");
+        if let Message::Text(text) = msg {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+/// Read frames until one with the given `type` arrives
+async fn next_of_type(ws: &mut WsClient, msg_type: &str) -> serde_json::Value {
+    loop {
+        let msg = next_json(ws).await;
+        if msg["type"] == msg_type {
+            return msg;
+        }
+    }
+}
+
+async fn send_json(ws: &mut WsClient, value: serde_json::Value) {
+    ws.send(Message::Text(value.to_string())).await.unwrap();
+}
+
+/// Connect and authenticate a WebSocket client
+async fn connect_authenticated(addr: SocketAddr, token: &str) -> WsClient {
+    let (mut ws, _) = connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+    assert_eq!(next_json(&mut ws).await["type"], "welcome");
+    send_json(&mut ws, serde_json::json!({"type": "auth", "token": token})).await;
+    let auth = next_of_type(&mut ws, "authResponse").await;
+    assert_eq!(auth["success"], true);
+    ws
+}
+
+#[tokio::test]
+async fn test_spectator_receives_match_frames() {
+    let (state, db) = test_state();
+    state.config.write().unwrap().spectator_frame_rate = 50;
+    let token = create_session(&db, "watcher");
+
+    let (status, _) = post_json(&state, "/api/campaign/start",
+        serde_json::json!({"run_id": "spectate_open_match"})).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let addr = spawn_server(state).await;
+    let mut ws = connect_authenticated(addr, &token).await;
+
+    // The watcher never joined this match but can still spectate it
+    send_json(&mut ws, serde_json::json!({"type": "spectate", "match_id": "spectate_open_match"})).await;
+    let response = next_of_type(&mut ws, "spectateResponse").await;
+    assert_eq!(response["success"], true);
+    assert_eq!(response["target"], "match");
+
+    let frame = next_of_type(&mut ws, "spectatorFrame").await;
+    assert_eq!(frame["id"], "spectate_open_match");
+    assert_eq!(frame["run"]["run_id"], "spectate_open_match");
+
+    // Frames keep coming at the spectator frame rate
+    next_of_type(&mut ws, "spectatorFrame").await;
+}
+
+#[tokio::test]
+async fn test_spectator_rejected_when_disabled() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "lurker");
+
+    let (status, _) = post_json(&state, "/api/campaign/start",
+        serde_json::json!({"run_id": "spectate_closed_match", "allow_spectators": false})).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let addr = spawn_server(state).await;
+    let mut ws = connect_authenticated(addr, &token).await;
+
+    send_json(&mut ws, serde_json::json!({"type": "spectate", "match_id": "spectate_closed_match"})).await;
+    let response = next_of_type(&mut ws, "spectateResponse").await;
+    assert_eq!(response["success"], false);
+    assert!(response["message"].as_str().unwrap().contains("not allowed"));
+}
+
+#[tokio::test]
+async fn test_spectator_commands_refused() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "streamer");
+    let zone_id = state.game_world.write().await.generate_player_zone("streamed_player").unwrap();
+
+    let addr = spawn_server(state).await;
+    let mut ws = connect_authenticated(addr, &token).await;
+
+    send_json(&mut ws, serde_json::json!({"type": "spectate", "zone_id": zone_id})).await;
+    let response = next_of_type(&mut ws, "spectateResponse").await;
+    assert_eq!(response["success"], true);
+    assert_eq!(next_of_type(&mut ws, "spectatorFrame").await["zone"]["id"], zone_id);
+
+    // Anything that is not a read-only query is refused while spectating
+    send_json(&mut ws, serde_json::json!({"type": "submitCode", "code": "move()"})).await;
+    let error = next_of_type(&mut ws, "error").await;
+    assert!(error["message"].as_str().unwrap().contains("Spectators cannot issue commands"));
+
+    // After leaving spectator mode the guard no longer applies
+    send_json(&mut ws, serde_json::json!({"type": "unspectate"})).await;
+    assert_eq!(next_of_type(&mut ws, "unspectateResponse").await["success"], true);
+    send_json(&mut ws, serde_json::json!({"type": "submitCode", "code": "move()"})).await;
+    assert_eq!(next_of_type(&mut ws, "submitCodeResponse").await["success"], true);
+}
+
+#[tokio::test]
+async fn test_spectate_requires_auth() {
+    let (state, _db) = test_state();
+    let addr = spawn_server(state).await;
+
+    let (mut ws, _) = connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+    next_of_type(&mut ws, "welcome").await;
+    send_json(&mut ws, serde_json::json!({"type": "spectate", "zone_id": "any"})).await;
+    let error = next_of_type(&mut ws, "error").await;
+    assert!(error["message"].as_str().unwrap().contains("Authentication required"));
+}
+
+#[tokio::test]
+async fn test_versioned_and_unversioned_gamestate_match() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "versioned_player");
+    state.script_engine.write().await
+        .submit_code("versioned_player".to_string(), "// bot".to_string())
+        .unwrap();
+
+    let legacy = get_with_token(&state, "/api/gamestate", Some(&token)).await;
+    let versioned = get_with_token(&state, "/api/v1/gamestate", Some(&token)).await;
+    assert_eq!(legacy.status(), StatusCode::OK);
+    assert_eq!(versioned.status(), StatusCode::OK);
+
+    // Only the unversioned path is flagged as deprecated
+    assert_eq!(legacy.headers().get("Deprecation").unwrap(), "true");
+    assert!(legacy.headers().get("Link").unwrap().to_str().unwrap().contains("/api/v1/gamestate"));
+    assert!(versioned.headers().get("Deprecation").is_none());
+
+    let legacy_body = axum::body::to_bytes(legacy.into_body(), usize::MAX).await.unwrap();
+    let versioned_body = axum::body::to_bytes(versioned.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(legacy_body, versioned_body);
+
+    // The caller's researched techs are included
+    state.game_world.write().await
+        .set_researched_techs("versioned_player", ["soldier_armor_1".to_string()].into());
+    let response = get_with_token(&state, "/api/v1/gamestate", Some(&token)).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let gamestate: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(gamestate["tech"]["researched"], serde_json::json!(["soldier_armor_1"]));
+    assert!(gamestate["tech"]["research"].is_null());
+}
+
+#[tokio::test]
+async fn test_versioned_paths_keep_auth_rules() {
+    let (state, _db) = test_state();
+
+    // Public endpoints stay public under the version prefix
+    let health = get_with_token(&state, "/api/v1/health/live", None).await;
+    assert_eq!(health.status(), StatusCode::OK);
+    assert!(health.headers().get("Deprecation").is_none());
+
+    // Protected endpoints stay protected
+    assert_eq!(get_with_token(&state, "/api/v1/players", None).await.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(get_with_token(&state, "/api/players", None).await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_get_code_returns_bundle() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "bundler");
+
+    let submission = serde_json::json!({
+        "modules": {
+            "main.js": "module.exports = require('./utils/path');",
+            "utils/path.js": "module.exports = {};"
+        }
+    });
+    let (status, _) = post_json_with_token(&state, "/api/v1/submit", &token, submission.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let response = get_with_token(&state, "/api/v1/code", Some(&token)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["player_id"], "bundler");
+    assert_eq!(body["modules"], submission["modules"]);
+    assert_eq!(body["code"], submission["modules"]["main.js"]);
+}
+
+#[tokio::test]
+async fn test_websocket_connection_limit_per_user() {
+    let (state, db) = test_state();
+    state.config.write().unwrap().max_ws_per_user = 3;
+    let token = create_session(&db, "many_tabs");
+    let user_id = db.get_user_by_username("many_tabs").unwrap().unwrap().id;
+    let counts = state.connected_ws_per_user.clone();
+
+    let addr = spawn_server(state).await;
+    let mut open = Vec::new();
+    for _ in 0..3 {
+        open.push(connect_authenticated(addr, &token).await);
+    }
+
+    // The 4th connection for the same user is rejected and closed
+    let (mut fourth, _) = connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+    next_of_type(&mut fourth, "welcome").await;
+    send_json(&mut fourth, serde_json::json!({"type": "auth", "token": token})).await;
+    let error = next_of_type(&mut fourth, "error").await;
+    assert_eq!(error["message"], "Connection limit reached");
+    let closed = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match fourth.next().await {
+                Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                _ => {}
+            }
+        }
+    }).await;
+    assert!(closed.is_ok(), "Rejected connection was not closed");
+
+    // Dropping a connection without a close frame still frees its slot
+    drop(open.pop());
+    let mut freed = false;
+    for _ in 0..50 {
+        if counts.get(&user_id).map(|c| *c).unwrap_or(0) < 3 {
+            freed = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(freed, "Connection slot was not released after disconnect");
+    connect_authenticated(addr, &token).await;
+}
+
+#[tokio::test]
+async fn test_validate_reports_syntax_error_with_line() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "typo");
+
+    let code = "class Bot {\n  onTick(game) {\n    game.buildStructure('turret', {x: 1, y: 2};\n  }\n}\nmodule.exports = Bot;";
+    let (status, body) = post_json_with_token(&state, "/api/v1/validate", &token,
+        serde_json::json!({"code": code})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["valid"], false);
+    let error = body["error"].as_str().unwrap();
+    assert!(error.contains("Unexpected token"));
+    assert!(error.contains("main.js:3"));
+}
+
+#[tokio::test]
+async fn test_validate_runtime_error_has_stack_trace() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "thrower");
+
+    let code = "function boom() {\n  throw new Error('kaboom');\n}\nboom();";
+    let (_, body) = post_json_with_token(&state, "/api/v1/validate", &token,
+        serde_json::json!({"code": code})).await;
+    assert_eq!(body["valid"], false);
+    let error = body["error"].as_str().unwrap();
+    assert!(error.contains("kaboom"));
+    assert!(error.contains("at boom (main.js:2"));
+}
+
+#[tokio::test]
+async fn test_validate_returns_commands_without_activating() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "careful");
+    state.game_world.write().await.generate_player_zone("careful").unwrap();
+    state.script_engine.write().await
+        .submit_code("careful".to_string(), "// active bot".to_string())
+        .unwrap();
+
+    let code = "class Bot {\n\
+        onTick(game) {\n\
+            const size = game.getMapSize();\n\
+            console.log('map', size.width);\n\
+            game.buildStructure('turret', {x: 1, y: 2});\n\
+        }\n\
+    }\n\
+    module.exports = Bot;";
+    let (status, body) = post_json_with_token(&state, "/api/v1/validate", &token,
+        serde_json::json!({"code": code})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["valid"], true);
+    assert_eq!(body["logs"], serde_json::json!(["map 30"]));
+    assert_eq!(body["commands"], serde_json::json!([{
+        "action": "buildStructure",
+        "actor": null,
+        "params": {"structureType": "turret", "position": {"x": 1, "y": 2}}
+    }]));
+
+    // The active script and the world are untouched
+    let engine = state.script_engine.read().await;
+    assert_eq!(engine.get_code("careful").unwrap(), "// active bot");
+    assert_eq!(state.game_world.read().await.get_tick(), 0);
+}
+
+#[tokio::test]
+async fn test_validate_uses_stricter_timeout() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "looper");
+
+    let (_, body) = post_json_with_token(&state, "/api/v1/validate", &token,
+        serde_json::json!({"code": "while (true) {}"})).await;
+    assert_eq!(body["valid"], false);
+    assert!(body["error"].as_str().unwrap().contains("time limit of 50ms"));
+}
+
+#[tokio::test]
+async fn test_lobby_start_requires_two_players_and_notifies_members() {
+    let (state, db) = test_state();
+    let owner = create_session(&db, "lobby_owner");
+    let guest = create_session(&db, "lobby_guest");
+
+    let (status, body) = post_json_with_token(&state, "/api/v1/lobbies/create", &owner,
+        serde_json::json!({"name": "Friday duel", "max_players": 2})).await;
+    assert_eq!(status, StatusCode::OK);
+    let lobby_id = body["lobby"]["id"].as_str().unwrap().to_string();
+    assert_eq!(body["lobby"]["status"], "Waiting");
+
+    // A lobby cannot start with fewer than 2 players
+    let start_uri = format!("/api/v1/lobbies/{}/start", lobby_id);
+    let (status, body) = post_json_with_token(&state, &start_uri, &owner, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["message"].as_str().unwrap().contains("At least 2 players"));
+
+    let (status, body) = post_json_with_token(&state,
+        &format!("/api/v1/lobbies/{}/join", lobby_id), &guest, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["lobby"]["players"].as_array().unwrap().len(), 2);
+
+    let addr = spawn_server(state.clone()).await;
+    let mut owner_ws = connect_authenticated(addr, &owner).await;
+    let mut guest_ws = connect_authenticated(addr, &guest).await;
+
+    // Only the owner can start
+    let (status, _) = post_json_with_token(&state, &start_uri, &guest, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = post_json_with_token(&state, &start_uri, &owner, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["lobby"]["status"], "InProgress");
+    let run_id = body["lobby"]["run_id"].as_str().unwrap().to_string();
+
+    for ws in [&mut owner_ws, &mut guest_ws] {
+        let started = next_of_type(ws, "lobbyStarted").await;
+        assert_eq!(started["run_id"], run_id.as_str());
+        assert_eq!(started["lobby_id"], lobby_id.as_str());
+    }
+
+    // The run plays on the match's own world, with a zone per player
+    {
+        let campaigns = campaign_manager();
+        let campaigns = campaigns.read().await;
+        let world = campaigns.world(&run_id).unwrap();
+        assert_eq!(world.get_zone_ids(), vec!["player_lobby_guest_zone".to_string(), "player_lobby_owner_zone".to_string()]);
+    }
+    assert!(!state.game_world.read().await.get_zone_ids().contains(&"player_lobby_owner_zone".to_string()));
+
+    // Stopping the run finishes the lobby
+    let (status, _) = post_json_with_token(&state, "/api/v1/campaign/stop", &owner,
+        serde_json::json!({"run_id": run_id})).await;
+    assert_eq!(status, StatusCode::OK);
+    let lobbies = state.lobby_manager.read().await;
+    assert_eq!(lobbies.get(&lobby_id.parse().unwrap()).unwrap().status, LobbyStatus::Finished);
+}
+
+#[tokio::test]
+async fn test_code_is_bound_to_an_api_version() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "veteran");
+
+    let code_api_version = |state: &AppState| {
+        let (state, token) = (state.clone(), token.clone());
+        async move {
+            let response = get_with_token(&state, "/api/v1/code", Some(&token)).await;
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            body["api_version"].clone()
+        }
+    };
+
+    let (status, _) = post_json_with_token(&state, "/api/v1/submit", &token,
+        serde_json::json!({"code": "game.moveUnit(1, 2, 3);", "api_version": 1})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(code_api_version(&state).await, 1);
+
+    // Raising the minimum stops the bot until it is resubmitted
+    state.script_engine.write().await.set_min_api_version(2);
+    let result = state.script_engine.write().await.execute_player("veteran", &serde_json::json!({"tick": 1})).unwrap();
+    assert!(result.error.unwrap().contains("bound to API v1, which is no longer supported"));
+    let (status, body) = post_json_with_token(&state, "/api/v1/submit", &token,
+        serde_json::json!({"code": "// @api v1\ngame.moveUnit(1, 2, 3);"})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["message"].as_str().unwrap().contains("API v1 is no longer supported"));
+
+    let (status, _) = post_json_with_token(&state, "/api/v1/submit", &token,
+        serde_json::json!({"code": "console.log(game.peekMessages().length);"})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(code_api_version(&state).await, 2);
+    let result = state.script_engine.write().await.execute_player("veteran", &serde_json::json!({"tick": 2})).unwrap();
+    assert_eq!(result.error, None);
+    assert_eq!(result.logs, vec!["0".to_string()]);
+
+    let (status, body) = post_json_with_token(&state, "/api/v1/submit", &token,
+        serde_json::json!({"code": "// bot", "api_version": 9})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["message"].as_str().unwrap().contains("Unsupported API version 9"));
+}
+
+#[tokio::test]
+async fn test_submit_refuses_code_with_many_suspicious_patterns() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "prober");
+
+    // A few matches are only logged
+    let (status, _) = post_json_with_token(&state, "/api/v1/submit", &token,
+        serde_json::json!({"code": "try { eval('1'); } catch (e) {}"})).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let code = "const fs = require('fs');\nprocess.exit(1);\nFunction('return this')();";
+    let (status, body) = post_json_with_token(&state, "/api/v1/submit", &token,
+        serde_json::json!({"code": code})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["message"].as_str().unwrap().contains("3 suspicious patterns"));
+    let engine = state.script_engine.read().await;
+    assert!(engine.get_bundle("prober").unwrap().entry().contains("eval"));
+    drop(engine);
+
+    // The limit follows the configuration
+    state.config.write().unwrap().max_suspicious_patterns = 4;
+    let (status, _) = post_json_with_token(&state, "/api/v1/submit", &token,
+        serde_json::json!({"code": code})).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_submit_rejects_unknown_language() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "polyglot");
+
+    let (status, body) = post_json_with_token(&state, "/api/v1/submit", &token,
+        serde_json::json!({"code": "print('hi')", "language": "cobol"})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["message"].as_str().unwrap().contains("Unsupported language"));
+
+    let (status, _) = post_json_with_token(&state, "/api/v1/submit", &token,
+        serde_json::json!({"code": "// bot", "language": "javascript"})).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let response = get_with_token(&state, "/api/v1/code", Some(&token)).await;
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["language"], "javascript");
+}
+
+#[tokio::test]
+async fn test_admin_tournament_records_results() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["referee".to_string()].into_iter().collect());
+    state.tournaments.write().await.set_max_ticks(20);
+    let admin = create_session(&db, "referee");
+
+    for (name, code) in [
+        ("steady_bot", "game.buildStructure('turret', {x: 1, y: 1});"),
+        ("fragile_bot", "if (game.tick >= 2) { throw new Error('crashed'); }"),
+    ] {
+        let token = create_session(&db, name);
+        let (status, _) = post_json_with_token(&state, "/api/v1/submit", &token,
+            serde_json::json!({"code": code})).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    // Only admins can start tournaments
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/tournament/start",
+        "token-steady_bot", serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = post_json_with_token(&state, "/api/v1/admin/tournament/start",
+        &admin, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    let tournament_id = body["tournament_id"].as_str().unwrap().to_string();
+
+    let status_uri = format!("/api/v1/admin/tournament/{}/status", tournament_id);
+    let mut tournament = serde_json::Value::Null;
+    for _ in 0..100 {
+        let response = get_with_token(&state, &status_uri, Some(&admin)).await;
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        tournament = body["tournament"].clone();
+        if tournament["status"] == "Completed" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(tournament["status"], "Completed");
+
+    let matches = tournament["matches"].as_array().unwrap();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0]["winner"], "steady_bot");
+    assert_eq!(matches[0]["recorded"], true);
+
+    let winner = db.get_user_by_username("steady_bot").unwrap().unwrap();
+    let loser = db.get_user_by_username("fragile_bot").unwrap().unwrap();
+    assert!(winner.rating > 1200);
+    assert!(loser.rating < 1200);
+    assert_eq!(db.get_match_history("steady_bot", 10).unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_round_robin_tournament_standings() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["referee".to_string()].into_iter().collect());
+    let admin = create_session(&db, "referee");
+
+    for (name, code) in [
+        ("steady_bot", "game.buildStructure('turret', {x: 1, y: 1});"),
+        ("idle_bot", "// idle"),
+        ("stalling_bot", "if (game.tick >= 2) { while (true) {} }"),
+    ] {
+        let token = create_session(&db, name);
+        let (status, _) = post_json_with_token(&state, "/api/v1/submit", &token,
+            serde_json::json!({"code": code})).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    // Every participant needs submitted code
+    let (status, body) = post_json_with_token(&state, "/api/v1/admin/tournament", &admin,
+        serde_json::json!({"players": ["steady_bot", "referee"]})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "referee has no submitted code");
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/tournament", "token-idle_bot",
+        serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = post_json_with_token(&state, "/api/v1/admin/tournament", &admin, serde_json::json!({
+        "players": ["steady_bot", "idle_bot", "stalling_bot"],
+        "format": "round_robin",
+        "settings": {"seed": 7, "max_ticks": 10},
+    })).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let tournament_id = body["tournament_id"].as_str().unwrap().to_string();
+
+    // Any player can follow the standings while the tournament runs
+    let uri = format!("/api/v1/tournament/{}", tournament_id);
+    let mut body = serde_json::Value::Null;
+    for _ in 0..200 {
+        let response = get_with_token(&state, &uri, Some("token-idle_bot")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        body = serde_json::from_slice(&bytes).unwrap();
+        if body["tournament"]["status"] == "Completed" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let tournament = &body["tournament"];
+    assert_eq!(tournament["status"], "Completed");
+    assert_eq!(tournament["format"], "round_robin");
+    assert_eq!(tournament["expected_matches"], 3);
+
+    // Three rounds of one match each, the third player sitting out
+    let matches = tournament["matches"].as_array().unwrap();
+    let mut rounds: Vec<u64> = matches.iter().map(|m| m["round"].as_u64().unwrap()).collect();
+    rounds.sort();
+    assert_eq!(rounds, vec![1, 2, 3]);
+
+    // The stalling bot times out and forfeits each match, but plays all of them
+    let standings: Vec<(String, u64, u64, u64, u64, u64)> = body["standings"].as_array().unwrap().iter()
+        .map(|s| (
+            s["player"].as_str().unwrap().to_string(),
+            s["played"].as_u64().unwrap(),
+            s["wins"].as_u64().unwrap(),
+            s["losses"].as_u64().unwrap(),
+            s["forfeits"].as_u64().unwrap(),
+            s["points"].as_u64().unwrap(),
+        ))
+        .collect();
+    // Turrets are not simulated, so the steady bot gains nothing in the world over the idle one: they draw
+    assert_eq!(standings, vec![
+        ("idle_bot".to_string(), 2, 1, 0, 0, 4),
+        ("steady_bot".to_string(), 2, 1, 0, 0, 4),
+        ("stalling_bot".to_string(), 2, 0, 2, 2, 0),
+    ]);
+    for result in matches {
+        if result["player_a"] == "stalling_bot" || result["player_b"] == "stalling_bot" {
+            assert_ne!(result["winner"], "stalling_bot");
+            assert_eq!(result["ticks"], 3);
+        }
+    }
+
+    // Each match keeps a replay from its first to its last tick
+    let response = get_with_token(&state, &format!("{}/matches/0/replay", uri), Some("token-idle_bot")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let replay: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    let ticks = replay["ticks"].as_array().unwrap();
+    assert_eq!(ticks.first().unwrap(), 0);
+    assert_eq!(replay["tick"], *ticks.last().unwrap());
+    assert_eq!(replay["players"].as_object().unwrap().len(), 2);
+    let response = get_with_token(&state, &format!("{}/matches/3/replay", uri), Some("token-idle_bot")).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_first_match_win_unlocks_achievement() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["referee".to_string()].into_iter().collect());
+    state.tournaments.write().await.set_max_ticks(5);
+    let admin = create_session(&db, "referee");
+
+    for (name, code) in [
+        ("winner_bot", "game.buildStructure('turret', {x: 1, y: 1});"),
+        ("loser_bot", "throw new Error('crashed');"),
+    ] {
+        let token = create_session(&db, name);
+        let (status, _) = post_json_with_token(&state, "/api/v1/submit", &token,
+            serde_json::json!({"code": code})).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let winner = db.get_user_by_username("winner_bot").unwrap().unwrap();
+    assert!(db.get_unlocked_achievements(winner.id).unwrap().is_empty());
+
+    let addr = spawn_server(state.clone()).await;
+    let mut ws = connect_authenticated(addr, "token-winner_bot").await;
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/tournament/start",
+        &admin, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+
+    loop {
+        let event = next_of_type(&mut ws, "achievementUnlocked").await;
+        if event["achievement"]["id"] == "first_victory" {
+            assert_eq!(event["achievement"]["condition"]["WinMatches"], 1);
+            break;
+        }
+    }
+
+    let response = get_with_token(&state, "/api/v1/achievements/me", Some("token-winner_bot")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    let unlocked: Vec<&str> = body["unlocked"].as_array().unwrap().iter()
+        .map(|achievement| achievement["id"].as_str().unwrap())
+        .collect();
+    assert!(unlocked.contains(&"first_victory"), "{:?}", unlocked);
+    assert!(unlocked.contains(&"first_blood"), "{:?}", unlocked);
+
+    let loser = db.get_user_by_username("loser_bot").unwrap().unwrap();
+    assert!(!db.get_unlocked_achievements(loser.id).unwrap().contains("first_victory"));
+}
+
+#[tokio::test]
+async fn test_zone_spectator_receives_deltas() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "delta_watcher");
+    let zone_id = state.game_world.write().await.generate_player_zone("delta_player").unwrap();
+
+    let addr = spawn_server(state.clone()).await;
+    let mut ws = connect_authenticated(addr, &token).await;
+
+    send_json(&mut ws, serde_json::json!({"type": "spectate", "zone_id": zone_id})).await;
+    assert_eq!(next_of_type(&mut ws, "spectatorFrame").await["zone"]["id"], zone_id);
+
+    // Steady state: deltas are empty
+    let delta = next_of_type(&mut ws, "spectatorDelta").await;
+    assert_eq!(delta["diff"]["changed_tiles"].as_array().unwrap().len(), 0);
+
+    {
+        let world = state.game_world.write().await;
+        world.with_zone_write(&zone_id, |zone| {
+            let tile = &mut zone.tiles[3][4];
+            tile.surface_type = match tile.surface_type {
+                SurfaceType::Obstacle => SurfaceType::Plain,
+                _ => SurfaceType::Obstacle,
+            };
+        }).unwrap();
+    }
+
+    // The change is sent exactly once
+    let changed = loop {
+        let delta = next_of_type(&mut ws, "spectatorDelta").await;
+        let tiles = delta["diff"]["changed_tiles"].as_array().unwrap().clone();
+        if !tiles.is_empty() {
+            break tiles;
+        }
+    };
+    assert_eq!(changed.len(), 1);
+    assert_eq!((changed[0]["x"].as_u64(), changed[0]["y"].as_u64()), (Some(4), Some(3)));
+
+    let delta = next_of_type(&mut ws, "spectatorDelta").await;
+    assert_eq!(delta["diff"]["changed_tiles"].as_array().unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn test_admin_portal_endpoints() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["world_admin".to_string()].into_iter().collect());
+    let admin = create_session(&db, "world_admin");
+    let player = create_session(&db, "world_player");
+
+    let (zone_a, zone_b) = {
+        let mut world = state.game_world.write().await;
+        (world.generate_player_zone("portal_a").unwrap(), world.generate_player_zone("portal_b").unwrap())
+    };
+    let walkable = |zone_id: &str| {
+        let world = state.game_world.try_read().unwrap();
+        let zone = world.with_zone_read(zone_id, Zone::clone).unwrap();
+        zone.tiles.iter().flatten()
+            .find(|tile| tile.surface_type != SurfaceType::Obstacle)
+            .map(|tile| (tile.x, tile.y))
+            .unwrap()
+    };
+    let (from_x, from_y) = walkable(&zone_a);
+    let (to_x, to_y) = walkable(&zone_b);
+    let body = serde_json::json!({
+        "from_zone_id": zone_a, "from_x": from_x, "from_y": from_y,
+        "to_zone_id": zone_b, "to_x": to_x, "to_y": to_y
+    });
+
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/world/portals", &player, body.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, response) = post_json_with_token(&state, "/api/v1/admin/world/portals", &admin, body).await;
+    assert_eq!(status, StatusCode::OK);
+    let portal_id = response["portal"]["id"].as_str().unwrap().to_string();
+    assert_eq!(state.game_world.read().await.portals().len(), 1);
+
+    let delete = |token: String| {
+        let request = Request::builder()
+            .method("DELETE")
+            .uri(format!("/api/v1/admin/world/portals/{}", portal_id))
+            .header("Authorization", format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        create_router(state.clone()).oneshot(request)
+    };
+    assert_eq!(delete(admin.clone()).await.unwrap().status(), StatusCode::OK);
+    assert!(state.game_world.read().await.portals().is_empty());
+    assert_eq!(delete(admin).await.unwrap().status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_admin_generates_zones_in_bulk() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["bulk_admin".to_string()].into_iter().collect());
+    let admin = create_session(&db, "bulk_admin");
+    let player = create_session(&db, "bulk_player");
+    let uri = "/api/v1/admin/world/generate-bulk";
+
+    let (status, _) = post_json_with_token(&state, uri, &player, serde_json::json!({"count": 10})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    for count in [0, 201] {
+        let (status, response) = post_json_with_token(&state, uri, &admin, serde_json::json!({"count": count})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(response["message"].as_str().unwrap().contains("between 1 and 200"), "{}", response);
+    }
+
+    let (status, response) = post_json_with_token(&state, uri, &admin, serde_json::json!({"count": 10, "connect_exits": true})).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", response);
+    let zone_ids: Vec<String> = serde_json::from_value(response["zone_ids"].clone()).unwrap();
+    let expected: Vec<String> = (1..=10).map(|n| format!("generated_{:04}", n)).collect();
+    assert_eq!(zone_ids, expected);
+
+    // Rows of 4: 7 East-West links and 6 North-South links, with a portal each way
+    assert_eq!(response["portals"].as_array().unwrap().len(), 26);
+    let mut world = state.game_world.write().await;
+    assert_eq!(world.get_zone_ids().len(), 10);
+    assert_eq!(world.portals().len(), 26);
+    let config = world.config().default_zone_config.clone();
+    assert_eq!(world.with_zone_read("generated_0001", Zone::clone).unwrap(), World::generate_grid_zone("generated_0001", &config, true).unwrap());
+
+    let exit = |world: &World, zone_id: &str, direction: ExitDirection| {
+        world.with_zone_read(zone_id, |zone| {
+            let exit = zone.exits.iter().find(|exit| exit.direction == direction).unwrap();
+            (exit.x, exit.y)
+        }).unwrap()
+    };
+    let (x, y) = world.place_player_start("bulk_player", "generated_0001").unwrap();
+    let unit_id = world.with_zone_read("generated_0001", |zone| {
+        zone.entities.iter().find(|entity| (entity.x, entity.y) == (x, y)).unwrap().id
+    }).unwrap();
+
+    // North into the next row, then East along it, then back South
+    let (x, y) = exit(&world, "generated_0001", ExitDirection::North);
+    let (zone_id, unit_id, x, y) = world.move_entity("generated_0001", unit_id, x, y).unwrap();
+    assert_eq!((zone_id.as_str(), (x, y)), ("generated_0005", exit(&world, "generated_0005", ExitDirection::South)));
+    let (x, y) = exit(&world, "generated_0005", ExitDirection::East);
+    let (zone_id, unit_id, x, y) = world.move_entity("generated_0005", unit_id, x, y).unwrap();
+    assert_eq!((zone_id.as_str(), (x, y)), ("generated_0006", exit(&world, "generated_0006", ExitDirection::West)));
+    let (x, y) = exit(&world, "generated_0006", ExitDirection::South);
+    let (zone_id, _, x, y) = world.move_entity("generated_0006", unit_id, x, y).unwrap();
+    assert_eq!((zone_id.as_str(), (x, y)), ("generated_0002", exit(&world, "generated_0002", ExitDirection::North)));
+    drop(world);
+
+    // Numbering continues after the existing zones
+    let (status, response) = post_json_with_token(&state, uri, &admin, serde_json::json!({"count": 2})).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", response);
+    assert_eq!(response["zone_ids"], serde_json::json!(["generated_0011", "generated_0012"]));
+    assert!(response["portals"].as_array().unwrap().is_empty());
+    assert_eq!(state.game_world.read().await.portals().len(), 26);
+
+    state.config.write().unwrap().features.portals_enabled = false;
+    let (status, _) = post_json_with_token(&state, uri, &admin, serde_json::json!({"count": 2, "connect_exits": true})).await;
+    assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+}
+
+#[tokio::test]
+async fn test_zone_stream_replica_tracks_state_and_resyncs() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "replica_watcher");
+    let zone_id = state.game_world.write().await.generate_player_zone("replica_player").unwrap();
+
+    let addr = spawn_server(state.clone()).await;
+    let mut ws = connect_authenticated(addr, &token).await;
+    send_json(&mut ws, serde_json::json!({"type": "spectate", "zone_id": zone_id})).await;
+
+    let mut replica = StateReplica::new();
+    let keyframe = next_of_type(&mut ws, "spectatorFrame").await;
+    assert_eq!(keyframe["seq"], 0);
+    replica.apply_keyframe(0, serde_json::from_value(keyframe).unwrap());
+
+    {
+        let mut world = state.game_world.write().await;
+        world.with_zone_write(&zone_id, |zone| zone.entities.push(EntityRef {
+            id: 1,
+            kind: "worker".to_string(),
+            owner: Some("replica_player".to_string()),
+            x: 2,
+            y: 3,
+            hits: DEFAULT_ENTITY_HITS,
+            can_swim: false,
+            can_fly: false,
+        })).unwrap();
+        world.advance_tick();
+    }
+
+    // Deltas follow each other and rebuild the latest state
+    for _ in 0..3 {
+        let frame = next_of_type(&mut ws, "spectatorDelta").await;
+        let seq = frame["seq"].as_u64().unwrap();
+        replica.apply_delta(seq, &serde_json::from_value(frame).unwrap()).unwrap();
+    }
+    let latest = {
+        let world = state.game_world.read().await;
+        world.with_zone_read(&zone_id, |zone| SyncState::new(world.get_tick(), zone)).unwrap()
+    };
+    assert_eq!(replica.state(), Some(&latest));
+
+    // A resync request gets a fresh keyframe with the next sequence number
+    send_json(&mut ws, serde_json::json!({"type": "resync"})).await;
+    let keyframe = next_of_type(&mut ws, "spectatorFrame").await;
+    assert!(keyframe["seq"].as_u64().unwrap() > replica.seq().unwrap());
+    assert_eq!(keyframe["zone"]["entities"][0]["id"], 1);
+}
+
+/// Read the next binary frame and decode it as MessagePack
+async fn next_msgpack(ws: &mut WsClient) -> serde_json::Value {
+    loop {
+        let msg = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .expect("Timed out waiting for WebSocket message")
+            .expect("WebSocket closed")
+            .expect("WebSocket error");
+        match msg {
+            Message::Binary(bytes) => return rmp_serde::from_slice(&bytes).unwrap(),
+            Message::Text(text) => panic!("Expected a binary frame, got {}", text),
+            _ => {}
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_msgpack_encoding_matches_json() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "packer");
+    let zone_id = state.game_world.write().await.generate_player_zone("packer").unwrap();
+
+    let addr = spawn_server(state.clone()).await;
+    let mut json_ws = connect_authenticated(addr, &token).await;
+
+    let (mut ws, _) = connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+    assert_eq!(next_json(&mut ws).await["type"], "welcome");
+    send_json(&mut ws, serde_json::json!({"type": "auth", "token": token, "encoding": "msgpack"})).await;
+    let auth = next_msgpack(&mut ws).await;
+    assert_eq!((auth["type"].as_str(), auth["success"].as_bool()), (Some("authResponse"), Some(true)));
+
+    // Same response, field for field, in both encodings
+    send_json(&mut json_ws, serde_json::json!({"type": "getGameState"})).await;
+    let expected = next_of_type(&mut json_ws, "gameStateResponse").await;
+    send_json(&mut ws, serde_json::json!({"type": "getGameState"})).await;
+    assert_eq!(next_msgpack(&mut ws).await, expected);
+
+    // Commands can be sent as MessagePack; malformed frames get an error
+    ws.send(Message::Binary(vec![0xc1, 0x00])).await.unwrap();
+    let error = next_msgpack(&mut ws).await;
+    assert_eq!(error["type"], "error");
+    assert!(error["message"].as_str().unwrap().contains("Malformed binary frame"));
+
+    let command = rmp_serde::to_vec_named(&serde_json::json!({"type": "spectate", "zone_id": zone_id})).unwrap();
+    ws.send(Message::Binary(command)).await.unwrap();
+    assert_eq!(next_msgpack(&mut ws).await["type"], "spectateResponse");
+
+    // Pushed frames use the connection's encoding
+    let frame = next_msgpack(&mut ws).await;
+    assert_eq!(frame["type"], "spectatorFrame");
+    assert_eq!(frame["zone"]["id"], zone_id);
+
+    send_json(&mut ws, serde_json::json!({"type": "spectate", "zone_id": zone_id, "encoding": "xml"})).await;
+    let error = loop {
+        let msg = next_msgpack(&mut ws).await;
+        if msg["type"] == "error" {
+            break msg;
+        }
+    };
+    assert!(error["message"].as_str().unwrap().contains("Unsupported encoding"));
+}
+
+#[tokio::test]
+async fn test_admin_schedules_weather() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["weather_admin".to_string()].into_iter().collect());
+    let admin = create_session(&db, "weather_admin");
+    let player = create_session(&db, "weather_player");
+    let zone_id = state.game_world.write().await.generate_player_zone("weather_player").unwrap();
+
+    let body = serde_json::json!({
+        "event_type": "Fog", "affected_zone_id": zone_id, "duration_ticks": 5, "magnitude": 0.5
+    });
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/world/weather", &player, body.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, response) = post_json_with_token(&state, "/api/v1/admin/world/weather", &admin, body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["event"]["start_tick"], 0);
+    assert_eq!(state.game_world.read().await.visibility_radius(&zone_id), 5);
+
+    let body = serde_json::json!({
+        "event_type": "Storm", "affected_zone_id": "missing", "duration_ticks": 5, "magnitude": 0.5
+    });
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/world/weather", &admin, body).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_disabled_features_answer_not_implemented() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["feature_admin".to_string()].into_iter().collect());
+    let admin = create_session(&db, "feature_admin");
+    let zone_id = state.game_world.write().await.generate_player_zone("feature_admin").unwrap();
+    let body = serde_json::json!({
+        "event_type": "Rain", "affected_zone_id": zone_id, "duration_ticks": 5, "magnitude": 0.5
+    });
+
+    state.config.write().unwrap().features.weather_enabled = false;
+    assert!(!state.is_feature_enabled(FeatureFlag::Weather));
+    assert!(state.is_feature_enabled(FeatureFlag::Portals));
+    let (status, response) = post_json_with_token(&state, "/api/v1/admin/world/weather", &admin, body.clone()).await;
+    assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+    assert_eq!(response["code"], "feature_disabled");
+    assert!(state.game_world.read().await.weather_events().is_empty());
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/world/weather", "not-a-session", body.clone()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    state.config.write().unwrap().features.weather_enabled = true;
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/world/weather", &admin, body).await;
+    assert_eq!(status, StatusCode::OK);
+
+    state.config.write().unwrap().features.tournament_enabled = false;
+    let response = get_with_token(&state, "/api/v1/tournament/missing", Some(&admin)).await;
+    assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+}
+
+#[tokio::test]
+async fn test_zone_generate_custom_config_requires_admin() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["map_admin".to_string()].into_iter().collect());
+    let admin = create_session(&db, "map_admin");
+    let player = create_session(&db, "map_player");
+
+    let config = serde_json::json!({
+        "width": 48, "height": 16, "plain_ratio": 0.5, "swamp_ratio": 0.2, "obstacle_ratio": 0.3,
+        "min_exits": 1, "max_exits": 2
+    });
+    let body = serde_json::json!({"player_id": "arena", "config": config});
+
+    let (status, _) = post_json(&state, "/api/v1/zone/generate", body.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = post_json_with_token(&state, "/api/v1/zone/generate", &player, body.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, response) = post_json_with_token(&state, "/api/v1/zone/generate", &admin, body).await;
+    assert_eq!(status, StatusCode::OK);
+    let zone_id = response["zone_id"].as_str().unwrap();
+    {
+        let world = state.game_world.read().await;
+        let zone = world.with_zone_read(zone_id, Zone::clone).unwrap();
+        assert_eq!((zone.width, zone.height), (48, 16));
+        assert!((1..=2).contains(&zone.exits.len()));
+    }
+
+    let bad = serde_json::json!({"player_id": "bad", "config": {
+        "width": 48, "height": 16, "plain_ratio": 0.5, "swamp_ratio": 0.5, "obstacle_ratio": 0.3,
+        "min_exits": 1, "max_exits": 2
+    }});
+    let (status, response) = post_json_with_token(&state, "/api/v1/zone/generate", &admin, bad).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(response["message"].as_str().unwrap().contains("sum to 1"));
+
+    // Without a config the endpoint stays public
+    let (status, _) = post_json(&state, "/api/v1/zone/generate", serde_json::json!({"player_id": "plain"})).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_zone_generate_refuses_locked_zones() {
+    let (state, _db) = test_state();
+    let zone_id = World::player_zone_id("climber");
+    state.game_world.write().await.set_zone_unlock_requirements(&zone_id, vec!["tutorial".to_string()]);
+
+    let body = serde_json::json!({"player_id": "climber"});
+    let (status, response) = post_json(&state, "/api/v1/zone/generate", body.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(response["message"].as_str().unwrap().contains("tutorial"));
+    assert!(state.game_world.read().await.with_zone_read(&zone_id, |_| ()).is_none());
+
+    state.game_world.write().await.mark_zone_cleared("climber", "tutorial");
+    let (status, response) = post_json(&state, "/api/v1/zone/generate", body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["zone_id"], zone_id.as_str());
+}
+
+#[tokio::test]
+async fn test_admin_zone_edits_apply_all_or_none() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["map_admin".to_string()].into_iter().collect());
+    let admin = create_session(&db, "map_admin");
+    let player = create_session(&db, "map_player");
+
+    // An all-Plain zone with exits on its West and East edges, and a worker of alice
+    let zone_id = World::player_zone_id("alice");
+    let mut zone = Zone::generate(zone_id.clone(), 1);
+    for tile in zone.tiles.iter_mut().flatten() {
+        tile.surface_type = SurfaceType::Plain;
+    }
+    zone.exits = vec![
+        Exit { x: 0, y: 15, direction: ExitDirection::West },
+        Exit { x: ZONE_SIZE - 1, y: 15, direction: ExitDirection::East },
+    ];
+    zone.entities.push(EntityRef {
+        id: 1, kind: "worker".to_string(), owner: Some("alice".to_string()), x: 2, y: 2,
+        hits: DEFAULT_ENTITY_HITS, can_swim: false, can_fly: false,
+    });
+    {
+        let mut world = state.game_world.write().await;
+        world.add_zone(zone);
+        assert_eq!(world.player_snapshot("alice")["obstacles"], serde_json::json!([]));
+    }
+    let tiles_uri = format!("/api/v1/admin/zone/{}/tiles", zone_id);
+    let get_zone = || async {
+        let response = get_with_token(&state, &format!("/api/v1/zone/{}", zone_id), None).await;
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()["zone"].clone()
+    };
+    let before = get_zone().await;
+
+    let (status, _) = send_json_with_token(&state, "PATCH", &tiles_uri, &player, serde_json::json!({"changes": []})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // A wall across the zone would cut the exits off from each other: nothing is applied
+    let mut wall: Vec<serde_json::Value> = (0..ZONE_SIZE)
+        .map(|y| serde_json::json!({"op": "set_tile", "x": 10, "y": y, "surface_type": "Obstacle"}))
+        .collect();
+    wall.push(serde_json::json!({"op": "add_resource", "x": 3, "y": 3, "amount": 500}));
+    let (status, response) = send_json_with_token(&state, "PATCH", &tiles_uri, &admin, serde_json::json!({"changes": wall})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(response["rejected"].as_array().unwrap().len(), ZONE_SIZE);
+    assert!(response["rejected"][0]["reason"].as_str().unwrap().contains("disconnected"));
+    assert_eq!(get_zone().await, before);
+
+    // Exit edits go through their own route
+    let exit = serde_json::json!({"op": "add_exit", "x": 7, "y": 0, "direction": "North"});
+    let (status, response) = send_json_with_token(&state, "PATCH", &tiles_uri, &admin, serde_json::json!({"changes": [exit]})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(response["rejected"][0]["edit"], exit);
+
+    let changes = serde_json::json!([
+        {"op": "set_tile", "x": 10, "y": 5, "surface_type": "Obstacle"},
+        {"op": "add_resource", "x": 3, "y": 3, "amount": 500}
+    ]);
+    let (status, response) = send_json_with_token(&state, "PATCH", &tiles_uri, &admin, serde_json::json!({"changes": changes})).await;
+    assert_eq!(status, StatusCode::OK, "{}", response);
+    let (status, _) = send_json_with_token(&state, "PATCH", &format!("/api/v1/admin/zone/{}/exits", zone_id), &admin, serde_json::json!({"changes": [exit]})).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let after = get_zone().await;
+    assert_eq!(&after["tiles"][5].as_str().unwrap()[10..11], "O");
+    assert_eq!(after["resources"], serde_json::json!([{"x": 3, "y": 3, "amount": 500}]));
+    assert_eq!(after["exits"].as_array().unwrap().len(), 3);
+    let snapshot = state.game_world.read().await.player_snapshot("alice");
+    assert_eq!(snapshot["obstacles"], serde_json::json!([{"x": 10, "y": 5}]));
+
+    let (status, _) = send_json_with_token(&state, "PATCH", "/api/v1/admin/zone/missing/tiles", &admin, serde_json::json!({"changes": changes})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_zone_export_and_admin_import() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["map_admin".to_string()].into_iter().collect());
+    let admin = create_session(&db, "map_admin");
+    let player = create_session(&db, "map_player");
+    let zone_id = state.game_world.write().await.generate_player_zone("map_player").unwrap();
+
+    let response = get_with_headers(&state, &format!("/api/v1/zone/{}/export", zone_id), &player, &[]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("Content-Disposition").unwrap().to_str().unwrap().starts_with("attachment"));
+    let document: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+    assert_eq!(document["format"], "geekcraft-zone");
+    assert!(document.get("entities").is_none());
+
+    let (status, _) = post_json(&state, "/api/v1/zone/import", document.clone()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = post_json_with_token(&state, "/api/v1/zone/import", &player, document.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let mut tampered = document.clone();
+    tampered["exits"] = serde_json::json!([]);
+    let (status, response) = post_json_with_token(&state, "/api/v1/zone/import", &admin, tampered).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(response["message"].as_str().unwrap().contains("checksum"), "{}", response);
+
+    let (status, response) = post_json_with_token(&state, "/api/v1/zone/import", &admin, document).await;
+    assert_eq!(status, StatusCode::OK, "{}", response);
+    let imported_id = response["zone_id"].as_str().unwrap();
+    let world = state.game_world.read().await;
+    assert_eq!(world.with_zone_read(imported_id, |zone| zone.tiles.clone()), world.with_zone_read(&zone_id, |zone| zone.tiles.clone()));
+}
+
+#[tokio::test]
+async fn test_world_config_endpoint() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "cartographer");
+
+    let response = get_with_token(&state, "/api/v1/world/config", Some(&token)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let config: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(config["width"], 100);
+    assert_eq!(config["height"], 100);
+    assert_eq!(config["max_zones"], 1000);
+    assert_eq!(config["default_zone_config"]["width"], 30);
+}
+
+#[tokio::test]
+async fn test_zone_capture_broadcasts_new_owner() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "conqueror");
+    let watcher = create_session(&db, "watcher");
+
+    let zone_id = {
+        let mut world = state.game_world.write().await;
+        let zone_id = world.generate_player_zone("frontier").unwrap();
+        world.with_zone_write(&zone_id, |zone| zone.entities.push(EntityRef {
+            id: 1,
+            kind: "soldier".to_string(),
+            owner: Some("conqueror".to_string()),
+            x: 0,
+            y: 0,
+            hits: DEFAULT_ENTITY_HITS,
+            can_swim: false,
+            can_fly: false,
+        })).unwrap();
+        zone_id
+    };
+    let owner_uri = format!("/api/v1/zones/{}/owner", zone_id);
+    let capture_uri = format!("/api/v1/zones/{}/capture", zone_id);
+
+    let response = get_with_token(&state, &owner_uri, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert!(body["owner"].is_null());
+
+    let (status, _) = post_json(&state, &capture_uri, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = post_json_with_token(&state, &capture_uri, &watcher, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["success"], false);
+
+    let addr = spawn_server(state.clone()).await;
+    let mut ws = connect_authenticated(addr, &watcher).await;
+    let (status, body) = post_json_with_token(&state, &capture_uri, &token, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["owner"], "conqueror");
+
+    let event = next_of_type(&mut ws, "zoneCaptured").await;
+    assert_eq!(event["zone_id"], zone_id.as_str());
+    assert_eq!(event["new_owner"], "conqueror");
+
+    let response = get_with_token(&state, &owner_uri, None).await;
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["owner"], "conqueror");
+}
+
+#[tokio::test]
+async fn test_registration_assigns_a_zone() {
+    let (state, _db) = test_state();
+    let store = Arc::new(InMemoryWorldStore::new());
+    *state.game_world.write().await = World::open(WorldConfig::default(), store.clone()).unwrap();
+
+    let credentials = serde_json::json!({"username": "settler", "password": "secret123"});
+    let (status, body) = post_json(&state, "/api/auth/register", credentials.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["success"], true);
+    let zone_id = store.load_zone_assignments().unwrap().values().next().cloned().expect("zone assigned");
+    assert!(state.game_world.read().await.with_zone_read(&zone_id, |_| ()).is_some());
+
+    // A deleted zone is recreated with the same ID at the next login
+    state.game_world.write().await.remove_zone(&zone_id).unwrap();
+    assert!(store.load_all_zones().unwrap().is_empty());
+    let (_, body) = post_json(&state, "/api/auth/login", credentials).await;
+    assert_eq!(body["success"], true);
+    assert!(state.game_world.read().await.with_zone_read(&zone_id, |_| ()).is_some());
+    assert_eq!(store.load_all_zones().unwrap().len(), 1);
+
+    let token = body["token"].as_str().unwrap().to_string();
+    let response = get_with_token(&state, "/api/v1/zone/mine", Some(&token)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["zone"]["id"], zone_id.as_str());
+    assert_eq!(get_with_token(&state, "/api/v1/zone/mine", None).await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_users_never_share_a_zone() {
+    let (state, db) = test_state();
+    let mut zone_ids = Vec::new();
+    for name in ["north", "south", "north_", "north-"] {
+        let token = create_session(&db, name);
+        let response = get_with_token(&state, "/api/v1/zone/mine", Some(&token)).await;
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        zone_ids.push(body["zone"]["id"].as_str().unwrap().to_string());
+    }
+
+    let mut unique = zone_ids.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), zone_ids.len(), "{:?}", zone_ids);
+}
+
+#[tokio::test]
+async fn test_zone_chat_reaches_only_players_in_zone() {
+    let (state, db) = test_state();
+    let alice = create_session(&db, "alice");
+    let bob = create_session(&db, "bob");
+    let carol = create_session(&db, "carol");
+
+    let zone_id = {
+        let mut world = state.game_world.write().await;
+        let zone_id = world.generate_player_zone("meadow").unwrap();
+        let other_zone = world.generate_player_zone("marsh").unwrap();
+        for (id, owner, zone) in [(1, "alice", &zone_id), (2, "bob", &zone_id), (3, "carol", &other_zone)] {
+            world.with_zone_write(zone, |zone| zone.entities.push(EntityRef {
+                id,
+                kind: "worker".to_string(),
+                owner: Some(owner.to_string()),
+                x: 0,
+                y: 0,
+                hits: DEFAULT_ENTITY_HITS,
+                can_swim: false,
+                can_fly: false,
+            })).unwrap();
+        }
+        zone_id
+    };
+
+    let addr = spawn_server(state).await;
+    let mut alice_ws = connect_authenticated(addr, &alice).await;
+    let mut bob_ws = connect_authenticated(addr, &bob).await;
+    let mut carol_ws = connect_authenticated(addr, &carol).await;
+
+    send_json(&mut alice_ws, serde_json::json!({
+        "type": "chat", "channel": "zone", "message": "<b>rush</b> the north exit"
+    })).await;
+    let response = next_of_type(&mut alice_ws, "chatResponse").await;
+    assert_eq!(response["success"], true);
+    assert_eq!(response["delivered"], 2);
+
+    let chat = next_of_type(&mut bob_ws, "chat").await;
+    assert_eq!(chat["from"], "alice");
+    assert_eq!(chat["channel"], "zone");
+    assert_eq!(chat["message"], "rush the north exit");
+
+    // Carol is in another zone: the next message she gets is her own history reply
+    send_json(&mut carol_ws, serde_json::json!({"type": "getChatHistory", "channel": "zone"})).await;
+    let history = next_json(&mut carol_ws).await;
+    assert_eq!(history["type"], "chatHistory");
+    assert_eq!(history["messages"], serde_json::json!([]));
+
+    send_json(&mut bob_ws, serde_json::json!({"type": "getChatHistory", "channel": "zone", "zoneId": zone_id})).await;
+    let history = next_of_type(&mut bob_ws, "chatHistory").await;
+    assert_eq!(history["messages"][0]["message"], "rush the north exit");
+
+    send_json(&mut carol_ws, serde_json::json!({"type": "chat", "channel": "team", "message": "anyone?"})).await;
+    let response = next_of_type(&mut carol_ws, "chatResponse").await;
+    assert_eq!(response["success"], false);
+}
+
+#[tokio::test]
+async fn test_team_endpoints_share_resource_pool() {
+    let (state, db) = test_state();
+    let leader = create_session(&db, "team_leader");
+    let member = create_session(&db, "team_member");
+    let outsider = create_session(&db, "team_outsider");
+    let member_id = db.get_user_by_username("team_member").unwrap().unwrap().id;
+
+    let (status, body) = post_json_with_token(&state, "/api/v1/teams/create", &leader,
+        serde_json::json!({"name": "Red"})).await;
+    assert_eq!(status, StatusCode::OK);
+    let team_id = body["team"]["id"].as_str().unwrap().to_string();
+
+    // Only members can invite
+    let invite_uri = format!("/api/v1/teams/{}/invite/{}", team_id, member_id);
+    let (status, _) = post_json_with_token(&state, &invite_uri, &outsider, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, body) = post_json_with_token(&state, &invite_uri, &leader, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["team"]["members"].as_array().unwrap().len(), 2);
+
+    {
+        let mut world = state.game_world.write().await;
+        world.deposit_resources("team_leader", ResourceType::Gas, 10);
+        world.deposit_resources("team_member", ResourceType::Gas, 5);
+        assert_eq!(world.stockpile("team_leader"), world.stockpile("team_member"));
+    }
+
+    let status_uri = format!("/api/v1/teams/{}/status", team_id);
+    let response = get_with_token(&state, &status_uri, Some(&member)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["members"], serde_json::json!(["team_leader", "team_member"]));
+    assert_eq!(body["resource_pool"]["gas"], 15);
+    assert_eq!(body["controlled_zones"], 0);
+
+    let response = get_with_token(&state, &status_uri, Some(&outsider)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // The pool stays with the team when a member leaves
+    let (status, _) = post_json_with_token(&state, &format!("/api/v1/teams/{}/leave", team_id), &member, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    let world = state.game_world.read().await;
+    assert!(world.stockpile("team_member").is_empty());
+    assert_eq!(world.stockpile("team_leader")[&ResourceType::Gas], 15);
+}
+
+#[tokio::test]
+async fn test_scripting_types_are_public() {
+    let (state, _) = test_state();
+
+    let response = get_with_token(&state, "/api/v1/scripting/types.d.ts", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/typescript; charset=utf-8");
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let types = String::from_utf8(bytes.to_vec()).unwrap();
+    for name in ["GameAPI", "Unit", "ZoneInfo", "ResourceNode", "BotCommand"] {
+        assert!(types.contains(&format!("interface {} {{", name)), "{} is not declared", name);
+    }
+}
+
+#[tokio::test]
+async fn test_messages_inbox_is_peeked() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "inbox_reader");
+    {
+        let mut engine = state.script_engine.write().await;
+        engine.submit_code("inbox_reader".to_string(), "// bot".to_string()).unwrap();
+        engine.begin_tick(1);
+        engine.send_message("inbox_sender", "inbox_reader", serde_json::json!({"hello": true})).unwrap();
+        engine.begin_tick(2);
+    }
+
+    for _ in 0..2 {
+        let response = get_with_token(&state, "/api/v1/messages", Some(&token)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["messages"], serde_json::json!([{"from": "inbox_sender", "payload": {"hello": true}, "sent_at_tick": 1}]));
+    }
+
+    let response = get_with_token(&state, "/api/v1/messages", None).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_script_modules_endpoints() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "module_author");
+
+    let (status, body) = post_json_with_token(&state, "/api/v1/scripts/modules", &token,
+        serde_json::json!({"name": "utils.js", "code": "export const x = 1;"})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["message"].as_str().unwrap().contains("main.js"));
+
+    for (name, code) in [("main.js", "import { x } from './lib/utils';"), ("lib/utils.js", "export const x = 1;")] {
+        let (status, body) = post_json_with_token(&state, "/api/v1/scripts/modules", &token,
+            serde_json::json!({"name": name, "code": code})).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    let response = get_with_token(&state, "/api/v1/scripts/modules", Some(&token)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["modules"], serde_json::json!([{"name": "lib/utils.js", "size": 19}, {"name": "main.js", "size": 32}]));
+
+    let delete = |uri: &'static str| {
+        let request = Request::builder()
+            .method("DELETE")
+            .uri(uri)
+            .header("Authorization", format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        create_router(state.clone()).oneshot(request)
+    };
+    let response = delete("/api/v1/scripts/modules/lib/utils.js").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = delete("/api/v1/scripts/modules/lib/utils.js").await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = delete("/api/v1/scripts/modules/main.js").await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(state.script_engine.read().await.get_bundle("module_author").unwrap().modules().len(), 1);
+}
+
+#[tokio::test]
+async fn test_shared_library_approval() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["library_admin".to_string()].into_iter().collect());
+    let author = create_session(&db, "library_author");
+    let admin = create_session(&db, "library_admin");
+
+    let (status, body) = post_json_with_token(&state, "/api/v1/scripts/libraries", &author,
+        serde_json::json!({"name": "priority-queue", "code": "export const pq = 1;"})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["library"]["version"], 1);
+    let library_id = body["library"]["id"].as_str().unwrap().to_string();
+
+    let list = |token: String| {
+        let state = state.clone();
+        async move {
+            let response = get_with_token(&state, "/api/v1/scripts/libraries", Some(&token)).await;
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+        }
+    };
+    assert_eq!(list(author.clone()).await["libraries"], serde_json::json!([]));
+
+    let approve_uri = format!("/api/v1/admin/scripts/libraries/{}/approve", library_id);
+    let (status, _) = post_json_with_token(&state, &approve_uri, &author, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = post_json_with_token(&state, &approve_uri, &admin, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["library"]["approved"], true);
+
+    let libraries = list(author.clone()).await["libraries"].clone();
+    assert_eq!(libraries.as_array().unwrap().len(), 1);
+    assert_eq!(libraries[0]["name"], "priority-queue");
+    assert_eq!(state.script_engine.read().await.libraries()["priority-queue"], "export const pq = 1;");
+}
+
+#[tokio::test]
+async fn test_alliance_invite_accept_and_leave() {
+    let (mut state, db) = test_state();
+    state.auth_service = Arc::new(AuthService::new(db.clone()).with_max_alliance_size(2));
+    let leader = create_session(&db, "ally_leader");
+    let member = create_session(&db, "ally_member");
+    let late = create_session(&db, "ally_late");
+
+    let (status, body) = post_json_with_token(&state, "/api/v1/alliance/create", &leader,
+        serde_json::json!({"name": "North"})).await;
+    assert_eq!(status, StatusCode::OK);
+    let alliance_id = body["alliance"]["id"].as_str().unwrap().to_string();
+
+    // Joining needs an invitation
+    let accept = serde_json::json!({"alliance_id": alliance_id});
+    let (status, _) = post_json_with_token(&state, "/api/v1/alliance/accept", &member, accept.clone()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    for username in ["ally_member", "ally_late"] {
+        let (status, _) = post_json_with_token(&state, "/api/v1/alliance/invite", &leader,
+            serde_json::json!({"username": username})).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (status, body) = post_json_with_token(&state, "/api/v1/alliance/accept", &member, accept.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["alliance"]["members"].as_array().unwrap().len(), 2);
+    assert!(state.game_world.read().await.are_allied("ally_leader", "ally_member"));
+
+    // The alliance is full
+    let (status, body) = post_json_with_token(&state, "/api/v1/alliance/accept", &late, accept).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["success"], false);
+
+    // Allies see each other's zones in detail
+    {
+        let mut world = state.game_world.write().await;
+        let zone_id = world.generate_player_zone("ally_leader").unwrap();
+        world.with_zone_write(&zone_id, |zone| zone.owner = Some("ally_leader".to_string())).unwrap();
+    }
+    for (token, detailed) in [(&member, true), (&late, false)] {
+        let response = get_with_token(&state, "/api/v1/map", Some(token)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["zones"][0]["owner"], "ally_leader");
+        assert_eq!(body["zones"][0]["units"].is_u64(), detailed);
+    }
+
+    // Leaving is applied at the end of the tick
+    let (status, body) = post_json_with_token(&state, "/api/v1/alliance/leave", &member, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["alliance"]["members"].as_array().unwrap().len(), 1);
+    let mut world = state.game_world.write().await;
+    assert!(world.are_allied("ally_leader", "ally_member"));
+    world.advance_tick();
+    assert!(!world.are_allied("ally_leader", "ally_member"));
+}
+
+#[tokio::test]
+async fn test_friendship_requires_request_and_accept() {
+    let (state, db) = test_state();
+    let alice = create_session(&db, "friend_alice");
+    let bob = create_session(&db, "friend_bob");
+    let carol = create_session(&db, "friend_carol");
+
+    let friends_of = |token: String| {
+        let state = state.clone();
+        async move {
+            let response = get_with_token(&state, "/api/v1/friends", Some(&token)).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+        }
+    };
+
+    // Accepting without a pending request cannot add a friend unilaterally
+    let (status, _) = post_json_with_token(&state, "/api/v1/friends/accept/friend_bob", &alice, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = post_json_with_token(&state, "/api/v1/friends/request/friend_alice", &alice, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = post_json_with_token(&state, "/api/v1/friends/request/friend_bob", &alice, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    // The sender cannot accept their own request
+    let (status, _) = post_json_with_token(&state, "/api/v1/friends/accept/friend_bob", &alice, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let body = friends_of(alice.clone()).await;
+    assert!(body["friends"].as_array().unwrap().is_empty());
+    assert_eq!(friends_of(bob.clone()).await["incoming_requests"], serde_json::json!(["friend_alice"]));
+
+    let (status, _) = post_json_with_token(&state, "/api/v1/friends/accept/friend_alice", &bob, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    let body = friends_of(alice.clone()).await;
+    assert_eq!(body["friends"], serde_json::json!([{"username": "friend_bob", "online": true}]));
+    assert_eq!(friends_of(bob.clone()).await["friends"][0]["username"], "friend_alice");
+
+    // Friends whose sessions have expired are offline
+    let (status, _) = post_json_with_token(&state, "/api/v1/friends/request/friend_carol", &alice, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = post_json_with_token(&state, "/api/v1/friends/accept/friend_alice", &carol, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    let carol_id = db.get_user_by_username("friend_carol").unwrap().unwrap().id;
+    db.delete_session(&carol).unwrap();
+    db.create_session(&carol, carol_id, chrono::Utc::now().timestamp() - 10).unwrap();
+    let body = friends_of(alice.clone()).await;
+    assert_eq!(body["friends"][1], serde_json::json!({"username": "friend_carol", "online": false}));
+
+    let request = Request::builder()
+        .method("DELETE")
+        .uri("/api/v1/friends/friend_alice")
+        .header("Authorization", format!("Bearer {}", bob))
+        .body(Body::empty())
+        .unwrap();
+    let response = create_router(state.clone()).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(friends_of(alice.clone()).await["friends"].as_array().unwrap().len(), 1);
+    assert!(friends_of(bob).await["friends"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_players_and_zones_are_paginated() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "pager");
+    {
+        let mut engine = state.script_engine.write().await;
+        let mut world = state.game_world.write().await;
+        for i in 0..150 {
+            let player_id = format!("paged_{:03}", i);
+            engine.submit_code(player_id.clone(), "// idle".to_string()).unwrap();
+            world.generate_player_zone(&player_id).unwrap();
+        }
+    }
+
+    let page_of = |uri: &'static str| {
+        let state = state.clone();
+        let token = token.clone();
+        async move {
+            let response = get_with_token(&state, uri, Some(&token)).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+        }
+    };
+
+    let body = page_of("/api/v1/players?format=ids&page=2&per_page=50").await;
+    let items = body["items"].as_array().unwrap();
+    assert_eq!(items.len(), 50);
+    assert_eq!(items[0], "paged_050");
+    assert_eq!((body["page"].as_u64(), body["per_page"].as_u64()), (Some(2), Some(50)));
+    assert_eq!((body["total"].as_u64(), body["total_pages"].as_u64()), (Some(150), Some(3)));
+
+    let body = page_of("/api/v1/zones?page=2&per_page=50").await;
+    assert_eq!(body["items"].as_array().unwrap().len(), 50);
+    assert_eq!(body["items"][0], "player_paged_050_zone");
+    assert_eq!((body["total"].as_u64(), body["total_pages"].as_u64()), (Some(150), Some(3)));
+
+    // per_page is capped
+    let body = page_of("/api/v1/players?format=ids&per_page=500").await;
+    assert_eq!(body["per_page"], 200);
+    assert_eq!(body["items"].as_array().unwrap().len(), 150);
+}
+
+#[tokio::test]
+async fn test_players_lists_profiles_of_registered_users() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "profile_alice");
+    db.create_user("profile_bob", "unused_hash").unwrap();
+    db.create_user("other_carol", "unused_hash").unwrap();
+    {
+        let mut engine = state.script_engine.write().await;
+        let mut world = state.game_world.write().await;
+        engine.submit_code("profile_alice".to_string(), "// idle".to_string()).unwrap();
+        let zone_id = world.generate_player_zone("profile_alice").unwrap();
+        world.with_zone_write(&zone_id, |zone| zone.entities.push(EntityRef {
+            id: 1,
+            kind: "worker".to_string(),
+            owner: Some("profile_alice".to_string()),
+            x: 2,
+            y: 3,
+            hits: DEFAULT_ENTITY_HITS,
+            can_swim: false,
+            can_fly: false,
+        })).unwrap();
+    }
+
+    let page_of = |uri: &'static str| {
+        let state = state.clone();
+        let token = token.clone();
+        async move {
+            let response = get_with_token(&state, uri, Some(&token)).await;
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+        }
+    };
+
+    // Players without code are listed too
+    let (status, body) = page_of("/api/v1/players").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["total"], 3);
+    let alice = &body["items"][0];
+    assert_eq!(alice["username"], "profile_alice");
+    assert_eq!((alice["has_code"].as_bool(), alice["zones"].as_u64(), alice["online"].as_bool()), (Some(true), Some(1), Some(true)));
+    assert!(alice["created_at"].as_i64().unwrap() > 0);
+    assert!(alice["stats"].is_null());
+    let bob = &body["items"][1];
+    assert_eq!(bob["username"], "profile_bob");
+    assert_eq!((bob["has_code"].as_bool(), bob["zones"].as_u64(), bob["online"].as_bool()), (Some(false), Some(0), Some(false)));
+
+    let (_, body) = page_of("/api/v1/players?q=Profile_&page=2&per_page=1").await;
+    assert_eq!((body["total"].as_u64(), body["total_pages"].as_u64()), (Some(2), Some(2)));
+    assert_eq!(body["items"][0]["username"], "profile_bob");
+    let (_, body) = page_of("/api/v1/players?q=profile_&page=3&per_page=1").await;
+    assert_eq!(body["items"].as_array().unwrap().len(), 0);
+
+    let (_, body) = page_of("/api/v1/players?format=ids").await;
+    assert_eq!(body["items"], serde_json::json!(["profile_alice"]));
+    let (status, _) = page_of("/api/v1/players?format=csv").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_events_endpoint_filters_by_tick_and_visibility() {
+    let (state, db) = test_state();
+    let alice = create_session(&db, "event_alice");
+    let carol = create_session(&db, "event_carol");
+    {
+        let mut world = state.game_world.write().await;
+        let zone_id = world.generate_player_zone("event_alice").unwrap();
+        let worker = EntityRef {
+            id: 1,
+            kind: "worker".to_string(),
+            owner: Some("event_alice".to_string()),
+            x: 0,
+            y: 0,
+            hits: DEFAULT_ENTITY_HITS,
+            can_swim: false,
+            can_fly: false,
+        };
+        // One attack destroys the soldier
+        let soldier = EntityRef { id: 2, kind: "soldier".to_string(), owner: Some("event_bob".to_string()), x: 1, hits: ATTACK_DAMAGE, ..worker.clone() };
+        let far = EntityRef { id: 3, owner: Some("event_carol".to_string()), x: ZONE_SIZE - 1, y: ZONE_SIZE - 1, ..worker.clone() };
+        world.with_zone_write(&zone_id, |zone| {
+            zone.entities = vec![worker, soldier, far];
+            zone.resources = vec![ResourceDeposit { x: 0, y: 0, amount: 500 }];
+        }).unwrap();
+
+        let worker = format!("{}:1", zone_id);
+        let command = |action: &str, params: serde_json::Value| BotCommand {
+            action: action.to_string(),
+            actor: Some(worker.clone()),
+            params,
+            player_id: None,
+        };
+        world.apply_commands("event_alice", &[command("harvest", serde_json::json!({}))]);
+        world.advance_tick();
+        world.apply_commands("event_alice", &[command("attack", serde_json::json!({"target": format!("{}:2", zone_id)}))]);
+        world.advance_tick();
+    }
+
+    let events_of = |uri: &'static str, token: String| {
+        let state = state.clone();
+        async move {
+            let response = get_with_token(&state, uri, Some(&token)).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            body["events"].as_array().unwrap().iter()
+                .map(|event| format!("{}@{}", event["type"].as_str().unwrap(), event["tick"]))
+                .collect::<Vec<_>>()
+        }
+    };
+
+    assert_eq!(events_of("/api/v1/events", alice.clone()).await, vec!["ResourceCollected@1", "UnitDestroyed@2"]);
+    assert_eq!(events_of("/api/v1/events?since_tick=1", alice.clone()).await, vec!["UnitDestroyed@2"]);
+    assert_eq!(events_of("/api/v1/events?limit=1", alice).await, vec!["ResourceCollected@1"]);
+    assert!(events_of("/api/v1/events", carol).await.is_empty());
+    assert_eq!(get_with_token(&state, "/api/v1/events", None).await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_history_endpoints_show_past_ticks() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "historian");
+    let other = create_session(&db, "rival");
+    let zone_id = {
+        let mut world = state.game_world.write().await;
+        let config = WorldConfig { history_retention_ticks: 20, history_keyframe_interval: 10, ..world.config().clone() };
+        world.set_config(config);
+        let zone_id = world.generate_player_zone("historian").unwrap();
+        let worker = EntityRef {
+            id: 1,
+            kind: "worker".to_string(),
+            owner: Some("historian".to_string()),
+            x: 0,
+            y: 0,
+            hits: DEFAULT_ENTITY_HITS,
+            can_swim: false,
+            can_fly: false,
+        };
+        world.with_zone_write(&zone_id, |zone| {
+            zone.tiles[0][1].surface_type = SurfaceType::Plain;
+            zone.entities = vec![worker];
+            zone.resources = vec![ResourceDeposit { x: 0, y: 0, amount: 500 }];
+        }).unwrap();
+        for tick in 1..=50 {
+            world.advance_tick();
+            if tick == 45 {
+                world.move_entity(&zone_id, 1, 1, 0).unwrap();
+            }
+        }
+        zone_id
+    };
+
+    let get_json = |uri: String, token: String| {
+        let state = state.clone();
+        async move {
+            let response = get_with_token(&state, &uri, Some(&token)).await;
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+        }
+    };
+
+    let (status, body) = get_json("/api/v1/history/gamestate?tick=44".to_string(), token.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["tick"], 44);
+    assert_eq!(body["snapshot"]["units"][0]["position"], serde_json::json!({"x": 0, "y": 0}));
+    let (_, body) = get_json("/api/v1/history/gamestate?tick=45".to_string(), token.clone()).await;
+    assert_eq!(body["snapshot"]["units"][0]["position"], serde_json::json!({"x": 1, "y": 0}));
+
+    // Zones are shown as the caller saw them
+    let uri = format!("/api/v1/history/zone/{}?tick=44", zone_id);
+    let (status, body) = get_json(uri.clone(), token.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["zone"]["entities"].as_array().unwrap().len(), 1);
+    assert_eq!(body["zone"]["resources"].as_array().unwrap().len(), 1);
+    let (status, body) = get_json(uri, other).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["zone"]["entities"].as_array().unwrap().is_empty());
+    assert!(body["zone"]["resources"].as_array().unwrap().is_empty());
+    let (status, _) = get_json("/api/v1/history/zone/missing?tick=44".to_string(), token.clone()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Pruned and future ticks
+    let (status, body) = get_json("/api/v1/history/gamestate?tick=5".to_string(), token.clone()).await;
+    assert_eq!(status, StatusCode::GONE);
+    assert_eq!(body["earliest_tick"], 30);
+    let (status, body) = get_json(format!("/api/v1/history/zone/{}?tick=5", zone_id), token.clone()).await;
+    assert_eq!(status, StatusCode::GONE);
+    assert_eq!(body["earliest_tick"], 30);
+    let (status, _) = get_json("/api/v1/history/gamestate?tick=51".to_string(), token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(get_with_token(&state, "/api/v1/history/gamestate?tick=44", None).await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_zone_tiles_cursor_covers_every_tile_once() {
+    let (state, _db) = test_state();
+    let zone_id = state.game_world.write().await.generate_player_zone("tile_pager").unwrap();
+
+    let mut seen = std::collections::HashSet::new();
+    let mut cursor: Option<String> = None;
+    let mut batches = 0;
+    loop {
+        let uri = match &cursor {
+            Some(cursor) => format!("/api/v1/zones/{}/tiles?limit=100&cursor={}", zone_id, cursor),
+            None => format!("/api/v1/zones/{}/tiles?limit=100", zone_id),
+        };
+        let response = get_with_token(&state, &uri, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        for tile in body["tiles"].as_array().unwrap() {
+            let position = (tile["x"].as_u64().unwrap(), tile["y"].as_u64().unwrap());
+            assert!(seen.insert(position), "tile {:?} returned twice", position);
+        }
+        batches += 1;
+        match body["next_cursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+
+    assert_eq!(seen.len(), ZONE_SIZE * ZONE_SIZE);
+    assert_eq!(batches, (ZONE_SIZE * ZONE_SIZE).div_ceil(100));
+
+    let uri = format!("/api/v1/zones/{}/tiles?cursor=not-a-cursor", zone_id);
+    assert_eq!(get_with_token(&state, &uri, None).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_market_order_endpoints() {
+    let (state, db) = test_state();
+    let seller = create_session(&db, "market_seller");
+    let other = create_session(&db, "market_other");
+    state.game_world.write().await.deposit_resources("market_seller", ResourceType::Minerals, 30);
+
+    let order = serde_json::json!({"side": "sell", "resource": "minerals", "amount": 20, "price": 7});
+    let (status, body) = post_json_with_token(&state, "/api/v1/market/order", &seller, order.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    let order_id = body["order"]["id"].as_str().unwrap().to_string();
+    assert_eq!(body["order"]["remaining"], 20);
+    // The other player has no minerals to sell
+    let (status, body) = post_json_with_token(&state, "/api/v1/market/order", &other, order).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["success"], false);
+
+    let response = get_with_token(&state, "/api/v1/market/orders", Some(&other)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["orders"][0]["player_id"], "market_seller");
+    assert_eq!(body["orders"][0]["side"], "sell");
+
+    let cancel = |token: String| {
+        let state = state.clone();
+        let uri = format!("/api/v1/market/orders/{}", order_id);
+        async move {
+            let request = Request::builder()
+                .method("DELETE")
+                .uri(uri)
+                .header("Authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap();
+            create_router(state).oneshot(request).await.unwrap().status()
+        }
+    };
+    assert_eq!(cancel(other).await, StatusCode::BAD_REQUEST);
+    assert_eq!(cancel(seller).await, StatusCode::OK);
+    assert!(state.game_world.read().await.market_orders().is_empty());
+}
+
+#[tokio::test]
+async fn test_script_dry_run_endpoint() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "dry_run_player");
+    state.game_world.write().await.record_snapshot().unwrap();
+
+    let code = "// @api v1\nmodule.exports = { onTick(game) { console.log('tick', game.tick); game.moveUnit('z:1', 5, 5); } };";
+    let (status, body) = post_json_with_token(&state, "/api/v1/scripts/dryrun", &token,
+        serde_json::json!({"code": code, "snapshot_tick": 0})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["success"], true);
+    assert_eq!(body["logs"], serde_json::json!(["tick 0"]));
+    assert_eq!(body["commands"][0]["actor"], "z:1");
+    // The code was not submitted
+    assert!(state.script_engine.read().await.get_code("dry_run_player").is_none());
+
+    let (status, body) = post_json_with_token(&state, "/api/v1/scripts/dryrun", &token,
+        serde_json::json!({"code": "let x = ;", "snapshot_tick": 0})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["success"], false);
+    assert!(body["error"].as_str().unwrap().contains("main.js"));
+
+    let (status, body) = post_json_with_token(&state, "/api/v1/scripts/dryrun", &token,
+        serde_json::json!({"code": code, "snapshot_tick": 42})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body["message"].as_str().unwrap().contains("ticks 0 to 0"));
+}
+
+#[tokio::test]
+async fn test_script_stats_endpoint() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "stats_player");
+
+    // No executions yet
+    let response = get_with_token(&state, "/api/v1/scripts/stats", Some(&token)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["success"], true);
+    assert_eq!(body["stats"]["total_executions"], 0);
+
+    state.script_engine.write().await
+        .submit_code("stats_player".to_string(), "console.log('hi');".to_string())
+        .unwrap();
+    let snapshots = BTreeMap::from([("stats_player".to_string(), serde_json::json!({"tick": 1}))]);
+    let results = state.script_engine.run_tick(1, &snapshots).await;
+    let cpu_ns = results[0].1.cpu_ns;
+
+    let response = get_with_token(&state, "/api/v1/scripts/stats", Some(&token)).await;
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["stats"]["total_executions"], 1);
+    assert_eq!(body["stats"]["total_cpu_ns"], cpu_ns);
+    assert_eq!(body["stats"]["last_execution_cpu_ns"], cpu_ns);
+
+    let response = get_with_token(&state, "/api/v1/scripts/stats", None).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_admin_pause_step_resume_game_loop() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["sim_admin".to_string()].into_iter().collect());
+    state.config.write().unwrap().ticks_per_second = 500;
+    let admin = create_session(&db, "sim_admin");
+    let player = create_session(&db, "sim_player");
+    tokio::spawn(run_game_loop(
+        state.game_world.clone(),
+        state.script_engine.clone(),
+        state.config.clone(),
+        state.sim_control.clone(),
+        spawn_stats_updater(state.stats.clone()),
+    ));
+
+    let game_state = |state: AppState, token: String| async move {
+        let response = get_with_token(&state, "/api/v1/gamestate", Some(&token)).await;
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+    };
+    let tick = |state: AppState| async move { state.game_world.read().await.get_tick() };
+
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/sim/pause", &player, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = post_json_with_token(&state, "/api/v1/admin/sim/step", &admin, serde_json::json!({"ticks": 5})).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["run_state"], "running");
+
+    // Paused: the tick is frozen, reads work, and submitted code doesn't run
+    let (status, body) = post_json_with_token(&state, "/api/v1/admin/sim/pause", &admin, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["run_state"], "paused");
+    let (status, _) = post_json_with_token(&state, "/api/v1/submit", &player,
+        serde_json::json!({"code": "console.log('hi');"})).await;
+    assert_eq!(status, StatusCode::OK);
+    // A tick that was already running when the pause arrived may still finish
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let frozen = tick(state.clone()).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(tick(state.clone()).await, frozen);
+    let body = game_state(state.clone(), player.clone()).await;
+    assert_eq!(body["tick"], frozen);
+    assert_eq!(body["run_state"], "paused");
+
+    // Step: exactly 5 ticks, then paused again
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/sim/step", &admin, serde_json::json!({"ticks": 0})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/sim/step", &admin, serde_json::json!({"ticks": 5})).await;
+    assert_eq!(status, StatusCode::OK);
+    for _ in 0..100 {
+        if state.sim_control.state() == RunState::Paused {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(tick(state.clone()).await, frozen + 5);
+    assert_eq!(game_state(state.clone(), player.clone()).await["run_state"], "paused");
+
+    // Resume: the loop advances again
+    let (status, body) = post_json_with_token(&state, "/api/v1/admin/sim/resume", &admin, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["run_state"], "running");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(tick(state.clone()).await > frozen + 5);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_admin_config_reload_changes_tick_rate() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["config_admin".to_string()].into_iter().collect());
+    state.config.write().unwrap().ticks_per_second = 20;
+    let admin = create_session(&db, "config_admin");
+    let player = create_session(&db, "config_player");
+    tokio::spawn(run_game_loop(
+        state.game_world.clone(),
+        state.script_engine.clone(),
+        state.config.clone(),
+        state.sim_control.clone(),
+        spawn_stats_updater(state.stats.clone()),
+    ));
+    let ticks_during = |state: AppState, millis: u64| async move {
+        let start = state.game_world.read().await.get_tick();
+        tokio::time::sleep(Duration::from_millis(millis)).await;
+        state.game_world.read().await.get_tick() - start
+    };
+
+    let response = get_with_token(&state, "/api/v1/admin/config", Some(&player)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = get_with_token(&state, "/api/v1/admin/config", Some(&admin)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["config"]["ticks_per_second"], 20);
+
+    // 20 ticks/s: about 6 ticks in 300ms
+    assert!(ticks_during(state.clone(), 300).await <= 10);
+
+    // Invalid patches change nothing
+    for patch in [
+        serde_json::json!({"port": 1}),
+        serde_json::json!({"ticks_per_second": 0}),
+        serde_json::json!({"tick_rate": 500}),
+    ] {
+        let (status, body) = post_json_with_token(&state, "/api/v1/admin/config/reload", &admin, patch).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+        assert_eq!(body["success"], false);
+    }
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/config/reload", &player,
+        serde_json::json!({"ticks_per_second": 500})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(state.config().ticks_per_second, 20);
+
+    // 500 ticks/s from the next tick on
+    let (status, body) = post_json_with_token(&state, "/api/v1/admin/config/reload", &admin,
+        serde_json::json!({"ticks_per_second": 500, "script_tick_interval": 7})).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["config"]["ticks_per_second"], 500);
+    assert_eq!(state.game_world.read().await.config().script_tick_interval, 7);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(ticks_during(state.clone(), 300).await >= 40);
+
+    // Reloading the file: restart-only fields keep their value and are reported
+    let reload_file = |state: AppState, token: String| async move {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/v1/admin/config/reload")
+            .header("Authorization", format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        let response = create_router(state).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+    };
+    let (status, _) = reload_file(state.clone(), admin.clone()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let path = std::env::temp_dir().join(format!("geekcraft_reload_{}.toml", std::process::id()));
+    std::fs::write(&path, "port = 4000\nticks_per_second = 20\n").unwrap();
+    state.config_path = Some(path.to_string_lossy().into_owned());
+    let (status, body) = reload_file(state.clone(), admin.clone()).await;
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["restart_required"], serde_json::json!(["port"]));
+    assert_eq!(body["config"]["port"], state.config().port);
+    assert_ne!(state.config().port, 4000);
+    assert_eq!(state.config().ticks_per_second, 20);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(ticks_during(state.clone(), 300).await <= 10);
+}
+
+#[tokio::test]
+async fn test_maintenance_mode_blocks_players() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["maintainer".to_string()].into_iter().collect());
+    let admin = create_session(&db, "maintainer");
+    let player = create_session(&db, "waiting_player");
+    let path = std::env::temp_dir().join(format!("geekcraft_maintenance_{}.toml", std::process::id()));
+    std::fs::write(&path, "# Production settings\nticks_per_second = 20\n").unwrap();
+    state.config_path = Some(path.to_string_lossy().into_owned());
+
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/maintenance/enable", &player, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(!state.in_maintenance());
+
+    let (status, body) = post_json_with_token(&state, "/api/v1/admin/maintenance/enable", &admin, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["maintenance_mode"], true);
+    assert_eq!(body["persisted"], true);
+
+    let response = get_with_token(&state, "/api/v1/players", Some(&player)).await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()["Retry-After"], "300");
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["code"], "maintenance");
+    assert_eq!(body["message"], "Server is under maintenance");
+
+    // Admins and public endpoints are still served
+    let response = get_with_token(&state, "/api/v1/players", Some(&admin)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = get_with_token(&state, "/api/v1/health/live", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The mode survives a restart, and the rest of the file is kept
+    let saved = std::fs::read_to_string(&path).unwrap();
+    assert!(saved.starts_with("# Production settings\nticks_per_second = 20\n"), "{}", saved);
+    assert!(geekcraft::config::ServerConfig::from_file(path.to_str().unwrap()).unwrap().maintenance_mode);
+
+    let (status, body) = post_json_with_token(&state, "/api/v1/admin/maintenance/disable", &admin, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["maintenance_mode"], false);
+    let response = get_with_token(&state, "/api/v1/players", Some(&player)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!geekcraft::config::ServerConfig::from_file(path.to_str().unwrap()).unwrap().maintenance_mode);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_maintenance_mode_covers_public_endpoints_and_websocket() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["maintainer".to_string()].into_iter().collect());
+    let admin = create_session(&db, "maintainer");
+    let player = create_session(&db, "early_player");
+    let zone_id = state.game_world.write().await.generate_player_zone("frontier").unwrap();
+    let capture_uri = format!("/api/v1/zones/{}/capture", zone_id);
+    let addr = spawn_server(state.clone()).await;
+    let mut connected = connect_authenticated(addr, &player).await;
+    state.set_maintenance(true);
+
+    let (status, body) = post_json_with_token(&state, &capture_uri, &player, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["code"], "maintenance");
+    // The admin gets past maintenance (and is refused only for owning no units there)
+    let (status, _) = post_json_with_token(&state, &capture_uri, &admin, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let response = get_with_token(&state, "/api/v1/health/live", Some(&player)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let (mut ws, _) = connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+    send_json(&mut ws, serde_json::json!({"type": "auth", "token": player})).await;
+    let auth = next_of_type(&mut ws, "authResponse").await;
+    assert_eq!(auth["success"], false);
+    assert_eq!(auth["code"], "maintenance");
+
+    send_json(&mut connected, serde_json::json!({"type": "submitCode", "code": "move()"})).await;
+    let response = next_of_type(&mut connected, "submitCodeResponse").await;
+    assert_eq!(response["success"], false);
+    assert_eq!(response["code"], "maintenance");
+}
+
+#[tokio::test]
+async fn test_announcements_reach_connected_players() {
+    let (mut state, db) = test_state();
+    let admin = create_session(&db, "announce_admin");
+    let player = create_session(&db, "announce_player");
+    state.admin_users = Arc::new(["announce_admin".to_string()].into_iter().collect());
+    let addr = spawn_server(state.clone()).await;
+    let mut ws = connect_authenticated(addr, &player).await;
+
+    let announcement = serde_json::json!({"message": "Restarting in 5 minutes", "severity": "warning"});
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/announce", &player, announcement.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = post_json_with_token(&state, "/api/v1/admin/announce", &admin, serde_json::json!({"message": "  "})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+
+    let (status, body) = post_json_with_token(&state, "/api/v1/admin/announce", &admin, announcement).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["delivered"], 1);
+
+    let pushed = next_of_type(&mut ws, "announcement").await;
+    assert_eq!(pushed["message"], "Restarting in 5 minutes");
+    assert_eq!(pushed["severity"], "warning");
+    assert_eq!(pushed["from"], "announce_admin");
+
+    let response = get_with_token(&state, "/api/v1/announcements", Some(&player)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["announcements"][0], pushed);
+
+    // Only the last ten are kept
+    for i in 0..10 {
+        let message = serde_json::json!({"message": format!("Notice {}", i)});
+        let (status, _) = post_json_with_token(&state, "/api/v1/admin/announce", &admin, message).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let response = get_with_token(&state, "/api/v1/announcements", Some(&player)).await;
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    let kept = body["announcements"].as_array().unwrap();
+    assert_eq!(kept.len(), 10);
+    assert_eq!((kept[0]["message"].as_str(), kept[0]["severity"].as_str()), (Some("Notice 0"), Some("info")));
+}
+
+#[tokio::test]
+async fn test_https_server_with_self_signed_cert() {
+    let cert_path = "tests/fixtures/tls/cert.pem";
+    let key_path = "tests/fixtures/tls/key.pem";
+    assert!(matches!(get_tls_config("tests/fixtures/tls/missing.pem", key_path), Err(TlsError::Io { .. })));
+    assert_eq!(get_tls_config(key_path, key_path).unwrap_err(), TlsError::NoCertificate(key_path.to_string()));
+    assert_eq!(get_tls_config(cert_path, cert_path).unwrap_err(), TlsError::NoPrivateKey(cert_path.to_string()));
+
+    let (state, _db) = test_state();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let tls = get_tls_config(cert_path, key_path).unwrap();
+    tokio::spawn(tls::serve(listener, create_router(state), Some(tls)));
+
+    // Trust the test certificate only
+    let mut roots = tokio_rustls::rustls::RootCertStore::empty();
+    let pem = std::fs::read(cert_path).unwrap();
+    for cert in rustls_pemfile::certs(&mut pem.as_slice()) {
+        roots.add(cert.unwrap()).unwrap();
+    }
+    let provider = Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+    let client = tokio_rustls::rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let connector = tokio_rustls::TlsConnector::from(Arc::new(client));
+    let tcp = TcpStream::connect(addr).await.unwrap();
+    let server_name = tokio_rustls::rustls::pki_types::ServerName::try_from("localhost").unwrap();
+    let mut stream = connector.connect(server_name, tcp).await.unwrap();
+
+    stream.write_all(b"GET /api/health/live HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+    // Plain HTTP is refused on the TLS port
+    let mut plain = TcpStream::connect(addr).await.unwrap();
+    plain.write_all(b"GET /api/health/live HTTP/1.1\r\nHost: localhost\r\n\r\n").await.unwrap();
+    let mut reply = Vec::new();
+    let _ = plain.read_to_end(&mut reply).await;
+    assert!(!String::from_utf8_lossy(&reply).contains("200 OK"));
+}
+
+/// Send one command line to the control socket and read the reply line
+#[cfg(unix)]
+async fn control_request(socket: &mut tokio::io::BufReader<tokio::net::UnixStream>, command: &str) -> serde_json::Value {
+    use tokio::io::AsyncBufReadExt;
+
+    socket.get_mut().write_all(format!("{}\n", command).as_bytes()).await.unwrap();
+    let mut line = String::new();
+    socket.read_line(&mut line).await.unwrap();
+    serde_json::from_str(&line).unwrap()
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_control_socket_commands() {
+    use std::os::unix::fs::PermissionsExt;
+    use geekcraft::network::control;
+
+    let (state, db) = test_state();
+    let store = Arc::new(InMemoryWorldStore::new());
+    *state.game_world.write().await = World::open(WorldConfig::default(), store.clone()).unwrap();
+    let zone_id = {
+        let mut world = state.game_world.write().await;
+        let zone_id = world.generate_player_zone("operator").unwrap();
+        world.with_zone_write(&zone_id, |zone| zone.entities.push(EntityRef {
+            id: 1,
+            kind: "worker".to_string(),
+            owner: Some("operator".to_string()),
+            x: 1,
+            y: 1,
+            hits: DEFAULT_ENTITY_HITS,
+            can_swim: false,
+            can_fly: false,
+        })).unwrap();
+        for _ in 0..7 {
+            world.advance_tick();
+        }
+        zone_id
+    };
+
+    let path = std::env::temp_dir().join(format!("geekcraft_control_{}.sock", std::process::id()));
+    std::fs::write(&path, "not a socket").unwrap();
+    assert!(control::bind(&path).is_err(), "a regular file must not be replaced");
+    std::fs::remove_file(&path).unwrap();
+    let listener = control::bind(&path).unwrap();
+    assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    tokio::spawn(control::serve(listener, state.clone()));
+
+    let addr = spawn_server(state.clone()).await;
+    let token = create_session(&db, "rowdy");
+    let mut ws = connect_authenticated(addr, &token).await;
+
+    let mut socket = tokio::io::BufReader::new(tokio::net::UnixStream::connect(&path).await.unwrap());
+
+    let stats = control_request(&mut socket, r#"{"command": "stats"}"#).await;
+    assert_eq!(stats["success"], true);
+    assert_eq!(stats["tick"], 7);
+    assert_eq!(stats["run_state"], "running");
+    assert_eq!(stats["stats"], serde_json::json!({"zones": 1, "connected_players": 1, "connections": 1}));
+
+    let paused = control_request(&mut socket, r#"{"command": "pause"}"#).await;
+    assert_eq!(paused["run_state"], "paused");
+    assert_eq!(state.sim_control.state(), RunState::Paused);
+    assert_eq!(control_request(&mut socket, r#"{"command": "resume"}"#).await["run_state"], "running");
+
+    assert!(store.load_all_zones().unwrap()[0].entities.is_empty());
+    let saved = control_request(&mut socket, r#"{"command": "save-all"}"#).await;
+    assert_eq!(saved["message"], "Saved 1 zones");
+    let stored = store.load_all_zones().unwrap();
+    assert_eq!(stored[0].id, zone_id);
+    assert_eq!(stored[0].entities.len(), 1);
+
+    let kicked = control_request(&mut socket, r#"{"command": "kick-player", "username": "rowdy"}"#).await;
+    assert_eq!(kicked["message"], "Closed 1 connections of rowdy");
+    let closed = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match ws.next().await {
+                Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                _ => {}
+            }
+        }
+    }).await;
+    assert!(closed.is_ok(), "Kicked connection was not closed");
+
+    let unknown = control_request(&mut socket, r#"{"command": "kick-player", "username": "nobody"}"#).await;
+    assert_eq!(unknown["success"], false);
+    assert_eq!(unknown["message"], "Unknown player nobody");
+    let invalid = control_request(&mut socket, r#"{"command": "reboot"}"#).await;
+    assert_eq!(invalid["success"], false);
+    assert!(invalid["message"].as_str().unwrap().starts_with("Invalid command"));
+    assert_eq!(invalid["tick"], 7);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_ip_filter_blocks_and_allows_addresses() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["gatekeeper".to_string()].into_iter().collect());
+    let token = create_session(&db, "gatekeeper");
+
+    // Open by default, even without a known peer address
+    assert_eq!(get_with_token(&state, "/api/health/live", None).await.status(), StatusCode::OK);
+    assert_eq!(get_from(&state, "203.0.113.9:5000", "/api/health/live").await, StatusCode::OK);
+
+    // Invalid ranges are rejected without touching the running filter
+    let (status, body) = post_json_with_token(&state, "/api/admin/config/reload", &token,
+        serde_json::json!({"ip_blocklist": ["10.0.0.0/33"]})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["message"].as_str().unwrap().contains("\"10.0.0.0/33\" is not an IP address or CIDR range"), "{}", body);
+    assert!(state.ip_filter.is_open());
+
+    // Blocklisted ranges get 403 on every route, the WebSocket endpoint included
+    let (status, body) = post_json_with_token(&state, "/api/admin/config/reload", &token,
+        serde_json::json!({"ip_blocklist": ["203.0.113.0/24", "2001:db8:bad::/48"]})).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(get_from(&state, "203.0.113.9:5000", "/api/health/live").await, StatusCode::FORBIDDEN);
+    assert_eq!(get_from(&state, "203.0.113.9:5000", "/ws").await, StatusCode::FORBIDDEN);
+    assert_eq!(get_from(&state, "[2001:db8:bad::1]:5000", "/").await, StatusCode::FORBIDDEN);
+    assert_eq!(get_from(&state, "[::ffff:203.0.113.9]:5000", "/").await, StatusCode::FORBIDDEN);
+    assert_eq!(get_from(&state, "198.51.100.1:5000", "/api/health/live").await, StatusCode::OK);
+    assert_eq!(get_from(&state, "[2001:db8:600d::1]:5000", "/api/health/live").await, StatusCode::OK);
+    // With ranges configured, a request without a peer address is refused
+    assert_eq!(get_with_token(&state, "/api/health/live", None).await.status(), StatusCode::FORBIDDEN);
+
+    // A non-empty allowlist refuses everyone else; the blocklist still wins
+    state.apply_config(geekcraft::config::ServerConfig {
+        ip_allowlist: vec!["127.0.0.1".to_string(), "10.0.0.0/8".to_string(), "203.0.113.0/24".to_string()],
+        ..state.config()
+    }).await;
+    assert_eq!(get_from(&state, "10.20.30.40:5000", "/api/health/live").await, StatusCode::OK);
+    assert_eq!(get_from(&state, "198.51.100.1:5000", "/api/health/live").await, StatusCode::FORBIDDEN);
+    assert_eq!(get_from(&state, "203.0.113.9:5000", "/api/health/live").await, StatusCode::FORBIDDEN);
+
+    // Over a real connection the peer address is the loopback
+    let addr = spawn_server(state.clone()).await;
+    let response = reqwest::get(format!("http://{}/api/health/live", addr)).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    state.apply_config(geekcraft::config::ServerConfig {
+        ip_allowlist: Vec::new(),
+        ip_blocklist: vec!["127.0.0.0/8".to_string()],
+        ..state.config()
+    }).await;
+    let response = reqwest::get(format!("http://{}/api/health/live", addr)).await.unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(connect_async(format!("ws://{}/ws", addr)).await.is_err());
+}
+
+/// GET through the router with a bearer token and extra headers
+async fn get_with_headers(state: &AppState, uri: &str, token: &str, headers: &[(&str, &str)]) -> axum::response::Response {
+    let mut request = Request::builder().method("GET").uri(uri).header("Authorization", format!("Bearer {}", token));
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    create_router(state.clone()).oneshot(request.body(Body::empty()).unwrap()).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_large_responses_are_compressed() {
+    use std::io::Read;
+
+    let (state, db) = test_state();
+    let token = create_session(&db, "compressed_player");
+    let zone_id = state.game_world.write().await.generate_player_zone("compressed_player").unwrap();
+    let uri = format!("/api/v1/zones/{}/tiles", zone_id);
+    let body = |response: axum::response::Response| async move {
+        axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()
+    };
+
+    let plain = get_with_headers(&state, &uri, &token, &[]).await;
+    assert_eq!(plain.status(), StatusCode::OK);
+    assert!(plain.headers().get("Content-Encoding").is_none());
+    let plain = body(plain).await;
+    assert!(plain.len() > MIN_COMPRESSED_SIZE, "zone response is only {} bytes", plain.len());
+
+    let gzip = get_with_headers(&state, &uri, &token, &[("Accept-Encoding", "gzip")]).await;
+    assert_eq!(gzip.headers().get("Content-Encoding").unwrap(), "gzip");
+    assert_eq!(gzip.headers().get("Vary").unwrap(), "accept-encoding");
+    let compressed = body(gzip).await;
+    assert!(compressed.len() < plain.len() / 2);
+    let mut decompressed = Vec::new();
+    flate2::read::GzDecoder::new(&compressed[..]).read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, plain);
+
+    let brotli = get_with_headers(&state, &uri, &token, &[("Accept-Encoding", "gzip;q=0.5, br")]).await;
+    assert_eq!(brotli.headers().get("Content-Encoding").unwrap(), "br");
+    let compressed = body(brotli).await;
+    let mut decompressed = Vec::new();
+    brotli::Decompressor::new(&compressed[..], 4096).read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, plain);
+
+    // Small responses and upgrade requests are sent as is
+    let health = get_with_headers(&state, "/api/v1/health/live", &token, &[("Accept-Encoding", "gzip")]).await;
+    assert!(health.headers().get("Content-Encoding").is_none());
+    let upgrade = get_with_headers(&state, &uri, &token, &[("Accept-Encoding", "gzip"), ("Upgrade", "websocket")]).await;
+    assert!(upgrade.headers().get("Content-Encoding").is_none());
+
+    // Compression can be turned off
+    let mut config = state.config();
+    config.enable_compression = false;
+    state.apply_config(config).await;
+    let disabled = get_with_headers(&state, &uri, &token, &[("Accept-Encoding", "gzip")]).await;
+    assert!(disabled.headers().get("Content-Encoding").is_none());
+    assert_eq!(body(disabled).await, plain);
+}
+
+#[tokio::test]
+async fn test_conditional_get_of_zone_and_gamestate() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "etag_player");
+    let zone_id = state.game_world.write().await.generate_player_zone("etag_player").unwrap();
+    let uri = format!("/api/v1/zone/{}", zone_id);
+    let etag = |response: &axum::response::Response| response.headers().get("ETag").unwrap().to_str().unwrap().to_string();
+
+    let first = get_with_headers(&state, &uri, &token, &[]).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    let tag = etag(&first);
+    assert!(tag.starts_with("W/\""));
+
+    let second = get_with_headers(&state, &uri, &token, &[("If-None-Match", &tag)]).await;
+    assert_eq!(second.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(etag(&second), tag);
+    assert!(axum::body::to_bytes(second.into_body(), usize::MAX).await.unwrap().is_empty());
+
+    // A changed zone gets a new ETag
+    state.game_world.write().await.with_zone_write(&zone_id, |zone| zone.owner = Some("etag_player".to_string())).unwrap();
+    let third = get_with_headers(&state, &uri, &token, &[("If-None-Match", &tag)]).await;
+    assert_eq!(third.status(), StatusCode::OK);
+    assert_ne!(etag(&third), tag);
+    let body = axum::body::to_bytes(third.into_body(), usize::MAX).await.unwrap();
+    let zone: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(zone["zone"]["owner"], "etag_player");
+
+    // The game state changes with every tick
+    let gamestate = get_with_headers(&state, "/api/v1/gamestate", &token, &[]).await;
+    let tag = etag(&gamestate);
+    let unchanged = get_with_headers(&state, "/api/v1/gamestate", &token, &[("If-None-Match", &tag)]).await;
+    assert_eq!(unchanged.status(), StatusCode::NOT_MODIFIED);
+    state.game_world.write().await.advance_tick();
+    let next_tick = get_with_headers(&state, "/api/v1/gamestate", &token, &[("If-None-Match", &tag)]).await;
+    assert_eq!(next_tick.status(), StatusCode::OK);
+    assert_ne!(etag(&next_tick), tag);
+}
+
+#[tokio::test]
+async fn test_zone_responses_cached_until_zone_changes() {
+    let (mut state, db) = test_state();
+    state.zone_cache = ZoneCache::new(2);
+    let token = create_session(&db, "cached_player");
+    let zone_id = state.game_world.write().await.generate_player_zone("cached_player").unwrap();
+    state.game_world.write().await.with_zone_write(&zone_id, |zone| zone.resources = vec![ResourceDeposit { x: 3, y: 3, amount: 100 }]).unwrap();
+    let uri = format!("/api/v1/zone/{}", zone_id);
+    let zone = || async { state.game_world.read().await.with_zone_read(&zone_id, Zone::clone).unwrap() };
+    let body = |response: axum::response::Response| async move {
+        axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()
+    };
+
+    // After a first request, the zone is served from the cached string
+    let first = body(get_with_headers(&state, &uri, &token, &[]).await).await;
+    let cached = state.zone_cache.get(&zone().await).unwrap();
+    assert_eq!(cached.body.as_bytes(), &first[..]);
+    let second = body(get_with_headers(&state, &uri, &token, &[]).await).await;
+    assert_eq!(second, first);
+    assert!(Arc::ptr_eq(&state.zone_cache.get(&zone().await).unwrap().body, &cached.body));
+
+    // Harvesting the deposit invalidates the entry
+    state.game_world.write().await.with_zone_write(&zone_id, |zone| zone.resources[0].amount -= 10).unwrap();
+    assert!(state.zone_cache.get(&zone().await).is_none());
+    assert!(!state.zone_cache.contains(&zone_id));
+    let third: serde_json::Value = serde_json::from_slice(&body(get_with_headers(&state, &uri, &token, &[]).await).await).unwrap();
+    assert_eq!(third["zone"]["resources"][0]["amount"], 90);
+    assert!(!Arc::ptr_eq(&state.zone_cache.get(&zone().await).unwrap().body, &cached.body));
+
+    // The least recently served zone is evicted
+    for name in ["cached_a", "cached_b"] {
+        let other = state.game_world.write().await.generate_player_zone(name).unwrap();
+        get_with_headers(&state, &format!("/api/v1/zone/{}", other), &token, &[]).await;
+    }
+    assert_eq!(state.zone_cache.len(), 2);
+    assert!(!state.zone_cache.contains(&zone_id));
+}
+
+#[tokio::test]
+async fn test_script_errors_reported_and_deduplicated() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "error_player");
+    let addr = spawn_server(state.clone()).await;
+    let mut ws = connect_authenticated(addr, &token).await;
+
+    let modules = BTreeMap::from([
+        ("main.js".to_string(), "const helper = require('./helper');\nhelper.fail();".to_string()),
+        ("helper.js".to_string(), "exports.fail = function () {\n  throw new Error('boom');\n};".to_string()),
+    ]);
+    state.script_engine.write().await.submit_bundle("error_player".to_string(), modules).unwrap();
+    let snapshots = BTreeMap::from([("error_player".to_string(), serde_json::json!({"tick": 1}))]);
+    state.script_engine.run_tick(1, &snapshots).await;
+
+    // The new error is pushed, located in the module that threw it
+    let pushed = next_of_type(&mut ws, "scriptError").await;
+    assert_eq!(pushed["error"]["message"], "Error: boom");
+    assert_eq!(pushed["error"]["module"], "helper.js");
+    assert_eq!(pushed["error"]["line"], 2);
+    assert_eq!(pushed["error"]["tick"], 1);
+    assert!(pushed["error"]["stack"].as_str().unwrap().contains("main.js:2"));
+
+    let response = get_with_token(&state, "/api/gamestate", Some(&token)).await;
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["errors_last_tick"], 1);
+    assert_eq!(body["errors"].as_array().unwrap().len(), 1);
+
+    // The same error again is counted, not pushed or stored twice
+    state.script_engine.run_tick(2, &snapshots).await;
+    state.script_engine.write().await
+        .submit_code("error_player".to_string(), "const a = 1; null.x;".to_string())
+        .unwrap();
+    state.script_engine.run_tick(3, &snapshots).await;
+    let pushed = next_of_type(&mut ws, "scriptError").await;
+    assert_eq!(pushed["error"]["module"], "main.js");
+    assert_eq!(pushed["error"]["line"], 1);
+    assert_eq!(pushed["error"]["column"], 14);
+    assert_eq!(pushed["error"]["tick"], 3);
+
+    let errors = state.script_engine.read().await.get_errors("error_player");
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].message, "Error: boom");
+    assert_eq!((errors[0].count, errors[0].tick, errors[0].last_tick), (2, 1, 2));
+    assert_eq!(errors[1].count, 1);
+
+    // A tick without errors resets the count
+    state.script_engine.write().await
+        .submit_code("error_player".to_string(), "console.log('fixed');".to_string())
+        .unwrap();
+    state.script_engine.run_tick(4, &snapshots).await;
+    let response = get_with_token(&state, "/api/gamestate", Some(&token)).await;
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["errors_last_tick"], 0);
+    assert_eq!(body["errors"].as_array().unwrap().len(), 2);
+}
+
+/// Serve a fake GitHub: the code `good-code` is exchanged for a token of the `octocat` account
+async fn spawn_mock_github() -> SocketAddr {
+    use axum::routing::{get, post};
+
+    async fn token(body: String) -> axum::Json<serde_json::Value> {
+        let token = if body.contains("code=good-code") && body.contains("client_secret=secret") {
+            serde_json::json!({"access_token": "gho_octocat", "token_type": "bearer"})
+        } else {
+            serde_json::json!({"error": "bad_verification_code"})
+        };
+        axum::Json(token)
+    }
+    async fn user(headers: axum::http::HeaderMap) -> axum::response::Response {
+        use axum::response::IntoResponse;
+        match headers.get("authorization").and_then(|value| value.to_str().ok()) {
+            Some("Bearer gho_octocat") => axum::Json(serde_json::json!({"id": 583231, "login": "octocat"})).into_response(),
+            _ => StatusCode::UNAUTHORIZED.into_response(),
+        }
+    }
+
+    let app = axum::Router::new()
+        .route("/login/oauth/access_token", post(token))
+        .route("/user", get(user));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    addr
+}
+
+/// Run a GitHub sign-in through the router with the given code
+async fn github_sign_in(state: &AppState, code: &str) -> (StatusCode, serde_json::Value) {
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/auth/oauth/github/start")
+        .header("host", "geekcraft.test")
+        .body(Body::empty())
+        .unwrap();
+    let response = create_router(state.clone()).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    let location = response.headers()["location"].to_str().unwrap().to_string();
+    assert!(location.contains("client_id=client"));
+    assert!(location.contains("redirect_uri=http%3A%2F%2Fgeekcraft.test%2Fapi%2Fv1%2Fauth%2Foauth%2Fgithub%2Fcallback"));
+    let flow = location.split("state=").nth(1).unwrap().split('&').next().unwrap().to_string();
+
+    let request = Request::builder()
+        .uri(format!("/api/auth/oauth/github/callback?code={}&state={}", code, flow))
+        .header("host", "geekcraft.test")
+        .body(Body::empty())
+        .unwrap();
+    let response = create_router(state.clone()).oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[tokio::test]
+async fn test_github_oauth_creates_then_retrieves_user() {
+    let (mut state, db) = test_state();
+    let github = spawn_mock_github().await;
+    state.oauth = Arc::new(OAuth::new().with_provider(
+        OAuthProvider::github("client".to_string(), "secret".to_string()).with_endpoints(
+            "https://github.example/login/oauth/authorize",
+            &format!("http://{}/login/oauth/access_token", github),
+            &format!("http://{}/user", github),
+        ),
+    ));
+    // A password account already uses the GitHub login
+    db.create_user("octocat", "unused_hash").unwrap();
+
+    // First sign-in creates the user
+    let (status, body) = github_sign_in(&state, "good-code").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["success"], true);
+    assert_eq!(body["username"], "octocat-2");
+    let token = body["token"].as_str().unwrap().to_string();
+    let user = db.get_user_by_oauth("github", "583231").unwrap().unwrap();
+    assert_eq!((user.username.as_str(), user.oauth_provider.as_str(), user.password_hash.as_str()), ("octocat-2", "github", ""));
+    let response = get_with_token(&state, "/api/gamestate", Some(&token)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Later sign-ins log the same user in
+    let (status, body) = github_sign_in(&state, "good-code").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["username"], "octocat-2");
+    assert_ne!(body["token"], token.as_str());
+    assert_eq!(db.list_users(0, 10, &UserFilter::default()).unwrap().1, 2);
+
+    // The OAuth account has no password to log in with
+    let (_, body) = post_json(&state, "/api/auth/login", serde_json::json!({"username": "octocat-2", "password": ""})).await;
+    assert_eq!(body["success"], false);
+    assert_eq!(body["message"], "This account signs in with github");
+
+    // Bad codes, replayed or unknown states, and disabled providers are refused
+    let (status, body) = github_sign_in(&state, "bad-code").await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert!(body["message"].as_str().unwrap().contains("bad_verification_code"));
+    let (status, _) = post_json(&state, "/api/auth/oauth/github/callback?code=good-code&state=forged", serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    let response = get_with_token(&state, "/api/auth/oauth/github/callback?code=good-code&state=forged", None).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let (status, _) = post_json(&state, "/api/auth/oauth/discord/start", serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_readiness_reports_each_failed_check() {
+    let (mut state, _db) = test_state();
+    state.save_dir = std::env::temp_dir().join(format!("geekcraft_ready_{}", uuid::Uuid::new_v4().simple()));
+    std::fs::create_dir_all(&state.save_dir).unwrap();
+    let ready = |state: AppState, uri: &'static str| async move {
+        let response = get_with_token(&state, uri, None).await;
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+    };
+
+    // The process is alive, but the game loop was never started
+    let (status, body) = ready(state.clone(), "/api/health/live").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "alive");
+    let (status, body) = ready(state.clone(), "/api/health/ready").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["status"], "unhealthy");
+    assert_eq!(body["checks"]["database"]["ok"], true);
+    assert_eq!(body["checks"]["save_dir"]["ok"], true);
+    assert_eq!(body["checks"]["game_loop"], serde_json::json!({"ok": false, "message": "Game loop has not started"}));
+
+    // Once the loop ticks, every check passes, on the legacy path too
+    let game_loop = tokio::spawn(run_game_loop(
+        state.game_world.clone(),
+        state.script_engine.clone(),
+        state.config.clone(),
+        state.sim_control.clone(),
+        spawn_stats_updater(state.stats.clone()),
+    ));
+    while state.sim_control.last_heartbeat().is_none() {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    for uri in ["/api/health/ready", "/api/health", "/api/v1/health/ready"] {
+        let (status, body) = ready(state.clone(), uri).await;
+        assert_eq!(status, StatusCode::OK, "{}: {}", uri, body);
+        assert_eq!(body["status"], "healthy");
+    }
+
+    // A wedged loop stops beating
+    game_loop.abort();
+    state.sim_control.heartbeat(chrono::Utc::now().timestamp_millis() - 60_000);
+    let (status, body) = ready(state.clone(), "/api/health").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["checks"]["game_loop"]["ok"], false);
+    assert!(body["checks"]["game_loop"]["message"].as_str().unwrap().ends_with("(limit 10000ms)"), "{}", body);
+
+    // An unwritable save directory (under a file, since permissions don't stop root)
+    state.sim_control.heartbeat(chrono::Utc::now().timestamp_millis());
+    let blocker = state.save_dir.join("blocker");
+    std::fs::write(&blocker, b"").unwrap();
+    state.save_dir = blocker.join("saves");
+    let (status, body) = ready(state.clone(), "/api/health/ready").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["checks"]["game_loop"]["ok"], true);
+    assert_eq!(body["checks"]["save_dir"]["ok"], false);
+    assert!(body["checks"]["save_dir"]["message"].as_str().unwrap().contains("is not writable"), "{}", body);
+    assert_eq!(ready(state.clone(), "/api/health/live").await.0, StatusCode::OK);
+    std::fs::remove_dir_all(blocker.parent().unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn test_email_verification_links() {
+    let (mut state, db) = test_state();
+    let mailbox = Arc::new(MockEmailSender::new());
+    state.email_sender = mailbox.clone();
+    state.config.write().unwrap().public_url = Some("https://geekcraft.test/".to_string());
+    let link_token = |mailbox: &MockEmailSender, to: &str| {
+        let email = mailbox.sent().pop().unwrap();
+        assert_eq!(email.to, to);
+        let link = email.body.lines()
+            .find(|line| line.starts_with("https://geekcraft.test/api/v1/auth/verify-email?token="))
+            .unwrap_or_else(|| panic!("no link in {}", email.body));
+        link.rsplit('=').next().unwrap().to_string()
+    };
+    let verify = |state: AppState, token: String| async move {
+        let response = get_with_token(&state, &format!("/api/auth/verify-email?token={}", token), None).await;
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+    };
+
+    // Registering with an email sends a link to it
+    let (status, body) = post_json(&state, "/api/auth/register",
+        serde_json::json!({"username": "ada", "password": "analytical", "email": "ada@example.com"})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["message"], "User ada registered successfully, check your email to verify it");
+    let token = link_token(&mailbox, "ada@example.com");
+    let ada = db.get_user_by_username("ada").unwrap().unwrap();
+    assert_eq!((ada.email.as_deref(), ada.email_verified), (Some("ada@example.com"), false));
+    assert_eq!(ada.email_verification_token.as_deref(), Some(token.as_str()));
+
+    // Following it verifies the address, once
+    let (status, body) = verify(state.clone(), token.clone()).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["message"], "Email address ada@example.com verified");
+    let ada = db.get_user_by_username("ada").unwrap().unwrap();
+    assert!(ada.email_verified);
+    assert_eq!(ada.email_verification_token, None);
+    let (status, body) = verify(state.clone(), token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "Unknown verification link");
+
+    // Changing the address needs a new verification, and links expire after a day
+    let token = create_session(&db, "grace");
+    let (status, _) = post_json(&state, "/api/auth/verify-email", serde_json::json!({"email": "grace@example.com"})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = post_json_with_token(&state, "/api/auth/verify-email", &token,
+        serde_json::json!({"email": "not an address"})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "not an address is not an email address");
+    let (status, body) = post_json_with_token(&state, "/api/auth/verify-email", &token,
+        serde_json::json!({"email": "grace@example.com"})).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let link = link_token(&mailbox, "grace@example.com");
+    let later = chrono::Utc::now().timestamp() + EMAIL_VERIFICATION_TTL_SECS + 1;
+    assert_eq!(state.auth_service.verify_email_at(&link, later).unwrap_err(),
+        "This verification link has expired, ask for a new one");
+    assert!(!db.get_user_by_username("grace").unwrap().unwrap().email_verified);
+
+    // A new link replaces the expired one
+    let (status, _) = post_json_with_token(&state, "/api/auth/verify-email", &token, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    let renewed = link_token(&mailbox, "grace@example.com");
+    assert_ne!(renewed, link);
+    assert_eq!(verify(state.clone(), link).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(verify(state.clone(), renewed).await.0, StatusCode::OK);
+    assert_eq!(mailbox.sent().len(), 3);
+}
+
+#[tokio::test]
+async fn test_submit_code_over_websocket_reloads_connections() {
+    let (state, db) = test_state();
+    let token = create_session(&db, "live_coder");
+    let addr = spawn_server(state.clone()).await;
+    let mut editor = connect_authenticated(addr, &token).await;
+    let mut viewer = connect_authenticated(addr, &token).await;
+
+    // The code is activated and every connection of the player hears about it (the
+    // submitting one before its response)
+    send_json(&mut editor, serde_json::json!({"type": "submitCode", "code": "console.log('v2');"})).await;
+    for ws in [&mut editor, &mut viewer] {
+        let reloaded = next_json(ws).await;
+        assert_eq!(reloaded["type"], "codeReloaded", "{}", reloaded);
+        assert_eq!(reloaded["language"], "javascript");
+        assert_eq!(reloaded["modules"], serde_json::json!(["main.js"]));
+    }
+    let response = next_json(&mut editor).await;
+    assert_eq!(response["type"], "submitCodeResponse");
+    assert_eq!(response["success"], true, "{}", response);
+    assert_eq!(state.script_engine.read().await.get_code("live_coder").unwrap(), "console.log('v2');");
+
+    // Bundles go through the same validation as POST /api/submit
+    send_json(&mut editor, serde_json::json!({"type": "submitCode", "modules": {"util.js": "export const x = 1;"}})).await;
+    let response = next_of_type(&mut editor, "submitCodeResponse").await;
+    assert_eq!(response["success"], false);
+    assert!(response["message"].as_str().unwrap().contains("main.js"), "{}", response);
+
+    // Syntax errors are reported and the active code is kept
+    send_json(&mut editor, serde_json::json!({"type": "submitCode", "code": "function broken( {"})).await;
+    let response = next_of_type(&mut editor, "submitCodeResponse").await;
+    assert_eq!(response["success"], false);
+    assert!(response["message"].as_str().unwrap().contains("main.js"), "{}", response);
+    assert_eq!(state.script_engine.read().await.get_code("live_coder").unwrap(), "console.log('v2');");
+
+    // Binary frames and oversized submissions are refused
+    let binary = rmp_serde::to_vec_named(&serde_json::json!({"type": "submitCode", "code": "move()"})).unwrap();
+    editor.send(Message::Binary(binary)).await.unwrap();
+    let response = next_of_type(&mut editor, "submitCodeResponse").await;
+    assert_eq!(response["message"], "submitCode must be sent as a JSON text frame");
+    let huge = "x".repeat(2 * geekcraft::scripting::bundle::MAX_BUNDLE_SIZE);
+    send_json(&mut editor, serde_json::json!({"type": "submitCode", "code": huge})).await;
+    assert!(next_of_type(&mut editor, "error").await["message"].as_str().unwrap().starts_with("Message too large"));
+    assert_eq!(state.script_engine.read().await.get_code("live_coder").unwrap(), "console.log('v2');");
+
+    // No other reload was announced
+    send_json(&mut viewer, serde_json::json!({"type": "getPlayers"})).await;
+    let next = next_json(&mut viewer).await;
+    assert_eq!(next["type"], "playersResponse", "{}", next);
+}
+
+#[tokio::test]
+async fn test_zone_reads_do_not_wait_for_the_world_lock() {
+    let (state, _db) = test_state();
+    let quiet_zone = state.game_world.write().await.spawn_player("quiet").unwrap();
+    let get = |state: AppState, uri: String| async move {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        create_router(state).oneshot(request).await.unwrap().status()
+    };
+
+    // While a tick holds the world lock, zones and the zone list are still served
+    let mut world = state.game_world.write().await;
+    world.advance_tick();
+    let read = async {
+        let zone = get(state.clone(), format!("/api/zone/{}", quiet_zone)).await;
+        let list = get(state.clone(), "/api/zones".to_string()).await;
+        (zone, list)
+    };
+    let (zone, list) = tokio::time::timeout(Duration::from_secs(10), read).await.expect("a zone read waited for the world lock");
+    assert_eq!((zone, list), (StatusCode::OK, StatusCode::OK));
+
+    // A zone generated under the lock is readable as soon as the lock is released
+    let zone_id = world.generate_player_zone("builder").unwrap();
+    drop(world);
+    assert_eq!(get(state.clone(), format!("/api/zone/{}", zone_id)).await, StatusCode::OK);
+    assert_eq!(state.zones.len(), 2);
+    assert_eq!(state.game_world.read().await.get_tick(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_zone_reads_stay_fast_under_ticks_and_generation() {
+    // A tick holds the world lock for this long; a read that waited for it would take as long
+    const TICK_HOLD: Duration = Duration::from_millis(250);
+    let (state, _db) = test_state();
+    let quiet_zone = state.game_world.write().await.spawn_player("quiet").unwrap();
+    let get = |state: AppState, uri: String| async move {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        create_router(state).oneshot(request).await.unwrap().status()
+    };
+
+    let stress = async {
+        let ticker = tokio::spawn({
+            let world = state.game_world.clone();
+            async move {
+                for _ in 0..10 {
+                    let mut world = world.write().await;
+                    world.advance_tick();
+                    tokio::time::sleep(TICK_HOLD).await;
+                }
+            }
+        });
+        // Zones generated and read while the ticks run
+        let generator = tokio::spawn({
+            let state = state.clone();
+            async move {
+                for i in 0..10 {
+                    let player_id = format!("builder_{}", i);
+                    let (status, body) = post_json(&state, "/api/zone/generate", serde_json::json!({"player_id": player_id})).await;
+                    assert_eq!(status, StatusCode::OK, "{}", body);
+                    let zone_id = body["zone_id"].as_str().unwrap().to_string();
+                    assert_eq!(get(state.clone(), format!("/api/zone/{}", zone_id)).await, StatusCode::OK);
+                }
+            }
+        });
+
+        // Reading an untouched zone stays fast throughout
+        let mut slowest = Duration::ZERO;
+        while !ticker.is_finished() {
+            let started = std::time::Instant::now();
+            assert_eq!(get(state.clone(), format!("/api/zone/{}", quiet_zone)).await, StatusCode::OK);
+            slowest = slowest.max(started.elapsed());
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        ticker.await.unwrap();
+        generator.await.unwrap();
+        slowest
+    };
+
+    let slowest = tokio::time::timeout(Duration::from_secs(60), stress).await.expect("zone locks deadlocked");
+    // Well under a tick, with room for a loaded machine
+    assert!(slowest < TICK_HOLD * 4 / 5, "a zone read waited {:?}", slowest);
+    assert_eq!(state.zones.len(), 11);
+    assert_eq!(state.game_world.read().await.get_tick(), 10);
+}
+
+#[tokio::test]
+async fn test_oversized_bodies_are_refused() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["operator".to_string()].into_iter().collect());
+    let token = create_session(&db, "operator");
+
+    let routes = [
+        ("/api/auth/register", AUTH_BODY_LIMIT),
+        ("/api/auth/login", AUTH_BODY_LIMIT),
+        ("/api/submit", CODE_BODY_LIMIT),
+        ("/api/validate", CODE_BODY_LIMIT),
+        ("/api/scripts/modules", CODE_BODY_LIMIT),
+        ("/api/lobbies/create", JSON_BODY_LIMIT),
+        ("/api/zone/import", IMPORT_BODY_LIMIT),
+    ];
+    for (uri, limit) in routes {
+        // Refused from the declared length, and while streaming when none is declared
+        for declared in [true, false] {
+            let mut request = Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("Content-Type", "application/json")
+                .header("Authorization", format!("Bearer {}", token));
+            if declared {
+                request = request.header("Content-Length", limit + 1);
+            }
+            let request = request.body(Body::from(vec![b' '; limit + 1])).unwrap();
+            let response = create_router(state.clone()).oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE, "{}", uri);
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(body["success"], false, "{}", uri);
+            assert_eq!(body["message"], format!("Request body too large (max: {} bytes)", limit), "{}", uri);
+        }
+    }
+
+    // A body at the limit is read and parsed
+    let mut body = serde_json::json!({"username": "", "password": ""}).to_string();
+    body.push_str(&" ".repeat(AUTH_BODY_LIMIT - body.len()));
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/auth/login")
+        .header("Content-Type", "application/json")
+        .body(Body::from(body))
+        .unwrap();
+    let response = create_router(state.clone()).oneshot(request).await.unwrap();
+    assert_ne!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // Malformed JSON gets the same envelope
+    let (status, body) = post_json_with_token(&state, "/api/lobbies/create", &token, serde_json::json!([1, 2])).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["success"], false);
+    assert!(body["message"].as_str().unwrap().starts_with("Invalid JSON"));
+}
+
+#[tokio::test]
+async fn test_stats_endpoints_read_the_read_model() {
+    let (state, db) = test_state();
+    create_session(&db, "statistician");
+    let zone_id = state.game_world.write().await.generate_player_zone("counted").unwrap();
+    let read = |uri: String| {
+        let state = state.clone();
+        async move {
+            let response = get_with_token(&state, &uri, Some("token-statistician")).await;
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+        }
+    };
+
+    let (status, body) = read(format!("/api/v1/stats/zones/{}", zone_id)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["success"], false);
+
+    // Batches reach the model through the updater task
+    let feed = spawn_stats_updater(state.stats.clone());
+    let batch = {
+        let mut world = state.game_world.write().await;
+        world.with_zone_write(&zone_id, |zone| zone.owner = Some("counted".to_string())).unwrap();
+        for _ in 0..3 {
+            world.advance_tick();
+        }
+        world.drain_stats()
+    };
+    feed.send(batch).unwrap();
+    while state.stats.read().tick < 3 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    // Reads answer while a tick holds the world lock
+    let _world = state.game_world.write().await;
+    let (status, body) = tokio::time::timeout(Duration::from_secs(1), read("/api/v1/stats/players/counted".to_string()))
+        .await
+        .expect("stats read waited for the world lock");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["tick"], 3);
+    assert_eq!(body["stats"]["ticks_survived"], 3);
+
+    let (status, body) = read(format!("/api/v1/stats/zones/{}", zone_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["stats"]["owner"], "counted");
+    assert_eq!(body["stats"]["ownership_history"][0]["tick"], 3);
+}
+
+#[tokio::test]
+async fn test_audit_log_records_and_filters_security_actions() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["auditor".to_string()].into_iter().collect());
+    state.audit = Arc::new(SqliteAuditStore::open_in_memory().unwrap());
+    let auditor = create_session(&db, "auditor");
+    let read = |uri: String, token: String| {
+        let state = state.clone();
+        async move {
+            let response = get_with_token(&state, &uri, Some(&token)).await;
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+        }
+    };
+
+    let (status, _) = post_json(&state, "/api/auth/register", serde_json::json!({"username": "alice", "password": "correct-horse"})).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // A failed login from a known address, then one for an unknown user
+    let mut request = Request::builder()
+        .method("POST")
+        .uri("/api/auth/login")
+        .header("Content-Type", "application/json")
+        .body(Body::from(serde_json::json!({"username": "alice", "password": "hunter2-guess"}).to_string()))
+        .unwrap();
+    request.extensions_mut().insert(axum::extract::ConnectInfo("203.0.113.7:4000".parse::<SocketAddr>().unwrap()));
+    create_router(state.clone()).oneshot(request).await.unwrap();
+    let (_, body) = post_json(&state, "/api/auth/login", serde_json::json!({"username": "mallory", "password": "hunter2-guess"})).await;
+    assert_eq!(body["success"], false);
+
+    let (_, body) = post_json(&state, "/api/auth/login", serde_json::json!({"username": "alice", "password": "correct-horse"})).await;
+    let alice = body["token"].as_str().unwrap().to_string();
+    let (status, _) = post_json_with_token(&state, "/api/v1/submit", &alice, serde_json::json!({"code": "class Bot { onTick() {} }"})).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/sim/pause", &alice, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/sim/pause", &auditor, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = post_json_with_token(&state, "/api/v1/auth/logout", &alice, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Only admins read the log; reads are not audited
+    let (status, _) = read("/api/v1/admin/audit".to_string(), auditor.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    let response = get_with_token(&state, "/api/v1/admin/audit", Some(&alice)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "alice logged out");
+    let bob = create_session(&db, "bob");
+    let (status, body) = read("/api/v1/admin/audit".to_string(), bob).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["success"], false);
+
+    let (_, body) = read("/api/v1/admin/audit".to_string(), auditor.clone()).await;
+    assert_eq!(body["total"], 7);
+    assert!(!body.to_string().contains("hunter2-guess") && !body.to_string().contains("correct-horse"));
+
+    let (_, body) = read("/api/v1/admin/audit?user=alice".to_string(), auditor.clone()).await;
+    let actions: Vec<(&str, &str)> = body["items"].as_array().unwrap().iter()
+        .map(|entry| (entry["action"].as_str().unwrap(), entry["outcome"].as_str().unwrap()))
+        .collect();
+    assert_eq!(actions, vec![
+        ("logout", "success"),
+        ("admin", "failure"),
+        ("code_submission", "success"),
+        ("login", "success"),
+        ("login", "failure"),
+    ]);
+    assert_eq!(body["items"][4]["ip"], "203.0.113.7");
+    assert_eq!(body["items"][1]["detail"], "POST /api/admin/sim/pause (403 Forbidden)");
+
+    let (_, body) = read("/api/v1/admin/audit?action=login&user=mallory".to_string(), auditor.clone()).await;
+    assert_eq!(body["total"], 1);
+    assert_eq!(body["items"][0]["outcome"], "failure");
+    let (_, body) = read("/api/v1/admin/audit?action=admin".to_string(), auditor.clone()).await;
+    assert_eq!(body["items"][0]["user"], "auditor");
+    assert_eq!(body["items"][0]["outcome"], "success");
+
+    let (_, body) = read("/api/v1/admin/audit?per_page=2&page=2".to_string(), auditor.clone()).await;
+    assert_eq!((body["items"].as_array().unwrap().len(), body["total_pages"].as_u64()), (2, Some(4)));
+    assert_eq!(body["items"][0]["action"], "admin");
+    let since = chrono::Utc::now().timestamp() + 60;
+    let (_, body) = read(format!("/api/v1/admin/audit?since={}", since), auditor.clone()).await;
+    assert_eq!(body["total"], 0);
+    let (status, _) = read("/api/v1/admin/audit?action=delete_everything".to_string(), auditor).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_invite_codes_gate_registration() {
+    let (mut state, db) = test_state();
+    state.admin_users = Arc::new(["gatekeeper".to_string()].into_iter().collect());
+    let admin = create_session(&db, "gatekeeper");
+    let player = create_session(&db, "regular");
+    state.config.write().unwrap().registration_mode = RegistrationMode::Invite;
+
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/invites", &player, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = post_json_with_token(&state, "/api/v1/admin/invites", &admin, serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["invite"]["max_uses"], 1);
+    assert_eq!(body["invite"]["created_by"], "gatekeeper");
+    let code = body["invite"]["code"].as_str().unwrap().to_string();
+    let (status, _) = post_json_with_token(&state, "/api/v1/admin/invites", &admin, serde_json::json!({"max_uses": 0})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (_, body) = post_json(&state, "/api/v1/auth/register", serde_json::json!({"username": "stranger", "password": "secret123"})).await;
+    assert_eq!(body["success"], false);
+    assert!(body["message"].as_str().unwrap().contains("requires an invite code"), "{}", body);
+
+    // Two players register at the same time with a single-use code: only one gets in
+    let racers = ["racer_one", "racer_two"].map(|username| {
+        let state = state.clone();
+        let request = serde_json::json!({"username": username, "password": "secret123", "invite_code": code});
+        tokio::spawn(async move { post_json(&state, "/api/v1/auth/register", request).await.1 })
+    });
+    let mut results = Vec::new();
+    for racer in racers {
+        results.push(racer.await.unwrap());
+    }
+    assert_eq!(results.iter().filter(|body| body["success"] == true).count(), 1, "{:?}", results);
+    let loser = results.iter().find(|body| body["success"] == false).unwrap();
+    assert_eq!(loser["message"], "Invite code has already been used");
+    let registered = ["racer_one", "racer_two"].iter()
+        .filter(|username| db.get_user_by_username(username).unwrap().is_some())
+        .count();
+    assert_eq!(registered, 1);
+
+    let response = get_with_token(&state, "/api/v1/admin/invites", Some(&admin)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["registration_mode"], "invite");
+    assert_eq!(body["invites"][0]["uses_left"], 0);
+
+    let revoke = || {
+        let request = Request::builder()
+            .method("DELETE")
+            .uri(format!("/api/v1/admin/invites/{}", code))
+            .header("Authorization", format!("Bearer {}", admin))
+            .body(Body::empty())
+            .unwrap();
+        create_router(state.clone()).oneshot(request)
+    };
+    assert_eq!(revoke().await.unwrap().status(), StatusCode::OK);
+    assert_eq!(revoke().await.unwrap().status(), StatusCode::NOT_FOUND);
+
+    // Closed registration still lets players log in
+    state.config.write().unwrap().registration_mode = RegistrationMode::Closed;
+    let (_, body) = post_json(&state, "/api/v1/auth/register", serde_json::json!({"username": "stranger", "password": "secret123"})).await;
+    assert_eq!(body["success"], false);
+    assert!(body["message"].as_str().unwrap().starts_with("Registration is closed"), "{}", body);
+    let winner = results.iter().find(|body| body["success"] == true).unwrap()["username"].as_str().unwrap().to_string();
+    let (_, body) = post_json(&state, "/api/v1/auth/login", serde_json::json!({"username": winner, "password": "secret123"})).await;
+    assert_eq!(body["success"], true, "{}", body);
+}
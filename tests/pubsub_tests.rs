@@ -0,0 +1,144 @@
+// Tests for the Redis pub/sub bridge (feature `redis_backend`).
+// The bridge test needs a Redis server at GEEKCRAFT_REDIS_URL (default
+// redis://127.0.0.1:6379), so it is ignored unless run with `--ignored`.
+#![cfg(feature = "redis_backend")]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::RwLock;
+
+use geekcraft::game::world::World;
+use geekcraft::game::zone::ResourceDeposit;
+use geekcraft::network::pubsub::{self, PubSubBridge, Role, TickMessage, TickPublisher};
+use geekcraft::network::state_sync::{StateReplica, SyncState};
+use geekcraft::network::ws_clients::WsClients;
+use geekcraft::network::ws_codec::Outgoing;
+
+/// Add a resource deposit to a zone, so that its next tick has a delta
+fn add_deposit(world: &mut World, zone_id: &str, x: usize) {
+    world.with_zone_write(zone_id, |zone| zone.resources.push(ResourceDeposit { x, y: 0, amount: 50 })).unwrap();
+}
+
+#[test]
+fn test_role_parsing() {
+    assert_eq!(Role::parse("sim"), Ok(Role::Sim));
+    assert_eq!(Role::parse("EDGE"), Ok(Role::Edge));
+    assert!(Role::parse("relay").is_err());
+    assert!(Role::Sim.simulates());
+    assert!(!Role::Edge.simulates());
+}
+
+#[test]
+fn test_tick_messages_rebuild_zones() {
+    let mut world = World::new();
+    let zone_id = world.spawn_player("alice").unwrap();
+    let mut publisher = TickPublisher::new(10);
+    let mut replica = StateReplica::new();
+
+    let messages = publisher.messages(&world);
+    let [TickMessage::ZoneKeyframe { seq, state }] = messages.as_slice() else {
+        panic!("expected one keyframe, got {:?}", messages);
+    };
+    replica.apply_keyframe(*seq, state.clone());
+    assert!(publisher.messages(&world).is_empty(), "the same tick is published once");
+
+    for x in 0..3 {
+        add_deposit(&mut world, &zone_id, x);
+        world.advance_tick();
+        let messages = publisher.messages(&world);
+        let [TickMessage::ZoneDelta { zone_id: delta_zone, seq, delta }] = messages.as_slice() else {
+            panic!("expected one delta, got {:?}", messages);
+        };
+        assert_eq!(delta_zone, &zone_id);
+        replica.apply_delta(*seq, delta).unwrap();
+    }
+    assert_eq!(replica.state(), world.with_zone_read(&zone_id, |zone| SyncState::new(world.get_tick(), zone)).as_ref());
+
+    // Unchanged zones get nothing, and keyframes come back after the interval
+    world.advance_tick();
+    assert!(publisher.messages(&world).is_empty());
+    for _ in 0..10 {
+        world.advance_tick();
+    }
+    let messages = publisher.messages(&world);
+    assert!(matches!(messages.as_slice(), [TickMessage::ZoneKeyframe { seq: 4, .. }]), "{:?}", messages);
+}
+
+/// Next fanned-out message of a given type received by a client
+async fn next_message(receiver: &mut UnboundedReceiver<Outgoing>, kind: &str) -> Option<serde_json::Value> {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(outgoing) = receiver.recv().await {
+            if let Outgoing::Json(message) = outgoing {
+                if message["type"] == kind {
+                    return Some(message);
+                }
+            }
+        }
+        None
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs a Redis server at GEEKCRAFT_REDIS_URL"]
+async fn test_edge_clients_receive_sim_ticks() {
+    let redis_url = pubsub::redis_url_from_env();
+    let available = async {
+        let client = redis::Client::open(redis_url.as_str()).ok()?;
+        client.get_multiplexed_async_connection().await.ok()
+    };
+    assert!(
+        tokio::time::timeout(Duration::from_secs(2), available).await.ok().flatten().is_some(),
+        "no Redis server at {}", redis_url
+    );
+
+    // An edge instance with one WebSocket client
+    let edge_clients = Arc::new(WsClients::new());
+    let (edge_sender, mut edge_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let _edge_connection = edge_clients.register(1, edge_sender);
+    let edge = PubSubBridge::start_edge(&redis_url, edge_clients.clone()).unwrap();
+    assert_eq!(edge.role(), Role::Edge);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // A sim instance with its own client
+    let mut world = World::new();
+    let zone_id = world.spawn_player("alice").unwrap();
+    let world = Arc::new(RwLock::new(world));
+    let sim_clients = Arc::new(WsClients::new());
+    let (sim_sender, mut sim_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let _sim_connection = sim_clients.register(2, sim_sender);
+    let sim = PubSubBridge::start_sim(&redis_url, world.clone(), sim_clients, Duration::from_millis(20), 1000).unwrap();
+
+    let keyframe = next_message(&mut edge_receiver, "zoneKeyframe").await.expect("edge client got no keyframe");
+    assert_eq!(keyframe["state"]["zone"]["id"], zone_id.as_str());
+    assert!(next_message(&mut sim_receiver, "zoneKeyframe").await.is_some());
+
+    {
+        let mut world = world.write().await;
+        add_deposit(&mut world, &zone_id, 1);
+        world.advance_tick();
+    }
+    let delta = next_message(&mut edge_receiver, "zoneDelta").await.expect("edge client got no delta");
+    assert_eq!(delta["zone_id"], zone_id.as_str());
+    assert_eq!(delta["seq"].as_u64(), Some(keyframe["seq"].as_u64().unwrap() + 1));
+    assert!(!sim.is_degraded());
+    assert!(!edge.is_degraded());
+}
+
+#[tokio::test]
+async fn test_sim_keeps_serving_local_clients_without_redis() {
+    // Nothing listens on port 1
+    let world = Arc::new(RwLock::new(World::new()));
+    world.write().await.spawn_player("alice").unwrap();
+    let clients = Arc::new(WsClients::new());
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let _connection = clients.register(1, sender);
+
+    let sim = PubSubBridge::start_sim("redis://127.0.0.1:1", world, clients, Duration::from_millis(20), 1000).unwrap();
+    assert!(next_message(&mut receiver, "zoneKeyframe").await.is_some());
+    assert!(sim.is_degraded());
+}
@@ -0,0 +1,237 @@
+// Security tests for the JavaScript script sandbox.
+// Each test runs a bot attempting a known escape pattern and checks it is blocked; the
+// capability tests check that bots find exactly what `Sandbox::capability_report` lists,
+// so a newly exposed binding must be added to the report.
+
+use geekcraft::scripting::api_version::{LATEST_API_VERSION, OLDEST_API_VERSION};
+use geekcraft::scripting::bundle::ScriptBundle;
+use geekcraft::scripting::js_runtime::{JsRuntime, ScriptExecutionResult, ScriptLimits};
+use geekcraft::scripting::runtime::ScriptRuntime;
+use geekcraft::scripting::sandbox::Sandbox;
+use std::time::{Duration, Instant};
+
+fn snapshot() -> serde_json::Value {
+    serde_json::json!({
+        "tick": 1,
+        "player_id": "alice",
+        "units": [{"id": "w1", "type": "worker", "owner": "alice", "position": {"x": 4, "y": 7}}],
+        "structures": [{"id": "b1", "type": "base", "owner": "alice", "position": {"x": 2, "y": 2}}]
+    })
+}
+
+fn run_with(limits: ScriptLimits, code: &str) -> ScriptExecutionResult {
+    let bundle = ScriptBundle::single(code.to_string()).unwrap();
+    JsRuntime::new(limits).execute_tick(&bundle, &snapshot())
+}
+
+fn run(code: &str) -> ScriptExecutionResult {
+    run_with(ScriptLimits::default(), code)
+}
+
+/// Runs each attempt and logs `blocked` if it threw or had no effect, `escaped` otherwise
+fn attempts(checks: &[&str]) -> String {
+    checks.iter()
+        .map(|check| format!("\
+            try {{ console.log(({}) ? 'escaped' : 'blocked'); }}\n\
+            catch (e) {{ console.log('blocked'); }}\n", check))
+        .collect()
+}
+
+fn assert_all_blocked(checks: &[&str]) {
+    let result = run(&attempts(checks));
+    assert_eq!(result.error, None);
+    assert_eq!(result.logs.len(), checks.len());
+    for (check, log) in checks.iter().zip(&result.logs) {
+        assert_eq!(log, "blocked", "not blocked: {}", check);
+    }
+}
+
+fn logged_list(result: &ScriptExecutionResult) -> Vec<String> {
+    assert_eq!(result.error, None);
+    serde_json::from_str(&result.logs[0]).unwrap()
+}
+
+#[test]
+fn test_capability_report_lists_every_global() {
+    let sandbox = Sandbox::new();
+    let report = sandbox.capability_report();
+    assert!(report.strict);
+    assert!(report.frozen_intrinsics);
+    assert!(!report.code_from_strings);
+    assert!(!report.globals.contains(&"eval"));
+
+    let result = run("console.log(JSON.stringify(Object.getOwnPropertyNames(globalThis).sort()));");
+    assert_eq!(logged_list(&result), report.globals);
+}
+
+#[test]
+fn test_capability_report_lists_every_game_member() {
+    let report = Sandbox::new().capability_report();
+    let code = "\
+        const unit = game.getMyUnits()[0];\n\
+        const structure = game.getMyBases()[0];\n\
+        const methods = (o) => Reflect.ownKeys(o).filter((k) => typeof o[k] === 'function');\n\
+        console.log(JSON.stringify(Reflect.ownKeys(game).map(String)));\n\
+        console.log(JSON.stringify(methods(unit)));\n\
+        console.log(JSON.stringify(methods(structure)));\n\
+        console.log(JSON.stringify(Reflect.ownKeys(console).map(String)));";
+
+    for version in OLDEST_API_VERSION..=LATEST_API_VERSION {
+        let bundle = ScriptBundle::single(code.to_string()).unwrap().with_api_version(version).unwrap();
+        let result = JsRuntime::new(ScriptLimits::default()).execute_tick(&bundle, &snapshot());
+        assert_eq!(result.error, None);
+        let lists: Vec<Vec<String>> = result.logs.iter().map(|log| serde_json::from_str(log).unwrap()).collect();
+        for (list, reported) in lists.iter().zip([report.game, report.unit, report.structure, report.console]) {
+            for member in list {
+                assert!(reported.contains(&member.as_str()), "{} is not in the capability report (API v{})", member, version);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_no_code_from_strings() {
+    assert_all_blocked(&[
+        "typeof eval === 'function' && eval('1') === 1",
+        "Function('return this')() !== undefined",
+        "new Function('return 1')() === 1",
+        "(function () {}).constructor('return 1')() === 1",
+        "(async function () {}).constructor('return 1') !== undefined",
+        "(function* () {}).constructor('yield 1') !== undefined",
+        "(async function* () {}).constructor('yield 1') !== undefined",
+        "Reflect.construct(Function, ['return 1'])() === 1",
+    ]);
+
+    // `instanceof` still works against the replaced constructors
+    let result = run("console.log((function () {}) instanceof Function, (() => 1) instanceof Function);");
+    assert_eq!(result.logs, vec!["true true"]);
+}
+
+#[test]
+fn test_constructor_chain_walking_is_blocked() {
+    assert_all_blocked(&[
+        "game.constructor.constructor('return globalThis')() !== undefined",
+        "game.getMyUnits.constructor('return this')() !== undefined",
+        "game.getMyUnits()[0].moveTo.constructor.constructor('return 1')() === 1",
+        "console.log.constructor('return 1')() === 1",
+        "Object.getPrototypeOf(game.getMyUnits).constructor('return 1')() === 1",
+        "[].map.constructor('return 1')() === 1",
+        "(1).constructor.constructor('return 1')() === 1",
+        "Error().constructor.constructor('return 1')() === 1",
+    ]);
+}
+
+#[test]
+fn test_prototype_pollution_is_blocked() {
+    assert_all_blocked(&[
+        "(Object.prototype.polluted = 1, ({}).polluted === 1)",
+        "(Object.prototype.toJSON = function () { return 'x'; }, JSON.stringify({}) === '\"x\"')",
+        "(Array.prototype.push = function () { return 0; }, [].push(1) === 0)",
+        "(Array.prototype.map = null, Array.prototype.map === null)",
+        "(JSON.stringify = function () { return 'x'; }, JSON.stringify({}) === 'x')",
+        "(Math.hypot = function () { return 0; }, Math.hypot(3, 4) === 0)",
+        "(Object.defineProperty(Object.prototype, 'then', { get() { return 1; } }), ({}).then === 1)",
+        "(({}).__proto__.polluted = 1, ({}).polluted === 1)",
+        "(Object.setPrototypeOf(Object.prototype, {}), true)",
+        "(Function.prototype.call = null, Function.prototype.call === null)",
+        "(console.log = null, console.log === null)",
+        "(Promise.prototype.then = null, Promise.prototype.then === null)",
+        "(Object.getPrototypeOf([][Symbol.iterator]()).next = null, [...[1]].length === 0)",
+        "(delete Object.prototype.hasOwnProperty, !('hasOwnProperty' in {}))",
+        "(globalThis.JSON = null, JSON === null)",
+    ]);
+}
+
+#[test]
+fn test_symbol_keyed_leaks_are_blocked() {
+    assert_all_blocked(&[
+        "Object.getOwnPropertySymbols(globalThis).some((s) => s !== Symbol.toStringTag)",
+        "Object.getOwnPropertySymbols(game).length > 0",
+        "(Array.prototype[Symbol.iterator] = function* () {}, [...[1]].length === 0)",
+        "(Object.prototype[Symbol.toPrimitive] = function () { return 1; }, +{} === 1)",
+        "(Symbol.prototype.toString = null, Symbol.prototype.toString === null)",
+        "(Object.defineProperty(Array, Symbol.species, { value: Object }), true)",
+        "(Object.prototype[Symbol.toStringTag] = 'x', String({}) === '[object x]')",
+    ]);
+}
+
+#[test]
+fn test_bot_objects_stay_writable() {
+    // Freezing the intrinsics must not break ordinary bot code
+    let code = "\
+        class BotError extends Error { constructor(m) { super(m); this.name = 'BotError'; } }\n\
+        const state = { count: 0 };\n\
+        state.count += 1;\n\
+        state.toString = () => 'state';\n\
+        const e = new BotError('boom');\n\
+        const list = [3, 1, 2].sort();\n\
+        list.push(4);\n\
+        console.log(state.count, String(state), e.name, e.message, list.join(','));\n\
+        game.sendMessage('bob', { toJSON() { return 'custom'; } });";
+    let result = run(code);
+    assert_eq!(result.error, None);
+    assert_eq!(result.logs, vec!["1 state BotError boom 1,2,3,4"]);
+    assert_eq!(result.sent_messages[0].payload, serde_json::json!("custom"));
+}
+
+#[test]
+fn test_promises_do_not_outlive_the_tick() {
+    let code = "\
+        Promise.resolve().then(() => game.getMyUnits()[0].stop());\n\
+        (async () => { await null; console.log('awaited'); })();";
+    let result = run(code);
+    assert_eq!(result.error, None);
+    assert_eq!(result.logs, vec!["awaited"]);
+    assert_eq!(result.commands.len(), 1);
+
+    // A promise chain that never settles is cut at the time limit
+    let limits = ScriptLimits { timeout: Duration::from_millis(50), ..ScriptLimits::default() };
+    let started = Instant::now();
+    let result = run_with(limits, "\
+        game.getMyUnits()[0].stop();\n\
+        function spin() { return Promise.resolve().then(spin); }\n\
+        spin();");
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(result.commands.len(), 1);
+
+    assert_all_blocked(&["typeof setTimeout !== 'undefined'", "typeof setInterval !== 'undefined'"]);
+}
+
+#[test]
+fn test_host_marshaling_ignores_bot_getters() {
+    // Getters on the bot's own values run once, during the host call, and cannot reach the host
+    let code = "\
+        let calls = 0;\n\
+        const params = { get position() { calls += 1; return { x: 1, y: 1 }; } };\n\
+        game.getMyUnits()[0].moveTo(params.position);\n\
+        console.log(calls);";
+    let result = run(code);
+    assert_eq!(result.error, None);
+    assert_eq!(result.logs, vec!["1"]);
+    assert_eq!(result.commands[0].params, serde_json::json!({"position": {"x": 1, "y": 1}}));
+}
+
+#[test]
+fn test_relaxed_limits_are_reported() {
+    let limits = ScriptLimits { strict: false, freeze_intrinsics: false, ..ScriptLimits::default() };
+    let report = Sandbox::with_limits(limits.clone()).capability_report();
+    assert!(report.code_from_strings);
+    assert!(!report.frozen_intrinsics);
+    assert!(report.globals.contains(&"eval"));
+
+    let result = run_with(limits.clone(), "\
+        const names = Object.getOwnPropertyNames(globalThis);\n\
+        Object.prototype.polluted = 1;\n\
+        console.log(JSON.stringify(names.sort()), eval('({}).polluted'));");
+    assert_eq!(result.error, None);
+    let (names, polluted) = result.logs[0].split_once(' ').unwrap();
+    let names: Vec<String> = serde_json::from_str(names).unwrap();
+    for name in &names {
+        assert!(report.globals.contains(&name.as_str()), "{} is not in the capability report", name);
+    }
+    assert_eq!(polluted, "1");
+
+    // Each tick gets a fresh context
+    let result = run_with(limits, "console.log(({}).polluted);");
+    assert_eq!(result.logs, vec!["undefined"]);
+}
@@ -0,0 +1,71 @@
+// Tests for graceful shutdown of the geekcraft server binary.
+// The server runs as a child process with in-memory stores, on a free port and in its
+// own working directory, and is stopped with SIGTERM.
+#![cfg(unix)]
+
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+/// Ask the system for a port nobody listens on
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+/// Wait for a child process to exit, killing it if it is still running after `timeout`
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> Option<std::process::ExitStatus> {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if let Some(status) = child.try_wait().unwrap() {
+            return Some(status);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    None
+}
+
+#[test]
+fn test_sigterm_saves_the_world_and_exits() {
+    let dir = std::env::temp_dir().join(format!("geekcraft_shutdown_{}", Uuid::new_v4()));
+    let save_dir = dir.join("saves");
+    std::fs::create_dir_all(&dir).unwrap();
+    let port = free_port();
+
+    let mut server = Command::new(env!("CARGO_BIN_EXE_geekcraft"))
+        .current_dir(&dir)
+        .env("GEEKCRAFT_HOST", "127.0.0.1")
+        .env("GEEKCRAFT_PORT", port.to_string())
+        .env("GEEKCRAFT_WORLD_STORE", "INMEMORY")
+        .env("GEEKCRAFT_AUDIT_STORE", "INMEMORY")
+        .env("GEEKCRAFT_SAVE_DIR", &save_dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Failed to start the server");
+
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while TcpStream::connect(("127.0.0.1", port)).is_err() {
+        assert!(Instant::now() < deadline, "Server did not start listening");
+        assert!(server.try_wait().unwrap().is_none(), "Server exited before listening");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    let stopping = Instant::now();
+    let signalled = Command::new("kill").args(["-TERM", &server.id().to_string()]).status().unwrap();
+    assert!(signalled.success());
+    let status = wait_with_timeout(&mut server, Duration::from_secs(10));
+    assert!(status.is_some(), "Server still running 10s after SIGTERM");
+    assert!(status.unwrap().success(), "Server exited with {:?}", status);
+    assert!(stopping.elapsed() < Duration::from_secs(10));
+
+    let saved = save_dir.join("world.json");
+    assert!(saved.exists(), "No world saved to {:?}", saved);
+    let world = geekcraft::game::world::World::load(&saved).unwrap();
+    assert_eq!(world.get_zone_ids().len(), 0);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}